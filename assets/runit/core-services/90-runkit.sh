@@ -0,0 +1,8 @@
+# vim: set ts=4 sw=4 et:
+# Apply the enable/disable changes Runkit scheduled for this boot, before
+# runsvdir starts the enabled services.
+
+if [ -x /usr/libexec/runkitd ]; then
+    msg "Applying scheduled Runkit service changes..."
+    /usr/libexec/runkitd boot-tasks >/dev/null || true
+fi
@@ -0,0 +1,83 @@
+//! What runkitd's D-Bus interface offers, so clients can check before they
+//! rely on a method and degrade gracefully with an older daemon.
+use serde::{Deserialize, Serialize};
+
+/// Version of the D-Bus interface. Adding methods is announced through
+/// [`CAPABILITIES`]; this only changes when an existing method does.
+pub const API_VERSION: u32 = 2;
+
+/// Version of the JSON every method answers with, sent as its
+/// `schema_version`. Responses from before it was introduced have none.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Features of the D-Bus interface, named in [`ServerInfo::capabilities`]:
+///
+/// - `signals`: the `ServiceChanged` and `ServiceAlert` signals
+/// - `wait`: `PerformActionAndWait`, `EnableAndStart` and `StopAndDisable`
+/// - `batch`: `RestartServices`
+/// - `dependencies`: `GetDependencyGraph` and the dependency-ordered calls
+/// - `streaming-logs`: `FollowLogs` and `StopFollowing`
+/// - `log-search`: `FetchLogsFiltered` and `SearchLogs`
+/// - `stage-logs`: `ListStageLogs` and the `runit/` log names
+/// - `alerts`: `SilenceAlerts`
+/// - `runlevels`, `trash`, `profiles`, `boot-schedule`: their methods
+/// - `protected-services`: `ListProtectedServices` and `PerformProtectedAction`
+/// - `template-bundles`: `CreateFromBundle`
+/// - `typed-interface`: the `tech.geektoshi.Runkit1.Controller2` interface
+/// - `jobs`: `StartActionJob`, `StartRestartJob`, `StartProfileRestoreJob`
+///   and `CancelJob`, with the `JobProgress` and `JobCompleted` signals
+/// - `scheduled-checks`: `SetCheckInterval`, `RunCheck`, the
+///   `ServiceHealthChanged` signal and `last_check` in `ListServices`
+/// - `history`: `GetServiceHistory`
+/// - `batch-actions`: `PerformBatchAction` and `StartBatchJob`
+/// - `service-query`: `ListServicesFiltered`
+pub const CAPABILITIES: &[&str] = &[
+    "signals",
+    "wait",
+    "batch",
+    "dependencies",
+    "streaming-logs",
+    "log-search",
+    "stage-logs",
+    "alerts",
+    "runlevels",
+    "trash",
+    "profiles",
+    "boot-schedule",
+    "protected-services",
+    "template-bundles",
+    "typed-interface",
+    "jobs",
+    "scheduled-checks",
+    "history",
+    "batch-actions",
+    "service-query",
+];
+
+/// What `GetServerInfo` reports.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    /// runkitd's package version.
+    pub version: String,
+    pub api_version: u32,
+    pub schema_version: u32,
+    pub capabilities: Vec<String>,
+}
+
+impl ServerInfo {
+    /// This build's interface, with `version` as the daemon's version.
+    pub fn current(version: &str) -> Self {
+        ServerInfo {
+            version: version.to_string(),
+            api_version: API_VERSION,
+            schema_version: SCHEMA_VERSION,
+            capabilities: CAPABILITIES.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities
+            .iter()
+            .any(|offered| offered == capability)
+    }
+}
@@ -0,0 +1,361 @@
+//! Export and import of service definitions as gzip-compressed tarballs.
+use crate::files::BACKUP_DIR;
+use crate::{Result, ServiceError, ServiceManager};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path};
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Runtime state and edit backups, which never belong in a shared definition.
+const EXCLUDED_ENTRIES: &[&str] = &["supervise", "log/supervise", BACKUP_DIR];
+
+/// Setuid, setgid and world-writable bits, refused in imported files.
+const UNSAFE_MODE_BITS: u32 = 0o6002;
+
+impl ServiceManager {
+    /// Write the definition directory of `service` to `writer` as a `.tar.gz` stream.
+    ///
+    /// Runtime state (`supervise` directories) is left out so the archive can be
+    /// imported on another machine.
+    pub fn export_definition<W: Write + ?Sized>(
+        &self,
+        service: &str,
+        writer: &mut W,
+    ) -> Result<u64> {
+        self.validate_service_name(service)?;
+        let definition_path = self.definitions_dir.join(service);
+        if !definition_path.is_dir() {
+            return Err(ServiceError::DefinitionMissing {
+                service: service.to_string(),
+                path: definition_path,
+            });
+        }
+
+        let mut command = Command::new("tar");
+        command.arg("-C").arg(&self.definitions_dir);
+        for excluded in EXCLUDED_ENTRIES {
+            command.arg(format!("--exclude={service}/{excluded}"));
+        }
+        command
+            .arg("-czf")
+            .arg("-")
+            .arg(service)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let mut child = command
+            .spawn()
+            .map_err(|err| ServiceError::from_io("tar", err))?;
+        let mut stdout = child.stdout.take().expect("tar stdout is piped");
+        let written =
+            io::copy(&mut stdout, writer).map_err(|err| ServiceError::from_io("tar", err))?;
+        let output = child
+            .wait_with_output()
+            .map_err(|err| ServiceError::from_io("tar", err))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(ServiceError::InvalidArchive(format!(
+                "tar failed to package {service}: {stderr}"
+            )));
+        }
+
+        Ok(written)
+    }
+
+    /// Import a definition previously produced by [`ServiceManager::export_definition`].
+    ///
+    /// The archive is unpacked into a hidden staging directory first and only moved
+    /// into place once it passes validation. A replaced definition goes to the
+    /// trash when one is configured. Returns the imported service name.
+    pub fn import_definition<R: Read + ?Sized>(
+        &self,
+        reader: &mut R,
+        replace: bool,
+    ) -> Result<String> {
+        let staging = self
+            .definitions_dir
+            .join(format!(".runkit-import-{}", unique_suffix()));
+        fs::create_dir(&staging).map_err(|err| ServiceError::from_io(&staging, err))?;
+
+        let result = self.import_into_staging(reader, &staging, replace);
+        let _ = fs::remove_dir_all(&staging);
+        result
+    }
+
+    fn import_into_staging<R: Read + ?Sized>(
+        &self,
+        reader: &mut R,
+        staging: &Path,
+        replace: bool,
+    ) -> Result<String> {
+        let archive_path = staging.join("definition.tar.gz");
+        {
+            let mut archive = File::create(&archive_path)
+                .map_err(|err| ServiceError::from_io(&archive_path, err))?;
+            io::copy(reader, &mut archive)
+                .map_err(|err| ServiceError::from_io(&archive_path, err))?;
+        }
+
+        let listing = run_tar(Command::new("tar").arg("-tzf").arg(&archive_path))?;
+        let members: Vec<&str> = listing.lines().filter(|line| !line.is_empty()).collect();
+        let service = validate_archive_members(&members)?;
+        self.validate_service_name(&service)?;
+
+        let unpack_dir = staging.join("root");
+        fs::create_dir(&unpack_dir).map_err(|err| ServiceError::from_io(&unpack_dir, err))?;
+        run_tar(
+            Command::new("tar")
+                .arg("-xzf")
+                .arg(&archive_path)
+                .arg("-C")
+                .arg(&unpack_dir)
+                .arg("--no-same-owner")
+                .arg("--no-same-permissions"),
+        )?;
+
+        let unpacked = unpack_dir.join(&service);
+        validate_unpacked_tree(&unpacked, &unpacked)?;
+
+        let run_script = unpacked.join("run");
+        let run_meta = fs::metadata(&run_script)
+            .map_err(|_| ServiceError::InvalidArchive(format!("{service} has no run script")))?;
+        if !run_meta.is_file() || run_meta.permissions().mode() & 0o111 == 0 {
+            return Err(ServiceError::InvalidArchive(format!(
+                "{service}/run is not an executable file"
+            )));
+        }
+
+        let destination = self.definitions_dir.join(&service);
+        if destination.exists() {
+            if !replace {
+                return Err(ServiceError::DefinitionExists(service));
+            }
+            if self.trash_dir().is_some() {
+                self.move_to_trash(&service, false)?;
+            } else {
+                fs::remove_dir_all(&destination)
+                    .map_err(|err| ServiceError::from_io(&destination, err))?;
+            }
+        }
+        fs::rename(&unpacked, &destination)
+            .map_err(|err| ServiceError::from_io(&destination, err))?;
+
+        Ok(service)
+    }
+}
+
+/// Check that every archive member lives under a single top-level service directory
+/// without absolute paths or parent traversal. Returns that directory name.
+fn validate_archive_members(members: &[&str]) -> Result<String> {
+    let mut service: Option<String> = None;
+
+    for member in members {
+        let path = Path::new(member);
+        let mut components = path.components();
+        let top = match components.next() {
+            Some(Component::Normal(name)) => name.to_string_lossy().to_string(),
+            Some(Component::CurDir) => {
+                return Err(ServiceError::InvalidArchive(
+                    "archive members must be rooted at the service directory".to_string(),
+                ));
+            }
+            _ => {
+                return Err(ServiceError::InvalidArchive(format!(
+                    "unsafe archive member: {member}"
+                )));
+            }
+        };
+        if components.any(|c| !matches!(c, Component::Normal(_))) {
+            return Err(ServiceError::InvalidArchive(format!(
+                "unsafe archive member: {member}"
+            )));
+        }
+
+        match &service {
+            Some(existing) if existing != &top => {
+                return Err(ServiceError::InvalidArchive(format!(
+                    "archive contains more than one service ({existing}, {top})"
+                )));
+            }
+            Some(_) => {}
+            None => service = Some(top),
+        }
+    }
+
+    service.ok_or_else(|| ServiceError::InvalidArchive("archive is empty".to_string()))
+}
+
+/// Reject symlinks that point outside the definition, any special files, and
+/// setuid, setgid or world-writable entries tar may have kept.
+fn validate_unpacked_tree(root: &Path, dir: &Path) -> Result<()> {
+    if dir == root {
+        let mode = fs::metadata(root)
+            .map_err(|err| ServiceError::from_io(root, err))?
+            .permissions()
+            .mode();
+        let name = root.file_name().unwrap_or_default().to_string_lossy();
+        reject_unsafe_mode(&name, mode)?;
+    }
+    let read_dir = fs::read_dir(dir).map_err(|err| ServiceError::from_io(dir, err))?;
+    for entry in read_dir {
+        let entry = entry.map_err(|err| ServiceError::from_io(dir, err))?;
+        let path = entry.path();
+        let file_type = entry
+            .file_type()
+            .map_err(|err| ServiceError::from_io(&path, err))?;
+        let relative = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .display()
+            .to_string();
+
+        if file_type.is_symlink() {
+            let target = fs::read_link(&path).map_err(|err| ServiceError::from_io(&path, err))?;
+            let escapes = target
+                .components()
+                .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+            if escapes {
+                return Err(ServiceError::InvalidArchive(format!(
+                    "symlink {relative} points outside the service directory"
+                )));
+            }
+            continue;
+        }
+        if !file_type.is_dir() && !file_type.is_file() {
+            return Err(ServiceError::InvalidArchive(format!(
+                "{relative} is not a regular file"
+            )));
+        }
+        let mode = entry
+            .metadata()
+            .map_err(|err| ServiceError::from_io(&path, err))?
+            .permissions()
+            .mode();
+        reject_unsafe_mode(&relative, mode)?;
+        if file_type.is_dir() {
+            validate_unpacked_tree(root, &path)?;
+        }
+    }
+    Ok(())
+}
+
+fn reject_unsafe_mode(relative: &str, mode: u32) -> Result<()> {
+    if mode & UNSAFE_MODE_BITS != 0 {
+        return Err(ServiceError::InvalidArchive(format!(
+            "{relative} has unsafe permissions {:o}",
+            mode & 0o7777
+        )));
+    }
+    Ok(())
+}
+
+fn run_tar(command: &mut Command) -> Result<String> {
+    let output = command
+        .output()
+        .map_err(|err| ServiceError::from_io("tar", err))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(ServiceError::InvalidArchive(if stderr.is_empty() {
+            format!("tar exited with {}", output.status)
+        } else {
+            stderr
+        }));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+pub(crate) fn unique_suffix() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    format!("{}-{nanos}", std::process::id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_archive_members, validate_unpacked_tree};
+    use crate::ServiceError;
+    use crate::testing::SandboxFixture;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn accepts_single_service_archive() {
+        let members = ["sshd/", "sshd/run", "sshd/log/run", "sshd/conf"];
+        assert_eq!(validate_archive_members(&members).unwrap(), "sshd");
+    }
+
+    #[test]
+    fn rejects_traversal_and_mixed_archives() {
+        assert!(validate_archive_members(&["sshd/../../etc/passwd"]).is_err());
+        assert!(validate_archive_members(&["/etc/sv/sshd/run"]).is_err());
+        assert!(validate_archive_members(&["sshd/run", "cron/run"]).is_err());
+        assert!(validate_archive_members(&[]).is_err());
+    }
+
+    #[test]
+    fn rejects_setuid_and_world_writable_entries() {
+        let sandbox = SandboxFixture::new().unwrap();
+        let definition = sandbox.add_service("sshd", "exec sshd -D\n").unwrap();
+        validate_unpacked_tree(&definition, &definition).unwrap();
+
+        let run = definition.join("run");
+        fs::set_permissions(&run, fs::Permissions::from_mode(0o4755)).unwrap();
+        assert!(matches!(
+            validate_unpacked_tree(&definition, &definition),
+            Err(ServiceError::InvalidArchive(_))
+        ));
+        fs::set_permissions(&run, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let log = definition.join("log");
+        fs::create_dir(&log).unwrap();
+        fs::set_permissions(&log, fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(validate_unpacked_tree(&definition, &definition).is_err());
+    }
+
+    #[test]
+    fn exported_definitions_import_back_through_staging_and_trash() {
+        let sandbox = SandboxFixture::new().unwrap();
+        let definition = sandbox.add_service("sshd", "exec sshd -D\n").unwrap();
+        fs::write(definition.join("conf"), "OPTS=-4\n").unwrap();
+        fs::create_dir(definition.join("supervise")).unwrap();
+        let trash = sandbox.root().join("trash");
+        let manager = sandbox.manager().clone().with_trash_dir(&trash);
+
+        let mut archive = Vec::new();
+        manager.export_definition("sshd", &mut archive).unwrap();
+        assert!(matches!(
+            manager.import_definition(&mut archive.as_slice(), false),
+            Err(ServiceError::DefinitionExists(_))
+        ));
+
+        fs::write(definition.join("conf"), "OPTS=-6\n").unwrap();
+        let imported = manager
+            .import_definition(&mut archive.as_slice(), true)
+            .unwrap();
+        assert_eq!(imported, "sshd");
+        assert_eq!(
+            fs::read_to_string(definition.join("conf")).unwrap(),
+            "OPTS=-4\n"
+        );
+        assert!(!definition.join("supervise").exists());
+        let mode = fs::metadata(definition.join("run"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o7777, 0o755);
+
+        let trashed = manager.list_trash().unwrap();
+        assert_eq!(trashed.len(), 1);
+        assert_eq!(trashed[0].service, "sshd");
+
+        let leftovers: Vec<_> = fs::read_dir(manager.definitions_dir())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(leftovers, ["sshd"]);
+    }
+}
@@ -0,0 +1,946 @@
+//! Backend abstraction over service discovery and control, with extension
+//! traits for the runit-specific features runkitd also drives.
+use crate::boot_report::current_boot;
+use crate::wait::POLL_INTERVAL;
+use crate::{
+    BootReport, BootSchedule, BootTaskReport, CheckResult, Confinement, DeleteMode, DeletedService,
+    DependencyGraph, EffectiveEnvironment, EnvDir, Inventory, LogFilter, LogFollower, LogMatch,
+    LogPattern, Maintenance, ProcessNode, Profile, ProfileChanges, ProfileRestore, RestartPolicy,
+    Result, RunScriptInfo, ScheduledBootChange, ServiceDescription, ServiceError, ServiceFile,
+    ServiceFileWrite, ServiceHealth, ServiceHistory, ServiceInfo, ServiceLogEntry, ServiceLogs,
+    ServiceManager, ServiceMetrics, ServiceQuery, ServiceRuntimeState, ServiceTemplate,
+    StateChange, SvlogdConfig, TargetState, TemplateBundle, TrashEntry, WaitOutcome, is_failed,
+};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Discovery and control: what runkitd needs from any service supervisor.
+///
+/// [`ServiceManager`] implements this against the local filesystem and `sv`;
+/// other implementations can stand in for tests or non-local supervisors.
+/// The runit-specific features are in the extension traits that
+/// [`RunitBackend`] gathers.
+pub trait ServiceBackend: Send + Sync {
+    fn list_services(&self) -> Result<Vec<ServiceInfo>>;
+
+    fn list_services_matching(&self, query: &ServiceQuery) -> Result<Vec<ServiceInfo>> {
+        self.list_services().map(|services| query.apply(services))
+    }
+
+    fn status(&self, service: &str) -> Result<ServiceRuntimeState>;
+
+    fn service_description(&self, service: &str) -> Result<Option<String>>;
+
+    /// The description together with where it came from.
+    fn describe_service(&self, service: &str) -> Result<Option<ServiceDescription>>;
+
+    /// Dependencies declared by every definition.
+    fn dependency_graph(&self) -> Result<DependencyGraph>;
+
+    /// Why there is no live status or control, when nothing supervises the
+    /// services.
+    fn reduced_mode(&self) -> Option<Confinement>;
+
+    fn restart_policy(&self, service: &str) -> Result<RestartPolicy>;
+
+    fn set_restart_policy(&self, service: &str, policy: RestartPolicy) -> Result<()>;
+
+    /// Send an `sv` verb (`up`, `down`, `once`, ...) and return any output.
+    fn control(&self, service: &str, command: &str) -> Result<String>;
+
+    /// Block until the service reaches `target` or `timeout` passes.
+    fn wait_for_state(
+        &self,
+        service: &str,
+        target: TargetState,
+        timeout: Duration,
+    ) -> Result<WaitOutcome>;
+
+    fn enable(&self, service: &str) -> Result<()>;
+
+    fn disable(&self, service: &str) -> Result<()>;
+
+    fn set_down_file(&self, service: &str) -> Result<()>;
+
+    fn clear_down_file(&self, service: &str) -> Result<()>;
+
+    /// Start a service, honouring its restart policy.
+    fn start(&self, service: &str) -> Result<String> {
+        // `sv up` would tell runsv to restart the service when it exits.
+        if self.restart_policy(service)? == RestartPolicy::Never {
+            self.control(service, "once")
+        } else {
+            self.control(service, "up")
+        }
+    }
+
+    /// Restart a service, then drop back to `once` if it should not be restarted.
+    fn restart(&self, service: &str) -> Result<String> {
+        let output = self.control(service, "restart")?;
+        if self.restart_policy(service)? == RestartPolicy::Never {
+            self.control(service, "once")?;
+        }
+        Ok(output)
+    }
+
+    /// Start, stop or restart a service as [`ServiceBackend::start`] and
+    /// [`ServiceBackend::restart`] do, then wait up to `timeout` for it to get
+    /// there. Returns `sv`'s output and where the wait ended.
+    fn change_and_wait(
+        &self,
+        service: &str,
+        change: StateChange,
+        timeout: Duration,
+    ) -> Result<(String, WaitOutcome)> {
+        let (output, target) = match change {
+            StateChange::Start => (self.start(service)?, TargetState::Up { replacing: None }),
+            StateChange::Stop => (self.control(service, "down")?, TargetState::Down),
+            StateChange::Restart => {
+                let replacing = self.status(service)?.pid();
+                (self.restart(service)?, TargetState::Up { replacing })
+            }
+        };
+        let outcome = self.wait_for_state(service, target, timeout)?;
+        Ok((output, outcome))
+    }
+
+    /// Enable `service` and start it now, waiting up to `timeout` for it to be
+    /// running. runsvdir only picks up a new link on its next scan, so the start
+    /// is retried until the service's runsv answers.
+    ///
+    /// If the service does not come up it is stopped again and, when this call
+    /// enabled it, disabled; the outcome then has `reached` unset.
+    fn enable_and_start(&self, service: &str, timeout: Duration) -> Result<WaitOutcome> {
+        let started = Instant::now();
+        let newly_enabled = match self.enable(service) {
+            Ok(()) => true,
+            Err(ServiceError::AlreadyEnabled(_)) => false,
+            Err(err) => return Err(err),
+        };
+        let roll_back = || {
+            let _ = self.control(service, "down");
+            if newly_enabled {
+                let _ = self.disable(service);
+            }
+        };
+        loop {
+            match self.start(service) {
+                Ok(_) => break,
+                Err(ServiceError::SvCommand { .. }) if started.elapsed() < timeout => {
+                    thread::sleep(POLL_INTERVAL);
+                }
+                Err(err) => {
+                    roll_back();
+                    return Err(err);
+                }
+            }
+        }
+        let remaining = timeout.saturating_sub(started.elapsed());
+        match self.wait_for_state(service, TargetState::Up { replacing: None }, remaining) {
+            Ok(outcome) => {
+                if !outcome.reached {
+                    roll_back();
+                }
+                Ok(outcome)
+            }
+            Err(err) => {
+                roll_back();
+                Err(err)
+            }
+        }
+    }
+
+    /// Stop `service`, wait up to `timeout` for it to be down, then disable it.
+    ///
+    /// If it does not go down, or cannot be disabled, a service that was
+    /// running is started again and stays enabled.
+    fn stop_and_disable(&self, service: &str, timeout: Duration) -> Result<WaitOutcome> {
+        let was_running = self.status(service)?.pid().is_some();
+        let roll_back = || {
+            if was_running {
+                let _ = self.start(service);
+            }
+        };
+        let (_, outcome) = self.change_and_wait(service, StateChange::Stop, timeout)?;
+        if !outcome.reached {
+            roll_back();
+            return Ok(outcome);
+        }
+        if let Err(err) = self.disable(service) {
+            roll_back();
+            return Err(err);
+        }
+        Ok(outcome)
+    }
+
+    /// Start `service` after the services it depends on, waiting up to
+    /// `timeout` for each to be running. Services that already run are left
+    /// alone. Stops at the first one that does not come up; the last outcome
+    /// then has `reached` unset.
+    fn start_with_dependencies(
+        &self,
+        service: &str,
+        timeout: Duration,
+    ) -> Result<Vec<(String, WaitOutcome)>> {
+        let order = self.dependency_graph()?.start_order(service)?;
+        self.change_in_order(&order, StateChange::Start, timeout)
+    }
+
+    /// Stop the services that depend on `service`, then `service` itself,
+    /// waiting up to `timeout` for each to be down.
+    fn stop_with_dependents(
+        &self,
+        service: &str,
+        timeout: Duration,
+    ) -> Result<Vec<(String, WaitOutcome)>> {
+        let order = self.dependency_graph()?.stop_order(service)?;
+        self.change_in_order(&order, StateChange::Stop, timeout)
+    }
+
+    /// Apply `change` to each of `services` in turn, skipping those already in
+    /// the target state, until one does not get there.
+    fn change_in_order(
+        &self,
+        services: &[String],
+        change: StateChange,
+        timeout: Duration,
+    ) -> Result<Vec<(String, WaitOutcome)>> {
+        let mut outcomes = Vec::new();
+        for name in services {
+            let running = self.status(name)?.pid().is_some();
+            if running == (change == StateChange::Start) {
+                continue;
+            }
+            let (_, outcome) = self.change_and_wait(name, change, timeout)?;
+            let reached = outcome.reached;
+            outcomes.push((name.clone(), outcome));
+            if !reached {
+                break;
+            }
+        }
+        Ok(outcomes)
+    }
+
+    /// Persist a restart policy and align a running supervisor with it.
+    fn apply_restart_policy(&self, service: &str, policy: RestartPolicy) -> Result<()> {
+        self.set_restart_policy(service, policy)?;
+        if matches!(
+            self.status(service),
+            Ok(ServiceRuntimeState::Running { .. })
+        ) {
+            let command = match policy {
+                RestartPolicy::Never => "once",
+                RestartPolicy::Always => "up",
+            };
+            self.control(service, command)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reading the services' logs.
+pub trait LogAccess: ServiceBackend {
+    fn tail_logs_filtered(
+        &self,
+        service: &str,
+        limit: usize,
+        filter: &LogFilter,
+    ) -> Result<Vec<ServiceLogEntry>>;
+
+    fn tail_logs(&self, service: &str, limit: usize) -> Result<Vec<ServiceLogEntry>> {
+        self.tail_logs_filtered(service, limit, &LogFilter::default())
+    }
+
+    fn follow_logs(&self, service: &str) -> Result<LogFollower>;
+
+    /// Names of runit's stage logs, which the log calls accept like services.
+    fn stage_logs(&self) -> Result<Vec<String>>;
+
+    /// The last `limit` log entries of every service [`is_failed`] picks out.
+    fn failed_service_logs(&self, limit: usize) -> Result<Vec<ServiceLogs>> {
+        self.list_services()?
+            .into_iter()
+            .filter(is_failed)
+            .map(|info| {
+                Ok(ServiceLogs {
+                    entries: self.tail_logs(&info.name, limit)?,
+                    service: info.name,
+                })
+            })
+            .collect()
+    }
+
+    fn search_logs(
+        &self,
+        pattern: &LogPattern,
+        services: &[String],
+        limit: usize,
+    ) -> Result<Vec<LogMatch>>;
+}
+
+/// The definitions under the definitions directory: their files, env dirs
+/// and svlogd configs, export and import, and the trash.
+pub trait DefinitionStore: ServiceBackend {
+    /// The package that installed the definition, with its version.
+    fn service_package(&self, service: &str) -> Result<Option<String>>;
+
+    fn run_script_info(&self, service: &str) -> Result<RunScriptInfo>;
+
+    /// Variables the run script sets, compared with the running process.
+    fn effective_environment(&self, service: &str) -> Result<EffectiveEnvironment>;
+
+    /// The service's env dir and the variables in it.
+    fn env_dir(&self, service: &str) -> Result<EnvDir>;
+
+    fn set_env_variable(&self, service: &str, name: &str, value: &str) -> Result<EnvDir>;
+
+    fn unset_env_variable(&self, service: &str, name: &str) -> Result<EnvDir>;
+
+    fn log_config(&self, service: &str) -> Result<SvlogdConfig>;
+
+    fn set_log_config(&self, service: &str, config: &SvlogdConfig) -> Result<()>;
+
+    fn export_definition(&self, service: &str, writer: &mut dyn Write) -> Result<u64>;
+
+    fn import_definition(&self, reader: &mut dyn Read, replace: bool) -> Result<String>;
+
+    fn create_service(&self, template: &ServiceTemplate, enable: bool) -> Result<PathBuf>;
+
+    /// Create `service` from a template bundle with `values` for its variables.
+    fn create_from_bundle(
+        &self,
+        bundle: &TemplateBundle,
+        service: &str,
+        values: &[(String, String)],
+        enable: bool,
+    ) -> Result<PathBuf>;
+
+    /// Copy a definition under a new name, replacing each `(from, to)` in its scripts.
+    fn instantiate_service(
+        &self,
+        source: &str,
+        name: &str,
+        substitutions: &[(String, String)],
+        enable: bool,
+    ) -> Result<PathBuf>;
+
+    fn read_service_file(&self, service: &str, file: &str) -> Result<ServiceFile>;
+
+    /// Replace a file of the definition, backing up the old one. Shell files
+    /// with a syntax error are refused unless `force` is set.
+    fn write_service_file(
+        &self,
+        service: &str,
+        file: &str,
+        contents: &str,
+        force: bool,
+    ) -> Result<ServiceFileWrite>;
+
+    fn trash_definition(&self, service: &str) -> Result<TrashEntry>;
+
+    /// Stop the service, unlink it everywhere and trash or remove its definition.
+    fn delete_service(&self, service: &str, mode: DeleteMode) -> Result<DeletedService>;
+
+    fn list_trash(&self) -> Result<Vec<TrashEntry>>;
+
+    fn restore_from_trash(&self, id: &str) -> Result<String>;
+
+    fn purge_trash(&self, everything: bool) -> Result<Vec<TrashEntry>>;
+}
+
+/// Runlevel directories and the profiles of what is enabled in them.
+pub trait RunlevelControl: ServiceBackend {
+    fn keep_only_in_runlevel(&self, service: &str, runlevel: Option<&str>) -> Result<Vec<PathBuf>>;
+
+    /// Runlevel directories by name, sorted.
+    fn runlevels(&self) -> Vec<(String, PathBuf)>;
+
+    fn current_runlevel(&self) -> String;
+
+    /// Runlevels each service is linked into.
+    fn enabled_runlevels(&self) -> Result<BTreeMap<String, Vec<String>>>;
+
+    fn switch_runlevel(&self, runlevel: &str) -> Result<()>;
+
+    fn set_enabled_in_runlevel(&self, service: &str, runlevel: &str, enabled: bool) -> Result<()>;
+
+    fn profile(&self) -> Result<Profile>;
+
+    /// What restoring `profile` would change.
+    fn profile_changes(&self, profile: &Profile) -> Result<ProfileChanges>;
+
+    /// Apply `profile`, telling `progress` how many of the changes are made
+    /// before each one; returning `false` from it stops the restore.
+    fn restore_profile(
+        &self,
+        profile: &Profile,
+        progress: &mut dyn FnMut(usize, usize) -> bool,
+    ) -> Result<ProfileRestore>;
+}
+
+/// Changes that take effect later: maintenance, changes scheduled for the
+/// next boot and restarts waiting for logout.
+pub trait DeferredChanges: ServiceBackend {
+    fn maintenance(&self, service: &str) -> Result<Option<Maintenance>>;
+
+    /// Stop the service and keep it down, without alerts, until maintenance ends.
+    fn begin_maintenance(&self, service: &str, reason: Option<&str>) -> Result<Maintenance>;
+
+    fn end_maintenance(&self, service: &str) -> Result<Option<Maintenance>>;
+
+    /// Schedule, or with `None` cancel, an enable or disable relative to the next boot.
+    fn schedule_boot_change(
+        &self,
+        service: &str,
+        schedule: Option<BootSchedule>,
+    ) -> Result<Option<ScheduledBootChange>>;
+
+    fn boot_schedule(&self) -> Result<Vec<ScheduledBootChange>>;
+
+    fn run_boot_tasks(&self) -> Result<BootTaskReport>;
+
+    /// Restart the service once the user has logged out, or with `false`
+    /// cancel that.
+    fn schedule_restart_at_logout(&self, service: &str, scheduled: bool) -> Result<()>;
+
+    fn logout_restarts(&self) -> Result<Vec<String>>;
+}
+
+/// Health, resource usage, history and `./check` runs of the services.
+pub trait ServiceMonitor: ServiceBackend {
+    fn check_health(&self) -> Result<ServiceHealth>;
+
+    fn inventory(&self) -> Result<Inventory>;
+
+    /// Resource usage of the service's process tree, `None` when it is not running.
+    fn service_metrics(&self, service: &str) -> Result<Option<ServiceMetrics>>;
+
+    /// Resident memory of every running service, keyed by name. Unlike
+    /// `service_metrics` it does not wait to measure CPU time.
+    fn memory_usage(&self) -> Result<BTreeMap<String, u64>>;
+
+    /// The service's supervised process and its descendants, `None` when it is not running.
+    fn process_tree(&self, service: &str) -> Result<Option<ProcessNode>>;
+
+    /// Starts, crashes and stops recorded for the service within `window`.
+    fn service_history(&self, service: &str, window: Duration) -> Result<ServiceHistory>;
+
+    /// How long after this boot each service came up, or `None` when `/proc`
+    /// does not say when the system booted.
+    fn boot_report(&self) -> Result<Option<BootReport>> {
+        let Some((boot_id, booted_at)) = current_boot() else {
+            return Ok(None);
+        };
+        let services = self.list_services()?;
+        Ok(Some(BootReport::from_services(
+            boot_id,
+            booted_at,
+            &services,
+            SystemTime::now(),
+        )))
+    }
+
+    /// Run the service's `./check` every `interval` while it runs, or with
+    /// `None` stop.
+    fn set_check_interval(&self, service: &str, interval: Option<Duration>) -> Result<()>;
+
+    /// Run the service's `./check` now and keep the outcome as its last check.
+    fn run_check(&self, service: &str) -> Result<CheckResult>;
+}
+
+/// Everything runkitd uses, which [`ServiceManager`] implements. Leaving out
+/// one of the extension traits is a compile error rather than a failure at
+/// runtime.
+pub trait RunitBackend:
+    ServiceBackend + LogAccess + DefinitionStore + RunlevelControl + DeferredChanges + ServiceMonitor
+{
+}
+
+impl<T> RunitBackend for T where
+    T: ServiceBackend
+        + LogAccess
+        + DefinitionStore
+        + RunlevelControl
+        + DeferredChanges
+        + ServiceMonitor
+{
+}
+
+impl ServiceBackend for ServiceManager {
+    fn list_services(&self) -> Result<Vec<ServiceInfo>> {
+        ServiceManager::list_services(self)
+    }
+
+    fn status(&self, service: &str) -> Result<ServiceRuntimeState> {
+        ServiceManager::status(self, service)
+    }
+
+    fn service_description(&self, service: &str) -> Result<Option<String>> {
+        ServiceManager::service_description(self, service)
+    }
+
+    fn describe_service(&self, service: &str) -> Result<Option<ServiceDescription>> {
+        ServiceManager::describe_service(self, service)
+    }
+
+    fn dependency_graph(&self) -> Result<DependencyGraph> {
+        ServiceManager::dependency_graph(self)
+    }
+
+    fn reduced_mode(&self) -> Option<Confinement> {
+        ServiceManager::reduced_mode(self).cloned()
+    }
+
+    fn restart_policy(&self, service: &str) -> Result<RestartPolicy> {
+        ServiceManager::restart_policy(self, service)
+    }
+
+    fn set_restart_policy(&self, service: &str, policy: RestartPolicy) -> Result<()> {
+        ServiceManager::set_restart_policy(self, service, policy)
+    }
+
+    fn control(&self, service: &str, command: &str) -> Result<String> {
+        ServiceManager::control(self, service, command)
+    }
+
+    fn wait_for_state(
+        &self,
+        service: &str,
+        target: TargetState,
+        timeout: Duration,
+    ) -> Result<WaitOutcome> {
+        ServiceManager::wait_for_state(self, service, target, timeout)
+    }
+
+    fn enable(&self, service: &str) -> Result<()> {
+        ServiceManager::enable(self, service)
+    }
+
+    fn disable(&self, service: &str) -> Result<()> {
+        ServiceManager::disable(self, service)
+    }
+
+    fn set_down_file(&self, service: &str) -> Result<()> {
+        ServiceManager::set_down_file(self, service)
+    }
+
+    fn clear_down_file(&self, service: &str) -> Result<()> {
+        ServiceManager::clear_down_file(self, service)
+    }
+}
+
+impl LogAccess for ServiceManager {
+    fn tail_logs_filtered(
+        &self,
+        service: &str,
+        limit: usize,
+        filter: &LogFilter,
+    ) -> Result<Vec<ServiceLogEntry>> {
+        ServiceManager::tail_logs_filtered(self, service, limit, filter)
+    }
+
+    fn follow_logs(&self, service: &str) -> Result<LogFollower> {
+        ServiceManager::follow_logs(self, service)
+    }
+
+    fn stage_logs(&self) -> Result<Vec<String>> {
+        ServiceManager::stage_logs(self)
+    }
+
+    fn search_logs(
+        &self,
+        pattern: &LogPattern,
+        services: &[String],
+        limit: usize,
+    ) -> Result<Vec<LogMatch>> {
+        ServiceManager::search_logs(self, pattern, services, limit)
+    }
+}
+
+impl DefinitionStore for ServiceManager {
+    fn service_package(&self, service: &str) -> Result<Option<String>> {
+        ServiceManager::service_package(self, service)
+    }
+
+    fn run_script_info(&self, service: &str) -> Result<RunScriptInfo> {
+        ServiceManager::run_script_info(self, service)
+    }
+
+    fn effective_environment(&self, service: &str) -> Result<EffectiveEnvironment> {
+        ServiceManager::effective_environment(self, service)
+    }
+
+    fn env_dir(&self, service: &str) -> Result<EnvDir> {
+        ServiceManager::env_dir(self, service)
+    }
+
+    fn set_env_variable(&self, service: &str, name: &str, value: &str) -> Result<EnvDir> {
+        ServiceManager::set_env_variable(self, service, name, value)
+    }
+
+    fn unset_env_variable(&self, service: &str, name: &str) -> Result<EnvDir> {
+        ServiceManager::unset_env_variable(self, service, name)
+    }
+
+    fn log_config(&self, service: &str) -> Result<SvlogdConfig> {
+        ServiceManager::log_config(self, service)
+    }
+
+    fn set_log_config(&self, service: &str, config: &SvlogdConfig) -> Result<()> {
+        ServiceManager::set_log_config(self, service, config)
+    }
+
+    fn export_definition(&self, service: &str, writer: &mut dyn Write) -> Result<u64> {
+        ServiceManager::export_definition(self, service, writer)
+    }
+
+    fn import_definition(&self, reader: &mut dyn Read, replace: bool) -> Result<String> {
+        ServiceManager::import_definition(self, reader, replace)
+    }
+
+    fn create_service(&self, template: &ServiceTemplate, enable: bool) -> Result<PathBuf> {
+        ServiceManager::create_service(self, template, enable)
+    }
+
+    fn create_from_bundle(
+        &self,
+        bundle: &TemplateBundle,
+        service: &str,
+        values: &[(String, String)],
+        enable: bool,
+    ) -> Result<PathBuf> {
+        ServiceManager::create_from_bundle(self, bundle, service, values, enable)
+    }
+
+    fn instantiate_service(
+        &self,
+        source: &str,
+        name: &str,
+        substitutions: &[(String, String)],
+        enable: bool,
+    ) -> Result<PathBuf> {
+        ServiceManager::instantiate_service(self, source, name, substitutions, enable)
+    }
+
+    fn read_service_file(&self, service: &str, file: &str) -> Result<ServiceFile> {
+        ServiceManager::read_service_file(self, service, file)
+    }
+
+    fn write_service_file(
+        &self,
+        service: &str,
+        file: &str,
+        contents: &str,
+        force: bool,
+    ) -> Result<ServiceFileWrite> {
+        ServiceManager::write_service_file(self, service, file, contents, force)
+    }
+
+    fn trash_definition(&self, service: &str) -> Result<TrashEntry> {
+        ServiceManager::trash_definition(self, service)
+    }
+
+    fn delete_service(&self, service: &str, mode: DeleteMode) -> Result<DeletedService> {
+        ServiceManager::delete_service(self, service, mode)
+    }
+
+    fn list_trash(&self) -> Result<Vec<TrashEntry>> {
+        ServiceManager::list_trash(self)
+    }
+
+    fn restore_from_trash(&self, id: &str) -> Result<String> {
+        ServiceManager::restore_from_trash(self, id)
+    }
+
+    fn purge_trash(&self, everything: bool) -> Result<Vec<TrashEntry>> {
+        ServiceManager::purge_trash(self, everything)
+    }
+}
+
+impl RunlevelControl for ServiceManager {
+    fn keep_only_in_runlevel(&self, service: &str, runlevel: Option<&str>) -> Result<Vec<PathBuf>> {
+        ServiceManager::keep_only_in_runlevel(self, service, runlevel)
+    }
+
+    fn runlevels(&self) -> Vec<(String, PathBuf)> {
+        ServiceManager::runlevels(self)
+    }
+
+    fn current_runlevel(&self) -> String {
+        ServiceManager::current_runlevel(self)
+    }
+
+    fn enabled_runlevels(&self) -> Result<BTreeMap<String, Vec<String>>> {
+        ServiceManager::enabled_runlevels(self)
+    }
+
+    fn switch_runlevel(&self, runlevel: &str) -> Result<()> {
+        ServiceManager::switch_runlevel(self, runlevel)
+    }
+
+    fn set_enabled_in_runlevel(&self, service: &str, runlevel: &str, enabled: bool) -> Result<()> {
+        ServiceManager::set_enabled_in_runlevel(self, service, runlevel, enabled)
+    }
+
+    fn profile(&self) -> Result<Profile> {
+        ServiceManager::profile(self)
+    }
+
+    fn profile_changes(&self, profile: &Profile) -> Result<ProfileChanges> {
+        ServiceManager::profile_changes(self, profile)
+    }
+
+    fn restore_profile(
+        &self,
+        profile: &Profile,
+        progress: &mut dyn FnMut(usize, usize) -> bool,
+    ) -> Result<ProfileRestore> {
+        ServiceManager::restore_profile_with_progress(self, profile, progress)
+    }
+}
+
+impl DeferredChanges for ServiceManager {
+    fn maintenance(&self, service: &str) -> Result<Option<Maintenance>> {
+        ServiceManager::maintenance(self, service)
+    }
+
+    fn begin_maintenance(&self, service: &str, reason: Option<&str>) -> Result<Maintenance> {
+        ServiceManager::begin_maintenance(self, service, reason)
+    }
+
+    fn end_maintenance(&self, service: &str) -> Result<Option<Maintenance>> {
+        ServiceManager::end_maintenance(self, service)
+    }
+
+    fn schedule_boot_change(
+        &self,
+        service: &str,
+        schedule: Option<BootSchedule>,
+    ) -> Result<Option<ScheduledBootChange>> {
+        ServiceManager::schedule_boot_change(self, service, schedule)
+    }
+
+    fn boot_schedule(&self) -> Result<Vec<ScheduledBootChange>> {
+        ServiceManager::boot_schedule(self)
+    }
+
+    fn run_boot_tasks(&self) -> Result<BootTaskReport> {
+        ServiceManager::run_boot_tasks(self)
+    }
+
+    fn schedule_restart_at_logout(&self, service: &str, scheduled: bool) -> Result<()> {
+        ServiceManager::schedule_restart_at_logout(self, service, scheduled)
+    }
+
+    fn logout_restarts(&self) -> Result<Vec<String>> {
+        ServiceManager::logout_restarts(self)
+    }
+}
+
+impl ServiceMonitor for ServiceManager {
+    fn check_health(&self) -> Result<ServiceHealth> {
+        ServiceManager::check_health(self)
+    }
+
+    fn inventory(&self) -> Result<Inventory> {
+        ServiceManager::inventory(self)
+    }
+
+    fn service_metrics(&self, service: &str) -> Result<Option<ServiceMetrics>> {
+        ServiceManager::service_metrics(self, service)
+    }
+
+    fn memory_usage(&self) -> Result<BTreeMap<String, u64>> {
+        ServiceManager::memory_usage(self)
+    }
+
+    fn process_tree(&self, service: &str) -> Result<Option<ProcessNode>> {
+        ServiceManager::process_tree(self, service)
+    }
+
+    fn service_history(&self, service: &str, window: Duration) -> Result<ServiceHistory> {
+        ServiceManager::service_history(self, service, window)
+    }
+
+    fn set_check_interval(&self, service: &str, interval: Option<Duration>) -> Result<()> {
+        ServiceManager::set_check_interval(self, service, interval)
+    }
+
+    fn run_check(&self, service: &str) -> Result<CheckResult> {
+        ServiceManager::run_check(self, service)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ServiceBackend;
+    use crate::{
+        Confinement, DependencyGraph, RestartPolicy, Result, ServiceDescription, ServiceInfo,
+        ServiceRuntimeState, TargetState, WaitOutcome,
+    };
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    struct MockBackend {
+        policy: Mutex<RestartPolicy>,
+        running: bool,
+        commands: Mutex<Vec<String>>,
+    }
+
+    impl MockBackend {
+        fn new(policy: RestartPolicy, running: bool) -> Self {
+            MockBackend {
+                policy: Mutex::new(policy),
+                running,
+                commands: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn commands(&self) -> Vec<String> {
+            self.commands.lock().unwrap().clone()
+        }
+    }
+
+    impl ServiceBackend for MockBackend {
+        fn list_services(&self) -> Result<Vec<ServiceInfo>> {
+            Ok(Vec::new())
+        }
+
+        fn status(&self, _service: &str) -> Result<ServiceRuntimeState> {
+            Ok(if self.running {
+                ServiceRuntimeState::Running {
+                    pid: 1,
+                    uptime: Duration::from_secs(1),
+                }
+            } else {
+                ServiceRuntimeState::Down {
+                    since: Duration::from_secs(1),
+                    normally_up: true,
+                }
+            })
+        }
+
+        fn service_description(&self, _service: &str) -> Result<Option<String>> {
+            Ok(None)
+        }
+
+        fn describe_service(&self, _service: &str) -> Result<Option<ServiceDescription>> {
+            Ok(None)
+        }
+
+        fn dependency_graph(&self) -> Result<DependencyGraph> {
+            Ok(DependencyGraph::default())
+        }
+
+        fn reduced_mode(&self) -> Option<Confinement> {
+            None
+        }
+
+        fn restart_policy(&self, _service: &str) -> Result<RestartPolicy> {
+            Ok(*self.policy.lock().unwrap())
+        }
+
+        fn set_restart_policy(&self, _service: &str, policy: RestartPolicy) -> Result<()> {
+            *self.policy.lock().unwrap() = policy;
+            Ok(())
+        }
+
+        fn control(&self, service: &str, command: &str) -> Result<String> {
+            self.commands
+                .lock()
+                .unwrap()
+                .push(format!("{command} {service}"));
+            Ok(String::new())
+        }
+
+        fn wait_for_state(
+            &self,
+            service: &str,
+            target: TargetState,
+            _timeout: Duration,
+        ) -> Result<WaitOutcome> {
+            Ok(WaitOutcome {
+                reached: matches!(target, TargetState::Up { .. }) == self.running,
+                state: self.status(service)?,
+                waited: Duration::ZERO,
+            })
+        }
+
+        fn enable(&self, service: &str) -> Result<()> {
+            self.commands
+                .lock()
+                .unwrap()
+                .push(format!("enable {service}"));
+            Ok(())
+        }
+
+        fn disable(&self, service: &str) -> Result<()> {
+            self.commands
+                .lock()
+                .unwrap()
+                .push(format!("disable {service}"));
+            Ok(())
+        }
+
+        fn set_down_file(&self, _service: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn clear_down_file(&self, _service: &str) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn start_uses_once_when_restarts_are_disabled() {
+        let backend = MockBackend::new(RestartPolicy::Never, false);
+        backend.start("sshd").unwrap();
+        backend.restart("sshd").unwrap();
+        assert_eq!(
+            backend.commands(),
+            ["once sshd", "restart sshd", "once sshd"]
+        );
+
+        let backend = MockBackend::new(RestartPolicy::Always, false);
+        backend.start("sshd").unwrap();
+        assert_eq!(backend.commands(), ["up sshd"]);
+    }
+
+    #[test]
+    fn policy_change_only_signals_running_services() {
+        let backend = MockBackend::new(RestartPolicy::Always, true);
+        backend
+            .apply_restart_policy("sshd", RestartPolicy::Never)
+            .unwrap();
+        assert_eq!(backend.commands(), ["once sshd"]);
+
+        let backend = MockBackend::new(RestartPolicy::Never, false);
+        backend
+            .apply_restart_policy("sshd", RestartPolicy::Always)
+            .unwrap();
+        assert!(backend.commands().is_empty());
+    }
+
+    #[test]
+    fn combined_changes_roll_back_when_not_reached() {
+        let timeout = Duration::from_secs(1);
+        let backend = MockBackend::new(RestartPolicy::Always, true);
+        assert!(backend.enable_and_start("sshd", timeout).unwrap().reached);
+        assert_eq!(backend.commands(), ["enable sshd", "up sshd"]);
+
+        // The mock never changes state, so these waits fail and are undone.
+        let backend = MockBackend::new(RestartPolicy::Always, false);
+        assert!(!backend.enable_and_start("sshd", timeout).unwrap().reached);
+        assert_eq!(
+            backend.commands(),
+            ["enable sshd", "up sshd", "down sshd", "disable sshd"]
+        );
+
+        let backend = MockBackend::new(RestartPolicy::Always, true);
+        assert!(!backend.stop_and_disable("sshd", timeout).unwrap().reached);
+        assert_eq!(backend.commands(), ["down sshd", "up sshd"]);
+    }
+}
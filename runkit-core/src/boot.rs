@@ -0,0 +1,299 @@
+//! Enable and disable changes that take effect relative to the next boot.
+//!
+//! Each scheduled change is a marker file named after the service. Markers are
+//! applied by [`ServiceManager::run_boot_tasks`], which `runkitd boot-tasks`
+//! runs early in boot, before runsvdir looks at the enabled directory.
+use crate::{Result, ServiceError, ServiceManager};
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+pub const DEFAULT_BOOT_SCHEDULE_DIR: &str = "/var/lib/runkit/boot";
+
+/// A change waiting for a boot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootSchedule {
+    /// Enabled for the next boot, then disabled again at the boot after.
+    EnableNextBootOnly,
+    /// Left enabled for the next boot, then disabled at the boot after.
+    DisableAfterNextBoot,
+    /// Disabled at the next boot, before runsvdir would start it.
+    DisableAtNextBoot,
+}
+
+impl BootSchedule {
+    pub const ALL: [BootSchedule; 3] = [
+        BootSchedule::EnableNextBootOnly,
+        BootSchedule::DisableAfterNextBoot,
+        BootSchedule::DisableAtNextBoot,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BootSchedule::EnableNextBootOnly => "enable-next-boot-only",
+            BootSchedule::DisableAfterNextBoot => "disable-after-next-boot",
+            BootSchedule::DisableAtNextBoot => "disable-at-next-boot",
+        }
+    }
+}
+
+impl fmt::Display for BootSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for BootSchedule {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        BootSchedule::ALL
+            .into_iter()
+            .find(|schedule| schedule.as_str().eq_ignore_ascii_case(value))
+            .ok_or_else(|| {
+                format!(
+                    "unknown boot schedule {value:?} (expected enable-next-boot-only, \
+                     disable-after-next-boot or disable-at-next-boot)"
+                )
+            })
+    }
+}
+
+/// A marker written by [`ServiceManager::schedule_boot_change`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledBootChange {
+    pub service: String,
+    pub schedule: BootSchedule,
+    /// Enabled directory the change applies to, resolved when it was scheduled
+    /// because `/var/service` only points at a runlevel once runit is up.
+    pub enabled_dir: PathBuf,
+}
+
+impl ScheduledBootChange {
+    fn to_marker(&self) -> String {
+        format!(
+            "schedule={}\nenabled_dir={}\n",
+            self.schedule.as_str(),
+            self.enabled_dir.display()
+        )
+    }
+
+    fn from_marker(service: &str, marker: &str) -> Option<Self> {
+        let field = |key: &str| {
+            marker
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+        };
+        Some(ScheduledBootChange {
+            service: service.to_string(),
+            schedule: field("schedule")?.parse().ok()?,
+            enabled_dir: PathBuf::from(field("enabled_dir")?),
+        })
+    }
+}
+
+/// What [`ServiceManager::run_boot_tasks`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BootTaskReport {
+    pub enabled: Vec<String>,
+    pub disabled: Vec<String>,
+    /// Markers that could not be applied, with the reason. They are left in
+    /// place and retried at the next boot.
+    pub failed: Vec<(String, String)>,
+}
+
+impl ServiceManager {
+    pub fn with_boot_schedule_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.boot_schedule_dir = Some(dir.into());
+        self
+    }
+
+    pub fn boot_schedule_dir(&self) -> Option<&Path> {
+        self.boot_schedule_dir.as_deref()
+    }
+
+    /// Schedule `service` to be enabled or disabled relative to the next boot,
+    /// replacing any change already scheduled for it. `None` cancels the
+    /// scheduled change; cancelling when there is none is not an error.
+    pub fn schedule_boot_change(
+        &self,
+        service: &str,
+        schedule: Option<BootSchedule>,
+    ) -> Result<Option<ScheduledBootChange>> {
+        let marker_path = self.require_boot_schedule_dir()?.join(service);
+        let Some(schedule) = schedule else {
+            self.validate_service_name(service)?;
+            return match fs::remove_file(&marker_path) {
+                Ok(()) => Ok(None),
+                Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(ServiceError::from_io(&marker_path, err)),
+            };
+        };
+        self.existing_definition(service)?;
+        let enabled_dir = self.dirs_for(service).1;
+        let change = ScheduledBootChange {
+            service: service.to_string(),
+            schedule,
+            enabled_dir: fs::canonicalize(enabled_dir)
+                .map_err(|err| ServiceError::from_io(enabled_dir, err))?,
+        };
+        self.write_marker(&change)?;
+        Ok(Some(change))
+    }
+
+    /// Changes waiting for a boot, sorted by service. An unconfigured or
+    /// missing schedule directory has none.
+    pub fn boot_schedule(&self) -> Result<Vec<ScheduledBootChange>> {
+        let Some(dir) = self.boot_schedule_dir.as_deref() else {
+            return Ok(Vec::new());
+        };
+        let read_dir = match fs::read_dir(dir) {
+            Ok(read_dir) => read_dir,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(ServiceError::from_io(dir, err)),
+        };
+        let mut changes: Vec<ScheduledBootChange> = read_dir
+            .flatten()
+            .filter_map(|entry| {
+                let service = entry.file_name().into_string().ok()?;
+                if service.starts_with('.') {
+                    return None;
+                }
+                let marker = fs::read_to_string(entry.path()).ok()?;
+                ScheduledBootChange::from_marker(&service, &marker)
+            })
+            .collect();
+        changes.sort_by(|a, b| a.service.cmp(&b.service));
+        Ok(changes)
+    }
+
+    /// Apply the scheduled changes for this boot. Meant to run once per boot,
+    /// before runsvdir starts.
+    ///
+    /// A service enabled for one boot, or left enabled for one more, gets a
+    /// marker to disable it at the following boot; a disable removes its marker.
+    pub fn run_boot_tasks(&self) -> Result<BootTaskReport> {
+        let mut report = BootTaskReport::default();
+        for change in self.boot_schedule()? {
+            let service = change.service.clone();
+            match self.apply_boot_change(change) {
+                Ok(BootSchedule::EnableNextBootOnly) => report.enabled.push(service),
+                Ok(BootSchedule::DisableAtNextBoot) => report.disabled.push(service),
+                Ok(BootSchedule::DisableAfterNextBoot) => {}
+                Err(err) => report.failed.push((service, err.to_string())),
+            }
+        }
+        Ok(report)
+    }
+
+    fn apply_boot_change(&self, change: ScheduledBootChange) -> Result<BootSchedule> {
+        let applied = change.schedule;
+        let link = change.enabled_dir.join(&change.service);
+        match applied {
+            BootSchedule::EnableNextBootOnly => {
+                if fs::symlink_metadata(&link).is_err() {
+                    let definition = self.existing_definition(&change.service)?;
+                    symlink(&definition, &link).map_err(|err| ServiceError::from_io(&link, err))?;
+                }
+            }
+            BootSchedule::DisableAfterNextBoot => {}
+            BootSchedule::DisableAtNextBoot => {
+                match fs::remove_file(&link) {
+                    Ok(()) => {}
+                    Err(err) if err.kind() == ErrorKind::NotFound => {}
+                    Err(err) => return Err(ServiceError::from_io(&link, err)),
+                }
+                let marker_path = self.require_boot_schedule_dir()?.join(&change.service);
+                fs::remove_file(&marker_path)
+                    .map_err(|err| ServiceError::from_io(&marker_path, err))?;
+                return Ok(applied);
+            }
+        }
+        self.write_marker(&ScheduledBootChange {
+            schedule: BootSchedule::DisableAtNextBoot,
+            ..change
+        })?;
+        Ok(applied)
+    }
+
+    fn write_marker(&self, change: &ScheduledBootChange) -> Result<()> {
+        let dir = self.require_boot_schedule_dir()?;
+        fs::create_dir_all(dir).map_err(|err| ServiceError::from_io(dir, err))?;
+        let marker_path = dir.join(&change.service);
+        let temp_path = dir.join(format!(".{}.tmp", change.service));
+        fs::write(&temp_path, change.to_marker())
+            .map_err(|err| ServiceError::from_io(&temp_path, err))?;
+        fs::rename(&temp_path, &marker_path).map_err(|err| ServiceError::from_io(&marker_path, err))
+    }
+
+    fn require_boot_schedule_dir(&self) -> Result<&Path> {
+        self.boot_schedule_dir
+            .as_deref()
+            .ok_or_else(|| ServiceError::Other("no boot schedule directory is configured".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BootSchedule;
+    use crate::testing::SandboxFixture;
+
+    #[test]
+    fn applies_boot_schedules_across_two_boots() {
+        let sandbox = SandboxFixture::new().unwrap();
+        sandbox.add_service("fsck-once", "exec fsck-all\n").unwrap();
+        sandbox.add_service("canary", "exec canary\n").unwrap();
+        sandbox.add_service("legacy", "exec legacy\n").unwrap();
+        sandbox.enable("canary").unwrap();
+        sandbox.enable("legacy").unwrap();
+        let manager = sandbox
+            .manager()
+            .clone()
+            .with_boot_schedule_dir(sandbox.root().join("boot"));
+        let enabled = |name: &str| manager.enabled_dir().join(name).exists();
+
+        manager
+            .schedule_boot_change("fsck-once", Some(BootSchedule::EnableNextBootOnly))
+            .unwrap();
+        manager
+            .schedule_boot_change("canary", Some(BootSchedule::DisableAfterNextBoot))
+            .unwrap();
+        manager
+            .schedule_boot_change("legacy", Some(BootSchedule::DisableAtNextBoot))
+            .unwrap();
+        assert_eq!(manager.boot_schedule().unwrap().len(), 3);
+        assert!(!enabled("fsck-once"));
+
+        let first = manager.run_boot_tasks().unwrap();
+        assert_eq!(first.enabled, ["fsck-once"]);
+        assert_eq!(first.disabled, ["legacy"]);
+        assert!(first.failed.is_empty());
+        assert!(enabled("fsck-once") && enabled("canary") && !enabled("legacy"));
+        let pending: Vec<BootSchedule> = manager
+            .boot_schedule()
+            .unwrap()
+            .into_iter()
+            .map(|change| change.schedule)
+            .collect();
+        assert_eq!(pending, [BootSchedule::DisableAtNextBoot; 2]);
+
+        let second = manager.run_boot_tasks().unwrap();
+        assert_eq!(second.disabled, ["canary", "fsck-once"]);
+        assert!(!enabled("fsck-once") && !enabled("canary"));
+        assert!(manager.boot_schedule().unwrap().is_empty());
+
+        manager
+            .schedule_boot_change("canary", Some(BootSchedule::EnableNextBootOnly))
+            .unwrap();
+        manager.schedule_boot_change("canary", None).unwrap();
+        assert!(manager.boot_schedule().unwrap().is_empty());
+        assert!(
+            manager
+                .schedule_boot_change("missing", Some(BootSchedule::DisableAtNextBoot))
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! How long after boot each service came up, kept for the last few boots so
+//! the latest one can be compared with those before it.
+//!
+//! A report is taken from the services' uptimes, so it is only accurate for
+//! services that have not restarted since; anything started later than
+//! [`BOOT_WINDOW`] after boot is left out.
+use crate::{Result, ServiceError, ServiceInfo, ServiceRuntimeState};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_BOOT_REPORTS: &str = "/var/lib/runkit/boot-reports.json";
+
+/// Boots kept in the history.
+pub const KEPT_BOOT_REPORTS: usize = 10;
+
+/// Services that came up later than this after boot were started by hand or
+/// restarted, and say nothing about the boot.
+pub const BOOT_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// A service counts as slower when it came up at least this many times as
+/// late as its usual time, and at least [`REGRESSION_MIN_DELAY`] later.
+pub const REGRESSION_RATIO: f64 = 1.5;
+pub const REGRESSION_MIN_DELAY: Duration = Duration::from_secs(2);
+
+/// One boot: seconds from boot until each service's current run started.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BootReport {
+    pub boot_id: String,
+    /// Unix time of the boot.
+    pub booted_at: i64,
+    pub services: BTreeMap<String, f64>,
+}
+
+/// A service that came up markedly later in the latest boot than usual.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BootRegression {
+    pub service: String,
+    /// Seconds after boot in the latest boot.
+    pub seconds: f64,
+    /// Median over the earlier boots that started it.
+    pub baseline: f64,
+}
+
+impl BootReport {
+    /// The report for a boot at `booted_at` given the services' state at `now`.
+    pub fn from_services(
+        boot_id: String,
+        booted_at: i64,
+        services: &[ServiceInfo],
+        now: SystemTime,
+    ) -> Self {
+        let since_boot = now
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64()
+            - booted_at as f64;
+        let services = services
+            .iter()
+            .filter_map(|info| match info.runtime_state {
+                ServiceRuntimeState::Running { uptime, .. } => {
+                    let started = since_boot - uptime.as_secs_f64();
+                    (0.0..=BOOT_WINDOW.as_secs_f64())
+                        .contains(&started)
+                        .then(|| (info.name.clone(), started))
+                }
+                _ => None,
+            })
+            .collect();
+        BootReport {
+            boot_id,
+            booted_at,
+            services,
+        }
+    }
+}
+
+/// This boot's id and Unix time, when `/proc` says.
+pub(crate) fn current_boot() -> Option<(String, i64)> {
+    let boot_id = fs::read_to_string("/proc/sys/kernel/random/boot_id").ok()?;
+    let stat = fs::read_to_string("/proc/stat").ok()?;
+    let booted_at = stat
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))?
+        .trim()
+        .parse()
+        .ok()?;
+    Some((boot_id.trim().to_string(), booted_at))
+}
+
+/// The recorded boots, oldest first; empty when there is no history yet.
+pub fn read_boot_reports(path: &Path) -> Result<Vec<BootReport>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .map_err(|err| ServiceError::Other(format!("{}: {err}", path.display()).into())),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(ServiceError::from_io(path, err)),
+    }
+}
+
+/// Add `report` to the history at `path` and drop all but the last
+/// [`KEPT_BOOT_REPORTS`] boots. An earlier report of the same boot is merged
+/// in, keeping services that have restarted since. Returns the history,
+/// oldest first.
+pub fn record_boot_report(path: &Path, report: BootReport) -> Result<Vec<BootReport>> {
+    let history = merge_boot_report(read_boot_reports(path)?, report);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| ServiceError::from_io(dir, err))?;
+    }
+    let contents =
+        serde_json::to_string_pretty(&history).map_err(|err| ServiceError::Other(Box::new(err)))?;
+    fs::write(path, contents).map_err(|err| ServiceError::from_io(path, err))?;
+    Ok(history)
+}
+
+/// `history` with `report` added as [`record_boot_report`] adds it, without
+/// writing anything.
+pub fn merge_boot_report(mut history: Vec<BootReport>, mut report: BootReport) -> Vec<BootReport> {
+    if let Some(index) = history
+        .iter()
+        .position(|earlier| earlier.boot_id == report.boot_id)
+    {
+        for (service, seconds) in history.remove(index).services {
+            report.services.entry(service).or_insert(seconds);
+        }
+    }
+    history.push(report);
+    let excess = history.len().saturating_sub(KEPT_BOOT_REPORTS);
+    history.drain(..excess);
+    history
+}
+
+/// Services the last boot in `history` brought up significantly later than
+/// the boots before it, the worst first.
+pub fn boot_regressions(history: &[BootReport]) -> Vec<BootRegression> {
+    let Some((latest, earlier)) = history.split_last() else {
+        return Vec::new();
+    };
+    let mut regressions: Vec<BootRegression> = latest
+        .services
+        .iter()
+        .filter_map(|(service, &seconds)| {
+            let mut times: Vec<f64> = earlier
+                .iter()
+                .filter_map(|report| report.services.get(service).copied())
+                .collect();
+            if times.is_empty() {
+                return None;
+            }
+            times.sort_by(f64::total_cmp);
+            let baseline = times[times.len() / 2];
+            let slower = seconds >= baseline * REGRESSION_RATIO
+                && seconds - baseline >= REGRESSION_MIN_DELAY.as_secs_f64();
+            slower.then(|| BootRegression {
+                service: service.clone(),
+                seconds,
+                baseline,
+            })
+        })
+        .collect();
+    regressions.sort_by(|a, b| (b.seconds - b.baseline).total_cmp(&(a.seconds - a.baseline)));
+    regressions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BootReport, boot_regressions, read_boot_reports, record_boot_report};
+    use crate::testing::SandboxFixture;
+    use std::collections::BTreeMap;
+
+    fn report(boot_id: &str, services: &[(&str, f64)]) -> BootReport {
+        BootReport {
+            boot_id: boot_id.to_string(),
+            booted_at: 0,
+            services: services
+                .iter()
+                .map(|(name, seconds)| (name.to_string(), *seconds))
+                .collect::<BTreeMap<_, _>>(),
+        }
+    }
+
+    #[test]
+    fn flags_services_that_came_up_much_later_than_usual() {
+        let sandbox = SandboxFixture::new().unwrap();
+        let path = sandbox.root().join("boot-reports.json");
+        for boot in 0..12 {
+            record_boot_report(
+                &path,
+                report(&format!("boot-{boot}"), &[("sshd", 3.0), ("dhcpcd", 1.0)]),
+            )
+            .unwrap();
+        }
+        let history = record_boot_report(
+            &path,
+            report("boot-11", &[("sshd", 9.0), ("dhcpcd", 1.5), ("ntpd", 30.0)]),
+        )
+        .unwrap();
+        assert_eq!(history.len(), 10);
+        assert_eq!(read_boot_reports(&path).unwrap(), history);
+
+        let regressions = boot_regressions(&history);
+        assert_eq!(regressions.len(), 1);
+        assert_eq!(regressions[0].service, "sshd");
+        assert_eq!(regressions[0].baseline, 3.0);
+        assert!(boot_regressions(&history[..1]).is_empty());
+    }
+}
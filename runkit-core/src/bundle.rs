@@ -0,0 +1,337 @@
+//! Template bundles: a service definition written to be shared, with the
+//! parts that differ between machines left as variables.
+//!
+//! A bundle is a JSON document:
+//!
+//! ```json
+//! {
+//!   "format": 1,
+//!   "name": "syncthing",
+//!   "summary": "Continuous file synchronization",
+//!   "author": "Jane Doe",
+//!   "variables": [
+//!     { "name": "USER", "description": "Account to sync as" },
+//!     { "name": "HOME", "default": "/home/{{USER}}" }
+//!   ],
+//!   "run": "#!/bin/sh\nexec chpst -u {{USER}} syncthing --home {{HOME}}\n",
+//!   "logger": true
+//! }
+//! ```
+//!
+//! `{{NAME}}` in `run`, `finish`, `check` and `conf` is replaced by the
+//! variable's value, and `{{SERVICE}}` by the name of the service created.
+//! A variable without a default must be given a value. `description`,
+//! `author`, `homepage` and `license` are for whoever picks the bundle.
+use crate::archive::unique_suffix;
+use crate::create::{LOG_RUN_SCRIPT, binary_problem};
+use crate::environment::is_name;
+use crate::{Result, RunScriptInfo, ServiceError, ServiceManager};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// The bundle format this version reads and writes.
+pub const TEMPLATE_BUNDLE_FORMAT: u32 = 1;
+
+/// The placeholder for the name of the service created from a bundle.
+const SERVICE_PLACEHOLDER: &str = "SERVICE";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateBundle {
+    pub format: u32,
+    /// Suggested name for the service.
+    pub name: String,
+    pub summary: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub homepage: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    #[serde(default)]
+    pub variables: Vec<TemplateVariable>,
+    pub run: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finish: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub check: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conf: Option<String>,
+    /// Add a `log/run` that collects output with svlogd in `log/main`.
+    #[serde(default)]
+    pub logger: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateVariable {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Used when no value is given; may refer to earlier variables.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+}
+
+impl TemplateBundle {
+    /// Read a bundle and check it with [`TemplateBundle::problems`].
+    pub fn parse(text: &str) -> Result<Self> {
+        let bundle: TemplateBundle = serde_json::from_str(text).map_err(|err| {
+            ServiceError::InvalidTemplate(format!("not a template bundle: {err}"))
+        })?;
+        let problems = bundle.problems();
+        if !problems.is_empty() {
+            return Err(ServiceError::InvalidTemplate(problems.join("; ")));
+        }
+        Ok(bundle)
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).map_err(|err| ServiceError::from_io(path, err))?;
+        Self::parse(&text)
+    }
+
+    /// Mistakes in the bundle itself, whatever values it is given. Empty when
+    /// the bundle is usable.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.format != TEMPLATE_BUNDLE_FORMAT {
+            problems.push(format!(
+                "bundle format {} is not supported (expected {TEMPLATE_BUNDLE_FORMAT})",
+                self.format
+            ));
+        }
+        if !self.run.starts_with("#!") {
+            problems.push("the run script does not start with #!".to_string());
+        }
+        for (index, variable) in self.variables.iter().enumerate() {
+            if !is_name(&variable.name) || variable.name == SERVICE_PLACEHOLDER {
+                problems.push(format!("{:?} is not a valid variable name", variable.name));
+            } else if self.variables[..index]
+                .iter()
+                .any(|earlier| earlier.name == variable.name)
+            {
+                problems.push(format!("{} is declared twice", variable.name));
+            }
+        }
+        for (file, text) in self.files() {
+            for placeholder in placeholders(text) {
+                if placeholder != SERVICE_PLACEHOLDER && self.variable(placeholder).is_none() {
+                    problems.push(format!("{file} uses the undeclared variable {placeholder}"));
+                }
+            }
+        }
+        problems
+    }
+
+    fn variable(&self, name: &str) -> Option<&TemplateVariable> {
+        self.variables.iter().find(|variable| variable.name == name)
+    }
+
+    /// The templates of the definition's files, by file name.
+    fn files(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("run", Some(&self.run)),
+            ("finish", self.finish.as_ref()),
+            ("check", self.check.as_ref()),
+            ("conf", self.conf.as_ref()),
+        ]
+        .into_iter()
+        .filter_map(|(file, text)| Some((file, text?.as_str())))
+        .collect()
+    }
+
+    /// The value of each variable for a service `service`: from `values`, or
+    /// else the variable's default with earlier variables filled in.
+    pub fn resolve(
+        &self,
+        service: &str,
+        values: &[(String, String)],
+    ) -> Result<Vec<(String, String)>> {
+        if let Some((name, _)) = values
+            .iter()
+            .find(|(name, _)| self.variable(name).is_none())
+        {
+            return Err(ServiceError::InvalidTemplate(format!(
+                "the bundle has no variable {name}"
+            )));
+        }
+        let mut resolved = vec![(SERVICE_PLACEHOLDER.to_string(), service.to_string())];
+        for variable in &self.variables {
+            let given = values
+                .iter()
+                .rev()
+                .find(|(name, _)| *name == variable.name)
+                .map(|(_, value)| value.clone());
+            let value = match (given, &variable.default) {
+                (Some(value), _) => value,
+                (None, Some(default)) => fill(default, &resolved),
+                (None, None) => {
+                    return Err(ServiceError::InvalidTemplate(format!(
+                        "no value for {}",
+                        variable.name
+                    )));
+                }
+            };
+            if value.contains(['\n', '\0']) {
+                return Err(ServiceError::InvalidTemplate(format!(
+                    "the value of {} spans several lines",
+                    variable.name
+                )));
+            }
+            resolved.push((variable.name.clone(), value));
+        }
+        Ok(resolved)
+    }
+
+    /// The definition's files for a service `service`, as `(file, contents)`.
+    pub fn render(
+        &self,
+        service: &str,
+        values: &[(String, String)],
+    ) -> Result<Vec<(&'static str, String)>> {
+        let resolved = self.resolve(service, values)?;
+        Ok(self
+            .files()
+            .into_iter()
+            .map(|(file, text)| (file, fill(text, &resolved)))
+            .collect())
+    }
+}
+
+/// The names between `{{` and `}}` in `text`.
+fn placeholders(text: &str) -> impl Iterator<Item = &str> {
+    text.split("{{")
+        .skip(1)
+        .filter_map(|part| part.split_once("}}"))
+        .map(|(name, _)| name.trim())
+}
+
+fn fill(text: &str, values: &[(String, String)]) -> String {
+    values.iter().fold(text.to_string(), |text, (name, value)| {
+        text.replace(&format!("{{{{{name}}}}}"), value)
+    })
+}
+
+impl ServiceManager {
+    /// Write a new definition `service` from `bundle` with `values` for its
+    /// variables, and enable it if `enable` is set. Like
+    /// [`ServiceManager::create_service`] the run script's program must be
+    /// installed, and nothing is left behind when a check or write fails.
+    pub fn create_from_bundle(
+        &self,
+        bundle: &TemplateBundle,
+        service: &str,
+        values: &[(String, String)],
+        enable: bool,
+    ) -> Result<PathBuf> {
+        self.validate_service_name(service)?;
+        let problems = bundle.problems();
+        if !problems.is_empty() {
+            return Err(ServiceError::InvalidTemplate(problems.join("; ")));
+        }
+        let definitions_dir = self.dirs_for(service).0;
+        let destination = definitions_dir.join(service);
+        if destination.exists() {
+            return Err(ServiceError::DefinitionExists(service.to_string()));
+        }
+        let files = bundle.render(service, values)?;
+
+        let staging = definitions_dir.join(format!(".runkit-create-{}", unique_suffix()));
+        let written = write_bundle(&files, bundle.logger, &staging).and_then(|()| {
+            fs::rename(&staging, &destination)
+                .map_err(|err| ServiceError::from_io(&destination, err))
+        });
+        if let Err(err) = written {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(err);
+        }
+        if enable {
+            self.enable(service)?;
+        }
+        Ok(destination)
+    }
+}
+
+fn write_bundle(files: &[(&str, String)], logger: bool, dir: &Path) -> Result<()> {
+    let write = |path: &Path, contents: &str, mode: u32| {
+        fs::write(path, contents)
+            .and_then(|()| fs::set_permissions(path, fs::Permissions::from_mode(mode)))
+            .map_err(|err| ServiceError::from_io(path, err))
+    };
+    let create_dir =
+        |path: &Path| fs::create_dir_all(path).map_err(|err| ServiceError::from_io(path, err));
+
+    create_dir(dir)?;
+    for (file, contents) in files {
+        if *file == "run" {
+            let program = RunScriptInfo::parse(contents, dir).binary;
+            if let Some(problem) = program.as_deref().and_then(binary_problem) {
+                return Err(ServiceError::InvalidTemplate(problem));
+            }
+        }
+        let mode = if *file == "conf" { 0o644 } else { 0o755 };
+        write(&dir.join(file), contents, mode)?;
+    }
+    if logger {
+        create_dir(&dir.join("log/main"))?;
+        write(&dir.join("log/run"), LOG_RUN_SCRIPT, 0o755)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TemplateBundle;
+    use crate::ServiceError;
+    use crate::testing::SandboxFixture;
+    use std::fs;
+
+    const BUNDLE: &str = r##"{
+        "format": 1,
+        "name": "worker",
+        "summary": "Queue worker",
+        "variables": [
+            { "name": "QUEUE", "description": "Queue to drain" },
+            { "name": "LOG", "default": "/var/log/{{SERVICE}}-{{QUEUE}}" }
+        ],
+        "run": "#!/bin/sh\n. ./conf\nexec /bin/sh -c ./work {{QUEUE}}\n",
+        "conf": "LOG={{LOG}}\n",
+        "logger": true
+    }"##;
+
+    #[test]
+    fn creates_definitions_from_bundles() {
+        let sandbox = SandboxFixture::new().unwrap();
+        let manager = sandbox.manager();
+        let bundle = TemplateBundle::parse(BUNDLE).unwrap();
+        assert!(matches!(
+            manager.create_from_bundle(&bundle, "worker", &[], false),
+            Err(ServiceError::InvalidTemplate(_))
+        ));
+
+        let values = [("QUEUE".to_string(), "mail".to_string())];
+        let path = manager
+            .create_from_bundle(&bundle, "mailer", &values, true)
+            .unwrap();
+        assert!(manager.enabled_dir().join("mailer").exists());
+        assert!(path.join("log/main").is_dir());
+        assert_eq!(
+            fs::read_to_string(path.join("conf")).unwrap(),
+            "LOG=/var/log/mailer-mail\n"
+        );
+        let info = manager.run_script_info("mailer").unwrap();
+        assert_eq!(info.binary.as_deref(), Some("/bin/sh"));
+
+        let broken = BUNDLE
+            .replace("\"format\": 1", "\"format\": 2")
+            .replace("{{QUEUE}}\\n\"", "{{QUEUES}}\\n\"");
+        let Err(ServiceError::InvalidTemplate(problems)) = TemplateBundle::parse(&broken) else {
+            panic!("a broken bundle was accepted");
+        };
+        assert!(problems.contains("format 2"));
+        assert!(problems.contains("undeclared variable QUEUES"));
+    }
+}
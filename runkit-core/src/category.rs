@@ -0,0 +1,182 @@
+//! Service categories, e.g. `networking` or `login`, for grouping a long list
+//! of services. Looked up in the user's own overrides in
+//! `$XDG_DATA_HOME/runkit/categories.json`, then in the system-wide
+//! `/usr/share/runkit/categories.json`.
+//!
+//! Both files map service names to category ids. A name ending in `*`
+//! covers every service starting with the rest, e.g. `agetty-*`; an exact
+//! name wins over a pattern, and a longer pattern over a shorter one. A
+//! `null` entry in the user's file leaves the service uncategorized whatever
+//! the system file says.
+use crate::{Result, ServiceError};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_SYSTEM_CATEGORIES: &str = "/usr/share/runkit/categories.json";
+
+/// Category of services that no file categorizes.
+pub const OTHER_CATEGORY: &str = "other";
+
+/// Entries of one categories.json file; `None` for `null`.
+type CategoryMap = BTreeMap<String, Option<String>>;
+
+/// `$XDG_DATA_HOME/runkit/categories.json`, falling back to
+/// `~/.local/share`. `None` when neither variable is set.
+pub fn user_categories_path() -> Option<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| {
+            env::var_os("HOME")
+                .filter(|home| !home.is_empty())
+                .map(|home| PathBuf::from(home).join(".local/share"))
+        })?;
+    Some(data_home.join("runkit/categories.json"))
+}
+
+/// The user's and the system's categories, read once for a whole listing.
+#[derive(Debug, Clone, Default)]
+pub struct Categories {
+    /// Most important first.
+    layers: Vec<CategoryMap>,
+}
+
+impl Categories {
+    /// Read the files at `user` and `system`. One that is missing or cannot
+    /// be read is skipped, so a stray edit does not lose every category.
+    pub fn load(user: Option<&Path>, system: Option<&Path>) -> Self {
+        Categories {
+            layers: [user, system]
+                .into_iter()
+                .flatten()
+                .filter_map(|path| read_category_map(path).ok())
+                .collect(),
+        }
+    }
+
+    /// Category of `service`, or [`OTHER_CATEGORY`].
+    pub fn category(&self, service: &str) -> &str {
+        self.layers
+            .iter()
+            .find_map(|layer| lookup(layer, service))
+            .flatten()
+            .unwrap_or(OTHER_CATEGORY)
+    }
+
+    /// Every category the files name, sorted, with [`OTHER_CATEGORY`] last.
+    pub fn known(&self) -> Vec<String> {
+        let mut known: Vec<String> = self
+            .layers
+            .iter()
+            .flat_map(|layer| layer.values().flatten())
+            .filter(|category| *category != OTHER_CATEGORY)
+            .cloned()
+            .collect();
+        known.sort();
+        known.dedup();
+        known.push(OTHER_CATEGORY.to_string());
+        known
+    }
+}
+
+/// The entry of `layer` that covers `service`: `Some(None)` for a `null`.
+fn lookup<'a>(layer: &'a CategoryMap, service: &str) -> Option<Option<&'a str>> {
+    let entry = layer.get(service).or_else(|| {
+        layer
+            .iter()
+            .filter_map(|(key, value)| {
+                let prefix = key.strip_suffix('*')?;
+                service.starts_with(prefix).then_some((prefix.len(), value))
+            })
+            .max_by_key(|(length, _)| *length)
+            .map(|(_, value)| value)
+    })?;
+    Some(entry.as_deref())
+}
+
+/// How a category id is shown, e.g. `Networking` for `networking`.
+pub fn category_label(category: &str) -> String {
+    let mut chars = category.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Put `service` in `category` in the categories.json file at `path`, or
+/// with `None` drop its entry. Other entries are kept as they are.
+pub fn write_category(path: &Path, service: &str, category: Option<&str>) -> Result<()> {
+    let mut map = read_category_map(path)?;
+    match category
+        .map(str::trim)
+        .filter(|category| !category.is_empty())
+    {
+        Some(category) => {
+            map.insert(service.to_string(), Some(category.to_lowercase()));
+        }
+        None => {
+            map.remove(service);
+        }
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| ServiceError::from_io(parent, err))?;
+    }
+    let data =
+        serde_json::to_string_pretty(&map).map_err(|err| ServiceError::Other(Box::new(err)))?;
+    fs::write(path, data + "\n").map_err(|err| ServiceError::from_io(path, err))
+}
+
+fn read_category_map(path: &Path) -> Result<CategoryMap> {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(err) => return Err(ServiceError::from_io(path, err)),
+    };
+    serde_json::from_str(&data)
+        .map_err(|err| ServiceError::Other(format!("{}: {err}", path.display()).into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Categories, OTHER_CATEGORY, category_label, write_category};
+    use crate::testing::SandboxFixture;
+    use std::fs;
+
+    #[test]
+    fn user_entries_and_exact_names_win() {
+        let sandbox = SandboxFixture::new().unwrap();
+        let system = sandbox.root().join("system.json");
+        fs::write(
+            &system,
+            r#"{"agetty-*": "login", "agetty-serial*": "hardware", "sshd": "networking",
+                "dhcpcd": "networking", "cron": "system"}"#,
+        )
+        .unwrap();
+        let user = sandbox.root().join("data/runkit/categories.json");
+        fs::create_dir_all(user.parent().unwrap()).unwrap();
+        fs::write(&user, r#"{"cron": null}"#).unwrap();
+        write_category(&user, "sshd", Some(" Login ")).unwrap();
+        write_category(&user, "backup", Some("custom")).unwrap();
+        write_category(&user, "backup", None).unwrap();
+
+        let categories = Categories::load(Some(&user), Some(&system));
+        assert_eq!(categories.category("agetty-tty1"), "login");
+        assert_eq!(categories.category("agetty-serial"), "hardware");
+        assert_eq!(categories.category("sshd"), "login");
+        assert_eq!(categories.category("dhcpcd"), "networking");
+        assert_eq!(categories.category("cron"), OTHER_CATEGORY);
+        assert_eq!(categories.category("backup"), OTHER_CATEGORY);
+        assert_eq!(
+            categories.known(),
+            ["hardware", "login", "networking", "system", "other"]
+        );
+        assert_eq!(category_label("networking"), "Networking");
+
+        fs::write(&user, "{ not json").unwrap();
+        let categories = Categories::load(Some(&user), Some(&system));
+        assert_eq!(categories.category("sshd"), "networking");
+    }
+}
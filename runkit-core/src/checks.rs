@@ -0,0 +1,299 @@
+//! Scheduled health checks: a service's `./check` script run every so often
+//! by runkitd, for services that opted in, with the last outcome kept.
+use crate::{Result, ServiceError, ServiceManager, remove_if_present};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Where the last outcome of each service's check is kept.
+pub const DEFAULT_CHECK_RESULTS_DIR: &str = "/run/runkit/checks";
+
+/// Shortest interval a check may be scheduled at.
+pub const MIN_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long `./check` may run before it is killed and counted as failed.
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Marker inside a definition holding the check interval in seconds.
+const CHECK_INTERVAL_MARKER: &str = ".runkit-check-interval";
+
+/// Longest output kept as a check's detail.
+const MAX_DETAIL_LEN: usize = 200;
+
+/// Outcome of one run of a service's `./check`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    /// Unix time at which the check ran.
+    pub at: i64,
+    pub passed: bool,
+    /// Last line the script printed, or how it ended when it printed nothing.
+    pub detail: String,
+}
+
+impl CheckResult {
+    fn to_record(&self) -> String {
+        format!(
+            "at={}\npassed={}\ndetail={}\n",
+            self.at,
+            self.passed,
+            self.detail.replace('\n', " ")
+        )
+    }
+
+    fn from_record(record: &str) -> Self {
+        let field = |key: &str| {
+            record
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+        };
+        CheckResult {
+            at: field("at").and_then(|at| at.parse().ok()).unwrap_or(0),
+            passed: field("passed") == Some("true"),
+            detail: field("detail").unwrap_or_default().to_string(),
+        }
+    }
+}
+
+/// Check interval recorded in the definition at `definition_path`, if the
+/// service opted in.
+pub(crate) fn read_check_interval(definition_path: &Path) -> Option<Duration> {
+    fs::read_to_string(definition_path.join(CHECK_INTERVAL_MARKER))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+impl ServiceManager {
+    pub fn with_check_results_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.check_results_dir = Some(dir.into());
+        self
+    }
+
+    /// How often runkitd runs the service's `./check`; `None` when it does not.
+    pub fn check_interval(&self, service: &str) -> Result<Option<Duration>> {
+        Ok(read_check_interval(&self.existing_definition(service)?))
+    }
+
+    /// Have runkitd run the service's `./check` every `interval` while it is
+    /// running, or with `None` stop doing so and forget the last outcome.
+    pub fn set_check_interval(&self, service: &str, interval: Option<Duration>) -> Result<()> {
+        let definition_path = self.existing_definition(service)?;
+        let marker_path = definition_path.join(CHECK_INTERVAL_MARKER);
+        let Some(interval) = interval else {
+            remove_if_present(&marker_path)?;
+            if let Some(dir) = &self.check_results_dir {
+                remove_if_present(&dir.join(service))?;
+            }
+            return Ok(());
+        };
+        if interval < MIN_CHECK_INTERVAL {
+            return Err(ServiceError::Other(
+                format!(
+                    "check interval must be at least {} seconds",
+                    MIN_CHECK_INTERVAL.as_secs()
+                )
+                .into(),
+            ));
+        }
+        if !definition_path.join("check").is_file() {
+            return Err(ServiceError::Other(
+                format!("service {service} has no ./check script").into(),
+            ));
+        }
+        fs::write(&marker_path, format!("{}\n", interval.as_secs()))
+            .map_err(|err| ServiceError::from_io(&marker_path, err))
+    }
+
+    /// Services that opted in to scheduled checks, with their intervals,
+    /// sorted by name. Reads only the definitions, so it is cheap to poll.
+    pub fn scheduled_checks(&self) -> Vec<(String, Duration)> {
+        let mut scheduled: Vec<(String, Duration)> = Vec::new();
+        for (definitions_dir, _) in self.service_dirs() {
+            let Ok(read_dir) = fs::read_dir(definitions_dir) else {
+                continue;
+            };
+            for entry in read_dir.flatten() {
+                let Ok(name) = entry.file_name().into_string() else {
+                    continue;
+                };
+                if name.starts_with('.') || scheduled.iter().any(|(known, _)| *known == name) {
+                    continue;
+                }
+                if let Some(interval) = read_check_interval(&entry.path()) {
+                    scheduled.push((name, interval));
+                }
+            }
+        }
+        scheduled.sort();
+        scheduled
+    }
+
+    /// Outcome of the last check runkitd ran, if any.
+    pub fn last_check(&self, service: &str) -> Result<Option<CheckResult>> {
+        self.validate_service_name(service)?;
+        Ok(self.read_last_check(service))
+    }
+
+    pub(crate) fn read_last_check(&self, service: &str) -> Option<CheckResult> {
+        let dir = self.check_results_dir.as_deref()?;
+        fs::read_to_string(dir.join(service))
+            .ok()
+            .map(|record| CheckResult::from_record(&record))
+    }
+
+    /// Run the service's `./check` in its definition directory, killing it
+    /// after [`CHECK_TIMEOUT`], and keep the outcome as the last check.
+    pub fn run_check(&self, service: &str) -> Result<CheckResult> {
+        let definition_path = self.existing_definition(service)?;
+        let script = definition_path.join("check");
+        if !script.is_file() {
+            return Err(ServiceError::Other(
+                format!("service {service} has no ./check script").into(),
+            ));
+        }
+        let (passed, detail) = run_script(&script, &definition_path)
+            .map_err(|err| ServiceError::from_io(&script, err))?;
+        let result = CheckResult {
+            at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs() as i64)
+                .unwrap_or_default(),
+            passed,
+            detail,
+        };
+        if let Some(dir) = &self.check_results_dir {
+            fs::create_dir_all(dir).map_err(|err| ServiceError::from_io(dir, err))?;
+            let record_path = dir.join(service);
+            fs::write(&record_path, result.to_record())
+                .map_err(|err| ServiceError::from_io(&record_path, err))?;
+        }
+        Ok(result)
+    }
+}
+
+/// Run `script` in `dir`; whether it passed and what it said.
+fn run_script(script: &Path, dir: &Path) -> std::io::Result<(bool, String)> {
+    let (mut reader, writer) = std::io::pipe()?;
+    let mut child = Command::new(script)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(writer.try_clone()?)
+        .stderr(writer)
+        .spawn()?;
+
+    // Read on the side so a chatty script cannot block on a full pipe.
+    let (sender, output) = mpsc::channel();
+    thread::spawn(move || {
+        let mut text = String::new();
+        let _ = reader.read_to_string(&mut text);
+        let _ = sender.send(text);
+    });
+
+    let deadline = Instant::now() + CHECK_TIMEOUT;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+    // Whatever the script left running may still hold the pipe open.
+    let text = output
+        .recv_timeout(Duration::from_millis(200))
+        .unwrap_or_default();
+    let last_line = text
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty())
+        .map(|line| line.chars().take(MAX_DETAIL_LEN).collect::<String>());
+
+    Ok(match status {
+        None => (
+            false,
+            format!("timed out after {} seconds", CHECK_TIMEOUT.as_secs()),
+        ),
+        Some(status) => {
+            let ended = match status.code() {
+                Some(code) => format!("exited with status {code}"),
+                None => "killed by a signal".to_string(),
+            };
+            (status.success(), last_line.unwrap_or(ended))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::SandboxFixture;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::Duration;
+
+    fn write_check(sandbox: &SandboxFixture, service: &str, body: &str) {
+        let path = sandbox
+            .manager()
+            .definitions_dir()
+            .join(service)
+            .join("check");
+        fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn records_check_outcomes_and_interval() {
+        let sandbox = SandboxFixture::new().unwrap();
+        sandbox.add_service("web", "exec httpd\n").unwrap();
+        let manager = sandbox
+            .manager()
+            .clone()
+            .with_check_results_dir(sandbox.root().join("checks"));
+
+        assert!(
+            manager
+                .set_check_interval("web", Some(Duration::from_secs(60)))
+                .is_err()
+        );
+        write_check(&sandbox, "web", "echo listening on 8080");
+        assert!(
+            manager
+                .set_check_interval("web", Some(Duration::from_secs(1)))
+                .is_err()
+        );
+        manager
+            .set_check_interval("web", Some(Duration::from_secs(60)))
+            .unwrap();
+        assert_eq!(
+            manager.check_interval("web").unwrap(),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            manager.scheduled_checks(),
+            [("web".to_string(), Duration::from_secs(60))]
+        );
+
+        let passed = manager.run_check("web").unwrap();
+        assert!(passed.passed);
+        assert_eq!(passed.detail, "listening on 8080");
+        assert_eq!(manager.last_check("web").unwrap(), Some(passed));
+
+        write_check(&sandbox, "web", "exit 3");
+        let failed = manager.run_check("web").unwrap();
+        assert!(!failed.passed);
+        assert_eq!(failed.detail, "exited with status 3");
+
+        manager.set_check_interval("web", None).unwrap();
+        assert_eq!(manager.check_interval("web").unwrap(), None);
+        assert!(manager.scheduled_checks().is_empty());
+        assert_eq!(manager.last_check("web").unwrap(), None);
+    }
+}
@@ -0,0 +1,149 @@
+//! Containers and chroots where no runsvdir supervises the services.
+//!
+//! Definitions can still be browsed and edited there, but `sv` has nothing to
+//! talk to. Rather than fail on every status query, a manager switched to
+//! reduced mode with [`ServiceManager::detect_reduced_mode`] reports every
+//! service's state as unknown and refuses control verbs with
+//! [`ServiceError::Unsupervised`].
+use crate::proc::ProcessTable;
+use crate::{ServiceError, ServiceManager};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// What the process is confined to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Confinement {
+    /// A container, named by its runtime, e.g. `docker`, `podman` or `lxc`.
+    Container { runtime: String },
+    /// A chroot: `/` is not the root init sees.
+    Chroot,
+}
+
+impl fmt::Display for Confinement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Confinement::Container { runtime } => write!(f, "a {runtime} container"),
+            Confinement::Chroot => f.write_str("a chroot"),
+        }
+    }
+}
+
+/// The container or chroot this process runs in, if any.
+pub fn detect_confinement() -> Option<Confinement> {
+    detect_container(Path::new("/")).or_else(|| is_chroot().then_some(Confinement::Chroot))
+}
+
+/// Container markers under `root`: the files docker and podman leave, the
+/// `container=` variable systemd-style runtimes pass to init, and the cgroup
+/// names of the rest.
+fn detect_container(root: &Path) -> Option<Confinement> {
+    let container = |runtime: &str| {
+        Some(Confinement::Container {
+            runtime: runtime.to_string(),
+        })
+    };
+    if root.join(".dockerenv").exists() {
+        return container("docker");
+    }
+    if root.join("run/.containerenv").exists() {
+        return container("podman");
+    }
+    if let Ok(environ) = fs::read(root.join("proc/1/environ"))
+        && let Some(runtime) = environ
+            .split(|&byte| byte == 0)
+            .find_map(|variable| variable.strip_prefix(b"container="))
+            .filter(|runtime| !runtime.is_empty())
+    {
+        return container(&String::from_utf8_lossy(runtime));
+    }
+    let cgroup = fs::read_to_string(root.join("proc/1/cgroup")).unwrap_or_default();
+    ["docker", "lxc", "kubepods", "libpod"]
+        .into_iter()
+        .find(|runtime| cgroup.contains(runtime))
+        .and_then(container)
+}
+
+/// Whether `/` differs from init's root. Without `/proc` there is no init to
+/// compare with, which only happens in a bare chroot.
+fn is_chroot() -> bool {
+    if !Path::new("/proc/1").exists() {
+        return true;
+    }
+    match (fs::metadata("/"), fs::metadata("/proc/1/root/")) {
+        (Ok(root), Ok(init_root)) => (root.dev(), root.ino()) != (init_root.dev(), init_root.ino()),
+        // Reading init's root needs privileges; assume the usual case.
+        _ => false,
+    }
+}
+
+impl ServiceManager {
+    /// Manager in reduced mode for `confinement`, or with `None` a normal one.
+    pub fn with_reduced_mode(mut self, confinement: Option<Confinement>) -> Self {
+        self.reduced_mode = confinement;
+        self
+    }
+
+    /// Switch to reduced mode when running in a container or chroot and no
+    /// runsvdir process is running. Sandboxed managers are left alone.
+    pub fn detect_reduced_mode(self) -> Self {
+        if self.sandboxed || ProcessTable::snapshot().has_runsvdir() {
+            return self;
+        }
+        let confinement = detect_confinement();
+        self.with_reduced_mode(confinement)
+    }
+
+    /// The confinement that put the manager in reduced mode, if it is in it.
+    pub fn reduced_mode(&self) -> Option<&Confinement> {
+        self.reduced_mode.as_ref()
+    }
+
+    pub(crate) fn require_supervision(&self) -> crate::Result<()> {
+        match &self.reduced_mode {
+            Some(confinement) => Err(ServiceError::Unsupervised(confinement.to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Confinement, detect_container};
+    use crate::ServiceRuntimeState;
+    use crate::testing::SandboxFixture;
+    use std::fs;
+
+    #[test]
+    fn detects_containers_and_lists_definitions_without_live_status() {
+        let sandbox = SandboxFixture::new().unwrap();
+        let root = sandbox.root().join("image");
+        fs::create_dir_all(root.join("proc/1")).unwrap();
+        assert_eq!(detect_container(&root), None);
+        fs::write(root.join("proc/1/environ"), b"HOME=/\0container=lxc\0").unwrap();
+        assert_eq!(
+            detect_container(&root),
+            Some(Confinement::Container {
+                runtime: "lxc".into()
+            })
+        );
+        fs::write(root.join(".dockerenv"), "").unwrap();
+        let docker = detect_container(&root).unwrap();
+        assert_eq!(docker.to_string(), "a docker container");
+
+        sandbox.add_service("sshd", "exec sshd\n").unwrap();
+        let manager = sandbox.manager().clone().with_reduced_mode(Some(docker));
+        let services = manager.list_services().unwrap();
+        assert_eq!(services.len(), 1);
+        assert!(matches!(
+            services[0].runtime_state,
+            ServiceRuntimeState::Unknown { .. }
+        ));
+        assert!(manager.control("sshd", "up").is_err());
+        assert!(manager.status("sshd").is_err());
+        assert!(sandbox.sv_calls().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,89 @@
+//! Privileged control operations: `sv` verbs and `/var/service` links.
+use crate::{Result, ServiceError, ServiceManager, remove_if_present};
+use std::fs::File;
+use std::path::PathBuf;
+
+impl ServiceManager {
+    /// Run `sv <command> <service>` and return its trimmed stdout.
+    pub fn control(&self, service: &str, command: &str) -> Result<String> {
+        self.validate_service_name(service)?;
+        self.require_supervision()?;
+        let output = self
+            .sv(command, service)
+            .output()
+            .map_err(|err| ServiceError::from_io(&self.sv_command, err))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(ServiceError::SvCommand {
+                command: command.to_string(),
+                service: service.to_string(),
+                message: if stderr.is_empty() {
+                    format!("exit status {}", output.status)
+                } else {
+                    stderr
+                },
+            });
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Link the definition into the enabled directory so runsvdir supervises it.
+    pub fn enable(&self, service: &str) -> Result<()> {
+        self.validate_service_name(service)?;
+        let (definitions_dir, enabled_dir) = self.dirs_for(service);
+        let src = definitions_dir.join(service);
+        if !src.exists() {
+            return Err(ServiceError::DefinitionMissing {
+                service: service.to_string(),
+                path: src,
+            });
+        }
+
+        let dest = enabled_dir.join(service);
+        if dest.exists() {
+            return Err(ServiceError::AlreadyEnabled(service.to_string()));
+        }
+
+        std::os::unix::fs::symlink(&src, &dest).map_err(|err| ServiceError::from_io(&dest, err))
+    }
+
+    /// Create the definition's `down` file so the service stays enabled but is not
+    /// started automatically at boot or when runsv is restarted.
+    pub fn set_down_file(&self, service: &str) -> Result<()> {
+        let down_file = self.existing_definition(service)?.join("down");
+        File::create(&down_file)
+            .map(|_| ())
+            .map_err(|err| ServiceError::from_io(&down_file, err))
+    }
+
+    /// Remove the definition's `down` file; a missing file is not an error.
+    pub fn clear_down_file(&self, service: &str) -> Result<()> {
+        let down_file = self.existing_definition(service)?.join("down");
+        remove_if_present(&down_file)
+    }
+
+    pub(crate) fn existing_definition(&self, service: &str) -> Result<PathBuf> {
+        self.validate_service_name(service)?;
+        let definition_path = self.dirs_for(service).0.join(service);
+        if !definition_path.is_dir() {
+            return Err(ServiceError::DefinitionMissing {
+                service: service.to_string(),
+                path: definition_path,
+            });
+        }
+        Ok(definition_path)
+    }
+
+    /// Remove the service link from the enabled directory.
+    pub fn disable(&self, service: &str) -> Result<()> {
+        self.validate_service_name(service)?;
+        let dest = self.dirs_for(service).1.join(service);
+        if !dest.exists() {
+            return Err(ServiceError::NotEnabled(service.to_string()));
+        }
+
+        std::fs::remove_file(&dest).map_err(|err| ServiceError::from_io(&dest, err))
+    }
+}
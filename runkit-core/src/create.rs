@@ -0,0 +1,415 @@
+//! Scaffolding a new service definition from a few answers, or as a copy of
+//! an existing one.
+use crate::archive::unique_suffix;
+use crate::environment::is_name;
+use crate::{Result, RunScriptInfo, ServiceError, ServiceManager};
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Search path used when the process has none, matching runit's default.
+const DEFAULT_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+/// What [`ServiceManager::create_service`] generates a definition from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceTemplate {
+    pub name: String,
+    /// Command line the `run` script execs, e.g. `/usr/bin/myd --foreground`.
+    /// It must keep running in the foreground.
+    pub command: String,
+    /// Account to run as via `chpst -u`, optionally `user:group`.
+    pub user: Option<String>,
+    /// Variables written to an `env` dir read by `chpst -e`.
+    pub environment: Vec<(String, String)>,
+    /// Add a `log/run` that collects output with svlogd in `log/main`.
+    pub logger: bool,
+}
+
+impl ServiceTemplate {
+    /// Problems that would stop the generated definition from working, such as
+    /// a command that is not installed. Empty when the template is usable.
+    pub fn check(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let command = self.command.trim();
+        if command.is_empty() {
+            problems.push("the command is empty".to_string());
+        } else if command.contains(['\n', ';', '&', '|', '`']) || command.contains("$(") {
+            problems.push(
+                "the command must be a single program and its arguments, without shell operators"
+                    .to_string(),
+            );
+        } else if let Some(problem) = binary_problem(command) {
+            problems.push(problem);
+        }
+        if let Some(user) = &self.user {
+            let valid = |part: &str| {
+                !part.is_empty()
+                    && !part.starts_with('-')
+                    && part
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+            };
+            let (account, group) = match user.split_once(':') {
+                Some((account, group)) => (account, Some(group)),
+                None => (user.as_str(), None),
+            };
+            if !valid(account) || group.is_some_and(|group| !valid(group)) {
+                problems.push(format!("{user:?} is not a valid user name"));
+            }
+        }
+        for (name, value) in &self.environment {
+            if !is_name(name) {
+                problems.push(format!("{name:?} is not a valid variable name"));
+            } else if value.contains(['\n', '\0']) {
+                problems.push(format!("the value of {name} spans several lines"));
+            }
+        }
+        problems
+    }
+
+    /// The generated `run` script.
+    pub fn run_script(&self) -> String {
+        let mut chpst = Vec::new();
+        if let Some(user) = &self.user {
+            chpst.push(format!("-u {user}"));
+        }
+        if !self.environment.is_empty() {
+            chpst.push("-e ./env".to_string());
+        }
+        let exec = if chpst.is_empty() {
+            format!("exec {}", self.command.trim())
+        } else {
+            format!("exec chpst {} {}", chpst.join(" "), self.command.trim())
+        };
+        format!("#!/bin/sh\n# Created by runkit.\nexec 2>&1\n{exec}\n")
+    }
+}
+
+/// Files of a definition that [`ServiceManager::instantiate_service`] applies
+/// substitutions to.
+const SUBSTITUTED_FILES: [&str; 5] = ["run", "finish", "check", "conf", "log/run"];
+
+/// The generated `log/run` script.
+pub(crate) const LOG_RUN_SCRIPT: &str = "#!/bin/sh\nexec svlogd -tt ./main\n";
+
+/// Why the program `command` starts with cannot be run, if it cannot.
+pub(crate) fn binary_problem(command: &str) -> Option<String> {
+    let program = command.split_whitespace().next()?;
+    if program.contains('$') {
+        return None;
+    }
+    let is_executable = |path: &Path| {
+        fs::metadata(path)
+            .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+    };
+    if program.contains('/') {
+        if !program.starts_with('/') {
+            return Some(format!("{program} must be an absolute path"));
+        }
+        return (!is_executable(Path::new(program)))
+            .then(|| format!("{program} does not exist or is not executable"));
+    }
+    let path = env::var("PATH").unwrap_or_else(|_| DEFAULT_PATH.to_string());
+    let found = path
+        .split(':')
+        .any(|dir| !dir.is_empty() && is_executable(&Path::new(dir).join(program)));
+    (!found).then(|| format!("{program} was not found in {path}"))
+}
+
+impl ServiceManager {
+    /// Write a new definition generated from `template`, checked with
+    /// [`ServiceTemplate::check`] first, and enable it if `enable` is set.
+    /// The definition is assembled in a hidden directory and moved into place
+    /// only once complete, so a failed check or write leaves nothing behind.
+    pub fn create_service(&self, template: &ServiceTemplate, enable: bool) -> Result<PathBuf> {
+        let service = template.name.as_str();
+        self.validate_service_name(service)?;
+        let definitions_dir = self.dirs_for(service).0;
+        let destination = definitions_dir.join(service);
+        if destination.exists() {
+            return Err(ServiceError::DefinitionExists(service.to_string()));
+        }
+        let problems = template.check();
+        if !problems.is_empty() {
+            return Err(ServiceError::InvalidTemplate(problems.join("; ")));
+        }
+
+        let staging = definitions_dir.join(format!(".runkit-create-{}", unique_suffix()));
+        let written = write_definition(template, &staging);
+        if let Err(err) = written.and_then(|()| {
+            fs::rename(&staging, &destination)
+                .map_err(|err| ServiceError::from_io(&destination, err))
+        }) {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(err);
+        }
+        if enable {
+            self.enable(service)?;
+        }
+        Ok(destination)
+    }
+
+    /// Copy the definition of `source` to a new service `name` next to it, such
+    /// as `agetty-tty7` from `agetty-tty1`, and enable it if `enable` is set.
+    ///
+    /// Each `(from, to)` in `substitutions` is replaced in the `run`, `finish`,
+    /// `check`, `conf` and `log/run` files. Symlinks are kept, so a shared run
+    /// script stays shared, unless a substitution changes what it points to;
+    /// then the copy gets its own file. runsv's `supervise` state, runkit's
+    /// markers and the source's logs are not copied.
+    pub fn instantiate_service(
+        &self,
+        source: &str,
+        name: &str,
+        substitutions: &[(String, String)],
+        enable: bool,
+    ) -> Result<PathBuf> {
+        let source_path = self.existing_definition(source)?;
+        self.validate_service_name(name)?;
+        if substitutions.iter().any(|(from, _)| from.is_empty()) {
+            return Err(ServiceError::InvalidTemplate(
+                "a substitution has nothing to replace".to_string(),
+            ));
+        }
+        let definitions_dir = self.dirs_for(source).0;
+        let destination = definitions_dir.join(name);
+        if destination.exists() || self.definitions_dir.join(name).exists() {
+            return Err(ServiceError::DefinitionExists(name.to_string()));
+        }
+
+        let staging = definitions_dir.join(format!(".runkit-create-{}", unique_suffix()));
+        let copied = copy_definition(&source_path, &staging, Path::new(""))
+            .and_then(|()| substitute(&staging, substitutions))
+            .and_then(|()| {
+                fs::rename(&staging, &destination)
+                    .map_err(|err| ServiceError::from_io(&destination, err))
+            });
+        if let Err(err) = copied {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(err);
+        }
+        if enable {
+            self.enable(name)?;
+        }
+        Ok(destination)
+    }
+}
+
+/// Copy `from` to `to`, keeping symlinks and permissions. `relative` is the
+/// path below the definition, used to leave out runtime state.
+fn copy_definition(from: &Path, to: &Path, relative: &Path) -> Result<()> {
+    fs::create_dir(to).map_err(|err| ServiceError::from_io(to, err))?;
+    let entries = fs::read_dir(from).map_err(|err| ServiceError::from_io(from, err))?;
+    for entry in entries {
+        let entry = entry.map_err(|err| ServiceError::from_io(from, err))?;
+        let file_name = entry.file_name();
+        let relative = relative.join(&file_name);
+        // svlogd's own settings are kept, the logs it wrote are not.
+        let old_log = relative.starts_with("log/main")
+            && relative != Path::new("log/main")
+            && relative != Path::new("log/main/config");
+        if file_name == "supervise"
+            || file_name.to_string_lossy().starts_with(".runkit-")
+            || old_log
+        {
+            continue;
+        }
+        let source = entry.path();
+        let target = to.join(&file_name);
+        let meta =
+            fs::symlink_metadata(&source).map_err(|err| ServiceError::from_io(&source, err))?;
+        if meta.file_type().is_symlink() {
+            let link = fs::read_link(&source).map_err(|err| ServiceError::from_io(&source, err))?;
+            std::os::unix::fs::symlink(link, &target)
+                .map_err(|err| ServiceError::from_io(&target, err))?;
+        } else if meta.is_dir() {
+            copy_definition(&source, &target, &relative)?;
+        } else {
+            fs::copy(&source, &target).map_err(|err| ServiceError::from_io(&target, err))?;
+        }
+    }
+    Ok(())
+}
+
+fn substitute(dir: &Path, substitutions: &[(String, String)]) -> Result<()> {
+    for file in SUBSTITUTED_FILES {
+        let path = dir.join(file);
+        let Ok(original) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let replaced = substitutions
+            .iter()
+            .fold(original.clone(), |text, (from, to)| text.replace(from, to));
+        if replaced == original {
+            continue;
+        }
+        // Writing through a symlink would change the source's file too.
+        let mode = fs::metadata(&path)
+            .map_err(|err| ServiceError::from_io(&path, err))?
+            .permissions()
+            .mode();
+        if fs::symlink_metadata(&path).is_ok_and(|meta| meta.file_type().is_symlink()) {
+            fs::remove_file(&path).map_err(|err| ServiceError::from_io(&path, err))?;
+        }
+        fs::write(&path, replaced)
+            .and_then(|()| fs::set_permissions(&path, fs::Permissions::from_mode(mode)))
+            .map_err(|err| ServiceError::from_io(&path, err))?;
+    }
+    Ok(())
+}
+
+fn write_definition(template: &ServiceTemplate, dir: &Path) -> Result<()> {
+    let write_script = |path: &Path, contents: &str| {
+        fs::write(path, contents)
+            .and_then(|()| fs::set_permissions(path, fs::Permissions::from_mode(0o755)))
+            .map_err(|err| ServiceError::from_io(path, err))
+    };
+    let create_dir =
+        |path: &Path| fs::create_dir_all(path).map_err(|err| ServiceError::from_io(path, err));
+
+    create_dir(dir)?;
+    let script = template.run_script();
+    // The parser must see the command the template asked for, or the script
+    // would not do what the user expects.
+    let parsed = RunScriptInfo::parse(&script, dir);
+    let expected = template.command.split_whitespace().next();
+    if parsed.binary.as_deref() != expected {
+        return Err(ServiceError::InvalidTemplate(format!(
+            "the generated run script would exec {:?} instead of {:?}",
+            parsed.binary.unwrap_or_default(),
+            expected.unwrap_or_default()
+        )));
+    }
+    write_script(&dir.join("run"), &script)?;
+
+    if !template.environment.is_empty() {
+        let env_dir = dir.join("env");
+        create_dir(&env_dir)?;
+        for (name, value) in &template.environment {
+            let path = env_dir.join(name);
+            fs::write(&path, format!("{value}\n"))
+                .map_err(|err| ServiceError::from_io(&path, err))?;
+        }
+    }
+    if template.logger {
+        create_dir(&dir.join("log/main"))?;
+        write_script(&dir.join("log/run"), LOG_RUN_SCRIPT)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ServiceTemplate;
+    use crate::ServiceError;
+    use crate::testing::SandboxFixture;
+    use std::fs;
+    use std::os::unix::fs::{PermissionsExt, symlink};
+    use std::path::PathBuf;
+
+    #[test]
+    fn creates_checked_definitions() {
+        let sandbox = SandboxFixture::new().unwrap();
+        let manager = sandbox.manager();
+        let template = ServiceTemplate {
+            name: "worker".to_string(),
+            command: "/bin/sh -c ./work".to_string(),
+            user: Some("_worker:_worker".to_string()),
+            environment: vec![("QUEUE".to_string(), "jobs".to_string())],
+            logger: true,
+        };
+        assert!(template.check().is_empty());
+
+        let path = manager.create_service(&template, true).unwrap();
+        assert_eq!(path, manager.definitions_dir().join("worker"));
+        assert!(manager.enabled_dir().join("worker").exists());
+        assert!(path.join("log/main").is_dir());
+        let info = manager.run_script_info("worker").unwrap();
+        assert_eq!(info.binary.as_deref(), Some("/bin/sh"));
+        assert_eq!(info.user.as_deref(), Some("_worker"));
+        assert_eq!(info.env_dir, Some(path.join("env")));
+        let environment = manager.effective_environment("worker").unwrap();
+        assert_eq!(environment.variables[0].value.as_deref(), Some("jobs"));
+        assert!(manager.check_health().unwrap().issues.is_empty());
+
+        assert!(matches!(
+            manager.create_service(&template, false),
+            Err(ServiceError::DefinitionExists(_))
+        ));
+        let broken = ServiceTemplate {
+            name: "broken".to_string(),
+            command: "runkit-no-such-daemon --serve; rm -rf /".to_string(),
+            environment: vec![("1BAD".to_string(), String::new())],
+            ..ServiceTemplate::default()
+        };
+        assert_eq!(broken.check().len(), 2);
+        assert!(matches!(
+            manager.create_service(&broken, false),
+            Err(ServiceError::InvalidTemplate(_))
+        ));
+        let missing = ServiceTemplate {
+            command: "runkit-no-such-daemon".to_string(),
+            ..broken
+        };
+        assert!(missing.check()[0].contains("was not found"));
+        let leftovers: Vec<PathBuf> = fs::read_dir(manager.definitions_dir())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(leftovers, [path]);
+    }
+
+    #[test]
+    fn instantiates_copies_with_substitutions() {
+        let sandbox = SandboxFixture::new().unwrap();
+        let manager = sandbox.manager();
+        let generic = sandbox
+            .add_service(
+                "agetty-generic",
+                "#!/bin/sh\ntty=${PWD##*-}\nexec agetty $tty\n",
+            )
+            .unwrap();
+        let tty1 = manager.definitions_dir().join("agetty-tty1");
+        fs::create_dir(&tty1).unwrap();
+        symlink("../agetty-generic/run", tty1.join("run")).unwrap();
+        symlink("/run/runit/supervise.agetty-tty1", tty1.join("supervise")).unwrap();
+        fs::write(tty1.join("conf"), "GETTY_ARGS=\"--noclear tty1\"\n").unwrap();
+        sandbox.write_log("agetty-tty1", &["started"]).unwrap();
+        fs::write(tty1.join("log/main/config"), "s100000\n").unwrap();
+
+        let substitutions = [("tty1".to_string(), "tty7".to_string())];
+        let tty7 = manager
+            .instantiate_service("agetty-tty1", "agetty-tty7", &substitutions, true)
+            .unwrap();
+        assert!(manager.enabled_dir().join("agetty-tty7").exists());
+        assert_eq!(
+            fs::read_link(tty7.join("run")).unwrap(),
+            PathBuf::from("../agetty-generic/run")
+        );
+        assert!(fs::symlink_metadata(tty7.join("supervise")).is_err());
+        assert_eq!(
+            fs::read_to_string(tty7.join("conf")).unwrap(),
+            "GETTY_ARGS=\"--noclear tty7\"\n"
+        );
+        assert!(tty7.join("log/main/config").exists());
+        assert!(!tty7.join("log/main/current").exists());
+
+        // A substitution in a shared script gives the copy its own file.
+        let own_script = [("agetty $tty".to_string(), "agetty -8 $tty".to_string())];
+        let tty8 = manager
+            .instantiate_service("agetty-tty1", "agetty-tty8", &own_script, false)
+            .unwrap();
+        let run = fs::symlink_metadata(tty8.join("run")).unwrap();
+        assert!(run.is_file() && run.permissions().mode() & 0o111 != 0);
+        assert!(
+            fs::read_to_string(generic.join("run"))
+                .unwrap()
+                .contains("exec agetty $tty")
+        );
+        assert!(matches!(
+            manager.instantiate_service("agetty-tty1", "agetty-tty7", &[], false),
+            Err(ServiceError::DefinitionExists(_))
+        ));
+    }
+}
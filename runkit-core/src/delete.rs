@@ -0,0 +1,147 @@
+//! Deleting a service for good: stopping it, unlinking it from every runlevel
+//! and trashing or removing its definition.
+use crate::{Result, ServiceError, ServiceManager, TargetState, TrashEntry};
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// How long a running service gets to go down before the delete is refused.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What [`ServiceManager::delete_service`] does with the definition directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteMode {
+    /// Move it to the trash, from where it can be restored.
+    Trash,
+    /// Remove it for good.
+    Remove,
+}
+
+/// What [`ServiceManager::delete_service`] did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletedService {
+    pub service: String,
+    /// Links removed from the enabled directory and the other runlevels.
+    pub unlinked: Vec<PathBuf>,
+    /// The trash entry, when the definition was trashed.
+    pub trash_entry: Option<TrashEntry>,
+}
+
+impl ServiceManager {
+    /// Stop `service`, remove every link to it, then trash or remove its
+    /// definition.
+    ///
+    /// Definitions installed by an xbps package are refused, since the package
+    /// manager would bring them back or complain about the missing files. A
+    /// service that does not go down within ten seconds is left as it was.
+    pub fn delete_service(&self, service: &str, mode: DeleteMode) -> Result<DeletedService> {
+        let definition_path = self.existing_definition(service)?;
+        if let Some(package) = self.package_owner(&definition_path) {
+            return Err(ServiceError::PackageOwned {
+                service: service.to_string(),
+                package,
+            });
+        }
+        if mode == DeleteMode::Trash {
+            self.require_trash_dir()?;
+        }
+
+        let (_, enabled_dir) = self.dirs_for(service);
+        let supervised = fs::symlink_metadata(enabled_dir.join(service)).is_ok();
+        if supervised && self.status(service)?.pid().is_some() {
+            self.control(service, "down")?;
+            let outcome = self.wait_for_state(service, TargetState::Down, STOP_TIMEOUT)?;
+            if !outcome.reached {
+                return Err(ServiceError::SvCommand {
+                    command: "down".to_string(),
+                    service: service.to_string(),
+                    message: format!(
+                        "still {} after {}s",
+                        outcome.state.state_name(),
+                        STOP_TIMEOUT.as_secs()
+                    ),
+                });
+            }
+        }
+
+        let mut link_dirs: Vec<PathBuf> =
+            self.runlevels().into_iter().map(|(_, dir)| dir).collect();
+        if !link_dirs.iter().any(|dir| dir == enabled_dir) {
+            link_dirs.push(enabled_dir.to_path_buf());
+        }
+        let mut unlinked = Vec::new();
+        for dir in link_dirs {
+            for (name, link) in self.enabled_links(&dir)? {
+                if name == service && !unlinked.contains(&link) {
+                    fs::remove_file(&link).map_err(|err| ServiceError::from_io(&link, err))?;
+                    unlinked.push(link);
+                }
+            }
+        }
+        // Tell runsv to exit now rather than when runsvdir next scans, so it
+        // does not hold on to a directory that is about to go away.
+        if supervised {
+            let _ = Command::new(self.sv_command_path())
+                .arg("exit")
+                .arg(&definition_path)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+        }
+
+        let trash_entry = match mode {
+            DeleteMode::Trash => Some(self.move_to_trash(service, !unlinked.is_empty())?),
+            DeleteMode::Remove => {
+                fs::remove_dir_all(&definition_path)
+                    .map_err(|err| ServiceError::from_io(&definition_path, err))?;
+                None
+            }
+        };
+        Ok(DeletedService {
+            service: service.to_string(),
+            unlinked,
+            trash_entry,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DeleteMode;
+    use crate::testing::SandboxFixture;
+
+    #[test]
+    fn deletes_stopped_and_unlinked_definitions() {
+        let sandbox = SandboxFixture::new().unwrap();
+        sandbox.add_service("scratch", "exec sleep 1000\n").unwrap();
+        sandbox.add_service("other", "exec sleep 1000\n").unwrap();
+        sandbox.enable("scratch").unwrap();
+        let trash = sandbox.root().join("trash");
+        let manager = sandbox.manager().clone().with_trash_dir(&trash);
+        sandbox.set_running("scratch", 42).unwrap();
+
+        let deleted = manager
+            .delete_service("scratch", DeleteMode::Trash)
+            .unwrap();
+        assert_eq!(
+            deleted.unlinked,
+            vec![manager.enabled_dir().join("scratch")]
+        );
+        assert!(!manager.definitions_dir().join("scratch").exists());
+        assert!(
+            sandbox
+                .sv_calls()
+                .unwrap()
+                .contains(&"down scratch".to_string())
+        );
+        let entry = deleted.trash_entry.unwrap();
+        assert!(entry.was_enabled);
+        assert_eq!(manager.list_trash().unwrap(), vec![entry]);
+
+        let removed = manager.delete_service("other", DeleteMode::Remove).unwrap();
+        assert!(removed.unlinked.is_empty() && removed.trash_entry.is_none());
+        assert!(!manager.definitions_dir().join("other").exists());
+        assert_eq!(manager.list_trash().unwrap().len(), 1);
+    }
+}
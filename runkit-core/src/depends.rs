@@ -0,0 +1,257 @@
+//! Startup dependencies declared by definitions, and the order to start or
+//! stop a service in together with the services around it.
+//!
+//! runit has no dependency mechanism of its own. A dependency is read from
+//! three places in the definition: a `depends` file listing one or more
+//! service names, `# Requires: a, b` comments in `run`, and the usual
+//! `sv check`/`sv start` lines a run script blocks on.
+use crate::proc::sv_wait_targets;
+use crate::{Result, ServiceError, ServiceManager};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsStr;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// File in a definition listing the services it needs, separated by
+/// whitespace or commas. `#` starts a comment.
+const DEPENDS_FILE: &str = "depends";
+
+/// Who needs whom across every definition.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DependencyGraph {
+    /// Declared dependencies of each service, in the order they were found.
+    /// A dependency need not have a definition of its own.
+    pub dependencies: BTreeMap<String, Vec<String>>,
+}
+
+impl DependencyGraph {
+    pub fn dependencies_of(&self, service: &str) -> &[String] {
+        self.dependencies
+            .get(service)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Services that declare `service` as a dependency.
+    pub fn dependents_of(&self, service: &str) -> Vec<String> {
+        self.dependencies
+            .iter()
+            .filter(|(_, dependencies)| dependencies.iter().any(|name| name == service))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Every dependency cycle, each listed once starting from its
+    /// alphabetically first service.
+    pub fn cycles(&self) -> Vec<Vec<String>> {
+        let mut cycles = BTreeSet::new();
+        for service in self.dependencies.keys() {
+            let mut path = Vec::new();
+            self.find_cycles(service, &mut path, &mut cycles);
+        }
+        cycles.into_iter().collect()
+    }
+
+    fn find_cycles(
+        &self,
+        service: &str,
+        path: &mut Vec<String>,
+        cycles: &mut BTreeSet<Vec<String>>,
+    ) {
+        if let Some(start) = path.iter().position(|name| name == service) {
+            let mut cycle = path[start..].to_vec();
+            let first = (0..cycle.len())
+                .min_by_key(|&index| &cycle[index])
+                .unwrap_or(0);
+            cycle.rotate_left(first);
+            cycles.insert(cycle);
+            return;
+        }
+        path.push(service.to_string());
+        for dependency in self.dependencies_of(service) {
+            self.find_cycles(dependency, path, cycles);
+        }
+        path.pop();
+    }
+
+    /// `service` and everything it needs, dependencies first.
+    pub fn start_order(&self, service: &str) -> Result<Vec<String>> {
+        self.ordered(service, &|name| self.dependencies_of(name).to_vec())
+    }
+
+    /// `service` and everything that needs it, dependents first, so nothing is
+    /// left running without a service it relies on.
+    pub fn stop_order(&self, service: &str) -> Result<Vec<String>> {
+        self.ordered(service, &|name| self.dependents_of(name))
+    }
+
+    /// Depth-first post-order from `service` along `next`.
+    fn ordered(&self, service: &str, next: &dyn Fn(&str) -> Vec<String>) -> Result<Vec<String>> {
+        fn visit(
+            service: &str,
+            next: &dyn Fn(&str) -> Vec<String>,
+            path: &mut Vec<String>,
+            order: &mut Vec<String>,
+        ) -> Result<()> {
+            if let Some(start) = path.iter().position(|name| name == service) {
+                let mut cycle = path[start..].to_vec();
+                cycle.push(service.to_string());
+                return Err(ServiceError::DependencyCycle(cycle));
+            }
+            if order.iter().any(|name| name == service) {
+                return Ok(());
+            }
+            path.push(service.to_string());
+            for neighbour in next(service) {
+                visit(&neighbour, next, path, order)?;
+            }
+            path.pop();
+            order.push(service.to_string());
+            Ok(())
+        }
+
+        let mut order = Vec::new();
+        visit(service, next, &mut Vec::new(), &mut order)?;
+        Ok(order)
+    }
+}
+
+impl ServiceManager {
+    /// Dependencies declared by every definition. Names that are not valid
+    /// service names are ignored.
+    pub fn dependency_graph(&self) -> Result<DependencyGraph> {
+        let mut graph = DependencyGraph::default();
+        for (index, (definitions_dir, _)) in self.service_dirs().into_iter().enumerate() {
+            let read_dir = match fs::read_dir(definitions_dir) {
+                Ok(read_dir) => read_dir,
+                Err(err) if index > 0 && err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => return Err(ServiceError::from_io(definitions_dir, err)),
+            };
+            for entry in read_dir.flatten() {
+                let path = entry.path();
+                let Some(name) = path.file_name().and_then(OsStr::to_str) else {
+                    continue;
+                };
+                if !path.is_dir() || graph.dependencies.contains_key(name) {
+                    continue;
+                }
+                let dependencies: Vec<String> = declared_dependencies(&path)
+                    .into_iter()
+                    .filter(|dependency| {
+                        dependency != name && self.validate_service_name(dependency).is_ok()
+                    })
+                    .collect();
+                if !dependencies.is_empty() {
+                    graph.dependencies.insert(name.to_string(), dependencies);
+                }
+            }
+        }
+        Ok(graph)
+    }
+}
+
+/// Dependencies from the definition's `depends` file and its `run` script.
+fn declared_dependencies(definition_path: &Path) -> Vec<String> {
+    let mut dependencies = Vec::new();
+    let mut add = |name: &str| {
+        if !name.is_empty() && !dependencies.iter().any(|known| known == name) {
+            dependencies.push(name.to_string());
+        }
+    };
+    let names = |text: &str| {
+        text.split(|c: char| c.is_whitespace() || c == ',')
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+
+    if let Ok(contents) = fs::read_to_string(definition_path.join(DEPENDS_FILE)) {
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            names(line).iter().for_each(|name| add(name));
+        }
+    }
+    if let Ok(script) = fs::read_to_string(definition_path.join("run")) {
+        for line in script.lines() {
+            let line = line.trim();
+            if let Some(comment) = line.strip_prefix('#') {
+                let comment = comment.trim_start();
+                if let Some((key, rest)) = comment.split_once(':')
+                    && key.eq_ignore_ascii_case("requires")
+                {
+                    names(rest).iter().for_each(|name| add(name));
+                }
+                continue;
+            }
+            for command in sv_commands(line) {
+                sv_wait_targets(&command).iter().for_each(|name| add(name));
+            }
+        }
+    }
+    dependencies
+}
+
+/// The words of each `sv ...` command on a shell line, cut at the first
+/// operator or redirection, e.g. `sv check dbus >/dev/null || exit 1`.
+fn sv_commands(line: &str) -> Vec<Vec<String>> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let mut commands = Vec::new();
+    for (index, word) in words.iter().enumerate() {
+        if Path::new(word).file_name() != Some(OsStr::new("sv")) {
+            continue;
+        }
+        let command = words[index..]
+            .iter()
+            .take_while(|word| {
+                !word
+                    .trim_start_matches(|c: char| c.is_ascii_digit())
+                    .starts_with(['>', '<', '|', '&', ';'])
+            })
+            .map(|word| word.trim_end_matches(';').to_string())
+            .collect();
+        commands.push(command);
+    }
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DependencyGraph;
+    use crate::ServiceError;
+    use crate::testing::SandboxFixture;
+    use std::fs;
+
+    #[test]
+    fn reads_dependencies_and_orders_them() {
+        let sandbox = SandboxFixture::new().unwrap();
+        sandbox
+            .add_service(
+                "app",
+                "#!/bin/sh\n# Requires: db, cache\nsv check net >/dev/null || exit 1\nexec app\n",
+            )
+            .unwrap();
+        let db = sandbox.add_service("db", "exec db\n").unwrap();
+        fs::write(db.join("depends"), "net # the database listens on it\n").unwrap();
+        sandbox.add_service("cache", "exec cache\n").unwrap();
+        sandbox.add_service("net", "exec net\n").unwrap();
+
+        let graph = sandbox.manager().dependency_graph().unwrap();
+        assert_eq!(graph.dependencies_of("app"), ["db", "cache", "net"]);
+        assert_eq!(graph.dependencies_of("db"), ["net"]);
+        assert_eq!(
+            graph.start_order("app").unwrap(),
+            ["net", "db", "cache", "app"]
+        );
+        assert_eq!(graph.stop_order("net").unwrap(), ["app", "db", "net"]);
+        assert!(graph.cycles().is_empty());
+
+        let mut cyclic = DependencyGraph::default();
+        cyclic.dependencies.insert("b".into(), vec!["a".into()]);
+        cyclic.dependencies.insert("a".into(), vec!["b".into()]);
+        assert_eq!(cyclic.cycles(), [["a", "b"]]);
+        assert!(matches!(
+            cyclic.start_order("a"),
+            Err(ServiceError::DependencyCycle(cycle)) if cycle == ["a", "b", "a"]
+        ));
+    }
+}
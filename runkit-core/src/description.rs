@@ -0,0 +1,481 @@
+//! Service descriptions, looked up in order from the user's own overrides in
+//! `$XDG_DATA_HOME/runkit/services.json`, the system-wide
+//! `/usr/share/runkit/services.json`, a file in the definition, and the
+//! `short_desc` of the xbps package that installed it. A definition no package
+//! owns is described as a plain "Custom service".
+//!
+//! Both JSON files map service names to descriptions, the format
+//! `services-merge` writes; `null` entries are skipped. A description is
+//! either a plain string or a map from locale to text, e.g.
+//! `{"sshd": {"en": "OpenSSH daemon", "cs": "Démon OpenSSH"}}`, read in the
+//! language of `LC_MESSAGES` with English as the fallback.
+//!
+//! `xbps-query` is slow enough to notice on every selection, so owners and
+//! package descriptions are cached for the life of the process. An owner is
+//! looked up again once its script's modification time changes, and a
+//! package's description is cached under its version, so an update is picked
+//! up without a restart.
+use crate::{Result, ServiceError, ServiceManager};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+pub const DEFAULT_SYSTEM_DESCRIPTIONS: &str = "/usr/share/runkit/services.json";
+
+/// Description given to definitions that no package owns.
+pub const CUSTOM_SERVICE_DESCRIPTION: &str = "Custom service";
+
+/// Owning package of a script, with the script's modification time.
+type CachedOwner = (Option<SystemTime>, Option<String>);
+
+static OWNERS: Lazy<Mutex<HashMap<PathBuf, CachedOwner>>> = Lazy::new(Default::default);
+
+/// `short_desc` by package version, e.g. `openssh-9.8p1_1`.
+static SHORT_DESCS: Lazy<Mutex<HashMap<String, Option<String>>>> = Lazy::new(Default::default);
+
+/// Where a [`ServiceDescription`] came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DescriptionSource {
+    /// The user's own overrides.
+    User,
+    /// The system-wide descriptions file.
+    System,
+    /// A `description`, `README` or `README.md` file in the definition.
+    File,
+    /// The `short_desc` of the package that installed the definition.
+    Package,
+    /// No package owns the definition.
+    Custom,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ServiceDescription {
+    pub text: String,
+    pub source: DescriptionSource,
+    /// Owning package with its version, when the text is its `short_desc`.
+    pub package: Option<String>,
+}
+
+/// One service's entry in a services.json file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DescriptionEntry {
+    /// The same text in every language; the original format.
+    Text(String),
+    /// Text by locale, e.g. `en`, `cs` or `pt_BR`.
+    Localized(BTreeMap<String, String>),
+}
+
+impl DescriptionEntry {
+    /// Text for `locale` as in `LC_MESSAGES`, e.g. `pt_BR.UTF-8`: that
+    /// territory's, else the language's, else English.
+    pub fn text_for(&self, locale: Option<&str>) -> Option<&str> {
+        let text = match self {
+            DescriptionEntry::Text(text) => text.as_str(),
+            DescriptionEntry::Localized(texts) => locale_keys(locale)
+                .iter()
+                .find_map(|key| texts.get(*key))?
+                .as_str(),
+        };
+        Some(text.trim()).filter(|text| !text.is_empty())
+    }
+
+    /// Text in the language of this process's locale.
+    pub fn text(&self) -> Option<&str> {
+        self.text_for(message_locale().as_deref())
+    }
+}
+
+/// The locale messages are shown in, from `LC_ALL`, `LC_MESSAGES` or `LANG`
+/// in that order, as setlocale picks it. `None` for `C` and `POSIX`.
+pub fn message_locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(env::var_os)
+        .find(|value| !value.is_empty())?
+        .into_string()
+        .ok()
+        .filter(|locale| !matches!(locale.as_str(), "C" | "POSIX") && !locale.starts_with("C."))
+}
+
+/// Keys to try for `locale`, most specific first: `pt_BR.UTF-8@euro` gives
+/// `pt_BR`, `pt`, then `en`.
+fn locale_keys(locale: Option<&str>) -> Vec<&str> {
+    let territory = locale.and_then(|locale| locale.split(['.', '@']).next());
+    let mut keys: Vec<&str> = territory.into_iter().collect();
+    keys.extend([language(locale), "en"]);
+    keys.dedup();
+    keys
+}
+
+/// Language part of `locale`, e.g. `pt` for `pt_BR.UTF-8`, or `en`.
+fn language(locale: Option<&str>) -> &str {
+    locale
+        .and_then(|locale| locale.split(['_', '.', '@']).next())
+        .filter(|language| !language.is_empty())
+        .unwrap_or("en")
+}
+
+/// `$XDG_DATA_HOME/runkit/services.json`, falling back to
+/// `~/.local/share`. `None` when neither variable is set.
+pub fn user_descriptions_path() -> Option<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| {
+            env::var_os("HOME")
+                .filter(|home| !home.is_empty())
+                .map(|home| PathBuf::from(home).join(".local/share"))
+        })?;
+    Some(data_home.join("runkit/services.json"))
+}
+
+/// Non-empty descriptions in a services.json file, in the language of this
+/// process's locale. A missing file has none.
+pub fn read_descriptions(path: &Path) -> Result<BTreeMap<String, String>> {
+    let locale = message_locale();
+    Ok(read_description_map(path)?
+        .into_iter()
+        .filter_map(|(service, entry)| {
+            let text = entry?.text_for(locale.as_deref())?.to_string();
+            Some((service, text))
+        })
+        .collect())
+}
+
+/// Set `service`'s entry in a services.json file, or with `None` or blank
+/// text remove it. An entry with texts by locale only has the text for this
+/// process's language set or removed. Other entries are kept as they are.
+pub fn write_description(path: &Path, service: &str, text: Option<&str>) -> Result<()> {
+    let mut map = read_description_map(path)?;
+    let text = text.map(str::trim).filter(|text| !text.is_empty());
+    match (map.get_mut(service), text) {
+        (Some(Some(DescriptionEntry::Localized(texts))), text) => {
+            let language = language(message_locale().as_deref()).to_string();
+            match text {
+                Some(text) => {
+                    texts.insert(language, text.to_string());
+                }
+                None => {
+                    texts.remove(&language);
+                }
+            }
+            if texts.is_empty() {
+                map.remove(service);
+            }
+        }
+        (_, Some(text)) => {
+            map.insert(
+                service.to_string(),
+                Some(DescriptionEntry::Text(text.to_string())),
+            );
+        }
+        (_, None) => {
+            map.remove(service);
+        }
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| ServiceError::from_io(parent, err))?;
+    }
+    let data =
+        serde_json::to_string_pretty(&map).map_err(|err| ServiceError::Other(Box::new(err)))?;
+    fs::write(path, data + "\n").map_err(|err| ServiceError::from_io(path, err))
+}
+
+fn read_description_map(path: &Path) -> Result<BTreeMap<String, Option<DescriptionEntry>>> {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(err) => return Err(ServiceError::from_io(path, err)),
+    };
+    serde_json::from_str(&data)
+        .map_err(|err| ServiceError::Other(format!("{}: {err}", path.display()).into()))
+}
+
+/// The user and system descriptions files, read once for a whole listing.
+pub(crate) struct DescriptionOverrides(Vec<(DescriptionSource, BTreeMap<String, String>)>);
+
+impl DescriptionOverrides {
+    /// First override for `service`, user before system.
+    pub(crate) fn lookup(&self, service: &str) -> Option<(DescriptionSource, &str)> {
+        self.0
+            .iter()
+            .find_map(|(source, map)| Some((*source, map.get(service)?.as_str())))
+    }
+}
+
+impl ServiceManager {
+    /// Look up the user's overrides in `path` before anything else.
+    pub fn with_user_descriptions(mut self, path: impl Into<PathBuf>) -> Self {
+        self.user_descriptions = Some(path.into());
+        self
+    }
+
+    pub fn with_system_descriptions(mut self, path: impl Into<PathBuf>) -> Self {
+        self.system_descriptions = Some(path.into());
+        self
+    }
+
+    /// Describe `service` as `text` for this user only, or with `None` drop
+    /// the override. Needs no privileges.
+    pub fn set_user_description(&self, service: &str, text: Option<&str>) -> Result<()> {
+        self.validate_service_name(service)?;
+        let path = self.user_descriptions.as_deref().ok_or_else(|| {
+            ServiceError::Other("no file for your own descriptions is configured".into())
+        })?;
+        write_description(path, service, text)
+    }
+
+    /// The configured descriptions files. One that cannot be read is
+    /// skipped, so a stray edit does not hide every other description.
+    pub(crate) fn description_overrides(&self) -> DescriptionOverrides {
+        let layers = [
+            (DescriptionSource::User, &self.user_descriptions),
+            (DescriptionSource::System, &self.system_descriptions),
+        ];
+        DescriptionOverrides(
+            layers
+                .into_iter()
+                .filter_map(|(source, path)| {
+                    let map = read_descriptions(path.as_deref()?).ok()?;
+                    Some((source, map))
+                })
+                .collect(),
+        )
+    }
+
+    /// Description of `service` and where it came from. `None` when there is
+    /// no definition, or when nothing describes it and the package database
+    /// cannot be queried.
+    ///
+    /// Sandboxed and per-user managers never query xbps; their definitions
+    /// live in the caller's directories, so no package owns them.
+    pub fn describe_service(&self, service: &str) -> Result<Option<ServiceDescription>> {
+        self.validate_service_name(service)?;
+        let definition_path = self.dirs_for(service).0.join(service);
+        if !definition_path.exists() {
+            return Ok(None);
+        }
+
+        if let Some((source, text)) = self.description_overrides().lookup(service) {
+            return Ok(Some(ServiceDescription {
+                text: text.to_string(),
+                source,
+                package: None,
+            }));
+        }
+
+        if let Some(text) = self.read_description(&definition_path) {
+            return Ok(Some(ServiceDescription {
+                text,
+                source: DescriptionSource::File,
+                package: None,
+            }));
+        }
+
+        let owner = if self.sandboxed {
+            Some(None)
+        } else {
+            definition_script(&definition_path).and_then(|script| owning_package(&script))
+        };
+        Ok(match owner {
+            None => None,
+            Some(None) => Some(ServiceDescription {
+                text: CUSTOM_SERVICE_DESCRIPTION.to_string(),
+                source: DescriptionSource::Custom,
+                package: None,
+            }),
+            Some(Some(package)) => package_short_desc(&package).map(|text| ServiceDescription {
+                text,
+                source: DescriptionSource::Package,
+                package: Some(package),
+            }),
+        })
+    }
+}
+
+/// Whichever of the definition's scripts exists, in the order xbps packages
+/// ship them.
+pub(crate) fn definition_script(definition_path: &Path) -> Option<PathBuf> {
+    ["run", "finish", "check"]
+        .into_iter()
+        .map(|candidate| definition_path.join(candidate))
+        .find(|path| path.exists())
+}
+
+/// Package version owning `script` per `xbps-query -o`, cached. `Some(None)`
+/// when no package owns it, `None` when xbps-query could not be run.
+pub(crate) fn owning_package(script: &Path) -> Option<Option<String>> {
+    let modified = fs::metadata(script).and_then(|meta| meta.modified()).ok();
+    if let Some((cached_modified, owner)) = OWNERS.lock().unwrap().get(script)
+        && *cached_modified == modified
+    {
+        return Some(owner.clone());
+    }
+
+    let output = Command::new("xbps-query")
+        .arg("-o")
+        .arg(script)
+        .output()
+        .ok()?;
+    let owner = if output.status.success() {
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .and_then(|line| line.split(':').next())
+            .map(|package| package.trim().to_string())
+            .filter(|package| !package.is_empty())
+    } else {
+        None
+    };
+    OWNERS
+        .lock()
+        .unwrap()
+        .insert(script.to_path_buf(), (modified, owner.clone()));
+    Some(owner)
+}
+
+/// `short_desc` of an installed package per `xbps-query -S`, cached.
+fn package_short_desc(package: &str) -> Option<String> {
+    if let Some(cached) = SHORT_DESCS.lock().unwrap().get(package) {
+        return cached.clone();
+    }
+
+    let output = Command::new("xbps-query")
+        .arg("-S")
+        .arg(package)
+        .output()
+        .ok()?;
+    let short_desc = if output.status.success() {
+        parse_short_desc(&String::from_utf8_lossy(&output.stdout))
+    } else {
+        None
+    };
+    SHORT_DESCS
+        .lock()
+        .unwrap()
+        .insert(package.to_string(), short_desc.clone());
+    short_desc
+}
+
+/// The `short_desc: ...` line of `xbps-query -S` output.
+fn parse_short_desc(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix("short_desc:"))
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CUSTOM_SERVICE_DESCRIPTION, DescriptionEntry, DescriptionSource, parse_short_desc,
+        read_descriptions,
+    };
+    use crate::testing::SandboxFixture;
+    use std::collections::BTreeMap;
+    use std::fs;
+
+    #[test]
+    fn describes_from_files_and_marks_unowned_definitions_custom() {
+        let output = "architecture: x86_64\npkgver: openssh-9.8p1_1\n\
+                      short_desc: OpenSSH free Secure Shell (SSH) client and server\n";
+        assert_eq!(
+            parse_short_desc(output).as_deref(),
+            Some("OpenSSH free Secure Shell (SSH) client and server")
+        );
+        assert_eq!(parse_short_desc("pkgver: foo-1.0_1\n"), None);
+
+        let sandbox = SandboxFixture::new().unwrap();
+        let sshd_dir = sandbox.add_service("sshd", "exec sshd\n").unwrap();
+        sandbox.add_service("backup", "exec backup\n").unwrap();
+        fs::write(
+            sshd_dir.join("description"),
+            "Secure shell daemon\nmore detail\n",
+        )
+        .unwrap();
+        let manager = sandbox.manager();
+
+        let sshd = manager.describe_service("sshd").unwrap().unwrap();
+        assert_eq!(sshd.text, "Secure shell daemon");
+        assert_eq!(sshd.source, DescriptionSource::File);
+
+        let backup = manager.describe_service("backup").unwrap().unwrap();
+        assert_eq!(backup.source, DescriptionSource::Custom);
+        assert_eq!(
+            manager.service_description("backup").unwrap().as_deref(),
+            Some(CUSTOM_SERVICE_DESCRIPTION)
+        );
+        assert_eq!(manager.describe_service("missing").unwrap(), None);
+    }
+
+    #[test]
+    fn picks_localized_texts_and_reads_flat_ones() {
+        let entries: BTreeMap<String, DescriptionEntry> = serde_json::from_str(
+            r#"{"sshd": {"en": "OpenSSH daemon", "cs": "Démon OpenSSH", "pt_BR": "Daemon"},
+                "cron": "Periodic jobs", "ntpd": {"cs": "Čas"}}"#,
+        )
+        .unwrap();
+        let sshd = &entries["sshd"];
+        assert_eq!(sshd.text_for(Some("cs_CZ.UTF-8")), Some("Démon OpenSSH"));
+        assert_eq!(sshd.text_for(Some("pt_BR.UTF-8@euro")), Some("Daemon"));
+        assert_eq!(sshd.text_for(Some("pt_PT")), Some("OpenSSH daemon"));
+        assert_eq!(sshd.text_for(None), Some("OpenSSH daemon"));
+        assert_eq!(entries["cron"].text_for(Some("cs")), Some("Periodic jobs"));
+        assert_eq!(entries["ntpd"].text_for(Some("de_DE")), None);
+    }
+
+    #[test]
+    fn user_descriptions_override_system_ones_and_files() {
+        let sandbox = SandboxFixture::new().unwrap();
+        let sshd_dir = sandbox.add_service("sshd", "exec sshd\n").unwrap();
+        sandbox.add_service("backup", "exec backup\n").unwrap();
+        fs::write(sshd_dir.join("description"), "From the definition\n").unwrap();
+        let system = sandbox.root().join("system.json");
+        fs::write(&system, r#"{"sshd": "OpenSSH daemon", "backup": null}"#).unwrap();
+        let user = sandbox.root().join("data/runkit/services.json");
+        let manager = sandbox
+            .manager()
+            .clone()
+            .with_system_descriptions(&system)
+            .with_user_descriptions(&user);
+
+        let sshd = manager.describe_service("sshd").unwrap().unwrap();
+        assert_eq!(sshd.text, "OpenSSH daemon");
+        assert_eq!(sshd.source, DescriptionSource::System);
+        let listed = manager.list_services().unwrap();
+        assert_eq!(listed[1].description.as_deref(), Some("OpenSSH daemon"));
+
+        manager
+            .set_user_description("backup", Some(" Nightly rsync "))
+            .unwrap();
+        manager
+            .set_user_description("sshd", Some("My ssh"))
+            .unwrap();
+        let backup = manager.describe_service("backup").unwrap().unwrap();
+        assert_eq!(backup.text, "Nightly rsync");
+        assert_eq!(backup.source, DescriptionSource::User);
+        assert_eq!(
+            manager.service_description("sshd").unwrap().as_deref(),
+            Some("My ssh")
+        );
+
+        manager.set_user_description("sshd", None).unwrap();
+        assert_eq!(read_descriptions(&user).unwrap().len(), 1);
+        assert_eq!(
+            manager.service_description("sshd").unwrap().as_deref(),
+            Some("OpenSSH daemon")
+        );
+    }
+}
@@ -0,0 +1,314 @@
+//! Installation checks shared by `runkitd doctor` and the GUI setup assistant.
+use crate::ServiceManager;
+use crate::polkit::POLKIT_ACTIONS;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Well-known system bus name of runkitd.
+pub const BUS_NAME: &str = "tech.geektoshi.Runkit1";
+
+const POLKIT_POLICY: &str = "/usr/share/polkit-1/actions/tech.geektoshi.Runkit.policy";
+const DBUS_SERVICE_FILE: &str = "/usr/share/dbus-1/system-services/tech.geektoshi.Runkit1.service";
+const DBUS_POLICY_FILES: &[&str] = &[
+    "/etc/dbus-1/system.d/tech.geektoshi.Runkit1.conf",
+    "/usr/share/dbus-1/system.d/tech.geektoshi.Runkit1.conf",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Error,
+}
+
+impl CheckStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CheckStatus::Ok => "ok",
+            CheckStatus::Warning => "warning",
+            CheckStatus::Error => "error",
+        }
+    }
+}
+
+/// Outcome of one installation check, with a suggested fix when it failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiagnosticCheck {
+    /// Stable identifier such as `polkit_policy`.
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// A command or instruction that resolves the problem.
+    pub fix: Option<String>,
+}
+
+impl DiagnosticCheck {
+    pub fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        DiagnosticCheck {
+            name,
+            status: CheckStatus::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    pub fn problem(
+        name: &'static str,
+        status: CheckStatus,
+        detail: impl Into<String>,
+        fix: impl Into<String>,
+    ) -> Self {
+        DiagnosticCheck {
+            name,
+            status,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    pub fn is_problem(&self) -> bool {
+        self.status != CheckStatus::Ok
+    }
+}
+
+impl ServiceManager {
+    /// Check the files and directories runkit needs: the polkit policy, the
+    /// D-Bus activation and policy files, the service directories and `sv`.
+    ///
+    /// These only read the filesystem, so an unprivileged client can run them
+    /// when runkitd itself is unreachable.
+    pub fn installation_checks(&self) -> Vec<DiagnosticCheck> {
+        vec![
+            check_polkit_policy(Path::new(POLKIT_POLICY)),
+            check_dbus_service_file(Path::new(DBUS_SERVICE_FILE)),
+            check_dbus_policy(),
+            check_directory("definitions_dir", &self.definitions_dir),
+            check_directory("enabled_dir", &self.enabled_dir),
+            check_sv(&self.sv_command),
+        ]
+    }
+}
+
+fn check_polkit_policy(path: &Path) -> DiagnosticCheck {
+    const NAME: &str = "polkit_policy";
+    let reinstall = format!(
+        "sudo install -Dm644 assets/polkit-1/actions/tech.geektoshi.Runkit.policy {}",
+        path.display()
+    );
+    let Ok(policy) = fs::read_to_string(path) else {
+        return DiagnosticCheck::problem(
+            NAME,
+            CheckStatus::Error,
+            format!(
+                "{} is missing; every privileged action will be refused",
+                path.display()
+            ),
+            reinstall,
+        );
+    };
+
+    let missing: Vec<&str> = POLKIT_ACTIONS
+        .iter()
+        .map(|action| action.id)
+        .filter(|action| !policy.contains(&format!("\"{action}\"")))
+        .collect();
+    if missing.is_empty() {
+        DiagnosticCheck::ok(NAME, format!("{} defines every action", path.display()))
+    } else {
+        DiagnosticCheck::problem(
+            NAME,
+            CheckStatus::Error,
+            format!(
+                "{} does not define {}; it is probably from an older release",
+                path.display(),
+                missing.join(", ")
+            ),
+            reinstall,
+        )
+    }
+}
+
+fn check_dbus_service_file(path: &Path) -> DiagnosticCheck {
+    const NAME: &str = "dbus_service_file";
+    let Ok(contents) = fs::read_to_string(path) else {
+        return DiagnosticCheck::problem(
+            NAME,
+            CheckStatus::Error,
+            format!(
+                "{} is missing, so the bus cannot start runkitd on demand",
+                path.display()
+            ),
+            format!(
+                "sudo install -Dm644 assets/dbus-1/system-services/tech.geektoshi.Runkit1.service {}",
+                path.display()
+            ),
+        );
+    };
+
+    let exec = contents
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Exec="))
+        .and_then(|command| command.split_whitespace().next())
+        .map(PathBuf::from);
+    match exec {
+        Some(binary) if is_executable(&binary) => DiagnosticCheck::ok(
+            NAME,
+            format!("{} starts {}", path.display(), binary.display()),
+        ),
+        Some(binary) => DiagnosticCheck::problem(
+            NAME,
+            CheckStatus::Error,
+            format!(
+                "{} starts {}, which is not an executable file",
+                path.display(),
+                binary.display()
+            ),
+            format!(
+                "install runkitd to {} (./start.sh does this) or point Exec= at the installed binary",
+                binary.display()
+            ),
+        ),
+        None => DiagnosticCheck::problem(
+            NAME,
+            CheckStatus::Error,
+            format!("{} has no Exec= line", path.display()),
+            "reinstall the file from assets/dbus-1/system-services/",
+        ),
+    }
+}
+
+fn check_dbus_policy() -> DiagnosticCheck {
+    const NAME: &str = "dbus_policy";
+    match DBUS_POLICY_FILES
+        .iter()
+        .find(|path| Path::new(path).is_file())
+    {
+        Some(path) => DiagnosticCheck::ok(NAME, format!("{path} is installed")),
+        None => DiagnosticCheck::problem(
+            NAME,
+            CheckStatus::Error,
+            format!(
+                "no bus policy for {BUS_NAME}; runkitd cannot own its name and clients cannot call it"
+            ),
+            format!(
+                "sudo install -Dm644 assets/dbus-1/system.d/tech.geektoshi.Runkit1.conf {}",
+                DBUS_POLICY_FILES[0]
+            ),
+        ),
+    }
+}
+
+fn check_directory(name: &'static str, path: &Path) -> DiagnosticCheck {
+    if path.is_dir() {
+        return DiagnosticCheck::ok(name, format!("{} exists", path.display()));
+    }
+    let fix = if name == "enabled_dir" {
+        format!(
+            "link it to the current runlevel: sudo ln -s /etc/runit/runsvdir/current {}",
+            path.display()
+        )
+    } else {
+        format!(
+            "create it or install runit: sudo xbps-install -S runit-void ({} should hold service definitions)",
+            path.display()
+        )
+    };
+    DiagnosticCheck::problem(
+        name,
+        CheckStatus::Error,
+        format!("{} does not exist or is not a directory", path.display()),
+        fix,
+    )
+}
+
+fn check_sv(command: &Path) -> DiagnosticCheck {
+    const NAME: &str = "sv_command";
+    let resolved = if command.components().count() > 1 {
+        is_executable(command).then(|| command.to_path_buf())
+    } else {
+        std::env::var_os("PATH").and_then(|path| {
+            std::env::split_paths(&path)
+                .map(|dir| dir.join(command))
+                .find(|candidate| is_executable(candidate))
+        })
+    };
+    match resolved {
+        Some(path) => DiagnosticCheck::ok(NAME, format!("using {}", path.display())),
+        None => DiagnosticCheck::problem(
+            NAME,
+            CheckStatus::Error,
+            format!(
+                "{} was not found in PATH; no service can be controlled",
+                command.display()
+            ),
+            "install runit (sudo xbps-install -S runit) or add the directory holding sv to PATH",
+        ),
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CheckStatus, check_directory, check_polkit_policy};
+    use crate::polkit::{DEFAULT_RUNKITD_PATH, polkit_policy};
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn reports_missing_and_outdated_files() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root =
+            std::env::temp_dir().join(format!("runkit-doctor-{}-{nanos}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let policy = root.join("tech.geektoshi.Runkit.policy");
+
+        let missing = check_polkit_policy(&policy);
+        assert_eq!(missing.status, CheckStatus::Error);
+        assert!(missing.fix.unwrap().contains("install -Dm644"));
+
+        fs::write(
+            &policy,
+            r#"<action id="tech.geektoshi.Runkit.require_password"></action>"#,
+        )
+        .unwrap();
+        let outdated = check_polkit_policy(&policy);
+        assert!(outdated.detail.contains("tech.geektoshi.Runkit.cached"));
+
+        fs::write(
+            &policy,
+            r#"<action id="tech.geektoshi.Runkit.require_password"/><action id="tech.geektoshi.Runkit.cached"/>"#,
+        )
+        .unwrap();
+        let without_force = check_polkit_policy(&policy);
+        assert!(without_force.detail.contains("tech.geektoshi.Runkit.force"));
+
+        fs::write(
+            &policy,
+            r#"<action id="tech.geektoshi.Runkit.require_password"/><action id="tech.geektoshi.Runkit.cached"/><action id="tech.geektoshi.Runkit.force"/>"#,
+        )
+        .unwrap();
+        let three_actions = check_polkit_policy(&policy);
+        assert!(
+            three_actions
+                .detail
+                .contains("tech.geektoshi.Runkit.manage.start")
+        );
+
+        fs::write(&policy, polkit_policy(DEFAULT_RUNKITD_PATH)).unwrap();
+        assert!(!check_polkit_policy(&policy).is_problem());
+
+        assert!(!check_directory("enabled_dir", &root).is_problem());
+        assert!(check_directory("enabled_dir", &root.join("service")).is_problem());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
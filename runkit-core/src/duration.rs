@@ -0,0 +1,73 @@
+//! Compact human-readable durations such as `3d 4h 12m`.
+use crate::ServiceRuntimeState;
+use std::time::Duration;
+
+const UNITS: [(u64, &str); 4] = [(86_400, "d"), (3_600, "h"), (60, "m"), (1, "s")];
+
+/// Render `duration` with at most three units, starting at the largest non-zero
+/// one: `42s`, `12m 5s`, `4h 12m 5s`, `3d 4h 12m`. Zero units in between are
+/// left out, so a day and five minutes reads `1d 5m`.
+pub fn format_duration(duration: Duration) -> String {
+    let mut remaining = duration.as_secs();
+    let Some(first) = UNITS.iter().position(|(size, _)| remaining >= *size) else {
+        return "0s".to_string();
+    };
+    let mut parts = Vec::new();
+    for (size, suffix) in &UNITS[first..(first + 3).min(UNITS.len())] {
+        let count = remaining / size;
+        remaining %= size;
+        if count > 0 {
+            parts.push(format!("{count}{suffix}"));
+        }
+    }
+    parts.join(" ")
+}
+
+impl ServiceRuntimeState {
+    /// The state as it will read `elapsed` later if nothing changes: a running
+    /// service's uptime and a stopped service's downtime keep growing.
+    pub fn advanced_by(&self, elapsed: Duration) -> Self {
+        match self {
+            ServiceRuntimeState::Running { pid, uptime } => ServiceRuntimeState::Running {
+                pid: *pid,
+                uptime: *uptime + elapsed,
+            },
+            ServiceRuntimeState::Down { since, normally_up } => ServiceRuntimeState::Down {
+                since: *since + elapsed,
+                normally_up: *normally_up,
+            },
+            other => other.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_duration;
+    use crate::ServiceRuntimeState;
+    use std::time::Duration;
+
+    #[test]
+    fn formats_largest_three_units() {
+        let secs = Duration::from_secs;
+        assert_eq!(format_duration(Duration::ZERO), "0s");
+        assert_eq!(format_duration(secs(42)), "42s");
+        assert_eq!(format_duration(secs(12 * 60 + 5)), "12m 5s");
+        assert_eq!(format_duration(secs(4 * 3600 + 12 * 60 + 5)), "4h 12m 5s");
+        assert_eq!(
+            format_duration(secs(3 * 86_400 + 4 * 3600 + 12 * 60 + 59)),
+            "3d 4h 12m"
+        );
+        assert_eq!(format_duration(secs(86_400 + 5 * 60)), "1d 5m");
+        assert_eq!(format_duration(secs(7200)), "2h");
+
+        let running = ServiceRuntimeState::Running {
+            pid: 7,
+            uptime: secs(60),
+        };
+        assert!(matches!(
+            running.advanced_by(secs(5)),
+            ServiceRuntimeState::Running { pid: 7, uptime } if uptime == secs(65)
+        ));
+    }
+}
@@ -0,0 +1,386 @@
+//! The environment a service starts with, worked out from its definition and
+//! checked against the running process, and editing of its env dir.
+use crate::archive::unique_suffix;
+use crate::{Result, RunScriptInfo, ServiceError, ServiceManager, remove_if_present};
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Where an expected variable comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvSource {
+    /// A file in an env dir read by `chpst -e` or `envdir`.
+    EnvDir(PathBuf),
+    /// An `export` in the sourced `conf` file.
+    ConfFile(PathBuf),
+}
+
+/// A variable the run script sets or removes before exec'ing the service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvVariable {
+    pub name: String,
+    /// `None` when an empty env dir file removes the variable.
+    pub value: Option<String>,
+    pub source: EnvSource,
+    /// The value contains shell expansions, so it cannot be compared literally.
+    pub dynamic: bool,
+}
+
+/// An expected variable the running process does not have as expected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvDiscrepancy {
+    pub name: String,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EffectiveEnvironment {
+    /// Variables in the order they take effect; later entries win.
+    pub variables: Vec<EnvVariable>,
+    /// Environment of the running main process, when it could be read.
+    pub running: Option<HashMap<String, String>>,
+    /// Differences between `variables` and `running`. Variables the process
+    /// inherited from runsv are not reported.
+    pub discrepancies: Vec<EnvDiscrepancy>,
+}
+
+/// A service's env dir, one file per variable as read by `chpst -e` and `envdir`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvDir {
+    pub path: PathBuf,
+    /// The run script loads this directory. When it does not, changes have no
+    /// effect until the script gains a `chpst -e ./env`.
+    pub used_by_run_script: bool,
+    /// Variables sorted by name.
+    pub variables: Vec<EnvVariable>,
+}
+
+impl ServiceManager {
+    /// The env dir the run script loads, or `./env` in the definition when it
+    /// loads none. Only a directory inside the definition is managed here.
+    pub fn env_dir(&self, service: &str) -> Result<EnvDir> {
+        let definition_path = self.existing_definition(service)?;
+        let script = self.run_script_info(service)?;
+        let used_by_run_script = script.env_dir.is_some();
+        let path = script
+            .env_dir
+            .unwrap_or_else(|| definition_path.join("env"));
+        let root = fs::canonicalize(&definition_path)
+            .map_err(|err| ServiceError::from_io(&definition_path, err))?;
+        let resolved = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if !resolved.starts_with(&root) {
+            return Err(ServiceError::InvalidServiceFile(format!(
+                "{}: the env dir is outside the definition",
+                path.display()
+            )));
+        }
+        Ok(EnvDir {
+            variables: read_env_dir(&path),
+            path,
+            used_by_run_script,
+        })
+    }
+
+    /// Set `name` to `value` in the service's env dir, creating the directory
+    /// if needed. An empty `value` makes `chpst -e` remove the variable from
+    /// the environment the service inherits. Takes effect at the next start.
+    pub fn set_env_variable(&self, service: &str, name: &str, value: &str) -> Result<EnvDir> {
+        let dir = self.env_dir(service)?;
+        let file = dir.path.join(checked_env_name(name)?);
+        fs::create_dir_all(&dir.path).map_err(|err| ServiceError::from_io(&dir.path, err))?;
+        let mode = fs::metadata(&file)
+            .map(|meta| meta.permissions().mode() & 0o7777)
+            .unwrap_or(0o644);
+        // envdir reads the first line only and turns NULs back into newlines.
+        let contents = if value.is_empty() {
+            String::new()
+        } else {
+            format!("{}\n", value.replace('\n', "\0"))
+        };
+        let temp_path = dir.path.join(format!(".runkit-env-{}", unique_suffix()));
+        fs::write(&temp_path, contents)
+            .and_then(|()| fs::set_permissions(&temp_path, fs::Permissions::from_mode(mode)))
+            .and_then(|()| fs::rename(&temp_path, &file))
+            .map_err(|err| {
+                let _ = fs::remove_file(&temp_path);
+                ServiceError::from_io(&file, err)
+            })?;
+        self.env_dir(service)
+    }
+
+    /// Remove `name` from the service's env dir, so the service inherits it
+    /// from runsv again. Removing a variable that is not set is not an error.
+    pub fn unset_env_variable(&self, service: &str, name: &str) -> Result<EnvDir> {
+        let dir = self.env_dir(service)?;
+        remove_if_present(&dir.path.join(checked_env_name(name)?))?;
+        self.env_dir(service)
+    }
+
+    /// Work out the variables the run script's `conf` and env dir set, and for a
+    /// running service compare them with `/proc/<pid>/environ`, which needs root
+    /// for services that drop privileges to another user.
+    pub fn effective_environment(&self, service: &str) -> Result<EffectiveEnvironment> {
+        let script = self.run_script_info(service)?;
+        let variables = expected_variables(&script);
+        let running = self
+            .status(service)?
+            .pid()
+            .and_then(|pid| read_environ(&PathBuf::from(format!("/proc/{pid}/environ"))));
+        let discrepancies = running
+            .as_ref()
+            .map(|running| compare(&variables, running))
+            .unwrap_or_default();
+        Ok(EffectiveEnvironment {
+            variables,
+            running,
+            discrepancies,
+        })
+    }
+}
+
+fn expected_variables(script: &RunScriptInfo) -> Vec<EnvVariable> {
+    let mut variables = Vec::new();
+    if let Some(conf) = &script.config_file
+        && let Ok(contents) = fs::read_to_string(conf)
+    {
+        variables.extend(parse_conf_exports(&contents, conf));
+    }
+    if let Some(dir) = &script.env_dir {
+        variables.extend(read_env_dir(dir));
+    }
+    variables
+}
+
+/// Exported assignments from a sourced shell file: `export NAME=value`, or a
+/// plain `NAME=value` that a later `export NAME` line exports.
+fn parse_conf_exports(contents: &str, path: &Path) -> Vec<EnvVariable> {
+    let mut assigned: HashMap<String, String> = HashMap::new();
+    let mut exported = Vec::new();
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (is_export, rest) = match line.strip_prefix("export ") {
+            Some(rest) => (true, rest.trim()),
+            None => (false, line),
+        };
+        match rest.split_once('=') {
+            Some((name, value)) if is_name(name) => {
+                let value = unquote(value.trim());
+                if is_export {
+                    exported.push((name.to_string(), value.clone()));
+                }
+                assigned.insert(name.to_string(), value);
+            }
+            None if is_export => {
+                for name in rest.split_whitespace().filter(|name| is_name(name)) {
+                    if let Some(value) = assigned.get(name) {
+                        exported.push((name.to_string(), value.clone()));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    exported
+        .into_iter()
+        .map(|(name, value)| EnvVariable {
+            name,
+            dynamic: value.contains(['$', '`']),
+            value: Some(value),
+            source: EnvSource::ConfFile(path.to_path_buf()),
+        })
+        .collect()
+}
+
+/// Variables set by an env dir, following `envdir`: the first line of each
+/// file with trailing spaces and tabs removed and NULs turned into newlines.
+fn read_env_dir(dir: &Path) -> Vec<EnvVariable> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut variables: Vec<EnvVariable> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_string();
+            if name.starts_with('.') || name.contains('=') {
+                return None;
+            }
+            let raw = fs::read(entry.path()).ok()?;
+            let value = if raw.is_empty() {
+                None
+            } else {
+                let line = raw.split(|byte| *byte == b'\n').next().unwrap_or_default();
+                let value = String::from_utf8_lossy(line)
+                    .trim_end_matches([' ', '\t'])
+                    .replace('\0', "\n");
+                Some(value)
+            };
+            Some(EnvVariable {
+                name,
+                value,
+                source: EnvSource::EnvDir(dir.to_path_buf()),
+                dynamic: false,
+            })
+        })
+        .collect();
+    variables.sort_by(|a, b| a.name.cmp(&b.name));
+    variables
+}
+
+fn read_environ(path: &Path) -> Option<HashMap<String, String>> {
+    let raw = fs::read(path).ok()?;
+    Some(
+        raw.split(|byte| *byte == 0)
+            .filter_map(|pair| {
+                let pair = String::from_utf8_lossy(pair);
+                let (name, value) = pair.split_once('=')?;
+                Some((name.to_string(), value.to_string()))
+            })
+            .collect(),
+    )
+}
+
+fn compare(variables: &[EnvVariable], running: &HashMap<String, String>) -> Vec<EnvDiscrepancy> {
+    // The last definition of a name is the one the process should have.
+    let mut expected: Vec<&EnvVariable> = Vec::new();
+    for variable in variables {
+        expected.retain(|seen| seen.name != variable.name);
+        expected.push(variable);
+    }
+    expected
+        .into_iter()
+        .filter(|variable| !variable.dynamic)
+        .filter_map(|variable| {
+            let actual = running.get(&variable.name);
+            (actual != variable.value.as_ref()).then(|| EnvDiscrepancy {
+                name: variable.name.clone(),
+                expected: variable.value.clone(),
+                actual: actual.cloned(),
+            })
+        })
+        .collect()
+}
+
+fn checked_env_name(name: &str) -> Result<&str> {
+    if is_name(name) {
+        Ok(name)
+    } else {
+        Err(ServiceError::InvalidServiceFile(format!(
+            "env/{name}: not a valid variable name"
+        )))
+    }
+}
+
+pub(crate) fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn unquote(value: &str) -> String {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|rest| rest.strip_suffix(quote))
+        {
+            return inner.to_string();
+        }
+    }
+    value.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EnvSource, compare, parse_conf_exports, read_env_dir};
+    use crate::testing::SandboxFixture;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::Path;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn derives_environment_and_flags_discrepancies() {
+        let conf = Path::new("/etc/sv/app/conf");
+        let mut variables = parse_conf_exports(
+            "OPTS=\"-v\"\nexport LANG=C.UTF-8\nexport HOME='/var/lib/app'\nexport PATH=$PATH:/opt/bin\nexport OPTS\n",
+            conf,
+        );
+        let names: Vec<&str> = variables.iter().map(|var| var.name.as_str()).collect();
+        assert_eq!(names, ["LANG", "HOME", "PATH", "OPTS"]);
+        assert_eq!(variables[1].value.as_deref(), Some("/var/lib/app"));
+        assert!(variables[2].dynamic);
+        assert_eq!(variables[3].value.as_deref(), Some("-v"));
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let env_dir =
+            std::env::temp_dir().join(format!("runkit-envdir-{}-{nanos}", std::process::id()));
+        fs::create_dir_all(&env_dir).unwrap();
+        fs::write(env_dir.join("LANG"), "en_US.UTF-8  \nignored\n").unwrap();
+        fs::write(env_dir.join("TZ"), "").unwrap();
+        let from_dir = read_env_dir(&env_dir);
+        assert_eq!(from_dir[0].value.as_deref(), Some("en_US.UTF-8"));
+        assert_eq!(from_dir[0].source, EnvSource::EnvDir(env_dir.clone()));
+        assert_eq!(from_dir[1].value, None);
+        variables.extend(from_dir);
+
+        let running: HashMap<String, String> = [
+            ("LANG", "C.UTF-8"),
+            ("HOME", "/var/lib/app"),
+            ("PATH", "/usr/bin"),
+            ("OPTS", "-v"),
+            ("TZ", "UTC"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+        let discrepancies = compare(&variables, &running);
+        let flagged: Vec<(&str, Option<&str>)> = discrepancies
+            .iter()
+            .map(|d| (d.name.as_str(), d.actual.as_deref()))
+            .collect();
+        assert_eq!(flagged, [("LANG", Some("C.UTF-8")), ("TZ", Some("UTC"))]);
+        let _ = fs::remove_dir_all(&env_dir);
+    }
+
+    #[test]
+    fn sets_and_unsets_env_dir_variables() {
+        let sandbox = SandboxFixture::new().unwrap();
+        sandbox
+            .add_service("web", "exec chpst -e ./env /usr/bin/web\n")
+            .unwrap();
+        let manager = sandbox.manager();
+
+        let dir = manager.env_dir("web").unwrap();
+        assert!(dir.used_by_run_script && dir.variables.is_empty());
+        manager
+            .set_env_variable("web", "RUST_LOG", "debug")
+            .unwrap();
+        let dir = manager
+            .set_env_variable("web", "BANNER", "two\nlines")
+            .unwrap();
+        let values: Vec<(&str, Option<&str>)> = dir
+            .variables
+            .iter()
+            .map(|var| (var.name.as_str(), var.value.as_deref()))
+            .collect();
+        assert_eq!(
+            values,
+            [("BANNER", Some("two\nlines")), ("RUST_LOG", Some("debug"))]
+        );
+
+        let dir = manager.unset_env_variable("web", "BANNER").unwrap();
+        assert_eq!(dir.variables.len(), 1);
+        manager.unset_env_variable("web", "BANNER").unwrap();
+        assert!(manager.set_env_variable("web", "BAD NAME", "x").is_err());
+        assert!(manager.set_env_variable("web", "../run", "x").is_err());
+    }
+}
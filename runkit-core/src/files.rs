@@ -0,0 +1,314 @@
+//! Reading and editing single files of a definition, such as `run` and `conf`.
+//!
+//! Paths are relative to the definition directory and must stay inside it,
+//! symlinks included. Each save keeps the previous version in the hidden
+//! `.runkit-backups` directory of the definition.
+use crate::archive::unique_suffix;
+use crate::create::binary_problem;
+use crate::{Result, RunScriptInfo, ServiceError, ServiceManager};
+use chrono::Local;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+/// Directory inside a definition that holds earlier versions of edited files.
+pub(crate) const BACKUP_DIR: &str = ".runkit-backups";
+
+/// Files runsv runs or a run script sources, checked with `sh -n` on save.
+const SHELL_FILES: [&str; 6] = ["run", "finish", "check", "conf", "log/run", "log/finish"];
+
+/// Files runsv executes, created executable.
+const EXECUTABLE_FILES: [&str; 5] = ["run", "finish", "check", "log/run", "log/finish"];
+
+/// A file of a definition as read by [`ServiceManager::read_service_file`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceFile {
+    pub path: PathBuf,
+    pub contents: String,
+    /// Permission bits, e.g. `0o755`.
+    pub mode: u32,
+}
+
+/// What [`ServiceManager::write_service_file`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceFileWrite {
+    pub path: PathBuf,
+    /// Copy of the previous contents; `None` when the file is new.
+    pub backup: Option<PathBuf>,
+    /// Problems that did not stop the save, e.g. a missing program.
+    pub warnings: Vec<String>,
+}
+
+impl ServiceManager {
+    pub fn read_service_file(&self, service: &str, file: &str) -> Result<ServiceFile> {
+        let path = self.service_file_path(service, file)?;
+        let contents = fs::read(&path).map_err(|err| ServiceError::from_io(&path, err))?;
+        let contents = String::from_utf8(contents)
+            .map_err(|_| ServiceError::InvalidServiceFile(format!("{file}: not a text file")))?;
+        let mode = fs::metadata(&path)
+            .map_err(|err| ServiceError::from_io(&path, err))?
+            .permissions()
+            .mode()
+            & 0o7777;
+        Ok(ServiceFile {
+            path,
+            contents,
+            mode,
+        })
+    }
+
+    /// Replace `file` of the definition with `contents`, keeping its mode.
+    ///
+    /// Shell files are checked with `sh -n` first and a syntax error refuses the
+    /// save unless `force` is set. The old contents are copied to
+    /// `.runkit-backups` and the new ones written to a temporary file that is
+    /// renamed over the original, so runsv never sees half a script.
+    pub fn write_service_file(
+        &self,
+        service: &str,
+        file: &str,
+        contents: &str,
+        force: bool,
+    ) -> Result<ServiceFileWrite> {
+        let definition_path = self.existing_definition(service)?;
+        let path = self.service_file_path(service, file)?;
+        let parent = path
+            .parent()
+            .ok_or_else(|| ServiceError::InvalidServiceFile(file.to_string()))?;
+        let existing = fs::metadata(&path).ok();
+        if existing.as_ref().is_some_and(|meta| !meta.is_file()) {
+            return Err(ServiceError::InvalidServiceFile(format!(
+                "{file}: not a regular file"
+            )));
+        }
+        let mode = existing
+            .as_ref()
+            .map(|meta| meta.permissions().mode() & 0o7777)
+            .unwrap_or(if EXECUTABLE_FILES.contains(&file) {
+                0o755
+            } else {
+                0o644
+            });
+
+        let temp_path = parent.join(format!(".runkit-edit-{}", unique_suffix()));
+        fs::write(&temp_path, contents)
+            .and_then(|()| fs::set_permissions(&temp_path, fs::Permissions::from_mode(mode)))
+            .map_err(|err| ServiceError::from_io(&temp_path, err))?;
+        let checked = if is_shell_file(file, contents) && !force {
+            shell_syntax_error(&temp_path)
+        } else {
+            Ok(None)
+        };
+        let syntax_error = match checked {
+            Ok(error) => error,
+            Err(err) => {
+                let _ = fs::remove_file(&temp_path);
+                return Err(err);
+            }
+        };
+        if let Some(message) = syntax_error {
+            let _ = fs::remove_file(&temp_path);
+            return Err(ServiceError::ScriptSyntax {
+                file: file.to_string(),
+                message,
+            });
+        }
+
+        let backup = match existing {
+            Some(_) => Some(backup_file(&definition_path, file, &path)?),
+            None => None,
+        };
+        fs::rename(&temp_path, &path).map_err(|err| {
+            let _ = fs::remove_file(&temp_path);
+            ServiceError::from_io(&path, err)
+        })?;
+
+        let mut warnings = Vec::new();
+        if matches!(file, "run" | "log/run") {
+            let script = RunScriptInfo::parse(contents, &definition_path);
+            match script.binary.as_deref() {
+                None => warnings.push(format!(
+                    "{file} does not exec a program, so runsv supervises the shell instead"
+                )),
+                Some(binary) => warnings.extend(binary_problem(binary)),
+            }
+        }
+        if EXECUTABLE_FILES.contains(&file) && mode & 0o111 == 0 {
+            warnings.push(format!("{file} is not executable, so runsv cannot run it"));
+        }
+        Ok(ServiceFileWrite {
+            path,
+            backup,
+            warnings,
+        })
+    }
+
+    /// Absolute path of `file` within the definition of `service`, refused when
+    /// it leaves the definition directory or touches runsv's or runkit's state.
+    fn service_file_path(&self, service: &str, file: &str) -> Result<PathBuf> {
+        let definition_path = self.existing_definition(service)?;
+        let relative = Path::new(file);
+        let refused = |reason: &str| ServiceError::InvalidServiceFile(format!("{file}: {reason}"));
+        if file.is_empty()
+            || !relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(refused("must be a path inside the definition"));
+        }
+        if relative.components().any(|component| {
+            let name = component.as_os_str().to_string_lossy();
+            name == "supervise" || name.starts_with(".runkit-")
+        }) {
+            return Err(refused("belongs to runsv or runkit and cannot be edited"));
+        }
+
+        let root = fs::canonicalize(&definition_path)
+            .map_err(|err| ServiceError::from_io(&definition_path, err))?;
+        let path = definition_path.join(relative);
+        // A file that does not exist yet is placed by its directory.
+        let resolved = match fs::canonicalize(&path) {
+            Ok(resolved) => resolved,
+            Err(_) => {
+                let parent = path.parent().unwrap_or(&definition_path);
+                let parent =
+                    fs::canonicalize(parent).map_err(|err| ServiceError::from_io(parent, err))?;
+                parent.join(relative.file_name().unwrap_or_default())
+            }
+        };
+        if !resolved.starts_with(&root) {
+            return Err(refused(&format!(
+                "links outside the definition, to {}",
+                resolved.display()
+            )));
+        }
+        Ok(resolved)
+    }
+}
+
+fn is_shell_file(file: &str, contents: &str) -> bool {
+    let shebang = contents
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("#!"));
+    match shebang {
+        Some(interpreter) => shell_for(interpreter).is_some(),
+        None => SHELL_FILES.contains(&file),
+    }
+}
+
+/// Shell that understands `-n` for the interpreter line of a script.
+fn shell_for(interpreter: &str) -> Option<&'static str> {
+    let mut words = interpreter.split_whitespace();
+    let mut program = words.next()?;
+    if Path::new(program).file_name()?.to_str()? == "env" {
+        program = words.next()?;
+    }
+    match Path::new(program).file_name()?.to_str()? {
+        "sh" | "dash" | "ash" => Some("sh"),
+        "bash" => Some("bash"),
+        _ => None,
+    }
+}
+
+/// `Ok(Some(message))` when the shell reports a syntax error in `path`.
+fn shell_syntax_error(path: &Path) -> Result<Option<String>> {
+    let contents = fs::read_to_string(path).map_err(|err| ServiceError::from_io(path, err))?;
+    let shell = contents
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("#!"))
+        .and_then(shell_for)
+        .unwrap_or("sh");
+    let output = Command::new(shell)
+        .arg("-n")
+        .arg(path)
+        .output()
+        .map_err(|err| ServiceError::from_io(shell, err))?;
+    if output.status.success() {
+        return Ok(None);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // The temporary file name means nothing to the user; keep the line number.
+    let message = stderr
+        .lines()
+        .map(|line| line.replace(&format!("{}: ", path.display()), ""))
+        .collect::<Vec<_>>()
+        .join("; ");
+    Ok(Some(if message.is_empty() {
+        format!("{shell} -n exited with {}", output.status)
+    } else {
+        message
+    }))
+}
+
+/// Copy `path` to `.runkit-backups/<file>.<timestamp>` inside the definition.
+fn backup_file(definition_path: &Path, file: &str, path: &Path) -> Result<PathBuf> {
+    let stamp = Local::now().format("%Y%m%d-%H%M%S%.3f");
+    let backup = definition_path
+        .join(BACKUP_DIR)
+        .join(format!("{file}.{stamp}"));
+    if let Some(dir) = backup.parent() {
+        fs::create_dir_all(dir).map_err(|err| ServiceError::from_io(dir, err))?;
+    }
+    fs::copy(path, &backup).map_err(|err| ServiceError::from_io(&backup, err))?;
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ServiceError;
+    use crate::testing::SandboxFixture;
+    use std::fs;
+    use std::os::unix::fs::{PermissionsExt, symlink};
+
+    #[test]
+    fn edits_files_with_backup_and_syntax_check() {
+        let sandbox = SandboxFixture::new().unwrap();
+        sandbox.add_service("sshd", "exec sshd -D\n").unwrap();
+        let manager = sandbox.manager();
+        let definition = manager.definitions_dir().join("sshd");
+
+        let run = manager.read_service_file("sshd", "run").unwrap();
+        assert!(run.contents.contains("exec sshd -D"));
+
+        let broken = manager.write_service_file("sshd", "run", "#!/bin/sh\nif true\n", false);
+        assert!(matches!(broken, Err(ServiceError::ScriptSyntax { .. })));
+        assert_eq!(manager.read_service_file("sshd", "run").unwrap(), run);
+
+        let written = manager
+            .write_service_file(
+                "sshd",
+                "run",
+                "#!/bin/sh\nexec /bin/sh -c 'sleep 1'\n",
+                false,
+            )
+            .unwrap();
+        assert!(written.warnings.is_empty(), "{:?}", written.warnings);
+        let backup = written.backup.unwrap();
+        assert_eq!(fs::read_to_string(&backup).unwrap(), run.contents);
+        assert!(backup.starts_with(definition.join(".runkit-backups")));
+        let mode = fs::metadata(definition.join("run"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, run.mode & 0o777);
+
+        let conf = manager
+            .write_service_file("sshd", "conf", "OPTS='-o x'\n", false)
+            .unwrap();
+        assert!(conf.backup.is_none());
+
+        symlink("/etc/hostname", definition.join("outside")).unwrap();
+        for refused in ["../other/run", "/etc/passwd", "supervise/stat", "outside"] {
+            assert!(
+                matches!(
+                    manager.read_service_file("sshd", refused),
+                    Err(ServiceError::InvalidServiceFile(_))
+                ),
+                "{refused} was not refused"
+            );
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! Consistency checks between the definitions and enabled directories.
+use crate::{Result, ServiceError, ServiceManager};
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Kind of inconsistency found by [`ServiceManager::check_health`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthIssueKind {
+    /// Enabled link whose target no longer exists.
+    DanglingLink,
+    /// Enabled link that resolves outside the definitions directory.
+    ExternalLink,
+    /// Definition without a `run` script.
+    MissingRunScript,
+    /// Definition whose `run` script is not executable.
+    RunScriptNotExecutable,
+    /// Definition linked into several runlevels, or twice into one.
+    DuplicateEnable,
+}
+
+impl HealthIssueKind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "dangling_link" => Some(HealthIssueKind::DanglingLink),
+            "external_link" => Some(HealthIssueKind::ExternalLink),
+            "missing_run_script" => Some(HealthIssueKind::MissingRunScript),
+            "run_script_not_executable" => Some(HealthIssueKind::RunScriptNotExecutable),
+            "duplicate_enable" => Some(HealthIssueKind::DuplicateEnable),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HealthIssueKind::DanglingLink => "dangling_link",
+            HealthIssueKind::ExternalLink => "external_link",
+            HealthIssueKind::MissingRunScript => "missing_run_script",
+            HealthIssueKind::RunScriptNotExecutable => "run_script_not_executable",
+            HealthIssueKind::DuplicateEnable => "duplicate_enable",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthIssue {
+    pub service: String,
+    pub kind: HealthIssueKind,
+    /// The offending link or definition path.
+    pub path: PathBuf,
+    /// Human readable explanation.
+    pub detail: String,
+}
+
+/// Result of a consistency check; empty when everything lines up.
+#[derive(Debug, Clone, Default)]
+pub struct ServiceHealth {
+    pub issues: Vec<HealthIssue>,
+}
+
+impl ServiceHealth {
+    pub fn is_healthy(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn issues_for<'a>(&'a self, service: &'a str) -> impl Iterator<Item = &'a HealthIssue> {
+        self.issues
+            .iter()
+            .filter(move |issue| issue.service == service)
+    }
+}
+
+impl ServiceManager {
+    /// Report enabled links that are dangling or point outside the definitions
+    /// directory, definitions without an executable `run` script, and services
+    /// enabled more than once across runlevels.
+    pub fn check_health(&self) -> Result<ServiceHealth> {
+        let mut issues = Vec::new();
+        self.check_enabled_links(&mut issues)?;
+        self.check_run_scripts(&mut issues)?;
+        self.check_duplicate_enables(&mut issues)?;
+        issues.sort_by(|a, b| a.service.cmp(&b.service));
+        Ok(ServiceHealth { issues })
+    }
+
+    fn check_enabled_links(&self, issues: &mut Vec<HealthIssue>) -> Result<()> {
+        let definitions_root = fs::canonicalize(&self.definitions_dir)
+            .map_err(|err| ServiceError::from_io(&self.definitions_dir, err))?;
+        let entries = fs::read_dir(&self.enabled_dir)
+            .map_err(|err| ServiceError::from_io(&self.enabled_dir, err))?;
+
+        for entry in entries {
+            let entry = entry.map_err(|err| ServiceError::from_io(&self.enabled_dir, err))?;
+            let Some(service) = visible_name(&entry.file_name()) else {
+                continue;
+            };
+            let path = entry.path();
+            let Ok(target) = fs::read_link(&path) else {
+                // A plain directory is a valid, if unusual, way to enable a service.
+                continue;
+            };
+            let target = if target.is_absolute() {
+                target
+            } else {
+                self.enabled_dir.join(target)
+            };
+
+            match fs::canonicalize(&target) {
+                Err(_) => issues.push(HealthIssue {
+                    service,
+                    kind: HealthIssueKind::DanglingLink,
+                    detail: format!("link target {} does not exist", target.display()),
+                    path,
+                }),
+                Ok(resolved) if !resolved.starts_with(&definitions_root) => {
+                    issues.push(HealthIssue {
+                        service,
+                        kind: HealthIssueKind::ExternalLink,
+                        detail: format!(
+                            "link points to {} outside {}",
+                            resolved.display(),
+                            self.definitions_dir.display()
+                        ),
+                        path,
+                    })
+                }
+                Ok(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn check_duplicate_enables(&self, issues: &mut Vec<HealthIssue>) -> Result<()> {
+        for duplicate in self.duplicate_enables()? {
+            let detail = if duplicate.double_supervised {
+                format!(
+                    "linked {} times; each link starts a runsv competing for the same supervise directory",
+                    duplicate.links.len()
+                )
+            } else {
+                format!("enabled in runlevels {}", duplicate.runlevels().join(", "))
+            };
+            issues.push(HealthIssue {
+                kind: HealthIssueKind::DuplicateEnable,
+                path: duplicate.links[0].path.clone(),
+                service: duplicate.service,
+                detail,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_run_scripts(&self, issues: &mut Vec<HealthIssue>) -> Result<()> {
+        let entries = fs::read_dir(&self.definitions_dir)
+            .map_err(|err| ServiceError::from_io(&self.definitions_dir, err))?;
+
+        for entry in entries {
+            let entry = entry.map_err(|err| ServiceError::from_io(&self.definitions_dir, err))?;
+            let Some(service) = visible_name(&entry.file_name()) else {
+                continue;
+            };
+            let path = entry.path();
+            if !path.is_dir() {
+                continue;
+            }
+            if let Some((kind, detail)) = run_script_problem(&path.join("run")) {
+                issues.push(HealthIssue {
+                    service,
+                    kind,
+                    path,
+                    detail,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn run_script_problem(run: &Path) -> Option<(HealthIssueKind, String)> {
+    match fs::metadata(run) {
+        Err(_) => Some((
+            HealthIssueKind::MissingRunScript,
+            "definition has no run script".to_string(),
+        )),
+        Ok(meta) if !meta.is_file() || meta.permissions().mode() & 0o111 == 0 => Some((
+            HealthIssueKind::RunScriptNotExecutable,
+            "run script is not executable".to_string(),
+        )),
+        Ok(_) => None,
+    }
+}
+
+fn visible_name(name: &OsStr) -> Option<String> {
+    let name = name.to_str()?;
+    (!name.starts_with('.')).then(|| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HealthIssueKind;
+    use crate::ServiceManager;
+    use std::fs;
+    use std::os::unix::fs::{PermissionsExt, symlink};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn reports_broken_links_and_run_scripts() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root =
+            std::env::temp_dir().join(format!("runkit-health-{}-{nanos}", std::process::id()));
+        let definitions = root.join("sv");
+        let enabled = root.join("service");
+        let elsewhere = root.join("elsewhere");
+        for dir in [&definitions, &enabled, &elsewhere] {
+            fs::create_dir_all(dir).unwrap();
+        }
+
+        let good = definitions.join("good");
+        fs::create_dir(&good).unwrap();
+        fs::write(good.join("run"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(good.join("run"), fs::Permissions::from_mode(0o755)).unwrap();
+        fs::create_dir(definitions.join("norun")).unwrap();
+        let noexec = definitions.join("noexec");
+        fs::create_dir(&noexec).unwrap();
+        fs::write(noexec.join("run"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(noexec.join("run"), fs::Permissions::from_mode(0o644)).unwrap();
+
+        symlink(&good, enabled.join("good")).unwrap();
+        symlink(definitions.join("gone"), enabled.join("gone")).unwrap();
+        symlink(&elsewhere, enabled.join("stray")).unwrap();
+
+        let health = ServiceManager::new(&definitions, &enabled)
+            .with_runsvdir_root(root.join("runsvdir"))
+            .check_health()
+            .unwrap();
+        let found: Vec<(&str, HealthIssueKind)> = health
+            .issues
+            .iter()
+            .map(|issue| (issue.service.as_str(), issue.kind))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("gone", HealthIssueKind::DanglingLink),
+                ("noexec", HealthIssueKind::RunScriptNotExecutable),
+                ("norun", HealthIssueKind::MissingRunScript),
+                ("stray", HealthIssueKind::ExternalLink),
+            ]
+        );
+        assert_eq!(health.issues_for("good").count(), 0);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
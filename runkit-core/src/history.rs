@@ -0,0 +1,332 @@
+//! Restart and crash history: the starts and exits runkitd observes, kept per
+//! service so a service that keeps crashing can be told apart from one that
+//! failed once.
+use crate::{RestartPolicy, Result, ServiceError, ServiceManager, ServiceRuntimeState};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Where each service's history is kept. Under `/run`, so it covers the
+/// current boot only.
+pub const DEFAULT_HISTORY_DIR: &str = "/run/runkit/history";
+
+/// A service that crashed this many times within [`FLAPPING_WINDOW`] is
+/// flapping.
+pub const FLAPPING_CRASHES: usize = 5;
+pub const FLAPPING_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// Events kept per service; older ones are dropped.
+const MAX_EVENTS: usize = 200;
+
+/// Exit codes reported in [`ServiceHistory::last_exit_codes`].
+const MAX_EXIT_CODES: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryEventKind {
+    Started,
+    /// Went down as asked, or with restarts turned off.
+    Stopped,
+    /// Exited although runit was meant to keep it up.
+    Crashed,
+}
+
+impl HistoryEventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HistoryEventKind::Started => "started",
+            HistoryEventKind::Stopped => "stopped",
+            HistoryEventKind::Crashed => "crashed",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "started" => Some(HistoryEventKind::Started),
+            "stopped" => Some(HistoryEventKind::Stopped),
+            "crashed" => Some(HistoryEventKind::Crashed),
+            _ => None,
+        }
+    }
+}
+
+/// One observed transition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEvent {
+    /// Unix time of the transition.
+    pub at: i64,
+    pub kind: HistoryEventKind,
+    /// Process that started, for [`HistoryEventKind::Started`].
+    pub pid: Option<u32>,
+    /// Exit code, when runit reported one.
+    pub exit_code: Option<i32>,
+}
+
+impl HistoryEvent {
+    fn to_line(&self) -> String {
+        let field = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+        format!(
+            "{} {} {} {}\n",
+            self.at,
+            self.kind.as_str(),
+            field(self.pid.map(|pid| pid.to_string())),
+            field(self.exit_code.map(|code| code.to_string()))
+        )
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        Some(HistoryEvent {
+            at: fields.next()?.parse().ok()?,
+            kind: HistoryEventKind::parse(fields.next()?)?,
+            pid: fields.next()?.parse().ok(),
+            exit_code: fields.next()?.parse().ok(),
+        })
+    }
+}
+
+/// What happened to a service within a window of time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceHistory {
+    /// Events within the window, oldest first.
+    pub events: Vec<HistoryEvent>,
+    /// Starts within the window, not counting the first start of the boot.
+    pub restarts: usize,
+    pub crashes: usize,
+    /// Most recent exit codes runit reported, newest first.
+    pub last_exit_codes: Vec<i32>,
+    /// At least [`FLAPPING_CRASHES`] crashes within [`FLAPPING_WINDOW`].
+    pub flapping: bool,
+}
+
+impl ServiceHistory {
+    fn from_events(events: Vec<HistoryEvent>, window: Duration, now: i64) -> Self {
+        let since = now.saturating_sub(window.as_secs() as i64);
+        let flapping_since = now.saturating_sub(FLAPPING_WINDOW.as_secs() as i64);
+        let first_start = events
+            .iter()
+            .position(|event| event.kind == HistoryEventKind::Started);
+        let restarts = events
+            .iter()
+            .enumerate()
+            .filter(|(index, event)| {
+                event.kind == HistoryEventKind::Started
+                    && Some(*index) != first_start
+                    && event.at >= since
+            })
+            .count();
+        let crashes = |since: i64| {
+            events
+                .iter()
+                .filter(|event| event.kind == HistoryEventKind::Crashed && event.at >= since)
+                .count()
+        };
+        ServiceHistory {
+            restarts,
+            crashes: crashes(since),
+            last_exit_codes: events
+                .iter()
+                .rev()
+                .filter_map(|event| event.exit_code)
+                .take(MAX_EXIT_CODES)
+                .collect(),
+            flapping: crashes(flapping_since) >= FLAPPING_CRASHES,
+            events: events
+                .into_iter()
+                .filter(|event| event.at >= since)
+                .collect(),
+        }
+    }
+}
+
+impl ServiceManager {
+    pub fn with_history_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.history_dir = Some(dir.into());
+        self
+    }
+
+    /// Record the transition that led to `state`, if the last recorded event
+    /// does not already account for it. A different pid than the one last
+    /// seen starting counts as a crash and a start, since runsv may restart
+    /// a service faster than its status can be read.
+    pub fn record_state(
+        &self,
+        service: &str,
+        state: &ServiceRuntimeState,
+        restart_policy: RestartPolicy,
+    ) -> Result<Vec<HistoryEvent>> {
+        self.validate_service_name(service)?;
+        let Some(dir) = self.history_dir.as_deref() else {
+            return Ok(Vec::new());
+        };
+        let now = unix_now();
+        let last = self.read_history(service)?.pop();
+        let last_pid = last
+            .as_ref()
+            .filter(|event| event.kind == HistoryEventKind::Started)
+            .and_then(|event| event.pid);
+        let exit = |kind, exit_code| HistoryEvent {
+            at: now,
+            kind,
+            pid: None,
+            exit_code,
+        };
+
+        let mut events = Vec::new();
+        match state {
+            ServiceRuntimeState::Running { pid, uptime } if last_pid != Some(*pid) => {
+                if last_pid.is_some() {
+                    events.push(exit(HistoryEventKind::Crashed, None));
+                }
+                events.push(HistoryEvent {
+                    at: now.saturating_sub(uptime.as_secs() as i64),
+                    kind: HistoryEventKind::Started,
+                    pid: Some(*pid),
+                    exit_code: None,
+                });
+            }
+            ServiceRuntimeState::Failed { exit_code, .. } if last_pid.is_some() => {
+                events.push(exit(HistoryEventKind::Crashed, Some(*exit_code)));
+            }
+            ServiceRuntimeState::Down { .. } if last_pid.is_some() => {
+                let kind = if restart_policy == RestartPolicy::Always {
+                    HistoryEventKind::Crashed
+                } else {
+                    HistoryEventKind::Stopped
+                };
+                events.push(exit(kind, None));
+            }
+            _ => {}
+        }
+        if events.is_empty() {
+            return Ok(events);
+        }
+
+        fs::create_dir_all(dir).map_err(|err| ServiceError::from_io(dir, err))?;
+        let path = dir.join(service);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|err| ServiceError::from_io(&path, err))?;
+        let lines: String = events.iter().map(HistoryEvent::to_line).collect();
+        file.write_all(lines.as_bytes())
+            .map_err(|err| ServiceError::from_io(&path, err))?;
+
+        let kept = self.read_history(service)?;
+        if kept.len() > MAX_EVENTS {
+            let trimmed: String = kept[kept.len() - MAX_EVENTS..]
+                .iter()
+                .map(HistoryEvent::to_line)
+                .collect();
+            fs::write(&path, trimmed).map_err(|err| ServiceError::from_io(&path, err))?;
+        }
+        Ok(events)
+    }
+
+    /// What happened to `service` within the last `window`.
+    pub fn service_history(&self, service: &str, window: Duration) -> Result<ServiceHistory> {
+        self.validate_service_name(service)?;
+        Ok(ServiceHistory::from_events(
+            self.read_history(service)?,
+            window,
+            unix_now(),
+        ))
+    }
+
+    fn read_history(&self, service: &str) -> Result<Vec<HistoryEvent>> {
+        let Some(dir) = self.history_dir.as_deref() else {
+            return Ok(Vec::new());
+        };
+        let path = dir.join(service);
+        match fs::read_to_string(&path) {
+            Ok(contents) => Ok(contents
+                .lines()
+                .filter_map(HistoryEvent::from_line)
+                .collect()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(ServiceError::from_io(&path, err)),
+        }
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FLAPPING_CRASHES, HistoryEventKind};
+    use crate::testing::SandboxFixture;
+    use crate::{RestartPolicy, ServiceRuntimeState};
+    use std::time::Duration;
+
+    fn running(pid: u32) -> ServiceRuntimeState {
+        ServiceRuntimeState::Running {
+            pid,
+            uptime: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn counts_restarts_and_detects_flapping() {
+        let sandbox = SandboxFixture::new().unwrap();
+        let manager = sandbox
+            .manager()
+            .clone()
+            .with_history_dir(sandbox.root().join("history"));
+        let hour = Duration::from_secs(3600);
+
+        manager
+            .record_state("web", &running(100), RestartPolicy::Always)
+            .unwrap();
+        assert!(
+            manager
+                .record_state("web", &running(100), RestartPolicy::Always)
+                .unwrap()
+                .is_empty()
+        );
+        let failed = ServiceRuntimeState::Failed {
+            pid: 100,
+            uptime: Duration::from_secs(1),
+            exit_code: 2,
+        };
+        let recorded = manager
+            .record_state("web", &failed, RestartPolicy::Always)
+            .unwrap();
+        assert_eq!(recorded[0].kind, HistoryEventKind::Crashed);
+        assert_eq!(recorded[0].exit_code, Some(2));
+
+        let history = manager.service_history("web", hour).unwrap();
+        assert_eq!((history.restarts, history.crashes), (0, 1));
+        assert_eq!(history.last_exit_codes, [2]);
+        assert!(!history.flapping);
+
+        // A new pid without a down state in between is a crash and a restart.
+        for pid in 101..101 + FLAPPING_CRASHES as u32 {
+            manager
+                .record_state("web", &running(pid), RestartPolicy::Always)
+                .unwrap();
+        }
+        let history = manager.service_history("web", hour).unwrap();
+        assert_eq!(history.restarts, FLAPPING_CRASHES);
+        assert_eq!(history.crashes, FLAPPING_CRASHES);
+        assert!(history.flapping);
+
+        let down = ServiceRuntimeState::Down {
+            since: Duration::from_secs(1),
+            normally_up: true,
+        };
+        let recorded = manager
+            .record_state("web", &down, RestartPolicy::Never)
+            .unwrap();
+        assert_eq!(recorded[0].kind, HistoryEventKind::Stopped);
+        assert_eq!(
+            manager.service_history("other", hour).unwrap(),
+            Default::default()
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Portable snapshots of a machine's service definitions, and diffs between them.
+use crate::{Result, ServiceError, ServiceManager};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+
+/// The services a machine defines, keyed by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Inventory {
+    /// Machine the inventory was taken on, for labelling diffs.
+    #[serde(default)]
+    pub hostname: Option<String>,
+    pub services: BTreeMap<String, InventoryEntry>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InventoryEntry {
+    pub enabled: bool,
+    /// Contents of the `run` script; `None` when the definition has none.
+    #[serde(default)]
+    pub run_script: Option<String>,
+}
+
+/// Differences between two inventories, each list sorted by service name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InventoryDiff {
+    pub only_left: Vec<String>,
+    pub only_right: Vec<String>,
+    /// Services defined on both sides but enabled on only one.
+    pub enabled_differs: Vec<EnabledDifference>,
+    /// Services defined on both sides whose `run` scripts are not identical.
+    pub run_script_differs: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnabledDifference {
+    pub service: String,
+    pub left: bool,
+    pub right: bool,
+}
+
+impl InventoryDiff {
+    pub fn is_empty(&self) -> bool {
+        self.only_left.is_empty()
+            && self.only_right.is_empty()
+            && self.enabled_differs.is_empty()
+            && self.run_script_differs.is_empty()
+    }
+}
+
+impl Inventory {
+    /// Compare `self` (left) against `other` (right).
+    pub fn diff(&self, other: &Inventory) -> InventoryDiff {
+        let mut diff = InventoryDiff::default();
+        for (name, left) in &self.services {
+            let Some(right) = other.services.get(name) else {
+                diff.only_left.push(name.clone());
+                continue;
+            };
+            if left.enabled != right.enabled {
+                diff.enabled_differs.push(EnabledDifference {
+                    service: name.clone(),
+                    left: left.enabled,
+                    right: right.enabled,
+                });
+            }
+            if left.run_script != right.run_script {
+                diff.run_script_differs.push(name.clone());
+            }
+        }
+        diff.only_right = other
+            .services
+            .keys()
+            .filter(|name| !self.services.contains_key(*name))
+            .cloned()
+            .collect();
+        diff
+    }
+}
+
+impl ServiceManager {
+    /// Snapshot every definition: whether it is enabled and its `run` script.
+    pub fn inventory(&self) -> Result<Inventory> {
+        let entries = fs::read_dir(&self.definitions_dir)
+            .map_err(|err| ServiceError::from_io(&self.definitions_dir, err))?;
+        let mut services = BTreeMap::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(name) = path.file_name().and_then(OsStr::to_str) else {
+                continue;
+            };
+            if name.starts_with('.') || !path.is_dir() {
+                continue;
+            }
+            services.insert(
+                name.to_string(),
+                InventoryEntry {
+                    enabled: self.enabled_dir.join(name).exists(),
+                    run_script: fs::read_to_string(path.join("run")).ok(),
+                },
+            );
+        }
+        let hostname = fs::read_to_string("/proc/sys/kernel/hostname")
+            .ok()
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty());
+        Ok(Inventory { hostname, services })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EnabledDifference, Inventory, InventoryEntry};
+    use crate::testing::SandboxFixture;
+
+    fn entry(enabled: bool, run: &str) -> InventoryEntry {
+        InventoryEntry {
+            enabled,
+            run_script: Some(run.to_string()),
+        }
+    }
+
+    #[test]
+    fn diffs_services_enablement_and_run_scripts() {
+        let mut left = Inventory::default();
+        left.services
+            .insert("sshd".into(), entry(true, "exec sshd -D\n"));
+        left.services
+            .insert("dbus".into(), entry(true, "exec dbus-daemon\n"));
+        left.services
+            .insert("cron".into(), entry(false, "exec crond\n"));
+        let mut right = Inventory::default();
+        right
+            .services
+            .insert("sshd".into(), entry(false, "exec sshd -D\n"));
+        right
+            .services
+            .insert("dbus".into(), entry(true, "exec dbus-daemon --nofork\n"));
+        right
+            .services
+            .insert("nginx".into(), entry(true, "exec nginx\n"));
+
+        let diff = left.diff(&right);
+        assert_eq!(diff.only_left, ["cron"]);
+        assert_eq!(diff.only_right, ["nginx"]);
+        assert_eq!(
+            diff.enabled_differs,
+            [EnabledDifference {
+                service: "sshd".into(),
+                left: true,
+                right: false,
+            }]
+        );
+        assert_eq!(diff.run_script_differs, ["dbus"]);
+        assert!(left.diff(&left).is_empty());
+
+        let sandbox = SandboxFixture::new().unwrap();
+        sandbox.add_service("sshd", "exec sshd -D\n").unwrap();
+        sandbox.add_service("cron", "exec crond\n").unwrap();
+        sandbox.enable("sshd").unwrap();
+        let inventory = sandbox.manager().inventory().unwrap();
+        assert!(inventory.services["sshd"].enabled);
+        assert!(!inventory.services["cron"].enabled);
+        assert_eq!(
+            inventory.services["cron"].run_script.as_deref(),
+            Some("exec crond\n")
+        );
+    }
+}
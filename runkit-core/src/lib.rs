@@ -1,17 +1,137 @@
 //! Core domain layer for discovering and describing Void Linux runit services.
+mod api;
+mod archive;
+mod backend;
+mod boot;
+mod boot_report;
+mod bundle;
+mod category;
+mod checks;
+mod confinement;
+mod control;
+mod create;
+mod delete;
+mod depends;
+mod description;
+mod doctor;
+mod duration;
+mod environment;
+mod files;
+mod health;
+mod history;
+mod inventory;
+mod log_archive;
+mod log_backend;
+mod logs;
+mod maintenance;
+mod metrics;
+mod names;
+mod polkit;
+mod proc;
+mod profile;
+mod profiling;
+mod protected;
+mod query;
+mod runlevel;
+mod runscript;
+mod session;
+mod stage_logs;
+mod supervisor;
+mod svlogd;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod timestamp;
+mod trash;
+mod wait;
+mod watch;
+
+pub use api::{API_VERSION, CAPABILITIES, SCHEMA_VERSION, ServerInfo};
+pub use backend::{
+    DeferredChanges, DefinitionStore, LogAccess, RunitBackend, RunlevelControl, ServiceBackend,
+    ServiceMonitor,
+};
+pub use boot::{BootSchedule, BootTaskReport, DEFAULT_BOOT_SCHEDULE_DIR, ScheduledBootChange};
+pub use boot_report::{
+    BOOT_WINDOW, BootRegression, BootReport, DEFAULT_BOOT_REPORTS, KEPT_BOOT_REPORTS,
+    boot_regressions, merge_boot_report, read_boot_reports, record_boot_report,
+};
+pub use bundle::{TEMPLATE_BUNDLE_FORMAT, TemplateBundle, TemplateVariable};
+pub use category::{
+    Categories, DEFAULT_SYSTEM_CATEGORIES, OTHER_CATEGORY, category_label, user_categories_path,
+    write_category,
+};
+pub use checks::{CHECK_TIMEOUT, CheckResult, DEFAULT_CHECK_RESULTS_DIR, MIN_CHECK_INTERVAL};
+pub use confinement::{Confinement, detect_confinement};
+pub use create::ServiceTemplate;
+pub use delete::{DeleteMode, DeletedService};
+pub use depends::DependencyGraph;
+pub use description::{
+    CUSTOM_SERVICE_DESCRIPTION, DEFAULT_SYSTEM_DESCRIPTIONS, DescriptionEntry, DescriptionSource,
+    ServiceDescription, message_locale, read_descriptions, user_descriptions_path,
+    write_description,
+};
+pub use doctor::{BUS_NAME, CheckStatus, DiagnosticCheck};
+pub use duration::format_duration;
+pub use environment::{EffectiveEnvironment, EnvDir, EnvDiscrepancy, EnvSource, EnvVariable};
+pub use files::{ServiceFile, ServiceFileWrite};
+pub use health::{HealthIssue, HealthIssueKind, ServiceHealth};
+pub use history::{
+    DEFAULT_HISTORY_DIR, FLAPPING_CRASHES, FLAPPING_WINDOW, HistoryEvent, HistoryEventKind,
+    ServiceHistory,
+};
+pub use inventory::{EnabledDifference, Inventory, InventoryDiff, InventoryEntry};
+pub use log_archive::{ServiceLogs, default_log_archive_dir, is_failed, write_log_archive};
+pub use log_backend::{DEFAULT_SYSLOG_PATHS, LogBackend, LogBackendKind};
+pub use logs::{LogFilter, LogFollower, LogMatch, LogPattern, LogSeverity};
+pub use maintenance::Maintenance;
+pub use metrics::ServiceMetrics;
+pub use names::NamePolicy;
+pub use polkit::{
+    DEFAULT_RUNKITD_PATH, POLKIT_ACTION_ALERTS, POLKIT_ACTION_ALLOW_CACHE,
+    POLKIT_ACTION_EDIT_FILES, POLKIT_ACTION_ENABLE, POLKIT_ACTION_FORCE, POLKIT_ACTION_PROTECTED,
+    POLKIT_ACTION_REQUIRE_PASSWORD, POLKIT_ACTION_RESTART, POLKIT_ACTION_START, POLKIT_ACTION_STOP,
+    POLKIT_ACTIONS, PolkitAction, polkit_policy,
+};
+pub use proc::{IoSource, IoUsage, ProcessNode};
+pub use profile::{Profile, ProfileChanges, ProfileEntry, ProfileRestore};
+pub use profiling::{SpanTotal, set_profiling, span, take_profile};
+pub use protected::{
+    DEFAULT_PROTECTED_CONFIG, DEFAULT_PROTECTED_SERVICES, ProtectedServices, active_console_getty,
+};
+pub use query::{ServiceQuery, ServiceSort, StateFilter};
+pub use runlevel::{DuplicateEnable, EnabledLink};
+pub use runscript::RunScriptInfo;
+pub use session::{
+    DEFAULT_LOGOUT_RESTART_DIR, DEFAULT_SESSIONS_DIR, LogoutRestartReport, graphical_user_sessions,
+    is_session_service, session_critical_reason,
+};
+pub use stage_logs::{DEFAULT_STAGE_LOG_DIR, STAGE_LOG_PREFIX};
+pub use supervisor::SupervisorDiagnostic;
+pub use svlogd::{SvlogdConfig, SvlogdFilter};
+pub use timestamp::{TimestampFormat, TimestampStyle};
+pub use trash::{DEFAULT_TRASH_DIR, DEFAULT_TRASH_RETENTION, TrashEntry};
+pub use wait::{SETTLE_TIME, StateChange, TargetState, WaitOutcome};
+pub use watch::{ServiceEvent, ServiceWatcher};
+
 use once_cell::sync::Lazy;
+use proc::ProcessTable;
 use regex::Regex;
-use std::collections::VecDeque;
 use std::ffi::OsStr;
 use std::fs::File;
-use std::io::{BufRead, BufReader, ErrorKind};
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::time::Duration;
+use supervisor::SupervisorLog;
 use thiserror::Error;
 
 pub const DEFAULT_SERVICE_DIR: &str = "/etc/sv";
 pub const DEFAULT_ENABLED_DIR: &str = "/var/service";
+pub const DEFAULT_RUNSVDIR_ROOT: &str = "/etc/runit/runsvdir";
+/// Definitions of a per-user runsvdir, relative to the user's home directory.
+pub const USER_SERVICE_DIR: &str = ".config/runit/sv";
+/// Directory a per-user `runsvdir` supervises, relative to the home directory.
+pub const USER_ENABLED_DIR: &str = "service";
 
 static RUNNING_REGEX: Lazy<Regex> = Lazy::new(|| {
     Regex::new(r"^run:\s+(?P<name>[^:]+):\s+\(pid\s+(?P<pid>\d+)\)\s+(?P<uptime>\d+)s").unwrap()
@@ -102,7 +222,7 @@ impl ServiceRuntimeState {
 
 #[cfg(test)]
 mod tests {
-    use super::{ServiceManager, ServiceRuntimeState};
+    use super::{RestartPolicy, ServiceManager, ServiceRuntimeState, effective_restart_policy};
     use std::time::Duration;
 
     #[test]
@@ -131,10 +251,16 @@ mod tests {
 
     #[test]
     fn decodes_tai64n_timestamp() {
-        let stamp = "400000000000000000000000";
+        let stamp = "400000000000000a00000000";
         let parsed = super::decode_tai64n(stamp).expect("failed to parse tai64n epoch");
         assert_eq!(parsed.0, 0);
         assert_eq!(parsed.1, 0);
+
+        // 2023-11-14 22:13:20.5 UTC as written by svlogd.
+        let parsed = super::decode_tai64n("400000006553f10a1dcd6500").unwrap();
+        assert_eq!(parsed, (1_700_000_000, 500_000_000));
+        assert!(super::decode_tai64n("400000000000000000000000").is_none());
+        assert!(super::decode_tai64n("400000000000000a3b9aca00").is_none());
     }
 
     #[test]
@@ -144,6 +270,75 @@ mod tests {
         assert!(manager.validate_service_name("../bad").is_err());
         assert!(manager.validate_service_name("").is_err());
     }
+
+    #[test]
+    fn detects_no_restart_policy() {
+        let missing = std::path::Path::new("/nonexistent/runkit-test");
+        assert_eq!(
+            effective_restart_policy(missing, "run: sshd: (pid 42) 10s; run: log: (pid 41) 10s"),
+            RestartPolicy::Always
+        );
+        assert_eq!(
+            effective_restart_policy(missing, "run: sshd: (pid 42) 10s, want down"),
+            RestartPolicy::Never
+        );
+    }
+
+    #[test]
+    fn manages_services_from_additional_dirs() {
+        let sandbox = crate::testing::SandboxFixture::new().unwrap();
+        sandbox.add_service("sshd", "exec sshd -D\n").unwrap();
+        let extra_definitions = sandbox.root().join("sv-extra");
+        let extra_enabled = sandbox.root().join("service-extra");
+        for service in ["sshd", "minio"] {
+            std::fs::create_dir_all(extra_definitions.join(service)).unwrap();
+        }
+        std::fs::create_dir_all(&extra_enabled).unwrap();
+        let manager = sandbox
+            .manager()
+            .clone()
+            .with_additional_dirs(&extra_definitions, &extra_enabled);
+
+        let services = manager.list_services().unwrap();
+        let listed: Vec<(&str, &std::path::Path)> = services
+            .iter()
+            .map(|service| (service.name.as_str(), service.definition_path.as_path()))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("minio", extra_definitions.join("minio").as_path()),
+                (
+                    "sshd",
+                    sandbox.manager().definitions_dir().join("sshd").as_path()
+                ),
+            ]
+        );
+
+        manager.enable("minio").unwrap();
+        assert!(extra_enabled.join("minio").exists());
+        assert!(!sandbox.manager().enabled_dir().join("minio").exists());
+        let minio = manager.list_services().unwrap().remove(0);
+        assert!(minio.enabled);
+        assert!(matches!(
+            minio.runtime_state,
+            ServiceRuntimeState::Down { .. }
+        ));
+    }
+
+    #[test]
+    fn points_user_manager_at_home() {
+        let manager = ServiceManager::user("/home/alice");
+        assert_eq!(
+            manager.definitions_dir(),
+            std::path::Path::new("/home/alice/.config/runit/sv")
+        );
+        assert_eq!(
+            manager.enabled_dir(),
+            std::path::Path::new("/home/alice/service")
+        );
+        assert!(manager.is_sandboxed());
+    }
 }
 
 /// Desired state of a service as configured by the user.
@@ -153,6 +348,16 @@ pub enum DesiredState {
     Manual,
 }
 
+/// Whether runsv brings a service back up after its process exits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RestartPolicy {
+    /// Default runit behaviour: runsv restarts the service whenever it exits.
+    #[default]
+    Always,
+    /// Keep the service down after it exits (`down` file plus `sv once` semantics).
+    Never,
+}
+
 /// Immutable snapshot of a runit service.
 #[derive(Debug, Clone)]
 pub struct ServiceInfo {
@@ -161,7 +366,27 @@ pub struct ServiceInfo {
     pub enabled: bool,
     pub desired_state: DesiredState,
     pub runtime_state: ServiceRuntimeState,
+    pub restart_policy: RestartPolicy,
+    /// A `down` file keeps runsv from starting the service when it is first supervised.
+    pub has_down_file: bool,
+    /// Zombie processes in the supervised process tree, main pid included.
+    pub defunct_pids: Vec<u32>,
+    /// Dependencies the run script is blocked on via `sv check`/`sv start`.
+    pub waiting_for: Vec<String>,
+    /// Cumulative disk I/O of the running service, when it can be read.
+    pub io: Option<IoUsage>,
     pub description: Option<String>,
+    /// Runlevels whose directory links the service, e.g. `["default"]`.
+    pub runlevels: Vec<String>,
+    /// Set while the service is intentionally kept down for maintenance.
+    pub maintenance: Option<Maintenance>,
+    /// runsv's last warning about a service that is not running, e.g. that
+    /// `./run` could not be executed. Needs runsvdir to be started with `-P`.
+    pub supervisor_diagnostic: Option<SupervisorDiagnostic>,
+    /// How often runkitd runs `./check`, for services that opted in.
+    pub check_interval: Option<Duration>,
+    /// Outcome of the last scheduled check.
+    pub last_check: Option<CheckResult>,
 }
 
 /// Structured log entry emitted by a runit service logger.
@@ -182,8 +407,12 @@ pub enum ServiceError {
         path: PathBuf,
     },
 
-    #[error("sv command failed for service {service}: {message}")]
-    SvCommand { service: String, message: String },
+    #[error("sv {command} failed for service {service}: {message}")]
+    SvCommand {
+        command: String,
+        service: String,
+        message: String,
+    },
 
     #[error("invalid service name: {0}")]
     InvalidServiceName(String),
@@ -191,6 +420,51 @@ pub enum ServiceError {
     #[error("log stream unavailable for service {0}")]
     LogUnavailable(String),
 
+    #[error("service definition missing: {service} ({path:?})")]
+    DefinitionMissing { service: String, path: PathBuf },
+
+    #[error("service definition already exists: {0}")]
+    DefinitionExists(String),
+
+    #[error("service already enabled: {0}")]
+    AlreadyEnabled(String),
+
+    #[error("service not enabled: {0}")]
+    NotEnabled(String),
+
+    #[error("invalid log pattern: {0}")]
+    InvalidPattern(String),
+
+    #[error("unknown runlevel: {0}")]
+    UnknownRunlevel(String),
+
+    #[error("invalid service archive: {0}")]
+    InvalidArchive(String),
+
+    #[error("no trashed definition with id {0}")]
+    NotInTrash(String),
+
+    #[error("invalid svlogd config: {0}")]
+    InvalidLogConfig(String),
+
+    #[error("invalid service template: {0}")]
+    InvalidTemplate(String),
+
+    #[error("cannot edit service file {0}")]
+    InvalidServiceFile(String),
+
+    #[error("shell syntax error in {file}: {message}")]
+    ScriptSyntax { file: String, message: String },
+
+    #[error("{service} belongs to the {package} package; remove it with xbps-remove")]
+    PackageOwned { service: String, package: String },
+
+    #[error("dependency cycle: {}", .0.join(" -> "))]
+    DependencyCycle(Vec<String>),
+
+    #[error("no runsvdir supervises services in {0}; live status and control are unavailable")]
+    Unsupervised(String),
+
     #[error(transparent)]
     Other(#[from] Box<dyn std::error::Error + Send + Sync>),
 }
@@ -211,7 +485,43 @@ pub type Result<T> = std::result::Result<T, ServiceError>;
 pub struct ServiceManager {
     definitions_dir: PathBuf,
     enabled_dir: PathBuf,
+    /// Further `(definitions, enabled)` pairs, searched in order after the
+    /// primary pair; a definition shadows one of the same name in a later pair.
+    additional_dirs: Vec<(PathBuf, PathBuf)>,
     sv_command: PathBuf,
+    /// Program that switches the current runlevel, normally `runsvchdir`.
+    runsvchdir_command: PathBuf,
+    /// Directory holding one subdirectory per runlevel.
+    runsvdir_root: Option<PathBuf>,
+    name_policy: NamePolicy,
+    /// Where deleted definitions are kept; `None` disables the trash.
+    trash_dir: Option<PathBuf>,
+    trash_retention: Duration,
+    /// Where enable and disable changes scheduled for the next boot are kept;
+    /// `None` disables scheduling.
+    boot_schedule_dir: Option<PathBuf>,
+    /// Where restarts waiting for the user to log out are kept; `None`
+    /// disables deferring them.
+    logout_restart_dir: Option<PathBuf>,
+    /// Where the outcome of each service's last scheduled check is kept;
+    /// `None` keeps none.
+    check_results_dir: Option<PathBuf>,
+    /// Where the starts and exits seen by runkitd are kept; `None` keeps none.
+    history_dir: Option<PathBuf>,
+    /// Descriptions files consulted before the definition; see
+    /// [`ServiceManager::describe_service`].
+    user_descriptions: Option<PathBuf>,
+    system_descriptions: Option<PathBuf>,
+    /// Where runit's stage loggers write; `None` hides stage logs.
+    stage_log_dir: Option<PathBuf>,
+    /// System logs searched for the services that log to syslog, first
+    /// existing one wins.
+    syslog_paths: Vec<PathBuf>,
+    /// Set in a container or chroot with no runsvdir; `sv` is never run.
+    reduced_mode: Option<Confinement>,
+    /// Confined to the caller's directories: `sv` gets `SVDIR` and the package
+    /// database is never queried.
+    sandboxed: bool,
 }
 
 impl Default for ServiceManager {
@@ -225,15 +535,109 @@ impl ServiceManager {
         ServiceManager {
             definitions_dir: definitions_dir.into(),
             enabled_dir: enabled_dir.into(),
+            additional_dirs: Vec::new(),
             sv_command: PathBuf::from("sv"),
+            runsvchdir_command: PathBuf::from("runsvchdir"),
+            runsvdir_root: Some(PathBuf::from(DEFAULT_RUNSVDIR_ROOT)),
+            name_policy: NamePolicy::default(),
+            trash_dir: Some(PathBuf::from(DEFAULT_TRASH_DIR)),
+            trash_retention: DEFAULT_TRASH_RETENTION,
+            boot_schedule_dir: Some(PathBuf::from(DEFAULT_BOOT_SCHEDULE_DIR)),
+            logout_restart_dir: Some(PathBuf::from(DEFAULT_LOGOUT_RESTART_DIR)),
+            check_results_dir: Some(PathBuf::from(DEFAULT_CHECK_RESULTS_DIR)),
+            history_dir: Some(PathBuf::from(DEFAULT_HISTORY_DIR)),
+            user_descriptions: None,
+            system_descriptions: Some(PathBuf::from(DEFAULT_SYSTEM_DESCRIPTIONS)),
+            stage_log_dir: Some(PathBuf::from(DEFAULT_STAGE_LOG_DIR)),
+            syslog_paths: DEFAULT_SYSLOG_PATHS.iter().map(PathBuf::from).collect(),
+            reduced_mode: None,
+            sandboxed: false,
+        }
+    }
+
+    /// Manager that touches nothing outside the given directories.
+    ///
+    /// `sv_command` is run with `SVDIR` set to `enabled_dir`, so a stub such as
+    /// the one installed by `testing::SandboxFixture` (feature `testing`) can
+    /// stand in for runit. Package descriptions are not looked up, and there is
+    /// no trash, boot schedule, restarts at logout, check results, history,
+    /// descriptions file, stage logs or system log unless one is set with
+    /// [`ServiceManager::with_trash_dir`],
+    /// [`ServiceManager::with_boot_schedule_dir`],
+    /// [`ServiceManager::with_logout_restart_dir`],
+    /// [`ServiceManager::with_check_results_dir`],
+    /// [`ServiceManager::with_history_dir`],
+    /// [`ServiceManager::with_system_descriptions`],
+    /// [`ServiceManager::with_stage_log_dir`] or
+    /// [`ServiceManager::with_syslog_path`].
+    pub fn sandbox(
+        definitions_dir: impl Into<PathBuf>,
+        enabled_dir: impl Into<PathBuf>,
+        sv_command: impl Into<PathBuf>,
+    ) -> Self {
+        ServiceManager {
+            runsvdir_root: None,
+            trash_dir: None,
+            boot_schedule_dir: None,
+            logout_restart_dir: None,
+            check_results_dir: None,
+            history_dir: None,
+            system_descriptions: None,
+            stage_log_dir: None,
+            syslog_paths: Vec::new(),
+            sandboxed: true,
+            ..Self::new(definitions_dir, enabled_dir).with_sv_command(sv_command)
         }
     }
 
+    /// Manager for a user's own supervision tree, a `runsvdir ~/service` run as
+    /// that user with definitions in `~/.config/runit/sv`.
+    ///
+    /// Like [`ServiceManager::sandbox`], `sv` gets `SVDIR` and package
+    /// descriptions are not looked up. There is no runsvdir root to switch
+    /// runlevels in, and deleted definitions go to `~/.local/share/runkit/trash`.
+    pub fn user(home: impl AsRef<Path>) -> Self {
+        let home = home.as_ref();
+        Self::sandbox(
+            home.join(USER_SERVICE_DIR),
+            home.join(USER_ENABLED_DIR),
+            "sv",
+        )
+        .with_trash_dir(home.join(".local/share/runkit/trash"))
+    }
+
     pub fn with_sv_command(mut self, cmd: impl Into<PathBuf>) -> Self {
         self.sv_command = cmd.into();
         self
     }
 
+    pub fn with_runsvdir_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.runsvdir_root = Some(root.into());
+        self
+    }
+
+    /// Also manage the services defined in `definitions_dir` and enabled in
+    /// `enabled_dir`, e.g. a second runsvdir for add-on services. `sv` addresses
+    /// these services by path.
+    pub fn with_additional_dirs(
+        mut self,
+        definitions_dir: impl Into<PathBuf>,
+        enabled_dir: impl Into<PathBuf>,
+    ) -> Self {
+        self.additional_dirs
+            .push((definitions_dir.into(), enabled_dir.into()));
+        self
+    }
+
+    pub fn with_name_policy(mut self, policy: NamePolicy) -> Self {
+        self.name_policy = policy;
+        self
+    }
+
+    pub fn name_policy(&self) -> NamePolicy {
+        self.name_policy
+    }
+
     pub fn definitions_dir(&self) -> &Path {
         &self.definitions_dir
     }
@@ -242,25 +646,108 @@ impl ServiceManager {
         &self.enabled_dir
     }
 
+    /// Every `(definitions, enabled)` pair, the primary one first.
+    pub fn service_dirs(&self) -> Vec<(&Path, &Path)> {
+        std::iter::once((self.definitions_dir.as_path(), self.enabled_dir.as_path()))
+            .chain(
+                self.additional_dirs
+                    .iter()
+                    .map(|(definitions, enabled)| (definitions.as_path(), enabled.as_path())),
+            )
+            .collect()
+    }
+
+    /// The pair that defines `service` (or `service/log`), falling back to the
+    /// primary pair for services defined nowhere.
+    pub(crate) fn dirs_for(&self, service: &str) -> (&Path, &Path) {
+        let name = service.split('/').next().unwrap_or(service);
+        if !self.definitions_dir.join(name).is_dir()
+            && let Some((definitions, enabled)) = self
+                .additional_dirs
+                .iter()
+                .find(|(definitions, _)| definitions.join(name).is_dir())
+        {
+            return (definitions, enabled);
+        }
+        (&self.definitions_dir, &self.enabled_dir)
+    }
+
     pub fn sv_command_path(&self) -> &Path {
         &self.sv_command
     }
 
+    pub fn is_sandboxed(&self) -> bool {
+        self.sandboxed
+    }
+
+    /// `sv <command> <service>`, pointed at the enabled directory when sandboxed.
+    pub(crate) fn sv(&self, command: &str, service: &str) -> Command {
+        let mut sv = Command::new(&self.sv_command);
+        if self.sandboxed {
+            sv.env("SVDIR", &self.enabled_dir);
+        }
+        sv.arg(command);
+        // Under the permissive policy a name may contain anything sv could
+        // misread, so address the service by path instead. Services from an
+        // additional pair are not under SVDIR and need their path too.
+        let (_, enabled_dir) = self.dirs_for(service);
+        if self.name_policy == NamePolicy::Permissive || enabled_dir != self.enabled_dir {
+            sv.arg(enabled_dir.join(service));
+        } else {
+            sv.arg(service);
+        }
+        sv
+    }
+
     /// Enumerate all services available on the system.
     pub fn list_services(&self) -> Result<Vec<ServiceInfo>> {
-        let mut services = Vec::new();
-
-        let read_dir = std::fs::read_dir(&self.definitions_dir)
-            .map_err(|e| ServiceError::from_io(&self.definitions_dir, e))?;
+        let _span = profiling::span("list:services");
+        let mut services: Vec<ServiceInfo> = Vec::new();
+        let processes = {
+            let _span = profiling::span("io:proc");
+            ProcessTable::snapshot()
+        };
+        let runlevels = {
+            let _span = profiling::span("io:runlevels");
+            self.enabled_runlevels()?
+        };
+        let overrides = {
+            let _span = profiling::span("io:description overrides");
+            self.description_overrides()
+        };
+        let supervisor_log = {
+            let _span = profiling::span("io:supervisor log");
+            SupervisorLog::collect(
+                &processes,
+                self.service_dirs().into_iter().map(|(_, enabled)| enabled),
+            )
+        };
 
-        for entry in read_dir {
-            let entry = entry.map_err(|e| ServiceError::from_io(&self.definitions_dir, e))?;
-            let path = entry.path();
-            if !path.is_dir() {
-                continue;
-            }
-            if let Some(name) = path.file_name().and_then(OsStr::to_str) {
-                if let Some(info) = self.build_service_info(name, &path)? {
+        for (index, (definitions_dir, enabled_dir)) in self.service_dirs().into_iter().enumerate() {
+            // Only the primary definitions directory is required to exist.
+            let read_dir = match std::fs::read_dir(definitions_dir) {
+                Ok(read_dir) => read_dir,
+                Err(err) if index > 0 && err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(ServiceError::from_io(definitions_dir, err)),
+            };
+            for entry in read_dir {
+                let entry = entry.map_err(|e| ServiceError::from_io(definitions_dir, e))?;
+                let path = entry.path();
+                if !path.is_dir() {
+                    continue;
+                }
+                if let Some(name) = path.file_name().and_then(OsStr::to_str)
+                    && !services.iter().any(|service| service.name == name)
+                    && let Some(mut info) =
+                        self.build_service_info(name, &path, enabled_dir, &processes)?
+                {
+                    info.runlevels = runlevels.get(name).cloned().unwrap_or_default();
+                    if let Some((_, text)) = overrides.lookup(name) {
+                        info.description = Some(text.to_string());
+                    }
+                    if info.runtime_state.pid().is_none() {
+                        info.supervisor_diagnostic = supervisor_log.latest(name).cloned();
+                    }
                     services.push(info);
                 }
             }
@@ -270,17 +757,24 @@ impl ServiceManager {
         Ok(services)
     }
 
+    /// [`ServiceManager::list_services`] narrowed and ordered by `query`.
+    pub fn list_services_matching(&self, query: &ServiceQuery) -> Result<Vec<ServiceInfo>> {
+        self.list_services().map(|services| query.apply(services))
+    }
+
     fn build_service_info(
         &self,
         name: &str,
         definition_path: &Path,
+        enabled_dir: &Path,
+        processes: &ProcessTable,
     ) -> Result<Option<ServiceInfo>> {
         // Skip hidden directories or invalid names.
         if name.starts_with('.') {
             return Ok(None);
         }
 
-        let enabled_path = self.enabled_dir.join(name);
+        let enabled_path = enabled_dir.join(name);
         let enabled = enabled_path.exists();
         let desired_state = if enabled {
             DesiredState::AutoStart
@@ -288,8 +782,35 @@ impl ServiceManager {
             DesiredState::Manual
         };
 
-        let runtime_state = self.status(name)?;
+        let (status_output, runtime_state) = match &self.reduced_mode {
+            Some(confinement) => (
+                String::new(),
+                ServiceRuntimeState::Unknown {
+                    raw: format!("not supervised in {confinement}"),
+                },
+            ),
+            None => {
+                let status_output = self.status_output(name)?;
+                let runtime_state = ServiceRuntimeState::from_sv_status(&status_output);
+                (status_output, runtime_state)
+            }
+        };
+        let _span = profiling::span("io:service files");
+        let restart_policy = effective_restart_policy(definition_path, &status_output);
+        let has_down_file = definition_path.join("down").exists();
+        let defunct_pids = runtime_state
+            .pid()
+            .map(|pid| processes.defunct_in_tree(pid))
+            .unwrap_or_default();
+        let waiting_for = runtime_state
+            .pid()
+            .map(|pid| processes.awaited_dependencies(pid))
+            .unwrap_or_default();
+        let io = runtime_state.pid().and_then(|pid| processes.io_usage(pid));
         let description = self.read_description(definition_path);
+        let maintenance = maintenance::read_maintenance(definition_path);
+        let check_interval = checks::read_check_interval(definition_path);
+        let last_check = check_interval.and_then(|_| self.read_last_check(name));
 
         Ok(Some(ServiceInfo {
             name: name.to_string(),
@@ -297,25 +818,72 @@ impl ServiceManager {
             enabled,
             desired_state,
             runtime_state,
+            restart_policy,
+            has_down_file,
+            defunct_pids,
+            waiting_for,
+            io,
             description,
+            runlevels: Vec::new(),
+            maintenance,
+            supervisor_diagnostic: None,
+            check_interval,
+            last_check,
         }))
     }
 
     /// Fetch the runtime status for a single service via `sv status`.
     pub fn status(&self, service: &str) -> Result<ServiceRuntimeState> {
+        let stdout = self.status_output(service)?;
+        Ok(ServiceRuntimeState::from_sv_status(&stdout))
+    }
+
+    /// Effective restart policy for a service, combining its `down` file with the
+    /// supervisor's current "want" state.
+    pub fn restart_policy(&self, service: &str) -> Result<RestartPolicy> {
+        let stdout = self.status_output(service)?;
+        let definition_path = self.dirs_for(service).0.join(service);
+        Ok(effective_restart_policy(&definition_path, &stdout))
+    }
+
+    /// Persist the restart policy.
+    ///
+    /// `Never` writes runkit's marker together with a `down` file so runsv does not
+    /// bring the service up with restart semantics at boot; `Always` removes both.
+    /// Callers are expected to send `sv once`/`sv up` to a running instance so the
+    /// supervisor's current intent matches.
+    pub fn set_restart_policy(&self, service: &str, policy: RestartPolicy) -> Result<()> {
+        let definition_path = self.existing_definition(service)?;
+        let marker = definition_path.join(NO_RESTART_MARKER);
+        match policy {
+            RestartPolicy::Never => {
+                File::create(&marker).map_err(|err| ServiceError::from_io(&marker, err))?;
+                self.set_down_file(service)
+            }
+            RestartPolicy::Always => {
+                remove_if_present(&marker)?;
+                self.clear_down_file(service)
+            }
+        }
+    }
+
+    fn status_output(&self, service: &str) -> Result<String> {
         self.validate_service_name(service)?;
+        self.require_supervision()?;
 
-        let output = Command::new(&self.sv_command)
-            .arg("status")
-            .arg(service)
-            .output()
-            .map_err(|err| ServiceError::from_io(&self.sv_command, err))?;
+        let output = {
+            let _span = profiling::span("spawn:sv status");
+            self.sv("status", service)
+                .output()
+                .map_err(|err| ServiceError::from_io(&self.sv_command, err))?
+        };
 
         let stdout = String::from_utf8_lossy(&output.stdout).to_string();
         let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
 
         if !stderr.is_empty() {
             return Err(ServiceError::SvCommand {
+                command: "status".to_string(),
                 service: service.to_string(),
                 message: stderr,
             });
@@ -328,12 +896,13 @@ impl ServiceManager {
                 .map(|code| format!("exit status {code}"))
                 .unwrap_or_else(|| output.status.to_string());
             return Err(ServiceError::SvCommand {
+                command: "status".to_string(),
                 service: service.to_string(),
                 message: format!("sv status returned no output ({status_desc})"),
             });
         }
 
-        Ok(ServiceRuntimeState::from_sv_status(&stdout))
+        Ok(stdout)
     }
 
     fn read_description(&self, definition_path: &Path) -> Option<String> {
@@ -350,134 +919,108 @@ impl ServiceManager {
         None
     }
 
+    /// Text of [`ServiceManager::describe_service`].
     pub fn service_description(&self, service: &str) -> Result<Option<String>> {
-        self.validate_service_name(service)?;
-        let definition_path = self.definitions_dir.join(service);
-        if !definition_path.exists() {
-            return Ok(None);
-        }
-
-        if let Some(description) = self.read_description(&definition_path) {
-            return Ok(Some(description));
-        }
-
-        Ok(self.lookup_package_description(&definition_path))
+        Ok(self
+            .describe_service(service)?
+            .map(|description| description.text))
     }
 
-    fn lookup_package_description(&self, definition_path: &Path) -> Option<String> {
-        let service_file = ["run", "finish", "check"]
-            .into_iter()
-            .map(|candidate| definition_path.join(candidate))
-            .find(|path| path.exists())?;
+    /// The xbps package that installed the definition, with its version, e.g.
+    /// `openssh-9.8p1_1`. `None` for definitions no package owns.
+    pub fn service_package(&self, service: &str) -> Result<Option<String>> {
+        let definition_path = self.existing_definition(service)?;
+        Ok(self.package_version(&definition_path))
+    }
 
-        let owner_output = Command::new("xbps-query")
-            .arg("-o")
-            .arg(&service_file)
-            .output()
-            .ok()?;
-        if !owner_output.status.success() {
-            return None;
-        }
+    /// Name of the xbps package that installed the definition, without its version.
+    pub(crate) fn package_owner(&self, definition_path: &Path) -> Option<String> {
+        self.package_version(definition_path)
+            .map(|package| strip_package_version(&package).to_string())
+    }
 
-        let owner_stdout = String::from_utf8(owner_output.stdout).ok()?;
-        let package_with_version = owner_stdout
-            .lines()
-            .map(str::trim)
-            .find(|line| !line.is_empty())?
-            .split(':')
-            .next()?
-            .trim();
-        let package_name = strip_package_version(package_with_version);
-
-        let desc_output = Command::new("xbps-query")
-            .arg("-p")
-            .arg("short_desc")
-            .arg(package_name)
-            .output()
-            .ok()?;
-        if !desc_output.status.success() {
+    /// Cached `xbps-query -o` on whichever of the definition's scripts
+    /// exists. Never queried when sandboxed.
+    fn package_version(&self, definition_path: &Path) -> Option<String> {
+        if self.sandboxed {
             return None;
         }
-
-        let description = String::from_utf8(desc_output.stdout).ok()?;
-        let trimmed = description.trim();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed.to_string())
-        }
+        description::owning_package(&description::definition_script(definition_path)?)?
     }
 
     pub fn validate_service_name(&self, service: &str) -> Result<()> {
-        let valid = !service.is_empty()
-            && service
-                .chars()
-                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
-        if valid {
+        if self.name_policy.allows(service) {
             Ok(())
         } else {
             Err(ServiceError::InvalidServiceName(service.to_string()))
         }
     }
 
-    /// Tail the newest log entries for a service, if its logger writes to svlogd-style files.
+    /// Tail the newest log entries for a service, wherever its logger writes;
+    /// see [`ServiceManager::log_backend`]. Rotated files are read once
+    /// `current` holds fewer than `limit` lines.
+    /// A `runit/` name reads one of runit's stage logs instead.
     pub fn tail_logs(&self, service: &str, limit: usize) -> Result<Vec<ServiceLogEntry>> {
-        self.validate_service_name(service)?;
+        self.tail_logs_filtered(service, limit, &LogFilter::default())
+    }
+
+    /// Like [`ServiceManager::tail_logs`], but returns the newest `limit` entries
+    /// that pass `filter`, searching as far back as the retained files allow.
+    pub fn tail_logs_filtered(
+        &self,
+        service: &str,
+        limit: usize,
+        filter: &LogFilter,
+    ) -> Result<Vec<ServiceLogEntry>> {
+        self.validate_log_name(service)?;
 
         if limit == 0 {
             return Ok(Vec::new());
         }
 
-        let definition_candidate = self.definitions_dir.join(service).join("log/main/current");
-        let enabled_candidate = self.enabled_dir.join(service).join("log/main/current");
-
-        let log_path = if definition_candidate.exists() {
-            definition_candidate
-        } else if enabled_candidate.exists() {
-            enabled_candidate
-        } else {
-            return Ok(Vec::new());
-        };
-
-        match read_svlogd_tail(&log_path, limit) {
-            Ok(entries) => Ok(entries),
-            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
-            Err(err) => Err(ServiceError::from_io(&log_path, err)),
+        let _span = profiling::span("io:log tail");
+        match self.log_backend(service) {
+            Some(backend) => backend.tail(limit, filter),
+            None => Ok(Vec::new()),
         }
     }
 }
 
-fn read_svlogd_tail(path: &Path, limit: usize) -> std::io::Result<Vec<ServiceLogEntry>> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let mut entries: VecDeque<ServiceLogEntry> = VecDeque::with_capacity(limit);
+/// Marker inside a definition recording that runkit should keep it down after exit.
+const NO_RESTART_MARKER: &str = ".runkit-no-restart";
 
-    for line in reader.lines() {
-        let line = line?;
-        if entries.len() == limit {
-            entries.pop_front();
-        }
-        entries.push_back(parse_svlogd_line(&line));
+fn effective_restart_policy(definition_path: &Path, status_output: &str) -> RestartPolicy {
+    let first_line = status_output.lines().next().unwrap_or_default();
+    if definition_path.join(NO_RESTART_MARKER).exists() || first_line.contains("want down") {
+        RestartPolicy::Never
+    } else {
+        RestartPolicy::Always
     }
+}
 
-    Ok(entries.into_iter().collect())
+pub(crate) fn remove_if_present(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(ServiceError::from_io(path, err)),
+    }
 }
 
 fn parse_svlogd_line(line: &str) -> ServiceLogEntry {
-    if let Some(rest) = line.strip_prefix('@') {
-        if rest.len() >= 24 {
-            let stamp = &rest[..24];
-            let message = rest[24..].trim_start().to_string();
-            let (unix, nanos) = decode_tai64n(stamp).unwrap_or((-1, 0));
-            let timestamp_unix = if unix >= 0 { Some(unix) } else { None };
-            let timestamp_nanos = if unix >= 0 { Some(nanos) } else { None };
-            return ServiceLogEntry {
-                timestamp_unix,
-                timestamp_nanos,
-                timestamp_raw: Some(stamp.to_string()),
-                message,
-            };
-        }
+    if let Some(rest) = line.strip_prefix('@')
+        && rest.len() >= 24
+    {
+        let stamp = &rest[..24];
+        let message = rest[24..].trim_start().to_string();
+        let decoded = decode_tai64n(stamp);
+        let timestamp_unix = decoded.map(|(unix, _)| unix);
+        let timestamp_nanos = decoded.map(|(_, nanos)| nanos);
+        return ServiceLogEntry {
+            timestamp_unix,
+            timestamp_nanos,
+            timestamp_raw: Some(stamp.to_string()),
+            message,
+        };
     }
 
     ServiceLogEntry {
@@ -488,6 +1031,12 @@ fn parse_svlogd_line(line: &str) -> ServiceLogEntry {
     }
 }
 
+/// Decode a TAI64N label into Unix seconds and nanoseconds (UTC).
+///
+/// A label is 2^62 plus TAI seconds. svlogd builds it from the POSIX clock with
+/// libtai's fixed 10 second TAI-UTC offset, the leap-second offset in force at
+/// the Unix epoch, so that offset is removed here. Leap seconds inserted since
+/// then are not part of the label and must not be subtracted again.
 fn decode_tai64n(stamp: &str) -> Option<(i64, u32)> {
     if stamp.len() != 24 {
         return None;
@@ -495,27 +1044,25 @@ fn decode_tai64n(stamp: &str) -> Option<(i64, u32)> {
 
     let secs = u64::from_str_radix(&stamp[..16], 16).ok()?;
     let nanos = u32::from_str_radix(&stamp[16..], 16).ok()?;
-
-    const TAI64_UNIX_OFFSET: u64 = 0x4000_0000_0000_0000;
-    if secs < TAI64_UNIX_OFFSET {
+    if nanos >= 1_000_000_000 {
         return None;
     }
 
-    let unix_secs = secs - TAI64_UNIX_OFFSET;
-    Some((unix_secs as i64, nanos))
+    const TAI64_UNIX_OFFSET: u64 = 0x4000_0000_0000_0000 + 10;
+    let unix_secs = secs.checked_sub(TAI64_UNIX_OFFSET)?;
+    Some((i64::try_from(unix_secs).ok()?, nanos))
 }
 
 fn strip_package_version(package: &str) -> &str {
-    if let Some(pos) = package.rfind('-') {
-        if pos + 1 < package.len()
-            && package[pos + 1..]
-                .chars()
-                .next()
-                .map(|c| c.is_ascii_digit())
-                .unwrap_or(false)
-        {
-            return &package[..pos];
-        }
+    if let Some(pos) = package.rfind('-')
+        && pos + 1 < package.len()
+        && package[pos + 1..]
+            .chars()
+            .next()
+            .map(|c| c.is_ascii_digit())
+            .unwrap_or(false)
+    {
+        return &package[..pos];
     }
     package
 }
@@ -556,12 +1103,12 @@ impl ActivityEvent {
         let now = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
             .unwrap_or_default();
-        let timestamp = format!(
-            "{}",
-            chrono::DateTime::<chrono::Utc>::from_timestamp(now.as_secs() as i64, now.subsec_nanos())
-                .unwrap_or_default()
-                .to_rfc3339()
-        );
+        let timestamp = chrono::DateTime::<chrono::Utc>::from_timestamp(
+            now.as_secs() as i64,
+            now.subsec_nanos(),
+        )
+        .unwrap_or_default()
+        .to_rfc3339();
         ActivityEvent {
             timestamp,
             event_type,
@@ -0,0 +1,128 @@
+//! Collecting the logs of every failed service into one archive, to keep the
+//! evidence of a bad boot before it rotates away.
+use crate::archive::unique_suffix;
+use crate::{
+    Result, ServiceError, ServiceInfo, ServiceLogEntry, ServiceRuntimeState, TimestampFormat,
+};
+use chrono::Local;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The newest entries of one service's log.
+#[derive(Debug, Clone)]
+pub struct ServiceLogs {
+    pub service: String,
+    pub entries: Vec<ServiceLogEntry>,
+}
+
+/// `~/runkit-logs`, where archives go unless told otherwise.
+pub fn default_log_archive_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join("runkit-logs"))
+}
+
+/// Whether `info` counts as failed: runsv reports a failing process, or the
+/// service is enabled and meant to be up but is down outside maintenance.
+pub fn is_failed(info: &ServiceInfo) -> bool {
+    match &info.runtime_state {
+        ServiceRuntimeState::Failed { .. } => true,
+        ServiceRuntimeState::Down { normally_up, .. } => {
+            info.enabled && *normally_up && info.maintenance.is_none()
+        }
+        _ => false,
+    }
+}
+
+/// Write `logs` to `runkit-failed-logs-<local time>.tar.gz` in `dir`, one
+/// `<service>.log` per service with each line's local timestamp. Creates `dir`
+/// if needed and returns the archive's path.
+pub fn write_log_archive(dir: &Path, logs: &[ServiceLogs]) -> Result<PathBuf> {
+    fs::create_dir_all(dir).map_err(|err| ServiceError::from_io(dir, err))?;
+    let name = format!(
+        "runkit-failed-logs-{}",
+        Local::now().format("%Y%m%d-%H%M%S")
+    );
+    let archive = dir.join(format!("{name}.tar.gz"));
+    let staging = dir.join(format!(".runkit-logs-{}", unique_suffix()));
+    let result = stage_logs(&staging.join(&name), logs).and_then(|()| {
+        let output = Command::new("tar")
+            .arg("-C")
+            .arg(&staging)
+            .arg("-czf")
+            .arg(&archive)
+            .arg(&name)
+            .output()
+            .map_err(|err| ServiceError::from_io("tar", err))?;
+        if output.status.success() {
+            Ok(archive)
+        } else {
+            Err(ServiceError::Other(
+                format!(
+                    "tar failed to write {}: {}",
+                    archive.display(),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )
+                .into(),
+            ))
+        }
+    });
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+fn stage_logs(dir: &Path, logs: &[ServiceLogs]) -> Result<()> {
+    fs::create_dir_all(dir).map_err(|err| ServiceError::from_io(dir, err))?;
+    let format = TimestampFormat::default();
+    for logs in logs {
+        let mut contents = String::new();
+        for entry in &logs.entries {
+            if let Some(timestamp) = entry.format_timestamp(format) {
+                contents.push_str(&timestamp);
+                contents.push(' ');
+            }
+            contents.push_str(&entry.message);
+            contents.push('\n');
+        }
+        let path = dir.join(format!("{}.log", logs.service));
+        fs::write(&path, contents).map_err(|err| ServiceError::from_io(&path, err))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_log_archive;
+    use crate::LogAccess;
+    use crate::testing::SandboxFixture;
+    use std::process::Command;
+
+    #[test]
+    fn archives_the_logs_of_enabled_services_that_are_down() {
+        let sandbox = SandboxFixture::new().unwrap();
+        for name in ["sshd", "ntpd", "cron"] {
+            sandbox.add_service(name, "exec true\n").unwrap();
+        }
+        sandbox.enable("sshd").unwrap();
+        sandbox.enable("ntpd").unwrap();
+        sandbox.set_running("ntpd", 42).unwrap();
+        sandbox
+            .write_log("sshd", &["@4000000065536fd01dcd6500 fatal: no host keys"])
+            .unwrap();
+
+        let logs = sandbox.manager().failed_service_logs(10).unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].service, "sshd");
+        assert_eq!(logs[0].entries[0].message, "fatal: no host keys");
+
+        let dir = sandbox.root().join("archives");
+        let archive = write_log_archive(&dir, &logs).unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        let listing = Command::new("tar")
+            .arg("-tzf")
+            .arg(&archive)
+            .output()
+            .unwrap();
+        let listing = String::from_utf8_lossy(&listing.stdout);
+        assert!(listing.lines().any(|line| line.ends_with("/sshd.log")));
+    }
+}
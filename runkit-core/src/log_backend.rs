@@ -0,0 +1,442 @@
+//! Finding where a service's logger writes, whatever the logger.
+//!
+//! The service's `log/run` script names the logger: `svlogd` and `s6-log`
+//! keep a directory of `current` plus rotated files, while `vlogger` and
+//! `logger` hand the lines to syslog, where they are picked out of the system
+//! log by their tag. Without a recognisable script, an svlogd directory at
+//! `log/main` is assumed, as runkit always has.
+use crate::logs::{LineParser, LogFollower, log_files_newest_first, read_log_tail};
+use crate::{
+    LogFilter, Result, STAGE_LOG_PREFIX, ServiceError, ServiceLogEntry, ServiceManager,
+    parse_svlogd_line,
+};
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, TimeZone};
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Where syslog output is looked for, in order: socklog's catch-all svlogd
+/// directory, then the flat files other syslog daemons write.
+pub const DEFAULT_SYSLOG_PATHS: [&str; 3] = [
+    "/var/log/socklog/everything",
+    "/var/log/messages",
+    "/var/log/syslog",
+];
+
+/// What writes a service's log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogBackendKind {
+    Svlogd,
+    S6Log,
+    Syslog,
+}
+
+impl LogBackendKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogBackendKind::Svlogd => "svlogd",
+            LogBackendKind::S6Log => "s6-log",
+            LogBackendKind::Syslog => "syslog",
+        }
+    }
+}
+
+impl fmt::Display for LogBackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One service's log, wherever and however it is kept.
+pub trait LogBackend: Send + Sync {
+    fn kind(&self) -> LogBackendKind;
+
+    /// The file or directory the entries are read from.
+    fn location(&self) -> &Path;
+
+    /// The newest `limit` entries that pass `filter`, oldest first.
+    fn tail(&self, limit: usize, filter: &LogFilter) -> Result<Vec<ServiceLogEntry>>;
+
+    /// Entries written from now on.
+    fn follow(&self) -> Result<LogFollower>;
+}
+
+/// A `current` file and its rotations, as svlogd and s6-log keep them.
+struct LogDir {
+    kind: LogBackendKind,
+    dir: PathBuf,
+}
+
+impl LogDir {
+    fn parser(&self) -> LineParser {
+        match self.kind {
+            LogBackendKind::S6Log => Arc::new(|line: &str| Some(parse_s6_log_line(line))),
+            _ => Arc::new(|line: &str| Some(parse_svlogd_line(line))),
+        }
+    }
+}
+
+impl LogBackend for LogDir {
+    fn kind(&self) -> LogBackendKind {
+        self.kind
+    }
+
+    fn location(&self) -> &Path {
+        &self.dir
+    }
+
+    fn tail(&self, limit: usize, filter: &LogFilter) -> Result<Vec<ServiceLogEntry>> {
+        let tail = log_files_newest_first(&self.dir)
+            .and_then(|files| read_log_tail(&files, limit, filter, &*self.parser()));
+        match tail {
+            Ok(entries) => Ok(entries),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(ServiceError::from_io(&self.dir, err)),
+        }
+    }
+
+    fn follow(&self) -> Result<LogFollower> {
+        LogFollower::new(self.dir.clone(), "current", self.parser())
+    }
+}
+
+/// A service's lines in the system log, recognised by their syslog tag.
+struct SyslogLog {
+    /// An svlogd directory when socklog collects the system log, otherwise a
+    /// flat file of which only the live one is read.
+    path: PathBuf,
+    tag: String,
+}
+
+impl SyslogLog {
+    fn parser(&self) -> LineParser {
+        let tag = self.tag.clone();
+        let stamped = self.path.is_dir();
+        Arc::new(move |line: &str| parse_syslog_line(line, &tag, stamped))
+    }
+}
+
+impl LogBackend for SyslogLog {
+    fn kind(&self) -> LogBackendKind {
+        LogBackendKind::Syslog
+    }
+
+    fn location(&self) -> &Path {
+        &self.path
+    }
+
+    fn tail(&self, limit: usize, filter: &LogFilter) -> Result<Vec<ServiceLogEntry>> {
+        let files = if self.path.is_dir() {
+            log_files_newest_first(&self.path)
+        } else {
+            Ok(vec![self.path.clone()])
+        };
+        let tail = files.and_then(|files| read_log_tail(&files, limit, filter, &*self.parser()));
+        match tail {
+            Ok(entries) => Ok(entries),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(ServiceError::from_io(&self.path, err)),
+        }
+    }
+
+    fn follow(&self) -> Result<LogFollower> {
+        if self.path.is_dir() {
+            return LogFollower::new(self.path.clone(), "current", self.parser());
+        }
+        let (Some(dir), Some(name)) = (self.path.parent(), self.path.file_name()) else {
+            return Err(ServiceError::LogUnavailable(self.tag.clone()));
+        };
+        LogFollower::new(dir.to_path_buf(), &name.to_string_lossy(), self.parser())
+    }
+}
+
+/// The logger a `log/run` script starts, as far as can be told without
+/// running it.
+#[derive(Debug, PartialEq, Eq)]
+enum Logger {
+    Dir(LogBackendKind, PathBuf),
+    Syslog { tag: Option<String> },
+}
+
+impl ServiceManager {
+    /// Read syslog output from `path` instead of the [`DEFAULT_SYSLOG_PATHS`].
+    pub fn with_syslog_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.syslog_paths = vec![path.into()];
+        self
+    }
+
+    /// Where the service's log is kept, or a stage log's for a `runit/` name;
+    /// `None` when nothing has been logged where the service's logger writes.
+    pub fn log_backend(&self, service: &str) -> Option<Box<dyn LogBackend>> {
+        if let Some(stage) = service.strip_prefix(STAGE_LOG_PREFIX) {
+            return self.stage_log_dir(stage).map(|dir| {
+                Box::new(LogDir {
+                    kind: LogBackendKind::Svlogd,
+                    dir,
+                }) as Box<dyn LogBackend>
+            });
+        }
+
+        let (definitions_dir, enabled_dir) = self.dirs_for(service);
+        for root in [definitions_dir, enabled_dir] {
+            let log_root = root.join(service).join("log");
+            match logger_of(&log_root) {
+                Some(Logger::Dir(kind, dir)) if dir.join("current").is_file() => {
+                    return Some(Box::new(LogDir { kind, dir }));
+                }
+                Some(Logger::Syslog { tag }) => {
+                    if let Some(path) = self.syslog_paths.iter().find(|path| path.exists()) {
+                        return Some(Box::new(SyslogLog {
+                            path: path.clone(),
+                            tag: tag.unwrap_or_else(|| service.to_string()),
+                        }));
+                    }
+                }
+                _ => {}
+            }
+        }
+        [definitions_dir, enabled_dir]
+            .into_iter()
+            .map(|root| root.join(service).join("log/main"))
+            .find(|dir| dir.join("current").exists())
+            .map(|dir| {
+                Box::new(LogDir {
+                    kind: LogBackendKind::Svlogd,
+                    dir,
+                }) as Box<dyn LogBackend>
+            })
+    }
+}
+
+/// Read the logger off `log_root/run`. Relative directories are taken from
+/// `log_root`, where runsv starts the logger.
+fn logger_of(log_root: &Path) -> Option<Logger> {
+    let run = log_root.join("run");
+    // Void links `log/run` straight to vlogger.
+    if let Ok(target) = fs::read_link(&run)
+        && target
+            .file_name()
+            .is_some_and(|name| name == "vlogger" || name == "logger")
+    {
+        return Some(Logger::Syslog { tag: None });
+    }
+    let script = fs::read_to_string(&run).ok()?;
+    script
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .find_map(|line| parse_logger_command(line, log_root))
+}
+
+fn parse_logger_command(line: &str, log_root: &Path) -> Option<Logger> {
+    let words: Vec<&str> = line
+        .split_whitespace()
+        .map(|word| word.trim_matches(|c| c == '"' || c == '\''))
+        .collect();
+    let start = words.iter().position(|word| {
+        matches!(
+            word.rsplit('/').next(),
+            Some("svlogd" | "s6-log" | "vlogger" | "logger")
+        )
+    })?;
+    let program = words[start].rsplit('/').next().unwrap_or_default();
+    let mut args = words[start + 1..].iter().copied();
+    let dir = |dir: &str| log_root.join(dir);
+
+    match program {
+        "svlogd" => {
+            while let Some(arg) = args.next() {
+                match arg {
+                    "-r" | "-R" | "-l" | "-b" => {
+                        args.next();
+                    }
+                    _ if arg.starts_with('-') => {}
+                    _ => return Some(Logger::Dir(LogBackendKind::Svlogd, dir(arg))),
+                }
+            }
+            None
+        }
+        "s6-log" => {
+            while let Some(arg) = args.next() {
+                match arg {
+                    "-l" | "-d" => {
+                        args.next();
+                    }
+                    _ if arg.starts_with('/') || arg.starts_with('.') => {
+                        return Some(Logger::Dir(LogBackendKind::S6Log, dir(arg)));
+                    }
+                    _ => {}
+                }
+            }
+            None
+        }
+        _ => {
+            let mut tag = None;
+            while let Some(arg) = args.next() {
+                if arg == "-t" {
+                    tag = args.next().map(str::to_string);
+                } else if let Some(value) = arg.strip_prefix("-t") {
+                    tag = Some(value.to_string());
+                }
+            }
+            Some(Logger::Syslog { tag })
+        }
+    }
+}
+
+/// An s6-log line: a TAI64N label like svlogd's, or with the `T` directive
+/// an ISO 8601 local time.
+fn parse_s6_log_line(line: &str) -> ServiceLogEntry {
+    if let Some(stamp) = line.get(..29)
+        && let Ok(time) =
+            NaiveDateTime::parse_from_str(&stamp.replacen('T', " ", 1), "%Y-%m-%d %H:%M:%S%.f")
+        && let Some(time) = Local.from_local_datetime(&time).earliest()
+    {
+        return ServiceLogEntry {
+            timestamp_unix: Some(time.timestamp()),
+            timestamp_nanos: Some(time.timestamp_subsec_nanos()),
+            timestamp_raw: None,
+            message: line[29..].trim_start().to_string(),
+        };
+    }
+    parse_svlogd_line(line)
+}
+
+/// The entry for `line` if syslog got it from `tag`, with the message after
+/// the tag. `stamped` lines carry socklog's TAI64N label; otherwise the
+/// syslog timestamp is read, RFC 3339 or the classic `Mmm dd hh:mm:ss`.
+fn parse_syslog_line(line: &str, tag: &str, stamped: bool) -> Option<ServiceLogEntry> {
+    let mut entry = if stamped {
+        parse_svlogd_line(line)
+    } else {
+        parse_syslog_timestamp(line)
+    };
+    let mut rest = entry.message.as_str();
+    let message = loop {
+        let (word, after) = rest.trim_start().split_once(' ').unwrap_or((rest, ""));
+        if word.is_empty() {
+            return None;
+        }
+        if word.strip_prefix(tag).is_some_and(|suffix| {
+            suffix == ":" || suffix.starts_with('[') && suffix.ends_with("]:")
+        }) {
+            break after.trim_start().to_string();
+        }
+        rest = after;
+    };
+    entry.message = message;
+    Some(entry)
+}
+
+fn parse_syslog_timestamp(line: &str) -> ServiceLogEntry {
+    let untimed = ServiceLogEntry {
+        timestamp_unix: None,
+        timestamp_nanos: None,
+        timestamp_raw: None,
+        message: line.to_string(),
+    };
+    let (first, rest) = line.split_once(' ').unwrap_or((line, ""));
+    if let Ok(time) = DateTime::parse_from_rfc3339(first) {
+        return ServiceLogEntry {
+            timestamp_unix: Some(time.timestamp()),
+            timestamp_nanos: Some(time.timestamp_subsec_nanos()),
+            message: rest.to_string(),
+            ..untimed
+        };
+    }
+    let Some(stamp) = line.get(..15) else {
+        return untimed;
+    };
+    let year = Local::now().year();
+    match NaiveDateTime::parse_from_str(&format!("{year} {stamp}"), "%Y %b %e %H:%M:%S")
+        .ok()
+        .and_then(|time| Local.from_local_datetime(&time).earliest())
+    {
+        Some(time) => ServiceLogEntry {
+            timestamp_unix: Some(time.timestamp()),
+            timestamp_nanos: Some(0),
+            message: line[15..].to_string(),
+            ..untimed
+        },
+        None => untimed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LogBackendKind, Logger, parse_logger_command};
+    use crate::testing::SandboxFixture;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn reads_logger_commands() {
+        let root = Path::new("/etc/sv/sshd/log");
+        assert_eq!(
+            parse_logger_command("exec svlogd -tt -l 200 /var/log/sshd", root),
+            Some(Logger::Dir(LogBackendKind::Svlogd, "/var/log/sshd".into()))
+        );
+        assert_eq!(
+            parse_logger_command("exec chpst -u log s6-log -b n20 s1000000 T ./main", root),
+            Some(Logger::Dir(LogBackendKind::S6Log, root.join("./main")))
+        );
+        assert_eq!(
+            parse_logger_command("exec logger -p daemon.info -t ssh", root),
+            Some(Logger::Syslog {
+                tag: Some("ssh".into())
+            })
+        );
+        assert_eq!(parse_logger_command("exec cat", root), None);
+    }
+
+    #[test]
+    fn tails_s6_log_and_syslog_services() {
+        let sandbox = SandboxFixture::new().unwrap();
+        let syslog = sandbox.root().join("messages");
+        fs::write(
+            &syslog,
+            "2024-03-01T10:00:00+00:00 host ntpd[40]: synchronised\n\
+             Mar  1 10:00:01 host sshd[12]: Server listening\n\
+             Mar  1 10:00:02 host ntpd[40]: stepped clock\n",
+        )
+        .unwrap();
+        let manager = sandbox.manager().clone().with_syslog_path(&syslog);
+
+        sandbox.add_service("ntpd", "exec ntpd -n\n").unwrap();
+        let log = sandbox.root().join("sv/ntpd/log");
+        fs::create_dir_all(&log).unwrap();
+        fs::write(log.join("run"), "#!/bin/sh\nexec vlogger -t ntpd\n").unwrap();
+        let backend = manager.log_backend("ntpd").unwrap();
+        assert_eq!(backend.kind(), LogBackendKind::Syslog);
+        let messages: Vec<String> = manager
+            .tail_logs("ntpd", 10)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.message)
+            .collect();
+        assert_eq!(messages, ["synchronised", "stepped clock"]);
+        assert_eq!(
+            manager.tail_logs("ntpd", 10).unwrap()[0].timestamp_unix,
+            Some(1_709_287_200)
+        );
+
+        sandbox.add_service("cron", "exec crond -f\n").unwrap();
+        let log = sandbox.root().join("sv/cron/log");
+        fs::create_dir_all(log.join("main")).unwrap();
+        fs::write(log.join("run"), "#!/bin/sh\nexec s6-log T ./main\n").unwrap();
+        fs::write(
+            log.join("main/current"),
+            "2024-03-01 10:00:00.500000000 job started\n",
+        )
+        .unwrap();
+        assert_eq!(
+            manager.log_backend("cron").unwrap().kind(),
+            LogBackendKind::S6Log
+        );
+        let entries = manager.tail_logs("cron", 10).unwrap();
+        assert_eq!(entries[0].message, "job started");
+        assert_eq!(entries[0].timestamp_nanos, Some(500_000_000));
+        assert!(manager.follow_logs("cron").is_ok());
+    }
+}
@@ -0,0 +1,722 @@
+//! Reading svlogd log directories: `current` plus rotated `@<tai64n>.s`/`.u` files.
+use crate::{Result, ServiceError, ServiceLogEntry, ServiceManager};
+use inotify::{EventMask, Inotify, WatchMask};
+use regex::Regex;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Bytes read per step when scanning a log file backwards.
+const TAIL_BLOCK_SIZE: u64 = 64 * 1024;
+const EVENT_BUFFER_SIZE: usize = 4096;
+
+/// Turns one line of a log file into an entry, or `None` for a line that
+/// belongs to some other service.
+pub(crate) type LineParser = Arc<dyn Fn(&str) -> Option<ServiceLogEntry> + Send + Sync>;
+
+/// Rough importance of a log line, guessed from its wording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum LogSeverity {
+    #[default]
+    Info,
+    Warning,
+    Error,
+}
+
+const ERROR_MARKERS: [&str; 8] = [
+    "error", "err:", "fatal", "panic", "crit", "emerg", "alert", "failed",
+];
+const WARNING_MARKERS: [&str; 3] = ["warn", "deprecated", "retrying"];
+
+impl LogSeverity {
+    pub const ALL: [LogSeverity; 3] = [LogSeverity::Info, LogSeverity::Warning, LogSeverity::Error];
+
+    /// Naive keyword match; runit loggers carry no structured level.
+    pub fn classify(message: &str) -> Self {
+        let lower = message.to_ascii_lowercase();
+        if ERROR_MARKERS.iter().any(|marker| lower.contains(marker)) {
+            LogSeverity::Error
+        } else if WARNING_MARKERS.iter().any(|marker| lower.contains(marker)) {
+            LogSeverity::Warning
+        } else {
+            LogSeverity::Info
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogSeverity::Info => "info",
+            LogSeverity::Warning => "warning",
+            LogSeverity::Error => "error",
+        }
+    }
+}
+
+impl fmt::Display for LogSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for LogSeverity {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "info" => Ok(LogSeverity::Info),
+            "warn" | "warning" => Ok(LogSeverity::Warning),
+            "error" | "err" => Ok(LogSeverity::Error),
+            _ => Err(format!(
+                "unknown severity {value:?} (expected info, warning or error)"
+            )),
+        }
+    }
+}
+
+impl ServiceLogEntry {
+    pub fn severity(&self) -> LogSeverity {
+        LogSeverity::classify(&self.message)
+    }
+}
+
+/// Text a log line must contain.
+#[derive(Debug, Clone)]
+pub enum LogPattern {
+    /// Case-insensitive substring.
+    Substring(String),
+    Regex(Regex),
+}
+
+impl LogPattern {
+    /// Build a pattern, compiling `pattern` as a regular expression when `regex` is set.
+    pub fn new(pattern: &str, regex: bool) -> Result<Self> {
+        if regex {
+            Regex::new(pattern)
+                .map(LogPattern::Regex)
+                .map_err(|err| ServiceError::InvalidPattern(err.to_string()))
+        } else {
+            Ok(LogPattern::Substring(pattern.to_lowercase()))
+        }
+    }
+
+    pub fn is_match(&self, message: &str) -> bool {
+        match self {
+            LogPattern::Substring(needle) => message.to_lowercase().contains(needle),
+            LogPattern::Regex(regex) => regex.is_match(message),
+        }
+    }
+}
+
+/// Server-side selection applied while reading logs, so matches further back
+/// than the line limit are still found.
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    pub pattern: Option<LogPattern>,
+    /// Only entries at or after this Unix time. Entries without a timestamp
+    /// are kept.
+    pub since: Option<i64>,
+    pub min_severity: Option<LogSeverity>,
+}
+
+impl LogFilter {
+    pub fn is_empty(&self) -> bool {
+        self.pattern.is_none() && self.since.is_none() && self.min_severity.is_none()
+    }
+
+    pub fn matches(&self, entry: &ServiceLogEntry) -> bool {
+        self.pattern
+            .as_ref()
+            .is_none_or(|pattern| pattern.is_match(&entry.message))
+            && self
+                .min_severity
+                .is_none_or(|severity| entry.severity() >= severity)
+            && !self.is_before_since(entry)
+    }
+
+    fn is_before_since(&self, entry: &ServiceLogEntry) -> bool {
+        matches!((self.since, entry.timestamp_unix), (Some(since), Some(unix)) if unix < since)
+    }
+}
+
+/// A log line found by [`ServiceManager::search_logs`].
+#[derive(Debug, Clone)]
+pub struct LogMatch {
+    pub service: String,
+    pub entry: ServiceLogEntry,
+}
+
+impl ServiceManager {
+    /// Find the newest `limit` lines matching `pattern` across `services`, or
+    /// across every defined service when `services` is empty. Results are
+    /// ordered newest first; services without a readable log are skipped.
+    pub fn search_logs(
+        &self,
+        pattern: &LogPattern,
+        services: &[String],
+        limit: usize,
+    ) -> Result<Vec<LogMatch>> {
+        let services = if services.is_empty() {
+            self.service_names()?
+        } else {
+            for service in services {
+                self.validate_log_name(service)?;
+            }
+            services.to_vec()
+        };
+        let filter = LogFilter {
+            pattern: Some(pattern.clone()),
+            ..LogFilter::default()
+        };
+
+        let mut matches = Vec::new();
+        for service in services {
+            let Ok(entries) = self.tail_logs_filtered(&service, limit, &filter) else {
+                continue;
+            };
+            matches.extend(entries.into_iter().map(|entry| LogMatch {
+                service: service.clone(),
+                entry,
+            }));
+        }
+
+        // Untimestamped lines sort last; within a service the read order is kept.
+        matches.sort_by_key(|found| {
+            std::cmp::Reverse((found.entry.timestamp_unix, found.entry.timestamp_nanos))
+        });
+        matches.truncate(limit);
+        Ok(matches)
+    }
+
+    /// Names of the visible definitions that pass the name policy, sorted.
+    fn service_names(&self) -> Result<Vec<String>> {
+        let entries = std::fs::read_dir(&self.definitions_dir)
+            .map_err(|err| ServiceError::from_io(&self.definitions_dir, err))?;
+        let additional = self
+            .additional_dirs
+            .iter()
+            .filter_map(|(definitions, _)| std::fs::read_dir(definitions).ok())
+            .flatten();
+        let mut names: Vec<String> = entries
+            .chain(additional)
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| self.validate_service_name(name).is_ok())
+            .collect();
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    /// Stream entries as svlogd appends them to the service's log, like `tail -f`.
+    ///
+    /// Following starts at the current end of the log; combine with
+    /// [`ServiceManager::tail_logs`] for history.
+    pub fn follow_logs(&self, service: &str) -> Result<LogFollower> {
+        self.validate_log_name(service)?;
+        self.log_backend(service)
+            .ok_or_else(|| ServiceError::LogUnavailable(service.to_string()))?
+            .follow()
+    }
+}
+
+/// Blocking iterator over entries appended to a log file, svlogd's `current`
+/// or any other.
+///
+/// When the logger rotates, the remainder of the old file is read before
+/// switching to the new one, so no lines are lost across the rename.
+pub struct LogFollower {
+    inotify: Inotify,
+    dir: PathBuf,
+    file_name: String,
+    parse: LineParser,
+    file: Option<File>,
+    position: u64,
+    pending: Vec<u8>,
+    ready: VecDeque<ServiceLogEntry>,
+}
+
+impl LogFollower {
+    /// Follow `file_name` in `dir`, reading its lines with `parse`.
+    pub(crate) fn new(dir: PathBuf, file_name: &str, parse: LineParser) -> Result<Self> {
+        let inotify = Inotify::init().map_err(|err| ServiceError::from_io("inotify", err))?;
+        inotify
+            .watches()
+            .add(
+                &dir,
+                WatchMask::MODIFY | WatchMask::CREATE | WatchMask::MOVED_TO | WatchMask::ONLYDIR,
+            )
+            .map_err(|err| ServiceError::from_io(&dir, err))?;
+
+        let current = dir.join(file_name);
+        let mut file = File::open(&current).map_err(|err| ServiceError::from_io(&current, err))?;
+        let position = file
+            .seek(SeekFrom::End(0))
+            .map_err(|err| ServiceError::from_io(&current, err))?;
+
+        Ok(LogFollower {
+            inotify,
+            dir,
+            file_name: file_name.to_string(),
+            parse,
+            file: Some(file),
+            position,
+            pending: Vec::new(),
+            ready: VecDeque::new(),
+        })
+    }
+
+    /// The next entry if one has been written since the last call, without
+    /// blocking, so a caller can poll and stop following between calls.
+    pub fn try_next(&mut self) -> Result<Option<ServiceLogEntry>> {
+        if self.ready.is_empty() {
+            let mut buffer = [0u8; EVENT_BUFFER_SIZE];
+            let events: Vec<EventMask> = match self.inotify.read_events(&mut buffer) {
+                Ok(events) => current_events(events, &self.file_name),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => Vec::new(),
+                Err(err) => return Err(ServiceError::from_io("inotify", err)),
+            };
+            self.handle(events)?;
+        }
+        Ok(self.ready.pop_front())
+    }
+
+    /// Block for the next batch of directory events and queue any new lines.
+    fn wait(&mut self) -> Result<()> {
+        let mut buffer = [0u8; EVENT_BUFFER_SIZE];
+        let events = self
+            .inotify
+            .read_events_blocking(&mut buffer)
+            .map_err(|err| ServiceError::from_io("inotify", err))?;
+        let events = current_events(events, &self.file_name);
+        self.handle(events)
+    }
+
+    fn handle(&mut self, events: Vec<EventMask>) -> Result<()> {
+        for mask in events {
+            self.drain()?;
+            if mask.intersects(EventMask::CREATE | EventMask::MOVED_TO) {
+                self.reopen()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Switch to a freshly created file, keeping a final unterminated line
+    /// from the rotated one.
+    fn reopen(&mut self) -> Result<()> {
+        if !self.pending.is_empty() {
+            let line = String::from_utf8_lossy(&self.pending).into_owned();
+            self.ready.extend((self.parse)(&line));
+            self.pending.clear();
+        }
+
+        let current = self.dir.join(&self.file_name);
+        self.position = 0;
+        self.file = match File::open(&current) {
+            Ok(file) => Some(file),
+            Err(err) if err.kind() == ErrorKind::NotFound => None,
+            Err(err) => return Err(ServiceError::from_io(&current, err)),
+        };
+        self.drain()
+    }
+
+    /// Read everything appended since the last call and queue complete lines.
+    fn drain(&mut self) -> Result<()> {
+        let Some(file) = self.file.as_mut() else {
+            return Ok(());
+        };
+        let current = self.dir.join(&self.file_name);
+        let io_err = |err| ServiceError::from_io(&current, err);
+
+        let length = file.metadata().map_err(io_err)?.len();
+        if length < self.position {
+            // Truncated in place; start over from the beginning.
+            self.position = 0;
+            self.pending.clear();
+        }
+        file.seek(SeekFrom::Start(self.position)).map_err(io_err)?;
+        let read = file.read_to_end(&mut self.pending).map_err(io_err)?;
+        self.position += read as u64;
+
+        while let Some(newline) = self.pending.iter().position(|byte| *byte == b'\n') {
+            let rest = self.pending.split_off(newline + 1);
+            let line = String::from_utf8_lossy(&self.pending[..newline]).into_owned();
+            self.ready.extend((self.parse)(&line));
+            self.pending = rest;
+        }
+        Ok(())
+    }
+}
+
+/// The masks of the events that concern the followed file.
+fn current_events(events: inotify::Events<'_>, file_name: &str) -> Vec<EventMask> {
+    events
+        .filter(|event| event.name.is_some_and(|name| name == file_name))
+        .map(|event| event.mask)
+        .collect()
+}
+
+impl Iterator for LogFollower {
+    type Item = Result<ServiceLogEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.ready.pop_front() {
+                return Some(Ok(entry));
+            }
+            if let Err(err) = self.wait() {
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+/// Return the last `limit` entries of `files`, newest file first, that
+/// `parse` accepts and pass `filter`, oldest first.
+///
+/// Files are read backwards in blocks so large logs cost only as much I/O as the
+/// requested lines need; when one file runs out the next is consulted in turn.
+/// Reading stops early once entries predate `filter.since`.
+pub(crate) fn read_log_tail(
+    files: &[PathBuf],
+    limit: usize,
+    filter: &LogFilter,
+    parse: &(dyn Fn(&str) -> Option<ServiceLogEntry> + Send + Sync),
+) -> io::Result<Vec<ServiceLogEntry>> {
+    let mut entries = Vec::new();
+    let mut finished = false;
+    for path in files {
+        let scanned = scan_backwards(path, TAIL_BLOCK_SIZE, &mut |line| {
+            let Some(entry) = parse(&String::from_utf8_lossy(line)) else {
+                return ControlFlow::Continue(());
+            };
+            if filter.is_before_since(&entry) {
+                finished = true;
+                return ControlFlow::Break(());
+            }
+            if filter.matches(&entry) {
+                entries.push(entry);
+            }
+            if entries.len() >= limit {
+                finished = true;
+                return ControlFlow::Break(());
+            }
+            ControlFlow::Continue(())
+        });
+        match scanned {
+            Ok(()) => {}
+            // svlogd may rotate or prune a file while we walk the directory.
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        }
+        if finished {
+            break;
+        }
+    }
+
+    entries.reverse();
+    Ok(entries)
+}
+
+/// `current` followed by the rotated files, newest first. TAI64N names sort
+/// chronologically, so a reverse name sort is enough.
+pub(crate) fn log_files_newest_first(log_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut rotated: Vec<String> = std::fs::read_dir(log_dir)?
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name.starts_with('@') && (name.ends_with(".s") || name.ends_with(".u")))
+        .collect();
+    rotated.sort_unstable_by(|a, b| b.cmp(a));
+
+    let mut files = vec![log_dir.join("current")];
+    files.extend(rotated.into_iter().map(|name| log_dir.join(name)));
+    Ok(files)
+}
+
+/// Feed the lines of `path` to `visit`, last line first, until it breaks.
+fn scan_backwards(
+    path: &Path,
+    block_size: u64,
+    visit: &mut dyn FnMut(&[u8]) -> ControlFlow<()>,
+) -> io::Result<()> {
+    let mut file = File::open(path)?;
+    let mut pos = file.seek(SeekFrom::End(0))?;
+    // Bytes after `pos` that have not been split into lines yet.
+    let mut pending: Vec<u8> = Vec::new();
+    // Still looking at the segment after the file's final newline, which is
+    // empty for a well-formed log and must not count as a line.
+    let mut trailing = true;
+
+    loop {
+        if pos == 0 {
+            if !(trailing && pending.is_empty()) {
+                let _ = visit(&pending);
+            }
+            return Ok(());
+        }
+
+        let start = pos.saturating_sub(block_size);
+        let mut block = vec![0; (pos - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut block)?;
+        block.append(&mut pending);
+        pos = start;
+
+        while let Some(newline) = block.iter().rposition(|byte| *byte == b'\n') {
+            if !(trailing && newline + 1 == block.len()) && visit(&block[newline + 1..]).is_break()
+            {
+                return Ok(());
+            }
+            trailing = false;
+            block.truncate(newline);
+        }
+        pending = block;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        LogFilter, LogPattern, LogSeverity, log_files_newest_first, read_log_tail, scan_backwards,
+    };
+    use crate::{ServiceLogEntry, ServiceManager, parse_svlogd_line};
+    use std::fs;
+    use std::io::{self, Write};
+    use std::ops::ControlFlow;
+    use std::path::Path;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn read_svlogd_tail(
+        log_dir: &Path,
+        limit: usize,
+        filter: &LogFilter,
+    ) -> io::Result<Vec<ServiceLogEntry>> {
+        read_log_tail(
+            &log_files_newest_first(log_dir)?,
+            limit,
+            filter,
+            &|line: &str| Some(parse_svlogd_line(line)),
+        )
+    }
+
+    fn temp_dir(label: &str) -> std::path::PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir =
+            std::env::temp_dir().join(format!("runkit-{label}-{}-{nanos}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn reads_lines_backwards_across_blocks() {
+        let dir = temp_dir("tail-blocks");
+        let path = dir.join("current");
+        fs::write(&path, "first\n\nthird line\nfourth\n").unwrap();
+
+        let last_lines = |wanted: usize, block_size: u64| -> Vec<String> {
+            let mut lines = Vec::new();
+            scan_backwards(&path, block_size, &mut |line| {
+                lines.push(String::from_utf8_lossy(line).into_owned());
+                if lines.len() == wanted {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })
+            .unwrap();
+            lines
+        };
+
+        for block_size in [1, 3, 7, 4096] {
+            assert_eq!(
+                last_lines(10, block_size),
+                ["fourth", "third line", "", "first"],
+                "{block_size}"
+            );
+            assert_eq!(last_lines(2, block_size), ["fourth", "third line"]);
+        }
+
+        fs::write(&path, "no newline").unwrap();
+        assert_eq!(last_lines(5, 4), ["no newline"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn continues_into_rotated_files() {
+        let dir = temp_dir("tail-rotated");
+        fs::write(dir.join("@400000006553f0000000000a.s"), "one\ntwo\n").unwrap();
+        fs::write(dir.join("@400000006553f1000000000a.u"), "three\n").unwrap();
+        fs::write(dir.join("current"), "four\nfive\n").unwrap();
+        fs::write(dir.join("state"), "ignored\n").unwrap();
+        fs::write(dir.join("config"), "s1000\n").unwrap();
+
+        let messages = |limit| -> Vec<String> {
+            read_svlogd_tail(&dir, limit, &LogFilter::default())
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.message)
+                .collect()
+        };
+        assert_eq!(messages(2), ["four", "five"]);
+        assert_eq!(messages(4), ["two", "three", "four", "five"]);
+        assert_eq!(messages(50), ["one", "two", "three", "four", "five"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn follows_appends_and_rotation() {
+        let root = temp_dir("follow");
+        let definitions = root.join("sv");
+        let log_dir = definitions.join("sshd").join("log/main");
+        fs::create_dir_all(&log_dir).unwrap();
+        fs::create_dir_all(root.join("service")).unwrap();
+        fs::write(log_dir.join("current"), "history\n").unwrap();
+
+        let manager = ServiceManager::new(&definitions, root.join("service"));
+        let mut follower = manager.follow_logs("sshd").unwrap();
+        let mut next_message = || follower.next().unwrap().unwrap().message;
+
+        let mut current = fs::OpenOptions::new()
+            .append(true)
+            .open(log_dir.join("current"))
+            .unwrap();
+        current.write_all(b"fresh\npart").unwrap();
+        assert_eq!(next_message(), "fresh");
+
+        current.write_all(b"ial\n").unwrap();
+        fs::rename(
+            log_dir.join("current"),
+            log_dir.join("@400000006553f0000000000a.s"),
+        )
+        .unwrap();
+        fs::write(log_dir.join("current"), "rotated\n").unwrap();
+        assert_eq!(next_message(), "partial");
+        assert_eq!(next_message(), "rotated");
+
+        assert!(follower.try_next().unwrap().is_none());
+        fs::write(log_dir.join("current"), "rotated\npolled\n").unwrap();
+        assert_eq!(follower.try_next().unwrap().unwrap().message, "polled");
+        assert!(follower.try_next().unwrap().is_none());
+
+        assert!(manager.follow_logs("missing").is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn filters_while_reading_backwards() {
+        let dir = temp_dir("tail-filter");
+        fs::write(
+            dir.join("@400000006553f0000000000a.s"),
+            "@400000006553f10a00000000 old ERROR disk full\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("current"),
+            "@400000006553f11400000000 listening\n\
+             @400000006553f11e00000000 warning: slow client\n\
+             @400000006553f12800000000 connect from 10.0.0.2\n\
+             @400000006553f13200000000 Error: bad packet\n",
+        )
+        .unwrap();
+        let messages = |filter: LogFilter, limit| -> Vec<String> {
+            read_svlogd_tail(&dir, limit, &filter)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.message)
+                .collect()
+        };
+
+        let errors = LogFilter {
+            min_severity: Some(LogSeverity::Error),
+            ..LogFilter::default()
+        };
+        assert_eq!(
+            messages(errors.clone(), 10),
+            ["old ERROR disk full", "Error: bad packet"]
+        );
+        assert_eq!(messages(errors, 1), ["Error: bad packet"]);
+
+        let grep = LogFilter {
+            pattern: Some(LogPattern::new("CONNECT", false).unwrap()),
+            ..LogFilter::default()
+        };
+        assert_eq!(messages(grep, 10), ["connect from 10.0.0.2"]);
+
+        let regex = LogFilter {
+            pattern: Some(LogPattern::new(r"\d+\.\d+\.\d+\.\d+$", true).unwrap()),
+            ..LogFilter::default()
+        };
+        assert_eq!(messages(regex, 10), ["connect from 10.0.0.2"]);
+        assert!(LogPattern::new("(", true).is_err());
+
+        let recent = LogFilter {
+            since: Some(1_700_000_030),
+            ..LogFilter::default()
+        };
+        assert_eq!(
+            messages(recent, 10),
+            ["connect from 10.0.0.2", "Error: bad packet"]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn searches_across_services_newest_first() {
+        let root = temp_dir("search");
+        let definitions = root.join("sv");
+        for (service, lines) in [
+            (
+                "sshd",
+                "@400000006553f10a00000000 accepted key\n@400000006553f12800000000 timeout waiting\n",
+            ),
+            ("dhcpcd", "@400000006553f11e00000000 lease timeout\n"),
+            ("cronie", "@400000006553f11400000000 job ran\n"),
+        ] {
+            let log_dir = definitions.join(service).join("log/main");
+            fs::create_dir_all(&log_dir).unwrap();
+            fs::write(log_dir.join("current"), lines).unwrap();
+        }
+        fs::create_dir_all(definitions.join("nolog")).unwrap();
+        let manager = ServiceManager::new(&definitions, root.join("service"));
+        let pattern = LogPattern::new("timeout", false).unwrap();
+
+        let found: Vec<(String, String)> = manager
+            .search_logs(&pattern, &[], 10)
+            .unwrap()
+            .into_iter()
+            .map(|found| (found.service, found.entry.message))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("sshd".to_string(), "timeout waiting".to_string()),
+                ("dhcpcd".to_string(), "lease timeout".to_string()),
+            ]
+        );
+
+        let only_dhcpcd = manager
+            .search_logs(&pattern, &["dhcpcd".to_string()], 10)
+            .unwrap();
+        assert_eq!(only_dhcpcd.len(), 1);
+        assert_eq!(manager.search_logs(&pattern, &[], 1).unwrap().len(), 1);
+        assert!(
+            manager
+                .search_logs(&pattern, &["../etc".to_string()], 10)
+                .is_err()
+        );
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,153 @@
+//! Planned downtime: a service kept down on purpose, without alerts.
+use crate::{Result, ServiceBackend, ServiceError, ServiceManager, remove_if_present};
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Marker inside a definition recording that the service is in maintenance.
+const MAINTENANCE_MARKER: &str = ".runkit-maintenance";
+
+/// Why and since when a service is in maintenance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Maintenance {
+    /// Unix time at which maintenance began.
+    pub since: i64,
+    pub reason: Option<String>,
+    /// The service was running and is started again when maintenance ends.
+    pub was_running: bool,
+    /// Maintenance created the `down` file and removes it when it ends.
+    pub added_down_file: bool,
+}
+
+impl Maintenance {
+    fn to_marker(&self) -> String {
+        let mut marker = format!(
+            "since={}\nwas_running={}\nadded_down_file={}\n",
+            self.since, self.was_running, self.added_down_file
+        );
+        if let Some(reason) = &self.reason {
+            marker.push_str(&format!("reason={}\n", reason.replace('\n', " ")));
+        }
+        marker
+    }
+
+    fn from_marker(marker: &str) -> Self {
+        let field = |key: &str| {
+            marker
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+        };
+        Maintenance {
+            since: field("since")
+                .and_then(|since| since.parse().ok())
+                .unwrap_or(0),
+            reason: field("reason").map(str::to_string),
+            was_running: field("was_running") == Some("true"),
+            added_down_file: field("added_down_file") == Some("true"),
+        }
+    }
+}
+
+/// Maintenance recorded in the definition at `definition_path`, if any.
+pub(crate) fn read_maintenance(definition_path: &Path) -> Option<Maintenance> {
+    fs::read_to_string(definition_path.join(MAINTENANCE_MARKER))
+        .ok()
+        .map(|marker| Maintenance::from_marker(&marker))
+}
+
+impl ServiceManager {
+    pub fn maintenance(&self, service: &str) -> Result<Option<Maintenance>> {
+        Ok(read_maintenance(&self.existing_definition(service)?))
+    }
+
+    /// Stop the service and keep it down, at boot too, until
+    /// [`ServiceManager::end_maintenance`]. runkitd raises no alerts for a
+    /// service in maintenance. Calling this again only updates the reason.
+    pub fn begin_maintenance(&self, service: &str, reason: Option<&str>) -> Result<Maintenance> {
+        let definition_path = self.existing_definition(service)?;
+        let marker_path = definition_path.join(MAINTENANCE_MARKER);
+        let reason = reason
+            .map(str::trim)
+            .filter(|reason| !reason.is_empty())
+            .map(str::to_string);
+        if let Some(current) = read_maintenance(&definition_path) {
+            let updated = Maintenance { reason, ..current };
+            fs::write(&marker_path, updated.to_marker())
+                .map_err(|err| ServiceError::from_io(&marker_path, err))?;
+            return Ok(updated);
+        }
+
+        let supervised = self.dirs_for(service).1.join(service).exists();
+        let maintenance = Maintenance {
+            since: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs() as i64)
+                .unwrap_or_default(),
+            reason,
+            was_running: supervised && self.status(service)?.pid().is_some(),
+            added_down_file: !definition_path.join("down").exists(),
+        };
+        // Written first so the stop below is already known to be intended.
+        fs::write(&marker_path, maintenance.to_marker())
+            .map_err(|err| ServiceError::from_io(&marker_path, err))?;
+        if maintenance.added_down_file {
+            self.set_down_file(service)?;
+        }
+        if supervised {
+            self.control(service, "down")?;
+        }
+        Ok(maintenance)
+    }
+
+    /// Leave maintenance, removing the `down` file it created and starting the
+    /// service again if it was running before. Returns the maintenance that
+    /// ended, or `None` when the service was not in maintenance.
+    pub fn end_maintenance(&self, service: &str) -> Result<Option<Maintenance>> {
+        let definition_path = self.existing_definition(service)?;
+        let Some(maintenance) = read_maintenance(&definition_path) else {
+            return Ok(None);
+        };
+        if maintenance.added_down_file {
+            self.clear_down_file(service)?;
+        }
+        remove_if_present(&definition_path.join(MAINTENANCE_MARKER))?;
+        if maintenance.was_running {
+            ServiceBackend::start(self, service)?;
+        }
+        Ok(Some(maintenance))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::SandboxFixture;
+
+    #[test]
+    fn maintenance_stops_and_restores_the_service() {
+        let sandbox = SandboxFixture::new().unwrap();
+        sandbox.add_service("nginx", "exec nginx\n").unwrap();
+        sandbox.enable("nginx").unwrap();
+        sandbox.set_running("nginx", 42).unwrap();
+        let manager = sandbox.manager();
+        let definition = manager.definitions_dir().join("nginx");
+
+        let maintenance = manager
+            .begin_maintenance("nginx", Some("certificate renewal"))
+            .unwrap();
+        assert!(maintenance.was_running && maintenance.added_down_file);
+        assert!(manager.status("nginx").unwrap().pid().is_none());
+        assert!(definition.join("down").exists());
+        let listed = manager.list_services().unwrap();
+        assert_eq!(
+            listed[0].maintenance.as_ref().unwrap().reason.as_deref(),
+            Some("certificate renewal")
+        );
+
+        let ended = manager.end_maintenance("nginx").unwrap().unwrap();
+        assert_eq!(ended.since, maintenance.since);
+        assert!(!definition.join("down").exists());
+        assert!(manager.status("nginx").unwrap().pid().is_some());
+        assert!(manager.maintenance("nginx").unwrap().is_none());
+        assert!(manager.end_maintenance("nginx").unwrap().is_none());
+    }
+}
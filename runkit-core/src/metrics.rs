@@ -0,0 +1,138 @@
+//! Live view of a supervised process tree: its processes and resource usage.
+use crate::proc::{ProcessNode, ProcessTable};
+use crate::{Result, ServiceManager};
+use std::collections::BTreeMap;
+use std::fs;
+use std::time::{Duration, Instant};
+
+/// How long [`ServiceManager::service_metrics`] watches CPU time for.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Clock ticks per second in `/proc/<pid>/stat`; `USER_HZ` is fixed at 100 in
+/// the kernel ABI regardless of the configured tick rate.
+const USER_HZ: f64 = 100.0;
+
+/// Resource usage of a running service, summed over its main process and every
+/// descendant.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ServiceMetrics {
+    pub pid: u32,
+    /// Share of one CPU used during the sample window, so a busy multi-threaded
+    /// service can exceed 100.
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+    /// `None` when the descriptors could not be listed, e.g. without root.
+    pub open_fds: Option<u64>,
+    pub threads: u64,
+    pub processes: u32,
+}
+
+impl ServiceManager {
+    /// Sample CPU, memory, descriptor and thread usage of a service's process
+    /// tree. Blocks for a quarter of a second to measure CPU time; `None` when the
+    /// service has no running process.
+    pub fn service_metrics(&self, service: &str) -> Result<Option<ServiceMetrics>> {
+        let Some(pid) = self.status(service)?.pid() else {
+            return Ok(None);
+        };
+        Ok(sample_tree(pid, SAMPLE_INTERVAL))
+    }
+
+    /// Resident memory of each running service's process tree, keyed by name.
+    /// The pids come from runsv's `supervise/pid` files and the memory from one
+    /// read of `/proc`, so this is cheap enough to run for the whole list.
+    pub fn memory_usage(&self) -> Result<BTreeMap<String, u64>> {
+        let processes = ProcessTable::snapshot();
+        let mut usage = BTreeMap::new();
+        for (_, enabled_dir) in self.service_dirs() {
+            let Ok(entries) = fs::read_dir(enabled_dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let Ok(name) = entry.file_name().into_string() else {
+                    continue;
+                };
+                if name.starts_with('.') || usage.contains_key(&name) {
+                    continue;
+                }
+                if let Some(rss_bytes) = fs::read_to_string(entry.path().join("supervise/pid"))
+                    .ok()
+                    .and_then(|pid| pid.trim().parse().ok())
+                    .and_then(|pid| processes.resource_sample(pid))
+                    .map(|sample| sample.rss_bytes)
+                {
+                    usage.insert(name, rss_bytes);
+                }
+            }
+        }
+        Ok(usage)
+    }
+
+    /// Every process descended from the service's supervised pid; `None` when the
+    /// service has no running process.
+    pub fn process_tree(&self, service: &str) -> Result<Option<ProcessNode>> {
+        let Some(pid) = self.status(service)?.pid() else {
+            return Ok(None);
+        };
+        Ok(ProcessTable::snapshot().process_tree(pid))
+    }
+}
+
+fn sample_tree(pid: u32, interval: Duration) -> Option<ServiceMetrics> {
+    let started = Instant::now();
+    let before = ProcessTable::snapshot().resource_sample(pid)?;
+    std::thread::sleep(interval);
+    let after = ProcessTable::snapshot().resource_sample(pid)?;
+    let elapsed = started.elapsed().as_secs_f64();
+
+    // Children that exited between the snapshots take their CPU time with them.
+    let ticks = after.cpu_ticks.saturating_sub(before.cpu_ticks) as f64;
+    Some(ServiceMetrics {
+        pid,
+        cpu_percent: ticks / USER_HZ / elapsed * 100.0,
+        rss_bytes: after.rss_bytes,
+        open_fds: after.open_fds,
+        threads: after.threads,
+        processes: after.processes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sample_tree;
+    use crate::testing::SandboxFixture;
+    use std::fs;
+    use std::time::Duration;
+
+    #[test]
+    fn samples_the_current_process() {
+        let metrics = sample_tree(std::process::id(), Duration::from_millis(20)).unwrap();
+        assert!(metrics.processes >= 1);
+        assert!(metrics.threads >= 1);
+        assert!(metrics.rss_bytes > 0);
+        assert!(metrics.open_fds.is_some_and(|fds| fds > 0));
+        assert!(metrics.cpu_percent >= 0.0);
+        assert!(sample_tree(u32::MAX, Duration::ZERO).is_none());
+    }
+
+    #[test]
+    fn reads_memory_from_supervised_pids() {
+        let sandbox = SandboxFixture::new().unwrap();
+        for name in ["sshd", "stale"] {
+            sandbox.add_service(name, "exec true\n").unwrap();
+            sandbox.enable(name).unwrap();
+        }
+        let manager = sandbox.manager();
+        let supervise = |name: &str, pid: u32| {
+            let dir = manager.enabled_dir().join(name).join("supervise");
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("pid"), format!("{pid}\n")).unwrap();
+        };
+        supervise("sshd", std::process::id());
+        supervise("stale", u32::MAX);
+
+        let usage = manager.memory_usage().unwrap();
+        assert!(usage["sshd"] > 0);
+        assert!(!usage.contains_key("stale"));
+    }
+}
@@ -0,0 +1,101 @@
+//! Which service names runkit accepts.
+use std::fmt;
+use std::str::FromStr;
+
+/// How strictly service names are validated.
+///
+/// Every policy rejects names that could escape the service directories or
+/// that runsvdir ignores: empty names, anything containing `/` or NUL, and
+/// names starting with `.`. A leading `-` is refused too, since `sv` would read
+/// it as an option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NamePolicy {
+    /// Lowercase ASCII letters, digits, `-`, `_` and `.`, as used by Void's own
+    /// service directories.
+    Strict,
+    /// The POSIX portable filename character set: ASCII letters, digits, `-`,
+    /// `_` and `.`.
+    #[default]
+    Portable,
+    /// Any single path component, including `@`, `+`, spaces and non-ASCII
+    /// text. Names are handed to `sv` as absolute paths so nothing in them is
+    /// interpreted.
+    Permissive,
+}
+
+impl NamePolicy {
+    pub const ALL: [NamePolicy; 3] = [
+        NamePolicy::Strict,
+        NamePolicy::Portable,
+        NamePolicy::Permissive,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NamePolicy::Strict => "strict",
+            NamePolicy::Portable => "portable",
+            NamePolicy::Permissive => "permissive",
+        }
+    }
+
+    pub fn allows(self, name: &str) -> bool {
+        if name.is_empty()
+            || name.starts_with('.')
+            || name.starts_with('-')
+            || name.contains(['/', '\0'])
+        {
+            return false;
+        }
+
+        match self {
+            NamePolicy::Strict => name.chars().all(|c| {
+                c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_' | '.')
+            }),
+            NamePolicy::Portable => name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')),
+            NamePolicy::Permissive => !name.chars().any(char::is_control),
+        }
+    }
+}
+
+impl fmt::Display for NamePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for NamePolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        NamePolicy::ALL
+            .into_iter()
+            .find(|policy| policy.as_str().eq_ignore_ascii_case(value))
+            .ok_or_else(|| {
+                format!("unknown name policy {value:?} (expected strict, portable or permissive)")
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NamePolicy;
+
+    #[test]
+    fn policies_widen_but_never_allow_traversal() {
+        for name in ["", ".", "..", "../etc", "a/b", ".hidden", "-h", "nul\0"] {
+            for policy in NamePolicy::ALL {
+                assert!(!policy.allows(name), "{policy} allowed {name:?}");
+            }
+        }
+
+        assert!(NamePolicy::Strict.allows("agetty-tty1"));
+        assert!(!NamePolicy::Strict.allows("NetworkManager"));
+        assert!(NamePolicy::Portable.allows("NetworkManager"));
+        assert!(!NamePolicy::Portable.allows("getty@tty1"));
+        assert!(NamePolicy::Permissive.allows("getty@tty1"));
+        assert!(NamePolicy::Permissive.allows("c++-daemon"));
+        assert!(!NamePolicy::Permissive.allows("tab\tname"));
+    }
+}
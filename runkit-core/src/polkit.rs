@@ -0,0 +1,149 @@
+//! The polkit actions runkitd checks, and the policy file that defines them.
+//!
+//! Each kind of change has its own action, so an administrator can write
+//! rules for one of them. runkitd passes the service and the operation as the
+//! `service` and `operation` details, which rules read with `action.lookup()`.
+
+pub const POLKIT_ACTION_START: &str = "tech.geektoshi.Runkit.manage.start";
+pub const POLKIT_ACTION_STOP: &str = "tech.geektoshi.Runkit.manage.stop";
+pub const POLKIT_ACTION_RESTART: &str = "tech.geektoshi.Runkit.manage.restart";
+pub const POLKIT_ACTION_ENABLE: &str = "tech.geektoshi.Runkit.manage.enable";
+pub const POLKIT_ACTION_EDIT_FILES: &str = "tech.geektoshi.Runkit.edit-files";
+pub const POLKIT_ACTION_ALERTS: &str = "tech.geektoshi.Runkit.alerts";
+/// Turning on cached authorization.
+pub const POLKIT_ACTION_REQUIRE_PASSWORD: &str = "tech.geektoshi.Runkit.require_password";
+/// Stands in for an action that would ask for a password while the caller
+/// has chosen to reuse authorizations.
+pub const POLKIT_ACTION_ALLOW_CACHE: &str = "tech.geektoshi.Runkit.cached";
+/// Signals and forced stops that can kill a service outright; always asks for a
+/// password, whatever the cached-authorization preference.
+pub const POLKIT_ACTION_FORCE: &str = "tech.geektoshi.Runkit.force";
+/// Stopping or disabling a protected service despite the guard; always asks
+/// for a password.
+pub const POLKIT_ACTION_PROTECTED: &str = "tech.geektoshi.Runkit.protected";
+
+/// Where pkexec would find runkitd, for the policy's `exec.path` annotation.
+pub const DEFAULT_RUNKITD_PATH: &str = "/usr/libexec/runkitd";
+
+/// One `<action>` of the policy file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PolkitAction {
+    pub id: &'static str,
+    pub description: &'static str,
+    pub message: &'static str,
+    /// `auth_admin` or `auth_admin_keep`, for every kind of session.
+    pub default: &'static str,
+}
+
+pub const POLKIT_ACTIONS: &[PolkitAction] = &[
+    PolkitAction {
+        id: POLKIT_ACTION_START,
+        description: "Start runit services",
+        message: "Authentication is required to start a runit service.",
+        default: "auth_admin",
+    },
+    PolkitAction {
+        id: POLKIT_ACTION_STOP,
+        description: "Stop runit services",
+        message: "Authentication is required to stop a runit service.",
+        default: "auth_admin",
+    },
+    PolkitAction {
+        id: POLKIT_ACTION_RESTART,
+        description: "Restart, reload or signal runit services",
+        message: "Authentication is required to restart a runit service.",
+        default: "auth_admin",
+    },
+    PolkitAction {
+        id: POLKIT_ACTION_ENABLE,
+        description: "Enable or disable runit services",
+        message: "Authentication is required to change which runit services start at boot.",
+        default: "auth_admin",
+    },
+    PolkitAction {
+        id: POLKIT_ACTION_EDIT_FILES,
+        description: "Create, edit or delete runit service definitions",
+        message: "Authentication is required to change a runit service definition.",
+        default: "auth_admin",
+    },
+    PolkitAction {
+        id: POLKIT_ACTION_ALERTS,
+        description: "Silence runit service alerts",
+        message: "Authentication is required to silence service alerts.",
+        default: "auth_admin",
+    },
+    PolkitAction {
+        id: POLKIT_ACTION_REQUIRE_PASSWORD,
+        description: "Manage runit services (always require password)",
+        message: "Authentication is required to manage runit services.",
+        default: "auth_admin",
+    },
+    PolkitAction {
+        id: POLKIT_ACTION_ALLOW_CACHE,
+        description: "Manage runit services (reuse authorization while Runkit is open)",
+        message: "Authentication is required to manage runit services.",
+        default: "auth_admin_keep",
+    },
+    PolkitAction {
+        id: POLKIT_ACTION_FORCE,
+        description: "Signal or force-stop runit services",
+        message: "Authentication is required to signal or force-stop a runit service.",
+        default: "auth_admin",
+    },
+    PolkitAction {
+        id: POLKIT_ACTION_PROTECTED,
+        description: "Stop or disable protected runit services",
+        message: "Authentication is required to stop or disable a service the system depends on.",
+        default: "auth_admin",
+    },
+];
+
+/// The policy file defining [`POLKIT_ACTIONS`], with `runkitd` at `exec_path`.
+pub fn polkit_policy(exec_path: &str) -> String {
+    let mut policy = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE policyconfig PUBLIC \"-//freedesktop//DTD PolicyKit Policy Configuration 1.0//EN\"\n  \
+         \"http://www.freedesktop.org/standards/PolicyKit/1/policyconfig.dtd\">\n\
+         <policyconfig>\n",
+    );
+    for (index, action) in POLKIT_ACTIONS.iter().enumerate() {
+        if index > 0 {
+            policy.push('\n');
+        }
+        policy.push_str(&format!(
+            "  <action id=\"{id}\">\n    \
+             <description>{description}</description>\n    \
+             <message>{message}</message>\n    \
+             <defaults>\n      \
+             <allow_any>{default}</allow_any>\n      \
+             <allow_inactive>{default}</allow_inactive>\n      \
+             <allow_active>{default}</allow_active>\n    \
+             </defaults>\n    \
+             <annotate key=\"org.freedesktop.policykit.exec.path\">{exec_path}</annotate>\n    \
+             <annotate key=\"org.freedesktop.policykit.exec.allow_gui\">true</annotate>\n  \
+             </action>\n",
+            id = action.id,
+            description = action.description,
+            message = action.message,
+            default = action.default,
+        ));
+    }
+    policy.push_str("</policyconfig>\n");
+    policy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DEFAULT_RUNKITD_PATH, POLKIT_ACTIONS, polkit_policy};
+
+    #[test]
+    fn shipped_policy_matches_the_generated_one() {
+        let shipped = include_str!("../../assets/polkit-1/actions/tech.geektoshi.Runkit.policy");
+        assert_eq!(shipped, polkit_policy(DEFAULT_RUNKITD_PATH));
+        assert!(
+            POLKIT_ACTIONS
+                .iter()
+                .all(|action| shipped.contains(&format!("\"{}\"", action.id)))
+        );
+    }
+}
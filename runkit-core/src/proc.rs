@@ -0,0 +1,554 @@
+//! Process inspection through `/proc` and the unified cgroup hierarchy.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Cumulative storage I/O attributed to a service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IoUsage {
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    pub source: IoSource,
+}
+
+/// Where an [`IoUsage`] figure came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IoSource {
+    /// Sum of `/proc/<pid>/io` over the live process tree; I/O done by children
+    /// that have already exited is not included.
+    #[default]
+    Processes,
+    /// `io.stat` of a cgroup dedicated to the service, which also covers exited
+    /// processes.
+    Cgroup,
+}
+
+/// One process of a service's tree, with its descendants.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessNode {
+    pub pid: u32,
+    /// Kernel command name from `/proc/<pid>/comm`, at most 15 bytes.
+    pub comm: String,
+    /// Arguments from `/proc/<pid>/cmdline`; empty for kernel threads and zombies.
+    pub cmdline: Vec<String>,
+    pub children: Vec<ProcessNode>,
+}
+
+/// Resource counters summed over a process tree at one instant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct ResourceSample {
+    /// User plus system time, in clock ticks.
+    pub(crate) cpu_ticks: u64,
+    pub(crate) rss_bytes: u64,
+    /// `None` when no `/proc/<pid>/fd` in the tree was readable.
+    pub(crate) open_fds: Option<u64>,
+    pub(crate) threads: u64,
+    pub(crate) processes: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ProcessEntry {
+    ppid: u32,
+    state: char,
+    cpu_ticks: u64,
+}
+
+/// Point-in-time view of every process's parent and scheduler state.
+#[derive(Debug, Default)]
+pub(crate) struct ProcessTable {
+    proc_dir: PathBuf,
+    cgroup_root: PathBuf,
+    processes: HashMap<u32, ProcessEntry>,
+    children: HashMap<u32, Vec<u32>>,
+}
+
+impl ProcessTable {
+    /// Read `/proc/<pid>/stat` for every visible process, skipping ones that exit
+    /// while the table is being built.
+    pub(crate) fn snapshot() -> Self {
+        Self::from_proc_dir(Path::new("/proc"))
+    }
+
+    fn from_proc_dir(proc_dir: &Path) -> Self {
+        let mut table = ProcessTable {
+            proc_dir: proc_dir.to_path_buf(),
+            cgroup_root: PathBuf::from("/sys/fs/cgroup"),
+            ..Default::default()
+        };
+        let Ok(entries) = std::fs::read_dir(proc_dir) else {
+            return table;
+        };
+        for entry in entries.flatten() {
+            let Some(pid) = entry
+                .file_name()
+                .to_str()
+                .and_then(|s| s.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            if let Ok(stat) = std::fs::read_to_string(entry.path().join("stat"))
+                && let Some(process) = parse_stat(&stat)
+            {
+                table.insert(pid, process);
+            }
+        }
+        table
+    }
+
+    fn insert(&mut self, pid: u32, process: ProcessEntry) {
+        if process.ppid != pid {
+            self.children.entry(process.ppid).or_default().push(pid);
+        }
+        self.processes.insert(pid, process);
+    }
+
+    /// `root` and all of its descendants that are present in the table.
+    fn tree(&self, root: u32) -> Vec<u32> {
+        let mut pids = Vec::new();
+        let mut pending = vec![root];
+        while let Some(pid) = pending.pop() {
+            if self.processes.contains_key(&pid) {
+                pids.push(pid);
+            }
+            if let Some(children) = self.children.get(&pid) {
+                pending.extend(children);
+            }
+        }
+        pids.sort_unstable();
+        pids
+    }
+
+    /// `root` and its descendants as a tree, children ordered by pid. `None`
+    /// when `root` is not in the table.
+    pub(crate) fn process_tree(&self, root: u32) -> Option<ProcessNode> {
+        if !self.processes.contains_key(&root) {
+            return None;
+        }
+        let mut children: Vec<u32> = self.children.get(&root).cloned().unwrap_or_default();
+        children.sort_unstable();
+        Some(ProcessNode {
+            pid: root,
+            comm: std::fs::read_to_string(self.pid_path(root, "comm"))
+                .map(|comm| comm.trim_end().to_string())
+                .unwrap_or_default(),
+            cmdline: self.cmdline(root),
+            children: children
+                .into_iter()
+                .filter_map(|pid| self.process_tree(pid))
+                .collect(),
+        })
+    }
+
+    fn cmdline(&self, pid: u32) -> Vec<String> {
+        let Ok(raw) = std::fs::read(self.pid_path(pid, "cmdline")) else {
+            return Vec::new();
+        };
+        raw.split(|byte| *byte == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect()
+    }
+
+    /// Zombie processes rooted at `root`, including `root` itself.
+    pub(crate) fn defunct_in_tree(&self, root: u32) -> Vec<u32> {
+        self.tree(root)
+            .into_iter()
+            .filter(|pid| self.processes[pid].state == 'Z')
+            .collect()
+    }
+
+    /// Bytes read from and written to storage by the service rooted at `root`.
+    ///
+    /// Prefers the cgroup's `io.stat` when the service runs in a cgroup of its own
+    /// (one its supervisor is not in), otherwise sums `/proc/<pid>/io` over the
+    /// tree. `None` when neither is readable, e.g. without root privileges.
+    pub(crate) fn io_usage(&self, root: u32) -> Option<IoUsage> {
+        if let Some(usage) = self.cgroup_io_usage(root) {
+            return Some(usage);
+        }
+
+        let mut usage = IoUsage::default();
+        let mut readable = false;
+        for pid in self.tree(root) {
+            if let Ok(text) = std::fs::read_to_string(self.pid_path(pid, "io"))
+                && let Some((read, written)) = parse_proc_io(&text)
+            {
+                usage.read_bytes += read;
+                usage.write_bytes += written;
+                readable = true;
+            }
+        }
+        readable.then_some(usage)
+    }
+
+    /// CPU time, resident memory, descriptors and threads of the tree rooted at
+    /// `root`. `None` when `root` is not in the table.
+    pub(crate) fn resource_sample(&self, root: u32) -> Option<ResourceSample> {
+        if !self.processes.contains_key(&root) {
+            return None;
+        }
+        let mut sample = ResourceSample::default();
+        for pid in self.tree(root) {
+            sample.processes += 1;
+            sample.cpu_ticks += self.processes[&pid].cpu_ticks;
+            if let Ok(status) = std::fs::read_to_string(self.pid_path(pid, "status")) {
+                let (rss_bytes, threads) = parse_status(&status);
+                sample.rss_bytes += rss_bytes;
+                sample.threads += threads;
+            }
+            // Reading another user's descriptors needs root; leave the count unknown.
+            if let Ok(entries) = std::fs::read_dir(self.pid_path(pid, "fd")) {
+                *sample.open_fds.get_or_insert(0) += entries.count() as u64;
+            }
+        }
+        Some(sample)
+    }
+
+    fn cgroup_io_usage(&self, root: u32) -> Option<IoUsage> {
+        let cgroup = self.cgroup_of(root)?;
+        let supervisor = self.processes.get(&root)?.ppid;
+        // A cgroup shared with runsv also accounts for every other service.
+        if cgroup == "/" || self.cgroup_of(supervisor).as_deref() == Some(cgroup.as_str()) {
+            return None;
+        }
+
+        let stat_path = self
+            .cgroup_root
+            .join(cgroup.trim_start_matches('/'))
+            .join("io.stat");
+        let stat = std::fs::read_to_string(stat_path).ok()?;
+        let (read_bytes, write_bytes) = parse_io_stat(&stat);
+        Some(IoUsage {
+            read_bytes,
+            write_bytes,
+            source: IoSource::Cgroup,
+        })
+    }
+
+    /// Path of the process in the cgroup v2 hierarchy.
+    fn cgroup_of(&self, pid: u32) -> Option<String> {
+        let text = std::fs::read_to_string(self.pid_path(pid, "cgroup")).ok()?;
+        text.lines()
+            .find_map(|line| line.strip_prefix("0::"))
+            .map(str::to_string)
+    }
+
+    fn pid_path(&self, pid: u32, file: &str) -> PathBuf {
+        self.proc_dir.join(pid.to_string()).join(file)
+    }
+
+    /// Services that a `sv check`/`sv start` call inside the tree is waiting on,
+    /// which is how run scripts usually express startup dependencies.
+    pub(crate) fn awaited_dependencies(&self, root: u32) -> Vec<String> {
+        let mut dependencies = Vec::new();
+        for pid in self.tree(root) {
+            for dependency in sv_wait_targets(&self.cmdline(pid)) {
+                if !dependencies.contains(&dependency) {
+                    dependencies.push(dependency);
+                }
+            }
+        }
+        dependencies
+    }
+
+    /// Whether any runsvdir is running, i.e. something supervises services.
+    pub(crate) fn has_runsvdir(&self) -> bool {
+        self.processes.keys().any(|&pid| {
+            self.cmdline(pid)
+                .first()
+                .and_then(|program| Path::new(program).file_name())
+                .is_some_and(|name| name == "runsvdir")
+        })
+    }
+
+    /// Directory and log argument of every `runsvdir -P dir log` in the table.
+    /// runsvdir keeps the recent stderr of its runsv children in that last
+    /// argument, so it reads back through `/proc/<pid>/cmdline`.
+    pub(crate) fn runsvdir_logs(&self) -> Vec<(PathBuf, String)> {
+        let mut pids: Vec<u32> = self.processes.keys().copied().collect();
+        pids.sort_unstable();
+        pids.into_iter()
+            .filter_map(|pid| {
+                let args = self.cmdline(pid);
+                let (program, rest) = args.split_first()?;
+                if Path::new(program).file_name()?.to_str()? != "runsvdir" {
+                    return None;
+                }
+                let mut operands = rest.iter().filter(|arg| !arg.starts_with('-'));
+                let dir = PathBuf::from(operands.next()?);
+                Some((dir, operands.next()?.clone()))
+            })
+            .collect()
+    }
+}
+
+/// Parse an `sv [-v] [-w sec] check|start service...` command line and return the
+/// service names it blocks on.
+pub(crate) fn sv_wait_targets(args: &[String]) -> Vec<String> {
+    let Some((program, rest)) = args.split_first() else {
+        return Vec::new();
+    };
+    if Path::new(program)
+        .file_name()
+        .and_then(|name| name.to_str())
+        != Some("sv")
+    {
+        return Vec::new();
+    }
+
+    let mut rest = rest.iter();
+    let command = loop {
+        match rest.next().map(String::as_str) {
+            Some("-w") => {
+                rest.next();
+            }
+            Some(option) if option.starts_with('-') => {}
+            Some(command) => break command,
+            None => return Vec::new(),
+        }
+    };
+    if !matches!(command, "check" | "start") {
+        return Vec::new();
+    }
+
+    rest.filter_map(|target| {
+        Path::new(target.trim_end_matches('/'))
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(str::to_string)
+    })
+    .collect()
+}
+
+/// `read_bytes` and `write_bytes` from `/proc/<pid>/io`.
+fn parse_proc_io(text: &str) -> Option<(u64, u64)> {
+    let mut read = None;
+    let mut written = None;
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        match key {
+            "read_bytes" => read = value.trim().parse().ok(),
+            "write_bytes" => written = value.trim().parse().ok(),
+            _ => {}
+        }
+    }
+    Some((read?, written?))
+}
+
+/// Total `rbytes`/`wbytes` across every device listed in a cgroup `io.stat`.
+fn parse_io_stat(text: &str) -> (u64, u64) {
+    let mut totals = (0, 0);
+    for field in text.split_whitespace() {
+        let Some((key, value)) = field.split_once('=') else {
+            continue;
+        };
+        let value: u64 = value.parse().unwrap_or(0);
+        match key {
+            "rbytes" => totals.0 += value,
+            "wbytes" => totals.1 += value,
+            _ => {}
+        }
+    }
+    totals
+}
+
+/// `VmRSS` in bytes and `Threads` from `/proc/<pid>/status`. Kernel threads have
+/// no `VmRSS` line and count as zero.
+fn parse_status(text: &str) -> (u64, u64) {
+    let mut rss_bytes = 0;
+    let mut threads = 0;
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let number = || {
+            value
+                .split_whitespace()
+                .next()
+                .and_then(|n| n.parse::<u64>().ok())
+                .unwrap_or(0)
+        };
+        match key {
+            "VmRSS" => rss_bytes = number() * 1024,
+            "Threads" => threads = number(),
+            _ => {}
+        }
+    }
+    (rss_bytes, threads)
+}
+
+/// Extract state, parent pid and CPU time from a `/proc/<pid>/stat` line. The
+/// command name is parenthesised and may itself contain spaces or parentheses.
+fn parse_stat(stat: &str) -> Option<ProcessEntry> {
+    let rest = &stat[stat.rfind(')')? + 1..];
+    let mut fields = rest.split_whitespace();
+    let state = fields.next()?.chars().next()?;
+    let ppid = fields.next()?.parse().ok()?;
+    // utime and stime are the 14th and 15th fields; the two above are the 3rd and 4th.
+    let mut times = fields
+        .skip(9)
+        .map(|field| field.parse::<u64>().unwrap_or(0));
+    let cpu_ticks = times.next().unwrap_or(0) + times.next().unwrap_or(0);
+    Some(ProcessEntry {
+        ppid,
+        state,
+        cpu_ticks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        IoSource, ProcessEntry, ProcessTable, parse_io_stat, parse_proc_io, parse_stat,
+        parse_status, sv_wait_targets,
+    };
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn parses_stat_with_awkward_command_names() {
+        let entry = parse_stat("4242 (my (odd) daemon) Z 17 4242 4242 0 -1 4194564").unwrap();
+        assert_eq!(entry.state, 'Z');
+        assert_eq!(entry.ppid, 17);
+        assert_eq!(entry.cpu_ticks, 0);
+        assert!(parse_stat("garbage").is_none());
+
+        let busy = parse_stat("7 (worker) R 1 7 7 0 -1 0 10 0 0 0 250 50 0 0 20 0 4").unwrap();
+        assert_eq!(busy.cpu_ticks, 300);
+    }
+
+    #[test]
+    fn finds_defunct_descendants() {
+        let mut table = ProcessTable::default();
+        for (pid, ppid, state) in [
+            (10, 1, 'S'),
+            (11, 10, 'Z'),
+            (12, 10, 'S'),
+            (13, 12, 'Z'),
+            (20, 1, 'Z'),
+        ] {
+            table.insert(
+                pid,
+                ProcessEntry {
+                    ppid,
+                    state,
+                    cpu_ticks: 0,
+                },
+            );
+        }
+        assert_eq!(table.defunct_in_tree(10), [11, 13]);
+        assert_eq!(table.defunct_in_tree(20), [20]);
+        assert_eq!(table.defunct_in_tree(12), [13]);
+    }
+
+    #[test]
+    fn parses_io_counters() {
+        let proc_io = "rchar: 900\nwchar: 80\nsyscr: 3\nsyscw: 2\nread_bytes: 4096\n\
+                       write_bytes: 8192\ncancelled_write_bytes: 0\n";
+        assert_eq!(parse_proc_io(proc_io), Some((4096, 8192)));
+        assert_eq!(parse_proc_io("rchar: 1\n"), None);
+
+        let io_stat = "8:0 rbytes=1000 wbytes=200 rios=4 wios=2 dbytes=0 dios=0\n\
+                       259:0 rbytes=24 wbytes=56 rios=1 wios=1 dbytes=0 dios=0\n";
+        assert_eq!(parse_io_stat(io_stat), (1024, 256));
+
+        let status = "Name:\tdaemon\nVmRSS:\t    2048 kB\nThreads:\t3\n";
+        assert_eq!(parse_status(status), (2 * 1024 * 1024, 3));
+        assert_eq!(parse_status("Name:\tkworker/0:1\nThreads:\t1\n"), (0, 1));
+    }
+
+    #[test]
+    fn prefers_dedicated_cgroup_for_io() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("runkit-proc-{}-{nanos}", std::process::id()));
+        let proc_dir = root.join("proc");
+        let cgroup_root = root.join("cgroup");
+        let process = |pid: u32, ppid: u32, cgroup: &str, read: u64| {
+            let dir = proc_dir.join(pid.to_string());
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("stat"), format!("{pid} (daemon) S {ppid} 0 0")).unwrap();
+            fs::write(dir.join("cgroup"), format!("0::{cgroup}\n")).unwrap();
+            fs::write(
+                dir.join("io"),
+                format!("read_bytes: {read}\nwrite_bytes: 1\n"),
+            )
+            .unwrap();
+        };
+        process(10, 1, "/", 0);
+        process(11, 10, "/", 100);
+        process(12, 11, "/", 20);
+        process(20, 10, "/sshd", 5);
+        fs::create_dir_all(cgroup_root.join("sshd")).unwrap();
+        fs::write(
+            cgroup_root.join("sshd").join("io.stat"),
+            "8:0 rbytes=7000 wbytes=3000 rios=1 wios=1\n",
+        )
+        .unwrap();
+
+        let mut table = ProcessTable::from_proc_dir(&proc_dir);
+        table.cgroup_root = cgroup_root;
+
+        let shared = table.io_usage(11).unwrap();
+        assert_eq!((shared.read_bytes, shared.write_bytes), (120, 2));
+        assert_eq!(shared.source, IoSource::Processes);
+
+        let dedicated = table.io_usage(20).unwrap();
+        assert_eq!((dedicated.read_bytes, dedicated.write_bytes), (7000, 3000));
+        assert_eq!(dedicated.source, IoSource::Cgroup);
+
+        assert!(table.io_usage(99).is_none());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn builds_process_tree() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let proc_dir =
+            std::env::temp_dir().join(format!("runkit-tree-{}-{nanos}", std::process::id()));
+        for (pid, ppid, comm, cmdline) in [
+            (10, 1, "nginx", "nginx: master process\0"),
+            (12, 10, "nginx", "nginx: worker process\0"),
+            (11, 10, "nginx", "nginx: worker process\0"),
+            (13, 11, "sh", "/bin/sh\0-c\0echo hi\0"),
+            (20, 1, "other", ""),
+        ] {
+            let dir = proc_dir.join(pid.to_string());
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("stat"), format!("{pid} ({comm}) S {ppid} 0 0")).unwrap();
+            fs::write(dir.join("comm"), format!("{comm}\n")).unwrap();
+            fs::write(dir.join("cmdline"), cmdline).unwrap();
+        }
+
+        let table = ProcessTable::from_proc_dir(&proc_dir);
+        let tree = table.process_tree(10).unwrap();
+        assert_eq!(tree.comm, "nginx");
+        assert_eq!(tree.cmdline, ["nginx: master process"]);
+        let pids: Vec<u32> = tree.children.iter().map(|child| child.pid).collect();
+        assert_eq!(pids, [11, 12]);
+        assert_eq!(
+            tree.children[0].children[0].cmdline,
+            ["/bin/sh", "-c", "echo hi"]
+        );
+        assert!(table.process_tree(99).is_none());
+        let _ = fs::remove_dir_all(&proc_dir);
+    }
+
+    #[test]
+    fn extracts_sv_check_dependencies() {
+        let args = |line: &str| -> Vec<String> { line.split(' ').map(str::to_string).collect() };
+        assert_eq!(sv_wait_targets(&args("sv -w 30 check dbus")), ["dbus"]);
+        assert_eq!(
+            sv_wait_targets(&args("/usr/bin/sv start /var/service/udevd/ elogind")),
+            ["udevd", "elogind"]
+        );
+        assert!(sv_wait_targets(&args("sv status dbus")).is_empty());
+        assert!(sv_wait_targets(&args("svlogd -tt /var/log/dbus")).is_empty());
+    }
+}
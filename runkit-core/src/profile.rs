@@ -0,0 +1,228 @@
+//! Profiles: which services a machine enables and which carry a `down` file,
+//! saved to replay the setup on another machine or to roll back after
+//! experimenting.
+use crate::{Result, ServiceError, ServiceManager};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::ErrorKind;
+
+/// Enabled and `down` file state of every definition, keyed by service name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    /// Machine the profile was saved on.
+    #[serde(default)]
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub services: BTreeMap<String, ProfileEntry>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileEntry {
+    pub enabled: bool,
+    #[serde(default)]
+    pub down_file: bool,
+}
+
+/// What restoring a profile changes, each list sorted by service name.
+/// Services defined here but absent from the profile are left alone.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileChanges {
+    pub enable: Vec<String>,
+    pub disable: Vec<String>,
+    pub add_down_file: Vec<String>,
+    pub remove_down_file: Vec<String>,
+    /// Services in the profile that have no definition here, so are skipped.
+    pub missing: Vec<String>,
+}
+
+impl ProfileChanges {
+    /// Whether restoring would change nothing; missing services do not count.
+    pub fn is_empty(&self) -> bool {
+        self.enable.is_empty()
+            && self.disable.is_empty()
+            && self.add_down_file.is_empty()
+            && self.remove_down_file.is_empty()
+    }
+}
+
+/// What [`ServiceManager::restore_profile`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileRestore {
+    /// Changes that were made.
+    pub applied: ProfileChanges,
+    /// Changes that failed, with the reason. The rest are still applied.
+    pub failed: Vec<(String, String)>,
+    /// Whether the restore was stopped before every change was made.
+    pub stopped: bool,
+}
+
+impl ServiceManager {
+    /// The current profile. A definition shadows one of the same name in a
+    /// later service directory, as everywhere else.
+    pub fn profile(&self) -> Result<Profile> {
+        let mut services = BTreeMap::new();
+        for (index, (definitions_dir, enabled_dir)) in self.service_dirs().into_iter().enumerate() {
+            let read_dir = match fs::read_dir(definitions_dir) {
+                Ok(read_dir) => read_dir,
+                Err(err) if index > 0 && err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => return Err(ServiceError::from_io(definitions_dir, err)),
+            };
+            for entry in read_dir.flatten() {
+                let path = entry.path();
+                let Some(name) = path.file_name().and_then(OsStr::to_str) else {
+                    continue;
+                };
+                if name.starts_with('.') || !path.is_dir() || services.contains_key(name) {
+                    continue;
+                }
+                services.insert(
+                    name.to_string(),
+                    ProfileEntry {
+                        enabled: enabled_dir.join(name).exists(),
+                        down_file: path.join("down").exists(),
+                    },
+                );
+            }
+        }
+        let hostname = fs::read_to_string("/proc/sys/kernel/hostname")
+            .ok()
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty());
+        Ok(Profile { hostname, services })
+    }
+
+    /// What restoring `profile` would change, without changing anything.
+    pub fn profile_changes(&self, profile: &Profile) -> Result<ProfileChanges> {
+        let current = self.profile()?;
+        let mut changes = ProfileChanges::default();
+        for (name, wanted) in &profile.services {
+            let Some(have) = current.services.get(name) else {
+                changes.missing.push(name.clone());
+                continue;
+            };
+            match (have.enabled, wanted.enabled) {
+                (false, true) => changes.enable.push(name.clone()),
+                (true, false) => changes.disable.push(name.clone()),
+                _ => {}
+            }
+            match (have.down_file, wanted.down_file) {
+                (false, true) => changes.add_down_file.push(name.clone()),
+                (true, false) => changes.remove_down_file.push(name.clone()),
+                _ => {}
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Bring enabled services and `down` files in line with `profile`.
+    ///
+    /// `down` files are written before services are enabled, so a service the
+    /// profile enables but keeps down is not started by runsvdir in between.
+    /// A disabled service is stopped by runsvdir when it notices the link is
+    /// gone.
+    pub fn restore_profile(&self, profile: &Profile) -> Result<ProfileRestore> {
+        self.restore_profile_with_progress(profile, &mut |_, _| true)
+    }
+
+    /// [`ServiceManager::restore_profile`], calling `progress` with the number
+    /// of changes made and the number to make before each change. When it
+    /// returns `false` the restore stops there.
+    pub fn restore_profile_with_progress(
+        &self,
+        profile: &Profile,
+        progress: &mut dyn FnMut(usize, usize) -> bool,
+    ) -> Result<ProfileRestore> {
+        type Step = (
+            fn(&ServiceManager, &str) -> Result<()>,
+            fn(&mut ProfileChanges) -> &mut Vec<String>,
+        );
+        let changes = self.profile_changes(profile)?;
+        let steps: [(&Vec<String>, Step); 4] = [
+            (
+                &changes.add_down_file,
+                (ServiceManager::set_down_file, |c| &mut c.add_down_file),
+            ),
+            (
+                &changes.remove_down_file,
+                (ServiceManager::clear_down_file, |c| &mut c.remove_down_file),
+            ),
+            (
+                &changes.disable,
+                (ServiceManager::disable, |c| &mut c.disable),
+            ),
+            (&changes.enable, (ServiceManager::enable, |c| &mut c.enable)),
+        ];
+        let total = steps.iter().map(|(services, _)| services.len()).sum();
+        let mut restore = ProfileRestore::default();
+        restore.applied.missing = changes.missing.clone();
+        let mut done = 0;
+        for (services, (change, applied)) in steps {
+            for service in services {
+                if !progress(done, total) {
+                    restore.stopped = true;
+                    return Ok(restore);
+                }
+                match change(self, service) {
+                    Ok(()) => applied(&mut restore.applied).push(service.clone()),
+                    Err(err) => restore.failed.push((service.clone(), err.to_string())),
+                }
+                done += 1;
+            }
+        }
+        progress(done, total);
+        Ok(restore)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Profile, ProfileEntry};
+    use crate::testing::SandboxFixture;
+
+    #[test]
+    fn restores_enabled_services_and_down_files() {
+        let sandbox = SandboxFixture::new().unwrap();
+        for name in ["sshd", "cron", "nginx"] {
+            sandbox
+                .add_service(name, &format!("exec {name}\n"))
+                .unwrap();
+        }
+        sandbox.enable("sshd").unwrap();
+        sandbox.enable("nginx").unwrap();
+        let manager = sandbox.manager();
+        manager.set_down_file("nginx").unwrap();
+
+        let saved = manager.profile().unwrap();
+        assert_eq!(
+            saved.services["nginx"],
+            ProfileEntry {
+                enabled: true,
+                down_file: true,
+            }
+        );
+        assert!(manager.profile_changes(&saved).unwrap().is_empty());
+
+        let mut wanted = Profile::default();
+        let entry = |enabled, down_file| ProfileEntry { enabled, down_file };
+        wanted.services.insert("sshd".into(), entry(false, false));
+        wanted.services.insert("cron".into(), entry(true, true));
+        wanted.services.insert("nginx".into(), entry(true, false));
+        wanted.services.insert("gone".into(), entry(true, false));
+        let changes = manager.profile_changes(&wanted).unwrap();
+        assert_eq!(changes.enable, ["cron"]);
+        assert_eq!(changes.disable, ["sshd"]);
+        assert_eq!(changes.add_down_file, ["cron"]);
+        assert_eq!(changes.remove_down_file, ["nginx"]);
+        assert_eq!(changes.missing, ["gone"]);
+
+        let restore = manager.restore_profile(&wanted).unwrap();
+        assert_eq!(restore.applied, changes);
+        assert!(restore.failed.is_empty());
+        assert!(manager.profile_changes(&wanted).unwrap().is_empty());
+
+        manager.restore_profile(&saved).unwrap();
+        assert_eq!(manager.profile().unwrap().services, saved.services);
+    }
+}
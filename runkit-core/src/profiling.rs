@@ -0,0 +1,109 @@
+//! Timing spans on the refresh paths: listing services, reading their status
+//! and tailing logs. While profiling is off a span costs one atomic load;
+//! while it is on, spans are summed by name until [`take_profile`].
+//!
+//! A span's name starts with its kind, such as `spawn:sv status`,
+//! `io:proc` or `serialize:services`, so a report can say how much time went
+//! to starting processes, to reading files and to encoding responses.
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static TOTALS: Mutex<BTreeMap<&'static str, (u64, Duration)>> = Mutex::new(BTreeMap::new());
+
+/// Start or stop recording spans. Stopping keeps what was recorded.
+pub fn set_profiling(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Time from now until the returned guard is dropped, under `name`.
+pub fn span(name: &'static str) -> Span {
+    Span {
+        name,
+        start: ENABLED.load(Ordering::Relaxed).then(Instant::now),
+    }
+}
+
+#[must_use = "a span measures until it is dropped"]
+pub struct Span {
+    name: &'static str,
+    start: Option<Instant>,
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let Some(start) = self.start else {
+            return;
+        };
+        let elapsed = start.elapsed();
+        let mut totals = TOTALS
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let total = totals.entry(self.name).or_default();
+        total.0 += 1;
+        total.1 += elapsed;
+    }
+}
+
+/// The time spent under one span name.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpanTotal {
+    pub name: String,
+    /// The part of the name before `:`, e.g. `spawn` or `io`.
+    pub kind: String,
+    pub count: u64,
+    pub total_ms: f64,
+    pub mean_ms: f64,
+}
+
+/// What was recorded since the last call, the most expensive first, and
+/// start afresh.
+pub fn take_profile() -> Vec<SpanTotal> {
+    let totals = std::mem::take(&mut *TOTALS.lock().unwrap_or_else(|p| p.into_inner()));
+    let mut spans: Vec<SpanTotal> = totals
+        .into_iter()
+        .map(|(name, (count, total))| {
+            let total_ms = total.as_secs_f64() * 1000.0;
+            SpanTotal {
+                name: name.to_string(),
+                kind: name
+                    .split_once(':')
+                    .map_or(name, |(kind, _)| kind)
+                    .to_string(),
+                count,
+                total_ms,
+                mean_ms: total_ms / count as f64,
+            }
+        })
+        .collect();
+    spans.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms));
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{set_profiling, span, take_profile};
+
+    #[test]
+    fn sums_spans_by_name_while_enabled() {
+        drop(span("test:off"));
+        set_profiling(true);
+        for _ in 0..3 {
+            let _span = span("test:on");
+        }
+        set_profiling(false);
+        drop(span("test:after"));
+
+        let spans = take_profile();
+        let on = spans.iter().find(|span| span.name == "test:on").unwrap();
+        assert_eq!((on.kind.as_str(), on.count), ("test", 3));
+        assert!(
+            spans
+                .iter()
+                .all(|span| span.name != "test:off" && span.name != "test:after")
+        );
+    }
+}
@@ -0,0 +1,105 @@
+//! Services whose loss can lock the user out of the machine, which runkitd
+//! will not stop or disable without an explicit override.
+//!
+//! The defaults are the device manager, the system bus, the seat manager,
+//! polkit (without which nothing can be authorized again) and the getty on
+//! the active console. The list is adjusted in
+//! [`DEFAULT_PROTECTED_CONFIG`], one service per line: a name adds it, a
+//! name after `!` removes it, and `#` starts a comment.
+use crate::{Result, ServiceError};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+pub const DEFAULT_PROTECTED_CONFIG: &str = "/etc/runkit/protected-services";
+
+pub const DEFAULT_PROTECTED_SERVICES: [&str; 4] = ["udevd", "dbus", "elogind", "polkitd"];
+
+/// Names the kernel's active virtual console, e.g. `tty1`.
+const ACTIVE_CONSOLE: &str = "/sys/class/tty/tty0/active";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProtectedServices {
+    services: BTreeSet<String>,
+}
+
+impl ProtectedServices {
+    /// The built-in list plus the getty of the active console, if known.
+    pub fn defaults() -> Self {
+        let mut services: BTreeSet<String> = DEFAULT_PROTECTED_SERVICES
+            .iter()
+            .map(|service| service.to_string())
+            .collect();
+        services.extend(active_console_getty());
+        ProtectedServices { services }
+    }
+
+    /// The defaults adjusted by the file at `path`, or the defaults alone when
+    /// it does not exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut protected = Self::defaults();
+        match fs::read_to_string(path) {
+            Ok(contents) => protected.apply(&contents),
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(ServiceError::from_io(path, err)),
+        }
+        Ok(protected)
+    }
+
+    fn apply(&mut self, contents: &str) {
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            match line.strip_prefix('!') {
+                Some(service) => {
+                    self.services.remove(service.trim());
+                }
+                None if !line.is_empty() => {
+                    self.services.insert(line.to_string());
+                }
+                None => {}
+            }
+        }
+    }
+
+    pub fn contains(&self, service: &str) -> bool {
+        self.services.contains(service)
+    }
+
+    /// The protected services, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        self.services.iter().map(String::as_str).collect()
+    }
+}
+
+/// The getty service for the console the kernel shows, `agetty-tty1` on
+/// Void, when `/sys` says which one that is.
+pub fn active_console_getty() -> Option<String> {
+    let console = fs::read_to_string(ACTIVE_CONSOLE).ok()?;
+    let console = console.trim();
+    (!console.is_empty()).then(|| format!("agetty-{console}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProtectedServices;
+    use crate::testing::SandboxFixture;
+    use std::fs;
+
+    #[test]
+    fn config_adds_and_removes_protected_services() {
+        let sandbox = SandboxFixture::new().unwrap();
+        let path = sandbox.root().join("protected-services");
+        assert_eq!(
+            ProtectedServices::load(&path).unwrap(),
+            ProtectedServices::defaults()
+        );
+
+        fs::write(&path, "# remote access\nsshd\n!elogind  # seatd here\n\n").unwrap();
+        let protected = ProtectedServices::load(&path).unwrap();
+        assert!(protected.contains("sshd"));
+        assert!(protected.contains("udevd"));
+        assert!(!protected.contains("elogind"));
+        assert!(!protected.contains("cronie"));
+    }
+}
@@ -0,0 +1,263 @@
+//! Filtering and ordering of the service list, shared by `runkitd list`,
+//! `ListServicesFiltered` and the GUI's search entry and filter chips.
+use crate::{ServiceInfo, ServiceRuntimeState, is_failed};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// Which runtime states a [`ServiceQuery`] keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateFilter {
+    Running,
+    /// Down on purpose: stopped, disabled or in maintenance.
+    Down,
+    /// Exited with an error, or down although enabled and meant to be up,
+    /// as [`is_failed`] decides.
+    Failed,
+}
+
+impl StateFilter {
+    pub fn matches(self, service: &ServiceInfo) -> bool {
+        match self {
+            StateFilter::Running => {
+                matches!(service.runtime_state, ServiceRuntimeState::Running { .. })
+            }
+            StateFilter::Down => {
+                matches!(service.runtime_state, ServiceRuntimeState::Down { .. })
+                    && !is_failed(service)
+            }
+            StateFilter::Failed => is_failed(service),
+        }
+    }
+}
+
+impl FromStr for StateFilter {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "running" | "up" => Ok(StateFilter::Running),
+            "down" | "stopped" => Ok(StateFilter::Down),
+            "failed" => Ok(StateFilter::Failed),
+            _ => Err(format!(
+                "unknown state {value:?} (expected running, down or failed)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for StateFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            StateFilter::Running => "running",
+            StateFilter::Down => "down",
+            StateFilter::Failed => "failed",
+        })
+    }
+}
+
+/// Order of a [`ServiceQuery`]'s results. Ties are broken by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ServiceSort {
+    #[default]
+    Name,
+    /// Most recently started first; services that are not running last.
+    Uptime,
+    /// Failed first, then running, then down.
+    State,
+}
+
+impl ServiceSort {
+    pub const ALL: [ServiceSort; 3] = [ServiceSort::Name, ServiceSort::Uptime, ServiceSort::State];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ServiceSort::Name => "Name",
+            ServiceSort::Uptime => "Uptime",
+            ServiceSort::State => "State",
+        }
+    }
+
+    fn compare(self, a: &ServiceInfo, b: &ServiceInfo) -> Ordering {
+        let by_key = match self {
+            ServiceSort::Name => Ordering::Equal,
+            ServiceSort::Uptime => uptime_key(a).cmp(&uptime_key(b)),
+            ServiceSort::State => state_rank(a).cmp(&state_rank(b)),
+        };
+        by_key.then_with(|| a.name.cmp(&b.name))
+    }
+}
+
+impl FromStr for ServiceSort {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "name" => Ok(ServiceSort::Name),
+            "uptime" => Ok(ServiceSort::Uptime),
+            "state" | "status" => Ok(ServiceSort::State),
+            _ => Err(format!(
+                "unknown sort order {value:?} (expected name, uptime or state)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ServiceSort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ServiceSort::Name => "name",
+            ServiceSort::Uptime => "uptime",
+            ServiceSort::State => "state",
+        })
+    }
+}
+
+/// `(not running, uptime)`, so running services come first, newest first.
+fn uptime_key(service: &ServiceInfo) -> (bool, std::time::Duration) {
+    match service.runtime_state {
+        ServiceRuntimeState::Running { uptime, .. } => (false, uptime),
+        _ => (true, std::time::Duration::ZERO),
+    }
+}
+
+fn state_rank(service: &ServiceInfo) -> u8 {
+    if is_failed(service) {
+        0
+    } else if StateFilter::Running.matches(service) {
+        1
+    } else if StateFilter::Down.matches(service) {
+        2
+    } else {
+        3
+    }
+}
+
+/// Selection and order for [`ServiceManager::list_services_matching`]. Every
+/// criterion that is set must hold; the default keeps every service, sorted
+/// by name.
+///
+/// [`ServiceManager::list_services_matching`]: crate::ServiceManager::list_services_matching
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceQuery {
+    /// Keep services in any of these states; all of them when empty.
+    pub states: Vec<StateFilter>,
+    /// Only enabled (`Some(true)`) or disabled (`Some(false)`) services.
+    pub enabled: Option<bool>,
+    /// Case-insensitive substring of the name or the description.
+    pub text: Option<String>,
+    pub sort: ServiceSort,
+}
+
+impl ServiceQuery {
+    pub fn matches(&self, service: &ServiceInfo) -> bool {
+        (self.states.is_empty() || self.states.iter().any(|state| state.matches(service)))
+            && self
+                .enabled
+                .is_none_or(|enabled| service.enabled == enabled)
+            && self.matches_text(service)
+    }
+
+    fn matches_text(&self, service: &ServiceInfo) -> bool {
+        let Some(text) = self.text.as_deref().filter(|text| !text.is_empty()) else {
+            return true;
+        };
+        let text = text.to_lowercase();
+        service.name.to_lowercase().contains(&text)
+            || service
+                .description
+                .as_ref()
+                .is_some_and(|description| description.to_lowercase().contains(&text))
+    }
+
+    /// Drop the services that do not match and sort the rest.
+    pub fn apply(&self, mut services: Vec<ServiceInfo>) -> Vec<ServiceInfo> {
+        services.retain(|service| self.matches(service));
+        services.sort_by(|a, b| self.sort.compare(a, b));
+        services
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ServiceQuery, ServiceSort, StateFilter};
+    use crate::{DesiredState, RestartPolicy, ServiceInfo, ServiceRuntimeState};
+    use std::time::Duration;
+
+    fn service(name: &str, enabled: bool, runtime_state: ServiceRuntimeState) -> ServiceInfo {
+        ServiceInfo {
+            name: name.to_string(),
+            definition_path: format!("/etc/sv/{name}").into(),
+            enabled,
+            desired_state: DesiredState::AutoStart,
+            runtime_state,
+            restart_policy: RestartPolicy::default(),
+            has_down_file: false,
+            defunct_pids: Vec::new(),
+            waiting_for: Vec::new(),
+            io: None,
+            description: None,
+            runlevels: Vec::new(),
+            maintenance: None,
+            supervisor_diagnostic: None,
+            check_interval: None,
+            last_check: None,
+        }
+    }
+
+    #[test]
+    fn filters_compose_and_sort_orders_hold() {
+        let running = |uptime| ServiceRuntimeState::Running {
+            pid: 1,
+            uptime: Duration::from_secs(uptime),
+        };
+        let down = |normally_up| ServiceRuntimeState::Down {
+            since: Duration::ZERO,
+            normally_up,
+        };
+        let mut sshd = service("sshd", true, running(500));
+        sshd.description = Some("OpenSSH daemon".to_string());
+        let services = vec![
+            sshd,
+            service("acpid", true, running(20)),
+            service("cups", true, down(true)),
+            service("nfs", false, down(false)),
+            service("tor", true, down(false)),
+        ];
+        let names = |query: &ServiceQuery| -> Vec<String> {
+            query
+                .apply(services.clone())
+                .into_iter()
+                .map(|service| service.name)
+                .collect()
+        };
+
+        assert_eq!(
+            names(&ServiceQuery::default()),
+            ["acpid", "cups", "nfs", "sshd", "tor"]
+        );
+        let query = ServiceQuery {
+            states: vec![StateFilter::Down],
+            enabled: Some(true),
+            ..ServiceQuery::default()
+        };
+        assert_eq!(names(&query), ["tor"]);
+        let query = ServiceQuery {
+            states: vec!["failed".parse().unwrap(), StateFilter::Running],
+            sort: ServiceSort::State,
+            ..ServiceQuery::default()
+        };
+        assert_eq!(names(&query), ["cups", "acpid", "sshd"]);
+        let query = ServiceQuery {
+            sort: "uptime".parse().unwrap(),
+            ..ServiceQuery::default()
+        };
+        assert_eq!(names(&query), ["acpid", "sshd", "cups", "nfs", "tor"]);
+        let query = ServiceQuery {
+            text: Some("OPENssh".to_string()),
+            ..ServiceQuery::default()
+        };
+        assert_eq!(names(&query), ["sshd"]);
+        assert!("sleeping".parse::<StateFilter>().is_err());
+    }
+}
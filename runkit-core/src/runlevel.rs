@@ -0,0 +1,377 @@
+//! runsvdir runlevels (`/etc/runit/runsvdir/<name>`) and services linked into more
+//! than one of them.
+use crate::{Result, ServiceError, ServiceManager};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Name used for the enabled directory when no runlevel directory contains it.
+const ACTIVE_FALLBACK: &str = "current";
+
+/// One link that enables a service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnabledLink {
+    pub runlevel: String,
+    pub path: PathBuf,
+}
+
+/// A service definition that is linked more than once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateEnable {
+    pub service: String,
+    pub links: Vec<EnabledLink>,
+    /// Two links in the same runlevel: runsvdir starts a runsv for each, and they
+    /// contend for one `supervise` directory.
+    pub double_supervised: bool,
+}
+
+impl DuplicateEnable {
+    pub fn runlevels(&self) -> Vec<&str> {
+        let mut runlevels: Vec<&str> = self
+            .links
+            .iter()
+            .map(|link| link.runlevel.as_str())
+            .collect();
+        runlevels.dedup();
+        runlevels
+    }
+}
+
+impl ServiceManager {
+    pub fn with_runsvchdir_command(mut self, command: impl Into<PathBuf>) -> Self {
+        self.runsvchdir_command = command.into();
+        self
+    }
+
+    /// Runlevel directories by name, sorted. Falls back to the enabled directory
+    /// alone when no runsvdir root is configured or readable.
+    pub fn runlevels(&self) -> Vec<(String, PathBuf)> {
+        let active = fs::canonicalize(&self.enabled_dir).ok();
+        let mut runlevels = Vec::new();
+        if let Some(root) = &self.runsvdir_root
+            && let Ok(entries) = fs::read_dir(root)
+        {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                // `current` and `previous` are symlinks to real runlevels.
+                let is_dir = entry.file_type().is_ok_and(|kind| kind.is_dir());
+                if let Ok(name) = entry.file_name().into_string()
+                    && is_dir
+                    && !name.starts_with('.')
+                {
+                    runlevels.push((name, path));
+                }
+            }
+        }
+        runlevels.sort();
+
+        let covered = runlevels
+            .iter()
+            .any(|(_, path)| fs::canonicalize(path).ok() == active);
+        if !covered {
+            runlevels.push((ACTIVE_FALLBACK.to_string(), self.enabled_dir.clone()));
+        }
+        runlevels
+    }
+
+    /// Name of the runlevel the enabled directory points at.
+    pub fn current_runlevel(&self) -> String {
+        let active = fs::canonicalize(&self.enabled_dir).ok();
+        self.runlevels()
+            .into_iter()
+            .find(|(_, path)| fs::canonicalize(path).ok() == active)
+            .map(|(name, _)| name)
+            .unwrap_or_else(|| ACTIVE_FALLBACK.to_string())
+    }
+
+    /// Runlevels each service is linked into, in runlevel order.
+    pub fn enabled_runlevels(&self) -> Result<BTreeMap<String, Vec<String>>> {
+        let mut enabled: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (runlevel, dir) in self.runlevels() {
+            for (service, _) in self.enabled_links(&dir)? {
+                let runlevels = enabled.entry(service).or_default();
+                if !runlevels.contains(&runlevel) {
+                    runlevels.push(runlevel.clone());
+                }
+            }
+        }
+        Ok(enabled)
+    }
+
+    /// Services linked into several runlevels, or more than once into one.
+    pub fn duplicate_enables(&self) -> Result<Vec<DuplicateEnable>> {
+        let mut links: BTreeMap<String, Vec<EnabledLink>> = BTreeMap::new();
+        for (runlevel, dir) in self.runlevels() {
+            for (service, path) in self.enabled_links(&dir)? {
+                links.entry(service).or_default().push(EnabledLink {
+                    runlevel: runlevel.clone(),
+                    path,
+                });
+            }
+        }
+
+        Ok(links
+            .into_iter()
+            .filter(|(_, links)| links.len() > 1)
+            .map(|(service, links)| {
+                let double_supervised = links.iter().enumerate().any(|(index, link)| {
+                    links[index + 1..]
+                        .iter()
+                        .any(|other| other.runlevel == link.runlevel)
+                });
+                DuplicateEnable {
+                    service,
+                    links,
+                    double_supervised,
+                }
+            })
+            .collect())
+    }
+
+    /// Remove every link to `service` except one in `runlevel` (the current
+    /// runlevel when `None`). Returns the links that were removed.
+    pub fn keep_only_in_runlevel(
+        &self,
+        service: &str,
+        runlevel: Option<&str>,
+    ) -> Result<Vec<PathBuf>> {
+        self.validate_service_name(service)?;
+        let keep_in = runlevel
+            .map(str::to_string)
+            .unwrap_or_else(|| self.current_runlevel());
+        if !self.runlevels().iter().any(|(name, _)| *name == keep_in) {
+            return Err(ServiceError::UnknownRunlevel(keep_in));
+        }
+
+        let links: Vec<EnabledLink> = self
+            .duplicate_enables()?
+            .into_iter()
+            .find(|duplicate| duplicate.service == service)
+            .map(|duplicate| duplicate.links)
+            .unwrap_or_default();
+        let in_runlevel = |link: &&EnabledLink| link.runlevel == keep_in;
+        // Prefer the link named after the service over an alias.
+        let kept = links
+            .iter()
+            .filter(in_runlevel)
+            .find(|link| link.path.file_name().is_some_and(|name| name == service))
+            .or_else(|| links.iter().find(in_runlevel))
+            .ok_or_else(|| ServiceError::NotEnabled(format!("{service} in {keep_in}")))?
+            .clone();
+
+        let mut removed = Vec::new();
+        for link in links.into_iter().filter(|link| *link != kept) {
+            fs::remove_file(&link.path).map_err(|err| ServiceError::from_io(&link.path, err))?;
+            removed.push(link.path);
+        }
+        Ok(removed)
+    }
+
+    /// Make `runlevel` the current one with `runsvchdir`. runsvdir then stops
+    /// the services not linked there and starts those that are.
+    pub fn switch_runlevel(&self, runlevel: &str) -> Result<()> {
+        let dir = self.runlevel_dir(runlevel)?;
+        // The fallback for an enabled directory outside any runlevel cannot be
+        // switched to.
+        if self
+            .runsvdir_root
+            .as_ref()
+            .is_none_or(|root| dir.parent() != Some(root.as_path()))
+        {
+            return Err(ServiceError::UnknownRunlevel(runlevel.to_string()));
+        }
+
+        let output = Command::new(&self.runsvchdir_command)
+            .arg(runlevel)
+            .output()
+            .map_err(|err| ServiceError::from_io(&self.runsvchdir_command, err))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(ServiceError::SvCommand {
+                command: "runsvchdir".to_string(),
+                service: runlevel.to_string(),
+                message: if stderr.is_empty() {
+                    format!("exit status {}", output.status)
+                } else {
+                    stderr
+                },
+            });
+        }
+        Ok(())
+    }
+
+    /// Link `service` into `runlevel`, or remove every link to it there, so a
+    /// runlevel can be prepared before switching to it.
+    pub fn set_enabled_in_runlevel(
+        &self,
+        service: &str,
+        runlevel: &str,
+        enabled: bool,
+    ) -> Result<()> {
+        let definition = self.existing_definition(service)?;
+        let dir = self.runlevel_dir(runlevel)?;
+        let links: Vec<PathBuf> = self
+            .enabled_links(&dir)?
+            .into_iter()
+            .filter(|(name, _)| name == service)
+            .map(|(_, link)| link)
+            .collect();
+
+        if enabled {
+            if !links.is_empty() {
+                return Err(ServiceError::AlreadyEnabled(format!(
+                    "{service} in {runlevel}"
+                )));
+            }
+            let link = dir.join(service);
+            return std::os::unix::fs::symlink(&definition, &link)
+                .map_err(|err| ServiceError::from_io(&link, err));
+        }
+
+        if links.is_empty() {
+            return Err(ServiceError::NotEnabled(format!("{service} in {runlevel}")));
+        }
+        for link in links {
+            fs::remove_file(&link).map_err(|err| ServiceError::from_io(&link, err))?;
+        }
+        Ok(())
+    }
+
+    fn runlevel_dir(&self, runlevel: &str) -> Result<PathBuf> {
+        self.runlevels()
+            .into_iter()
+            .find(|(name, _)| name == runlevel)
+            .map(|(_, dir)| dir)
+            .ok_or_else(|| ServiceError::UnknownRunlevel(runlevel.to_string()))
+    }
+
+    /// `(service, link)` for each entry of a runlevel directory. Links into the
+    /// definitions directory are attributed to the definition they resolve to,
+    /// so aliases of one service are grouped together.
+    pub(crate) fn enabled_links(&self, dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+        let definitions_root = fs::canonicalize(&self.definitions_dir).ok();
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(ServiceError::from_io(dir, err)),
+        };
+
+        let mut links = Vec::new();
+        for entry in entries.flatten() {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if name.starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            let service = fs::canonicalize(&path)
+                .ok()
+                .filter(|resolved| {
+                    definitions_root
+                        .as_ref()
+                        .is_some_and(|root| resolved.parent() == Some(root.as_path()))
+                })
+                .and_then(|resolved| resolved.file_name()?.to_str().map(str::to_string))
+                .unwrap_or(name);
+            links.push((service, path));
+        }
+        links.sort();
+        Ok(links)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ServiceManager;
+    use std::fs;
+    use std::os::unix::fs::{PermissionsExt, symlink};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn finds_and_resolves_duplicate_enables() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root =
+            std::env::temp_dir().join(format!("runkit-runlevel-{}-{nanos}", std::process::id()));
+        let definitions = root.join("sv");
+        let runsvdir = root.join("runsvdir");
+        let default = runsvdir.join("default");
+        let single = runsvdir.join("single");
+        for dir in [&definitions, &default, &single] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        for service in ["sshd", "dhcpcd", "agetty-tty1"] {
+            fs::create_dir(definitions.join(service)).unwrap();
+        }
+        symlink(&default, runsvdir.join("current")).unwrap();
+        symlink(definitions.join("sshd"), default.join("sshd")).unwrap();
+        symlink(definitions.join("sshd"), single.join("sshd")).unwrap();
+        symlink(definitions.join("dhcpcd"), default.join("dhcpcd")).unwrap();
+        symlink(definitions.join("dhcpcd"), default.join("network")).unwrap();
+        symlink(definitions.join("agetty-tty1"), single.join("agetty-tty1")).unwrap();
+
+        let manager = ServiceManager::new(&definitions, runsvdir.join("current"))
+            .with_runsvdir_root(&runsvdir);
+        assert_eq!(manager.current_runlevel(), "default");
+        let enabled = manager.enabled_runlevels().unwrap();
+        assert_eq!(enabled["sshd"], ["default", "single"]);
+        assert_eq!(enabled["dhcpcd"], ["default"]);
+
+        let duplicates = manager.duplicate_enables().unwrap();
+        let summary: Vec<(&str, Vec<&str>, bool)> = duplicates
+            .iter()
+            .map(|dup| (dup.service.as_str(), dup.runlevels(), dup.double_supervised))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("dhcpcd", vec!["default"], true),
+                ("sshd", vec!["default", "single"], false),
+            ]
+        );
+
+        let removed = manager.keep_only_in_runlevel("sshd", None).unwrap();
+        assert_eq!(removed, [single.join("sshd")]);
+        let removed = manager.keep_only_in_runlevel("dhcpcd", None).unwrap();
+        assert_eq!(removed, [default.join("network")]);
+        assert!(manager.duplicate_enables().unwrap().is_empty());
+        assert!(
+            manager
+                .keep_only_in_runlevel("sshd", Some("bogus"))
+                .is_err()
+        );
+
+        manager
+            .set_enabled_in_runlevel("agetty-tty1", "default", true)
+            .unwrap();
+        assert!(default.join("agetty-tty1").exists());
+        assert!(
+            manager
+                .set_enabled_in_runlevel("agetty-tty1", "default", true)
+                .is_err()
+        );
+        manager
+            .set_enabled_in_runlevel("agetty-tty1", "single", false)
+            .unwrap();
+        assert!(!single.join("agetty-tty1").exists());
+
+        let switched = root.join("switched");
+        let runsvchdir = root.join("runsvchdir");
+        fs::write(
+            &runsvchdir,
+            format!("#!/bin/sh\necho \"$1\" > {}\n", switched.display()),
+        )
+        .unwrap();
+        fs::set_permissions(&runsvchdir, fs::Permissions::from_mode(0o755)).unwrap();
+        let manager = manager.with_runsvchdir_command(&runsvchdir);
+        manager.switch_runlevel("single").unwrap();
+        assert_eq!(fs::read_to_string(&switched).unwrap(), "single\n");
+        assert!(manager.switch_runlevel("bogus").is_err());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,285 @@
+//! Static inspection of a service's `run` script.
+use crate::{Result, ServiceError, ServiceManager};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What a `run` script does, as far as can be told without running it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunScriptInfo {
+    /// Program the script finally `exec`s, after `chpst`, `envdir` and similar
+    /// wrappers. May be a shell variable such as `$DAEMON`.
+    pub binary: Option<String>,
+    pub args: Vec<String>,
+    /// User the service drops to via `chpst -u`, `setuidgid` or `envuidgid`;
+    /// `None` means it keeps runsv's user, normally root.
+    pub user: Option<String>,
+    pub group: Option<String>,
+    /// File the script sources, typically the definition's `conf`.
+    pub config_file: Option<PathBuf>,
+    /// Directory loaded with `chpst -e` or `envdir`.
+    pub env_dir: Option<PathBuf>,
+}
+
+impl RunScriptInfo {
+    /// Parse the script text; relative paths are resolved against `definition_dir`.
+    pub fn parse(script: &str, definition_dir: &Path) -> Self {
+        let mut info = RunScriptInfo::default();
+        for line in logical_lines(script) {
+            let words = split_words(&line);
+            for command in words.split(|word| matches!(word.as_str(), "&&" | "||" | ";")) {
+                info.inspect_command(command, definition_dir);
+            }
+        }
+        info
+    }
+
+    fn inspect_command(&mut self, words: &[String], definition_dir: &Path) {
+        let words: Vec<&str> = words
+            .iter()
+            .map(String::as_str)
+            .filter(|word| !is_redirection(word))
+            .collect();
+        match words.as_slice() {
+            ["." | "source", file, ..] => {
+                self.config_file = Some(resolve(definition_dir, file));
+            }
+            ["exec", rest @ ..] if !rest.is_empty() => self.inspect_exec(rest, definition_dir),
+            _ => {}
+        }
+    }
+
+    /// Peel known wrappers off an `exec` command line until the real program.
+    fn inspect_exec<'a>(&mut self, mut words: &'a [&'a str], definition_dir: &Path) {
+        while let Some((program, rest)) = words.split_first() {
+            let name = Path::new(program)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or(program);
+            words = match name {
+                "chpst" => self.inspect_chpst(rest, definition_dir),
+                "setuidgid" | "envuidgid" => match rest.split_first() {
+                    Some((account, rest)) => {
+                        self.set_account(account);
+                        rest
+                    }
+                    None => rest,
+                },
+                "envdir" => match rest.split_first() {
+                    Some((dir, rest)) => {
+                        self.env_dir = Some(resolve(definition_dir, dir));
+                        rest
+                    }
+                    None => rest,
+                },
+                "softlimit" => skip_options(rest, "madofcrtls"),
+                "nice" => skip_options(rest, "n"),
+                "pgrphack" => rest,
+                "env" => {
+                    let skip = rest
+                        .iter()
+                        .take_while(|word| word.starts_with('-') || word.contains('='))
+                        .count();
+                    &rest[skip..]
+                }
+                _ => {
+                    self.binary = Some(program.to_string());
+                    self.args = rest.iter().map(|arg| arg.to_string()).collect();
+                    return;
+                }
+            };
+        }
+    }
+
+    fn inspect_chpst<'a>(
+        &mut self,
+        mut words: &'a [&'a str],
+        definition_dir: &Path,
+    ) -> &'a [&'a str] {
+        // Options that take a value, which may be attached (`-uuser`) or separate.
+        const WITH_VALUE: &str = "uUbeL/nlmdofpcrt";
+        while let Some((word, rest)) = words.split_first() {
+            let Some(option) = word.strip_prefix('-') else {
+                break;
+            };
+            let mut chars = option.chars();
+            let Some(flag) = chars.next() else {
+                break;
+            };
+            words = rest;
+            if !WITH_VALUE.contains(flag) {
+                continue;
+            }
+            let value = match chars.as_str() {
+                "" => match words.split_first() {
+                    Some((value, rest)) => {
+                        words = rest;
+                        *value
+                    }
+                    None => break,
+                },
+                attached => attached,
+            };
+            match flag {
+                'u' | 'U' => self.set_account(value),
+                'e' => self.env_dir = Some(resolve(definition_dir, value)),
+                _ => {}
+            }
+        }
+        words
+    }
+
+    fn set_account(&mut self, account: &str) {
+        let (user, group) = match account.split_once(':') {
+            Some((user, group)) => (user, Some(group)),
+            None => (account, None),
+        };
+        self.user = Some(user.to_string());
+        self.group = group.map(str::to_string);
+    }
+}
+
+impl ServiceManager {
+    /// Inspect the service's `run` script without executing it.
+    pub fn run_script_info(&self, service: &str) -> Result<RunScriptInfo> {
+        let definition = self.existing_definition(service)?;
+        let run = definition.join("run");
+        let script = fs::read_to_string(&run).map_err(|err| ServiceError::from_io(&run, err))?;
+        Ok(RunScriptInfo::parse(&script, &definition))
+    }
+}
+
+/// Script lines with comments dropped and backslash continuations joined.
+fn logical_lines(script: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut pending = String::new();
+    for line in script.lines() {
+        let line = line.trim();
+        if pending.is_empty() && line.starts_with('#') {
+            continue;
+        }
+        match line.strip_suffix('\\') {
+            Some(head) => {
+                pending.push_str(head);
+                pending.push(' ');
+            }
+            None => {
+                pending.push_str(line);
+                lines.push(std::mem::take(&mut pending));
+            }
+        }
+    }
+    if !pending.is_empty() {
+        lines.push(pending);
+    }
+    lines
+}
+
+/// Split a line into words, honouring single and double quotes but expanding
+/// nothing. `&&`, `||` and `;` come out as words of their own.
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, '#') if !in_word => break,
+            (None, ';') => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+                words.push(";".to_string());
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+                if (c == '&' || c == '|') && chars.peek() == Some(&c) && word.len() == 1 {
+                    chars.next();
+                    word.push(c);
+                }
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+/// A path from the script; runsv starts it inside the definition directory.
+fn resolve(definition_dir: &Path, path: &str) -> PathBuf {
+    definition_dir.join(path.trim_start_matches("./"))
+}
+
+fn is_redirection(word: &str) -> bool {
+    let word = word.trim_start_matches(|c: char| c.is_ascii_digit());
+    word.starts_with('>') || word.starts_with('<')
+}
+
+/// Drop leading options, where those in `with_value` consume the next word.
+fn skip_options<'a>(mut words: &'a [&'a str], with_value: &str) -> &'a [&'a str] {
+    while let Some((word, rest)) = words.split_first() {
+        let Some(option) = word.strip_prefix('-') else {
+            break;
+        };
+        words = rest;
+        if option.len() == 1 && with_value.contains(option) && !words.is_empty() {
+            words = &words[1..];
+        }
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RunScriptInfo;
+    use std::path::Path;
+
+    #[test]
+    fn finds_binary_user_conf_and_env_dir() {
+        let dir = Path::new("/etc/sv/sshd");
+        let script = "#!/bin/sh\n\
+                      # start sshd\n\
+                      [ -r conf ] && . ./conf\n\
+                      ssh-keygen -A >/dev/null 2>&1\n\
+                      exec 2>&1\n\
+                      exec chpst -u _sshd:_sshd -e ./env \\\n    \
+                      /usr/bin/sshd -D $OPTS\n";
+        let info = RunScriptInfo::parse(script, dir);
+        assert_eq!(info.binary.as_deref(), Some("/usr/bin/sshd"));
+        assert_eq!(info.args, ["-D", "$OPTS"]);
+        assert_eq!(info.user.as_deref(), Some("_sshd"));
+        assert_eq!(info.group.as_deref(), Some("_sshd"));
+        assert_eq!(info.config_file, Some(dir.join("conf")));
+        assert_eq!(info.env_dir, Some(dir.join("env")));
+
+        let wrapped = RunScriptInfo::parse(
+            "exec envdir /etc/app/env setuidgid app softlimit -m 1000000 nice -n 5 app --serve\n",
+            dir,
+        );
+        assert_eq!(wrapped.binary.as_deref(), Some("app"));
+        assert_eq!(wrapped.user.as_deref(), Some("app"));
+        assert_eq!(
+            wrapped.env_dir,
+            Some(Path::new("/etc/app/env").to_path_buf())
+        );
+
+        let root = RunScriptInfo::parse("exec /usr/bin/dbus-daemon --system --nofork", dir);
+        assert_eq!(root.user, None);
+        assert_eq!(root.args, ["--system", "--nofork"]);
+    }
+}
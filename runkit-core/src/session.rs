@@ -0,0 +1,235 @@
+//! Services whose restart ends the graphical session, and restarts put off
+//! until the user has logged out.
+//!
+//! Each deferred restart is an empty marker file named after the service in a
+//! directory under `/run`, so nothing is left over after a reboot. Markers
+//! are acted on by [`ServiceManager::restart_after_logout`] once elogind
+//! reports no graphical user session.
+use crate::{Result, ServiceError, ServiceManager};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_LOGOUT_RESTART_DIR: &str = "/run/runkit/logout";
+
+/// Where elogind keeps one file per session.
+pub const DEFAULT_SESSIONS_DIR: &str = "/run/systemd/sessions";
+
+/// Display managers packaged for Void. Restarting one ends every graphical
+/// session it started.
+const DISPLAY_MANAGERS: [&str; 11] = [
+    "gdm", "lightdm", "sddm", "lxdm", "slim", "xdm", "greetd", "ly", "emptty", "nodm", "entrance",
+];
+
+/// Services graphical sessions cannot outlive: the system bus and the seat
+/// managers that hand out input and display devices.
+const SEAT_SERVICES: [&str; 3] = ["dbus", "elogind", "seatd"];
+
+/// Helpers that belong to a desktop session rather than the machine, as run
+/// by a per-user runsvdir: the session bus, audio, portals, keyrings and
+/// notification daemons.
+const SESSION_HELPERS: [&str; 16] = [
+    "dbus",
+    "dbus-session",
+    "pipewire",
+    "pipewire-pulse",
+    "wireplumber",
+    "pulseaudio",
+    "xdg-desktop-portal",
+    "xdg-desktop-portal-gtk",
+    "xdg-desktop-portal-wlr",
+    "xdg-desktop-portal-hyprland",
+    "gnome-keyring-daemon",
+    "ssh-agent",
+    "gpg-agent",
+    "mako",
+    "dunst",
+    "swayidle",
+];
+
+/// Whether `service` is one of the per-session helpers commonly run under a
+/// user's runsvdir, such as pipewire or the session bus.
+pub fn is_session_service(service: &str) -> bool {
+    SESSION_HELPERS.contains(&service)
+}
+
+/// Why restarting `service` would end the graphical session, or `None` when it
+/// would not.
+pub fn session_critical_reason(service: &str) -> Option<&'static str> {
+    if DISPLAY_MANAGERS.contains(&service) {
+        Some("it is a display manager; restarting it closes every graphical session it started")
+    } else if SEAT_SERVICES.contains(&service) {
+        Some("graphical sessions depend on it and are closed when it restarts")
+    } else {
+        None
+    }
+}
+
+/// Number of graphical sessions belonging to users, leaving out login
+/// greeters. `None` when `sessions_dir` does not exist, i.e. elogind is not
+/// running and a logout cannot be noticed.
+pub fn graphical_user_sessions(sessions_dir: &Path) -> Result<Option<usize>> {
+    let read_dir = match fs::read_dir(sessions_dir) {
+        Ok(read_dir) => read_dir,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(ServiceError::from_io(sessions_dir, err)),
+    };
+    let count = read_dir
+        .flatten()
+        .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+        .filter(|session| {
+            let field = |key: &str| {
+                session
+                    .lines()
+                    .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+            };
+            matches!(field("TYPE"), Some("x11" | "wayland" | "mir"))
+                && field("CLASS").is_none_or(|class| class == "user")
+                && field("STATE") != Some("closing")
+        })
+        .count();
+    Ok(Some(count))
+}
+
+/// What [`ServiceManager::restart_after_logout`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogoutRestartReport {
+    pub restarted: Vec<String>,
+    /// Services whose restart failed, with the reason. Their markers are
+    /// removed all the same, so a broken service is not retried forever.
+    pub failed: Vec<(String, String)>,
+}
+
+impl ServiceManager {
+    pub fn with_logout_restart_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.logout_restart_dir = Some(dir.into());
+        self
+    }
+
+    /// Restart `service` once no graphical user session is left, or with
+    /// `false` cancel a restart waiting for that. Cancelling when none is
+    /// waiting is not an error.
+    pub fn schedule_restart_at_logout(&self, service: &str, scheduled: bool) -> Result<()> {
+        let dir = self.require_logout_restart_dir()?;
+        let marker_path = dir.join(service);
+        if !scheduled {
+            self.validate_service_name(service)?;
+            return match fs::remove_file(&marker_path) {
+                Ok(()) => Ok(()),
+                Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+                Err(err) => Err(ServiceError::from_io(&marker_path, err)),
+            };
+        }
+        self.existing_definition(service)?;
+        fs::create_dir_all(dir).map_err(|err| ServiceError::from_io(dir, err))?;
+        fs::write(&marker_path, "").map_err(|err| ServiceError::from_io(&marker_path, err))
+    }
+
+    /// Services waiting for a logout to restart, sorted by name.
+    pub fn logout_restarts(&self) -> Result<Vec<String>> {
+        let Some(dir) = self.logout_restart_dir.as_deref() else {
+            return Ok(Vec::new());
+        };
+        let read_dir = match fs::read_dir(dir) {
+            Ok(read_dir) => read_dir,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(ServiceError::from_io(dir, err)),
+        };
+        let mut services: Vec<String> = read_dir
+            .flatten()
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| !name.starts_with('.'))
+            .collect();
+        services.sort();
+        Ok(services)
+    }
+
+    /// Restart the services waiting for a logout, if any are and no graphical
+    /// user session is listed in `sessions_dir`. `None` while there is nothing
+    /// to do yet.
+    pub fn restart_after_logout(&self, sessions_dir: &Path) -> Result<Option<LogoutRestartReport>> {
+        let services = self.logout_restarts()?;
+        if services.is_empty() || graphical_user_sessions(sessions_dir)? != Some(0) {
+            return Ok(None);
+        }
+        let dir = self.require_logout_restart_dir()?;
+        let mut report = LogoutRestartReport::default();
+        for service in services {
+            let marker_path = dir.join(&service);
+            fs::remove_file(&marker_path)
+                .map_err(|err| ServiceError::from_io(&marker_path, err))?;
+            match self.control(&service, "restart") {
+                Ok(_) => report.restarted.push(service),
+                Err(err) => report.failed.push((service, err.to_string())),
+            }
+        }
+        Ok(Some(report))
+    }
+
+    fn require_logout_restart_dir(&self) -> Result<&Path> {
+        self.logout_restart_dir.as_deref().ok_or_else(|| {
+            ServiceError::Other("no directory for restarts at logout is configured".into())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{graphical_user_sessions, is_session_service, session_critical_reason};
+    use crate::testing::SandboxFixture;
+    use std::fs;
+
+    #[test]
+    fn restarts_session_services_once_the_user_logs_out() {
+        let sandbox = SandboxFixture::new().unwrap();
+        sandbox.add_service("lightdm", "exec lightdm\n").unwrap();
+        sandbox.enable("lightdm").unwrap();
+        assert!(session_critical_reason("lightdm").is_some());
+        assert!(session_critical_reason("sshd").is_none());
+        assert!(is_session_service("wireplumber"));
+        assert!(!is_session_service("lightdm"));
+
+        let sessions = sandbox.root().join("sessions");
+        assert_eq!(graphical_user_sessions(&sessions).unwrap(), None);
+        fs::create_dir(&sessions).unwrap();
+        fs::write(
+            sessions.join("c1"),
+            "TYPE=x11\nCLASS=greeter\nSTATE=online\n",
+        )
+        .unwrap();
+        fs::write(
+            sessions.join("2"),
+            "TYPE=wayland\nCLASS=user\nSTATE=active\n",
+        )
+        .unwrap();
+        fs::write(sessions.join("3"), "TYPE=tty\nCLASS=user\nSTATE=active\n").unwrap();
+        assert_eq!(graphical_user_sessions(&sessions).unwrap(), Some(1));
+
+        let manager = sandbox
+            .manager()
+            .clone()
+            .with_logout_restart_dir(sandbox.root().join("logout"));
+        manager.schedule_restart_at_logout("lightdm", true).unwrap();
+        assert!(manager.schedule_restart_at_logout("missing", true).is_err());
+        assert_eq!(manager.logout_restarts().unwrap(), ["lightdm"]);
+        assert_eq!(manager.restart_after_logout(&sessions).unwrap(), None);
+
+        fs::remove_file(sessions.join("2")).unwrap();
+        let report = manager.restart_after_logout(&sessions).unwrap().unwrap();
+        assert_eq!(report.restarted, ["lightdm"]);
+        assert!(report.failed.is_empty());
+        assert!(
+            sandbox
+                .sv_calls()
+                .unwrap()
+                .contains(&"restart lightdm".to_string())
+        );
+        assert!(manager.logout_restarts().unwrap().is_empty());
+
+        manager.schedule_restart_at_logout("lightdm", true).unwrap();
+        manager
+            .schedule_restart_at_logout("lightdm", false)
+            .unwrap();
+        assert_eq!(manager.restart_after_logout(&sessions).unwrap(), None);
+    }
+}
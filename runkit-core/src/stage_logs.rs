@@ -0,0 +1,110 @@
+//! Logs of runit's own stages, read through the same calls as service logs.
+//!
+//! When stage 1 and 2 pipe their output into svlogd, each logger writes to a
+//! directory under [`DEFAULT_STAGE_LOG_DIR`]. Such a directory is addressed as
+//! `runit/<directory>` wherever a service name is taken for reading logs; a
+//! `current` file directly in the stage log directory is `runit/main`. Stage
+//! logs are read-only: nothing else accepts these names.
+use crate::{Result, ServiceError, ServiceManager};
+use std::path::PathBuf;
+
+/// Where runit's stage loggers write.
+pub const DEFAULT_STAGE_LOG_DIR: &str = "/var/log/runit";
+
+/// Prefix that marks a log name as a stage log rather than a service.
+pub const STAGE_LOG_PREFIX: &str = "runit/";
+
+/// The stage log's name for a `current` file in the stage log directory itself.
+const ROOT_STAGE_LOG: &str = "main";
+
+impl ServiceManager {
+    /// Read stage logs from `dir` instead of [`DEFAULT_STAGE_LOG_DIR`].
+    pub fn with_stage_log_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.stage_log_dir = Some(dir.into());
+        self
+    }
+
+    /// Names of the stage logs present, sorted; empty when runit's stages are
+    /// not logged.
+    pub fn stage_logs(&self) -> Result<Vec<String>> {
+        let Some(root) = &self.stage_log_dir else {
+            return Ok(Vec::new());
+        };
+        let entries = match std::fs::read_dir(root) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(ServiceError::from_io(root, err)),
+        };
+        let mut names: Vec<String> = entries
+            .flatten()
+            .filter(|entry| entry.path().join("current").is_file())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| valid_stage_component(name))
+            .map(|name| format!("{STAGE_LOG_PREFIX}{name}"))
+            .collect();
+        if root.join("current").is_file() {
+            names.push(format!("{STAGE_LOG_PREFIX}{ROOT_STAGE_LOG}"));
+        }
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    /// Check a name given for reading logs: a stage log or a valid service name.
+    pub(crate) fn validate_log_name(&self, name: &str) -> Result<()> {
+        match name.strip_prefix(STAGE_LOG_PREFIX) {
+            Some(stage) if valid_stage_component(stage) => Ok(()),
+            Some(_) => Err(ServiceError::InvalidServiceName(name.to_string())),
+            None => self.validate_service_name(name),
+        }
+    }
+
+    /// The svlogd directory of a stage log, given the part of its name after
+    /// [`STAGE_LOG_PREFIX`].
+    pub(crate) fn stage_log_dir(&self, stage: &str) -> Option<PathBuf> {
+        let root = self.stage_log_dir.as_ref()?;
+        let dir = if stage == ROOT_STAGE_LOG && root.join("current").is_file() {
+            root.clone()
+        } else {
+            root.join(stage)
+        };
+        dir.join("current").is_file().then_some(dir)
+    }
+}
+
+fn valid_stage_component(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\0'])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::SandboxFixture;
+    use std::fs;
+
+    #[test]
+    fn reads_stage_logs_through_the_log_calls() {
+        let sandbox = SandboxFixture::new().unwrap();
+        let root = sandbox.root().join("stage-logs");
+        fs::create_dir_all(root.join("2")).unwrap();
+        fs::create_dir_all(root.join("empty")).unwrap();
+        fs::write(root.join("current"), "stage 1 done\n").unwrap();
+        fs::write(
+            root.join("2/current"),
+            "@4000000065536fd01dcd6500 runsvdir started\n",
+        )
+        .unwrap();
+        let manager = sandbox.manager().clone().with_stage_log_dir(&root);
+
+        assert_eq!(manager.stage_logs().unwrap(), ["runit/2", "runit/main"]);
+        let entries = manager.tail_logs("runit/2", 10).unwrap();
+        assert_eq!(entries[0].message, "runsvdir started");
+        assert_eq!(
+            manager.tail_logs("runit/main", 10).unwrap()[0].message,
+            "stage 1 done"
+        );
+        assert!(manager.tail_logs("runit/empty", 10).unwrap().is_empty());
+        assert!(manager.tail_logs("runit/../etc", 10).is_err());
+        assert!(manager.follow_logs("runit/2").is_ok());
+        assert!(sandbox.manager().stage_logs().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+//! runsv's own warnings about a service, such as a run script it could not
+//! execute, read back from the log runsvdir keeps in its process title.
+use crate::proc::ProcessTable;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// `runsv NAME: warning: ...` or `runsv NAME: fatal: ...`, possibly preceded by
+/// the dots runsvdir fills an unused log with.
+static RUNSV_MESSAGE_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"runsv (?P<name>[^:\s]+): (?P<level>warning|fatal): (?P<message>.+)").unwrap()
+});
+
+/// The last warning runsv logged for a service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SupervisorDiagnostic {
+    /// The message without runsv's prefix, e.g.
+    /// `unable to start ./run: file does not exist`.
+    pub message: String,
+    /// runsv gave up on the service and exited.
+    pub fatal: bool,
+}
+
+/// Most recent diagnostic per service from the runsvdirs supervising the
+/// manager's enabled directories.
+#[derive(Debug, Default)]
+pub(crate) struct SupervisorLog {
+    latest: HashMap<String, SupervisorDiagnostic>,
+}
+
+impl SupervisorLog {
+    /// Read the log of each runsvdir whose directory is one of `enabled_dirs`.
+    /// A runsvdir started without `-P` keeps no log, so it contributes nothing.
+    pub(crate) fn collect<'a>(
+        processes: &ProcessTable,
+        enabled_dirs: impl IntoIterator<Item = &'a Path>,
+    ) -> Self {
+        let enabled: Vec<_> = enabled_dirs
+            .into_iter()
+            .filter_map(|dir| fs::canonicalize(dir).ok())
+            .collect();
+        let mut log = SupervisorLog::default();
+        for (dir, text) in processes.runsvdir_logs() {
+            if fs::canonicalize(&dir).is_ok_and(|dir| enabled.contains(&dir)) {
+                log.add(&text);
+            }
+        }
+        log
+    }
+
+    /// Add the messages in `text`, later ones replacing earlier ones. runsvdir
+    /// drops the oldest bytes as new ones arrive, so the first line may be cut.
+    fn add(&mut self, text: &str) {
+        for line in text.lines() {
+            if let Some(caps) = RUNSV_MESSAGE_REGEX.captures(line) {
+                self.latest.insert(
+                    caps["name"].to_string(),
+                    SupervisorDiagnostic {
+                        message: caps["message"].trim().to_string(),
+                        fatal: &caps["level"] == "fatal",
+                    },
+                );
+            }
+        }
+    }
+
+    pub(crate) fn latest(&self, service: &str) -> Option<&SupervisorDiagnostic> {
+        self.latest.get(service)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SupervisorLog;
+
+    #[test]
+    fn keeps_the_latest_runsv_message_per_service() {
+        let mut log = SupervisorLog::default();
+        log.add(
+            "log: ......ning: unable to open supervise/ok\n\
+             runsv sshd: warning: unable to start ./run: file does not exist\n\
+             runsvdir: warning: unable to open ./bogus: file does not exist\n\
+             runsv dhcpcd: fatal: unable to lock supervise/lock: temporary failure\n\
+             runsv sshd: warning: unable to start ./run: access denied\n",
+        );
+
+        let sshd = log.latest("sshd").unwrap();
+        assert_eq!(sshd.message, "unable to start ./run: access denied");
+        assert!(!sshd.fatal);
+        assert!(log.latest("dhcpcd").unwrap().fatal);
+        assert!(log.latest("bogus").is_none());
+    }
+}
@@ -0,0 +1,236 @@
+//! Reading and writing the `config` file svlogd keeps in each log directory.
+use crate::{LogBackendKind, Result, ServiceError, ServiceManager};
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+/// One line of svlogd's pattern selection, applied in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SvlogdFilter {
+    /// `+pattern`: log lines matching the pattern.
+    Select(String),
+    /// `-pattern`: drop lines matching the pattern.
+    Deselect(String),
+    /// `epattern`: also print matching lines to stderr.
+    SelectAlert(String),
+    /// `Epattern`: do not print matching lines to stderr.
+    DeselectAlert(String),
+}
+
+impl SvlogdFilter {
+    /// Parse a single svlogd filter line such as `-*debug*`.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut chars = line.chars();
+        let kind = chars.next()?;
+        let pattern = chars.as_str().to_string();
+        match kind {
+            '+' => Some(SvlogdFilter::Select(pattern)),
+            '-' => Some(SvlogdFilter::Deselect(pattern)),
+            'e' => Some(SvlogdFilter::SelectAlert(pattern)),
+            'E' => Some(SvlogdFilter::DeselectAlert(pattern)),
+            _ => None,
+        }
+    }
+
+    pub fn pattern(&self) -> &str {
+        match self {
+            SvlogdFilter::Select(pattern)
+            | SvlogdFilter::Deselect(pattern)
+            | SvlogdFilter::SelectAlert(pattern)
+            | SvlogdFilter::DeselectAlert(pattern) => pattern,
+        }
+    }
+}
+
+impl fmt::Display for SvlogdFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefix = match self {
+            SvlogdFilter::Select(_) => '+',
+            SvlogdFilter::Deselect(_) => '-',
+            SvlogdFilter::SelectAlert(_) => 'e',
+            SvlogdFilter::DeselectAlert(_) => 'E',
+        };
+        write!(f, "{prefix}{}", self.pattern())
+    }
+}
+
+/// Typed view of an svlogd `config` file. Unset fields use svlogd's defaults
+/// (1 MB files, 10 old files kept, no time-based rotation).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SvlogdConfig {
+    /// `s`: rotate `current` once it reaches this many bytes (0 disables).
+    pub size: Option<u64>,
+    /// `n`: keep at most this many rotated files (0 keeps all).
+    pub num: Option<u32>,
+    /// `N`: keep at least this many rotated files even when the disk is full.
+    pub min: Option<u32>,
+    /// `t`: rotate `current` after this many seconds if it is not empty.
+    pub timeout: Option<u64>,
+    /// `!`: processor each rotated file is fed through.
+    pub processor: Option<String>,
+    /// `p`: prefix added to every line.
+    pub prefix: Option<String>,
+    pub filters: Vec<SvlogdFilter>,
+    /// Lines runkit does not model (UDP forwarding, comments), kept verbatim.
+    pub other: Vec<String>,
+}
+
+impl SvlogdConfig {
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut config = SvlogdConfig::default();
+        for line in contents.lines() {
+            let Some(kind) = line.chars().next() else {
+                continue;
+            };
+            let value = &line[kind.len_utf8()..];
+            match kind {
+                's' => config.size = Some(parse_number(line, value)?),
+                'n' => config.num = Some(parse_number(line, value)?),
+                'N' => config.min = Some(parse_number(line, value)?),
+                't' => config.timeout = Some(parse_number(line, value)?),
+                '!' => config.processor = Some(value.to_string()),
+                'p' => config.prefix = Some(value.to_string()),
+                _ => match SvlogdFilter::parse(line) {
+                    Some(filter) => config.filters.push(filter),
+                    None => config.other.push(line.to_string()),
+                },
+            }
+        }
+        Ok(config)
+    }
+
+    /// Check that every value can be written back as a single config line.
+    pub fn validate(&self) -> Result<()> {
+        let texts = self
+            .processor
+            .iter()
+            .chain(&self.prefix)
+            .map(String::as_str)
+            .chain(self.filters.iter().map(SvlogdFilter::pattern))
+            .chain(self.other.iter().map(String::as_str));
+        for text in texts {
+            if text.contains(['\n', '\0']) {
+                return Err(ServiceError::InvalidLogConfig(format!(
+                    "{text:?} spans more than one line"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SvlogdConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(size) = self.size {
+            writeln!(f, "s{size}")?;
+        }
+        if let Some(num) = self.num {
+            writeln!(f, "n{num}")?;
+        }
+        if let Some(min) = self.min {
+            writeln!(f, "N{min}")?;
+        }
+        if let Some(timeout) = self.timeout {
+            writeln!(f, "t{timeout}")?;
+        }
+        if let Some(processor) = &self.processor {
+            writeln!(f, "!{processor}")?;
+        }
+        if let Some(prefix) = &self.prefix {
+            writeln!(f, "p{prefix}")?;
+        }
+        for filter in &self.filters {
+            writeln!(f, "{filter}")?;
+        }
+        for line in &self.other {
+            writeln!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(line: &str, value: &str) -> Result<T> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| ServiceError::InvalidLogConfig(format!("{line:?} is not a number")))
+}
+
+impl ServiceManager {
+    /// The service's svlogd configuration; a missing `config` file is the default.
+    pub fn log_config(&self, service: &str) -> Result<SvlogdConfig> {
+        let config_path = self.log_config_path(service)?;
+        match fs::read_to_string(&config_path) {
+            Ok(contents) => SvlogdConfig::parse(&contents),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(SvlogdConfig::default()),
+            Err(err) => Err(ServiceError::from_io(&config_path, err)),
+        }
+    }
+
+    /// Replace the service's svlogd `config` file and ask its logger to reread it.
+    ///
+    /// The reload is best effort: a logger that is not running picks the file up
+    /// when it next starts.
+    pub fn set_log_config(&self, service: &str, config: &SvlogdConfig) -> Result<()> {
+        config.validate()?;
+        let config_path = self.log_config_path(service)?;
+        let staging = config_path.with_extension("runkit-new");
+        fs::write(&staging, config.to_string())
+            .map_err(|err| ServiceError::from_io(&staging, err))?;
+        fs::rename(&staging, &config_path).map_err(|err| {
+            let _ = fs::remove_file(&staging);
+            ServiceError::from_io(&config_path, err)
+        })?;
+
+        // svlogd rereads its config on SIGHUP; `sv hup` on the log service sends it.
+        let _ = self.sv("hup", &format!("{service}/log")).output();
+        Ok(())
+    }
+
+    fn log_config_path(&self, service: &str) -> Result<PathBuf> {
+        self.validate_service_name(service)?;
+        if let Some(backend) = self.log_backend(service)
+            && backend.kind() == LogBackendKind::Svlogd
+        {
+            return Ok(backend.location().join("config"));
+        }
+        let (definitions_dir, enabled_dir) = self.dirs_for(service);
+        [definitions_dir, enabled_dir]
+            .into_iter()
+            .map(|root| root.join(service).join("log/main"))
+            .find(|dir| dir.is_dir())
+            .map(|dir| dir.join("config"))
+            .ok_or_else(|| ServiceError::LogUnavailable(service.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SvlogdConfig, SvlogdFilter};
+
+    #[test]
+    fn round_trips_config_files() {
+        let contents = "s1000000\nn5\nt86400\n-*debug*\ne*panic*\nu10.0.0.1:514\n";
+        let config = SvlogdConfig::parse(contents).unwrap();
+        assert_eq!(config.size, Some(1_000_000));
+        assert_eq!(config.num, Some(5));
+        assert_eq!(config.timeout, Some(86_400));
+        assert_eq!(
+            config.filters,
+            [
+                SvlogdFilter::Deselect("*debug*".to_string()),
+                SvlogdFilter::SelectAlert("*panic*".to_string()),
+            ]
+        );
+        assert_eq!(config.other, ["u10.0.0.1:514"]);
+        assert_eq!(config.to_string(), contents);
+
+        assert!(SvlogdConfig::parse("sbig\n").is_err());
+        let multiline = SvlogdConfig {
+            prefix: Some("a\nb".to_string()),
+            ..SvlogdConfig::default()
+        };
+        assert!(multiline.validate().is_err());
+    }
+}
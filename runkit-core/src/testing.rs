@@ -0,0 +1,203 @@
+//! Throwaway service trees for tests that must not need root or runit.
+//!
+//! Available with the `testing` feature. A [`SandboxFixture`] owns a temporary
+//! directory with definitions, an enabled directory, and a stub `sv` that keeps
+//! per-service state in plain files, so start/stop/status round-trips behave
+//! like a tiny runsvdir.
+use crate::ServiceManager;
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static NEXT_FIXTURE: AtomicU32 = AtomicU32::new(0);
+
+/// Stub `sv`: records each call in `calls` and keeps `run <pid>` or `down` per
+/// service under `state/`. `@STATE@` and `@CALLS@` are replaced on install.
+const STUB_SV: &str = r#"#!/bin/sh
+command=$1
+shift
+status=0
+for target in "$@"; do
+    name=${target##*/}
+    case $target in
+        /*) dir=$target ;;
+        *) dir=$SVDIR/$name ;;
+    esac
+    echo "$command $name" >> "@CALLS@"
+    if [ ! -d "$dir" ]; then
+        echo "fail: $name: unable to change to service directory: file does not exist"
+        status=1
+        continue
+    fi
+    state_file="@STATE@/$name"
+    case $command in
+        up|u|start|once|o|restart|t)
+            echo "run $$" > "$state_file"
+            prefix="ok: " ;;
+        down|d|stop|exit|x)
+            echo "down" > "$state_file"
+            prefix="ok: " ;;
+        status|s)
+            prefix="" ;;
+        *)
+            prefix="ok: " ;;
+    esac
+    state=down
+    if [ -f "$state_file" ]; then
+        read -r state pid < "$state_file"
+    fi
+    if [ "$state" = run ]; then
+        echo "${prefix}run: $name: (pid $pid) 1s"
+    elif [ -e "$dir/down" ]; then
+        echo "${prefix}down: $name: 1s"
+    else
+        echo "${prefix}down: $name: 1s, normally up"
+    fi
+done
+exit $status
+"#;
+
+/// Temporary definitions/enabled tree plus a stub `sv`, removed on drop.
+#[derive(Debug)]
+pub struct SandboxFixture {
+    root: PathBuf,
+    manager: ServiceManager,
+}
+
+impl SandboxFixture {
+    pub fn new() -> io::Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!(
+            "runkit-sandbox-{}-{}-{nanos}",
+            std::process::id(),
+            NEXT_FIXTURE.fetch_add(1, Ordering::Relaxed)
+        ));
+        let definitions = root.join("sv");
+        let enabled = root.join("service");
+        let state = root.join("state");
+        for dir in [&definitions, &enabled, &state] {
+            fs::create_dir_all(dir)?;
+        }
+
+        let sv = root.join("sv-stub");
+        let script = STUB_SV
+            .replace("@STATE@", &state.to_string_lossy())
+            .replace("@CALLS@", &root.join("calls").to_string_lossy());
+        fs::write(&sv, script)?;
+        fs::set_permissions(&sv, fs::Permissions::from_mode(0o755))?;
+
+        Ok(SandboxFixture {
+            manager: ServiceManager::sandbox(definitions, enabled, sv),
+            root,
+        })
+    }
+
+    pub fn manager(&self) -> &ServiceManager {
+        &self.manager
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Create a definition with an executable `run` script.
+    pub fn add_service(&self, name: &str, run_script: &str) -> io::Result<PathBuf> {
+        let definition = self.manager.definitions_dir().join(name);
+        fs::create_dir_all(&definition)?;
+        let run = definition.join("run");
+        fs::write(&run, run_script)?;
+        fs::set_permissions(&run, fs::Permissions::from_mode(0o755))?;
+        Ok(definition)
+    }
+
+    /// Link an existing definition into the enabled directory.
+    pub fn enable(&self, name: &str) -> io::Result<()> {
+        std::os::unix::fs::symlink(
+            self.manager.definitions_dir().join(name),
+            self.manager.enabled_dir().join(name),
+        )
+    }
+
+    /// Mark the service as running under `pid` as far as the stub `sv` is concerned.
+    pub fn set_running(&self, name: &str, pid: u32) -> io::Result<()> {
+        fs::write(self.root.join("state").join(name), format!("run {pid}\n"))
+    }
+
+    /// Write svlogd-style lines to the service's `log/main/current`.
+    pub fn write_log(&self, name: &str, lines: &[&str]) -> io::Result<()> {
+        let log_dir = self.manager.definitions_dir().join(name).join("log/main");
+        fs::create_dir_all(&log_dir)?;
+        let mut contents = lines.join("\n");
+        contents.push('\n');
+        fs::write(log_dir.join("current"), contents)
+    }
+
+    /// Every `sv <command> <service>` the stub has received, oldest first.
+    pub fn sv_calls(&self) -> io::Result<Vec<String>> {
+        match fs::read_to_string(self.root.join("calls")) {
+            Ok(calls) => Ok(calls.lines().map(str::to_string).collect()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl Drop for SandboxFixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SandboxFixture;
+    use crate::{DesiredState, ServiceRuntimeState};
+
+    #[test]
+    fn sandbox_round_trips_through_stub_sv() {
+        let fixture = SandboxFixture::new().unwrap();
+        fixture
+            .add_service("sshd", "#!/bin/sh\nexec sshd -D\n")
+            .unwrap();
+        fixture
+            .add_service("cronie", "#!/bin/sh\nexec crond -n\n")
+            .unwrap();
+        fixture.enable("sshd").unwrap();
+        fixture
+            .write_log("sshd", &["@400000006553f0000000000a listening"])
+            .unwrap();
+        let manager = fixture.manager();
+
+        let services = manager.list_services().unwrap();
+        let sshd = services.iter().find(|info| info.name == "sshd").unwrap();
+        assert_eq!(sshd.desired_state, DesiredState::AutoStart);
+        assert!(matches!(
+            sshd.runtime_state,
+            ServiceRuntimeState::Down {
+                normally_up: true,
+                ..
+            }
+        ));
+
+        manager.control("sshd", "up").unwrap();
+        assert!(matches!(
+            manager.status("sshd").unwrap(),
+            ServiceRuntimeState::Running { .. }
+        ));
+        assert!(manager.control("cronie", "up").is_err());
+        let calls = fixture.sv_calls().unwrap();
+        assert_eq!(
+            calls[calls.len() - 3..],
+            ["up sshd", "status sshd", "up cronie"]
+        );
+
+        let logs = manager.tail_logs("sshd", 10).unwrap();
+        assert_eq!(logs[0].message, "listening");
+    }
+}
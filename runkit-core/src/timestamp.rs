@@ -0,0 +1,206 @@
+//! Rendering of svlogd timestamps for display and export.
+use crate::ServiceLogEntry;
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// How a log timestamp is written out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampStyle {
+    /// Calendar time in the local time zone.
+    #[default]
+    Local,
+    /// Calendar time in UTC, suffixed with `Z`.
+    Utc,
+    /// The raw `@4000…` label exactly as svlogd wrote it.
+    Tai64n,
+    /// Age relative to now, e.g. `3m ago`.
+    Relative,
+}
+
+impl TimestampStyle {
+    pub const ALL: [TimestampStyle; 4] = [
+        TimestampStyle::Local,
+        TimestampStyle::Utc,
+        TimestampStyle::Tai64n,
+        TimestampStyle::Relative,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TimestampStyle::Local => "local",
+            TimestampStyle::Utc => "utc",
+            TimestampStyle::Tai64n => "tai64n",
+            TimestampStyle::Relative => "relative",
+        }
+    }
+}
+
+impl fmt::Display for TimestampStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TimestampStyle {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        TimestampStyle::ALL
+            .into_iter()
+            .find(|style| style.as_str().eq_ignore_ascii_case(value))
+            .ok_or_else(|| {
+                format!(
+                    "unknown timestamp style {value:?} (expected local, utc, tai64n or relative)"
+                )
+            })
+    }
+}
+
+/// Timestamp style plus whether fractional seconds are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimestampFormat {
+    pub style: TimestampStyle,
+    pub subsecond: bool,
+}
+
+impl Default for TimestampFormat {
+    fn default() -> Self {
+        TimestampFormat {
+            style: TimestampStyle::Local,
+            subsecond: true,
+        }
+    }
+}
+
+impl TimestampFormat {
+    pub fn new(style: TimestampStyle, subsecond: bool) -> Self {
+        TimestampFormat { style, subsecond }
+    }
+
+    /// Render decoded or raw timestamp parts; `None` when the entry carries none.
+    pub fn render(
+        &self,
+        unix_seconds: Option<i64>,
+        nanos: Option<u32>,
+        raw: Option<&str>,
+    ) -> Option<String> {
+        self.render_at(unix_seconds, nanos, raw, Utc::now())
+    }
+
+    fn render_at(
+        &self,
+        unix_seconds: Option<i64>,
+        nanos: Option<u32>,
+        raw: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Option<String> {
+        if self.style == TimestampStyle::Tai64n {
+            return raw.map(|raw| format!("@{raw}"));
+        }
+
+        let Some(datetime) =
+            unix_seconds.and_then(|secs| DateTime::<Utc>::from_timestamp(secs, nanos.unwrap_or(0)))
+        else {
+            return raw.map(|raw| format!("@{raw}"));
+        };
+
+        let fraction = if self.subsecond && datetime.timestamp_subsec_micros() > 0 {
+            format!(".{:06}", datetime.timestamp_subsec_micros())
+        } else {
+            String::new()
+        };
+
+        Some(match self.style {
+            TimestampStyle::Local => {
+                let local: DateTime<Local> = datetime.into();
+                format!("{}{fraction}", local.format("%Y-%m-%d %H:%M:%S"))
+            }
+            TimestampStyle::Utc => format!("{}{fraction}Z", datetime.format("%Y-%m-%d %H:%M:%S")),
+            TimestampStyle::Relative => format_relative(now - datetime, self.subsecond),
+            TimestampStyle::Tai64n => unreachable!(),
+        })
+    }
+}
+
+impl ServiceLogEntry {
+    /// The entry's decoded TAI64N label as a UTC instant.
+    pub fn utc_datetime(&self) -> Option<DateTime<Utc>> {
+        DateTime::<Utc>::from_timestamp(self.timestamp_unix?, self.timestamp_nanos.unwrap_or(0))
+    }
+
+    /// The entry's timestamp in the system's local time zone.
+    pub fn local_datetime(&self) -> Option<DateTime<Local>> {
+        self.utc_datetime()
+            .map(|datetime| datetime.with_timezone(&Local))
+    }
+
+    pub fn format_timestamp(&self, format: TimestampFormat) -> Option<String> {
+        format.render(
+            self.timestamp_unix,
+            self.timestamp_nanos,
+            self.timestamp_raw.as_deref(),
+        )
+    }
+}
+
+fn format_relative(age: chrono::TimeDelta, subsecond: bool) -> String {
+    if age < chrono::TimeDelta::zero() {
+        return "in the future".to_string();
+    }
+    let secs = age.num_seconds();
+    match secs {
+        0..=59 if subsecond => format!("{:.1}s ago", age.num_milliseconds() as f64 / 1000.0),
+        0..=4 => "just now".to_string(),
+        5..=59 => format!("{secs}s ago"),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86_399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86_400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TimestampFormat, TimestampStyle};
+    use chrono::{DateTime, Utc};
+
+    #[test]
+    fn renders_each_style() {
+        let now = DateTime::<Utc>::from_timestamp(1_700_000_180, 0).unwrap();
+        let raw = Some("4000000065536fd01dcd6500");
+        let parts = (Some(1_700_000_000), Some(500_000_000));
+
+        let utc = TimestampFormat::new(TimestampStyle::Utc, true);
+        assert_eq!(
+            utc.render_at(parts.0, parts.1, raw, now).unwrap(),
+            "2023-11-14 22:13:20.500000Z"
+        );
+        let utc_whole = TimestampFormat::new(TimestampStyle::Utc, false);
+        assert_eq!(
+            utc_whole.render_at(parts.0, parts.1, raw, now).unwrap(),
+            "2023-11-14 22:13:20Z"
+        );
+
+        let tai = TimestampFormat::new(TimestampStyle::Tai64n, true);
+        assert_eq!(
+            tai.render_at(parts.0, parts.1, raw, now).unwrap(),
+            "@4000000065536fd01dcd6500"
+        );
+
+        let relative = TimestampFormat::new(TimestampStyle::Relative, false);
+        assert_eq!(
+            relative.render_at(parts.0, parts.1, raw, now).unwrap(),
+            "2m ago"
+        );
+        assert!(relative.render_at(None, None, None, now).is_none());
+    }
+
+    #[test]
+    fn parses_style_names() {
+        assert_eq!("UTC".parse::<TimestampStyle>(), Ok(TimestampStyle::Utc));
+        assert!("iso".parse::<TimestampStyle>().is_err());
+    }
+}
@@ -0,0 +1,320 @@
+//! Deleted definitions parked in a trash directory so they can be restored.
+use crate::{Result, ServiceError, ServiceManager};
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_TRASH_DIR: &str = "/var/lib/runkit/trash";
+/// How long trashed definitions are kept before they are purged for good.
+pub const DEFAULT_TRASH_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+const DEFINITION_ENTRY: &str = "definition";
+const INFO_ENTRY: &str = "info";
+/// Runtime-only entries left behind when a definition has to be copied.
+const RUNTIME_ENTRIES: &[&str] = &["supervise", "log/supervise"];
+
+/// A definition moved to the trash by [`ServiceManager::trash_definition`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashEntry {
+    /// Name of the entry inside the trash directory, used to restore it.
+    pub id: String,
+    pub service: String,
+    /// Where the definition lived before it was trashed.
+    pub original_path: PathBuf,
+    /// Unix time at which the definition was trashed.
+    pub deleted_at: i64,
+    /// The service was linked into the enabled directory and is re-enabled on restore.
+    pub was_enabled: bool,
+}
+
+impl TrashEntry {
+    fn to_info(&self) -> String {
+        format!(
+            "service={}\noriginal_path={}\ndeleted_at={}\nwas_enabled={}\n",
+            self.service,
+            self.original_path.display(),
+            self.deleted_at,
+            self.was_enabled
+        )
+    }
+
+    fn from_info(id: &str, info: &str) -> Option<Self> {
+        let field = |key: &str| {
+            info.lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+        };
+        Some(TrashEntry {
+            id: id.to_string(),
+            service: field("service")?.to_string(),
+            original_path: PathBuf::from(field("original_path")?),
+            deleted_at: field("deleted_at")?.parse().ok()?,
+            was_enabled: field("was_enabled") == Some("true"),
+        })
+    }
+}
+
+impl ServiceManager {
+    pub fn with_trash_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.trash_dir = Some(dir.into());
+        self
+    }
+
+    pub fn with_trash_retention(mut self, retention: Duration) -> Self {
+        self.trash_retention = retention;
+        self
+    }
+
+    pub fn trash_dir(&self) -> Option<&Path> {
+        self.trash_dir.as_deref()
+    }
+
+    /// Move the definition of `service` into the trash instead of deleting it.
+    ///
+    /// The service's enable link is removed first so runsvdir lets go of it.
+    /// Entries older than the retention period are purged on the way.
+    pub fn trash_definition(&self, service: &str) -> Result<TrashEntry> {
+        self.existing_definition(service)?;
+        self.require_trash_dir()?;
+
+        let link = self.enabled_dir.join(service);
+        let was_enabled = fs::symlink_metadata(&link).is_ok();
+        if was_enabled {
+            fs::remove_file(&link).map_err(|err| ServiceError::from_io(&link, err))?;
+        }
+        self.move_to_trash(service, was_enabled)
+    }
+
+    /// Park the definition in the trash without touching its enable link.
+    pub(crate) fn move_to_trash(&self, service: &str, was_enabled: bool) -> Result<TrashEntry> {
+        let definition_path = self.existing_definition(service)?;
+        let trash_dir = self.require_trash_dir()?;
+        fs::create_dir_all(trash_dir).map_err(|err| ServiceError::from_io(trash_dir, err))?;
+        let _ = self.purge_trash(false);
+
+        let deleted_at = unix_now();
+        let (id, entry_dir) = unique_entry_dir(trash_dir, service, deleted_at)?;
+        let entry = TrashEntry {
+            id,
+            service: service.to_string(),
+            original_path: definition_path.clone(),
+            deleted_at,
+            was_enabled,
+        };
+        let info_path = entry_dir.join(INFO_ENTRY);
+        fs::write(&info_path, entry.to_info())
+            .map_err(|err| ServiceError::from_io(&info_path, err))?;
+        move_tree(&definition_path, &entry_dir.join(DEFINITION_ENTRY))?;
+        Ok(entry)
+    }
+
+    /// Trashed definitions, newest first. An unconfigured or missing trash is empty.
+    pub fn list_trash(&self) -> Result<Vec<TrashEntry>> {
+        let Some(trash_dir) = self.trash_dir.as_deref() else {
+            return Ok(Vec::new());
+        };
+        let read_dir = match fs::read_dir(trash_dir) {
+            Ok(read_dir) => read_dir,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(ServiceError::from_io(trash_dir, err)),
+        };
+
+        let mut entries: Vec<TrashEntry> = read_dir
+            .flatten()
+            .filter_map(|dir_entry| {
+                let id = dir_entry.file_name().into_string().ok()?;
+                let info = fs::read_to_string(dir_entry.path().join(INFO_ENTRY)).ok()?;
+                TrashEntry::from_info(&id, &info)
+            })
+            .filter(|entry| trash_dir.join(&entry.id).join(DEFINITION_ENTRY).is_dir())
+            .collect();
+        entries.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at).then(a.id.cmp(&b.id)));
+        Ok(entries)
+    }
+
+    /// Move a trashed definition back into the definitions directory, and re-enable
+    /// it if it was enabled when it was trashed. Returns the service name.
+    pub fn restore_from_trash(&self, id: &str) -> Result<String> {
+        let trash_dir = self.require_trash_dir()?;
+        let entry = self
+            .list_trash()?
+            .into_iter()
+            .find(|entry| entry.id == id)
+            .ok_or_else(|| ServiceError::NotInTrash(id.to_string()))?;
+        self.validate_service_name(&entry.service)?;
+
+        let destination = self.definitions_dir.join(&entry.service);
+        if fs::symlink_metadata(&destination).is_ok() {
+            return Err(ServiceError::DefinitionExists(entry.service));
+        }
+
+        let entry_dir = trash_dir.join(&entry.id);
+        move_tree(&entry_dir.join(DEFINITION_ENTRY), &destination)?;
+        let _ = fs::remove_dir_all(&entry_dir);
+
+        if entry.was_enabled {
+            match self.enable(&entry.service) {
+                Ok(()) | Err(ServiceError::AlreadyEnabled(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(entry.service)
+    }
+
+    /// Permanently remove trashed definitions past the retention period, or all of
+    /// them when `everything` is set. Returns the removed entries.
+    pub fn purge_trash(&self, everything: bool) -> Result<Vec<TrashEntry>> {
+        let Some(trash_dir) = self.trash_dir.as_deref() else {
+            return Ok(Vec::new());
+        };
+        let cutoff = unix_now().saturating_sub(self.trash_retention.as_secs() as i64);
+
+        let mut purged = Vec::new();
+        for entry in self.list_trash()? {
+            if !everything && entry.deleted_at > cutoff {
+                continue;
+            }
+            let entry_dir = trash_dir.join(&entry.id);
+            fs::remove_dir_all(&entry_dir).map_err(|err| ServiceError::from_io(&entry_dir, err))?;
+            purged.push(entry);
+        }
+        Ok(purged)
+    }
+
+    pub(crate) fn require_trash_dir(&self) -> Result<&Path> {
+        self.trash_dir
+            .as_deref()
+            .ok_or_else(|| ServiceError::Other("no trash directory is configured".into()))
+    }
+}
+
+fn unique_entry_dir(trash_dir: &Path, service: &str, deleted_at: i64) -> Result<(String, PathBuf)> {
+    let mut attempt = 0;
+    loop {
+        let id = match attempt {
+            0 => format!("{service}.{deleted_at}"),
+            n => format!("{service}.{deleted_at}-{n}"),
+        };
+        let path = trash_dir.join(&id);
+        match fs::create_dir(&path) {
+            Ok(()) => return Ok((id, path)),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => attempt += 1,
+            Err(err) => return Err(ServiceError::from_io(&path, err)),
+        }
+    }
+}
+
+/// Rename `from` to `to`, copying and removing instead when they are on
+/// different filesystems (e.g. `/etc/sv` and `/var/lib`).
+fn move_tree(from: &Path, to: &Path) -> Result<()> {
+    match fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == ErrorKind::CrossesDevices => {
+            if let Err(err) = copy_tree(from, to, from) {
+                let _ = fs::remove_dir_all(to);
+                return Err(err);
+            }
+            fs::remove_dir_all(from).map_err(|err| ServiceError::from_io(from, err))
+        }
+        Err(err) => Err(ServiceError::from_io(from, err)),
+    }
+}
+
+/// Copy directories, regular files and symlinks; runsv's control FIFOs and the
+/// `supervise` directories holding them are skipped.
+fn copy_tree(from: &Path, to: &Path, root: &Path) -> Result<()> {
+    fs::create_dir(to).map_err(|err| ServiceError::from_io(to, err))?;
+    let permissions = fs::metadata(from)
+        .map_err(|err| ServiceError::from_io(from, err))?
+        .permissions();
+    fs::set_permissions(to, permissions).map_err(|err| ServiceError::from_io(to, err))?;
+
+    let read_dir = fs::read_dir(from).map_err(|err| ServiceError::from_io(from, err))?;
+    for entry in read_dir {
+        let entry = entry.map_err(|err| ServiceError::from_io(from, err))?;
+        let source = entry.path();
+        let relative = source.strip_prefix(root).unwrap_or(&source);
+        if RUNTIME_ENTRIES
+            .iter()
+            .any(|runtime| relative == Path::new(runtime))
+        {
+            continue;
+        }
+        let target = to.join(entry.file_name());
+        let file_type = entry
+            .file_type()
+            .map_err(|err| ServiceError::from_io(&source, err))?;
+
+        if file_type.is_symlink() {
+            let link = fs::read_link(&source).map_err(|err| ServiceError::from_io(&source, err))?;
+            symlink(link, &target).map_err(|err| ServiceError::from_io(&target, err))?;
+        } else if file_type.is_dir() {
+            copy_tree(&source, &target, root)?;
+        } else if file_type.is_file() {
+            fs::copy(&source, &target).map_err(|err| ServiceError::from_io(&target, err))?;
+        }
+    }
+    Ok(())
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ServiceError, ServiceManager};
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn trashes_restores_and_purges_definitions() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root =
+            std::env::temp_dir().join(format!("runkit-trash-{}-{nanos}", std::process::id()));
+        let definitions = root.join("sv");
+        let enabled = root.join("service");
+        fs::create_dir_all(definitions.join("sshd")).unwrap();
+        fs::create_dir_all(definitions.join("cronie")).unwrap();
+        fs::create_dir_all(&enabled).unwrap();
+        fs::write(definitions.join("sshd/run"), "#!/bin/sh\nexec sshd -D\n").unwrap();
+        symlink(definitions.join("sshd"), enabled.join("sshd")).unwrap();
+
+        let manager =
+            ServiceManager::new(&definitions, &enabled).with_trash_dir(root.join("trash"));
+        let trashed = manager.trash_definition("sshd").unwrap();
+        assert!(trashed.was_enabled);
+        assert!(!definitions.join("sshd").exists());
+        assert!(fs::symlink_metadata(enabled.join("sshd")).is_err());
+        manager.trash_definition("cronie").unwrap();
+        assert_eq!(manager.list_trash().unwrap().len(), 2);
+
+        assert_eq!(manager.restore_from_trash(&trashed.id).unwrap(), "sshd");
+        assert_eq!(
+            fs::read_to_string(definitions.join("sshd/run")).unwrap(),
+            "#!/bin/sh\nexec sshd -D\n"
+        );
+        assert!(enabled.join("sshd").exists());
+        assert!(matches!(
+            manager.restore_from_trash(&trashed.id),
+            Err(ServiceError::NotInTrash(_))
+        ));
+
+        assert!(manager.purge_trash(false).unwrap().is_empty());
+        let expired = manager.with_trash_retention(Duration::ZERO);
+        let purged = expired.purge_trash(false).unwrap();
+        assert_eq!(purged.len(), 1);
+        assert_eq!(purged[0].service, "cronie");
+        assert!(expired.list_trash().unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
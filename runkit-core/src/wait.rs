@@ -0,0 +1,137 @@
+//! Waiting for a service to reach the state a start, stop or restart asked for.
+use crate::{Result, ServiceManager, ServiceRuntimeState};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the status is read while waiting.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long a started process must stay up to count as started, so one that
+/// crashes straight away is not reported as running.
+pub const SETTLE_TIME: Duration = Duration::from_secs(1);
+
+/// A state change that can be waited for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateChange {
+    Start,
+    Stop,
+    Restart,
+}
+
+impl StateChange {
+    /// Word for messages, e.g. "sshd did not become running".
+    pub fn target_name(self) -> &'static str {
+        match self {
+            StateChange::Start | StateChange::Restart => "running",
+            StateChange::Stop => "down",
+        }
+    }
+}
+
+/// State [`ServiceManager::wait_for_state`] waits for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetState {
+    /// Running and settled. `replacing` is a pid that does not count, such as
+    /// the one a restart is replacing.
+    Up { replacing: Option<u32> },
+    /// Down, whether it exited cleanly or not.
+    Down,
+}
+
+/// Where a wait ended.
+#[derive(Debug, Clone)]
+pub struct WaitOutcome {
+    pub reached: bool,
+    /// The last state read, which explains a wait that was not `reached`.
+    pub state: ServiceRuntimeState,
+    pub waited: Duration,
+}
+
+impl ServiceManager {
+    /// Poll the service's status until it reaches `target` or `timeout` passes.
+    ///
+    /// This is what `sv -w` does, with one addition: a started service must keep
+    /// the same pid for [`SETTLE_TIME`], so a crash right after exec is not
+    /// mistaken for success. A wait for `Up` ends early if runit reports the
+    /// service as failed.
+    pub fn wait_for_state(
+        &self,
+        service: &str,
+        target: TargetState,
+        timeout: Duration,
+    ) -> Result<WaitOutcome> {
+        let started = Instant::now();
+        let mut first_seen: Option<(u32, Instant)> = None;
+        loop {
+            let state = self.status(service)?;
+            let reached = match (target, &state) {
+                (TargetState::Down, ServiceRuntimeState::Down { .. })
+                | (TargetState::Down, ServiceRuntimeState::Failed { .. }) => true,
+                (TargetState::Up { replacing }, ServiceRuntimeState::Running { pid, uptime })
+                    if replacing != Some(*pid) =>
+                {
+                    let seen_since = match first_seen {
+                        Some((seen, since)) if seen == *pid => since,
+                        _ => first_seen.insert((*pid, Instant::now())).1,
+                    };
+                    *uptime >= SETTLE_TIME || seen_since.elapsed() >= SETTLE_TIME
+                }
+                (TargetState::Up { .. }, ServiceRuntimeState::Failed { .. }) => {
+                    return Ok(WaitOutcome {
+                        reached: false,
+                        state,
+                        waited: started.elapsed(),
+                    });
+                }
+                _ => {
+                    first_seen = None;
+                    false
+                }
+            };
+            let waited = started.elapsed();
+            if reached || waited >= timeout {
+                return Ok(WaitOutcome {
+                    reached,
+                    state,
+                    waited,
+                });
+            }
+            thread::sleep(POLL_INTERVAL.min(timeout - waited));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StateChange, TargetState};
+    use crate::ServiceBackend;
+    use crate::testing::SandboxFixture;
+    use std::time::Duration;
+
+    #[test]
+    fn waits_for_start_and_stop() {
+        let sandbox = SandboxFixture::new().unwrap();
+        sandbox.add_service("sshd", "exec sshd -D\n").unwrap();
+        sandbox.enable("sshd").unwrap();
+        let manager = sandbox.manager();
+
+        let timeout = Duration::from_millis(300);
+        let outcome = manager
+            .wait_for_state("sshd", TargetState::Up { replacing: None }, timeout)
+            .unwrap();
+        assert!(!outcome.reached);
+        assert!(outcome.waited >= timeout);
+
+        let (_, outcome) = manager
+            .change_and_wait("sshd", StateChange::Start, timeout)
+            .unwrap();
+        assert!(outcome.reached);
+        assert!(outcome.state.pid().is_some());
+
+        let (_, outcome) = manager
+            .change_and_wait("sshd", StateChange::Stop, timeout)
+            .unwrap();
+        assert!(outcome.reached);
+        assert!(outcome.state.pid().is_none());
+    }
+}
@@ -0,0 +1,300 @@
+//! inotify-based change notifications for service definitions and supervisors.
+use crate::{Result, ServiceError, ServiceManager};
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+const EVENT_BUFFER_SIZE: usize = 4096;
+
+/// Change observed by a [`ServiceWatcher`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServiceEvent {
+    /// A definition directory appeared under the definitions directory.
+    ServiceAdded(String),
+    /// A definition directory was removed or moved away.
+    ServiceRemoved(String),
+    /// The service was linked into the enabled directory.
+    Enabled(String),
+    /// The service link was removed from the enabled directory.
+    Disabled(String),
+    /// runsv rewrote the service's `supervise/status` file.
+    StateChanged(String),
+}
+
+impl ServiceEvent {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ServiceEvent::ServiceAdded(_) => "added",
+            ServiceEvent::ServiceRemoved(_) => "removed",
+            ServiceEvent::Enabled(_) => "enabled",
+            ServiceEvent::Disabled(_) => "disabled",
+            ServiceEvent::StateChanged(_) => "state_changed",
+        }
+    }
+
+    pub fn service(&self) -> &str {
+        match self {
+            ServiceEvent::ServiceAdded(name)
+            | ServiceEvent::ServiceRemoved(name)
+            | ServiceEvent::Enabled(name)
+            | ServiceEvent::Disabled(name)
+            | ServiceEvent::StateChanged(name) => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum WatchTarget {
+    Definitions,
+    Enabled,
+    /// The definition directory itself, watched so a late `supervise` link is noticed.
+    Definition(String),
+    Supervise(String),
+}
+
+/// Blocking watcher over the definitions directories, the enabled directories
+/// and every service's `supervise` directory.
+pub struct ServiceWatcher {
+    inotify: Inotify,
+    targets: HashMap<WatchDescriptor, WatchTarget>,
+    /// In lookup order, the primary directory first.
+    definitions_dirs: Vec<PathBuf>,
+}
+
+impl ServiceManager {
+    /// Start watching this manager's directories for service changes.
+    pub fn watch(&self) -> Result<ServiceWatcher> {
+        ServiceWatcher::new(&self.service_dirs())
+    }
+}
+
+impl ServiceWatcher {
+    fn new(dirs: &[(&Path, &Path)]) -> Result<Self> {
+        let inotify = Inotify::init().map_err(|err| ServiceError::from_io("inotify", err))?;
+        let mut watcher = ServiceWatcher {
+            inotify,
+            targets: HashMap::new(),
+            definitions_dirs: dirs
+                .iter()
+                .map(|(definitions, _)| definitions.to_path_buf())
+                .collect(),
+        };
+
+        let listing_mask = WatchMask::CREATE
+            | WatchMask::DELETE
+            | WatchMask::MOVED_FROM
+            | WatchMask::MOVED_TO
+            | WatchMask::ONLYDIR;
+        for (index, (definitions_dir, enabled_dir)) in dirs.iter().enumerate() {
+            let watched = watcher
+                .add_watch(definitions_dir, listing_mask, WatchTarget::Definitions)
+                .and_then(|()| watcher.add_watch(enabled_dir, listing_mask, WatchTarget::Enabled));
+            match watched {
+                Ok(()) => {}
+                // Additional directories may not exist yet; only the primary pair is required.
+                Err(_) if index > 0 => continue,
+                Err(err) => return Err(err),
+            }
+
+            let entries = std::fs::read_dir(definitions_dir)
+                .map_err(|err| ServiceError::from_io(definitions_dir, err))?;
+            for entry in entries.flatten() {
+                if let Some(name) = service_name(&entry.file_name())
+                    && entry.path().is_dir()
+                {
+                    watcher.watch_service(&name);
+                }
+            }
+        }
+
+        Ok(watcher)
+    }
+
+    /// The definition directory of `service`, from the first directory that has it.
+    fn definition_path(&self, service: &str) -> PathBuf {
+        self.definitions_dirs
+            .iter()
+            .map(|dir| dir.join(service))
+            .find(|path| path.is_dir())
+            .unwrap_or_else(|| self.definitions_dirs[0].join(service))
+    }
+
+    /// Block until at least one relevant change happens and return the events,
+    /// with duplicates from the same batch collapsed.
+    pub fn read_events(&mut self) -> Result<Vec<ServiceEvent>> {
+        let mut buffer = [0u8; EVENT_BUFFER_SIZE];
+        loop {
+            let raw: Vec<(WatchDescriptor, EventMask, Option<String>)> = self
+                .inotify
+                .read_events_blocking(&mut buffer)
+                .map_err(|err| ServiceError::from_io("inotify", err))?
+                .map(|event| {
+                    (
+                        event.wd.clone(),
+                        event.mask,
+                        event.name.and_then(service_name),
+                    )
+                })
+                .collect();
+
+            let mut events = Vec::new();
+            for (wd, mask, name) in raw {
+                if let Some(event) = self.translate(&wd, mask, name)
+                    && !events.contains(&event)
+                {
+                    events.push(event);
+                }
+            }
+
+            if !events.is_empty() {
+                return Ok(events);
+            }
+        }
+    }
+
+    fn translate(
+        &mut self,
+        wd: &WatchDescriptor,
+        mask: EventMask,
+        name: Option<String>,
+    ) -> Option<ServiceEvent> {
+        if mask.contains(EventMask::IGNORED) {
+            self.targets.remove(wd);
+            return None;
+        }
+
+        let appeared = mask.intersects(EventMask::CREATE | EventMask::MOVED_TO);
+        let vanished = mask.intersects(EventMask::DELETE | EventMask::MOVED_FROM);
+
+        match self.targets.get(wd)?.clone() {
+            WatchTarget::Definitions => {
+                let name = name?;
+                if appeared && mask.contains(EventMask::ISDIR) {
+                    self.watch_service(&name);
+                    Some(ServiceEvent::ServiceAdded(name))
+                } else if vanished && mask.contains(EventMask::ISDIR) {
+                    Some(ServiceEvent::ServiceRemoved(name))
+                } else {
+                    None
+                }
+            }
+            WatchTarget::Enabled => {
+                let name = name?;
+                if appeared {
+                    self.watch_service(&name);
+                    Some(ServiceEvent::Enabled(name))
+                } else if vanished {
+                    Some(ServiceEvent::Disabled(name))
+                } else {
+                    None
+                }
+            }
+            WatchTarget::Definition(service) => {
+                if appeared && name.as_deref() == Some("supervise") {
+                    self.watch_supervise(&service);
+                    Some(ServiceEvent::StateChanged(service))
+                } else {
+                    None
+                }
+            }
+            WatchTarget::Supervise(service) => {
+                (name.as_deref() == Some("status")).then_some(ServiceEvent::StateChanged(service))
+            }
+        }
+    }
+
+    fn watch_service(&mut self, service: &str) {
+        let definition = self.definition_path(service);
+        let already_watched = self
+            .targets
+            .values()
+            .any(|target| matches!(target, WatchTarget::Definition(name) if name == service));
+        if !already_watched {
+            let _ = self.add_watch(
+                &definition,
+                WatchMask::CREATE | WatchMask::MOVED_TO | WatchMask::ONLYDIR,
+                WatchTarget::Definition(service.to_string()),
+            );
+        }
+        self.watch_supervise(service);
+    }
+
+    fn watch_supervise(&mut self, service: &str) {
+        // The supervise directory is commonly a symlink into /run; inotify follows it.
+        let supervise = self.definition_path(service).join("supervise");
+        if supervise.is_dir() {
+            let _ = self.add_watch(
+                &supervise,
+                WatchMask::MOVED_TO | WatchMask::CLOSE_WRITE,
+                WatchTarget::Supervise(service.to_string()),
+            );
+        }
+    }
+
+    fn add_watch(&mut self, path: &Path, mask: WatchMask, target: WatchTarget) -> Result<()> {
+        let wd = self
+            .inotify
+            .watches()
+            .add(path, mask)
+            .map_err(|err| ServiceError::from_io(path, err))?;
+        self.targets.insert(wd, target);
+        Ok(())
+    }
+}
+
+fn service_name(name: &OsStr) -> Option<String> {
+    let name = name.to_str()?;
+    (!name.is_empty() && !name.starts_with('.')).then(|| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ServiceEvent;
+    use crate::ServiceManager;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn reports_definition_and_link_changes() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root =
+            std::env::temp_dir().join(format!("runkit-watch-{}-{nanos}", std::process::id()));
+        let definitions = root.join("sv");
+        let enabled = root.join("service");
+        fs::create_dir_all(&definitions).unwrap();
+        fs::create_dir_all(&enabled).unwrap();
+
+        let manager = ServiceManager::new(&definitions, &enabled);
+        let mut watcher = manager.watch().unwrap();
+
+        fs::create_dir(definitions.join("sshd")).unwrap();
+        assert_eq!(
+            watcher.read_events().unwrap(),
+            [ServiceEvent::ServiceAdded("sshd".to_string())]
+        );
+
+        std::os::unix::fs::symlink(definitions.join("sshd"), enabled.join("sshd")).unwrap();
+        assert_eq!(
+            watcher.read_events().unwrap(),
+            [ServiceEvent::Enabled("sshd".to_string())]
+        );
+
+        fs::create_dir(definitions.join("sshd").join("supervise")).unwrap();
+        assert_eq!(
+            watcher.read_events().unwrap(),
+            [ServiceEvent::StateChanged("sshd".to_string())]
+        );
+
+        fs::remove_file(enabled.join("sshd")).unwrap();
+        assert_eq!(
+            watcher.read_events().unwrap(),
+            [ServiceEvent::Disabled("sshd".to_string())]
+        );
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
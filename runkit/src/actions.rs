@@ -1,66 +1,1263 @@
-use runkit_core::{DesiredState, ServiceInfo, ServiceRuntimeState};
+use runkit_core::{
+    CheckResult, Confinement, DEFAULT_SYSTEM_DESCRIPTIONS, DeleteMode, DesiredState, EnvDir,
+    HealthIssue, HealthIssueKind, IoSource, IoUsage, LogAccess, LogFilter, LogSeverity,
+    Maintenance, RestartPolicy, RunScriptInfo, SCHEMA_VERSION, ServerInfo, ServiceBackend,
+    ServiceError, ServiceHistory, ServiceInfo, ServiceLogEntry, ServiceLogs, ServiceManager,
+    ServiceRuntimeState, StateChange, SupervisorDiagnostic, TemplateBundle, format_duration,
+    read_descriptions, user_descriptions_path, write_description,
+};
 use serde::Deserialize;
 use serde_json::Value;
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
 use std::time::Duration;
-use zbus::blocking::{Connection, Proxy};
-use zbus::zvariant::Type;
+use zbus::blocking::{Connection, MessageIterator, Proxy};
+use zbus::zvariant::{Fd, Type, Value as Variant};
+use zbus::{MatchRule, MessageType};
 
 const BUS_NAME: &str = "tech.geektoshi.Runkit1";
 const OBJECT_PATH: &str = "/tech/geektoshi/Runkit1";
 const INTERFACE: &str = "tech.geektoshi.Runkit1.Controller";
 
+/// How far back the crash history shown for a service looks; the same as
+/// runkitd's `GetServiceHistory`.
+const HISTORY_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// How often the user's own logs are checked for new lines while followed,
+/// as runkitd does for the system's.
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Change announced by runkitd, e.g. `("state_changed", "sshd")`.
+#[derive(Debug, Clone)]
+pub struct ServiceChange {
+    pub kind: String,
+    pub service: String,
+}
+
+/// A failure or recovery reported by runkitd, e.g. `("critical", "sshd", "...")`.
+#[derive(Debug, Clone)]
+pub struct ServiceAlert {
+    /// `info`, `warning` or `critical`.
+    pub severity: String,
+    pub service: String,
+    pub message: String,
+}
+
+/// What a batch did: a summary, and each service it failed on with why.
+#[derive(Debug, Clone, Default)]
+pub struct BatchOutcome {
+    pub message: String,
+    pub failures: Vec<(String, String)>,
+}
+
+/// How a runkitd job is going, before it completes.
+#[derive(Debug, Clone)]
+pub enum JobUpdate {
+    /// The job was accepted under this id, which `cancel_job` takes.
+    Started(u64),
+    Progress {
+        done: u32,
+        total: u32,
+        message: String,
+    },
+}
+
+/// Entries appended to a log from now on, read by [`ActionDispatcher::follow_logs`]
+/// until the stream is dropped. The channel closes once the log can no longer
+/// be read, after an `Err` saying why.
+pub struct LogStream {
+    pub receiver: async_channel::Receiver<Result<LogEntry, String>>,
+    stop: Arc<AtomicBool>,
+    /// runkitd's subscription, with the connection it was made on; `None`
+    /// for the user's own services, which are followed in-process.
+    subscription: Option<(Connection, u64)>,
+}
+
+impl Drop for LogStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let Some((connection, id)) = self.subscription.take() else {
+            return;
+        };
+        if let Ok(proxy) = Proxy::new(&connection, BUS_NAME, OBJECT_PATH, INTERFACE) {
+            let _ = proxy.call::<_, _, String>("StopFollowing", &(id,));
+        }
+        // runkitd sends nothing once stopped, so wake the listener with an
+        // end signal of our own to let its thread finish.
+        if let Some(name) = connection.unique_name() {
+            let _ = connection.emit_signal(
+                Some(name.as_str()),
+                OBJECT_PATH,
+                INTERFACE,
+                "LogFollowEnded",
+                &(id, "stopped"),
+            );
+        }
+    }
+}
+
+/// Which supervision tree the dispatcher manages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scope {
+    /// The system's services, managed through runkitd.
+    #[default]
+    System,
+    /// The current user's own `runsvdir`, driven directly without D-Bus or polkit.
+    User,
+}
+
+/// What polkit would answer for an action, asked without prompting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Authorization {
+    #[default]
+    Allowed,
+    /// Allowed once the user enters a password.
+    AuthenticationRequired,
+    Denied,
+}
+
 #[derive(Clone)]
 pub struct ActionDispatcher {
-    connection: Connection,
+    /// Kept as an error rather than panicking so the setup assistant can explain it.
+    connection: Result<Connection, String>,
+    /// Manager for `~/.config/runit/sv`; `None` when `HOME` is not set.
+    user_manager: Option<ServiceManager>,
+    /// The user's own descriptions, looked up before runkitd is asked.
+    user_descriptions: Option<PathBuf>,
+    scope: Cell<Scope>,
+}
+
+impl Default for ActionDispatcher {
+    fn default() -> Self {
+        let connection = Connection::system()
+            .map_err(|err| format!("Failed to connect to the system bus: {err}"));
+        let user_descriptions = user_descriptions_path();
+        let user_manager = std::env::var_os("HOME")
+            .filter(|home| !home.is_empty())
+            .map(|home| {
+                let manager = ServiceManager::user(home);
+                match &user_descriptions {
+                    Some(path) => manager.with_user_descriptions(path),
+                    None => manager,
+                }
+            });
+        ActionDispatcher {
+            connection,
+            user_manager,
+            user_descriptions,
+            scope: Cell::new(Scope::System),
+        }
+    }
 }
 
-impl Default for ActionDispatcher {
-    fn default() -> Self {
-        let connection =
-            Connection::system().expect("Failed to connect to the system bus for runkitd");
-        ActionDispatcher { connection }
+impl ActionDispatcher {
+    pub fn scope(&self) -> Scope {
+        self.scope.get()
+    }
+
+    pub fn set_scope(&self, scope: Scope) -> Result<(), String> {
+        if scope == Scope::User && self.user_manager.is_none() {
+            return Err("Cannot find your home directory to look for user services".to_string());
+        }
+        self.scope.set(scope);
+        Ok(())
+    }
+
+    /// The manager to drive in-process while the user's own services are shown.
+    fn direct(&self) -> Option<&ServiceManager> {
+        match self.scope.get() {
+            Scope::System => None,
+            Scope::User => self.user_manager.as_ref(),
+        }
+    }
+
+    fn connection(&self) -> Result<&Connection, String> {
+        self.connection.as_ref().map_err(Clone::clone)
+    }
+
+    fn proxy(&self) -> Result<Proxy<'_>, String> {
+        if self.direct().is_some() {
+            return Err("This is only available for system services".to_string());
+        }
+        Proxy::new(self.connection()?, BUS_NAME, OBJECT_PATH, INTERFACE)
+            .map_err(|err| format!("Failed to connect to runkitd: {err}"))
+    }
+
+    fn call_helper<T>(&self, method: &str, body: &T) -> Result<DaemonProcessResponse, String>
+    where
+        T: serde::ser::Serialize + Type,
+    {
+        let proxy = self.proxy()?;
+        let reply: String = proxy
+            .call(method, body)
+            .map_err(|err| format!("runkitd call {method} failed: {err}"))?;
+        let response: DaemonProcessResponse = serde_json::from_str(&reply)
+            .map_err(|err| format!("Failed to decode runkitd response for {method}: {err}"))?;
+        if response.schema_version > SCHEMA_VERSION {
+            return Err(format!(
+                "runkitd answered {method} in format {}, newer than this Runkit reads \
+                 ({SCHEMA_VERSION}); update Runkit",
+                response.schema_version
+            ));
+        }
+        Ok(response)
+    }
+
+    /// What the daemon offers. User services are handled in-process, so they
+    /// get everything; a runkitd from before `GetServerInfo` reports API
+    /// version 0 and no capabilities.
+    pub fn server_info(&self) -> Result<ServerInfo, String> {
+        if self.direct().is_some() {
+            return Ok(ServerInfo::current(env!("CARGO_PKG_VERSION")));
+        }
+        let response = match self.call_helper("GetServerInfo", &()) {
+            Ok(response) => response,
+            Err(err) if err.contains("UnknownMethod") => return Ok(ServerInfo::default()),
+            Err(err) => return Err(err),
+        };
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| "runkitd could not describe itself".to_string()));
+        }
+        let data = response
+            .data
+            .ok_or_else(|| "runkitd returned no server info".to_string())?;
+        serde_json::from_value(data)
+            .map_err(|err| format!("Failed to decode runkitd server info: {err}"))
+    }
+
+    /// Forward runkitd's `ServiceChanged` signals to the returned channel from a
+    /// background thread, along with `ServiceHealthChanged` as a
+    /// `health_changed` change.
+    pub fn subscribe_changes(&self) -> Result<async_channel::Receiver<ServiceChange>, String> {
+        let connection = self.connection()?.clone();
+        let (sender, receiver) = async_channel::unbounded();
+        let health_connection = connection.clone();
+        let health_sender = sender.clone();
+        thread::Builder::new()
+            .name("runkit-health".into())
+            .spawn(move || {
+                let signals = Proxy::new(&health_connection, BUS_NAME, OBJECT_PATH, INTERFACE)
+                    .and_then(|proxy| proxy.receive_signal("ServiceHealthChanged"));
+                let Ok(signals) = signals else {
+                    return;
+                };
+                for message in signals {
+                    let Ok((service, _, _)) = message.body::<(String, bool, String)>() else {
+                        continue;
+                    };
+                    let change = ServiceChange {
+                        kind: "health_changed".to_string(),
+                        service,
+                    };
+                    if health_sender.send_blocking(change).is_err() {
+                        break;
+                    }
+                }
+            })
+            .map_err(|err| format!("Failed to start health listener: {err}"))?;
+        thread::Builder::new()
+            .name("runkit-changes".into())
+            .spawn(move || {
+                let signals = Proxy::new(&connection, BUS_NAME, OBJECT_PATH, INTERFACE)
+                    .and_then(|proxy| proxy.receive_signal("ServiceChanged"));
+                let signals = match signals {
+                    Ok(signals) => signals,
+                    Err(err) => {
+                        eprintln!("runkit: failed to subscribe to runkitd changes: {err}");
+                        return;
+                    }
+                };
+                for message in signals {
+                    let Ok((kind, service)) = message.body::<(String, String)>() else {
+                        continue;
+                    };
+                    if sender
+                        .send_blocking(ServiceChange { kind, service })
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            })
+            .map_err(|err| format!("Failed to start change listener: {err}"))?;
+        Ok(receiver)
+    }
+
+    /// Forward runkitd's `ServiceAlert` signals to the main loop.
+    pub fn subscribe_alerts(&self) -> Result<async_channel::Receiver<ServiceAlert>, String> {
+        let connection = self.connection()?.clone();
+        let (sender, receiver) = async_channel::unbounded();
+        thread::Builder::new()
+            .name("runkit-alerts".into())
+            .spawn(move || {
+                let signals = Proxy::new(&connection, BUS_NAME, OBJECT_PATH, INTERFACE)
+                    .and_then(|proxy| proxy.receive_signal("ServiceAlert"));
+                let signals = match signals {
+                    Ok(signals) => signals,
+                    Err(err) => {
+                        eprintln!("runkit: failed to subscribe to runkitd alerts: {err}");
+                        return;
+                    }
+                };
+                for message in signals {
+                    let Ok((severity, service, message)) =
+                        message.body::<(String, String, String)>()
+                    else {
+                        continue;
+                    };
+                    let alert = ServiceAlert {
+                        severity,
+                        service,
+                        message,
+                    };
+                    if sender.send_blocking(alert).is_err() {
+                        break;
+                    }
+                }
+            })
+            .map_err(|err| format!("Failed to start alert listener: {err}"))?;
+        Ok(receiver)
+    }
+
+    /// Watch the user's own service directories and forward their changes,
+    /// since runkitd does not announce them.
+    pub fn subscribe_user_changes(&self) -> Result<async_channel::Receiver<ServiceChange>, String> {
+        let manager = self
+            .user_manager
+            .as_ref()
+            .ok_or_else(|| "Cannot find your home directory".to_string())?;
+        let mut watcher = manager.watch().map_err(|err| err.to_string())?;
+        let (sender, receiver) = async_channel::unbounded();
+        thread::Builder::new()
+            .name("runkit-user-changes".into())
+            .spawn(move || {
+                loop {
+                    let events = match watcher.read_events() {
+                        Ok(events) => events,
+                        Err(err) => {
+                            eprintln!("runkit: stopped watching user services: {err}");
+                            return;
+                        }
+                    };
+                    for event in events {
+                        let change = ServiceChange {
+                            kind: event.kind().to_string(),
+                            service: event.service().to_string(),
+                        };
+                        if sender.send_blocking(change).is_err() {
+                            return;
+                        }
+                    }
+                }
+            })
+            .map_err(|err| format!("Failed to start change listener: {err}"))?;
+        Ok(receiver)
+    }
+
+    pub fn run(&self, action: &str, service: &str) -> Result<String, String> {
+        if let Some(manager) = self.direct() {
+            return run_directly(manager, action, service);
+        }
+        let response = self.call_helper("PerformAction", &(action, service))?;
+        match response.status.as_str() {
+            "ok" => Ok(response
+                .message
+                .unwrap_or_else(|| format!("{action} command completed for {service}"))),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd reported failure for {service}"))),
+        }
+    }
+
+    /// Start, stop or restart `service` and wait up to `timeout` for it to be
+    /// running or down, so a service that crashes right away is reported as a
+    /// failure rather than a success. `enable-and-start` and `stop-and-disable`
+    /// also change whether it is enabled, and undo everything on failure.
+    pub fn run_and_wait(
+        &self,
+        action: &str,
+        service: &str,
+        timeout: Duration,
+    ) -> Result<String, String> {
+        if let Some(manager) = self.direct() {
+            let (change, outcome, undone) = match action {
+                "enable-and-start" => (
+                    StateChange::Start,
+                    manager.enable_and_start(service, timeout),
+                    true,
+                ),
+                "stop-and-disable" => (
+                    StateChange::Stop,
+                    manager.stop_and_disable(service, timeout),
+                    true,
+                ),
+                _ => {
+                    let change = match action {
+                        "start" => StateChange::Start,
+                        "stop" => StateChange::Stop,
+                        "restart" => StateChange::Restart,
+                        other => return Err(format!("Cannot wait for action {other}")),
+                    };
+                    let outcome = manager
+                        .change_and_wait(service, change, timeout)
+                        .map(|(_, outcome)| outcome);
+                    (change, outcome, false)
+                }
+            };
+            let outcome = outcome.map_err(|err| err.to_string())?;
+            let state = outcome.state.state_name();
+            return if outcome.reached {
+                Ok(format!("{service} is {state}"))
+            } else {
+                Err(format!(
+                    "{service} was not {} after {}{}; it is {state}",
+                    change.target_name(),
+                    format_duration(outcome.waited),
+                    if undone {
+                        ", so the change was undone"
+                    } else {
+                        ""
+                    }
+                ))
+            };
+        }
+        let timeout_secs = timeout.as_secs().min(u32::MAX.into()) as u32;
+        let response = match action {
+            "enable-and-start" => self.call_helper("EnableAndStart", &(service, timeout_secs))?,
+            "stop-and-disable" => self.call_helper("StopAndDisable", &(service, timeout_secs))?,
+            _ => self.call_helper("PerformActionAndWait", &(action, service, timeout_secs))?,
+        };
+        match response.status.as_str() {
+            "ok" => Ok(response
+                .message
+                .unwrap_or_else(|| format!("{action} command completed for {service}"))),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd reported failure for {service}"))),
+        }
+    }
+
+    /// [`ActionDispatcher::run_and_wait`] for a start, stop or restart, as a
+    /// runkitd job: `updates` gets its id once it is accepted and its progress
+    /// after that, and the call returns when the job completes or is
+    /// cancelled. User services are changed in-process as before.
+    pub fn run_as_job(
+        &self,
+        action: &str,
+        service: &str,
+        timeout: Duration,
+        updates: &async_channel::Sender<JobUpdate>,
+    ) -> Result<String, String> {
+        if self.direct().is_some() {
+            return self.run_and_wait(action, service, timeout);
+        }
+        let timeout_secs = timeout.as_secs().min(u32::MAX.into()) as u32;
+        let response = self
+            .run_job("StartActionJob", &(action, service, timeout_secs), updates)
+            .map_err(|err| {
+                err.unwrap_or_else(|| format!("runkitd could not start {action} for {service}"))
+            })?;
+        match response.status.as_str() {
+            "ok" => Ok(response
+                .message
+                .unwrap_or_else(|| format!("{action} command completed for {service}"))),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd reported failure for {service}"))),
+        }
+    }
+
+    /// Start, stop, restart, enable or disable each of `services` under one
+    /// authorization, as a runkitd job reporting through `updates` like
+    /// [`ActionDispatcher::run_as_job`]. A service that fails does not stop
+    /// the others; the outcome lists each failure.
+    pub fn run_batch(
+        &self,
+        action: &str,
+        services: &[String],
+        updates: &async_channel::Sender<JobUpdate>,
+    ) -> Result<BatchOutcome, String> {
+        if let Some(manager) = self.direct() {
+            let mut failures = Vec::new();
+            for (index, service) in services.iter().enumerate() {
+                let _ = updates.send_blocking(JobUpdate::Progress {
+                    done: index as u32,
+                    total: services.len() as u32,
+                    message: format!("Running {action} on {service}"),
+                });
+                if let Err(err) = run_directly(manager, action, service) {
+                    failures.push((service.clone(), err));
+                }
+            }
+            return Ok(BatchOutcome {
+                message: format!(
+                    "Ran {action} on {} of {} services",
+                    services.len() - failures.len(),
+                    services.len()
+                ),
+                failures,
+            });
+        }
+        // The user already confirmed the batch, however large.
+        let response = self
+            .run_job("StartBatchJob", &(action, services, true), updates)
+            .map_err(|err| {
+                err.unwrap_or_else(|| format!("runkitd could not start the {action} batch"))
+            })?;
+        if response.status != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd could not {action} the services")));
+        }
+        let results: Vec<BatchResult> = response
+            .data
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(|err| format!("Failed to decode batch results: {err}"))?
+            .unwrap_or_default();
+        Ok(BatchOutcome {
+            message: response
+                .message
+                .unwrap_or_else(|| format!("Ran {action} on {} services", services.len())),
+            failures: results
+                .into_iter()
+                .filter(|result| !result.ok)
+                .map(|result| (result.service, result.message.unwrap_or_default()))
+                .collect(),
+        })
+    }
+
+    /// Call `method`, which starts a runkitd job, and wait for it: `updates`
+    /// gets the job's id and progress, and the response is the one the job
+    /// completed with. The error is `None` when runkitd refused to start the
+    /// job without saying why.
+    fn run_job<T>(
+        &self,
+        method: &str,
+        body: &T,
+        updates: &async_channel::Sender<JobUpdate>,
+    ) -> Result<DaemonProcessResponse, Option<String>>
+    where
+        T: serde::ser::Serialize + Type,
+    {
+        // Listen before starting so a quick job's signals are not missed.
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .interface(INTERFACE)
+            .and_then(|rule| rule.path(OBJECT_PATH))
+            .map_err(|err| err.to_string())?
+            .build();
+        let signals = MessageIterator::for_match_rule(rule, self.connection()?, None)
+            .map_err(|err| format!("Failed to listen for runkitd jobs: {err}"))?;
+
+        let response = self.call_helper(method, body)?;
+        if response.status.as_str() != "ok" {
+            return Err(response.message);
+        }
+        let job = response
+            .data
+            .as_ref()
+            .and_then(|data| data["job"].as_u64())
+            .ok_or_else(|| "runkitd returned no job id".to_string())?;
+        let _ = updates.send_blocking(JobUpdate::Started(job));
+
+        for message in signals.flatten() {
+            let member = message.member();
+            match member.as_ref().map(|member| member.as_str()) {
+                Some("JobProgress") => {
+                    if let Ok((id, done, total, message)) =
+                        message.body::<(u64, u32, u32, String)>()
+                        && id == job
+                    {
+                        let _ = updates.send_blocking(JobUpdate::Progress {
+                            done,
+                            total,
+                            message,
+                        });
+                    }
+                }
+                Some("JobCompleted") => {
+                    let Ok((id, reply)) = message.body::<(u64, String)>() else {
+                        continue;
+                    };
+                    if id != job {
+                        continue;
+                    }
+                    return serde_json::from_str(&reply).map_err(|err| {
+                        Some(format!("Failed to decode runkitd job result: {err}"))
+                    });
+                }
+                _ => {}
+            }
+        }
+        Err(Some(format!("Lost runkitd while waiting for job {job}")))
+    }
+
+    /// Cancel a job started with [`ActionDispatcher::run_as_job`]. What it
+    /// already did to the service is not undone.
+    pub fn cancel_job(&self, job: u64) -> Result<(), String> {
+        let response = self.call_helper("CancelJob", &(job,))?;
+        match response.status.as_str() {
+            "ok" => Ok(()),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd could not cancel job {job}"))),
+        }
+    }
+
+    /// Start `service` after what it depends on, or stop it after what depends
+    /// on it, waiting up to `timeout` for each service in turn.
+    pub fn change_with_dependencies(
+        &self,
+        service: &str,
+        change: StateChange,
+        timeout: Duration,
+    ) -> Result<String, String> {
+        if let Some(manager) = self.direct() {
+            let outcomes = match change {
+                StateChange::Stop => manager.stop_with_dependents(service, timeout),
+                _ => manager.start_with_dependencies(service, timeout),
+            }
+            .map_err(|err| err.to_string())?;
+            if let Some((name, outcome)) = outcomes.last()
+                && !outcome.reached
+            {
+                return Err(format!(
+                    "{name} was not {} after {}; it is {}",
+                    change.target_name(),
+                    format_duration(outcome.waited),
+                    outcome.state.state_name()
+                ));
+            }
+            let changed: Vec<&str> = outcomes.iter().map(|(name, _)| name.as_str()).collect();
+            return Ok(match (changed.is_empty(), change) {
+                (true, _) => format!(
+                    "Nothing to do; every service was already {}",
+                    change.target_name()
+                ),
+                (false, StateChange::Stop) => format!("Stopped {}", changed.join(", ")),
+                (false, _) => format!("Started {}", changed.join(", ")),
+            });
+        }
+        let method = match change {
+            StateChange::Stop => "StopWithDependents",
+            _ => "StartWithDependencies",
+        };
+        let timeout_secs = timeout.as_secs().min(u32::MAX.into()) as u32;
+        let response = self.call_helper(method, &(service, timeout_secs))?;
+        match response.status.as_str() {
+            "ok" => Ok(response
+                .message
+                .unwrap_or_else(|| format!("{method} completed for {service}"))),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd reported failure for {service}"))),
+        }
+    }
+
+    /// Put `service` into maintenance with an optional `reason`, or end it.
+    pub fn set_maintenance(
+        &self,
+        service: &str,
+        enabled: bool,
+        reason: Option<&str>,
+    ) -> Result<String, String> {
+        if let Some(manager) = self.direct() {
+            return if enabled {
+                manager
+                    .begin_maintenance(service, reason)
+                    .map(|_| format!("{service} is in maintenance"))
+                    .map_err(|err| err.to_string())
+            } else {
+                manager
+                    .end_maintenance(service)
+                    .map(|_| format!("{service} is out of maintenance"))
+                    .map_err(|err| err.to_string())
+            };
+        }
+        let response = self.call_helper(
+            "SetMaintenanceMode",
+            &(service, enabled, reason.unwrap_or_default()),
+        )?;
+        match response.status.as_str() {
+            "ok" => Ok(response
+                .message
+                .unwrap_or_else(|| format!("Updated maintenance for {service}"))),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to update maintenance for {service}"))),
+        }
+    }
+
+    pub fn export_definition(&self, service: &str, destination: &File) -> Result<String, String> {
+        let response = self.call_helper("ExportDefinition", &(service, Fd::from(destination)))?;
+        match response.status.as_str() {
+            "ok" => Ok(response
+                .message
+                .unwrap_or_else(|| format!("Exported service {service}"))),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to export {service}"))),
+        }
+    }
+
+    pub fn import_definition(&self, source: &File, replace: bool) -> Result<String, String> {
+        let response = self.call_helper("ImportDefinition", &(Fd::from(source), replace))?;
+        match response.status.as_str() {
+            "ok" => Ok(response
+                .message
+                .unwrap_or_else(|| "Imported service definition".to_string())),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| "runkitd failed to import the archive".to_string())),
+        }
+    }
+
+    /// Remove duplicate enable links for `service`, keeping the current runlevel's.
+    pub fn keep_only_in_current_runlevel(&self, service: &str) -> Result<String, String> {
+        let response = self.call_helper("KeepOnlyInRunlevel", &(service, ""))?;
+        match response.status.as_str() {
+            "ok" => Ok(response
+                .message
+                .unwrap_or_else(|| format!("Removed duplicate links for {service}"))),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to clean up links for {service}"))),
+        }
+    }
+
+    pub fn list_runlevels(&self) -> Result<Vec<Runlevel>, String> {
+        let response = self.call_helper("ListRunlevels", &())?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| "runkitd failed to list runlevels".to_string()));
+        }
+
+        let data = response
+            .data
+            .ok_or_else(|| "runkitd returned no runlevel data".to_string())?;
+        let listing: RunlevelListing = serde_json::from_value(data)
+            .map_err(|err| format!("Failed to decode runkitd runlevels: {err}"))?;
+        Ok(listing.runlevels)
+    }
+
+    pub fn switch_runlevel(&self, runlevel: &str) -> Result<String, String> {
+        let response = self.call_helper("SwitchRunlevel", &(runlevel,))?;
+        match response.status.as_str() {
+            "ok" => Ok(response
+                .message
+                .unwrap_or_else(|| format!("Switched to runlevel {runlevel}"))),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to switch to {runlevel}"))),
+        }
+    }
+
+    /// Link `service` into `runlevel`, or remove its links there.
+    pub fn set_enabled_in_runlevel(
+        &self,
+        service: &str,
+        runlevel: &str,
+        enabled: bool,
+    ) -> Result<String, String> {
+        let response = self.call_helper("SetEnabledInRunlevel", &(service, runlevel, enabled))?;
+        match response.status.as_str() {
+            "ok" => Ok(response
+                .message
+                .unwrap_or_else(|| format!("Updated {service} in runlevel {runlevel}"))),
+            _ => Err(response.message.unwrap_or_else(|| {
+                format!("runkitd failed to update {service} in runlevel {runlevel}")
+            })),
+        }
+    }
+
+    /// Resource usage of the service's process tree; `None` when it is not running.
+    pub fn fetch_metrics(&self, service: &str) -> Result<Option<ResourceUsage>, String> {
+        let response = self.call_helper("GetServiceMetrics", &(service,))?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to sample {service}")));
+        }
+
+        response
+            .data
+            .map(|data| {
+                serde_json::from_value(data)
+                    .map_err(|err| format!("Failed to decode runkitd metrics: {err}"))
+            })
+            .transpose()
+    }
+
+    /// Resident memory of every running service, keyed by name.
+    pub fn fetch_memory_usage(&self) -> Result<HashMap<String, u64>, String> {
+        if let Some(manager) = self.direct() {
+            return manager
+                .memory_usage()
+                .map(|usage| usage.into_iter().collect())
+                .map_err(|err| err.to_string());
+        }
+        let response = self.call_helper("GetMemoryUsage", &())?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| "runkitd failed to read memory usage".to_string()));
+        }
+
+        let data = response
+            .data
+            .ok_or_else(|| "runkitd returned no memory usage".to_string())?;
+        serde_json::from_value(data)
+            .map_err(|err| format!("Failed to decode runkitd memory usage: {err}"))
+    }
+
+    /// The service's processes; `None` when it is not running.
+    pub fn fetch_process_tree(&self, service: &str) -> Result<Option<ProcessInfo>, String> {
+        let response = self.call_helper("GetProcessTree", &(service,))?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to list processes of {service}")));
+        }
+
+        response
+            .data
+            .map(|data| {
+                serde_json::from_value(data)
+                    .map_err(|err| format!("Failed to decode runkitd process tree: {err}"))
+            })
+            .transpose()
+    }
+
+    pub fn inspect_run_script(&self, service: &str) -> Result<RunScript, String> {
+        if let Some(manager) = self.direct() {
+            return manager
+                .run_script_info(service)
+                .map(RunScript::from)
+                .map_err(|err| err.to_string());
+        }
+        let response = self.call_helper("InspectRunScript", &(service,))?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to read the run script of {service}")));
+        }
+
+        let data = response
+            .data
+            .ok_or_else(|| "runkitd returned no run script details".to_string())?;
+        serde_json::from_value(data)
+            .map_err(|err| format!("Failed to decode runkitd run script details: {err}"))
+    }
+
+    /// Crashes and restarts runkitd recorded for `service` in the last hour.
+    pub fn fetch_service_history(&self, service: &str) -> Result<CrashHistory, String> {
+        if let Some(manager) = self.direct() {
+            return manager
+                .service_history(service, HISTORY_WINDOW)
+                .map(CrashHistory::from)
+                .map_err(|err| err.to_string());
+        }
+        let response = self.call_helper("GetServiceHistory", &(service,))?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to read the history of {service}")));
+        }
+
+        let data = response
+            .data
+            .ok_or_else(|| "runkitd returned no service history".to_string())?;
+        serde_json::from_value(data)
+            .map_err(|err| format!("Failed to decode runkitd service history: {err}"))
+    }
+
+    /// The xbps package, with its version, that installed the definition.
+    pub fn fetch_service_package(&self, service: &str) -> Result<Option<String>, String> {
+        if let Some(manager) = self.direct() {
+            return manager
+                .service_package(service)
+                .map_err(|err| err.to_string());
+        }
+        let response = self.call_helper("GetServicePackage", &(service,))?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to look up the package of {service}")));
+        }
+        Ok(response
+            .data
+            .as_ref()
+            .and_then(|data| data["package"].as_str())
+            .map(str::to_string))
+    }
+
+    /// Which system services are enabled and have a `down` file, rendered by
+    /// runkitd as `format` (json or toml).
+    pub fn fetch_profile(&self, format: &str) -> Result<String, String> {
+        let response = self.call_helper("GetProfile", &(format,))?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| "runkitd failed to read the profile".to_string()));
+        }
+        response
+            .data
+            .as_ref()
+            .and_then(|data| data["contents"].as_str())
+            .map(str::to_string)
+            .ok_or_else(|| "runkitd returned no profile".to_string())
+    }
+
+    /// Apply a saved profile, or with `dry_run` describe what that would change.
+    pub fn restore_profile(&self, contents: &str, dry_run: bool) -> Result<String, String> {
+        let response = self.call_helper("RestoreProfile", &(contents, dry_run))?;
+        match response.status.as_str() {
+            "ok" => Ok(response
+                .message
+                .unwrap_or_else(|| "Restored the profile".to_string())),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| "runkitd failed to restore the profile".to_string())),
+        }
+    }
+
+    pub fn fetch_env_dir(&self, service: &str) -> Result<EnvDirView, String> {
+        if let Some(manager) = self.direct() {
+            return manager
+                .env_dir(service)
+                .map(EnvDirView::from)
+                .map_err(|err| err.to_string());
+        }
+        let response = self.call_helper("GetEnvDir", &(service,))?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to read the env dir of {service}")));
+        }
+
+        let data = response
+            .data
+            .ok_or_else(|| "runkitd returned no env dir".to_string())?;
+        serde_json::from_value(data)
+            .map_err(|err| format!("Failed to decode runkitd env dir: {err}"))
+    }
+
+    /// Set `name` in the service's env dir to `value`, or with `None` remove it.
+    pub fn set_env_variable(
+        &self,
+        service: &str,
+        name: &str,
+        value: Option<&str>,
+    ) -> Result<String, String> {
+        let fallback = match value {
+            Some(_) => format!("Set {name} for {service}"),
+            None => format!("Removed {name} from {service}"),
+        };
+        if let Some(manager) = self.direct() {
+            let result = match value {
+                Some(value) => manager.set_env_variable(service, name, value),
+                None => manager.unset_env_variable(service, name),
+            };
+            return result
+                .map(|dir| {
+                    if dir.used_by_run_script {
+                        format!("{fallback}; restart the service to apply it")
+                    } else {
+                        format!(
+                            "{fallback}; note that the run script does not load {}",
+                            dir.path.display()
+                        )
+                    }
+                })
+                .map_err(|err| err.to_string());
+        }
+        let response = match value {
+            Some(value) => self.call_helper("SetEnvVariable", &(service, name, value))?,
+            None => self.call_helper("UnsetEnvVariable", &(service, name))?,
+        };
+        match response.status.as_str() {
+            "ok" => Ok(response.message.unwrap_or(fallback)),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to update {name} for {service}"))),
+        }
+    }
+
+    pub fn fetch_log_config(&self, service: &str) -> Result<LogConfig, String> {
+        let response = self.call_helper("GetLogConfig", &(service,))?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to read log settings for {service}")));
+        }
+
+        let data = response
+            .data
+            .ok_or_else(|| "runkitd returned no log settings".to_string())?;
+        serde_json::from_value(data)
+            .map_err(|err| format!("Failed to decode runkitd log settings: {err}"))
+    }
+
+    pub fn set_log_config(&self, service: &str, config: &LogConfig) -> Result<String, String> {
+        let mut options: HashMap<&str, Variant<'_>> = HashMap::new();
+        if let Some(size) = config.size {
+            options.insert("size", size.into());
+        }
+        if let Some(num) = config.num {
+            options.insert("num", num.into());
+        }
+        if let Some(min) = config.min {
+            options.insert("min", min.into());
+        }
+        if let Some(timeout) = config.timeout {
+            options.insert("timeout", timeout.into());
+        }
+        if let Some(processor) = &config.processor {
+            options.insert("processor", processor.as_str().into());
+        }
+        if let Some(prefix) = &config.prefix {
+            options.insert("prefix", prefix.as_str().into());
+        }
+        options.insert("filters", config.filters.clone().into());
+
+        let response = self.call_helper("SetLogConfig", &(service, options))?;
+        match response.status.as_str() {
+            "ok" => Ok(response
+                .message
+                .unwrap_or_else(|| format!("Updated log settings for {service}"))),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to update log settings for {service}"))),
+        }
+    }
+
+    /// Contents of `file` in the service's definition, e.g. `run` or `conf`.
+    /// `Ok(None)` when the file does not exist yet.
+    pub fn read_service_file(&self, service: &str, file: &str) -> Result<Option<String>, String> {
+        if let Some(manager) = self.direct() {
+            return match manager.read_service_file(service, file) {
+                Ok(read) => Ok(Some(read.contents)),
+                Err(ServiceError::Io { source, .. }) if source.kind() == ErrorKind::NotFound => {
+                    Ok(None)
+                }
+                Err(err) => Err(err.to_string()),
+            };
+        }
+        let response = self.call_helper("ReadServiceFile", &(service, file))?;
+        if response.status.as_str() != "ok" {
+            let message = response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to read {file} of {service}"));
+            // runkitd reports a missing file as an I/O error naming the OS error.
+            if message.contains("No such file or directory") {
+                return Ok(None);
+            }
+            return Err(message);
+        }
+        Ok(response
+            .data
+            .as_ref()
+            .and_then(|data| data["contents"].as_str())
+            .map(str::to_string))
+    }
+
+    /// Save `contents` as `file` of the service's definition. A shell syntax
+    /// error is refused unless `force` is set; other problems come back as
+    /// warnings in the message.
+    pub fn write_service_file(
+        &self,
+        service: &str,
+        file: &str,
+        contents: &str,
+        force: bool,
+    ) -> Result<String, String> {
+        if let Some(manager) = self.direct() {
+            return manager
+                .write_service_file(service, file, contents, force)
+                .map(|written| {
+                    let mut message = format!("Saved {file} of {service}");
+                    for warning in &written.warnings {
+                        message.push_str(&format!("; warning: {warning}"));
+                    }
+                    message
+                })
+                .map_err(|err| err.to_string());
+        }
+        let response = self.call_helper("WriteServiceFile", &(service, file, contents, force))?;
+        match response.status.as_str() {
+            "ok" => Ok(response
+                .message
+                .unwrap_or_else(|| format!("Saved {file} of {service}"))),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to save {file} of {service}"))),
+        }
+    }
+
+    /// Create `service` from the template bundle `bundle`, its JSON text,
+    /// with `values` for the bundle's variables.
+    pub fn create_from_bundle(
+        &self,
+        service: &str,
+        bundle: &str,
+        values: &[(String, String)],
+        enable: bool,
+    ) -> Result<String, String> {
+        if let Some(manager) = self.direct() {
+            return TemplateBundle::parse(bundle)
+                .and_then(|bundle| manager.create_from_bundle(&bundle, service, values, enable))
+                .map(|_| format!("Created {service}"))
+                .map_err(|err| err.to_string());
+        }
+        let response = self.call_helper("CreateFromBundle", &(service, bundle, values, enable))?;
+        match response.status.as_str() {
+            "ok" => Ok(response
+                .message
+                .unwrap_or_else(|| format!("Created {service}"))),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to create {service}"))),
+        }
+    }
+
+    /// Copy `source` to a new service `name`, replacing each `(from, to)` in its scripts.
+    pub fn instantiate_service(
+        &self,
+        source: &str,
+        name: &str,
+        substitutions: &[(String, String)],
+        enable: bool,
+    ) -> Result<String, String> {
+        if let Some(manager) = self.direct() {
+            return manager
+                .instantiate_service(source, name, substitutions, enable)
+                .map(|_| format!("Created {name} from {source}"))
+                .map_err(|err| err.to_string());
+        }
+        let response =
+            self.call_helper("InstantiateService", &(source, name, substitutions, enable))?;
+        match response.status.as_str() {
+            "ok" => Ok(response
+                .message
+                .unwrap_or_else(|| format!("Created {name} from {source}"))),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to create {name} from {source}"))),
+        }
+    }
+
+    /// Stop `service`, unlink it everywhere and move its definition to the
+    /// trash, or with `remove` delete it for good.
+    pub fn delete_service(&self, service: &str, remove: bool) -> Result<String, String> {
+        let fallback = if remove {
+            format!("Deleted {service} for good")
+        } else {
+            format!("Deleted {service}; it can be restored from the trash")
+        };
+        if let Some(manager) = self.direct() {
+            let mode = if remove {
+                DeleteMode::Remove
+            } else {
+                DeleteMode::Trash
+            };
+            return manager
+                .delete_service(service, mode)
+                .map(|_| fallback)
+                .map_err(|err| err.to_string());
+        }
+        let response = self.call_helper("DeleteService", &(service, remove))?;
+        match response.status.as_str() {
+            "ok" => Ok(response.message.unwrap_or(fallback)),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to delete {service}"))),
+        }
+    }
+
+    /// Restart `service` once the last graphical session has ended, or with
+    /// `cancel` drop such a restart.
+    pub fn restart_at_logout(&self, service: &str, cancel: bool) -> Result<String, String> {
+        let response = self.call_helper("RestartAtLogout", &(service, cancel))?;
+        match response.status.as_str() {
+            "ok" => Ok(response
+                .message
+                .unwrap_or_else(|| format!("{service} will restart at logout"))),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd could not schedule {service}"))),
+        }
+    }
+
+    /// Silence runkitd's alerts for `minutes`, or with 0 send them again.
+    pub fn silence_alerts(&self, minutes: u32) -> Result<String, String> {
+        let response = self.call_helper("SilenceAlerts", &(minutes,))?;
+        match response.status.as_str() {
+            "ok" => Ok(response
+                .message
+                .unwrap_or_else(|| "Updated the alert silence".to_string())),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| "runkitd failed to silence alerts".to_string())),
+        }
     }
-}
 
-impl ActionDispatcher {
-    fn proxy(&self) -> Result<Proxy<'_>, String> {
-        Proxy::new(&self.connection, BUS_NAME, OBJECT_PATH, INTERFACE)
-            .map_err(|err| format!("Failed to connect to runkitd: {err}"))
+    /// Unix time runkitd's alert silence ends, or `None` when alerts are sent.
+    pub fn alert_silence(&self) -> Result<Option<u64>, String> {
+        let response = self.call_helper("GetAlertSilence", &())?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| "runkitd failed to report the alert silence".to_string()));
+        }
+        Ok(response
+            .data
+            .as_ref()
+            .and_then(|data| data.get("silenced_until"))
+            .and_then(Value::as_u64))
     }
 
-    fn call_helper<T>(&self, method: &str, body: &T) -> Result<DaemonProcessResponse, String>
-    where
-        T: serde::ser::Serialize + Type,
-    {
-        let proxy = self.proxy()?;
-        let reply: String = proxy
-            .call(method, body)
-            .map_err(|err| format!("runkitd call {method} failed: {err}"))?;
-        serde_json::from_str(&reply)
-            .map_err(|err| format!("Failed to decode runkitd response for {method}: {err}"))
+    pub fn list_trash(&self) -> Result<Vec<TrashedService>, String> {
+        let response = self.call_helper("ListTrash", &())?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| "runkitd failed to list the trash".to_string()));
+        }
+
+        let data = response
+            .data
+            .ok_or_else(|| "runkitd returned no trash data".to_string())?;
+        serde_json::from_value(data)
+            .map_err(|err| format!("Failed to decode runkitd trash response: {err}"))
     }
 
-    pub fn run(
-        &self,
-        action: &str,
-        service: &str,
-        allow_cached_authorization: bool,
-    ) -> Result<String, String> {
-        let response = self.call_helper(
-            "PerformAction",
-            &(action, service, allow_cached_authorization),
-        )?;
+    pub fn restore_service(&self, id: &str) -> Result<String, String> {
+        let response = self.call_helper("RestoreService", &(id,))?;
         match response.status.as_str() {
             "ok" => Ok(response
                 .message
-                .unwrap_or_else(|| format!("{action} command completed for {service}"))),
+                .unwrap_or_else(|| "Restored service definition".to_string())),
             _ => Err(response
                 .message
-                .unwrap_or_else(|| format!("runkitd reported failure for {service}"))),
+                .unwrap_or_else(|| format!("runkitd failed to restore {id}"))),
         }
     }
 
     pub fn fetch_services(&self) -> Result<Vec<ServiceInfo>, String> {
+        if let Some(manager) = self.direct() {
+            return manager.list_services().map_err(|err| err.to_string());
+        }
         let response = self.call_helper::<()>("ListServices", &())?;
         if response.status.as_str() != "ok" {
             return Err(response
@@ -75,12 +1272,110 @@ impl ActionDispatcher {
         let snapshots: Vec<ServiceSnapshot> = serde_json::from_value(data)
             .map_err(|err| format!("Failed to decode runkitd response: {err}"))?;
 
-        Ok(snapshots.into_iter().map(ServiceInfo::from).collect())
+        let local = self.local_descriptions();
+        Ok(snapshots
+            .into_iter()
+            .map(|snapshot| {
+                let mut info = ServiceInfo::from(snapshot);
+                if let Some(text) = local.iter().find_map(|texts| texts.get(&info.name)) {
+                    info.description = Some(text.clone());
+                }
+                info
+            })
+            .collect())
+    }
+
+    /// The user's own descriptions, then the system-wide ones, read here in
+    /// the user's language rather than by runkitd in its own.
+    fn local_descriptions(&self) -> Vec<BTreeMap<String, String>> {
+        [
+            self.user_descriptions.as_deref(),
+            Some(Path::new(DEFAULT_SYSTEM_DESCRIPTIONS)),
+        ]
+        .into_iter()
+        .flatten()
+        .filter_map(|path| read_descriptions(path).ok())
+        .collect()
+    }
+
+    /// The last `lines` log lines of every failed service.
+    pub fn failed_logs(&self, lines: usize) -> Result<Vec<ServiceLogs>, String> {
+        if let Some(manager) = self.direct() {
+            return manager
+                .failed_service_logs(lines)
+                .map_err(|err| err.to_string());
+        }
+        let line_cap = lines.max(1).min(u32::MAX as usize) as u32;
+        let response = self.call_helper("FetchFailedLogs", &(line_cap,))?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| "runkitd failed to collect the logs".to_string()));
+        }
+        let data = response
+            .data
+            .ok_or_else(|| "runkitd returned no log data".to_string())?;
+        let logs: Vec<ServiceLogsSnapshot> = serde_json::from_value(data)
+            .map_err(|err| format!("Failed to decode runkitd logs response: {err}"))?;
+        Ok(logs
+            .into_iter()
+            .map(|logs| ServiceLogs {
+                service: logs.service,
+                entries: logs
+                    .entries
+                    .into_iter()
+                    .map(ServiceLogEntry::from)
+                    .collect(),
+            })
+            .collect())
+    }
+
+    /// Names of runit's stage logs, which [`Self::fetch_logs`] reads like services.
+    pub fn stage_logs(&self) -> Result<Vec<String>, String> {
+        if let Some(manager) = self.direct() {
+            return manager.stage_logs().map_err(|err| err.to_string());
+        }
+        let response = self.call_helper("ListStageLogs", &())?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| "runkitd failed to list the stage logs".to_string()));
+        }
+
+        let data = response
+            .data
+            .ok_or_else(|| "runkitd returned no stage logs".to_string())?;
+        serde_json::from_value(data)
+            .map_err(|err| format!("Failed to decode runkitd stage log response: {err}"))
     }
 
-    pub fn fetch_logs(&self, service: &str, lines: usize) -> Result<Vec<LogEntry>, String> {
+    /// The last `lines` log lines of `service`, or with `min_severity` the last
+    /// `lines` lines at or above it, filtered by runkitd.
+    pub fn fetch_logs(
+        &self,
+        service: &str,
+        lines: usize,
+        min_severity: Option<LogSeverity>,
+    ) -> Result<Vec<LogEntry>, String> {
+        if let Some(manager) = self.direct() {
+            let filter = LogFilter {
+                min_severity,
+                ..LogFilter::default()
+            };
+            return manager
+                .tail_logs_filtered(service, lines, &filter)
+                .map(|entries| entries.into_iter().map(LogEntry::from).collect())
+                .map_err(|err| err.to_string());
+        }
         let line_cap = lines.max(1).min(u32::MAX as usize) as u32;
-        let response = self.call_helper("FetchLogs", &(service, line_cap))?;
+        let response = match min_severity {
+            Some(severity) => {
+                let mut options: HashMap<&str, Variant<'_>> = HashMap::new();
+                options.insert("severity", severity.as_str().into());
+                self.call_helper("FetchLogsFiltered", &(service, line_cap, options))?
+            }
+            None => self.call_helper("FetchLogs", &(service, line_cap))?,
+        };
 
         if response.status.as_str() != "ok" {
             return Err(response
@@ -98,7 +1393,264 @@ impl ActionDispatcher {
         Ok(entries.into_iter().map(LogEntry::from).collect())
     }
 
+    /// Stream what is written to `service`'s log from now on, from runkitd's
+    /// `LogLine` signals or, for the user's own services, by reading the log
+    /// here.
+    pub fn follow_logs(&self, service: &str) -> Result<LogStream, String> {
+        let (sender, receiver) = async_channel::unbounded();
+        let stop = Arc::new(AtomicBool::new(false));
+        if let Some(manager) = self.direct() {
+            let mut follower = manager
+                .follow_logs(service)
+                .map_err(|err| err.to_string())?;
+            let thread_stop = Arc::clone(&stop);
+            thread::Builder::new()
+                .name("runkit-follow".into())
+                .spawn(move || {
+                    while !thread_stop.load(Ordering::Relaxed) && !sender.is_closed() {
+                        match follower.try_next() {
+                            Ok(Some(entry)) => {
+                                let _ = sender.send_blocking(Ok(LogEntry::from(entry)));
+                            }
+                            Ok(None) => thread::sleep(FOLLOW_POLL_INTERVAL),
+                            Err(err) => {
+                                let _ = sender.send_blocking(Err(err.to_string()));
+                                return;
+                            }
+                        }
+                    }
+                })
+                .map_err(|err| format!("Failed to follow {service}: {err}"))?;
+            return Ok(LogStream {
+                receiver,
+                stop,
+                subscription: None,
+            });
+        }
+
+        // Listen before subscribing so the first lines are not missed.
+        let connection = self.connection()?.clone();
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .interface(INTERFACE)
+            .and_then(|rule| rule.path(OBJECT_PATH))
+            .map_err(|err| err.to_string())?
+            .build();
+        let signals = MessageIterator::for_match_rule(rule, &connection, None)
+            .map_err(|err| format!("Failed to listen for {service}'s log: {err}"))?;
+        let response = self.call_helper("FollowLogs", &(service,))?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd could not follow {service}")));
+        }
+        let id = response
+            .data
+            .as_ref()
+            .and_then(|data| data["subscription"].as_u64())
+            .ok_or_else(|| "runkitd returned no subscription".to_string())?;
+
+        let thread_stop = Arc::clone(&stop);
+        thread::Builder::new()
+            .name("runkit-follow".into())
+            .spawn(move || {
+                for message in signals.flatten() {
+                    if thread_stop.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    let member = message.member();
+                    let sent = match member.as_ref().map(|member| member.as_str()) {
+                        Some("LogLine") => match message.body::<(u64, String, String)>() {
+                            Ok((line_id, _, entry)) if line_id == id => {
+                                let entry = serde_json::from_str::<LogEntrySnapshot>(&entry)
+                                    .map(LogEntry::from)
+                                    .map_err(|err| format!("Failed to decode a log line: {err}"));
+                                sender.send_blocking(entry)
+                            }
+                            _ => Ok(()),
+                        },
+                        Some("LogFollowEnded") => match message.body::<(u64, String)>() {
+                            Ok((ended_id, reason)) if ended_id == id => {
+                                let _ = sender.send_blocking(Err(reason));
+                                return;
+                            }
+                            _ => Ok(()),
+                        },
+                        _ => Ok(()),
+                    };
+                    if sent.is_err() {
+                        return;
+                    }
+                }
+            })
+            .map_err(|err| format!("Failed to follow {service}: {err}"))?;
+        Ok(LogStream {
+            receiver,
+            stop,
+            subscription: Some((connection, id)),
+        })
+    }
+
+    /// Whether the user may run `operation`, e.g. `start` or `kill`, on system
+    /// services. The user's own services need no authorization.
+    pub fn check_authorization(&self, operation: &str) -> Result<Authorization, String> {
+        if self.direct().is_some() {
+            return Ok(Authorization::Allowed);
+        }
+        let response = self.call_helper("CheckAuthorization", &(operation, ""))?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| "runkitd could not check authorization".to_string()));
+        }
+        let result = response
+            .data
+            .as_ref()
+            .and_then(|data| data["result"].as_str())
+            .unwrap_or_default();
+        Ok(match result {
+            "denied" => Authorization::Denied,
+            "authentication-required" => Authorization::AuthenticationRequired,
+            _ => Authorization::Allowed,
+        })
+    }
+
+    /// The services runkitd will not stop or disable without an override;
+    /// empty for user services, which are never guarded.
+    pub fn protected_services(&self) -> Result<Vec<String>, String> {
+        if self.direct().is_some() {
+            return Ok(Vec::new());
+        }
+        let response = self.call_helper("ListProtectedServices", &())?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| "runkitd failed to list the protected services".to_string()));
+        }
+        let data = response
+            .data
+            .ok_or_else(|| "runkitd returned no protected services".to_string())?;
+        serde_json::from_value(data)
+            .map_err(|err| format!("Failed to decode runkitd protected services: {err}"))
+    }
+
+    /// Stop or disable a protected service despite runkitd's guard, which
+    /// always asks for a password. Stops wait up to `timeout`.
+    pub fn run_protected(
+        &self,
+        action: &str,
+        service: &str,
+        timeout: Duration,
+    ) -> Result<String, String> {
+        let timeout_secs = timeout.as_secs().min(u32::MAX.into()) as u32;
+        let response =
+            self.call_helper("PerformProtectedAction", &(action, service, timeout_secs))?;
+        match response.status.as_str() {
+            "ok" => Ok(response
+                .message
+                .unwrap_or_else(|| format!("{action} command completed for {service}"))),
+            _ => Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd reported failure for {service}"))),
+        }
+    }
+
+    /// The container or chroot, e.g. "a docker container", when runkitd
+    /// runs where no runsvdir supervises services; `None` normally.
+    pub fn reduced_mode(&self) -> Result<Option<String>, String> {
+        if let Some(manager) = self.direct() {
+            return Ok(manager.reduced_mode().map(ToString::to_string));
+        }
+        let response = self.call_helper("GetSupervision", &())?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| "runkitd could not report supervision".to_string()));
+        }
+        let confinement = response
+            .data
+            .and_then(|data| {
+                serde_json::from_value::<Option<Confinement>>(data["confinement"].clone()).ok()
+            })
+            .flatten();
+        Ok(confinement.map(|confinement| confinement.to_string()))
+    }
+
+    /// Unix time until which runkitd reuses this user's authorizations, or
+    /// `None` while it asks for the password every time.
+    pub fn cached_authorization(&self) -> Result<Option<u64>, String> {
+        let response = self.call_helper("GetCachedAuthorization", &())?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| "runkitd could not report cached authorization".to_string()));
+        }
+        Ok(response
+            .data
+            .as_ref()
+            .and_then(|data| data["until"].as_u64()))
+    }
+
+    /// Have runkitd reuse this user's authorizations for its default period,
+    /// which asks for the password once, or stop. Returns runkitd's message.
+    pub fn set_cached_authorization(&self, enabled: bool) -> Result<String, String> {
+        let response = self.call_helper("SetCachedAuthorization", &(enabled, 0u64))?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| "runkitd could not change cached authorization".to_string()));
+        }
+        Ok(response.message.unwrap_or_default())
+    }
+
+    pub fn check_health(&self) -> Result<Vec<HealthIssue>, String> {
+        if let Some(manager) = self.direct() {
+            return manager
+                .check_health()
+                .map(|health| health.issues)
+                .map_err(|err| err.to_string());
+        }
+        let response = self.call_helper("CheckHealth", &())?;
+
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| "runkitd failed to check service health".to_string()));
+        }
+
+        let data = response
+            .data
+            .ok_or_else(|| "runkitd returned no health data".to_string())?;
+
+        let issues: Vec<HealthIssueSnapshot> = serde_json::from_value(data)
+            .map_err(|err| format!("Failed to decode runkitd health response: {err}"))?;
+
+        Ok(issues
+            .into_iter()
+            .filter_map(|issue| {
+                Some(HealthIssue {
+                    kind: HealthIssueKind::parse(&issue.kind)?,
+                    service: issue.service,
+                    path: issue.path.into(),
+                    detail: issue.detail,
+                })
+            })
+            .collect())
+    }
+
     pub fn fetch_description(&self, service: &str) -> Result<Option<String>, String> {
+        if let Some(manager) = self.direct() {
+            return manager
+                .service_description(service)
+                .map_err(|err| err.to_string());
+        }
+        if let Some(description) = self
+            .local_descriptions()
+            .into_iter()
+            .find_map(|mut texts| texts.remove(service))
+        {
+            return Ok(Some(description));
+        }
         let response = self.call_helper("FetchDescription", &(service,))?;
 
         if response.status.as_str() != "ok" {
@@ -116,15 +1668,126 @@ impl ActionDispatcher {
 
         Ok(snapshot.description)
     }
+
+    /// Describe `service` for this user only, or with `None` go back to the
+    /// shared description. Written straight to the user's own file.
+    pub fn set_user_description(&self, service: &str, text: Option<&str>) -> Result<(), String> {
+        if let Some(manager) = self.direct() {
+            return manager
+                .set_user_description(service, text)
+                .map_err(|err| err.to_string());
+        }
+        let path = self
+            .user_descriptions
+            .as_ref()
+            .ok_or_else(|| "Cannot find your home directory to save descriptions".to_string())?;
+        write_description(path, service, text).map_err(|err| err.to_string())
+    }
+}
+
+/// Read a template bundle from a file, or download it with curl when `source`
+/// is an http(s) URL. Returns the bundle's text, which is what runkitd is
+/// given, and the bundle read from it.
+pub fn load_template_bundle(source: &str) -> Result<(String, TemplateBundle), String> {
+    let text = if source.starts_with("https://") || source.starts_with("http://") {
+        let output = Command::new("curl")
+            .args(["--fail", "--silent", "--show-error", "--location"])
+            .args(["--max-time", "30", "--max-filesize", "1048576", "--"])
+            .arg(source)
+            .output()
+            .map_err(|err| format!("Unable to run curl: {err}"))?;
+        if !output.status.success() {
+            return Err(format!(
+                "Downloading {source} failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        String::from_utf8(output.stdout).map_err(|_| format!("{source} is not text"))?
+    } else {
+        std::fs::read_to_string(source).map_err(|err| format!("Unable to open {source}: {err}"))?
+    };
+    let bundle = TemplateBundle::parse(&text).map_err(|err| err.to_string())?;
+    Ok((text, bundle))
+}
+
+/// Perform a `PerformAction` action in-process, as runkitd would.
+fn run_directly(manager: &ServiceManager, action: &str, service: &str) -> Result<String, String> {
+    let sv_message = |command: &str, output: String| {
+        if output.is_empty() {
+            format!("{command} command executed for {service}")
+        } else {
+            output
+        }
+    };
+    let result = match action {
+        "start" => manager
+            .start(service)
+            .map(|output| sv_message(action, output)),
+        "restart" => {
+            ServiceBackend::restart(manager, service).map(|output| sv_message(action, output))
+        }
+        "stop" => manager
+            .control(service, "down")
+            .map(|output| sv_message("down", output)),
+        "reload" | "check" | "once" | "hup" | "alarm" | "interrupt" | "quit" | "term" | "kill"
+        | "pause" | "cont" | "force-stop" | "force-reload" | "force-restart" => manager
+            .control(service, action)
+            .map(|output| sv_message(action, output)),
+        "enable" => manager
+            .enable(service)
+            .map(|()| format!("Enabled service {service}")),
+        "disable" => manager
+            .disable(service)
+            .map(|()| format!("Disabled service {service}")),
+        "no-restart" => manager
+            .apply_restart_policy(service, RestartPolicy::Never)
+            .map(|()| format!("{service} will stay down after it exits")),
+        "auto-restart" => manager
+            .apply_restart_policy(service, RestartPolicy::Always)
+            .map(|()| format!("{service} will be restarted when it exits")),
+        "set-down-file" => manager
+            .set_down_file(service)
+            .map(|()| format!("{service} will not be started at boot")),
+        "clear-down-file" => manager
+            .clear_down_file(service)
+            .map(|()| format!("{service} will be started at boot")),
+        other => return Err(format!("Unknown action {other}")),
+    };
+    result.map_err(|err| err.to_string())
+}
+
+/// A runsvdir runlevel and the services linked into it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Runlevel {
+    pub name: String,
+    pub current: bool,
+    #[serde(default)]
+    pub services: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RunlevelListing {
+    runlevels: Vec<Runlevel>,
 }
 
 #[derive(Debug, Deserialize)]
 struct DaemonProcessResponse {
+    /// Missing from runkitd versions before it was introduced.
+    #[serde(default)]
+    schema_version: u32,
     status: String,
     message: Option<String>,
     data: Option<Value>,
 }
 
+/// One service's entry in a batch's results.
+#[derive(Debug, Deserialize)]
+struct BatchResult {
+    service: String,
+    ok: bool,
+    message: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct ServiceSnapshot {
     name: String,
@@ -132,7 +1795,27 @@ struct ServiceSnapshot {
     enabled: bool,
     desired_state: SnapshotDesiredState,
     runtime_state: SnapshotRuntimeState,
+    #[serde(default)]
+    restart_policy: SnapshotRestartPolicy,
+    #[serde(default)]
+    has_down_file: bool,
+    #[serde(default)]
+    defunct_pids: Vec<u32>,
+    #[serde(default)]
+    waiting_for: Vec<String>,
+    #[serde(default)]
+    io: Option<SnapshotIoUsage>,
     description: Option<String>,
+    #[serde(default)]
+    runlevels: Vec<String>,
+    #[serde(default)]
+    maintenance: Option<MaintenanceSnapshot>,
+    #[serde(default)]
+    supervisor_diagnostic: Option<SupervisorDiagnosticSnapshot>,
+    #[serde(default)]
+    check_interval_seconds: Option<u64>,
+    #[serde(default)]
+    last_check: Option<CheckSnapshot>,
 }
 
 impl From<ServiceSnapshot> for ServiceInfo {
@@ -143,7 +1826,70 @@ impl From<ServiceSnapshot> for ServiceInfo {
             enabled: snapshot.enabled,
             desired_state: DesiredState::from(snapshot.desired_state),
             runtime_state: ServiceRuntimeState::from(snapshot.runtime_state),
+            restart_policy: RestartPolicy::from(snapshot.restart_policy),
+            has_down_file: snapshot.has_down_file,
+            defunct_pids: snapshot.defunct_pids,
+            waiting_for: snapshot.waiting_for,
+            io: snapshot.io.map(IoUsage::from),
             description: snapshot.description,
+            runlevels: snapshot.runlevels,
+            maintenance: snapshot.maintenance.map(Maintenance::from),
+            supervisor_diagnostic: snapshot
+                .supervisor_diagnostic
+                .map(SupervisorDiagnostic::from),
+            check_interval: snapshot.check_interval_seconds.map(Duration::from_secs),
+            last_check: snapshot.last_check.map(CheckResult::from),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CheckSnapshot {
+    at: i64,
+    passed: bool,
+    detail: String,
+}
+
+impl From<CheckSnapshot> for CheckResult {
+    fn from(value: CheckSnapshot) -> Self {
+        CheckResult {
+            at: value.at,
+            passed: value.passed,
+            detail: value.detail,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MaintenanceSnapshot {
+    since: i64,
+    reason: Option<String>,
+    was_running: bool,
+    added_down_file: bool,
+}
+
+impl From<MaintenanceSnapshot> for Maintenance {
+    fn from(value: MaintenanceSnapshot) -> Self {
+        Maintenance {
+            since: value.since,
+            reason: value.reason,
+            was_running: value.was_running,
+            added_down_file: value.added_down_file,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SupervisorDiagnosticSnapshot {
+    message: String,
+    fatal: bool,
+}
+
+impl From<SupervisorDiagnosticSnapshot> for SupervisorDiagnostic {
+    fn from(value: SupervisorDiagnosticSnapshot) -> Self {
+        SupervisorDiagnostic {
+            message: value.message,
+            fatal: value.fatal,
         }
     }
 }
@@ -164,6 +1910,60 @@ impl From<SnapshotDesiredState> for DesiredState {
     }
 }
 
+#[derive(Debug, Deserialize)]
+struct HealthIssueSnapshot {
+    service: String,
+    kind: String,
+    path: String,
+    detail: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SnapshotRestartPolicy {
+    #[default]
+    Always,
+    Never,
+}
+
+impl From<SnapshotRestartPolicy> for RestartPolicy {
+    fn from(value: SnapshotRestartPolicy) -> Self {
+        match value {
+            SnapshotRestartPolicy::Always => RestartPolicy::Always,
+            SnapshotRestartPolicy::Never => RestartPolicy::Never,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SnapshotIoUsage {
+    read_bytes: u64,
+    write_bytes: u64,
+    #[serde(default)]
+    source: SnapshotIoSource,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SnapshotIoSource {
+    #[default]
+    Processes,
+    Cgroup,
+}
+
+impl From<SnapshotIoUsage> for IoUsage {
+    fn from(value: SnapshotIoUsage) -> Self {
+        IoUsage {
+            read_bytes: value.read_bytes,
+            write_bytes: value.write_bytes,
+            source: match value.source {
+                SnapshotIoSource::Processes => IoSource::Processes,
+                SnapshotIoSource::Cgroup => IoSource::Cgroup,
+            },
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(tag = "state", rename_all = "snake_case")]
 enum SnapshotRuntimeState {
@@ -224,6 +2024,34 @@ struct LogEntrySnapshot {
     message: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct ServiceLogsSnapshot {
+    service: String,
+    entries: Vec<LogEntrySnapshot>,
+}
+
+impl From<LogEntrySnapshot> for ServiceLogEntry {
+    fn from(snapshot: LogEntrySnapshot) -> Self {
+        ServiceLogEntry {
+            timestamp_unix: snapshot.unix_seconds,
+            timestamp_nanos: snapshot.nanos,
+            timestamp_raw: snapshot.raw,
+            message: snapshot.message,
+        }
+    }
+}
+
+impl From<ServiceLogEntry> for LogEntry {
+    fn from(entry: ServiceLogEntry) -> Self {
+        LogEntry {
+            unix_seconds: entry.timestamp_unix,
+            nanos: entry.timestamp_nanos,
+            raw: entry.timestamp_raw,
+            message: entry.message,
+        }
+    }
+}
+
 impl From<LogEntrySnapshot> for LogEntry {
     fn from(snapshot: LogEntrySnapshot) -> Self {
         LogEntry {
@@ -235,6 +2063,128 @@ impl From<LogEntrySnapshot> for LogEntry {
     }
 }
 
+/// CPU, memory, descriptor and thread usage of a service and its children.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResourceUsage {
+    pub pid: u32,
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+    /// `None` when runkitd could not list the descriptors.
+    pub open_fds: Option<u64>,
+    pub threads: u64,
+    pub processes: u32,
+}
+
+/// A service's env dir as shown in the environment dialog.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnvDirView {
+    pub path: String,
+    pub used_by_run_script: bool,
+    pub variables: Vec<EnvDirVariable>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnvDirVariable {
+    pub name: String,
+    /// `None` when the file is empty, which unsets the variable.
+    pub value: Option<String>,
+}
+
+impl From<EnvDir> for EnvDirView {
+    fn from(dir: EnvDir) -> Self {
+        EnvDirView {
+            path: dir.path.display().to_string(),
+            used_by_run_script: dir.used_by_run_script,
+            variables: dir
+                .variables
+                .into_iter()
+                .map(|variable| EnvDirVariable {
+                    name: variable.name,
+                    value: variable.value,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CrashHistory {
+    pub crashes: usize,
+    pub restarts: usize,
+    pub last_exit_codes: Vec<i32>,
+    pub flapping: bool,
+}
+
+impl From<ServiceHistory> for CrashHistory {
+    fn from(history: ServiceHistory) -> Self {
+        CrashHistory {
+            crashes: history.crashes,
+            restarts: history.restarts,
+            last_exit_codes: history.last_exit_codes,
+            flapping: history.flapping,
+        }
+    }
+}
+
+/// What a service's run script executes, as parsed by runkitd.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RunScript {
+    pub binary: Option<String>,
+    pub args: Vec<String>,
+    pub user: Option<String>,
+    pub group: Option<String>,
+    pub config_file: Option<String>,
+    pub env_dir: Option<String>,
+}
+
+impl From<RunScriptInfo> for RunScript {
+    fn from(info: RunScriptInfo) -> Self {
+        RunScript {
+            binary: info.binary,
+            args: info.args,
+            user: info.user,
+            group: info.group,
+            config_file: info.config_file.map(|path| path.display().to_string()),
+            env_dir: info.env_dir.map(|path| path.display().to_string()),
+        }
+    }
+}
+
+/// One process of a service and its descendants.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub comm: String,
+    pub cmdline: Vec<String>,
+    pub children: Vec<ProcessInfo>,
+}
+
+/// svlogd settings of one service as reported by runkitd; `None` means
+/// svlogd's default.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    pub size: Option<u64>,
+    pub num: Option<u32>,
+    pub min: Option<u32>,
+    pub timeout: Option<u64>,
+    pub processor: Option<String>,
+    pub prefix: Option<String>,
+    pub filters: Vec<String>,
+}
+
+/// A deleted definition waiting in runkitd's trash.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrashedService {
+    pub id: String,
+    pub service: String,
+    pub deleted_at: i64,
+    #[serde(default)]
+    pub was_enabled: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct LogEntry {
     pub unix_seconds: Option<i64>,
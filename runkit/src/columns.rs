@@ -0,0 +1,74 @@
+//! Optional columns of the service list, shown at the end of each row.
+use crate::formatting::format_bytes;
+use runkit_core::{ServiceInfo, ServiceRuntimeState, format_duration};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ListColumn {
+    Pid,
+    Uptime,
+    User,
+    Memory,
+    Runlevel,
+}
+
+impl ListColumn {
+    /// Every column, in the order they are shown.
+    pub const ALL: [ListColumn; 5] = [
+        ListColumn::Pid,
+        ListColumn::Uptime,
+        ListColumn::User,
+        ListColumn::Memory,
+        ListColumn::Runlevel,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ListColumn::Pid => "PID",
+            ListColumn::Uptime => "Uptime",
+            ListColumn::User => "User",
+            ListColumn::Memory => "Memory",
+            ListColumn::Runlevel => "Runlevel",
+        }
+    }
+
+    /// Width in characters, so values line up from row to row.
+    pub fn width_chars(self) -> i32 {
+        match self {
+            ListColumn::Pid => 7,
+            ListColumn::Uptime => 9,
+            ListColumn::User => 10,
+            ListColumn::Memory => 9,
+            ListColumn::Runlevel => 8,
+        }
+    }
+
+    /// The cell for `service`, or `None` when there is nothing to show, e.g.
+    /// the PID of a stopped service or a user that has not been looked up.
+    pub fn text(self, service: &ServiceInfo, data: &ColumnData) -> Option<String> {
+        match self {
+            ListColumn::Pid => service.runtime_state.pid().map(|pid| pid.to_string()),
+            ListColumn::Uptime => match &service.runtime_state {
+                ServiceRuntimeState::Running { uptime, .. } => Some(format_duration(*uptime)),
+                _ => None,
+            },
+            ListColumn::User => data.users.get(&service.name).cloned().flatten(),
+            ListColumn::Memory => data.memory.get(&service.name).copied().map(format_bytes),
+            ListColumn::Runlevel => {
+                (!service.runlevels.is_empty()).then(|| service.runlevels.join(", "))
+            }
+        }
+    }
+}
+
+/// Column values that the service list does not carry. They are only fetched
+/// while their column is shown, so the default list costs nothing extra.
+#[derive(Debug, Clone, Default)]
+pub struct ColumnData {
+    /// Account each service's run script runs as, read once per service.
+    /// `None` when the script could not be parsed.
+    pub users: HashMap<String, Option<String>>,
+    /// Resident memory of running services, refreshed with the list.
+    pub memory: HashMap<String, u64>,
+}
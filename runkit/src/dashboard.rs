@@ -0,0 +1,220 @@
+use crate::actions::LogEntry;
+use crate::formatting::{
+    StatusLevel, format_log_entry, runtime_state_detail, runtime_state_short, status_level,
+};
+use crate::ui::build_status_indicator;
+use gtk::pango;
+use gtk4 as gtk;
+use libadwaita::{self as adw, prelude::*};
+use runkit_core::{ServiceInfo, TimestampFormat};
+
+/// Number of log lines shown in the rotating snippet panel.
+pub const SNIPPET_LINES: usize = 4;
+
+/// Fullscreen monitoring layout intended for wall-mounted displays.
+pub struct Dashboard {
+    pub window: adw::ApplicationWindow,
+    summary_label: gtk::Label,
+    tiles: gtk::FlowBox,
+    snippet_title: gtk::Label,
+    snippet_body: gtk::Label,
+}
+
+impl Dashboard {
+    pub fn new(app: &impl IsA<gtk::Application>) -> Self {
+        let window = adw::ApplicationWindow::builder()
+            .application(app)
+            .title("Runkit Dashboard")
+            .default_width(1280)
+            .default_height(800)
+            .build();
+
+        let summary_label = gtk::Label::builder()
+            .xalign(0.0)
+            .css_classes(["title-1"])
+            .label("Loading services…")
+            .build();
+
+        let tiles = gtk::FlowBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .homogeneous(true)
+            .column_spacing(12)
+            .row_spacing(12)
+            .min_children_per_line(2)
+            .max_children_per_line(8)
+            .valign(gtk::Align::Start)
+            .build();
+
+        let tiles_scroller = gtk::ScrolledWindow::builder()
+            .vexpand(true)
+            .hexpand(true)
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .child(&tiles)
+            .build();
+
+        let snippet_title = gtk::Label::builder()
+            .xalign(0.0)
+            .css_classes(["title-3"])
+            .build();
+        let snippet_body = gtk::Label::builder()
+            .xalign(0.0)
+            .wrap(true)
+            .wrap_mode(pango::WrapMode::WordChar)
+            .css_classes(["monospace"])
+            .build();
+
+        let snippet_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(6)
+            .css_classes(["card"])
+            .build();
+        let snippet_inner = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(6)
+            .margin_top(12)
+            .margin_bottom(12)
+            .margin_start(12)
+            .margin_end(12)
+            .build();
+        snippet_inner.append(&snippet_title);
+        snippet_inner.append(&snippet_body);
+        snippet_box.append(&snippet_inner);
+
+        let content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(18)
+            .margin_top(24)
+            .margin_bottom(24)
+            .margin_start(24)
+            .margin_end(24)
+            .build();
+        content.append(&summary_label);
+        content.append(&tiles_scroller);
+        content.append(&snippet_box);
+
+        window.set_content(Some(&content));
+
+        let dashboard = Dashboard {
+            window,
+            summary_label,
+            tiles,
+            snippet_title,
+            snippet_body,
+        };
+        dashboard.clear_snippet();
+        dashboard
+    }
+
+    /// Rebuild the tiles from the latest service snapshot, failures first.
+    pub fn update_services(&self, services: &[ServiceInfo]) {
+        while let Some(child) = self.tiles.first_child() {
+            self.tiles.remove(&child);
+        }
+
+        let mut visible: Vec<&ServiceInfo> = services.iter().filter(|s| s.enabled).collect();
+        visible.sort_by_key(|service| (level_rank(status_level(service)), service.name.clone()));
+
+        let failing = visible
+            .iter()
+            .filter(|service| status_level(service) == StatusLevel::Critical)
+            .count();
+        let running = visible
+            .iter()
+            .filter(|service| status_level(service) == StatusLevel::Good)
+            .count();
+        let summary = if failing > 0 {
+            format!(
+                "{failing} failing · {running} of {} services running",
+                visible.len()
+            )
+        } else {
+            format!("{running} of {} services running", visible.len())
+        };
+        self.summary_label.set_label(&summary);
+
+        for service in visible {
+            self.tiles.insert(&build_tile(service), -1);
+        }
+    }
+
+    pub fn show_snippet(&self, service: &str, entries: &[LogEntry], format: TimestampFormat) {
+        self.snippet_title
+            .set_label(&format!("Recent log · {service}"));
+        if entries.is_empty() {
+            self.snippet_body.set_label("No log output recorded.");
+            return;
+        }
+        let start = entries.len().saturating_sub(SNIPPET_LINES);
+        let lines: Vec<String> = entries[start..]
+            .iter()
+            .map(|entry| format_log_entry(entry, format))
+            .collect();
+        self.snippet_body.set_label(&lines.join("\n"));
+    }
+
+    pub fn clear_snippet(&self) {
+        self.snippet_title.set_label("Recent log");
+        self.snippet_body.set_label("Waiting for service activity…");
+    }
+}
+
+fn build_tile(service: &ServiceInfo) -> gtk::Box {
+    let level = status_level(service);
+    let tile = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(6)
+        .css_classes(["card"])
+        .build();
+    if level == StatusLevel::Critical {
+        tile.add_css_class("error");
+    }
+
+    let header = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .spacing(6)
+        .margin_top(12)
+        .margin_start(12)
+        .margin_end(12)
+        .build();
+    let name = gtk::Label::builder()
+        .label(&service.name)
+        .xalign(0.0)
+        .hexpand(true)
+        .ellipsize(pango::EllipsizeMode::End)
+        .css_classes(["title-2"])
+        .build();
+    header.append(&name);
+    header.append(&build_status_indicator(level));
+
+    let state = gtk::Label::builder()
+        .label(runtime_state_short(service))
+        .xalign(0.0)
+        .margin_start(12)
+        .css_classes(["title-4"])
+        .build();
+    let detail = gtk::Label::builder()
+        .label(runtime_state_detail(service))
+        .xalign(0.0)
+        .wrap(true)
+        .wrap_mode(pango::WrapMode::WordChar)
+        .margin_start(12)
+        .margin_end(12)
+        .margin_bottom(12)
+        .css_classes(["dim-label"])
+        .build();
+
+    tile.append(&header);
+    tile.append(&state);
+    tile.append(&detail);
+    tile
+}
+
+/// Sort key placing failures first, then warnings, then healthy services.
+pub fn level_rank(level: StatusLevel) -> u8 {
+    match level {
+        StatusLevel::Critical => 0,
+        StatusLevel::Warning => 1,
+        StatusLevel::Good => 2,
+        StatusLevel::Neutral => 3,
+    }
+}
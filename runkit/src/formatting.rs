@@ -1,9 +1,19 @@
-use crate::actions::LogEntry;
-use gtk4::glib;
-use humantime::format_duration;
-use runkit_core::{DesiredState, ServiceInfo, ServiceRuntimeState};
+use crate::actions::{CrashHistory, LogEntry, RunScript};
+use runkit_core::{
+    DesiredState, IoSource, Maintenance, RestartPolicy, ServiceInfo, ServiceRuntimeState,
+    TimestampFormat, TimestampStyle, format_duration,
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub fn runtime_state_short(service: &ServiceInfo) -> String {
+    if service.maintenance.is_some() {
+        return "Maintenance".to_string();
+    }
+
+    if !service.waiting_for.is_empty() {
+        return "Waiting".to_string();
+    }
+
     if matches!(&service.runtime_state, ServiceRuntimeState::Running { .. }) {
         return "Running".to_string();
     }
@@ -27,15 +37,155 @@ pub fn runtime_state_short(service: &ServiceInfo) -> String {
 }
 
 pub fn runtime_state_detail(service: &ServiceInfo) -> String {
+    let mut detail = runtime_state_summary(service);
+    for note in [
+        supervisor_note(service),
+        check_note(service),
+        defunct_note(service),
+        io_note(service),
+        runlevel_note(service),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        detail.push_str("; ");
+        detail.push_str(&note);
+    }
+    detail
+}
+
+fn maintenance_summary(maintenance: &Maintenance, state: &ServiceRuntimeState) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default();
+    let elapsed = Duration::from_secs(now.saturating_sub(maintenance.since).max(0) as u64);
+    let mut summary = format!("In maintenance for {}", format_duration(elapsed));
+    if let Some(reason) = &maintenance.reason {
+        summary.push_str(&format!(": {reason}"));
+    }
+    if let ServiceRuntimeState::Running { pid, .. } = state {
+        summary.push_str(&format!("; started anyway (PID {pid})"));
+    }
+    summary
+}
+
+/// What runsv itself said about a service that is not running, which often
+/// names the cause, e.g. a run script that is not executable.
+fn supervisor_note(service: &ServiceInfo) -> Option<String> {
+    let diagnostic = service.supervisor_diagnostic.as_ref()?;
+    Some(if diagnostic.fatal {
+        format!("runsv gave up: {}", diagnostic.message)
+    } else {
+        format!("runsv: {}", diagnostic.message)
+    })
+}
+
+/// Outcome of the last scheduled `./check`, once one has run.
+fn check_note(service: &ServiceInfo) -> Option<String> {
+    let check = service.last_check.as_ref()?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default();
+    let ago = format_duration(Duration::from_secs(
+        now.saturating_sub(check.at).max(0) as u64
+    ));
+    let verdict = if check.passed { "passed" } else { "failed" };
+    Some(if check.detail.is_empty() {
+        format!("check {verdict} {ago} ago")
+    } else {
+        format!("check {verdict} {ago} ago: {}", check.detail)
+    })
+}
+
+/// Cumulative disk traffic, so a daemon thrashing the disk stands out.
+fn io_note(service: &ServiceInfo) -> Option<String> {
+    let io = service.io?;
+    let scope = match io.source {
+        IoSource::Cgroup => "",
+        IoSource::Processes => " by live processes",
+    };
+    Some(format!(
+        "{} read, {} written{scope}",
+        format_bytes(io.read_bytes),
+        format_bytes(io.write_bytes)
+    ))
+}
+
+/// Runlevels the service is linked into, which matters once there is more than
+/// one to switch between.
+fn runlevel_note(service: &ServiceInfo) -> Option<String> {
+    match service.runlevels.as_slice() {
+        [] => None,
+        [runlevel] => Some(format!("in runlevel {runlevel}")),
+        runlevels => Some(format!("in runlevels {}", runlevels.join(", "))),
+    }
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+/// Warning for zombie processes, which `sv status` alone does not reveal.
+fn defunct_note(service: &ServiceInfo) -> Option<String> {
+    let main_pid = service.runtime_state.pid();
+    let main_defunct = main_pid.is_some_and(|pid| service.defunct_pids.contains(&pid));
+    let children = service
+        .defunct_pids
+        .iter()
+        .filter(|pid| Some(**pid) != main_pid)
+        .count();
+    match (main_defunct, children) {
+        (false, 0) => None,
+        (true, 0) => Some("main process is defunct (zombie)".to_string()),
+        (false, 1) => Some("1 defunct child process".to_string()),
+        (false, n) => Some(format!("{n} defunct child processes")),
+        (true, n) => Some(format!(
+            "main process and {n} child process(es) are defunct"
+        )),
+    }
+}
+
+fn runtime_state_summary(service: &ServiceInfo) -> String {
+    if let Some(maintenance) = &service.maintenance {
+        return maintenance_summary(maintenance, &service.runtime_state);
+    }
+
+    if !service.waiting_for.is_empty() {
+        return format!(
+            "Waiting for {} before starting",
+            service.waiting_for.join(", ")
+        );
+    }
+
     match &service.runtime_state {
-        ServiceRuntimeState::Running { pid, uptime } => format!(
-            "Running (PID {pid}) for {}",
-            format_duration(*uptime).to_string()
-        ),
+        ServiceRuntimeState::Running { pid, uptime } => {
+            if service.restart_policy == RestartPolicy::Never {
+                format!(
+                    "Running (PID {pid}) for {}; will stay down once it exits",
+                    format_duration(*uptime)
+                )
+            } else {
+                format!("Running (PID {pid}) for {}", format_duration(*uptime))
+            }
+        }
         ServiceRuntimeState::Down { since, normally_up } => {
-            let downtime = format_duration(*since).to_string();
+            let downtime = format_duration(*since);
             if !service.enabled {
                 format!("Disabled; last ran {downtime} ago")
+            } else if service.restart_policy == RestartPolicy::Never {
+                format!("Stopped {downtime} ago; automatic restarts are turned off")
             } else if *normally_up {
                 format!("Stopped {downtime} ago; runit will restart automatically")
             } else {
@@ -65,6 +215,53 @@ pub fn runtime_state_detail(service: &ServiceInfo) -> String {
     }
 }
 
+/// One line such as "Runs /usr/bin/sshd -D as root · config: /etc/sv/sshd/conf".
+pub fn run_script_summary(script: &RunScript) -> Option<String> {
+    let binary = script.binary.as_deref()?;
+    let command = if script.args.is_empty() {
+        binary.to_string()
+    } else {
+        format!("{binary} {}", script.args.join(" "))
+    };
+    let account = match (&script.user, &script.group) {
+        (Some(user), Some(group)) if group != user => format!("{user}:{group}"),
+        (Some(user), _) => user.clone(),
+        (None, _) => "root".to_string(),
+    };
+    let mut summary = format!("Runs {command} as {account}");
+    if let Some(config) = &script.config_file {
+        summary.push_str(&format!(" · config: {config}"));
+    }
+    if let Some(env_dir) = &script.env_dir {
+        summary.push_str(&format!(" · environment: {env_dir}"));
+    }
+    Some(summary)
+}
+
+/// Warning such as "Crashed 14 times in the last hour (flapping); last exit
+/// codes: 1, 1"; `None` when it has not crashed.
+pub fn crash_history_warning(history: &CrashHistory) -> Option<String> {
+    if history.crashes == 0 {
+        return None;
+    }
+    let mut warning = match history.crashes {
+        1 => "Crashed once in the last hour".to_string(),
+        n => format!("Crashed {n} times in the last hour"),
+    };
+    if history.flapping {
+        warning.push_str(" (flapping)");
+    }
+    if !history.last_exit_codes.is_empty() {
+        let codes: Vec<String> = history
+            .last_exit_codes
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        warning.push_str(&format!("; last exit codes: {}", codes.join(", ")));
+    }
+    Some(warning)
+}
+
 pub fn list_row_subtitle(service: &ServiceInfo) -> String {
     runtime_state_short(service)
 }
@@ -78,8 +275,25 @@ pub fn is_auto_start(desired: DesiredState) -> bool {
 }
 
 pub fn status_level(service: &ServiceInfo) -> StatusLevel {
+    // Planned downtime is neither a warning nor a failure.
+    if service.maintenance.is_some() {
+        return StatusLevel::Neutral;
+    }
+
+    if !service.waiting_for.is_empty() {
+        return StatusLevel::Warning;
+    }
+
     if matches!(&service.runtime_state, ServiceRuntimeState::Running { .. }) {
-        return StatusLevel::Good;
+        let check_failed = service
+            .last_check
+            .as_ref()
+            .is_some_and(|check| !check.passed);
+        return if service.defunct_pids.is_empty() && !check_failed {
+            StatusLevel::Good
+        } else {
+            StatusLevel::Warning
+        };
     }
 
     if !service.enabled {
@@ -100,16 +314,10 @@ pub fn status_level(service: &ServiceInfo) -> StatusLevel {
     }
 }
 
-pub fn format_log_entry(entry: &LogEntry) -> String {
-    let timestamp = entry
-        .unix_seconds
-        .and_then(|secs| format_timestamp(secs, entry.nanos.unwrap_or(0)));
-
-    let prefix = match (timestamp, &entry.raw) {
-        (Some(ts), _) => ts,
-        (None, Some(raw)) => format!("@{raw}"),
-        (None, None) => String::new(),
-    };
+pub fn format_log_entry(entry: &LogEntry, format: TimestampFormat) -> String {
+    let prefix = format
+        .render(entry.unix_seconds, entry.nanos, entry.raw.as_deref())
+        .unwrap_or_default();
 
     if prefix.is_empty() {
         entry.message.trim_end().to_string()
@@ -118,21 +326,84 @@ pub fn format_log_entry(entry: &LogEntry) -> String {
     }
 }
 
-fn format_timestamp(secs: i64, nanos: u32) -> Option<String> {
-    let datetime = glib::DateTime::from_unix_utc(secs).ok()?;
-    let local = datetime.to_timezone(&glib::TimeZone::local()).ok()?;
-    let base = local
-        .format("%Y-%m-%d %H:%M:%S")
-        .ok()
-        .map(|s| s.to_string())?;
-    let micros = nanos / 1_000;
+/// What goes into a diagnostic report besides the service itself.
+pub struct ReportDetails<'a> {
+    pub package: Option<&'a str>,
+    pub run_script: Option<&'a str>,
+    pub events: &'a [String],
+    pub logs: &'a [LogEntry],
+}
 
-    if micros > 0 {
-        Some(format!("{base}.{micros:06}"))
-    } else {
-        Some(base)
-    }
+/// Plain-text summary of a service for pasting into a bug report or forum
+/// post: state, package, recent events, the run script and the log tail.
+pub fn diagnostic_report(service: &ServiceInfo, details: &ReportDetails) -> String {
+    let mut report = format!("Runkit diagnostic report for {}\n", service.name);
+    let mut field = |label: &str, value: &str| {
+        report.push_str(&format!("{label:<12}{value}\n"));
+    };
+    field("Runkit:", env!("CARGO_PKG_VERSION"));
+    field("State:", &runtime_state_detail(service));
+    field(
+        "Enabled:",
+        match (service.enabled, service.runlevels.is_empty()) {
+            (false, _) => "no".to_string(),
+            (true, true) => "yes".to_string(),
+            (true, false) => format!("yes ({})", service.runlevels.join(", ")),
+        }
+        .as_str(),
+    );
+    field(
+        "Boot:",
+        if service.has_down_file {
+            "kept down (down file)"
+        } else {
+            "started with its runsv"
+        },
+    );
+    field(
+        "Restart:",
+        match service.restart_policy {
+            RestartPolicy::Always => "restarted when it exits",
+            RestartPolicy::Never => "kept down after it exits",
+        },
+    );
+    field(
+        "Definition:",
+        &service.definition_path.display().to_string(),
+    );
+    field("Package:", details.package.unwrap_or("none"));
+
+    let mut section = |title: &str, lines: &mut dyn Iterator<Item = String>| {
+        report.push_str(&format!("\n--- {title} ---\n"));
+        let mut empty = true;
+        for line in lines {
+            report.push_str(line.trim_end());
+            report.push('\n');
+            empty = false;
+        }
+        if empty {
+            report.push_str("(none)\n");
+        }
+    };
+    section("Recent events", &mut details.events.iter().cloned());
+    section(
+        "run",
+        &mut details
+            .run_script
+            .into_iter()
+            .flat_map(|script| script.lines().map(str::to_string).collect::<Vec<_>>()),
+    );
+    let format = TimestampFormat::new(TimestampStyle::Local, false);
+    section(
+        &format!("Last {} log lines", details.logs.len()),
+        &mut details
+            .logs
+            .iter()
+            .map(|entry| format_log_entry(entry, format)),
+    );
+    report
 }
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum StatusLevel {
     Good,
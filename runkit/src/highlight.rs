@@ -0,0 +1,218 @@
+//! Syntax highlighting for the shell scripts of a definition, such as `run`
+//! and `conf`, shown in a text view.
+use gtk::glib::translate::IntoGlib;
+use gtk4 as gtk;
+use gtk4::prelude::*;
+
+/// Words the shell treats as syntax rather than commands.
+const SHELL_KEYWORDS: [&str; 20] = [
+    "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case", "esac",
+    "in", "function", "return", "exit", "export", "set", "local", "exec",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShellToken {
+    Comment,
+    String,
+    Variable,
+    Keyword,
+    Command,
+}
+
+impl ShellToken {
+    const ALL: [ShellToken; 5] = [
+        ShellToken::Comment,
+        ShellToken::String,
+        ShellToken::Variable,
+        ShellToken::Keyword,
+        ShellToken::Command,
+    ];
+
+    fn tag_name(self) -> &'static str {
+        match self {
+            ShellToken::Comment => "shell-comment",
+            ShellToken::String => "shell-string",
+            ShellToken::Variable => "shell-variable",
+            ShellToken::Keyword => "shell-keyword",
+            ShellToken::Command => "shell-command",
+        }
+    }
+
+    /// Colors readable on both the light and the dark theme. Later tags win,
+    /// so a variable inside a string keeps its own color.
+    fn tag(self) -> gtk::TextTag {
+        let builder = gtk::TextTag::builder().name(self.tag_name());
+        match self {
+            ShellToken::Comment => builder
+                .foreground("#8e8e8e")
+                .style(gtk::pango::Style::Italic),
+            ShellToken::String => builder.foreground("#2f9e5f"),
+            ShellToken::Variable => builder.foreground("#a04fbf"),
+            ShellToken::Keyword => builder
+                .foreground("#3584e4")
+                .weight(gtk::pango::Weight::Bold.into_glib()),
+            ShellToken::Command => builder.foreground("#d5762c"),
+        }
+        .build()
+    }
+}
+
+/// Color the shell syntax of a script such as `run` or `conf` in `buffer`,
+/// replacing earlier highlighting. Adds the tags on first use.
+pub fn highlight_shell(buffer: &gtk::TextBuffer) {
+    let table = buffer.tag_table();
+    for token in ShellToken::ALL {
+        if table.lookup(token.tag_name()).is_none() {
+            table.add(&token.tag());
+        }
+    }
+    let (start, end) = buffer.bounds();
+    for token in ShellToken::ALL {
+        buffer.remove_tag_by_name(token.tag_name(), &start, &end);
+    }
+
+    let text = buffer.text(&start, &end, false);
+    // TextIter offsets count characters, the scanner bytes.
+    let offsets: Vec<usize> = text.char_indices().map(|(byte, _)| byte).collect();
+    let char_offset = |byte: usize| offsets.partition_point(|&offset| offset < byte) as i32;
+    for (from, to, token) in shell_tokens(&text) {
+        buffer.apply_tag_by_name(
+            token.tag_name(),
+            &buffer.iter_at_offset(char_offset(from)),
+            &buffer.iter_at_offset(char_offset(to)),
+        );
+    }
+}
+
+/// Byte ranges of the highlighted parts of `script`. Good enough for the
+/// short scripts of a definition rather than a full shell parser: heredocs
+/// and nested quoting are not followed.
+fn shell_tokens(script: &str) -> Vec<(usize, usize, ShellToken)> {
+    let bytes = script.as_bytes();
+    let mut tokens = Vec::new();
+    let mut command_position = true;
+    let mut index = 0;
+    while index < bytes.len() {
+        let byte = bytes[index];
+        match byte {
+            b'\n' | b';' | b'|' | b'&' | b'(' | b'`' => {
+                command_position = true;
+                index += 1;
+            }
+            b' ' | b'\t' | b')' | b'{' | b'}' => index += 1,
+            b'\\' => {
+                index += 1 + script[index + 1..].chars().next().map_or(0, char::len_utf8);
+            }
+            b'#' => {
+                let end = line_end(bytes, index);
+                tokens.push((index, end, ShellToken::Comment));
+                index = end;
+            }
+            b'\'' => {
+                let end = bytes[index + 1..]
+                    .iter()
+                    .position(|&byte| byte == b'\'')
+                    .map_or(bytes.len(), |offset| index + offset + 2);
+                tokens.push((index, end, ShellToken::String));
+                command_position = false;
+                index = end;
+            }
+            b'"' => {
+                let mut end = index + 1;
+                while end < bytes.len() && bytes[end] != b'"' {
+                    if bytes[end] == b'\\' {
+                        end += 1;
+                    } else if bytes[end] == b'$' {
+                        let variable_end = variable_end(bytes, end);
+                        tokens.push((end, variable_end, ShellToken::Variable));
+                        end = variable_end - 1;
+                    }
+                    end += 1;
+                }
+                let end = (end + 1).min(bytes.len());
+                tokens.push((index, end, ShellToken::String));
+                command_position = false;
+                index = end;
+            }
+            b'$' => {
+                let end = variable_end(bytes, index);
+                tokens.push((index, end, ShellToken::Variable));
+                command_position = bytes.get(index + 1) == Some(&b'(');
+                index = end;
+            }
+            _ => {
+                let end = word_end(bytes, index);
+                let word = &script[index..end];
+                if SHELL_KEYWORDS.contains(&word) {
+                    tokens.push((index, end, ShellToken::Keyword));
+                    // `exec chpst …` and `then cmd` are followed by a command.
+                    command_position = !matches!(word, "export" | "local" | "set" | "in");
+                } else if command_position {
+                    match word.find('=') {
+                        Some(equals) if equals > 0 => {
+                            tokens.push((index, index + equals, ShellToken::Variable));
+                        }
+                        _ => {
+                            tokens.push((index, end, ShellToken::Command));
+                            command_position = false;
+                        }
+                    }
+                }
+                index = end.max(index + 1);
+            }
+        }
+    }
+    tokens
+}
+
+fn line_end(bytes: &[u8], from: usize) -> usize {
+    bytes[from..]
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .map_or(bytes.len(), |offset| from + offset)
+}
+
+/// End of the plain word starting at `from`, up to the next space, quote,
+/// variable or operator.
+fn word_end(bytes: &[u8], from: usize) -> usize {
+    bytes[from..]
+        .iter()
+        .position(|byte| {
+            matches!(
+                byte,
+                b' ' | b'\t'
+                    | b'\n'
+                    | b';'
+                    | b'|'
+                    | b'&'
+                    | b'('
+                    | b')'
+                    | b'\''
+                    | b'"'
+                    | b'$'
+                    | b'`'
+            )
+        })
+        .map_or(bytes.len(), |offset| from + offset)
+}
+
+/// End of `$NAME`, `${…}`, `$(` or a special parameter such as `$1` or `$@`.
+fn variable_end(bytes: &[u8], dollar: usize) -> usize {
+    let next = dollar + 1;
+    match bytes.get(next) {
+        Some(b'{') => bytes[next..]
+            .iter()
+            .position(|&byte| byte == b'}')
+            .map_or(bytes.len(), |offset| next + offset + 1),
+        Some(b'(') => next + 1,
+        Some(byte) if byte.is_ascii_digit() || b"@*#?$!-".contains(byte) => next + 1,
+        Some(_) => {
+            let length = bytes[next..]
+                .iter()
+                .take_while(|byte| byte.is_ascii_alphanumeric() || **byte == b'_')
+                .count();
+            next + length
+        }
+        None => next,
+    }
+}
@@ -0,0 +1,58 @@
+//! Opening a service's directories outside Runkit, in the file manager or a terminal.
+use gtk::gio;
+use gtk::prelude::*;
+use gtk4 as gtk;
+use std::env;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Terminals tried in order when `$TERMINAL` is not set. Each is started with
+/// its working directory set, which all of them open their shell in.
+const TERMINALS: [&str; 10] = [
+    "x-terminal-emulator",
+    "kgx",
+    "gnome-terminal",
+    "konsole",
+    "xfce4-terminal",
+    "mate-terminal",
+    "alacritty",
+    "foot",
+    "kitty",
+    "xterm",
+];
+
+/// Show `dir` in the default file manager.
+pub fn open_folder(dir: &Path) -> Result<(), String> {
+    let uri = gio::File::for_path(dir).uri();
+    gio::AppInfo::launch_default_for_uri(&uri, None::<&gio::AppLaunchContext>)
+        .map_err(|err| format!("Could not open {}: {err}", dir.display()))
+}
+
+/// Start a terminal whose shell begins in `dir`: `$TERMINAL` when set,
+/// otherwise the first of [`TERMINALS`] that is installed.
+pub fn open_terminal(dir: &Path) -> Result<(), String> {
+    let preferred = env::var("TERMINAL").ok().filter(|value| !value.is_empty());
+    let candidates = preferred
+        .iter()
+        .map(String::as_str)
+        .chain(TERMINALS.iter().copied());
+    for terminal in candidates {
+        let spawned = Command::new(terminal)
+            .current_dir(dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        match spawned {
+            Ok(mut child) => {
+                // Reap the terminal when it closes so it does not linger as a zombie.
+                thread::spawn(move || child.wait());
+                return Ok(());
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(format!("Could not start {terminal}: {err}")),
+        }
+    }
+    Err("No terminal emulator found; set $TERMINAL to the one you use".to_string())
+}
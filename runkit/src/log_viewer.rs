@@ -0,0 +1,305 @@
+use crate::actions::LogEntry;
+use crate::formatting::format_log_entry;
+use gtk4 as gtk;
+use libadwaita::{self as adw, prelude::*};
+use regex::Regex;
+use runkit_core::TimestampFormat;
+use std::cell::RefCell;
+
+/// A service's log in a window of its own: follows new lines as they are
+/// written, highlights what a regular expression matches, and exports the
+/// text.
+pub struct LogViewer {
+    pub window: adw::Window,
+    /// Streams new lines and keeps the newest in view.
+    pub follow_toggle: gtk::ToggleButton,
+    pub search_entry: gtk::SearchEntry,
+    pub next_button: gtk::Button,
+    pub export_button: gtk::Button,
+    toast_overlay: adw::ToastOverlay,
+    text_view: gtk::TextView,
+    buffer: gtk::TextBuffer,
+    /// Stays at the end of the buffer, for scrolling there.
+    end_mark: gtk::TextMark,
+    match_label: gtk::Label,
+    match_tag: gtk::TextTag,
+    current_tag: gtk::TextTag,
+    pattern: RefCell<Option<Regex>>,
+    /// Matches in the buffer, kept up to date as lines are appended.
+    matches: RefCell<usize>,
+}
+
+impl LogViewer {
+    pub fn new(parent: &impl IsA<gtk::Window>, service: &str) -> Self {
+        let follow_toggle = gtk::ToggleButton::builder()
+            .icon_name("media-playback-start-symbolic")
+            .tooltip_text("Follow new lines as they are written")
+            .build();
+        let export_button = gtk::Button::builder()
+            .label("Export…")
+            .tooltip_text("Save the log to a file")
+            .build();
+        let header = adw::HeaderBar::builder()
+            .title_widget(&adw::WindowTitle::new(service, "Log"))
+            .build();
+        header.pack_start(&follow_toggle);
+        header.pack_end(&export_button);
+
+        let search_entry = gtk::SearchEntry::builder()
+            .placeholder_text("Find (regular expression)")
+            .hexpand(true)
+            .build();
+        let match_label = gtk::Label::builder()
+            .css_classes(["dim-label", "caption"])
+            .build();
+        let next_button = gtk::Button::builder()
+            .icon_name("go-down-symbolic")
+            .tooltip_text("Next match")
+            .sensitive(false)
+            .build();
+        let search_row = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(6)
+            .margin_top(6)
+            .margin_bottom(6)
+            .margin_start(12)
+            .margin_end(12)
+            .build();
+        search_row.append(&search_entry);
+        search_row.append(&match_label);
+        search_row.append(&next_button);
+
+        let buffer = gtk::TextBuffer::new(None);
+        let match_tag = gtk::TextTag::builder()
+            .name("match")
+            .background("rgba(246, 211, 45, 0.45)")
+            .build();
+        let current_tag = gtk::TextTag::builder()
+            .name("current-match")
+            .background("rgba(230, 97, 0, 0.65)")
+            .build();
+        buffer.tag_table().add(&match_tag);
+        buffer.tag_table().add(&current_tag);
+        let end_mark = buffer.create_mark(None, &buffer.end_iter(), false);
+        let text_view = gtk::TextView::builder()
+            .buffer(&buffer)
+            .editable(false)
+            .monospace(true)
+            .wrap_mode(gtk::WrapMode::WordChar)
+            .top_margin(6)
+            .bottom_margin(6)
+            .left_margin(12)
+            .right_margin(12)
+            .build();
+        let scroller = gtk::ScrolledWindow::builder()
+            .child(&text_view)
+            .vexpand(true)
+            .hexpand(true)
+            .build();
+
+        let content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .build();
+        content.append(&search_row);
+        content.append(&scroller);
+        let toast_overlay = adw::ToastOverlay::new();
+        toast_overlay.set_child(Some(&content));
+
+        let toolbar_view = adw::ToolbarView::new();
+        toolbar_view.add_top_bar(&header);
+        toolbar_view.set_content(Some(&toast_overlay));
+
+        let window = adw::Window::builder()
+            .transient_for(parent)
+            .title(format!("{service} Log"))
+            .default_width(900)
+            .default_height(600)
+            .content(&toolbar_view)
+            .build();
+
+        LogViewer {
+            window,
+            follow_toggle,
+            search_entry,
+            next_button,
+            export_button,
+            toast_overlay,
+            text_view,
+            buffer,
+            end_mark,
+            match_label,
+            match_tag,
+            current_tag,
+            pattern: RefCell::new(None),
+            matches: RefCell::new(0),
+        }
+    }
+
+    /// Show `entries`, newest last, scrolled to the end.
+    pub fn set_entries(&self, entries: &[LogEntry], format: TimestampFormat) {
+        let lines: Vec<String> = entries
+            .iter()
+            .map(|entry| format_log_entry(entry, format))
+            .collect();
+        if lines.is_empty() {
+            self.buffer.set_text("");
+        } else {
+            self.buffer.set_text(&lines.join("\n"));
+        }
+        *self.matches.borrow_mut() = 0;
+        self.highlight_from(0);
+        self.scroll_to_end();
+    }
+
+    /// Add a line written since, keeping it in view while following.
+    pub fn append(&self, entry: &LogEntry, format: TimestampFormat) {
+        let first_line = self.buffer.line_count() - 1;
+        let mut end = self.buffer.end_iter();
+        let line = format_log_entry(entry, format);
+        if self.buffer.char_count() == 0 {
+            self.buffer.insert(&mut end, &line);
+            self.highlight_from(0);
+        } else {
+            self.buffer.insert(&mut end, &format!("\n{line}"));
+            self.highlight_from(first_line + 1);
+        }
+        if self.follow_toggle.is_active() {
+            self.scroll_to_end();
+        }
+    }
+
+    /// Replace the text shown when the log cannot be read.
+    pub fn show_error(&self, message: &str) {
+        self.buffer.set_text(message);
+        *self.matches.borrow_mut() = 0;
+        self.update_match_label();
+    }
+
+    pub fn show_toast(&self, message: &str) {
+        self.toast_overlay.add_toast(adw::Toast::new(message));
+    }
+
+    /// Everything shown, for export.
+    pub fn text(&self) -> String {
+        let (start, end) = self.buffer.bounds();
+        self.buffer.text(&start, &end, false).to_string()
+    }
+
+    /// Highlight what `pattern` matches, or nothing when it is empty. An
+    /// invalid expression is flagged on the search entry.
+    pub fn set_pattern(&self, pattern: &str) {
+        let (start, end) = self.buffer.bounds();
+        self.buffer.remove_tag(&self.match_tag, &start, &end);
+        self.buffer.remove_tag(&self.current_tag, &start, &end);
+        *self.matches.borrow_mut() = 0;
+        let compiled = if pattern.is_empty() {
+            Ok(None)
+        } else {
+            Regex::new(pattern).map(Some)
+        };
+        match compiled {
+            Ok(regex) => {
+                self.search_entry.remove_css_class("error");
+                self.search_entry.set_tooltip_text(None);
+                *self.pattern.borrow_mut() = regex;
+            }
+            Err(err) => {
+                self.search_entry.add_css_class("error");
+                self.search_entry.set_tooltip_text(Some(&err.to_string()));
+                *self.pattern.borrow_mut() = None;
+            }
+        }
+        self.highlight_from(0);
+    }
+
+    /// Select the first match after the cursor, from the top once past the
+    /// last one. Stops following so the match stays in view.
+    pub fn next_match(&self) {
+        let Some(mut start) =
+            self.find_match_after(self.buffer.iter_at_mark(&self.buffer.get_insert()))
+        else {
+            return;
+        };
+        self.follow_toggle.set_active(false);
+        let mut end = start;
+        end.forward_to_tag_toggle(Some(&self.match_tag));
+        let (buffer_start, buffer_end) = self.buffer.bounds();
+        self.buffer
+            .remove_tag(&self.current_tag, &buffer_start, &buffer_end);
+        self.buffer.apply_tag(&self.current_tag, &start, &end);
+        self.buffer.place_cursor(&end);
+        self.text_view
+            .scroll_to_iter(&mut start, 0.1, false, 0.0, 0.0);
+    }
+
+    fn find_match_after(&self, from: gtk::TextIter) -> Option<gtk::TextIter> {
+        let mut iter = from;
+        let mut wrapped = false;
+        loop {
+            if !iter.forward_to_tag_toggle(Some(&self.match_tag)) {
+                if wrapped {
+                    return None;
+                }
+                wrapped = true;
+                iter = self.buffer.start_iter();
+                if iter.starts_tag(Some(&self.match_tag)) {
+                    return Some(iter);
+                }
+                continue;
+            }
+            if iter.starts_tag(Some(&self.match_tag)) {
+                return Some(iter);
+            }
+        }
+    }
+
+    /// Tag the matches on lines from `first_line` on.
+    fn highlight_from(&self, first_line: i32) {
+        if let Some(regex) = self.pattern.borrow().as_ref() {
+            let mut found = 0;
+            for line_number in first_line..self.buffer.line_count() {
+                let Some(start) = self.buffer.iter_at_line(line_number) else {
+                    continue;
+                };
+                let mut end = start;
+                if !end.ends_line() {
+                    end.forward_to_line_end();
+                }
+                let line = self.buffer.text(&start, &end, false);
+                for found_match in regex.find_iter(&line) {
+                    if found_match.is_empty() {
+                        continue;
+                    }
+                    let iter_at =
+                        |byte: usize| self.buffer.iter_at_line_index(line_number, byte as i32);
+                    if let (Some(match_start), Some(match_end)) =
+                        (iter_at(found_match.start()), iter_at(found_match.end()))
+                    {
+                        self.buffer
+                            .apply_tag(&self.match_tag, &match_start, &match_end);
+                        found += 1;
+                    }
+                }
+            }
+            *self.matches.borrow_mut() += found;
+        }
+        self.update_match_label();
+    }
+
+    fn update_match_label(&self) {
+        let matches = *self.matches.borrow();
+        let searching = self.pattern.borrow().is_some();
+        self.next_button.set_sensitive(matches > 0);
+        self.match_label.set_text(&match (searching, matches) {
+            (false, _) => String::new(),
+            (true, 0) => "No matches".to_string(),
+            (true, 1) => "1 match".to_string(),
+            (true, matches) => format!("{matches} matches"),
+        });
+    }
+
+    fn scroll_to_end(&self) {
+        self.text_view
+            .scroll_to_mark(&self.end_mark, 0.0, false, 0.0, 1.0);
+    }
+}
@@ -1,21 +1,42 @@
 mod actions;
+mod columns;
+mod dashboard;
 mod formatting;
+mod highlight;
+mod launch;
+mod log_viewer;
+mod setup;
+mod tray;
 mod ui;
 
-use actions::{ActionDispatcher, LogEntry};
+use actions::{
+    ActionDispatcher, Authorization, BatchOutcome, JobUpdate, LogEntry, LogStream, ProcessInfo,
+    Scope, ServiceAlert, ServiceChange, load_template_bundle,
+};
+use columns::{ColumnData, ListColumn};
+use gtk::gio;
 use gtk::glib::ControlFlow;
 use gtk::glib::{self, source::SourceId};
 use gtk4::{self as gtk, pango};
 use libadwaita::{self as adw, Application, prelude::*};
-use runkit_core::{ActivityEvent, ActivityEventType, ServiceInfo};
+use log_viewer::LogViewer;
+use runkit_core::{
+    API_VERSION, ActivityEvent, ActivityEventType, Categories, DEFAULT_SYSTEM_CATEGORIES,
+    DescriptionEntry, HealthIssueKind, RestartPolicy, ServerInfo, ServiceInfo, ServiceQuery,
+    StateChange, TemplateBundle, TimestampFormat, TimestampStyle, category_label,
+    default_log_archive_dir, format_duration, is_session_service, session_critical_reason,
+    user_categories_path, write_category, write_log_archive,
+};
 use serde::{Deserialize, Serialize};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::{HashMap, VecDeque};
 use std::env;
 use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 fn main() -> glib::ExitCode {
     adw::init().expect("Failed to initialize libadwaita");
@@ -23,15 +44,79 @@ fn main() -> glib::ExitCode {
     let app = Application::builder()
         .application_id("tech.geektoshi.Runkit")
         .build();
+    app.add_main_option(
+        "read-only",
+        glib::Char::from(b'r'),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Browse services without offering any action that changes them",
+        None,
+    );
+
+    app.add_main_option(
+        "kiosk",
+        glib::Char::from(b'k'),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Open the fullscreen monitoring dashboard (implies --read-only)",
+        None,
+    );
+
+    app.add_main_option(
+        "background",
+        glib::Char::from(b'b'),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Start hidden with a tray icon; closing the window keeps it running",
+        None,
+    );
+
+    let launch = Rc::new(Cell::new(LaunchOptions::default()));
+    {
+        let launch = Rc::clone(&launch);
+        app.connect_handle_local_options(move |_, options| {
+            launch.set(LaunchOptions {
+                read_only: options.contains("read-only"),
+                kiosk: options.contains("kiosk"),
+                background: options.contains("background"),
+            });
+            -1
+        });
+    }
 
-    app.connect_activate(|app| {
-        let controller = AppController::new(app, ActionDispatcher::default());
+    // In background mode the controller outlives its window, so activating
+    // again (e.g. launching Runkit once more) shows the existing window.
+    let background: Rc<RefCell<Option<Rc<AppController>>>> = Rc::new(RefCell::new(None));
+    app.connect_activate(move |app| {
+        if let Some(controller) = background.borrow().as_ref() {
+            controller.widgets.window.present();
+            return;
+        }
+        let launch = launch.get();
+        let controller = AppController::new(app, ActionDispatcher::default(), launch);
         controller.request_initial_load();
+        if launch.kiosk {
+            controller.show_dashboard();
+            controller.widgets.window.set_visible(false);
+        } else if launch.background {
+            controller.start_tray();
+            controller.widgets.window.set_visible(false);
+            background.replace(Some(controller));
+        }
     });
 
     app.run()
 }
 
+/// Options given on the command line that override saved preferences.
+#[derive(Debug, Clone, Copy, Default)]
+struct LaunchOptions {
+    read_only: bool,
+    kiosk: bool,
+    /// Start hidden behind a tray icon, and hide instead of quitting on close.
+    background: bool,
+}
+
 struct AppController {
     dispatcher: ActionDispatcher,
     model: Rc<RefCell<AppModel>>,
@@ -40,8 +125,37 @@ struct AppController {
     activity_store: RefCell<ActivityStore>,
     preferences_window: RefCell<Option<adw::PreferencesWindow>>,
     about_dialog: RefCell<Option<adw::MessageDialog>>,
+    setup_dialog: RefCell<Option<adw::MessageDialog>>,
+    file_chooser: RefCell<Option<gtk::FileChooserNative>>,
     preferences: RefCell<UserPreferences>,
     refresh_source: RefCell<Option<SourceId>>,
+    dashboard: RefCell<Option<dashboard::Dashboard>>,
+    /// The open log window, and what it is following.
+    log_viewer: RefCell<Option<Rc<LogViewer>>>,
+    log_stream: RefCell<Option<LogStream>>,
+    snippet_source: RefCell<Option<SourceId>>,
+    snippet_index: Cell<usize>,
+    change_refresh_pending: Cell<bool>,
+    change_refresh_logs: Cell<bool>,
+    /// The user's service directories are only watched once they are shown.
+    watching_user_services: Cell<bool>,
+    /// What polkit answers for ordinary and for forceful actions on system
+    /// services, checked without prompting.
+    control_authorization: Cell<Authorization>,
+    force_authorization: Cell<Authorization>,
+    /// The container or chroot runkitd found itself in without a runsvdir.
+    reduced_mode: RefCell<Option<String>>,
+    /// System services runkitd refuses to stop or disable without an override.
+    protected_services: RefCell<Vec<String>>,
+    /// What runkitd offers, once asked; features it lacks are hidden.
+    server_info: RefCell<Option<ServerInfo>>,
+    /// The runkitd job the spinner is waiting for, which Cancel ends.
+    running_job: Cell<Option<u64>>,
+    /// The tray icon of `--background` mode.
+    tray: RefCell<Option<tray::Tray>>,
+    /// What each service is grouped and filtered under.
+    categories: RefCell<Categories>,
+    launch: LaunchOptions,
 }
 
 #[derive(Default)]
@@ -58,11 +172,17 @@ struct AppModel {
     pending_selection: Option<String>,
     previous_states: HashMap<String, (String, Option<u32>)>, // service -> (state_name, pid)
     initial_load_completed: bool,
+    /// When `services` was fetched, to extrapolate uptimes until the next refresh.
+    fetched_at: Option<Instant>,
+    /// Values for the optional list columns that are shown.
+    column_data: ColumnData,
 }
 
 struct DescriptionStore {
     path: Option<PathBuf>,
-    entries: HashMap<String, Option<String>>,
+    /// Entries seeded by the installer may hold texts by locale; they are
+    /// kept as they are when the file is written back.
+    entries: HashMap<String, Option<DescriptionEntry>>,
 }
 
 impl DescriptionStore {
@@ -77,7 +197,10 @@ impl DescriptionStore {
     }
 
     fn lookup(&self, service: &str) -> Option<Option<String>> {
-        self.entries.get(service).cloned()
+        match self.entries.get(service)? {
+            Some(entry) => entry.text().map(|text| Some(text.to_string())),
+            None => Some(None),
+        }
     }
 
     fn ensure_present(&mut self, service: &str, description: &str) {
@@ -94,16 +217,14 @@ impl DescriptionStore {
     }
 
     fn store(&mut self, service: &str, description: Option<String>) -> io::Result<()> {
-        let needs_write = match self.entries.get(service) {
-            Some(existing) if existing == &description => false,
-            _ => true,
-        };
+        let description = description.map(DescriptionEntry::Text);
+        let needs_write =
+            !matches!(self.entries.get(service), Some(existing) if existing == &description);
         if !needs_write {
             return Ok(());
         }
 
-        self.entries
-            .insert(service.to_string(), description.clone());
+        self.entries.insert(service.to_string(), description);
         self.save()
     }
 
@@ -115,8 +236,7 @@ impl DescriptionStore {
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)?;
         }
-        let data = serde_json::to_string_pretty(&self.entries)
-            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        let data = serde_json::to_string_pretty(&self.entries).map_err(io::Error::other)?;
         fs::write(path, data)
     }
 }
@@ -197,8 +317,7 @@ impl ActivityStore {
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)?;
         }
-        let data = serde_json::to_string_pretty(&self.data)
-            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        let data = serde_json::to_string_pretty(&self.data).map_err(io::Error::other)?;
         fs::write(path, data)
     }
 }
@@ -227,20 +346,41 @@ fn config_root() -> Option<PathBuf> {
 const MIN_REFRESH_INTERVAL: u32 = 5;
 const MAX_REFRESH_INTERVAL: u32 = 3600;
 const MIN_LOG_LINES: u32 = 10;
+const DASHBOARD_SNIPPET_INTERVAL: u32 = 8;
+const METRICS_REFRESH_INTERVAL: u32 = 2;
 const MAX_LOG_LINES: u32 = 2000;
-
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+/// Delay used to coalesce bursts of `ServiceChanged` signals into one refresh.
+const CHANGE_REFRESH_DELAY_MS: u64 = 250;
+/// How long start, stop and restart wait for the service to get there.
+const ACTION_WAIT: Duration = Duration::from_secs(10);
+/// `sv` verbs offered by the Advanced menu.
+const CONTROL_ACTIONS: [&str; 11] = [
+    "hup",
+    "alarm",
+    "interrupt",
+    "quit",
+    "term",
+    "kill",
+    "pause",
+    "cont",
+    "force-stop",
+    "force-reload",
+    "force-restart",
+];
+/// Log lines included in a diagnostic report.
+const DIAGNOSTIC_LOG_LINES: usize = 100;
+/// Lines kept per service by "Archive failed-service logs".
+const FAILED_LOG_LINES: usize = 200;
+/// Files offered by *Edit files…*, the first one opened by default.
+const EDITABLE_SERVICE_FILES: [&str; 5] = ["run", "finish", "check", "conf", "log/run"];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
 enum StartupBehavior {
     RememberLastService,
+    #[default]
     ShowOverview,
 }
 
-impl Default for StartupBehavior {
-    fn default() -> Self {
-        StartupBehavior::ShowOverview
-    }
-}
-
 fn default_true() -> bool {
     true
 }
@@ -255,7 +395,18 @@ struct UserPreferences {
     show_all_services: bool,
     #[serde(default = "default_true")]
     require_password: bool,
+    read_only: bool,
+    timestamp_format: TimestampFormat,
     last_service: Option<String>,
+    #[serde(default = "default_true")]
+    desktop_alerts: bool,
+    /// Extra columns shown in the service list.
+    list_columns: Vec<ListColumn>,
+    /// Services pinned to the top of the list and offered in the tray menu
+    /// of `--background` mode.
+    favorite_services: Vec<String>,
+    /// List services under a heading per category.
+    group_by_category: bool,
 }
 
 impl Default for UserPreferences {
@@ -267,11 +418,25 @@ impl Default for UserPreferences {
             startup_behavior: StartupBehavior::ShowOverview,
             show_all_services: true,
             require_password: true,
+            read_only: false,
+            timestamp_format: TimestampFormat::default(),
             last_service: None,
+            desktop_alerts: true,
+            list_columns: Vec::new(),
+            favorite_services: Vec::new(),
+            group_by_category: false,
         }
     }
 }
 
+/// The user's categories over the bundled ones.
+fn load_categories() -> Categories {
+    Categories::load(
+        user_categories_path().as_deref(),
+        Some(Path::new(DEFAULT_SYSTEM_CATEGORIES)),
+    )
+}
+
 fn preferences_path() -> Option<PathBuf> {
     let mut base = config_root()?;
     base.push("runkit");
@@ -295,11 +460,47 @@ fn save_user_preferences(prefs: &UserPreferences) -> io::Result<()> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)?;
     }
-    let data = serde_json::to_string_pretty(prefs)
-        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+    let data = serde_json::to_string_pretty(prefs).map_err(io::Error::other)?;
     fs::write(path, data)
 }
 
+/// One row per process, indented by depth, showing the command line in full.
+fn append_process_rows(list: &gtk::ListBox, process: &ProcessInfo, depth: i32) {
+    let command = if process.cmdline.is_empty() {
+        format!("[{}]", process.comm)
+    } else {
+        process.cmdline.join(" ")
+    };
+    let row = adw::ActionRow::builder()
+        .title(format!("{} ({})", process.comm, process.pid))
+        .subtitle(command)
+        .subtitle_lines(2)
+        .subtitle_selectable(true)
+        .build();
+    row.set_margin_start(depth.min(6) * 18);
+    list.append(&row);
+    for child in &process.children {
+        append_process_rows(list, child, depth + 1);
+    }
+}
+
+/// Name and `(from, to)` replacement offered when cloning `source`: a trailing
+/// number is bumped, so `agetty-tty1` suggests `agetty-tty2` with `tty1`
+/// replaced by `tty2`. Anything else gets `-2` appended and no replacement.
+fn suggest_instance(source: &str) -> (String, String, String) {
+    let stem = source.trim_end_matches(|c: char| c.is_ascii_digit());
+    let Ok(number) = source[stem.len()..].parse::<u64>() else {
+        return (format!("{source}-2"), String::new(), String::new());
+    };
+    let name = format!("{stem}{}", number + 1);
+    let segment_start = stem.rfind('-').map_or(0, |index| index + 1);
+    (
+        name.clone(),
+        source[segment_start..].to_string(),
+        name[segment_start..].to_string(),
+    )
+}
+
 fn normalize_preferences(prefs: &mut UserPreferences) {
     prefs.refresh_interval_secs = prefs
         .refresh_interval_secs
@@ -311,17 +512,25 @@ fn normalize_preferences(prefs: &mut UserPreferences) {
 }
 
 impl AppController {
-    fn new(app: &Application, dispatcher: ActionDispatcher) -> Rc<Self> {
+    fn new(app: &Application, dispatcher: ActionDispatcher, launch: LaunchOptions) -> Rc<Self> {
         let preferences = load_user_preferences();
-        let widgets = ui::AppWidgets::new(app, preferences.show_all_services);
+        let widgets = ui::AppWidgets::new(
+            app,
+            preferences.show_all_services,
+            &preferences.list_columns,
+            preferences.group_by_category,
+        );
+        widgets.set_timestamp_format(preferences.timestamp_format);
         let description_store = DescriptionStore::load();
         let activity_store = ActivityStore::load();
 
         // Load previous states from activity store
         let previous_states = activity_store.get_previous_states();
 
-        let mut model = AppModel::default();
-        model.previous_states = previous_states;
+        let model = AppModel {
+            previous_states,
+            ..Default::default()
+        };
 
         let controller = Rc::new(Self {
             dispatcher,
@@ -331,11 +540,37 @@ impl AppController {
             activity_store: RefCell::new(activity_store),
             preferences_window: RefCell::new(None),
             about_dialog: RefCell::new(None),
+            setup_dialog: RefCell::new(None),
+            file_chooser: RefCell::new(None),
             preferences: RefCell::new(preferences),
             refresh_source: RefCell::new(None),
+            dashboard: RefCell::new(None),
+            log_viewer: RefCell::new(None),
+            log_stream: RefCell::new(None),
+            snippet_source: RefCell::new(None),
+            snippet_index: Cell::new(0),
+            change_refresh_pending: Cell::new(false),
+            change_refresh_logs: Cell::new(false),
+            watching_user_services: Cell::new(false),
+            control_authorization: Cell::new(Authorization::Allowed),
+            force_authorization: Cell::new(Authorization::Allowed),
+            reduced_mode: RefCell::new(None),
+            protected_services: RefCell::new(Vec::new()),
+            server_info: RefCell::new(None),
+            running_job: Cell::new(None),
+            tray: RefCell::new(None),
+            categories: RefCell::new(load_categories()),
+            launch,
         });
+        let known = controller.categories.borrow().known();
+        controller.widgets.set_categories(&known);
         controller.setup_handlers();
+        controller.apply_read_only();
+        controller.check_authorization();
         controller.configure_auto_refresh();
+        controller.listen_for_changes();
+        controller.listen_for_alerts();
+        controller.start_uptime_ticker();
         controller
     }
 
@@ -347,6 +582,62 @@ impl AppController {
                 controller.on_search_changed(entry.text().to_string());
             });
 
+        for (column, check) in &self.widgets.column_checks {
+            let controller = Rc::clone(self);
+            let column = *column;
+            check.connect_toggled(move |check| {
+                {
+                    let mut prefs = controller.preferences.borrow_mut();
+                    prefs.list_columns.retain(|shown| *shown != column);
+                    if check.is_active() {
+                        prefs.list_columns.push(column);
+                        prefs.list_columns.sort_by_key(|shown| {
+                            ListColumn::ALL
+                                .iter()
+                                .position(|candidate| candidate == shown)
+                        });
+                    }
+                }
+                controller.save_preferences();
+                controller.load_column_data(false);
+                controller.render_service_list();
+            });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            self.widgets.group_check.connect_toggled(move |check| {
+                controller.preferences.borrow_mut().group_by_category = check.is_active();
+                controller.save_preferences();
+                controller.render_service_list();
+            });
+        }
+
+        for (_, chip) in &self.widgets.state_chips {
+            let controller = Rc::clone(self);
+            chip.connect_toggled(move |_| {
+                controller.render_service_list();
+            });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            self.widgets
+                .sort_dropdown
+                .connect_selected_notify(move |_| {
+                    controller.render_service_list();
+                });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            self.widgets
+                .category_filter
+                .connect_selected_notify(move |_| {
+                    controller.render_service_list();
+                });
+        }
+
         {
             let controller = Rc::clone(self);
             let toggle = self.widgets.service_filter_toggle.clone();
@@ -371,11 +662,89 @@ impl AppController {
             });
         }
 
+        {
+            let controller = Rc::clone(self);
+            self.widgets.scope_user.connect_toggled(move |button| {
+                let scope = if button.is_active() {
+                    Scope::User
+                } else {
+                    Scope::System
+                };
+                controller.switch_scope(scope);
+            });
+        }
+
         let controller = Rc::clone(self);
         self.widgets
             .list_box
             .connect_row_selected(move |_, row| controller.on_row_selected(row));
 
+        for action in [
+            &self.widgets.control_action,
+            &self.widgets.control_force_action,
+        ] {
+            let controller = Rc::clone(self);
+            action.connect_activate(move |_, parameter| {
+                if let Some(verb) = parameter.and_then(|value| value.str()) {
+                    controller.confirm_control(verb);
+                }
+            });
+        }
+
+        for action in [
+            &self.widgets.row_control_action,
+            &self.widgets.row_control_force_action,
+        ] {
+            let controller = Rc::clone(self);
+            action.connect_activate(move |_, parameter| {
+                if let Some((service, verb)) =
+                    parameter.and_then(|value| value.get::<(String, String)>())
+                {
+                    controller.row_control(&service, &verb);
+                }
+            });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            self.widgets
+                .row_open_action
+                .connect_activate(move |_, parameter| {
+                    if let Some((service, target)) =
+                        parameter.and_then(|value| value.get::<(String, String)>())
+                    {
+                        controller.row_open(&service, &target);
+                    }
+                });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            self.widgets.loading_cancel.connect_clicked(move |_| {
+                controller.cancel_running_job();
+            });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            self.widgets.selection_toggle.connect_toggled(move |_| {
+                controller.render_service_list();
+            });
+        }
+        {
+            let controller = Rc::clone(self);
+            self.widgets.selection_clear.connect_clicked(move |_| {
+                controller.widgets.clear_selection();
+            });
+        }
+        for (action, button) in &self.widgets.selection_buttons {
+            let controller = Rc::clone(self);
+            let action = *action;
+            button.connect_clicked(move |_| {
+                controller.confirm_batch(action);
+            });
+        }
+
         let register_action = |button: &gtk::Button, action: &'static str| {
             let controller = Rc::clone(self);
             button.connect_clicked(move |_| {
@@ -385,355 +754,3294 @@ impl AppController {
 
         register_action(&self.widgets.action_start, "start");
         register_action(&self.widgets.action_stop, "stop");
-        register_action(&self.widgets.action_restart, "restart");
         register_action(&self.widgets.action_reload, "reload");
-        register_action(&self.widgets.action_enable, "enable");
-        register_action(&self.widgets.action_disable, "disable");
+        register_action(&self.widgets.action_enable, "enable-and-start");
+        register_action(&self.widgets.action_disable, "stop-and-disable");
         register_action(&self.widgets.action_check, "check");
 
         {
             let controller = Rc::clone(self);
-            let popover = self.widgets.menu_popover.clone();
             self.widgets
-                .preferences_action
-                .connect_activate(move |_, _| {
-                    popover.popdown();
-                    controller.show_preferences();
-                });
+                .action_restart
+                .connect_clicked(move |_| controller.confirm_restart());
         }
 
         {
             let controller = Rc::clone(self);
-            let popover = self.widgets.menu_popover.clone();
-            self.widgets.about_action.connect_activate(move |_, _| {
-                popover.popdown();
-                controller.show_about();
-            });
+            self.widgets
+                .action_restart_policy
+                .connect_clicked(move |_| controller.toggle_restart_policy());
         }
-    }
 
-    fn request_initial_load(self: &Rc<Self>) {
-        self.widgets.show_loading(true);
-        let result = self.dispatcher.fetch_services();
-        self.widgets.show_loading(false);
-        match result {
-            Ok(services) => self.update_services(services),
-            Err(err) => self.widgets.show_error(&err),
+        {
+            let controller = Rc::clone(self);
+            self.widgets
+                .action_down_file
+                .connect_clicked(move |_| controller.toggle_down_file());
         }
-    }
 
-    fn on_search_changed(self: &Rc<Self>, text: String) {
-        self.model.borrow_mut().filter_text = text.clone();
-        let count = self.render_service_list();
-        if text.is_empty() {
+        {
+            let controller = Rc::clone(self);
             self.widgets
-                .update_status_summary(&self.model.borrow().services);
-        } else {
-            self.widgets.update_status_summary_filtered(&text, count);
+                .action_maintenance
+                .connect_clicked(move |_| controller.toggle_maintenance());
         }
-    }
-
-    fn on_row_selected(self: &Rc<Self>, row: Option<&gtk::ListBoxRow>) {
-        match row.and_then(|r| self.widgets.row_service_name(r)) {
-            Some(service_name) => {
-                let service = {
-                    self.model
-                        .borrow()
-                        .services
-                        .iter()
-                        .find(|service| service.name == service_name)
-                        .cloned()
-                };
-
-                if let Some(service) = service {
-                    let name = service.name.clone();
-
-                    let service_changed = {
-                        let model = self.model.borrow();
-                        model.log_service.as_deref() != Some(name.as_str())
-                    };
-                    {
-                        let mut model = self.model.borrow_mut();
-                        if service_changed {
-                            model.log_service = Some(name.clone());
-                            model.log_entries.clear();
-                            model.log_error = None;
-                            model.activity_notes.clear();
-                        }
-                        model.current_description = service.description.clone();
-                        model.description_error = None;
-                    }
 
-                    if let Some(text) = service.description.as_deref() {
-                        self.description_store
-                            .borrow_mut()
-                            .ensure_present(&name, text);
-                    }
+        {
+            let controller = Rc::clone(self);
+            self.widgets
+                .health_fix_button
+                .connect_clicked(move |_| controller.fix_duplicate_enables());
+        }
 
-                    self.widgets.show_service_details(&service);
-                    self.widgets.action_bar_set_enabled(true, Some(&service));
-                    self.ensure_service_description(&service);
+        {
+            let controller = Rc::clone(self);
+            self.widgets
+                .action_log_config
+                .connect_clicked(move |_| controller.edit_log_config());
+        }
 
-                    let remember_last = {
-                        let prefs = self.preferences.borrow();
-                        prefs.startup_behavior == StartupBehavior::RememberLastService
-                    };
-                    if remember_last {
-                        let mut prefs = self.preferences.borrow_mut();
-                        if prefs.last_service.as_deref() != Some(name.as_str()) {
-                            prefs.last_service = Some(name.clone());
-                            drop(prefs);
-                            self.save_preferences();
-                        } else {
-                            drop(prefs);
-                        }
-                    }
+        {
+            let controller = Rc::clone(self);
+            self.widgets
+                .action_metrics
+                .connect_clicked(move |_| controller.show_metrics());
+        }
 
-                    let (entries_snapshot, error_snapshot, notes_snapshot) = {
-                        let model = self.model.borrow();
-                        (
-                            model.log_entries.clone(),
-                            model.log_error.clone(),
-                            model.activity_notes.clone(),
-                        )
-                    };
+        {
+            let controller = Rc::clone(self);
+            self.widgets
+                .action_runlevels
+                .connect_clicked(move |_| controller.show_runlevels());
+        }
 
-                    if let Some(error) = error_snapshot {
-                        self.widgets.show_activity_error(&name, &error);
-                    } else if !entries_snapshot.is_empty() || !notes_snapshot.is_empty() {
-                        self.widgets
-                            .show_activity(&name, &entries_snapshot, &notes_snapshot);
-                    } else {
-                        self.request_logs(name);
-                    }
-                }
-            }
-            None => {
-                if self.model.borrow().list_refreshing {
-                    return;
+        for toggle in [
+            &self.widgets.log_filter_all,
+            &self.widgets.log_filter_warnings,
+            &self.widgets.log_filter_errors,
+        ] {
+            let controller = Rc::clone(self);
+            toggle.connect_toggled(move |toggle| {
+                // Grouped toggles also fire when deactivated; refresh once.
+                if toggle.is_active() {
+                    controller.refresh_logs_for_selection();
                 }
-                self.widgets.show_placeholder();
-                self.widgets.action_bar_set_enabled(false, None);
-                let mut model = self.model.borrow_mut();
-                model.log_service = None;
-                model.log_entries.clear();
-                model.log_error = None;
-                model.current_description = None;
-                model.description_error = None;
-                model.activity_notes.clear();
-            }
+            });
         }
-    }
 
-    fn update_services(self: &Rc<Self>, services: Vec<ServiceInfo>) {
         {
-            let mut store = self.description_store.borrow_mut();
-            for service in &services {
-                if let Some(description) = service.description.as_deref() {
-                    store.ensure_present(&service.name, description);
+            let controller = Rc::clone(self);
+            self.widgets.log_viewer_button.connect_clicked(move |_| {
+                if let Some(service) = controller.widgets.current_service() {
+                    controller.open_log_viewer(&service);
                 }
-            }
+            });
         }
 
-        // Detect state changes and record activity
         {
-            let mut activity_store = self.activity_store.borrow_mut();
-            let mut model = self.model.borrow_mut();
-            let is_initial_load = !model.initial_load_completed;
-
-            for service in &services {
-                let current_state = service.runtime_state.state_name();
+            let controller = Rc::clone(self);
+            self.widgets
+                .definition_expander
+                .connect_expanded_notify(move |expander| {
+                    if expander.is_expanded() {
+                        controller.load_definition_file();
+                    }
+                });
+        }
+
+        for (_, button) in &self.widgets.definition_files {
+            let controller = Rc::clone(self);
+            button.connect_toggled(move |button| {
+                if button.is_active() {
+                    controller.load_definition_file();
+                }
+            });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            self.widgets.definition_edit.connect_toggled(move |toggle| {
+                let editing = toggle.is_active();
+                controller.widgets.set_definition_editing(editing);
+                // Leaving edit mode without saving drops the changes.
+                if !editing {
+                    controller.load_definition_file();
+                }
+            });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            self.widgets.definition_save.connect_clicked(move |_| {
+                if let Some(service) = controller.widgets.current_service() {
+                    let file = controller.widgets.selected_definition_file();
+                    let contents = controller.widgets.definition_text();
+                    controller.save_service_file(&service, file, contents, false);
+                }
+            });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            self.widgets.action_export.connect_clicked(move |_| {
+                controller.export_definition();
+            });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            self.widgets
+                .action_clone
+                .connect_clicked(move |_| controller.clone_service());
+        }
+
+        {
+            let controller = Rc::clone(self);
+            self.widgets
+                .action_edit_files
+                .connect_clicked(move |_| controller.edit_service_files());
+        }
+
+        {
+            let controller = Rc::clone(self);
+            self.widgets
+                .action_environment
+                .connect_clicked(move |_| controller.edit_environment());
+        }
+
+        {
+            let controller = Rc::clone(self);
+            let popover = self.widgets.menu_popover.clone();
+            self.widgets.import_action.connect_activate(move |_, _| {
+                popover.popdown();
+                controller.import_definition();
+            });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            let popover = self.widgets.menu_popover.clone();
+            self.widgets.template_action.connect_activate(move |_, _| {
+                popover.popdown();
+                controller.open_template_bundle();
+            });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            let popover = self.widgets.menu_popover.clone();
+            self.widgets.trash_action.connect_activate(move |_, _| {
+                popover.popdown();
+                controller.show_trash();
+            });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            let popover = self.widgets.menu_popover.clone();
+            self.widgets
+                .save_profile_action
+                .connect_activate(move |_, _| {
+                    popover.popdown();
+                    controller.save_profile();
+                });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            let popover = self.widgets.menu_popover.clone();
+            self.widgets
+                .restore_profile_action
+                .connect_activate(move |_, _| {
+                    popover.popdown();
+                    controller.restore_profile();
+                });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            let popover = self.widgets.menu_popover.clone();
+            self.widgets
+                .preferences_action
+                .connect_activate(move |_, _| {
+                    popover.popdown();
+                    controller.show_preferences();
+                });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            let popover = self.widgets.menu_popover.clone();
+            self.widgets.dashboard_action.connect_activate(move |_, _| {
+                popover.popdown();
+                controller.show_dashboard();
+            });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            let popover = self.widgets.menu_popover.clone();
+            self.widgets.silence_action.connect_activate(move |_, _| {
+                popover.popdown();
+                controller.show_alert_silence();
+            });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            let popover = self.widgets.menu_popover.clone();
+            self.widgets
+                .archive_logs_action
+                .connect_activate(move |_, _| {
+                    popover.popdown();
+                    controller.archive_failed_logs();
+                });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            let popover = self.widgets.menu_popover.clone();
+            self.widgets
+                .stage_logs_action
+                .connect_activate(move |_, _| {
+                    popover.popdown();
+                    controller.show_stage_logs();
+                });
+        }
+
+        {
+            let controller = Rc::clone(self);
+            let popover = self.widgets.menu_popover.clone();
+            self.widgets.about_action.connect_activate(move |_, _| {
+                popover.popdown();
+                controller.show_about();
+            });
+        }
+    }
+
+    fn request_initial_load(self: &Rc<Self>) {
+        self.widgets.show_loading(true);
+        let result = self.dispatcher.fetch_services();
+        self.widgets.show_loading(false);
+        match result {
+            Ok(services) => self.update_services(services),
+            Err(err) => {
+                self.widgets.show_error(&err);
+                self.show_setup_assistant(&err);
+            }
+        }
+        self.refresh_health();
+    }
+
+    /// Explain a failure to reach or use runkitd with the client-side doctor
+    /// checks and the commands that fix them.
+    fn show_setup_assistant(self: &Rc<Self>, failure: &str) {
+        if self.launch.kiosk {
+            return;
+        }
+        if let Some(dialog) = self.setup_dialog.borrow_mut().take() {
+            dialog.close();
+        }
+
+        let checks = setup::run_checks(failure);
+        let dialog = setup::build_dialog(&self.widgets.window, &checks);
+        let controller = Rc::downgrade(self);
+        dialog.connect_response(None, move |_, response| {
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            controller.setup_dialog.borrow_mut().take();
+            if response == setup::RETRY_RESPONSE {
+                controller.request_initial_load();
+            }
+        });
+        dialog.present();
+        self.setup_dialog.borrow_mut().replace(dialog);
+    }
+
+    fn on_search_changed(self: &Rc<Self>, text: String) {
+        self.model.borrow_mut().filter_text = text.clone();
+        let count = self.render_service_list();
+        if text.is_empty() {
+            self.widgets
+                .update_status_summary(&self.model.borrow().services);
+        } else {
+            self.widgets.update_status_summary_filtered(&text, count);
+        }
+    }
+
+    fn on_row_selected(self: &Rc<Self>, row: Option<&gtk::ListBoxRow>) {
+        match row.and_then(|r| self.widgets.row_service_name(r)) {
+            Some(service_name) => {
+                let service = {
+                    self.model
+                        .borrow()
+                        .services
+                        .iter()
+                        .find(|service| service.name == service_name)
+                        .cloned()
+                };
+
+                if let Some(service) = service {
+                    let name = service.name.clone();
+
+                    let service_changed = {
+                        let model = self.model.borrow();
+                        model.log_service.as_deref() != Some(name.as_str())
+                    };
+                    {
+                        let mut model = self.model.borrow_mut();
+                        if service_changed {
+                            model.log_service = Some(name.clone());
+                            model.log_entries.clear();
+                            model.log_error = None;
+                            model.activity_notes.clear();
+                        }
+                        model.current_description = service.description.clone();
+                        model.description_error = None;
+                    }
+
+                    if let Some(text) = service.description.as_deref() {
+                        self.description_store
+                            .borrow_mut()
+                            .ensure_present(&name, text);
+                    }
+
+                    self.widgets.show_service_details(&service);
+                    if service_changed {
+                        let summary = self
+                            .dispatcher
+                            .inspect_run_script(&name)
+                            .ok()
+                            .and_then(|script| formatting::run_script_summary(&script));
+                        self.widgets.show_run_script(summary.as_deref());
+                        if self.widgets.definition_edit.is_active() {
+                            // Reloads the newly selected service's file.
+                            self.widgets.definition_edit.set_active(false);
+                        } else {
+                            self.load_definition_file();
+                        }
+                    }
+                    let crash_warning = self
+                        .dispatcher
+                        .fetch_service_history(&name)
+                        .ok()
+                        .and_then(|history| formatting::crash_history_warning(&history));
+                    self.widgets.show_crash_history(crash_warning.as_deref());
+                    self.widgets
+                        .action_bar_set_enabled(!self.is_read_only(), Some(&service));
+                    self.ensure_service_description(&service);
+
+                    let remember_last = {
+                        let prefs = self.preferences.borrow();
+                        prefs.startup_behavior == StartupBehavior::RememberLastService
+                    };
+                    if remember_last {
+                        let mut prefs = self.preferences.borrow_mut();
+                        if prefs.last_service.as_deref() != Some(name.as_str()) {
+                            prefs.last_service = Some(name.clone());
+                            drop(prefs);
+                            self.save_preferences();
+                        } else {
+                            drop(prefs);
+                        }
+                    }
+
+                    let (entries_snapshot, error_snapshot, notes_snapshot) = {
+                        let model = self.model.borrow();
+                        (
+                            model.log_entries.clone(),
+                            model.log_error.clone(),
+                            model.activity_notes.clone(),
+                        )
+                    };
+
+                    if let Some(error) = error_snapshot {
+                        self.widgets.show_activity_error(&name, &error);
+                    } else if !entries_snapshot.is_empty() || !notes_snapshot.is_empty() {
+                        self.widgets
+                            .show_activity(&name, &entries_snapshot, &notes_snapshot);
+                    } else {
+                        self.request_logs(name);
+                    }
+                }
+            }
+            None => {
+                if self.model.borrow().list_refreshing {
+                    return;
+                }
+                self.widgets.show_placeholder();
+                self.widgets.action_bar_set_enabled(false, None);
+                let mut model = self.model.borrow_mut();
+                model.log_service = None;
+                model.log_entries.clear();
+                model.log_error = None;
+                model.current_description = None;
+                model.description_error = None;
+                model.activity_notes.clear();
+            }
+        }
+    }
+
+    fn update_services(self: &Rc<Self>, services: Vec<ServiceInfo>) {
+        {
+            let mut store = self.description_store.borrow_mut();
+            for service in &services {
+                if let Some(description) = service.description.as_deref() {
+                    store.ensure_present(&service.name, description);
+                }
+            }
+        }
+
+        // Detect state changes and record activity
+        {
+            let mut activity_store = self.activity_store.borrow_mut();
+            let mut model = self.model.borrow_mut();
+            let is_initial_load = !model.initial_load_completed;
+
+            for service in &services {
+                let current_state = service.runtime_state.state_name();
                 let current_pid = service.runtime_state.pid();
 
-                if let Some((prev_state, prev_pid)) = model.previous_states.get(&service.name) {
-                    // Check if state changed
-                    if prev_state != current_state {
-                        let event = ActivityEvent::new(ActivityEventType::StateChange {
-                            from_state: prev_state.to_string(),
-                            to_state: current_state.to_string(),
-                            pid: current_pid,
-                        });
-                        activity_store.add_event(&service.name, event);
+                if let Some((prev_state, prev_pid)) = model.previous_states.get(&service.name) {
+                    // Check if state changed
+                    if prev_state != current_state {
+                        let event = ActivityEvent::new(ActivityEventType::StateChange {
+                            from_state: prev_state.to_string(),
+                            to_state: current_state.to_string(),
+                            pid: current_pid,
+                        });
+                        activity_store.add_event(&service.name, event);
+                    }
+                    // Check if PID changed while running (indicates restart)
+                    else if current_state == "running" && prev_pid != &current_pid {
+                        let event = ActivityEvent::new(ActivityEventType::StateChange {
+                            from_state: "running".to_string(),
+                            to_state: "running".to_string(),
+                            pid: current_pid,
+                        });
+                        activity_store.add_event(&service.name, event);
+                    }
+                }
+
+                // Update the previous state
+                model.previous_states.insert(
+                    service.name.clone(),
+                    (current_state.to_string(), current_pid),
+                );
+            }
+
+            // Mark initial load as completed
+            if is_initial_load {
+                model.initial_load_completed = true;
+            }
+
+            // Save previous states to disk
+            activity_store.update_previous_states(model.previous_states.clone());
+        }
+
+        let pending_selection = {
+            let prefs = self.preferences.borrow();
+            if prefs.startup_behavior == StartupBehavior::RememberLastService {
+                prefs.last_service.as_ref().and_then(|name| {
+                    services
+                        .iter()
+                        .find(|svc| svc.name == *name)
+                        .and_then(|svc| {
+                            if prefs.show_all_services || svc.enabled {
+                                Some(name.clone())
+                            } else {
+                                None
+                            }
+                        })
+                })
+            } else {
+                None
+            }
+        };
+        {
+            let mut model = self.model.borrow_mut();
+            model.services = services;
+            model.pending_selection = pending_selection;
+            model.fetched_at = Some(Instant::now());
+        }
+        self.widgets
+            .update_status_summary(&self.model.borrow().services);
+        self.load_column_data(true);
+        self.render_service_list();
+        self.refresh_logs_for_selection();
+        self.refresh_description_for_selection();
+        if let Some(dashboard) = self.dashboard.borrow().as_ref() {
+            dashboard.update_services(&self.model.borrow().services);
+        }
+        self.update_tray();
+    }
+
+    /// Advance the uptimes and downtimes on screen every second between
+    /// refreshes, from the last fetched values plus the time since.
+    fn start_uptime_ticker(self: &Rc<Self>) {
+        let controller = Rc::downgrade(self);
+        glib::timeout_add_seconds_local(1, move || {
+            let Some(controller) = controller.upgrade() else {
+                return ControlFlow::Break;
+            };
+            controller.tick_uptimes();
+            ControlFlow::Continue
+        });
+    }
+
+    fn tick_uptimes(&self) {
+        let services: Vec<ServiceInfo> = {
+            let model = self.model.borrow();
+            let Some(elapsed) = model.fetched_at.map(|fetched| fetched.elapsed()) else {
+                return;
+            };
+            model
+                .services
+                .iter()
+                .map(|service| ServiceInfo {
+                    runtime_state: service.runtime_state.advanced_by(elapsed),
+                    ..service.clone()
+                })
+                .collect()
+        };
+        if let Some(name) = self.widgets.current_service()
+            && let Some(service) = services.iter().find(|service| service.name == name)
+        {
+            self.widgets.show_service_state(service);
+        }
+        if let Some(dashboard) = self.dashboard.borrow().as_ref() {
+            dashboard.update_services(&services);
+        }
+    }
+
+    fn render_service_list(self: &Rc<Self>) -> usize {
+        let show_all = self.preferences.borrow().show_all_services;
+        self.widgets.update_service_filter_toggle_label(show_all);
+        let categories = self.categories.borrow();
+        let category_filter = self.widgets.selected_category();
+        let filtered = {
+            let model = self.model.borrow();
+            let query = ServiceQuery {
+                states: self.widgets.selected_states(),
+                enabled: (!show_all).then_some(true),
+                text: Some(model.filter_text.clone()),
+                sort: self.widgets.selected_sort(),
+            };
+            let mut filtered = query.apply(model.services.clone());
+            if let Some(category) = category_filter {
+                filtered.retain(|service| categories.category(&service.name) == category);
+            }
+            filtered
+        };
+
+        let count = filtered.len();
+        {
+            let mut model = self.model.borrow_mut();
+            model.list_refreshing = true;
+        }
+        // Favorites are pinned first. A user's own tree mixes desktop helpers
+        // such as pipewire with everything else, so they come next in a
+        // section of their own; otherwise services may be grouped by category.
+        let favorites = self.preferences.borrow().favorite_services.clone();
+        let session_section = self.dispatcher.scope() == Scope::User;
+        let group = self.preferences.borrow().group_by_category;
+        let known = categories.known();
+        let section = |service: &ServiceInfo| -> (usize, String) {
+            if favorites.contains(&service.name) {
+                (0, "Favorites".to_string())
+            } else if session_section && is_session_service(&service.name) {
+                (1, "Session services".to_string())
+            } else if group {
+                let category = categories.category(&service.name);
+                let rank = known.iter().position(|known| known == category);
+                (2 + rank.unwrap_or(known.len()), category_label(category))
+            } else {
+                (usize::MAX, "Other services".to_string())
+            }
+        };
+        let mut filtered: Vec<((usize, String), ServiceInfo)> = filtered
+            .into_iter()
+            .map(|service| (section(&service), service))
+            .collect();
+        filtered.sort_by_key(|((rank, _), _)| *rank);
+        let sectioned = filtered.iter().any(|((rank, _), _)| *rank != usize::MAX);
+        let (sections, filtered): (Vec<String>, Vec<ServiceInfo>) = filtered
+            .into_iter()
+            .map(|((_, section), service)| (section, service))
+            .unzip();
+        let sections = if sectioned { sections } else { Vec::new() };
+        drop(categories);
+        let columns = self.preferences.borrow().list_columns.clone();
+        let column_data = self.model.borrow().column_data.clone();
+        self.widgets
+            .populate_list(&filtered, &columns, &column_data, &sections, &favorites);
+        let pending = {
+            let mut model = self.model.borrow_mut();
+            model.list_refreshing = false;
+            model.pending_selection.take()
+        };
+        if let Some(target) = pending {
+            self.widgets.select_service(&target);
+        }
+        if self.widgets.current_service().is_none() {
+            let mut model = self.model.borrow_mut();
+            model.log_service = None;
+            model.log_entries.clear();
+            model.log_error = None;
+            model.activity_notes.clear();
+        }
+        count
+    }
+
+    /// Fetch what the shown list columns need and the service list lacks.
+    /// Users are read once per service; memory again when `refresh_memory`.
+    fn load_column_data(&self, refresh_memory: bool) {
+        let columns = self.preferences.borrow().list_columns.clone();
+        if columns.contains(&ListColumn::Memory)
+            && (refresh_memory || self.model.borrow().column_data.memory.is_empty())
+        {
+            match self.dispatcher.fetch_memory_usage() {
+                Ok(memory) => self.model.borrow_mut().column_data.memory = memory,
+                Err(err) => eprintln!("Failed to read memory usage: {err}"),
+            }
+        }
+        if columns.contains(&ListColumn::User) {
+            let missing: Vec<String> = {
+                let model = self.model.borrow();
+                model
+                    .services
+                    .iter()
+                    .filter(|service| !model.column_data.users.contains_key(&service.name))
+                    .map(|service| service.name.clone())
+                    .collect()
+            };
+            for name in missing {
+                // A parsed script without `chpst -u` runs as root.
+                let user = self
+                    .dispatcher
+                    .inspect_run_script(&name)
+                    .ok()
+                    .and_then(|script| {
+                        script
+                            .binary
+                            .is_some()
+                            .then(|| script.user.unwrap_or_else(|| "root".into()))
+                    });
+                self.model.borrow_mut().column_data.users.insert(name, user);
+            }
+        }
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.launch.read_only || self.launch.kiosk || self.preferences.borrow().read_only
+    }
+
+    /// Ask runkitd whether it reuses this user's authorizations, which is what
+    /// the password preference shows, and without prompting whether ordinary
+    /// and forceful actions are allowed, then grey out whatever would be
+    /// refused. Failures leave everything enabled; the setup assistant
+    /// explains a missing runkitd.
+    fn check_authorization(self: &Rc<Self>) {
+        let dispatcher = self.dispatcher.clone();
+        let (sender, receiver) = async_channel::bounded(1);
+        let spawned = thread::Builder::new()
+            .name("runkit-authorization".into())
+            .spawn(move || {
+                let reduced = dispatcher.reduced_mode().ok().flatten();
+                let info = dispatcher.server_info().ok();
+                let protected = if info
+                    .as_ref()
+                    .is_none_or(|info| info.supports("protected-services"))
+                {
+                    dispatcher.protected_services().unwrap_or_default()
+                } else {
+                    Vec::new()
+                };
+                let cached = dispatcher.cached_authorization();
+                let check = |operation| {
+                    dispatcher
+                        .check_authorization(operation)
+                        .unwrap_or_default()
+                };
+                let _ = sender.send_blocking((
+                    reduced,
+                    info,
+                    protected,
+                    cached,
+                    check("start"),
+                    check("kill"),
+                ));
+            });
+        if spawned.is_err() {
+            return;
+        }
+        let controller = Rc::downgrade(self);
+        glib::MainContext::default().spawn_local(async move {
+            let Ok((reduced, info, protected, cached, control, force)) = receiver.recv().await
+            else {
+                return;
+            };
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            if let Ok(until) = cached {
+                controller.preferences.borrow_mut().require_password = until.is_none();
+            }
+            controller.control_authorization.set(control);
+            controller.force_authorization.set(force);
+            controller.reduced_mode.replace(reduced);
+            controller.protected_services.replace(protected);
+            if let Some(info) = &info {
+                controller.warn_api_mismatch(info);
+            }
+            controller.server_info.replace(info);
+            controller.apply_read_only();
+        });
+    }
+
+    /// Say when runkitd speaks another version of the interface, since some
+    /// actions may then fail or be missing.
+    fn warn_api_mismatch(&self, info: &ServerInfo) {
+        if info.api_version == 0 {
+            self.widgets
+                .show_toast("runkitd is older than Runkit; features it does not offer are hidden");
+        } else if info.api_version != API_VERSION {
+            self.widgets.show_toast(&format!(
+                "runkitd {} speaks interface version {}, Runkit expects {API_VERSION}; \
+                 some actions may fail",
+                info.version, info.api_version
+            ));
+        }
+    }
+
+    fn apply_read_only(&self) {
+        let read_only = self.is_read_only();
+        self.widgets.set_read_only(read_only);
+        if let Some(info) = self.server_info.borrow().as_ref() {
+            self.widgets
+                .hide_unsupported(|capability| info.supports(capability));
+        }
+        if !read_only && self.dispatcher.scope() == Scope::System {
+            self.widgets.set_authorization(
+                self.control_authorization.get() == Authorization::Denied,
+                self.force_authorization.get() == Authorization::Denied,
+            );
+            if let Some(confinement) = self.reduced_mode.borrow().as_deref() {
+                self.widgets.set_reduced_mode(confinement);
+            }
+        }
+        let selected = self.widgets.current_service().and_then(|name| {
+            self.model
+                .borrow()
+                .services
+                .iter()
+                .find(|service| service.name == name)
+                .cloned()
+        });
+        if let Some(service) = selected {
+            self.widgets
+                .action_bar_set_enabled(!read_only, Some(&service));
+        }
+    }
+
+    fn toggle_down_file(self: &Rc<Self>) {
+        let Some(service_name) = self.widgets.current_service() else {
+            return;
+        };
+        let has_down_file = self
+            .model
+            .borrow()
+            .services
+            .iter()
+            .find(|info| info.name == service_name)
+            .is_some_and(|info| info.has_down_file);
+        if has_down_file {
+            self.trigger_action("clear-down-file");
+        } else {
+            self.trigger_action("set-down-file");
+        }
+    }
+
+    /// End maintenance, or ask for an optional reason and start it.
+    fn toggle_maintenance(self: &Rc<Self>) {
+        if self.is_read_only() {
+            return;
+        }
+        let Some(service_name) = self.widgets.current_service() else {
+            return;
+        };
+        let in_maintenance = self
+            .model
+            .borrow()
+            .services
+            .iter()
+            .find(|info| info.name == service_name)
+            .is_some_and(|info| info.maintenance.is_some());
+        if in_maintenance {
+            let result = self.dispatcher.set_maintenance(&service_name, false, None);
+            self.finish_action("end-maintenance", service_name, result);
+            return;
+        }
+
+        let reason = gtk::Entry::builder()
+            .placeholder_text("Reason (optional)")
+            .activates_default(true)
+            .build();
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading(format!("Put {service_name} into maintenance?"))
+            .body(
+                "The service is stopped and kept down, even across reboots, and no alerts \
+                 are raised for it until maintenance ends.",
+            )
+            .extra_child(&reason)
+            .build();
+        dialog.add_response("cancel", "Cancel");
+        dialog.add_response("begin", "Start Maintenance");
+        dialog.set_response_appearance("begin", adw::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("begin"));
+        dialog.set_close_response("cancel");
+
+        let controller = Rc::downgrade(self);
+        dialog.connect_response(None, move |_, response| {
+            if response != "begin" {
+                return;
+            }
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            let reason = reason.text();
+            let result =
+                controller
+                    .dispatcher
+                    .set_maintenance(&service_name, true, Some(reason.as_str()));
+            controller.finish_action("maintenance", service_name.clone(), result);
+        });
+        dialog.present();
+    }
+
+    /// Run a verb from the Advanced menu, asking first for the ones that can
+    /// take the service down.
+    fn confirm_control(self: &Rc<Self>, verb: &str) {
+        let Some(&action) = CONTROL_ACTIONS.iter().find(|action| **action == verb) else {
+            return;
+        };
+        let Some(service_name) = self.widgets.current_service() else {
+            return;
+        };
+        if !ui::FORCEFUL_VERBS.contains(&action) {
+            self.trigger_action(action);
+            return;
+        }
+
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading(format!("Run {action} on {service_name}?"))
+            .body("This can end the service without a clean shutdown.")
+            .build();
+        dialog.add_response("cancel", "Cancel");
+        dialog.add_response("run", "Run");
+        dialog.set_response_appearance("run", adw::ResponseAppearance::Destructive);
+        dialog.set_close_response("cancel");
+
+        let controller = Rc::downgrade(self);
+        dialog.connect_response(None, move |_, response| {
+            if response == "run"
+                && let Some(controller) = controller.upgrade()
+            {
+                controller.trigger_action(action);
+            }
+        });
+        dialog.present();
+    }
+
+    /// Restart the selected service, first warning when that would end the
+    /// graphical session, e.g. for the running display manager, and offering
+    /// to restart it after logout instead.
+    fn confirm_restart(self: &Rc<Self>) {
+        let Some(service_name) = self.widgets.current_service() else {
+            return;
+        };
+        let running = self
+            .model
+            .borrow()
+            .services
+            .iter()
+            .any(|info| info.name == service_name && info.runtime_state.pid().is_some());
+        let reason = session_critical_reason(&service_name)
+            .filter(|_| running && self.dispatcher.scope() == Scope::System);
+        let Some(reason) = reason else {
+            self.trigger_action("restart");
+            return;
+        };
+        if self.is_read_only() {
+            return;
+        }
+
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading(format!("Restarting {service_name} will log you out"))
+            .body(format!(
+                "Your graphical session will end because {reason}. Unsaved work in open \
+                 applications will be lost. You can restart it once you have logged out \
+                 instead."
+            ))
+            .build();
+        dialog.add_response("cancel", "Cancel");
+        dialog.add_response("logout", "Restart After Logout");
+        dialog.add_response("now", "Restart Now");
+        dialog.set_response_appearance("logout", adw::ResponseAppearance::Suggested);
+        dialog.set_response_appearance("now", adw::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("logout"));
+        dialog.set_close_response("cancel");
+
+        let controller = Rc::downgrade(self);
+        dialog.connect_response(None, move |_, response| {
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            match response {
+                "now" => controller.trigger_action("restart"),
+                "logout" => {
+                    match controller
+                        .dispatcher
+                        .restart_at_logout(&service_name, false)
+                    {
+                        Ok(message) => controller.widgets.show_toast(&message),
+                        Err(err) => controller.widgets.show_error(&format!(
+                            "Could not schedule {service_name} for logout: {err}"
+                        )),
+                    }
+                }
+                _ => {}
+            }
+        });
+        dialog.present();
+    }
+
+    /// Run a changing action from a row's quick actions menu. The row is
+    /// selected first, so the action and its feedback refer to that service.
+    fn row_control(self: &Rc<Self>, service: &str, verb: &str) {
+        self.widgets.select_service(service);
+        if self.widgets.current_service().as_deref() != Some(service) {
+            return;
+        }
+        match verb {
+            "once" => self.trigger_action("once"),
+            "check" => self.trigger_action("check"),
+            "reload" => self.trigger_action("reload"),
+            "start-with-dependencies" => self.trigger_action("start-with-dependencies"),
+            "stop-with-dependents" => self.trigger_action("stop-with-dependents"),
+            "maintenance" => self.toggle_maintenance(),
+            "delete" => self.confirm_delete(service),
+            verb => self.confirm_control(verb),
+        }
+    }
+
+    /// Ask whether to trash or permanently delete a service, then do it.
+    fn confirm_delete(self: &Rc<Self>, service: &str) {
+        if self.is_read_only() {
+            return;
+        }
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading(format!("Delete {service}?"))
+            .body(
+                "The service is stopped and removed from every runlevel. A definition in \
+                 the trash can be restored for a while; a permanent delete cannot be undone.",
+            )
+            .build();
+        dialog.add_response("cancel", "Cancel");
+        dialog.add_response("trash", "Move to Trash");
+        dialog.add_response("remove", "Delete Permanently");
+        dialog.set_response_appearance("trash", adw::ResponseAppearance::Destructive);
+        dialog.set_response_appearance("remove", adw::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+
+        let controller = Rc::downgrade(self);
+        let service = service.to_string();
+        dialog.connect_response(None, move |_, response| {
+            let remove = match response {
+                "trash" => false,
+                "remove" => true,
+                _ => return,
+            };
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            match controller.dispatcher.delete_service(&service, remove) {
+                Ok(message) => {
+                    controller.widgets.show_toast(&message);
+                    controller.request_refresh(true);
+                }
+                Err(err) => controller
+                    .widgets
+                    .show_error(&format!("Deleting {service} failed: {err}")),
+            }
+        });
+        dialog.present();
+    }
+
+    /// Put a plain-text report on the service into the clipboard for pasting
+    /// into a bug report. Parts that cannot be read are left out.
+    fn copy_diagnostic_report(self: &Rc<Self>, service: &str) {
+        let Some(info) = self
+            .model
+            .borrow()
+            .services
+            .iter()
+            .find(|info| info.name == service)
+            .cloned()
+        else {
+            return;
+        };
+        let package = self
+            .dispatcher
+            .fetch_service_package(service)
+            .ok()
+            .flatten();
+        let run_script = self
+            .dispatcher
+            .read_service_file(service, "run")
+            .ok()
+            .flatten();
+        let logs = self
+            .dispatcher
+            .fetch_logs(service, DIAGNOSTIC_LOG_LINES, None)
+            .unwrap_or_default();
+        let events = self.format_activity_history(service);
+        let report = formatting::diagnostic_report(
+            &info,
+            &formatting::ReportDetails {
+                package: package.as_deref(),
+                run_script: run_script.as_deref(),
+                events: &events,
+                logs: &logs,
+            },
+        );
+        self.widgets.window.clipboard().set_text(&report);
+        self.widgets
+            .show_toast(&format!("Copied a diagnostic report for {service}"));
+    }
+
+    /// Show the service's logs, or open its definition or log directory in the
+    /// file manager or a terminal.
+    fn row_open(self: &Rc<Self>, service: &str, target: &str) {
+        match target {
+            "logs" => {
+                self.widgets.select_service(service);
+                self.open_log_viewer(service);
+                return;
+            }
+            "report" => {
+                self.copy_diagnostic_report(service);
+                return;
+            }
+            "description" => {
+                self.edit_description(service);
+                return;
+            }
+            "favorite" => {
+                self.toggle_favorite(service);
+                return;
+            }
+            "category" => {
+                self.edit_category(service);
+                return;
+            }
+            _ => {}
+        }
+        let definition_path = self
+            .model
+            .borrow()
+            .services
+            .iter()
+            .find(|info| info.name == service)
+            .map(|info| info.definition_path.clone());
+        let Some(definition_path) = definition_path else {
+            return;
+        };
+        let dir = match target {
+            "log-folder" | "log-terminal" => {
+                // svlogd writes to log/main by convention; fall back to log/.
+                match ["log/main", "log"]
+                    .into_iter()
+                    .map(|relative| definition_path.join(relative))
+                    .find(|dir| dir.is_dir())
+                {
+                    Some(dir) => dir,
+                    None => {
+                        self.widgets
+                            .show_error(&format!("{service} has no log directory"));
+                        return;
+                    }
+                }
+            }
+            _ => definition_path,
+        };
+        let result = match target {
+            "terminal" | "log-terminal" => launch::open_terminal(&dir),
+            _ => launch::open_folder(&dir),
+        };
+        if let Err(err) = result {
+            self.widgets.show_error(&err);
+        }
+    }
+
+    fn toggle_favorite(self: &Rc<Self>, service: &str) {
+        let added = {
+            let mut prefs = self.preferences.borrow_mut();
+            let before = prefs.favorite_services.len();
+            prefs.favorite_services.retain(|name| name != service);
+            let added = prefs.favorite_services.len() == before;
+            if added {
+                prefs.favorite_services.push(service.to_string());
+                prefs.favorite_services.sort();
+            }
+            added
+        };
+        self.save_preferences();
+        self.render_service_list();
+        self.update_tray();
+        self.widgets.show_toast(&if added {
+            format!("Added {service} to favorites")
+        } else {
+            format!("Removed {service} from favorites")
+        });
+    }
+
+    /// Put `service` in a category of the user's choosing, for this user
+    /// only. Clearing the text goes back to the bundled category.
+    fn edit_category(self: &Rc<Self>, service: &str) {
+        let Some(path) = user_categories_path() else {
+            self.widgets
+                .show_error("Cannot find your home directory to save categories");
+            return;
+        };
+        let (current, known) = {
+            let categories = self.categories.borrow();
+            (categories.category(service).to_string(), categories.known())
+        };
+        let entry = gtk::Entry::builder()
+            .text(current.as_str())
+            .placeholder_text("Category")
+            .activates_default(true)
+            .build();
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading(format!("Categorize {service}"))
+            .body(format!(
+                "Known categories: {}. Leave it empty to go back to the bundled category.",
+                known.join(", ")
+            ))
+            .extra_child(&entry)
+            .build();
+        dialog.add_response("cancel", "Cancel");
+        dialog.add_response("save", "Save");
+        dialog.set_response_appearance("save", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("save"));
+        dialog.set_close_response("cancel");
+
+        let controller = Rc::downgrade(self);
+        let service = service.to_string();
+        dialog.connect_response(None, move |_, response| {
+            if response != "save" {
+                return;
+            }
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            let text = entry.text();
+            let category = Some(text.trim()).filter(|text| !text.is_empty());
+            if let Err(err) = write_category(&path, &service, category) {
+                controller.widgets.show_error(&err.to_string());
+                return;
+            }
+            controller.reload_categories();
+        });
+        dialog.present();
+    }
+
+    fn reload_categories(self: &Rc<Self>) {
+        self.categories.replace(load_categories());
+        let known = self.categories.borrow().known();
+        self.widgets.set_categories(&known);
+        self.render_service_list();
+    }
+
+    /// Put the icon of `--background` mode in the tray and act on its menu.
+    /// Without a tray to show it in, the window is the only way back in, so
+    /// it stays open.
+    fn start_tray(self: &Rc<Self>) {
+        let (sender, receiver) = async_channel::unbounded();
+        let tray = match tray::Tray::start(sender) {
+            Ok(tray) => tray,
+            Err(err) => {
+                eprintln!("runkit: {err}");
+                self.widgets.window.present();
+                return;
+            }
+        };
+        self.tray.replace(Some(tray));
+        self.update_tray();
+
+        let window = self.widgets.window.clone();
+        window.connect_close_request(|window| {
+            window.set_visible(false);
+            glib::Propagation::Stop
+        });
+
+        let controller = Rc::downgrade(self);
+        glib::MainContext::default().spawn_local(async move {
+            while let Ok(event) = receiver.recv().await {
+                let Some(controller) = controller.upgrade() else {
+                    break;
+                };
+                match event {
+                    tray::TrayEvent::Open => controller.widgets.window.present(),
+                    tray::TrayEvent::Control { service, verb } => {
+                        controller.widgets.window.present();
+                        controller.tray_control(&service, verb);
+                    }
+                    tray::TrayEvent::Quit => {
+                        if let Some(app) = controller.widgets.window.application() {
+                            app.quit();
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    /// Start, stop or restart a favorite from the tray, through the same
+    /// confirmations as the buttons on its detail page.
+    fn tray_control(self: &Rc<Self>, service: &str, verb: &str) {
+        self.widgets.select_service(service);
+        if self.widgets.current_service().as_deref() != Some(service) {
+            self.widgets
+                .show_error(&format!("{service} is hidden by the current filter"));
+            return;
+        }
+        match verb {
+            "start" => self.trigger_action("start"),
+            "stop" => self.trigger_action("stop"),
+            "restart" => self.confirm_restart(),
+            _ => {}
+        }
+    }
+
+    fn update_tray(&self) {
+        if let Some(tray) = self.tray.borrow().as_ref() {
+            tray.update(
+                &self.model.borrow().services,
+                &self.preferences.borrow().favorite_services,
+            );
+        }
+    }
+
+    /// Let the user describe a service in their own words, e.g. to annotate
+    /// a custom one. Clearing the text goes back to the shared description.
+    fn edit_description(self: &Rc<Self>, service: &str) {
+        let current = self
+            .model
+            .borrow()
+            .services
+            .iter()
+            .find(|info| info.name == service)
+            .and_then(|info| info.description.clone())
+            .unwrap_or_default();
+        let entry = gtk::Entry::builder()
+            .text(current.as_str())
+            .placeholder_text("Description")
+            .activates_default(true)
+            .build();
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading(format!("Describe {service}"))
+            .body(
+                "Your description is only shown to you and takes precedence over the \
+                 packaged one. Leave it empty to go back to the packaged description.",
+            )
+            .extra_child(&entry)
+            .build();
+        dialog.add_response("cancel", "Cancel");
+        dialog.add_response("save", "Save");
+        dialog.set_response_appearance("save", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("save"));
+        dialog.set_close_response("cancel");
+
+        let controller = Rc::downgrade(self);
+        let service = service.to_string();
+        dialog.connect_response(None, move |_, response| {
+            if response != "save" {
+                return;
+            }
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            let text = entry.text();
+            let text = Some(text.trim()).filter(|text| !text.is_empty());
+            if let Err(err) = controller.dispatcher.set_user_description(&service, text) {
+                controller.widgets.show_error(&err);
+                return;
+            }
+            // Replace the cached description too, or it would keep
+            // shadowing the one just set or cleared.
+            let description = match text {
+                Some(text) => Some(text.to_string()),
+                None => controller
+                    .dispatcher
+                    .fetch_description(&service)
+                    .ok()
+                    .flatten(),
+            };
+            if let Err(err) = controller
+                .description_store
+                .borrow_mut()
+                .store(&service, description.clone())
+            {
+                eprintln!("Failed to persist description for {service}: {err}");
+            }
+            if controller.widgets.current_service().as_deref() == Some(service.as_str()) {
+                controller.record_description(&service, description);
+            } else if let Some(info) = controller
+                .model
+                .borrow_mut()
+                .services
+                .iter_mut()
+                .find(|info| info.name == service)
+            {
+                info.description = description;
+            }
+        });
+        dialog.present();
+    }
+
+    fn toggle_restart_policy(self: &Rc<Self>) {
+        let Some(service_name) = self.widgets.current_service() else {
+            return;
+        };
+        let policy = self
+            .model
+            .borrow()
+            .services
+            .iter()
+            .find(|info| info.name == service_name)
+            .map(|info| info.restart_policy)
+            .unwrap_or_default();
+        match policy {
+            RestartPolicy::Always => self.trigger_action("no-restart"),
+            RestartPolicy::Never => self.trigger_action("auto-restart"),
+        }
+    }
+
+    fn trigger_action(self: &Rc<Self>, action: &'static str) {
+        if self.is_read_only() {
+            return;
+        }
+        let Some(service_name) = self.widgets.current_service() else {
+            return;
+        };
+        if matches!(
+            action,
+            "stop"
+                | "force-stop"
+                | "pause"
+                | "disable"
+                | "set-down-file"
+                | "stop-and-disable"
+                | "no-restart"
+                | "interrupt"
+                | "quit"
+                | "term"
+                | "kill"
+        ) && self.dispatcher.scope() == Scope::System
+            && self.protected_services.borrow().contains(&service_name)
+        {
+            self.confirm_protected(action, service_name);
+            return;
+        }
+        if matches!(
+            action,
+            "start"
+                | "stop"
+                | "restart"
+                | "enable-and-start"
+                | "stop-and-disable"
+                | "start-with-dependencies"
+                | "stop-with-dependents"
+        ) {
+            self.run_and_wait(action, service_name, false);
+            return;
+        }
+        let result = self.dispatcher.run(action, &service_name);
+        self.finish_action(action, service_name, result);
+    }
+
+    /// Ask before running `action` on every checked service.
+    fn confirm_batch(self: &Rc<Self>, action: &'static str) {
+        if self.is_read_only() {
+            return;
+        }
+        let services = self.widgets.selected_services();
+        if services.is_empty() {
+            return;
+        }
+        let verb = match action {
+            "start" => "Start",
+            "stop" => "Stop",
+            "restart" => "Restart",
+            "enable" => "Enable",
+            _ => "Disable",
+        };
+        let mut body = services.join(", ");
+        if matches!(action, "stop" | "disable") && self.dispatcher.scope() == Scope::System {
+            let protected = self.protected_services.borrow();
+            let skipped: Vec<&str> = services
+                .iter()
+                .filter(|service| protected.contains(service))
+                .map(String::as_str)
+                .collect();
+            if !skipped.is_empty() {
+                body.push_str(&format!(
+                    "\n\nProtected services are skipped: {}",
+                    skipped.join(", ")
+                ));
+            }
+        }
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading(match services.len() {
+                1 => format!("{verb} 1 service?"),
+                count => format!("{verb} {count} services?"),
+            })
+            .body(body)
+            .build();
+        dialog.add_response("cancel", "Cancel");
+        dialog.add_response("run", verb);
+        dialog.set_response_appearance(
+            "run",
+            if matches!(action, "stop" | "disable") {
+                adw::ResponseAppearance::Destructive
+            } else {
+                adw::ResponseAppearance::Suggested
+            },
+        );
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+
+        let controller = Rc::downgrade(self);
+        dialog.connect_response(Some("run"), move |_, _| {
+            if let Some(controller) = controller.upgrade() {
+                controller.run_batch(action, services.clone());
+            }
+        });
+        dialog.present();
+    }
+
+    /// Run `action` on `services` on a worker thread, under one authorization
+    /// and with progress next to the spinner.
+    fn run_batch(self: &Rc<Self>, action: &'static str, services: Vec<String>) {
+        let dispatcher = self.dispatcher.clone();
+        let (sender, receiver) = async_channel::bounded(1);
+        let (update_sender, update_receiver) = async_channel::unbounded();
+        let batch = services.clone();
+        let spawned = thread::Builder::new()
+            .name("runkit-batch".into())
+            .spawn(move || {
+                let result = dispatcher.run_batch(action, &batch, &update_sender);
+                let _ = sender.send_blocking(result);
+            });
+        if let Err(err) = spawned {
+            self.widgets
+                .show_error(&format!("Failed to start {action}: {err}"));
+            return;
+        }
+        self.widgets.show_loading(true);
+        let controller = Rc::downgrade(self);
+        glib::MainContext::default().spawn_local(async move {
+            while let Ok(update) = update_receiver.recv().await {
+                let Some(controller) = controller.upgrade() else {
+                    return;
+                };
+                match update {
+                    JobUpdate::Started(job) => {
+                        controller.running_job.set(Some(job));
+                        controller.widgets.show_job("");
+                    }
+                    JobUpdate::Progress {
+                        done,
+                        total,
+                        message,
+                    } => controller
+                        .widgets
+                        .show_job(&format!("{message} ({} of {total})", done + 1)),
+                }
+            }
+            let Ok(result) = receiver.recv().await else {
+                return;
+            };
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            controller.running_job.set(None);
+            controller.widgets.show_loading(false);
+            controller.finish_batch(action, &services, result);
+        });
+    }
+
+    fn finish_batch(
+        self: &Rc<Self>,
+        action: &str,
+        services: &[String],
+        result: Result<BatchOutcome, String>,
+    ) {
+        let outcome = match result {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                if err.contains("polkit") {
+                    self.show_setup_assistant(&err);
+                }
+                self.widgets.show_error(&format!("Operation failed: {err}"));
+                return;
+            }
+        };
+        {
+            let mut activity_store = self.activity_store.borrow_mut();
+            for service in services {
+                let error = outcome
+                    .failures
+                    .iter()
+                    .find(|(failed, _)| failed == service)
+                    .map(|(_, error)| error.clone());
+                let event = ActivityEvent::new(ActivityEventType::UserAction {
+                    action: action.to_string(),
+                    success: error.is_none(),
+                    error,
+                });
+                activity_store.add_event(service, event);
+            }
+        }
+        if outcome.failures.is_empty() {
+            self.widgets.show_toast(&outcome.message);
+        } else {
+            let failures: Vec<String> = outcome
+                .failures
+                .iter()
+                .map(|(service, error)| format!("{service}: {error}"))
+                .collect();
+            self.widgets
+                .show_error(&format!("{}\n{}", outcome.message, failures.join("\n")));
+        }
+        self.request_refresh(true);
+    }
+
+    /// Stop or disable a protected service only once the user has confirmed,
+    /// overriding runkitd's guard with a password prompt of its own.
+    fn confirm_protected(self: &Rc<Self>, action: &'static str, service_name: String) {
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading(format!("{service_name} is protected"))
+            .body(format!(
+                "The system depends on {service_name}: without it you may be unable to log \
+                 in, authenticate or manage services until you reboot. Runkit will ask for \
+                 your password again before it goes ahead."
+            ))
+            .build();
+        dialog.add_response("cancel", "Cancel");
+        dialog.add_response("override", "Override Protection");
+        dialog.set_response_appearance("override", adw::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+
+        let controller = Rc::downgrade(self);
+        dialog.connect_response(Some("override"), move |_, _| {
+            if let Some(controller) = controller.upgrade() {
+                controller.run_and_wait(action, service_name.clone(), true);
+            }
+        });
+        dialog.present();
+    }
+
+    /// Wait for a start, stop or restart to take effect on a worker thread, so
+    /// a service that crashes right away is reported without freezing the UI.
+    /// With `override_protection` the action goes through runkitd's guard for
+    /// protected services.
+    fn run_and_wait(
+        self: &Rc<Self>,
+        action: &'static str,
+        service_name: String,
+        override_protection: bool,
+    ) {
+        let dispatcher = self.dispatcher.clone();
+        let (sender, receiver) = async_channel::bounded(1);
+        let (update_sender, update_receiver) = async_channel::unbounded();
+        let as_job = matches!(action, "start" | "stop" | "restart")
+            && self
+                .server_info
+                .borrow()
+                .as_ref()
+                .is_some_and(|info| info.supports("jobs"));
+        let service = service_name.clone();
+        let spawned = thread::Builder::new()
+            .name("runkit-action".into())
+            .spawn(move || {
+                let result = match action {
+                    _ if override_protection => {
+                        dispatcher.run_protected(action, &service, ACTION_WAIT)
+                    }
+                    "start-with-dependencies" => dispatcher.change_with_dependencies(
+                        &service,
+                        StateChange::Start,
+                        ACTION_WAIT,
+                    ),
+                    "stop-with-dependents" => dispatcher.change_with_dependencies(
+                        &service,
+                        StateChange::Stop,
+                        ACTION_WAIT,
+                    ),
+                    _ if as_job => {
+                        dispatcher.run_as_job(action, &service, ACTION_WAIT, &update_sender)
+                    }
+                    _ => dispatcher.run_and_wait(action, &service, ACTION_WAIT),
+                };
+                let _ = sender.send_blocking(result);
+            });
+        if let Err(err) = spawned {
+            self.finish_action(
+                action,
+                service_name,
+                Err(format!("Failed to start {action}: {err}")),
+            );
+            return;
+        }
+        self.widgets.show_loading(true);
+        let controller = Rc::downgrade(self);
+        glib::MainContext::default().spawn_local(async move {
+            // The worker drops its end of the updates once it has sent the result.
+            while let Ok(update) = update_receiver.recv().await {
+                let Some(controller) = controller.upgrade() else {
+                    return;
+                };
+                match update {
+                    JobUpdate::Started(job) => {
+                        controller.running_job.set(Some(job));
+                        controller.widgets.show_job("");
+                    }
+                    JobUpdate::Progress {
+                        done,
+                        total,
+                        message,
+                    } => {
+                        let text = if total > 1 {
+                            format!("{message} ({done} of {total})")
+                        } else {
+                            message
+                        };
+                        controller.widgets.show_job(&text);
+                    }
+                }
+            }
+            let Ok(result) = receiver.recv().await else {
+                return;
+            };
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            controller.running_job.set(None);
+            controller.widgets.show_loading(false);
+            controller.finish_action(action, service_name, result);
+        });
+    }
+
+    /// Ask runkitd to give up on the job the spinner is waiting for; the
+    /// action then finishes as cancelled.
+    fn cancel_running_job(self: &Rc<Self>) {
+        let Some(job) = self.running_job.take() else {
+            return;
+        };
+        self.widgets.loading_cancel.set_sensitive(false);
+        let dispatcher = self.dispatcher.clone();
+        let (sender, receiver) = async_channel::bounded(1);
+        let spawned = thread::Builder::new()
+            .name("runkit-cancel".into())
+            .spawn(move || {
+                let _ = sender.send_blocking(dispatcher.cancel_job(job));
+            });
+        if spawned.is_err() {
+            self.widgets.loading_cancel.set_sensitive(true);
+            return;
+        }
+        let controller = Rc::downgrade(self);
+        glib::MainContext::default().spawn_local(async move {
+            let Ok(result) = receiver.recv().await else {
+                return;
+            };
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            controller.widgets.loading_cancel.set_sensitive(true);
+            if let Err(err) = result {
+                controller.widgets.show_error(&err);
+            }
+        });
+    }
+
+    fn finish_action(
+        self: &Rc<Self>,
+        action: &str,
+        service_name: String,
+        result: Result<String, String>,
+    ) {
+        match result {
+            Ok(message) => {
+                // Record successful user action
+                {
+                    let mut activity_store = self.activity_store.borrow_mut();
+                    let event = ActivityEvent::new(ActivityEventType::UserAction {
+                        action: action.to_string(),
+                        success: true,
+                        error: None,
+                    });
+                    activity_store.add_event(&service_name, event);
+                }
+
+                let (entries_snapshot, notes_snapshot) = {
+                    let mut model = self.model.borrow_mut();
+                    if model.log_service.as_deref() != Some(service_name.as_str()) {
+                        model.log_service = Some(service_name.clone());
+                        model.log_entries.clear();
+                        model.log_error = None;
+                        model.activity_notes.clear();
+                    }
+                    model.log_error = None;
+                    model.activity_notes.insert(0, message.clone());
+                    if model.activity_notes.len() > 20 {
+                        model.activity_notes.truncate(20);
+                    }
+                    (model.log_entries.clone(), model.activity_notes.clone())
+                };
+                self.widgets
+                    .show_activity(&service_name, &entries_snapshot, &notes_snapshot);
+                self.request_refresh(true);
+            }
+            Err(err) => {
+                // Record failed user action
+                {
+                    let mut activity_store = self.activity_store.borrow_mut();
+                    let event = ActivityEvent::new(ActivityEventType::UserAction {
+                        action: action.to_string(),
+                        success: false,
+                        error: Some(err.to_string()),
+                    });
+                    activity_store.add_event(&service_name, event);
+                }
+
+                // authorize() prefixes every polkit failure other than a
+                // denial, e.g. an action that is not registered at all.
+                if err.contains("polkit") {
+                    self.show_setup_assistant(&err);
+                }
+
+                let error_message = format!("Operation failed: {err}");
+                let (entries_snapshot, notes_snapshot) = {
+                    let mut model = self.model.borrow_mut();
+                    if model.log_service.as_deref() != Some(service_name.as_str()) {
+                        model.log_service = Some(service_name.clone());
+                        model.log_entries.clear();
+                        model.log_error = None;
+                        model.activity_notes.clear();
+                    }
+                    model.log_error = Some(error_message.clone());
+                    model.activity_notes.insert(0, error_message.clone());
+                    if model.activity_notes.len() > 20 {
+                        model.activity_notes.truncate(20);
+                    }
+                    (model.log_entries.clone(), model.activity_notes.clone())
+                };
+                self.widgets
+                    .show_activity(&service_name, &entries_snapshot, &notes_snapshot);
+            }
+        }
+    }
+
+    /// Let the user change svlogd's rotation size, file count and interval.
+    fn edit_log_config(self: &Rc<Self>) {
+        if self.is_read_only() {
+            return;
+        }
+        let Some(service_name) = self.widgets.current_service() else {
+            return;
+        };
+        let config = match self.dispatcher.fetch_log_config(&service_name) {
+            Ok(config) => config,
+            Err(err) => {
+                self.widgets.show_error(&err);
+                return;
+            }
+        };
+
+        // Shown in the dialog's units; svlogd's defaults when the file leaves them unset.
+        let size_kib = config.size.unwrap_or(1_000_000) as f64 / 1024.0;
+        let num = f64::from(config.num.unwrap_or(10));
+        let timeout_hours = config.timeout.unwrap_or(0) as f64 / 3600.0;
+
+        let size_row = adw::SpinRow::with_range(0.0, 1_048_576.0, 64.0);
+        size_row.set_title("Rotate at size (KiB)");
+        size_row.set_subtitle("0 never rotates by size");
+        size_row.set_value(size_kib.round());
+        let num_row = adw::SpinRow::with_range(0.0, 1000.0, 1.0);
+        num_row.set_title("Old files to keep");
+        num_row.set_subtitle("0 keeps every rotated file");
+        num_row.set_value(num);
+        let timeout_row = adw::SpinRow::with_range(0.0, 8760.0, 1.0);
+        timeout_row.set_title("Rotate every (hours)");
+        timeout_row.set_subtitle("0 only rotates by size");
+        timeout_row.set_value(timeout_hours.round());
+
+        let list = gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .build();
+        list.add_css_class("boxed-list");
+        list.append(&size_row);
+        list.append(&num_row);
+        list.append(&timeout_row);
+
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading(format!("Log Rotation for {service_name}"))
+            .build();
+        dialog.set_extra_child(Some(&list));
+        dialog.add_response("cancel", "Cancel");
+        dialog.add_response("save", "Save");
+        dialog.set_response_appearance("save", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("save"));
+        dialog.set_close_response("cancel");
+
+        let controller = Rc::downgrade(self);
+        dialog.connect_response(None, move |_, response| {
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            if response != "save" {
+                return;
+            }
+            // Only touch values the user changed, so unrounded settings survive.
+            let mut updated = config.clone();
+            if size_row.value() != size_kib.round() {
+                updated.size = Some(size_row.value() as u64 * 1024);
+            }
+            if num_row.value() != num {
+                updated.num = Some(num_row.value() as u32);
+            }
+            if timeout_row.value() != timeout_hours.round() {
+                updated.timeout = Some(timeout_row.value() as u64 * 3600);
+            }
+
+            match controller
+                .dispatcher
+                .set_log_config(&service_name, &updated)
+            {
+                Ok(message) => controller.widgets.show_toast(&message),
+                Err(err) => controller.widgets.show_error(&err),
+            }
+        });
+        dialog.present();
+    }
+
+    /// Per-runlevel switches for the selected service, plus a way to make another
+    /// runlevel current.
+    fn show_runlevels(self: &Rc<Self>) {
+        let Some(service_name) = self.widgets.current_service() else {
+            return;
+        };
+        let runlevels = match self.dispatcher.list_runlevels() {
+            Ok(runlevels) => runlevels,
+            Err(err) => {
+                self.widgets.show_error(&err);
+                return;
+            }
+        };
+
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading(format!("Runlevels for {service_name}"))
+            .body("runsvdir starts the service in every runlevel switched on here.")
+            .build();
+        let list = gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .build();
+        list.add_css_class("boxed-list");
+
+        for runlevel in runlevels {
+            let row = adw::ActionRow::builder()
+                .title(&runlevel.name)
+                .subtitle(if runlevel.current {
+                    "Current runlevel".to_string()
+                } else {
+                    format!("{} service(s)", runlevel.services.len())
+                })
+                .build();
+
+            let switch_button = gtk::Button::builder()
+                .label("Switch")
+                .valign(gtk::Align::Center)
+                .sensitive(!runlevel.current && !self.is_read_only())
+                .tooltip_text(format!("Make {} the current runlevel", runlevel.name))
+                .build();
+            {
+                let controller = Rc::downgrade(self);
+                let dialog = dialog.clone();
+                let name = runlevel.name.clone();
+                switch_button.connect_clicked(move |_| {
+                    if let Some(controller) = controller.upgrade() {
+                        dialog.close();
+                        controller.confirm_switch_runlevel(&name);
+                    }
+                });
+            }
+
+            let toggle = gtk::Switch::builder()
+                .active(runlevel.services.contains(&service_name))
+                .valign(gtk::Align::Center)
+                .sensitive(!self.is_read_only())
+                .build();
+            {
+                let controller = Rc::downgrade(self);
+                let service_name = service_name.clone();
+                let name = runlevel.name.clone();
+                // Set while flipping the switch back after a failure, which
+                // emits state-set again.
+                let reverting = Rc::new(Cell::new(false));
+                toggle.connect_state_set(move |toggle, state| {
+                    if reverting.get() {
+                        toggle.set_state(state);
+                        return glib::Propagation::Stop;
+                    }
+                    let Some(controller) = controller.upgrade() else {
+                        return glib::Propagation::Stop;
+                    };
+                    match controller
+                        .dispatcher
+                        .set_enabled_in_runlevel(&service_name, &name, state)
+                    {
+                        Ok(message) => {
+                            controller.widgets.show_toast(&message);
+                            toggle.set_state(state);
+                            controller.request_refresh(true);
+                        }
+                        Err(err) => {
+                            controller.widgets.show_error(&err);
+                            reverting.set(true);
+                            toggle.set_active(!state);
+                            reverting.set(false);
+                        }
+                    }
+                    glib::Propagation::Stop
+                });
+            }
+
+            row.add_suffix(&switch_button);
+            row.add_suffix(&toggle);
+            row.set_activatable_widget(Some(&toggle));
+            list.append(&row);
+        }
+
+        dialog.set_extra_child(Some(&list));
+        dialog.add_response("close", "Close");
+        dialog.set_close_response("close");
+        dialog.present();
+    }
+
+    fn confirm_switch_runlevel(self: &Rc<Self>, runlevel: &str) {
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading(format!("Switch to {runlevel}?"))
+            .body("Services not linked into this runlevel will be stopped, including your session if the display manager is not among them.")
+            .build();
+        dialog.add_response("cancel", "Cancel");
+        dialog.add_response("switch", "Switch");
+        dialog.set_response_appearance("switch", adw::ResponseAppearance::Destructive);
+        dialog.set_close_response("cancel");
+
+        let controller = Rc::downgrade(self);
+        let runlevel = runlevel.to_string();
+        dialog.connect_response(None, move |_, response| {
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            if response != "switch" {
+                return;
+            }
+            match controller.dispatcher.switch_runlevel(&runlevel) {
+                Ok(message) => controller.widgets.show_toast(&message),
+                Err(err) => controller.widgets.show_error(&err),
+            }
+            controller.request_refresh(true);
+        });
+        dialog.present();
+    }
+
+    /// A small "top" for the selected service, resampled while the dialog is open.
+    fn show_metrics(self: &Rc<Self>) {
+        let Some(service_name) = self.widgets.current_service() else {
+            return;
+        };
+
+        let row = |title: &str| {
+            adw::ActionRow::builder()
+                .title(title)
+                .subtitle("…")
+                .subtitle_selectable(true)
+                .build()
+        };
+        let cpu_row = row("CPU");
+        let memory_row = row("Memory (resident)");
+        let fds_row = row("Open files");
+        let threads_row = row("Threads");
+        let processes_row = row("Processes");
+        let list = gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .build();
+        list.add_css_class("boxed-list");
+        for row in [
+            &cpu_row,
+            &memory_row,
+            &fds_row,
+            &threads_row,
+            &processes_row,
+        ] {
+            list.append(row);
+        }
+
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading(format!("Resource Usage of {service_name}"))
+            .body("Totals for the main process and all of its children.")
+            .build();
+        let content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(12)
+            .build();
+        content.append(&list);
+        if let Ok(Some(tree)) = self.dispatcher.fetch_process_tree(&service_name) {
+            let processes = gtk::ListBox::builder()
+                .selection_mode(gtk::SelectionMode::None)
+                .build();
+            processes.add_css_class("boxed-list");
+            append_process_rows(&processes, &tree, 0);
+            content.append(
+                &gtk::ScrolledWindow::builder()
+                    .hscrollbar_policy(gtk::PolicyType::Never)
+                    .propagate_natural_height(true)
+                    .max_content_height(280)
+                    .child(&processes)
+                    .build(),
+            );
+        }
+        dialog.set_extra_child(Some(&content));
+        dialog.add_response("close", "Close");
+        dialog.set_close_response("close");
+
+        // The timer stops itself once the dialog closes or the service stops.
+        let closed = Rc::new(Cell::new(false));
+        let update = {
+            let controller = Rc::downgrade(self);
+            let dialog = dialog.clone();
+            let closed = Rc::clone(&closed);
+            move || {
+                let Some(controller) = controller.upgrade() else {
+                    return ControlFlow::Break;
+                };
+                if closed.get() {
+                    return ControlFlow::Break;
+                }
+                match controller.dispatcher.fetch_metrics(&service_name) {
+                    Ok(Some(usage)) => {
+                        cpu_row.set_subtitle(&format!("{:.1}%", usage.cpu_percent));
+                        memory_row.set_subtitle(&formatting::format_bytes(usage.rss_bytes));
+                        fds_row.set_subtitle(
+                            &usage
+                                .open_fds
+                                .map_or_else(|| "Unknown".to_string(), |fds| fds.to_string()),
+                        );
+                        threads_row.set_subtitle(&usage.threads.to_string());
+                        processes_row
+                            .set_subtitle(&format!("{} (main PID {})", usage.processes, usage.pid));
+                        ControlFlow::Continue
+                    }
+                    Ok(None) => {
+                        dialog.set_body(&format!("{service_name} is not running."));
+                        ControlFlow::Break
+                    }
+                    Err(err) => {
+                        dialog.set_body(&err);
+                        ControlFlow::Break
+                    }
+                }
+            }
+        };
+        if update() == ControlFlow::Continue {
+            glib::timeout_add_seconds_local(METRICS_REFRESH_INTERVAL, update);
+        }
+        dialog.connect_response(None, move |_, _| closed.set(true));
+        dialog.present();
+    }
+
+    fn export_definition(self: &Rc<Self>) {
+        if self.is_read_only() {
+            return;
+        }
+        let Some(service_name) = self.widgets.current_service() else {
+            return;
+        };
+
+        let chooser = gtk::FileChooserNative::new(
+            Some("Export Service Definition"),
+            Some(&self.widgets.window),
+            gtk::FileChooserAction::Save,
+            Some("Export"),
+            Some("Cancel"),
+        );
+        chooser.set_modal(true);
+        chooser.set_current_name(&format!("{service_name}.tar.gz"));
+
+        let controller = Rc::downgrade(self);
+        chooser.connect_response(move |chooser, response| {
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            controller.file_chooser.borrow_mut().take();
+            if response != gtk::ResponseType::Accept {
+                return;
+            }
+            let Some(path) = chooser.file().and_then(|file| file.path()) else {
+                return;
+            };
+
+            let result = fs::File::create(&path)
+                .map_err(|err| format!("Unable to create {}: {err}", path.display()))
+                .and_then(|file| {
+                    controller
+                        .dispatcher
+                        .export_definition(&service_name, &file)
+                });
+            match result {
+                Ok(message) => controller.widgets.show_toast(&message),
+                Err(err) => {
+                    let _ = fs::remove_file(&path);
+                    controller
+                        .widgets
+                        .show_error(&format!("Export failed: {err}"));
+                }
+            }
+        });
+
+        chooser.show();
+        self.file_chooser.borrow_mut().replace(chooser);
+    }
+
+    /// Save the recent logs of every failed service to a timestamped archive
+    /// in `~/runkit-logs`, read on a worker thread.
+    fn archive_failed_logs(self: &Rc<Self>) {
+        let Some(dir) = default_log_archive_dir() else {
+            self.widgets
+                .show_error("Archiving logs failed: HOME is not set");
+            return;
+        };
+        let dispatcher = self.dispatcher.clone();
+        let (sender, receiver) = async_channel::bounded(1);
+        let spawned = thread::Builder::new()
+            .name("runkit-log-archive".into())
+            .spawn(move || {
+                let result = dispatcher.failed_logs(FAILED_LOG_LINES).and_then(|logs| {
+                    if logs.is_empty() {
+                        return Ok(None);
+                    }
+                    write_log_archive(&dir, &logs)
+                        .map(|archive| Some((logs.len(), archive)))
+                        .map_err(|err| err.to_string())
+                });
+                let _ = sender.send_blocking(result);
+            });
+        if let Err(err) = spawned {
+            self.widgets
+                .show_error(&format!("Archiving logs failed: {err}"));
+            return;
+        }
+        self.widgets.show_loading(true);
+        let controller = Rc::downgrade(self);
+        glib::MainContext::default().spawn_local(async move {
+            let Ok(result) = receiver.recv().await else {
+                return;
+            };
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            controller.widgets.show_loading(false);
+            match result {
+                Ok(Some((count, archive))) => controller.widgets.show_toast(&format!(
+                    "Saved the logs of {count} failed service(s) to {}",
+                    archive.display()
+                )),
+                Ok(None) => controller.widgets.show_toast("No service has failed"),
+                Err(err) => controller
+                    .widgets
+                    .show_error(&format!("Archiving logs failed: {err}")),
+            }
+        });
+    }
+
+    /// Save which services are enabled and have a `down` file, as TOML for a
+    /// `.toml` name and JSON otherwise.
+    fn save_profile(self: &Rc<Self>) {
+        let chooser = gtk::FileChooserNative::new(
+            Some("Save Profile"),
+            Some(&self.widgets.window),
+            gtk::FileChooserAction::Save,
+            Some("Save"),
+            Some("Cancel"),
+        );
+        chooser.set_modal(true);
+        chooser.set_current_name("runkit-profile.toml");
+
+        let controller = Rc::downgrade(self);
+        chooser.connect_response(move |chooser, response| {
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            controller.file_chooser.borrow_mut().take();
+            if response != gtk::ResponseType::Accept {
+                return;
+            }
+            let Some(path) = chooser.file().and_then(|file| file.path()) else {
+                return;
+            };
+
+            let format = match path.extension().and_then(|extension| extension.to_str()) {
+                Some("toml") => "toml",
+                _ => "json",
+            };
+            let result = controller
+                .dispatcher
+                .fetch_profile(format)
+                .and_then(|contents| {
+                    fs::write(&path, contents)
+                        .map_err(|err| format!("Unable to write {}: {err}", path.display()))
+                });
+            match result {
+                Ok(()) => controller
+                    .widgets
+                    .show_toast(&format!("Saved the profile to {}", path.display())),
+                Err(err) => controller
+                    .widgets
+                    .show_error(&format!("Saving the profile failed: {err}")),
+            }
+        });
+
+        chooser.show();
+        self.file_chooser.borrow_mut().replace(chooser);
+    }
+
+    /// Pick a saved profile, show what restoring it would change, and apply it
+    /// with a single authorization once confirmed.
+    fn restore_profile(self: &Rc<Self>) {
+        if self.is_read_only() {
+            return;
+        }
+        let chooser = gtk::FileChooserNative::new(
+            Some("Restore Profile"),
+            Some(&self.widgets.window),
+            gtk::FileChooserAction::Open,
+            Some("Open"),
+            Some("Cancel"),
+        );
+        chooser.set_modal(true);
+        let filter = gtk::FileFilter::new();
+        filter.set_name(Some("Profiles"));
+        filter.add_pattern("*.toml");
+        filter.add_pattern("*.json");
+        chooser.add_filter(&filter);
+
+        let controller = Rc::downgrade(self);
+        chooser.connect_response(move |chooser, response| {
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            controller.file_chooser.borrow_mut().take();
+            if response != gtk::ResponseType::Accept {
+                return;
+            }
+            let Some(path) = chooser.file().and_then(|file| file.path()) else {
+                return;
+            };
+            let result = fs::read_to_string(&path)
+                .map_err(|err| format!("Unable to open {}: {err}", path.display()))
+                .and_then(|contents| {
+                    let plan = controller.dispatcher.restore_profile(&contents, true)?;
+                    Ok((contents, plan))
+                });
+            match result {
+                Ok((contents, plan)) => controller.confirm_restore_profile(contents, &plan),
+                Err(err) => controller
+                    .widgets
+                    .show_error(&format!("Reading the profile failed: {err}")),
+            }
+        });
+
+        chooser.show();
+        self.file_chooser.borrow_mut().replace(chooser);
+    }
+
+    fn confirm_restore_profile(self: &Rc<Self>, contents: String, plan: &str) {
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading("Restore profile?")
+            .body(plan)
+            .build();
+        dialog.add_response("cancel", "Cancel");
+        dialog.add_response("restore", "Restore");
+        dialog.set_response_appearance("restore", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("restore"));
+        dialog.set_close_response("cancel");
+
+        let controller = Rc::downgrade(self);
+        dialog.connect_response(None, move |_, response| {
+            if response != "restore" {
+                return;
+            }
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            match controller.dispatcher.restore_profile(&contents, false) {
+                Ok(message) => controller.widgets.show_toast(&message),
+                Err(err) => controller
+                    .widgets
+                    .show_error(&format!("Restoring the profile failed: {err}")),
+            }
+            controller.request_refresh(true);
+        });
+        dialog.present();
+    }
+
+    fn import_definition(self: &Rc<Self>) {
+        if self.is_read_only() {
+            return;
+        }
+        let chooser = gtk::FileChooserNative::new(
+            Some("Import Service Definition"),
+            Some(&self.widgets.window),
+            gtk::FileChooserAction::Open,
+            Some("Import"),
+            Some("Cancel"),
+        );
+        chooser.set_modal(true);
+        let filter = gtk::FileFilter::new();
+        filter.set_name(Some("Service archives"));
+        filter.add_pattern("*.tar.gz");
+        filter.add_pattern("*.tgz");
+        chooser.add_filter(&filter);
+
+        let controller = Rc::downgrade(self);
+        chooser.connect_response(move |chooser, response| {
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            controller.file_chooser.borrow_mut().take();
+            if response != gtk::ResponseType::Accept {
+                return;
+            }
+            let Some(path) = chooser.file().and_then(|file| file.path()) else {
+                return;
+            };
+
+            let result = fs::File::open(&path)
+                .map_err(|err| format!("Unable to open {}: {err}", path.display()))
+                .and_then(|file| controller.dispatcher.import_definition(&file, false));
+            match result {
+                Ok(message) => {
+                    controller.widgets.show_toast(&message);
+                    controller.request_refresh(true);
+                }
+                Err(err) => controller
+                    .widgets
+                    .show_error(&format!("Import failed: {err}")),
+            }
+        });
+
+        chooser.show();
+        self.file_chooser.borrow_mut().replace(chooser);
+    }
+
+    /// Ask for a template bundle's file or URL and load it off the main
+    /// thread, then let the user fill in its variables.
+    fn open_template_bundle(self: &Rc<Self>) {
+        if self.is_read_only() {
+            return;
+        }
+        let source_entry = gtk::Entry::builder()
+            .placeholder_text("File or https:// URL")
+            .activates_default(true)
+            .hexpand(true)
+            .build();
+        let browse = gtk::Button::builder()
+            .icon_name("document-open-symbolic")
+            .tooltip_text("Choose a file")
+            .build();
+        let content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(6)
+            .build();
+        content.append(&source_entry);
+        content.append(&browse);
+
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading("New Service from Template")
+            .body(
+                "A template bundle is a service definition shared as a JSON file, with \
+                 the parts that differ between machines left for you to fill in.",
+            )
+            .extra_child(&content)
+            .build();
+        dialog.add_response("cancel", "Cancel");
+        dialog.add_response("open", "Open");
+        dialog.set_response_appearance("open", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("open"));
+        dialog.set_close_response("cancel");
+
+        {
+            let controller = Rc::downgrade(self);
+            let entry = source_entry.clone();
+            let parent = dialog.clone();
+            browse.connect_clicked(move |_| {
+                let Some(controller) = controller.upgrade() else {
+                    return;
+                };
+                let chooser = gtk::FileChooserNative::new(
+                    Some("Open Template Bundle"),
+                    Some(&parent),
+                    gtk::FileChooserAction::Open,
+                    Some("Open"),
+                    Some("Cancel"),
+                );
+                chooser.set_modal(true);
+                let filter = gtk::FileFilter::new();
+                filter.set_name(Some("Template bundles"));
+                filter.add_pattern("*.json");
+                chooser.add_filter(&filter);
+                let entry = entry.clone();
+                let weak = Rc::downgrade(&controller);
+                chooser.connect_response(move |chooser, response| {
+                    if let Some(controller) = weak.upgrade() {
+                        controller.file_chooser.borrow_mut().take();
+                    }
+                    if response == gtk::ResponseType::Accept
+                        && let Some(path) = chooser.file().and_then(|file| file.path())
+                    {
+                        entry.set_text(&path.to_string_lossy());
+                    }
+                });
+                chooser.show();
+                controller.file_chooser.borrow_mut().replace(chooser);
+            });
+        }
+
+        let controller = Rc::downgrade(self);
+        dialog.connect_response(Some("open"), move |_, _| {
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            let source = source_entry.text().trim().to_string();
+            if source.is_empty() {
+                return;
+            }
+            let (sender, receiver) = async_channel::bounded(1);
+            let spawned = thread::Builder::new()
+                .name("runkit-template".into())
+                .spawn(move || {
+                    let _ = sender.send_blocking(load_template_bundle(&source));
+                });
+            if let Err(err) = spawned {
+                controller
+                    .widgets
+                    .show_error(&format!("Failed to load the template: {err}"));
+                return;
+            }
+            controller.widgets.show_loading(true);
+            let weak = Rc::downgrade(&controller);
+            glib::MainContext::default().spawn_local(async move {
+                let Ok(result) = receiver.recv().await else {
+                    return;
+                };
+                let Some(controller) = weak.upgrade() else {
+                    return;
+                };
+                controller.widgets.show_loading(false);
+                match result {
+                    Ok((text, bundle)) => controller.create_from_template(text, bundle),
+                    Err(err) => controller
+                        .widgets
+                        .show_error(&format!("Loading the template failed: {err}")),
+                }
+            });
+        });
+        dialog.present();
+    }
+
+    /// Show what a template bundle is and the run script it generates, and ask
+    /// for the new service's name and the bundle's variables.
+    fn create_from_template(self: &Rc<Self>, text: String, bundle: TemplateBundle) {
+        let mut body = bundle.summary.clone();
+        if let Some(description) = &bundle.description {
+            body.push_str(&format!("\n\n{description}"));
+        }
+        let credits: Vec<&str> = [&bundle.author, &bundle.homepage, &bundle.license]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        if !credits.is_empty() {
+            body.push_str(&format!("\n\n{}", credits.join(" · ")));
+        }
+
+        let name_entry = gtk::Entry::builder()
+            .text(bundle.name.as_str())
+            .placeholder_text("New service name")
+            .activates_default(true)
+            .build();
+        let content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(12)
+            .build();
+        content.append(&name_entry);
+        let variable_entries: Vec<(String, gtk::Entry)> = bundle
+            .variables
+            .iter()
+            .map(|variable| {
+                let entry = gtk::Entry::builder()
+                    .placeholder_text(match &variable.default {
+                        Some(default) => format!("{} (default {default})", variable.name),
+                        None => variable.name.clone(),
+                    })
+                    .activates_default(true)
+                    .build();
+                if !variable.description.is_empty() {
+                    entry.set_tooltip_text(Some(&variable.description));
+                }
+                content.append(&entry);
+                (variable.name.clone(), entry)
+            })
+            .collect();
+        let script = gtk::TextView::builder()
+            .buffer(&gtk::TextBuffer::builder().text(bundle.run.as_str()).build())
+            .editable(false)
+            .monospace(true)
+            .top_margin(6)
+            .bottom_margin(6)
+            .left_margin(6)
+            .right_margin(6)
+            .build();
+        let scroller = gtk::ScrolledWindow::builder()
+            .child(&script)
+            .min_content_width(480)
+            .min_content_height(160)
+            .build();
+        scroller.add_css_class("card");
+        content.append(&scroller);
+        let enable_check = gtk::CheckButton::with_label("Enable the new service");
+        content.append(&enable_check);
+
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading(format!("Create from {}", bundle.name))
+            .body(body)
+            .extra_child(&content)
+            .build();
+        dialog.add_response("cancel", "Cancel");
+        dialog.add_response("create", "Create");
+        dialog.set_response_appearance("create", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("create"));
+        dialog.set_close_response("cancel");
+
+        let controller = Rc::downgrade(self);
+        dialog.connect_response(Some("create"), move |_, _| {
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            let name = name_entry.text().trim().to_string();
+            let values: Vec<(String, String)> = variable_entries
+                .iter()
+                .filter(|(_, entry)| !entry.text().is_empty())
+                .map(|(variable, entry)| (variable.clone(), entry.text().to_string()))
+                .collect();
+            match controller.dispatcher.create_from_bundle(
+                &name,
+                &text,
+                &values,
+                enable_check.is_active(),
+            ) {
+                Ok(message) => {
+                    controller.widgets.show_toast(&message);
+                    controller.request_refresh(true);
+                }
+                Err(err) => controller
+                    .widgets
+                    .show_error(&format!("Creating {name} failed: {err}")),
+            }
+        });
+        dialog.present();
+    }
+
+    /// Edit one of the selected service's files in a simple text view. runkitd
+    /// keeps a backup of the old version; a shell syntax error is reported
+    /// and the save can then be forced.
+    fn edit_service_files(self: &Rc<Self>) {
+        if self.is_read_only() {
+            return;
+        }
+        let Some(service) = self.widgets.current_service() else {
+            return;
+        };
+
+        let file_picker = gtk::DropDown::from_strings(&EDITABLE_SERVICE_FILES);
+        let buffer = gtk::TextBuffer::new(None);
+        buffer.connect_changed(highlight::highlight_shell);
+        let text_view = gtk::TextView::builder()
+            .buffer(&buffer)
+            .monospace(true)
+            .top_margin(6)
+            .bottom_margin(6)
+            .left_margin(6)
+            .right_margin(6)
+            .build();
+        let scroller = gtk::ScrolledWindow::builder()
+            .child(&text_view)
+            .min_content_width(560)
+            .min_content_height(320)
+            .vexpand(true)
+            .build();
+        scroller.add_css_class("card");
+        let status = gtk::Label::builder()
+            .xalign(0.0)
+            .wrap(true)
+            .css_classes(["dim-label", "caption"])
+            .build();
+        let content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(6)
+            .build();
+        content.append(&file_picker);
+        content.append(&scroller);
+        content.append(&status);
+
+        let load = {
+            let controller = Rc::downgrade(self);
+            let service = service.clone();
+            let buffer = buffer.clone();
+            let status = status.clone();
+            move |file: &str| {
+                let Some(controller) = controller.upgrade() else {
+                    return;
+                };
+                match controller.dispatcher.read_service_file(&service, file) {
+                    Ok(Some(contents)) => {
+                        buffer.set_text(&contents);
+                        status.set_text("");
                     }
-                    // Check if PID changed while running (indicates restart)
-                    else if current_state == "running" && prev_pid != &current_pid {
-                        let event = ActivityEvent::new(ActivityEventType::StateChange {
-                            from_state: "running".to_string(),
-                            to_state: "running".to_string(),
-                            pid: current_pid,
-                        });
-                        activity_store.add_event(&service.name, event);
+                    Ok(None) => {
+                        buffer.set_text("");
+                        status.set_text(&format!("{file} does not exist yet; saving creates it."));
+                    }
+                    Err(err) => {
+                        buffer.set_text("");
+                        status.set_text(&err);
                     }
                 }
-
-                // Update the previous state
-                model
-                    .previous_states
-                    .insert(service.name.clone(), (current_state.to_string(), current_pid));
             }
+        };
+        load(EDITABLE_SERVICE_FILES[0]);
+        file_picker.connect_selected_notify(move |picker| {
+            if let Some(file) = EDITABLE_SERVICE_FILES.get(picker.selected() as usize) {
+                load(file);
+            }
+        });
 
-            // Mark initial load as completed
-            if is_initial_load {
-                model.initial_load_completed = true;
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading(format!("Edit {service}"))
+            .extra_child(&content)
+            .build();
+        dialog.add_response("cancel", "Cancel");
+        dialog.add_response("save", "Save");
+        dialog.set_response_appearance("save", adw::ResponseAppearance::Suggested);
+        dialog.set_close_response("cancel");
+
+        let controller = Rc::downgrade(self);
+        dialog.connect_response(None, move |_, response| {
+            if response != "save" {
+                return;
             }
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            let Some(file) = EDITABLE_SERVICE_FILES.get(file_picker.selected() as usize) else {
+                return;
+            };
+            let contents = buffer
+                .text(&buffer.start_iter(), &buffer.end_iter(), false)
+                .to_string();
+            controller.save_service_file(&service, file, contents, false);
+        });
+        dialog.present();
+    }
 
-            // Save previous states to disk
-            activity_store.update_previous_states(model.previous_states.clone());
+    /// Show the variables in the selected service's env dir, each with a
+    /// button to remove it, and entries to set one. Changes apply at the next
+    /// start of the service.
+    fn edit_environment(self: &Rc<Self>) {
+        if self.is_read_only() {
+            return;
         }
+        let Some(service) = self.widgets.current_service() else {
+            return;
+        };
 
-        let pending_selection = {
-            let prefs = self.preferences.borrow();
-            if prefs.startup_behavior == StartupBehavior::RememberLastService {
-                prefs.last_service.as_ref().and_then(|name| {
-                    services
-                        .iter()
-                        .find(|svc| svc.name == *name)
-                        .and_then(|svc| {
-                            if prefs.show_all_services || svc.enabled {
-                                Some(name.clone())
-                            } else {
-                                None
-                            }
-                        })
-                })
-            } else {
-                None
+        let list = gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .css_classes(["boxed-list"])
+            .build();
+        let status = gtk::Label::builder()
+            .xalign(0.0)
+            .wrap(true)
+            .css_classes(["dim-label", "caption"])
+            .build();
+        let name_entry = gtk::Entry::builder().placeholder_text("NAME").build();
+        let value_entry = gtk::Entry::builder()
+            .placeholder_text("value")
+            .hexpand(true)
+            .build();
+        let entries = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(6)
+            .build();
+        let set_button = gtk::Button::with_label("Set");
+        set_button.add_css_class("suggested-action");
+        entries.append(&name_entry);
+        entries.append(&value_entry);
+        entries.append(&set_button);
+        let content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(12)
+            .width_request(480)
+            .build();
+        content.append(&list);
+        content.append(&status);
+        content.append(&entries);
+
+        self.fill_environment_list(&service, &list, &status);
+
+        let heading = format!("Environment of {service}");
+        // Setting a variable keeps the dialog open for further changes.
+        let controller = Rc::downgrade(self);
+        set_button.connect_clicked(move |_| {
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            let name = name_entry.text().trim().to_string();
+            let value = value_entry.text().to_string();
+            match controller
+                .dispatcher
+                .set_env_variable(&service, &name, Some(&value))
+            {
+                Ok(message) => {
+                    controller.widgets.show_toast(&message);
+                    name_entry.set_text("");
+                    value_entry.set_text("");
+                }
+                Err(err) => controller
+                    .widgets
+                    .show_error(&format!("Setting {name} failed: {err}")),
+            }
+            controller.fill_environment_list(&service, &list, &status);
+        });
+
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading(heading)
+            .extra_child(&content)
+            .build();
+        dialog.add_response("close", "Close");
+        dialog.set_close_response("close");
+        dialog.present();
+    }
+
+    fn fill_environment_list(
+        self: &Rc<Self>,
+        service: &str,
+        list: &gtk::ListBox,
+        status: &gtk::Label,
+    ) {
+        while let Some(row) = list.first_child() {
+            list.remove(&row);
+        }
+        let dir = match self.dispatcher.fetch_env_dir(service) {
+            Ok(dir) => dir,
+            Err(err) => {
+                list.set_visible(false);
+                status.set_text(&err);
+                return;
             }
         };
-        {
-            let mut model = self.model.borrow_mut();
-            model.services = services;
-            model.pending_selection = pending_selection;
+        list.set_visible(!dir.variables.is_empty());
+        let mut note = if dir.variables.is_empty() {
+            format!("{} holds no variables.", dir.path)
+        } else {
+            dir.path.clone()
+        };
+        if !dir.used_by_run_script {
+            note.push_str(" The run script does not load this directory with chpst -e or envdir.");
+        }
+        status.set_text(&note);
+
+        for variable in dir.variables {
+            let row = adw::ActionRow::builder()
+                .title(variable.name.as_str())
+                .subtitle(
+                    variable
+                        .value
+                        .as_deref()
+                        .unwrap_or("(empty: removed from the environment)"),
+                )
+                .build();
+            let remove = gtk::Button::builder()
+                .icon_name("user-trash-symbolic")
+                .tooltip_text("Remove this variable")
+                .valign(gtk::Align::Center)
+                .css_classes(["flat"])
+                .build();
+            let controller = Rc::downgrade(self);
+            let service = service.to_string();
+            let rows = list.clone();
+            let status = status.clone();
+            remove.connect_clicked(move |_| {
+                let Some(controller) = controller.upgrade() else {
+                    return;
+                };
+                match controller
+                    .dispatcher
+                    .set_env_variable(&service, &variable.name, None)
+                {
+                    Ok(message) => controller.widgets.show_toast(&message),
+                    Err(err) => controller
+                        .widgets
+                        .show_error(&format!("Removing {} failed: {err}", variable.name)),
+                }
+                controller.fill_environment_list(&service, &rows, &status);
+            });
+            row.add_suffix(&remove);
+            list.append(&row);
         }
-        self.widgets
-            .update_status_summary(&self.model.borrow().services);
-        self.render_service_list();
-        self.refresh_logs_for_selection();
-        self.refresh_description_for_selection();
     }
 
-    fn render_service_list(self: &Rc<Self>) -> usize {
-        let show_all = self.preferences.borrow().show_all_services;
-        self.widgets.update_service_filter_toggle_label(show_all);
-        let filtered = {
-            let model = self.model.borrow();
-            let filter = model.filter_text.to_lowercase();
-            model
-                .services
-                .iter()
-                .filter(|service| {
-                    if !show_all && !service.enabled {
-                        return false;
-                    }
-                    if filter.is_empty() {
-                        return true;
-                    }
-                    service.name.to_lowercase().contains(&filter)
-                        || service
-                            .description
-                            .as_ref()
-                            .map(|d| d.to_lowercase().contains(&filter))
-                            .unwrap_or(false)
-                })
-                .cloned()
-                .collect::<Vec<_>>()
+    /// Show the selected service's file chosen on the detail page, once the
+    /// scripts expander is open.
+    fn load_definition_file(&self) {
+        if !self.widgets.definition_expander.is_expanded() {
+            return;
+        }
+        let Some(service) = self.widgets.current_service() else {
+            return;
         };
+        let file = self.widgets.selected_definition_file();
+        let contents = self.dispatcher.read_service_file(&service, file);
+        self.widgets.show_definition_file(file, contents);
+    }
 
-        let count = filtered.len();
+    /// Save an edited file; on a shell syntax error, offer to save it anyway.
+    fn save_service_file(
+        self: &Rc<Self>,
+        service: &str,
+        file: &str,
+        contents: String,
+        force: bool,
+    ) {
+        match self
+            .dispatcher
+            .write_service_file(service, file, &contents, force)
         {
-            let mut model = self.model.borrow_mut();
-            model.list_refreshing = true;
+            Ok(message) => {
+                self.widgets.show_toast(&message);
+                if self.widgets.definition_edit.is_active()
+                    && self.widgets.current_service().as_deref() == Some(service)
+                    && self.widgets.selected_definition_file() == file
+                {
+                    // Shows the saved file read-only again.
+                    self.widgets.definition_edit.set_active(false);
+                }
+                self.request_refresh(true);
+            }
+            Err(err) if !force && err.contains("shell syntax error") => {
+                let dialog = adw::MessageDialog::builder()
+                    .transient_for(&self.widgets.window)
+                    .modal(true)
+                    .heading(format!("{file} has a syntax error"))
+                    .body(format!(
+                        "{err}\n\nrunsv will fail to run the script until this is fixed."
+                    ))
+                    .build();
+                dialog.add_response("cancel", "Cancel");
+                dialog.add_response("force", "Save Anyway");
+                dialog.set_response_appearance("force", adw::ResponseAppearance::Destructive);
+                dialog.set_close_response("cancel");
+                let controller = Rc::downgrade(self);
+                let service = service.to_string();
+                let file = file.to_string();
+                dialog.connect_response(None, move |_, response| {
+                    if response == "force"
+                        && let Some(controller) = controller.upgrade()
+                    {
+                        controller.save_service_file(&service, &file, contents.clone(), true);
+                    }
+                });
+                dialog.present();
+            }
+            Err(err) => self
+                .widgets
+                .show_error(&format!("Saving {file} failed: {err}")),
         }
-        self.widgets.populate_list(&filtered);
-        let pending = {
-            let mut model = self.model.borrow_mut();
-            model.list_refreshing = false;
-            model.pending_selection.take()
+    }
+
+    /// Ask for a name and an optional substitution, then copy the selected
+    /// definition. A trailing number is bumped to suggest both, so
+    /// `agetty-tty1` offers `agetty-tty2` with `tty1` replaced by `tty2`.
+    fn clone_service(self: &Rc<Self>) {
+        if self.is_read_only() {
+            return;
+        }
+        let Some(source) = self.widgets.current_service() else {
+            return;
         };
-        if let Some(target) = pending {
-            self.widgets.select_service(&target);
+        let (suggested_name, suggested_from, suggested_to) = suggest_instance(&source);
+
+        let name_entry = gtk::Entry::builder()
+            .text(suggested_name.as_str())
+            .placeholder_text("New service name")
+            .activates_default(true)
+            .build();
+        let from_entry = gtk::Entry::builder()
+            .text(suggested_from.as_str())
+            .placeholder_text("Replace")
+            .hexpand(true)
+            .build();
+        let to_entry = gtk::Entry::builder()
+            .text(suggested_to.as_str())
+            .placeholder_text("With")
+            .hexpand(true)
+            .build();
+        let substitution_row = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(6)
+            .build();
+        substitution_row.append(&from_entry);
+        substitution_row.append(&to_entry);
+        let enable_check = gtk::CheckButton::with_label("Enable the new service");
+        let content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(12)
+            .build();
+        content.append(&name_entry);
+        content.append(&substitution_row);
+        content.append(&enable_check);
+
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading(format!("Clone {source}"))
+            .body(
+                "The copy shares symlinked scripts with the original. The replacement \
+                 is applied to run, finish, check and conf.",
+            )
+            .extra_child(&content)
+            .build();
+        dialog.add_response("cancel", "Cancel");
+        dialog.add_response("clone", "Clone");
+        dialog.set_response_appearance("clone", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("clone"));
+        dialog.set_close_response("cancel");
+
+        let controller = Rc::downgrade(self);
+        dialog.connect_response(None, move |_, response| {
+            if response != "clone" {
+                return;
+            }
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            let name = name_entry.text().trim().to_string();
+            let from = from_entry.text().to_string();
+            let substitutions: Vec<(String, String)> = if from.is_empty() {
+                Vec::new()
+            } else {
+                vec![(from, to_entry.text().to_string())]
+            };
+            match controller.dispatcher.instantiate_service(
+                &source,
+                &name,
+                &substitutions,
+                enable_check.is_active(),
+            ) {
+                Ok(message) => {
+                    controller.widgets.show_toast(&message);
+                    controller.request_refresh(true);
+                }
+                Err(err) => controller
+                    .widgets
+                    .show_error(&format!("Clone failed: {err}")),
+            }
+        });
+        dialog.present();
+    }
+
+    /// Offer to silence runkitd's alerts for a while, or to resume them early.
+    fn show_alert_silence(self: &Rc<Self>) {
+        if self.is_read_only() {
+            return;
         }
-        if self.widgets.current_service().is_none() {
-            let mut model = self.model.borrow_mut();
-            model.log_service = None;
-            model.log_entries.clear();
-            model.log_error = None;
-            model.activity_notes.clear();
+        let silenced_until = match self.dispatcher.alert_silence() {
+            Ok(until) => until,
+            Err(err) => {
+                self.widgets.show_error(&err);
+                return;
+            }
+        };
+
+        let body = match silenced_until {
+            Some(until) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_secs())
+                    .unwrap_or_default();
+                format!(
+                    "Alerts are silenced for another {}. Choose a new period or resume them now.",
+                    format_duration(Duration::from_secs(until.saturating_sub(now)))
+                )
+            }
+            None => "No notifications, webhooks or other alerts are sent while silenced. \
+                     Services keep being supervised as usual."
+                .to_string(),
+        };
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading("Silence Alerts")
+            .body(body)
+            .build();
+        dialog.add_response("cancel", "Cancel");
+        if silenced_until.is_some() {
+            dialog.add_response("0", "Resume Now");
         }
-        count
+        dialog.add_response("30", "30 Minutes");
+        dialog.add_response("60", "1 Hour");
+        dialog.add_response("240", "4 Hours");
+        dialog.set_close_response("cancel");
+
+        let controller = Rc::downgrade(self);
+        dialog.connect_response(None, move |_, response| {
+            let Ok(minutes) = response.parse::<u32>() else {
+                return;
+            };
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            match controller.dispatcher.silence_alerts(minutes) {
+                Ok(message) => controller.widgets.show_toast(&message),
+                Err(err) => controller.widgets.show_error(&err),
+            }
+        });
+        dialog.present();
     }
 
-    fn trigger_action(self: &Rc<Self>, action: &'static str) {
-        if let Some(service_name) = self.widgets.current_service() {
-            let allow_cached = {
-                let prefs = self.preferences.borrow();
-                !prefs.require_password
-            };
-            match self.dispatcher.run(action, &service_name, allow_cached) {
-                Ok(message) => {
-                    // Record successful user action
-                    {
-                        let mut activity_store = self.activity_store.borrow_mut();
-                        let event = ActivityEvent::new(ActivityEventType::UserAction {
-                            action: action.to_string(),
-                            success: true,
-                            error: None,
-                        });
-                        activity_store.add_event(&service_name, event);
-                    }
+    /// Show runit's stage logs, one at a time, with as many lines as the
+    /// service log view.
+    fn show_stage_logs(self: &Rc<Self>) {
+        let logs = match self.dispatcher.stage_logs() {
+            Ok(logs) => logs,
+            Err(err) => {
+                self.widgets.show_error(&err);
+                return;
+            }
+        };
+        if logs.is_empty() {
+            self.widgets
+                .show_toast("runit's boot stages are not logged on this system");
+            return;
+        }
 
-                    let (entries_snapshot, notes_snapshot) = {
-                        let mut model = self.model.borrow_mut();
-                        if model.log_service.as_deref() != Some(service_name.as_str()) {
-                            model.log_service = Some(service_name.clone());
-                            model.log_entries.clear();
-                            model.log_error = None;
-                            model.activity_notes.clear();
-                        }
-                        model.log_error = None;
-                        model.activity_notes.insert(0, message.clone());
-                        if model.activity_notes.len() > 20 {
-                            model.activity_notes.truncate(20);
-                        }
-                        (model.log_entries.clone(), model.activity_notes.clone())
-                    };
-                    self.widgets
-                        .show_activity(&service_name, &entries_snapshot, &notes_snapshot);
-                    self.request_refresh(true);
-                }
-                Err(err) => {
-                    // Record failed user action
-                    {
-                        let mut activity_store = self.activity_store.borrow_mut();
-                        let event = ActivityEvent::new(ActivityEventType::UserAction {
-                            action: action.to_string(),
-                            success: false,
-                            error: Some(err.to_string()),
-                        });
-                        activity_store.add_event(&service_name, event);
+        let names: Vec<&str> = logs.iter().map(String::as_str).collect();
+        let log_picker = gtk::DropDown::from_strings(&names);
+        let buffer = gtk::TextBuffer::new(None);
+        let text_view = gtk::TextView::builder()
+            .buffer(&buffer)
+            .editable(false)
+            .cursor_visible(false)
+            .monospace(true)
+            .top_margin(6)
+            .bottom_margin(6)
+            .left_margin(6)
+            .right_margin(6)
+            .build();
+        let scroller = gtk::ScrolledWindow::builder()
+            .child(&text_view)
+            .min_content_width(640)
+            .min_content_height(360)
+            .vexpand(true)
+            .build();
+        scroller.add_css_class("card");
+        let content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(6)
+            .build();
+        content.append(&log_picker);
+        content.append(&scroller);
+
+        let load = {
+            let controller = Rc::downgrade(self);
+            let buffer = buffer.clone();
+            move |log: &str| {
+                let Some(controller) = controller.upgrade() else {
+                    return;
+                };
+                let (lines, format) = {
+                    let prefs = controller.preferences.borrow();
+                    (prefs.log_lines.max(1) as usize, prefs.timestamp_format)
+                };
+                match controller.dispatcher.fetch_logs(log, lines, None) {
+                    Ok(entries) if entries.is_empty() => buffer.set_text("Nothing logged yet."),
+                    Ok(entries) => {
+                        let text: Vec<String> = entries
+                            .iter()
+                            .map(|entry| formatting::format_log_entry(entry, format))
+                            .collect();
+                        buffer.set_text(&text.join("\n"));
                     }
+                    Err(err) => buffer.set_text(&err),
+                }
+            }
+        };
+        load(&logs[0]);
+        log_picker.connect_selected_notify(move |picker| {
+            if let Some(log) = logs.get(picker.selected() as usize) {
+                load(log);
+            }
+        });
+
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading("Boot Logs")
+            .body("Read from the loggers of runit's stages, newest lines last.")
+            .extra_child(&content)
+            .build();
+        dialog.add_response("close", "Close");
+        dialog.set_default_response(Some("close"));
+        dialog.present();
+    }
+
+    /// List trashed definitions with a Restore button each.
+    fn show_trash(self: &Rc<Self>) {
+        if self.is_read_only() {
+            return;
+        }
+        let entries = match self.dispatcher.list_trash() {
+            Ok(entries) => entries,
+            Err(err) => {
+                self.widgets.show_error(&err);
+                return;
+            }
+        };
+        if entries.is_empty() {
+            self.widgets.show_toast("The trash is empty");
+            return;
+        }
 
-                    let error_message = format!("Operation failed: {err}");
-                    let (entries_snapshot, notes_snapshot) = {
-                        let mut model = self.model.borrow_mut();
-                        if model.log_service.as_deref() != Some(service_name.as_str()) {
-                            model.log_service = Some(service_name.clone());
-                            model.log_entries.clear();
-                            model.log_error = None;
-                            model.activity_notes.clear();
-                        }
-                        model.log_error = Some(error_message.clone());
-                        model.activity_notes.insert(0, error_message.clone());
-                        if model.activity_notes.len() > 20 {
-                            model.activity_notes.truncate(20);
-                        }
-                        (model.log_entries.clone(), model.activity_notes.clone())
-                    };
-                    self.widgets
-                        .show_activity(&service_name, &entries_snapshot, &notes_snapshot);
+        let dialog = adw::MessageDialog::builder()
+            .transient_for(&self.widgets.window)
+            .modal(true)
+            .heading("Deleted Services")
+            .body("Restoring puts the definition back and re-enables it if it was enabled.")
+            .build();
+
+        let list = gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .build();
+        list.add_css_class("boxed-list");
+        for entry in entries {
+            let deleted = TimestampFormat::new(TimestampStyle::Local, false)
+                .render(Some(entry.deleted_at), None, None)
+                .unwrap_or_default();
+            let row = adw::ActionRow::builder()
+                .title(entry.service.as_str())
+                .subtitle(if entry.was_enabled {
+                    format!("Deleted {deleted} · was enabled")
+                } else {
+                    format!("Deleted {deleted}")
+                })
+                .build();
+            let restore = gtk::Button::builder()
+                .label("Restore")
+                .valign(gtk::Align::Center)
+                .build();
+            let controller = Rc::downgrade(self);
+            let dialog_for_button = dialog.clone();
+            restore.connect_clicked(move |_| {
+                let Some(controller) = controller.upgrade() else {
+                    return;
+                };
+                dialog_for_button.close();
+                match controller.dispatcher.restore_service(&entry.id) {
+                    Ok(message) => {
+                        controller.widgets.show_toast(&message);
+                        controller.request_refresh(true);
+                    }
+                    Err(err) => controller
+                        .widgets
+                        .show_error(&format!("Restore failed: {err}")),
                 }
-            }
+            });
+            row.add_suffix(&restore);
+            list.append(&row);
         }
+
+        let scroller = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .propagate_natural_height(true)
+            .max_content_height(360)
+            .child(&list)
+            .build();
+        dialog.set_extra_child(Some(&scroller));
+        dialog.add_response("close", "Close");
+        dialog.set_default_response(Some("close"));
+        dialog.present();
     }
 
     fn request_refresh(self: &Rc<Self>, silent: bool) {
@@ -746,12 +4054,74 @@ impl AppController {
             Ok(services) => self.update_services(services),
             Err(err) => self.widgets.show_error(&err),
         }
+        self.refresh_health();
+    }
+
+    /// Show either the system's services or the user's own, which are managed
+    /// in-process instead of through runkitd.
+    fn switch_scope(self: &Rc<Self>, scope: Scope) {
+        if self.dispatcher.scope() == scope {
+            return;
+        }
+        if let Err(err) = self.dispatcher.set_scope(scope) {
+            self.widgets.show_error(&err);
+            self.widgets.scope_system.set_active(true);
+            return;
+        }
+        if scope == Scope::User && !self.watching_user_services.get() {
+            self.listen_for_user_changes();
+        }
+        self.widgets.set_user_scope(scope == Scope::User);
+        self.model.borrow_mut().column_data = ColumnData::default();
+        self.apply_read_only();
+        if scope == Scope::System {
+            self.check_authorization();
+        }
+        self.request_refresh(false);
+    }
+
+    fn fix_duplicate_enables(self: &Rc<Self>) {
+        if self.is_read_only() {
+            return;
+        }
+        let issues = match self.dispatcher.check_health() {
+            Ok(issues) => issues,
+            Err(err) => {
+                self.widgets.show_error(&err);
+                return;
+            }
+        };
+        let mut services: Vec<&str> = issues
+            .iter()
+            .filter(|issue| issue.kind == HealthIssueKind::DuplicateEnable)
+            .map(|issue| issue.service.as_str())
+            .collect();
+        services.dedup();
+        for service in services {
+            match self.dispatcher.keep_only_in_current_runlevel(service) {
+                Ok(message) => self.widgets.show_toast(&message),
+                Err(err) => {
+                    self.widgets.show_error(&err);
+                    break;
+                }
+            }
+        }
+        self.request_refresh(true);
+    }
+
+    /// Update the header badge; a failed check just hides it.
+    fn refresh_health(&self) {
+        match self.dispatcher.check_health() {
+            Ok(issues) => self.widgets.update_health(&issues),
+            Err(_) => self.widgets.update_health(&[]),
+        }
     }
 
     fn request_logs(self: &Rc<Self>, service: String) {
         self.widgets.show_activity_loading(&service);
         let lines = self.preferences.borrow().log_lines.max(1) as usize;
-        match self.dispatcher.fetch_logs(&service, lines) {
+        let severity = self.widgets.log_severity_filter();
+        match self.dispatcher.fetch_logs(&service, lines, severity) {
             Ok(entries) => {
                 let mut notes = {
                     let model = self.model.borrow();
@@ -787,6 +4157,228 @@ impl AppController {
         }
     }
 
+    /// Open `service`'s log in a window of its own, in place of one open for
+    /// another service.
+    fn open_log_viewer(self: &Rc<Self>, service: &str) {
+        if let Some(previous) = self.log_viewer.take() {
+            previous.window.close();
+        }
+        let viewer = Rc::new(LogViewer::new(&self.widgets.window, service));
+        self.load_log_viewer(&viewer, service);
+
+        {
+            let controller = Rc::downgrade(self);
+            let weak_viewer = Rc::downgrade(&viewer);
+            let service = service.to_string();
+            viewer.follow_toggle.connect_toggled(move |toggle| {
+                let (Some(controller), Some(viewer)) =
+                    (controller.upgrade(), weak_viewer.upgrade())
+                else {
+                    return;
+                };
+                if toggle.is_active() {
+                    controller.follow_log(&viewer, &service);
+                } else {
+                    controller.log_stream.take();
+                }
+            });
+        }
+        {
+            let weak_viewer = Rc::downgrade(&viewer);
+            viewer.search_entry.connect_search_changed(move |entry| {
+                if let Some(viewer) = weak_viewer.upgrade() {
+                    viewer.set_pattern(&entry.text());
+                }
+            });
+        }
+        {
+            let weak_viewer = Rc::downgrade(&viewer);
+            let next = move || {
+                if let Some(viewer) = weak_viewer.upgrade() {
+                    viewer.next_match();
+                }
+            };
+            let on_click = next.clone();
+            viewer.next_button.connect_clicked(move |_| on_click());
+            let on_activate = next.clone();
+            viewer.search_entry.connect_activate(move |_| on_activate());
+            viewer.search_entry.connect_next_match(move |_| next());
+        }
+        {
+            let controller = Rc::downgrade(self);
+            let weak_viewer = Rc::downgrade(&viewer);
+            let service = service.to_string();
+            viewer.export_button.connect_clicked(move |_| {
+                if let (Some(controller), Some(viewer)) =
+                    (controller.upgrade(), weak_viewer.upgrade())
+                {
+                    controller.export_log(&viewer, &service);
+                }
+            });
+        }
+        {
+            let controller = Rc::downgrade(self);
+            viewer.window.connect_close_request(move |_| {
+                if let Some(controller) = controller.upgrade() {
+                    controller.log_stream.take();
+                    controller.log_viewer.take();
+                }
+                glib::Propagation::Proceed
+            });
+        }
+
+        viewer.window.present();
+        self.log_viewer.replace(Some(viewer));
+    }
+
+    /// Show the last lines of `service`'s log, as many as the activity view reads.
+    fn load_log_viewer(&self, viewer: &LogViewer, service: &str) {
+        let (lines, format) = {
+            let prefs = self.preferences.borrow();
+            (prefs.log_lines.max(1) as usize, prefs.timestamp_format)
+        };
+        match self.dispatcher.fetch_logs(service, lines, None) {
+            Ok(entries) => viewer.set_entries(&entries, format),
+            Err(err) => viewer.show_error(&err),
+        }
+    }
+
+    /// Append lines to the log window as they are written.
+    fn follow_log(self: &Rc<Self>, viewer: &Rc<LogViewer>, service: &str) {
+        // Reload first, so nothing written while not following is missing.
+        self.load_log_viewer(viewer, service);
+        let stream = match self.dispatcher.follow_logs(service) {
+            Ok(stream) => stream,
+            Err(err) => {
+                viewer.show_toast(&err);
+                viewer.follow_toggle.set_active(false);
+                return;
+            }
+        };
+        let receiver = stream.receiver.clone();
+        self.log_stream.replace(Some(stream));
+
+        let controller = Rc::downgrade(self);
+        let viewer = Rc::downgrade(viewer);
+        glib::MainContext::default().spawn_local(async move {
+            while let Ok(entry) = receiver.recv().await {
+                let (Some(controller), Some(viewer)) = (controller.upgrade(), viewer.upgrade())
+                else {
+                    break;
+                };
+                match entry {
+                    Ok(entry) => {
+                        let format = controller.preferences.borrow().timestamp_format;
+                        viewer.append(&entry, format);
+                    }
+                    Err(reason) => {
+                        viewer.show_toast(&format!("Stopped following: {reason}"));
+                        viewer.follow_toggle.set_active(false);
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Save the lines shown in the log window, or the whole log read on a
+    /// worker thread, to a file the user picks.
+    fn export_log(self: &Rc<Self>, viewer: &Rc<LogViewer>, service: &str) {
+        let chooser = gtk::FileChooserNative::new(
+            Some("Export Log"),
+            Some(&viewer.window),
+            gtk::FileChooserAction::Save,
+            Some("Export"),
+            Some("Cancel"),
+        );
+        chooser.set_modal(true);
+        chooser.set_current_name(&format!("{service}.log"));
+        chooser.add_choice(
+            "range",
+            "Export",
+            &[("shown", "Lines shown"), ("full", "Whole log")],
+        );
+        chooser.set_choice("range", "shown");
+
+        let controller = Rc::downgrade(self);
+        let weak_viewer = Rc::downgrade(viewer);
+        let service = service.to_string();
+        chooser.connect_response(move |chooser, response| {
+            let Some(controller) = controller.upgrade() else {
+                return;
+            };
+            controller.file_chooser.borrow_mut().take();
+            if response != gtk::ResponseType::Accept {
+                return;
+            }
+            let (Some(path), Some(viewer)) = (
+                chooser.file().and_then(|file| file.path()),
+                weak_viewer.upgrade(),
+            ) else {
+                return;
+            };
+            if chooser.choice("range").as_deref() == Some("full") {
+                controller.export_full_log(&viewer, &service, path);
+                return;
+            }
+            match fs::write(&path, viewer.text() + "\n") {
+                Ok(()) => viewer.show_toast(&format!("Exported the log to {}", path.display())),
+                Err(err) => {
+                    viewer.show_toast(&format!("Unable to write {}: {err}", path.display()))
+                }
+            }
+        });
+
+        chooser.show();
+        self.file_chooser.borrow_mut().replace(chooser);
+    }
+
+    fn export_full_log(&self, viewer: &Rc<LogViewer>, service: &str, path: PathBuf) {
+        let dispatcher = self.dispatcher.clone();
+        let format = self.preferences.borrow().timestamp_format;
+        let thread_service = service.to_string();
+        let (sender, receiver) = async_channel::bounded(1);
+        let spawned = thread::Builder::new()
+            .name("runkit-log-export".into())
+            .spawn(move || {
+                let result = dispatcher
+                    .fetch_logs(&thread_service, u32::MAX as usize, None)
+                    .and_then(|entries| {
+                        let mut text: String = entries
+                            .iter()
+                            .map(|entry| formatting::format_log_entry(entry, format) + "\n")
+                            .collect();
+                        if text.is_empty() {
+                            text.push('\n');
+                        }
+                        fs::write(&path, text)
+                            .map(|()| (entries.len(), path.clone()))
+                            .map_err(|err| format!("Unable to write {}: {err}", path.display()))
+                    });
+                let _ = sender.send_blocking(result);
+            });
+        if let Err(err) = spawned {
+            viewer.show_toast(&format!("Exporting the log failed: {err}"));
+            return;
+        }
+        viewer.show_toast(&format!("Exporting the whole log of {service}…"));
+        let viewer = Rc::downgrade(viewer);
+        glib::MainContext::default().spawn_local(async move {
+            let Ok(result) = receiver.recv().await else {
+                return;
+            };
+            let Some(viewer) = viewer.upgrade() else {
+                return;
+            };
+            match result {
+                Ok((lines, path)) => {
+                    viewer.show_toast(&format!("Exported {lines} lines to {}", path.display()))
+                }
+                Err(err) => viewer.show_toast(&format!("Exporting the log failed: {err}")),
+            }
+        });
+    }
+
     fn format_activity_history(&self, service: &str) -> Vec<String> {
         let activity_store = self.activity_store.borrow();
         let activities = activity_store.get_activities(service);
@@ -803,9 +4395,9 @@ impl AppController {
 
         let timestamp = DateTime::parse_from_rfc3339(&event.timestamp)
             .ok()
-            .and_then(|dt| {
+            .map(|dt| {
                 let local: chrono::DateTime<chrono::Local> = dt.into();
-                Some(local.format("%b %d, %I:%M %p").to_string())
+                local.format("%b %d, %I:%M %p").to_string()
             })
             .unwrap_or_else(|| "Unknown time".to_string());
 
@@ -890,10 +4482,118 @@ impl AppController {
         }
     }
 
+    /// Refresh when runkitd reports a change instead of waiting for the next poll.
+    fn listen_for_changes(self: &Rc<Self>) {
+        let receiver = match self.dispatcher.subscribe_changes() {
+            Ok(receiver) => receiver,
+            Err(err) => {
+                eprintln!("runkit: live updates unavailable: {err}");
+                return;
+            }
+        };
+        let controller = Rc::downgrade(self);
+        glib::MainContext::default().spawn_local(async move {
+            while let Ok(change) = receiver.recv().await {
+                let Some(controller) = controller.upgrade() else {
+                    break;
+                };
+                controller.schedule_change_refresh(&change);
+            }
+        });
+    }
+
+    /// Refresh when the user's own services change; runkitd only watches the
+    /// system's.
+    fn listen_for_user_changes(self: &Rc<Self>) {
+        let receiver = match self.dispatcher.subscribe_user_changes() {
+            Ok(receiver) => receiver,
+            Err(err) => {
+                eprintln!("runkit: live updates of user services unavailable: {err}");
+                return;
+            }
+        };
+        self.watching_user_services.set(true);
+        let controller = Rc::downgrade(self);
+        glib::MainContext::default().spawn_local(async move {
+            while let Ok(change) = receiver.recv().await {
+                let Some(controller) = controller.upgrade() else {
+                    break;
+                };
+                controller.schedule_change_refresh(&change);
+            }
+        });
+    }
+
+    /// Show runkitd's failure alerts as desktop notifications.
+    fn listen_for_alerts(self: &Rc<Self>) {
+        let receiver = match self.dispatcher.subscribe_alerts() {
+            Ok(receiver) => receiver,
+            Err(err) => {
+                eprintln!("runkit: desktop alerts unavailable: {err}");
+                return;
+            }
+        };
+        let controller = Rc::downgrade(self);
+        glib::MainContext::default().spawn_local(async move {
+            while let Ok(alert) = receiver.recv().await {
+                let Some(controller) = controller.upgrade() else {
+                    break;
+                };
+                controller.notify_alert(&alert);
+            }
+        });
+    }
+
+    fn notify_alert(&self, alert: &ServiceAlert) {
+        if !self.preferences.borrow().desktop_alerts {
+            return;
+        }
+        let Some(app) = self.widgets.window.application() else {
+            return;
+        };
+        let title = match alert.severity.as_str() {
+            "critical" => format!("{} failed", alert.service),
+            "info" => format!("{} recovered", alert.service),
+            _ => format!("{} stopped unexpectedly", alert.service),
+        };
+        let notification = gio::Notification::new(&title);
+        notification.set_body(Some(&alert.message));
+        if alert.severity == "critical" {
+            notification.set_priority(gio::NotificationPriority::High);
+        }
+        // One notification per service; a newer alert replaces the older one.
+        app.send_notification(Some(&format!("alert-{}", alert.service)), &notification);
+    }
+
+    fn schedule_change_refresh(self: &Rc<Self>, change: &ServiceChange) {
+        if change.kind == "state_changed"
+            && self.widgets.current_service().as_deref() == Some(change.service.as_str())
+        {
+            self.change_refresh_logs.set(true);
+        }
+        if self.change_refresh_pending.replace(true) {
+            return;
+        }
+        let controller = Rc::downgrade(self);
+        glib::timeout_add_local_once(
+            std::time::Duration::from_millis(CHANGE_REFRESH_DELAY_MS),
+            move || {
+                if let Some(controller) = controller.upgrade() {
+                    controller.change_refresh_pending.set(false);
+                    controller.request_refresh(true);
+                    if controller.change_refresh_logs.replace(false) {
+                        controller.refresh_logs_for_selection();
+                    }
+                }
+            },
+        );
+    }
+
     fn configure_auto_refresh(self: &Rc<Self>) {
         self.clear_auto_refresh();
         let prefs = self.preferences.borrow().clone();
-        if prefs.auto_refresh {
+        // A wall display is useless without refreshes, so kiosk mode always polls.
+        if prefs.auto_refresh || self.launch.kiosk {
             let interval = prefs
                 .refresh_interval_secs
                 .clamp(MIN_REFRESH_INTERVAL, MAX_REFRESH_INTERVAL);
@@ -949,6 +4649,22 @@ impl AppController {
         visibility_row.set_activatable_widget(Some(&show_switch));
         startup_group.add(&visibility_row);
 
+        let read_only_row = adw::ActionRow::builder()
+            .title("Read-only mode")
+            .subtitle("Hide actions that start, stop, enable, or otherwise change services.")
+            .build();
+        let read_only_switch = gtk::Switch::builder()
+            .valign(gtk::Align::Center)
+            .active(self.is_read_only())
+            .build();
+        if self.launch.read_only || self.launch.kiosk {
+            read_only_switch.set_sensitive(false);
+            read_only_row.set_subtitle("Enabled by a launch option.");
+        }
+        read_only_row.add_suffix(&read_only_switch);
+        read_only_row.set_activatable_widget(Some(&read_only_switch));
+        startup_group.add(&read_only_row);
+
         let refresh_group = adw::PreferencesGroup::builder()
             .title("Status Refresh")
             .description("Control how Runkit keeps service status up to date.")
@@ -967,7 +4683,10 @@ impl AppController {
 
         let auth_row = adw::ActionRow::builder()
             .title("Require password for service changes")
-            .subtitle("Ask for authentication every time a privileged action runs.")
+            .subtitle(
+                "Ask for authentication every time a privileged action runs. When off, \
+                 runkitd reuses your authorization for eight hours.",
+            )
             .build();
         let auth_switch = gtk::Switch::builder()
             .valign(gtk::Align::Center)
@@ -977,6 +4696,18 @@ impl AppController {
         auth_row.set_activatable_widget(Some(&auth_switch));
         refresh_group.add(&auth_row);
 
+        let alerts_row = adw::ActionRow::builder()
+            .title("Notify when a service fails")
+            .subtitle("Show runkitd's failure alerts as desktop notifications.")
+            .build();
+        let alerts_switch = gtk::Switch::builder()
+            .valign(gtk::Align::Center)
+            .active(prefs_snapshot.desktop_alerts)
+            .build();
+        alerts_row.add_suffix(&alerts_switch);
+        alerts_row.set_activatable_widget(Some(&alerts_switch));
+        refresh_group.add(&alerts_row);
+
         let interval_adjustment = gtk::Adjustment::new(
             prefs_snapshot.refresh_interval_secs as f64,
             MIN_REFRESH_INTERVAL as f64,
@@ -1025,6 +4756,30 @@ impl AppController {
         log_row.set_activatable(false);
         log_group.add(&log_row);
 
+        let timestamp_options =
+            gtk::StringList::new(&["Local time", "UTC", "TAI64N (raw)", "Relative"]);
+        let timestamp_combo = adw::ComboRow::builder()
+            .title("Timestamp format")
+            .model(&timestamp_options)
+            .build();
+        let selected_style = TimestampStyle::ALL
+            .iter()
+            .position(|style| *style == prefs_snapshot.timestamp_format.style)
+            .unwrap_or(0);
+        timestamp_combo.set_selected(selected_style as u32);
+        log_group.add(&timestamp_combo);
+
+        let subsecond_row = adw::ActionRow::builder()
+            .title("Show fractional seconds")
+            .build();
+        let subsecond_switch = gtk::Switch::builder()
+            .valign(gtk::Align::Center)
+            .active(prefs_snapshot.timestamp_format.subsecond)
+            .build();
+        subsecond_row.add_suffix(&subsecond_switch);
+        subsecond_row.set_activatable_widget(Some(&subsecond_switch));
+        log_group.add(&subsecond_row);
+
         page.add(&startup_group);
         page.add(&refresh_group);
         page.add(&log_group);
@@ -1052,13 +4807,38 @@ impl AppController {
         });
 
         let controller_for_auth = Rc::downgrade(self);
-        auth_switch.connect_state_set(move |_, state| {
-            if let Some(controller) = controller_for_auth.upgrade() {
+        auth_switch.connect_state_set(move |switch, state| {
+            let Some(controller) = controller_for_auth.upgrade() else {
+                return glib::Propagation::Proceed;
+            };
+            if controller.preferences.borrow().require_password == state {
+                return glib::Propagation::Proceed;
+            }
+            // runkitd keeps the choice for system services, so a client
+            // cannot skip the password by claiming the user opted out.
+            if controller.dispatcher.scope() == Scope::System {
+                match controller.dispatcher.set_cached_authorization(!state) {
+                    Ok(message) => controller.widgets.show_toast(&message),
+                    Err(err) => {
+                        controller.widgets.show_error(&err);
+                        switch.set_active(!state);
+                        return glib::Propagation::Stop;
+                    }
+                }
+            }
+            controller.preferences.borrow_mut().require_password = state;
+            controller.save_preferences();
+            glib::Propagation::Proceed
+        });
+
+        let controller_for_alerts = Rc::downgrade(self);
+        alerts_switch.connect_state_set(move |_, state| {
+            if let Some(controller) = controller_for_alerts.upgrade() {
                 let mut changed = false;
                 {
                     let mut prefs = controller.preferences.borrow_mut();
-                    if prefs.require_password != state {
-                        prefs.require_password = state;
+                    if prefs.desktop_alerts != state {
+                        prefs.desktop_alerts = state;
                         changed = true;
                     }
                 }
@@ -1119,6 +4899,25 @@ impl AppController {
             }
         });
 
+        let controller_for_timestamps = Rc::downgrade(self);
+        timestamp_combo.connect_selected_notify(move |combo| {
+            if let Some(controller) = controller_for_timestamps.upgrade() {
+                let style = TimestampStyle::ALL
+                    .get(combo.selected() as usize)
+                    .copied()
+                    .unwrap_or_default();
+                controller.update_timestamp_format(|format| format.style = style);
+            }
+        });
+
+        let controller_for_subsecond = Rc::downgrade(self);
+        subsecond_switch.connect_state_set(move |_, state| {
+            if let Some(controller) = controller_for_subsecond.upgrade() {
+                controller.update_timestamp_format(|format| format.subsecond = state);
+            }
+            glib::Propagation::Proceed
+        });
+
         let controller_for_startup = Rc::downgrade(self);
         startup_combo.connect_selected_notify(move |combo| {
             if let Some(controller) = controller_for_startup.upgrade() {
@@ -1161,6 +4960,25 @@ impl AppController {
             }
         });
 
+        let controller_for_read_only = Rc::downgrade(self);
+        read_only_switch.connect_state_set(move |_, state| {
+            if let Some(controller) = controller_for_read_only.upgrade() {
+                let mut changed = false;
+                {
+                    let mut prefs = controller.preferences.borrow_mut();
+                    if prefs.read_only != state {
+                        prefs.read_only = state;
+                        changed = true;
+                    }
+                }
+                if changed {
+                    controller.save_preferences();
+                    controller.apply_read_only();
+                }
+            }
+            glib::Propagation::Proceed
+        });
+
         let controller_for_visibility = Rc::downgrade(self);
         show_switch.connect_state_set(move |_, state| {
             if let Some(controller) = controller_for_visibility.upgrade() {
@@ -1202,6 +5020,130 @@ impl AppController {
         window.present();
     }
 
+    fn show_dashboard(self: &Rc<Self>) {
+        if let Some(dashboard) = self.dashboard.borrow().as_ref() {
+            dashboard.window.present();
+            return;
+        }
+        let Some(app) = self.widgets.window.application() else {
+            return;
+        };
+
+        let dashboard = dashboard::Dashboard::new(&app);
+        dashboard.update_services(&self.model.borrow().services);
+
+        if !self.launch.kiosk {
+            let keys = gtk::EventControllerKey::new();
+            let window = dashboard.window.clone();
+            keys.connect_key_pressed(move |_, key, _, _| {
+                if key == gtk::gdk::Key::Escape {
+                    window.close();
+                    glib::Propagation::Stop
+                } else {
+                    glib::Propagation::Proceed
+                }
+            });
+            dashboard.window.add_controller(keys);
+        }
+
+        let weak = Rc::downgrade(self);
+        dashboard.window.connect_close_request(move |_| {
+            if let Some(controller) = weak.upgrade() {
+                if let Some(source) = controller.snippet_source.borrow_mut().take() {
+                    source.remove();
+                }
+                controller.dashboard.borrow_mut().take();
+                if controller.launch.kiosk
+                    && let Some(app) = controller.widgets.window.application()
+                {
+                    app.quit();
+                }
+            }
+            glib::Propagation::Proceed
+        });
+
+        let controller = Rc::downgrade(self);
+        let source = glib::timeout_add_seconds_local(DASHBOARD_SNIPPET_INTERVAL, move || {
+            if let Some(controller) = controller.upgrade() {
+                controller.cycle_dashboard_snippet();
+            }
+            ControlFlow::Continue
+        });
+        self.snippet_source.borrow_mut().replace(source);
+
+        dashboard.window.fullscreen();
+        dashboard.window.present();
+        self.dashboard.borrow_mut().replace(dashboard);
+        self.cycle_dashboard_snippet();
+    }
+
+    /// Show the next log snippet, preferring services that need attention.
+    fn cycle_dashboard_snippet(&self) {
+        let candidates: Vec<String> = {
+            let model = self.model.borrow();
+            let mut troubled: Vec<&ServiceInfo> = model
+                .services
+                .iter()
+                .filter(|service| service.enabled)
+                .filter(|service| {
+                    matches!(
+                        formatting::status_level(service),
+                        formatting::StatusLevel::Critical | formatting::StatusLevel::Warning
+                    )
+                })
+                .collect();
+            if troubled.is_empty() {
+                troubled = model
+                    .services
+                    .iter()
+                    .filter(|service| service.enabled)
+                    .collect();
+            }
+            troubled
+                .sort_by_key(|service| dashboard::level_rank(formatting::status_level(service)));
+            troubled
+                .into_iter()
+                .map(|service| service.name.clone())
+                .collect()
+        };
+
+        let dashboard = self.dashboard.borrow();
+        let Some(dashboard) = dashboard.as_ref() else {
+            return;
+        };
+        if candidates.is_empty() {
+            dashboard.clear_snippet();
+            return;
+        }
+
+        let index = self.snippet_index.get() % candidates.len();
+        self.snippet_index.set(index + 1);
+        let service = &candidates[index];
+        let format = self.preferences.borrow().timestamp_format;
+        match self
+            .dispatcher
+            .fetch_logs(service, dashboard::SNIPPET_LINES, None)
+        {
+            Ok(entries) => dashboard.show_snippet(service, &entries, format),
+            Err(_) => dashboard.show_snippet(service, &[], format),
+        }
+    }
+
+    fn update_timestamp_format(self: &Rc<Self>, update: impl FnOnce(&mut TimestampFormat)) {
+        let format = {
+            let mut prefs = self.preferences.borrow_mut();
+            let before = prefs.timestamp_format;
+            update(&mut prefs.timestamp_format);
+            if prefs.timestamp_format == before {
+                return;
+            }
+            prefs.timestamp_format
+        };
+        self.save_preferences();
+        self.widgets.set_timestamp_format(format);
+        self.refresh_logs_for_selection();
+    }
+
     fn show_about(self: &Rc<Self>) {
         if let Some(dialog) = self.about_dialog.borrow().as_ref() {
             dialog.present();
@@ -1237,7 +5179,7 @@ impl AppController {
         content_box.append(&title);
 
         let version = gtk::Label::builder()
-            .label(&format!("Version {}", env!("CARGO_PKG_VERSION")))
+            .label(format!("Version {}", env!("CARGO_PKG_VERSION")))
             .wrap(true)
             .wrap_mode(pango::WrapMode::WordChar)
             .css_classes(["dim-label"])
@@ -0,0 +1,89 @@
+use gtk4 as gtk;
+use libadwaita::{self as adw, prelude::*};
+use runkit_core::{CheckStatus, DiagnosticCheck, ServiceManager};
+
+/// Response id of the assistant's "Check Again" button.
+pub const RETRY_RESPONSE: &str = "retry";
+
+/// Run the installation checks an unprivileged client can perform, plus one
+/// entry describing why runkitd could not be used.
+pub fn run_checks(failure: &str) -> Vec<DiagnosticCheck> {
+    let mut checks: Vec<DiagnosticCheck> = ServiceManager::default()
+        .installation_checks()
+        .into_iter()
+        .filter(DiagnosticCheck::is_problem)
+        .collect();
+    checks.push(DiagnosticCheck::problem(
+        "daemon",
+        CheckStatus::Error,
+        failure,
+        "sudo /usr/libexec/runkitd doctor",
+    ));
+    checks
+}
+
+/// Dialog listing failed checks with a copyable command for each.
+pub fn build_dialog(
+    parent: &impl IsA<gtk::Window>,
+    checks: &[DiagnosticCheck],
+) -> adw::MessageDialog {
+    let installation_problems = checks.iter().filter(|check| check.name != "daemon").count();
+    let body = if installation_problems == 0 {
+        "Runkit could not use its helper, although its files look installed. \
+         Running the doctor as root also checks the bus name."
+            .to_string()
+    } else {
+        format!(
+            "{installation_problems} part(s) of the installation are missing or broken. \
+             Run the commands below from the Runkit source directory, then check again."
+        )
+    };
+    let dialog = adw::MessageDialog::builder()
+        .transient_for(parent)
+        .modal(true)
+        .heading("Runkit Needs Setup")
+        .body(body)
+        .build();
+
+    let list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .build();
+    list.add_css_class("boxed-list");
+    for check in checks {
+        let row = adw::ActionRow::builder()
+            .title(check.detail.as_str())
+            .title_lines(0)
+            .build();
+        if let Some(fix) = &check.fix {
+            row.set_subtitle(fix);
+            row.set_subtitle_selectable(true);
+            let copy = gtk::Button::builder()
+                .icon_name("edit-copy-symbolic")
+                .tooltip_text("Copy command")
+                .valign(gtk::Align::Center)
+                .css_classes(["flat"])
+                .build();
+            let fix = fix.clone();
+            copy.connect_clicked(move |button| {
+                button.clipboard().set_text(&fix);
+                button.set_icon_name("object-select-symbolic");
+            });
+            row.add_suffix(&copy);
+        }
+        list.append(&row);
+    }
+
+    let scroller = gtk::ScrolledWindow::builder()
+        .hscrollbar_policy(gtk::PolicyType::Never)
+        .propagate_natural_height(true)
+        .max_content_height(420)
+        .child(&list)
+        .build();
+    dialog.set_extra_child(Some(&scroller));
+    dialog.add_response("close", "Close");
+    dialog.add_response(RETRY_RESPONSE, "Check Again");
+    dialog.set_response_appearance(RETRY_RESPONSE, adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some(RETRY_RESPONSE));
+    dialog.set_close_response("close");
+    dialog
+}
@@ -0,0 +1,519 @@
+//! The tray icon of `--background` mode: a StatusNotifierItem whose icon
+//! shows how the services are doing, with a menu to open the window and to
+//! start, stop or restart the favorite services.
+//!
+//! The item and its `com.canonical.dbusmenu` menu are served on the session
+//! bus from zbus's own thread; clicks reach the GTK main loop as
+//! [`TrayEvent`]s.
+use crate::formatting::{StatusLevel, is_running, runtime_state_short, status_level};
+use runkit_core::ServiceInfo;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use zbus::blocking::{Connection, ConnectionBuilder, Proxy};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, StructureBuilder, Value};
+use zbus::{SignalContext, dbus_interface, fdo};
+
+const ITEM_PATH: &str = "/StatusNotifierItem";
+const MENU_PATH: &str = "/MenuBar";
+const ICON_NAME: &str = "runkit";
+
+/// What a click in the tray asks the main loop to do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrayEvent {
+    /// Show the main window.
+    Open,
+    /// Start, stop or restart a favorite service.
+    Control {
+        service: String,
+        verb: &'static str,
+    },
+    Quit,
+}
+
+/// A favorite service as the tray menu shows it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Favorite {
+    name: String,
+    state: String,
+    running: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TrayState {
+    level: StatusLevel,
+    summary: String,
+    favorites: Vec<Favorite>,
+    /// Bumped whenever the menu changes, for `LayoutUpdated`.
+    revision: u32,
+}
+
+type Shared = Arc<Mutex<TrayState>>;
+
+fn lock(state: &Shared) -> MutexGuard<'_, TrayState> {
+    state.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+pub struct Tray {
+    connection: Connection,
+    state: Shared,
+}
+
+impl Tray {
+    /// Put the icon in the tray. Fails when the session bus or a tray host
+    /// (a StatusNotifierWatcher) is not available.
+    pub fn start(events: async_channel::Sender<TrayEvent>) -> Result<Tray, String> {
+        let state = Arc::new(Mutex::new(TrayState {
+            level: StatusLevel::Neutral,
+            summary: "Loading services…".to_string(),
+            favorites: Vec::new(),
+            revision: 1,
+        }));
+        let name = format!("org.kde.StatusNotifierItem-{}-1", std::process::id());
+        let connection = ConnectionBuilder::session()
+            .and_then(|builder| builder.name(name.as_str()))
+            .and_then(|builder| {
+                builder.serve_at(
+                    ITEM_PATH,
+                    StatusNotifierItem {
+                        state: Arc::clone(&state),
+                        events: events.clone(),
+                    },
+                )
+            })
+            .and_then(|builder| {
+                builder.serve_at(
+                    MENU_PATH,
+                    DbusMenu {
+                        state: Arc::clone(&state),
+                        events,
+                    },
+                )
+            })
+            .and_then(ConnectionBuilder::build)
+            .map_err(|err| format!("Cannot reach the session bus: {err}"))?;
+
+        Proxy::new(
+            &connection,
+            "org.kde.StatusNotifierWatcher",
+            "/StatusNotifierWatcher",
+            "org.kde.StatusNotifierWatcher",
+        )
+        .and_then(|watcher| watcher.call_method("RegisterStatusNotifierItem", &(name.as_str(),)))
+        .map_err(|err| format!("No system tray is available: {err}"))?;
+
+        Ok(Tray { connection, state })
+    }
+
+    /// Show the state of `services`, with `favorites` in the menu.
+    pub fn update(&self, services: &[ServiceInfo], favorites: &[String]) {
+        let attention: Vec<&ServiceInfo> = services
+            .iter()
+            .filter(|service| {
+                matches!(
+                    status_level(service),
+                    StatusLevel::Warning | StatusLevel::Critical
+                )
+            })
+            .collect();
+        let level = if attention
+            .iter()
+            .any(|service| status_level(service) == StatusLevel::Critical)
+        {
+            StatusLevel::Critical
+        } else if attention.is_empty() {
+            StatusLevel::Good
+        } else {
+            StatusLevel::Warning
+        };
+        let summary = match attention.as_slice() {
+            [] => "All services are fine".to_string(),
+            [service] => format!("{} needs attention", service.name),
+            services => format!(
+                "{} services need attention: {}",
+                services.len(),
+                services
+                    .iter()
+                    .map(|service| service.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        let favorites: Vec<Favorite> = favorites
+            .iter()
+            .filter_map(|name| services.iter().find(|service| service.name == *name))
+            .map(|service| Favorite {
+                name: service.name.clone(),
+                state: runtime_state_short(service),
+                running: is_running(&service.runtime_state),
+            })
+            .collect();
+
+        let (icon_changed, menu_changed, revision) = {
+            let mut state = lock(&self.state);
+            let icon_changed = state.level != level || state.summary != summary;
+            let menu_changed = state.favorites != favorites;
+            state.level = level;
+            state.summary = summary;
+            if menu_changed {
+                state.favorites = favorites;
+                state.revision += 1;
+            }
+            (icon_changed, menu_changed, state.revision)
+        };
+
+        if icon_changed && let Ok(ctxt) = SignalContext::new(self.connection.inner(), ITEM_PATH) {
+            let status = status(level);
+            let _ = zbus::block_on(StatusNotifierItem::new_icon(&ctxt));
+            let _ = zbus::block_on(StatusNotifierItem::new_overlay_icon(&ctxt));
+            let _ = zbus::block_on(StatusNotifierItem::new_tool_tip(&ctxt));
+            let _ = zbus::block_on(StatusNotifierItem::new_status(&ctxt, status));
+        }
+        if menu_changed && let Ok(ctxt) = SignalContext::new(self.connection.inner(), MENU_PATH) {
+            let _ = zbus::block_on(DbusMenu::layout_updated(&ctxt, revision, 0));
+        }
+    }
+}
+
+fn status(level: StatusLevel) -> &'static str {
+    match level {
+        StatusLevel::Critical => "NeedsAttention",
+        _ => "Active",
+    }
+}
+
+fn overlay_icon(level: StatusLevel) -> &'static str {
+    match level {
+        StatusLevel::Critical => "dialog-error",
+        StatusLevel::Warning => "dialog-warning",
+        StatusLevel::Good | StatusLevel::Neutral => "",
+    }
+}
+
+/// `(width, height, ARGB32 data)`.
+type Pixmap = (i32, i32, Vec<u8>);
+/// `(icon name, icon pixmaps, title, description)`.
+type ToolTip = (String, Vec<Pixmap>, String, String);
+
+struct StatusNotifierItem {
+    state: Shared,
+    events: async_channel::Sender<TrayEvent>,
+}
+
+#[dbus_interface(name = "org.kde.StatusNotifierItem")]
+impl StatusNotifierItem {
+    #[dbus_interface(property)]
+    fn category(&self) -> &str {
+        "SystemServices"
+    }
+
+    #[dbus_interface(property)]
+    fn id(&self) -> &str {
+        "runkit"
+    }
+
+    #[dbus_interface(property)]
+    fn title(&self) -> &str {
+        "Runkit"
+    }
+
+    #[dbus_interface(property)]
+    fn status(&self) -> &str {
+        status(lock(&self.state).level)
+    }
+
+    #[dbus_interface(property)]
+    fn icon_name(&self) -> &str {
+        ICON_NAME
+    }
+
+    #[dbus_interface(property)]
+    fn overlay_icon_name(&self) -> &str {
+        overlay_icon(lock(&self.state).level)
+    }
+
+    #[dbus_interface(property)]
+    fn attention_icon_name(&self) -> &str {
+        "dialog-error"
+    }
+
+    #[dbus_interface(property)]
+    fn tool_tip(&self) -> ToolTip {
+        let summary = lock(&self.state).summary.clone();
+        (
+            ICON_NAME.to_string(),
+            Vec::new(),
+            "Runkit".to_string(),
+            summary,
+        )
+    }
+
+    #[dbus_interface(property)]
+    fn item_is_menu(&self) -> bool {
+        false
+    }
+
+    #[dbus_interface(property)]
+    fn menu(&self) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(MENU_PATH).expect("valid object path")
+    }
+
+    fn activate(&self, _x: i32, _y: i32) {
+        let _ = self.events.send_blocking(TrayEvent::Open);
+    }
+
+    fn secondary_activate(&self, _x: i32, _y: i32) {
+        let _ = self.events.send_blocking(TrayEvent::Open);
+    }
+
+    fn context_menu(&self, _x: i32, _y: i32) {}
+
+    fn scroll(&self, _delta: i32, _orientation: &str) {}
+
+    #[dbus_interface(signal)]
+    async fn new_icon(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    #[dbus_interface(signal)]
+    async fn new_overlay_icon(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    #[dbus_interface(signal)]
+    async fn new_tool_tip(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    #[dbus_interface(signal)]
+    async fn new_status(ctxt: &SignalContext<'_>, status: &str) -> zbus::Result<()>;
+}
+
+/// One entry of the tray menu.
+struct MenuItem {
+    label: String,
+    enabled: bool,
+    separator: bool,
+    event: Option<TrayEvent>,
+    children: Vec<MenuItem>,
+}
+
+impl MenuItem {
+    fn action(label: impl Into<String>, enabled: bool, event: TrayEvent) -> Self {
+        MenuItem {
+            label: label.into(),
+            enabled,
+            separator: false,
+            event: Some(event),
+            children: Vec::new(),
+        }
+    }
+
+    fn separator() -> Self {
+        MenuItem {
+            label: String::new(),
+            enabled: true,
+            separator: true,
+            event: None,
+            children: Vec::new(),
+        }
+    }
+
+    fn properties(&self) -> HashMap<String, OwnedValue> {
+        let mut properties = HashMap::new();
+        if self.separator {
+            properties.insert("type".to_string(), Value::from("separator").into());
+            return properties;
+        }
+        properties.insert("label".to_string(), Value::from(self.label.as_str()).into());
+        properties.insert("enabled".to_string(), Value::from(self.enabled).into());
+        if !self.children.is_empty() {
+            properties.insert(
+                "children-display".to_string(),
+                Value::from("submenu").into(),
+            );
+        }
+        properties
+    }
+}
+
+/// The menu for `state`, as the children of the root item 0. Items are
+/// numbered depth first from 1, which [`number`] follows.
+fn menu_items(state: &TrayState) -> Vec<MenuItem> {
+    let mut items = vec![
+        MenuItem::action("Open Runkit", true, TrayEvent::Open),
+        MenuItem::separator(),
+    ];
+    if state.favorites.is_empty() {
+        items.push(MenuItem {
+            label: "No favorite services yet".to_string(),
+            enabled: false,
+            separator: false,
+            event: None,
+            children: Vec::new(),
+        });
+    }
+    for favorite in &state.favorites {
+        let control = |verb| TrayEvent::Control {
+            service: favorite.name.clone(),
+            verb,
+        };
+        items.push(MenuItem {
+            label: format!("{} — {}", favorite.name, favorite.state),
+            enabled: true,
+            separator: false,
+            event: None,
+            children: vec![
+                MenuItem::action("Start", !favorite.running, control("start")),
+                MenuItem::action("Stop", favorite.running, control("stop")),
+                MenuItem::action("Restart", true, control("restart")),
+            ],
+        });
+    }
+    items.push(MenuItem::separator());
+    items.push(MenuItem::action("Quit", true, TrayEvent::Quit));
+    items
+}
+
+/// Every item with its id, depth first.
+fn number(items: &[MenuItem]) -> Vec<(i32, &MenuItem)> {
+    fn walk<'a>(items: &'a [MenuItem], next: &mut i32, out: &mut Vec<(i32, &'a MenuItem)>) {
+        for item in items {
+            out.push((*next, item));
+            *next += 1;
+            walk(&item.children, next, out);
+        }
+    }
+    let mut numbered = Vec::new();
+    walk(items, &mut 1, &mut numbered);
+    numbered
+}
+
+type Layout = (i32, HashMap<String, OwnedValue>, Vec<OwnedValue>);
+
+/// `(ia{sv}av)` for `items` starting at `id`, each child as a variant of the
+/// same structure. Returns the layout and the id after the last item.
+fn layout_children(items: &[MenuItem], mut id: i32) -> (Vec<OwnedValue>, i32) {
+    let mut children = Vec::new();
+    for item in items {
+        let own_id = id;
+        let (grandchildren, next) = layout_children(&item.children, id + 1);
+        id = next;
+        let structure = StructureBuilder::new()
+            .add_field(own_id)
+            .add_field(item.properties())
+            .add_field(grandchildren)
+            .build();
+        children.push(OwnedValue::from(Value::from(structure)));
+    }
+    (children, id)
+}
+
+struct DbusMenu {
+    state: Shared,
+    events: async_channel::Sender<TrayEvent>,
+}
+
+impl DbusMenu {
+    fn clicked(&self, id: i32) -> bool {
+        let state = lock(&self.state).clone();
+        let items = menu_items(&state);
+        let Some((_, item)) = number(&items)
+            .into_iter()
+            .find(|(item_id, _)| *item_id == id)
+        else {
+            return false;
+        };
+        if let Some(event) = &item.event {
+            let _ = self.events.send_blocking(event.clone());
+        }
+        true
+    }
+}
+
+#[dbus_interface(name = "com.canonical.dbusmenu")]
+impl DbusMenu {
+    #[dbus_interface(property)]
+    fn version(&self) -> u32 {
+        3
+    }
+
+    #[dbus_interface(property)]
+    fn text_direction(&self) -> &str {
+        "ltr"
+    }
+
+    #[dbus_interface(property)]
+    fn status(&self) -> &str {
+        "normal"
+    }
+
+    #[dbus_interface(property)]
+    fn icon_theme_path(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The whole menu, whatever part was asked for; it is small.
+    fn get_layout(
+        &self,
+        _parent_id: i32,
+        _recursion_depth: i32,
+        _property_names: Vec<String>,
+    ) -> (u32, Layout) {
+        let state = lock(&self.state).clone();
+        let (children, _) = layout_children(&menu_items(&state), 1);
+        let mut root = HashMap::new();
+        root.insert(
+            "children-display".to_string(),
+            Value::from("submenu").into(),
+        );
+        (state.revision, (0, root, children))
+    }
+
+    fn get_group_properties(
+        &self,
+        ids: Vec<i32>,
+        _property_names: Vec<String>,
+    ) -> Vec<(i32, HashMap<String, OwnedValue>)> {
+        let state = lock(&self.state).clone();
+        let items = menu_items(&state);
+        number(&items)
+            .into_iter()
+            .filter(|(id, _)| ids.is_empty() || ids.contains(id))
+            .map(|(id, item)| (id, item.properties()))
+            .collect()
+    }
+
+    fn get_property(&self, id: i32, name: &str) -> fdo::Result<OwnedValue> {
+        let state = lock(&self.state).clone();
+        let items = menu_items(&state);
+        number(&items)
+            .into_iter()
+            .find(|(item_id, _)| *item_id == id)
+            .and_then(|(_, item)| item.properties().remove(name))
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("no property {name} on item {id}")))
+    }
+
+    fn event(&self, id: i32, event_id: &str, _data: OwnedValue, _timestamp: u32) {
+        if event_id == "clicked" {
+            self.clicked(id);
+        }
+    }
+
+    /// Returns the ids that were not found.
+    fn event_group(&self, events: Vec<(i32, String, OwnedValue, u32)>) -> Vec<i32> {
+        events
+            .into_iter()
+            .filter(|(id, event_id, _, _)| event_id == "clicked" && !self.clicked(*id))
+            .map(|(id, _, _, _)| id)
+            .collect()
+    }
+
+    fn about_to_show(&self, _id: i32) -> bool {
+        false
+    }
+
+    fn about_to_show_group(&self, _ids: Vec<i32>) -> (Vec<i32>, Vec<i32>) {
+        (Vec::new(), Vec::new())
+    }
+
+    #[dbus_interface(signal)]
+    async fn layout_updated(
+        ctxt: &SignalContext<'_>,
+        revision: u32,
+        parent: i32,
+    ) -> zbus::Result<()>;
+}
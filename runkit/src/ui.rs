@@ -1,43 +1,300 @@
 use crate::actions::LogEntry;
+use crate::columns::{ColumnData, ListColumn};
 use crate::formatting::{
     StatusLevel, format_log_entry, is_auto_start, is_running, list_row_subtitle,
     runtime_state_detail, runtime_state_short, status_level,
 };
+use crate::highlight::highlight_shell;
 use gtk::{cairo, gdk, gio, glib, pango};
 use gtk4 as gtk;
 use libadwaita::{self as adw, prelude::*};
-use runkit_core::ServiceInfo;
-use std::{f64::consts::PI, rc::Rc};
+use runkit_core::{
+    HealthIssue, HealthIssueKind, LogSeverity, RestartPolicy, ServiceInfo, ServiceSort,
+    StateFilter, TimestampFormat, category_label,
+};
+use std::{
+    cell::{Cell, RefCell},
+    f64::consts::PI,
+    rc::Rc,
+};
 
 pub struct AppWidgets {
     pub window: adw::ApplicationWindow,
     pub search_entry: gtk::SearchEntry,
+    pub scope_system: gtk::ToggleButton,
+    pub scope_user: gtk::ToggleButton,
+    user_scope: Cell<bool>,
     pub service_filter_toggle: gtk::ToggleButton,
+    /// Chips that narrow the list to running, down or failed services; none
+    /// active lists every state.
+    pub state_chips: Vec<(StateFilter, gtk::ToggleButton)>,
+    /// Order of the list, one entry per [`ServiceSort::ALL`].
+    pub sort_dropdown: gtk::DropDown,
+    /// One check button per optional list column.
+    pub column_checks: Vec<(ListColumn, gtk::CheckButton)>,
+    /// Lists the services under a heading per category.
+    pub group_check: gtk::CheckButton,
+    /// "All categories", then each category by its label.
+    pub category_filter: gtk::DropDown,
+    category_names: gtk::StringList,
+    /// Category ids in the order of `category_filter` after its first item.
+    category_ids: RefCell<Vec<String>>,
     pub list_box: gtk::ListBox,
+    /// Shows a check box on each row, to act on several services at once.
+    pub selection_toggle: gtk::ToggleButton,
+    selection_revealer: gtk::Revealer,
+    /// `(action, button)` for each action the selection bar runs on every
+    /// checked service, e.g. `("stop", …)`.
+    pub selection_buttons: Vec<(&'static str, gtk::Button)>,
+    pub selection_clear: gtk::Button,
+    /// The check box of each listed service while selecting.
+    selection_checks: Rc<RefCell<Vec<(String, gtk::CheckButton)>>>,
+    selection_label: gtk::Label,
+    selection_actions: gtk::Box,
     pub action_start: gtk::Button,
     pub action_stop: gtk::Button,
     pub action_restart: gtk::Button,
     pub action_reload: gtk::Button,
+    action_advanced: gtk::MenuButton,
     pub action_enable: gtk::Button,
     pub action_disable: gtk::Button,
     pub action_check: gtk::Button,
+    pub action_export: gtk::Button,
+    pub action_clone: gtk::Button,
+    pub action_edit_files: gtk::Button,
+    pub action_environment: gtk::Button,
+    pub action_restart_policy: gtk::Button,
+    pub action_down_file: gtk::Button,
+    pub action_maintenance: gtk::Button,
+    pub action_log_config: gtk::Button,
+    pub action_metrics: gtk::Button,
+    pub action_runlevels: gtk::Button,
+    action_row_one: gtk::Box,
+    action_row_two: gtk::Box,
+    import_row: adw::ActionRow,
+    template_row: adw::ActionRow,
+    trash_row: adw::ActionRow,
+    save_profile_row: adw::ActionRow,
+    restore_profile_row: adw::ActionRow,
+    silence_row: adw::ActionRow,
+    stage_logs_row: adw::ActionRow,
+    window_title: adw::WindowTitle,
     detail_stack: gtk::Stack,
     detail_title: gtk::Label,
     detail_state_label: gtk::Label,
+    detail_run_script_label: gtk::Label,
+    detail_history_label: gtk::Label,
+    /// The definition's scripts, read once the expander is opened so that
+    /// browsing the list never asks for a password.
+    pub definition_expander: gtk::Expander,
+    /// `(file, button)` choosing which file of the definition is shown.
+    pub definition_files: Vec<(&'static str, gtk::ToggleButton)>,
+    pub definition_edit: gtk::ToggleButton,
+    pub definition_save: gtk::Button,
+    definition_status: gtk::Label,
+    definition_view: gtk::TextView,
     detail_description_label: gtk::Label,
     detail_status_indicator: gtk::DrawingArea,
     detail_status_text: gtk::Label,
+    pub log_filter_all: gtk::ToggleButton,
+    pub log_filter_warnings: gtk::ToggleButton,
+    pub log_filter_errors: gtk::ToggleButton,
+    /// Opens the whole log in a window that can follow, search and export it.
+    pub log_viewer_button: gtk::Button,
     activity_label: gtk::Label,
     banner: adw::Banner,
     summary_label: gtk::Label,
     loading_revealer: gtk::Revealer,
     loading_spinner: gtk::Spinner,
+    loading_label: gtk::Label,
+    pub loading_cancel: gtk::Button,
+    toast_overlay: adw::ToastOverlay,
+    health_button: gtk::MenuButton,
+    health_list: gtk::Box,
+    pub health_fix_button: gtk::Button,
+    timestamp_format: Cell<TimestampFormat>,
     pub menu_popover: gtk::Popover,
     pub preferences_action: gio::SimpleAction,
+    pub import_action: gio::SimpleAction,
+    pub template_action: gio::SimpleAction,
+    pub trash_action: gio::SimpleAction,
+    pub save_profile_action: gio::SimpleAction,
+    pub restore_profile_action: gio::SimpleAction,
+    pub dashboard_action: gio::SimpleAction,
+    pub silence_action: gio::SimpleAction,
+    pub archive_logs_action: gio::SimpleAction,
+    pub stage_logs_action: gio::SimpleAction,
     pub about_action: gio::SimpleAction,
+    /// Activated with a runit control verb, e.g. `hup`, from the Advanced menu.
+    pub control_action: gio::SimpleAction,
+    /// Like `control_action`, for the verbs polkit guards with its force action.
+    pub control_force_action: gio::SimpleAction,
+    /// `(service, verb)` from a row's quick actions menu that changes the service.
+    pub row_control_action: gio::SimpleAction,
+    /// Like `row_control_action`, for the verbs polkit guards with its force action.
+    pub row_control_force_action: gio::SimpleAction,
+    /// `(service, target)` from a row's quick actions menu that shows the
+    /// logs or opens a directory, e.g. `"folder"` or `"log-terminal"`.
+    pub row_open_action: gio::SimpleAction,
+}
+
+/// Files of a definition shown on the detail page, in the order of their
+/// buttons.
+const DEFINITION_FILES: [&str; 3] = ["run", "finish", "conf"];
+
+/// Verbs runkitd authorizes with the polkit force action, which always asks
+/// for a password.
+pub const FORCEFUL_VERBS: [&str; 7] = [
+    "interrupt",
+    "quit",
+    "term",
+    "kill",
+    "force-stop",
+    "force-reload",
+    "force-restart",
+];
+
+fn is_forceful(verb: &str) -> bool {
+    FORCEFUL_VERBS.contains(&verb)
+}
+
+/// The Advanced menu: every `sv` control verb the main buttons leave out.
+fn build_control_menu() -> gio::Menu {
+    let section = |items: &[(&str, &str)]| {
+        let section = gio::Menu::new();
+        for (label, verb) in items {
+            let action = if is_forceful(verb) {
+                "control-force"
+            } else {
+                "control"
+            };
+            section.append(Some(label), Some(&format!("app.{action}::{verb}")));
+        }
+        section
+    };
+    let menu = gio::Menu::new();
+    menu.append_section(
+        Some("Signals"),
+        &section(&[
+            ("Send SIGHUP", "hup"),
+            ("Send SIGALRM", "alarm"),
+            ("Send SIGINT", "interrupt"),
+            ("Send SIGQUIT", "quit"),
+            ("Send SIGTERM", "term"),
+            ("Send SIGKILL", "kill"),
+        ]),
+    );
+    menu.append_section(
+        None,
+        &section(&[("Pause (SIGSTOP)", "pause"), ("Continue (SIGCONT)", "cont")]),
+    );
+    menu.append_section(
+        Some("Force"),
+        &section(&[
+            ("Force stop", "force-stop"),
+            ("Force reload", "force-reload"),
+            ("Force restart", "force-restart"),
+        ]),
+    );
+    menu
+}
+
+/// The quick actions menu at the end of a service's row: the less common
+/// actions, so they are reachable without going through the detail page.
+fn build_row_menu(service: &str, favorite: bool) -> gio::Menu {
+    let item = |label: &str, action: &str, verb: &str| {
+        let item = gio::MenuItem::new(Some(label), None);
+        item.set_action_and_target_value(
+            Some(action),
+            Some(&(service.to_string(), verb.to_string()).to_variant()),
+        );
+        item
+    };
+    let section = |items: &[(&str, &str, &str)]| {
+        let section = gio::Menu::new();
+        for (label, action, verb) in items {
+            section.append_item(&item(label, action, verb));
+        }
+        section
+    };
+
+    let menu = gio::Menu::new();
+    menu.append_section(
+        None,
+        &section(&[
+            ("Run once", "app.row-control", "once"),
+            ("Run health check", "app.row-control", "check"),
+            ("Reload", "app.row-control", "reload"),
+            (
+                "Start with dependencies",
+                "app.row-control",
+                "start-with-dependencies",
+            ),
+            (
+                "Stop with dependents",
+                "app.row-control",
+                "stop-with-dependents",
+            ),
+            (
+                "Start or end maintenance…",
+                "app.row-control",
+                "maintenance",
+            ),
+        ]),
+    );
+    menu.append_submenu(
+        Some("Send signal"),
+        &section(&[
+            ("SIGHUP", "app.row-control", "hup"),
+            ("SIGALRM", "app.row-control", "alarm"),
+            ("SIGINT", "app.row-control-force", "interrupt"),
+            ("SIGTERM", "app.row-control-force", "term"),
+            ("SIGKILL", "app.row-control-force", "kill"),
+        ]),
+    );
+    menu.append_section(
+        None,
+        &section(&[
+            if favorite {
+                ("Remove from favorites", "app.row-open", "favorite")
+            } else {
+                ("Add to favorites", "app.row-open", "favorite")
+            },
+            ("Open log…", "app.row-open", "logs"),
+            ("Copy diagnostic report", "app.row-open", "report"),
+            ("Edit description…", "app.row-open", "description"),
+            ("Set category…", "app.row-open", "category"),
+            ("Open definition folder", "app.row-open", "folder"),
+            ("Open terminal in definition", "app.row-open", "terminal"),
+            ("Open log folder", "app.row-open", "log-folder"),
+            (
+                "Open terminal in log folder",
+                "app.row-open",
+                "log-terminal",
+            ),
+        ]),
+    );
+    menu.append_section(None, &section(&[("Delete…", "app.row-control", "delete")]));
+    menu
 }
 
-fn build_status_indicator(level: StatusLevel) -> gtk::DrawingArea {
+/// Count the checked services and offer the selection bar's actions once
+/// there are any.
+fn update_selection_summary(
+    label: &gtk::Label,
+    actions: &gtk::Box,
+    checks: &[(String, gtk::CheckButton)],
+) {
+    let count = checks.iter().filter(|(_, check)| check.is_active()).count();
+    label.set_text(&match count {
+        0 => "No services selected".to_string(),
+        1 => "1 service selected".to_string(),
+        count => format!("{count} services selected"),
+    });
+    actions.set_sensitive(count > 0);
+}
+
+pub fn build_status_indicator(level: StatusLevel) -> gtk::DrawingArea {
     let indicator = gtk::DrawingArea::builder()
         .content_width(14)
         .content_height(14)
@@ -130,7 +387,12 @@ enum ThemeCircle {
 }
 
 impl AppWidgets {
-    pub fn new(app: &adw::Application, show_all_services: bool) -> Self {
+    pub fn new(
+        app: &adw::Application,
+        show_all_services: bool,
+        list_columns: &[ListColumn],
+        group_by_category: bool,
+    ) -> Self {
         gtk::Window::set_default_icon_name("runkit");
         let window = adw::ApplicationWindow::builder()
             .application(app)
@@ -181,7 +443,7 @@ impl AppWidgets {
 
         let theme_action = gio::SimpleAction::new_stateful(
             "theme",
-            Some(&glib::VariantTy::STRING),
+            Some(glib::VariantTy::STRING),
             &glib::Variant::from(current_theme_key),
         );
         app.add_action(&theme_action);
@@ -204,8 +466,42 @@ impl AppWidgets {
 
         let preferences_action = gio::SimpleAction::new("preferences", None);
         app.add_action(&preferences_action);
+        let import_action = gio::SimpleAction::new("import", None);
+        app.add_action(&import_action);
+        let template_action = gio::SimpleAction::new("new-from-template", None);
+        app.add_action(&template_action);
+        let trash_action = gio::SimpleAction::new("trash", None);
+        app.add_action(&trash_action);
+        let save_profile_action = gio::SimpleAction::new("save-profile", None);
+        app.add_action(&save_profile_action);
+        let restore_profile_action = gio::SimpleAction::new("restore-profile", None);
+        app.add_action(&restore_profile_action);
+        let dashboard_action = gio::SimpleAction::new("dashboard", None);
+        app.add_action(&dashboard_action);
+        let silence_action = gio::SimpleAction::new("silence-alerts", None);
+        app.add_action(&silence_action);
+        let archive_logs_action = gio::SimpleAction::new("archive-failed-logs", None);
+        app.add_action(&archive_logs_action);
+        let stage_logs_action = gio::SimpleAction::new("stage-logs", None);
+        app.add_action(&stage_logs_action);
         let about_action = gio::SimpleAction::new("about", None);
         app.add_action(&about_action);
+        let control_action = gio::SimpleAction::new("control", Some(glib::VariantTy::STRING));
+        app.add_action(&control_action);
+        let control_force_action =
+            gio::SimpleAction::new("control-force", Some(glib::VariantTy::STRING));
+        app.add_action(&control_force_action);
+        let row_control_action =
+            gio::SimpleAction::new("row-control", Some(glib::VariantTy::new("(ss)").unwrap()));
+        app.add_action(&row_control_action);
+        let row_control_force_action = gio::SimpleAction::new(
+            "row-control-force",
+            Some(glib::VariantTy::new("(ss)").unwrap()),
+        );
+        app.add_action(&row_control_force_action);
+        let row_open_action =
+            gio::SimpleAction::new("row-open", Some(glib::VariantTy::new("(ss)").unwrap()));
+        app.add_action(&row_open_action);
 
         let menu_button = gtk::MenuButton::builder()
             .icon_name("open-menu-symbolic")
@@ -307,6 +603,72 @@ impl AppWidgets {
         prefs_row.set_action_name(Some("app.preferences"));
         menu_list.append(&prefs_row);
 
+        let import_row = adw::ActionRow::builder()
+            .title("Import service…")
+            .activatable(true)
+            .build();
+        import_row.set_action_name(Some("app.import"));
+        menu_list.append(&import_row);
+
+        let template_row = adw::ActionRow::builder()
+            .title("New service from template…")
+            .activatable(true)
+            .build();
+        template_row.set_action_name(Some("app.new-from-template"));
+        menu_list.append(&template_row);
+
+        let trash_row = adw::ActionRow::builder()
+            .title("Restore deleted service…")
+            .activatable(true)
+            .build();
+        trash_row.set_action_name(Some("app.trash"));
+        menu_list.append(&trash_row);
+
+        let save_profile_row = adw::ActionRow::builder()
+            .title("Save profile…")
+            .subtitle("Which services are enabled, for another machine or later")
+            .activatable(true)
+            .build();
+        save_profile_row.set_action_name(Some("app.save-profile"));
+        menu_list.append(&save_profile_row);
+
+        let restore_profile_row = adw::ActionRow::builder()
+            .title("Restore profile…")
+            .activatable(true)
+            .build();
+        restore_profile_row.set_action_name(Some("app.restore-profile"));
+        menu_list.append(&restore_profile_row);
+
+        let dashboard_row = adw::ActionRow::builder()
+            .title("Monitoring dashboard")
+            .activatable(true)
+            .build();
+        dashboard_row.set_action_name(Some("app.dashboard"));
+        menu_list.append(&dashboard_row);
+
+        let silence_row = adw::ActionRow::builder()
+            .title("Silence alerts…")
+            .activatable(true)
+            .build();
+        silence_row.set_action_name(Some("app.silence-alerts"));
+        menu_list.append(&silence_row);
+
+        let archive_logs_row = adw::ActionRow::builder()
+            .title("Archive failed-service logs")
+            .subtitle("Saved to ~/runkit-logs")
+            .activatable(true)
+            .build();
+        archive_logs_row.set_action_name(Some("app.archive-failed-logs"));
+        menu_list.append(&archive_logs_row);
+
+        let stage_logs_row = adw::ActionRow::builder()
+            .title("Boot logs")
+            .subtitle("What runit logged while starting the system")
+            .activatable(true)
+            .build();
+        stage_logs_row.set_action_name(Some("app.stage-logs"));
+        menu_list.append(&stage_logs_row);
+
         let about_row = adw::ActionRow::builder()
             .title("About Runkit")
             .activatable(true)
@@ -317,6 +679,36 @@ impl AppWidgets {
         popover_box.append(&menu_list);
         popover.set_child(Some(&popover_box));
 
+        let health_list = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(6)
+            .build();
+        let health_fix_button = gtk::Button::builder()
+            .label("Keep duplicates in current runlevel only")
+            .tooltip_text("Remove extra enable links so each service is supervised once")
+            .halign(gtk::Align::End)
+            .visible(false)
+            .build();
+        let health_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(12)
+            .margin_top(12)
+            .margin_bottom(12)
+            .margin_start(12)
+            .margin_end(12)
+            .build();
+        health_box.append(&health_list);
+        health_box.append(&health_fix_button);
+        let health_popover = gtk::Popover::builder().child(&health_box).build();
+        let health_button = gtk::MenuButton::builder()
+            .icon_name("dialog-warning-symbolic")
+            .css_classes(["flat", "warning"])
+            .valign(gtk::Align::Center)
+            .popover(&health_popover)
+            .visible(false)
+            .build();
+
+        header_end_box.append(&health_button);
         header_end_box.append(&menu_button);
         let window_controls_end = gtk::WindowControls::new(gtk::PackType::End);
         header_end_box.append(&window_controls_end);
@@ -423,18 +815,142 @@ impl AppWidgets {
                 .set_tooltip_text(Some("Click to include disabled services in the list."));
         }
 
+        let columns_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(6)
+            .margin_top(12)
+            .margin_bottom(12)
+            .margin_start(12)
+            .margin_end(12)
+            .build();
+        let columns_heading = gtk::Label::builder()
+            .label("Show in list")
+            .xalign(0.0)
+            .css_classes(["heading"])
+            .build();
+        columns_box.append(&columns_heading);
+        let column_checks: Vec<(ListColumn, gtk::CheckButton)> = ListColumn::ALL
+            .into_iter()
+            .map(|column| {
+                let check = gtk::CheckButton::with_label(column.label());
+                check.set_active(list_columns.contains(&column));
+                columns_box.append(&check);
+                (column, check)
+            })
+            .collect();
+        let categories_heading = gtk::Label::builder()
+            .label("Categories")
+            .xalign(0.0)
+            .margin_top(6)
+            .css_classes(["heading"])
+            .build();
+        let group_check = gtk::CheckButton::with_label("Group by category");
+        group_check.set_active(group_by_category);
+        let category_names = gtk::StringList::new(&["All categories"]);
+        let category_filter = gtk::DropDown::builder()
+            .model(&category_names)
+            .tooltip_text("Only list services in this category")
+            .build();
+        columns_box.append(&categories_heading);
+        columns_box.append(&group_check);
+        columns_box.append(&category_filter);
+        let columns_button = gtk::MenuButton::builder()
+            .icon_name("view-list-symbolic")
+            .css_classes(["flat"])
+            .tooltip_text("Choose columns, grouping and categories for the service list")
+            .popover(&gtk::Popover::builder().child(&columns_box).build())
+            .build();
+
+        let scope_system = gtk::ToggleButton::with_label("System");
+        scope_system.set_active(true);
+        scope_system.set_hexpand(true);
+        scope_system.set_tooltip_text(Some("Services supervised by runit for the whole system."));
+        let scope_user = gtk::ToggleButton::with_label("User");
+        scope_user.set_group(Some(&scope_system));
+        scope_user.set_hexpand(true);
+        scope_user.set_tooltip_text(Some(
+            "Your own services in ~/.config/runit/sv, managed without runkitd or a password.",
+        ));
+        let scope_row = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .css_classes(["linked"])
+            .build();
+        scope_row.append(&scope_system);
+        scope_row.append(&scope_user);
+
         let controls_row = gtk::Box::builder()
             .orientation(gtk::Orientation::Horizontal)
             .spacing(6)
             .build();
+        let selection_toggle = gtk::ToggleButton::builder()
+            .icon_name("selection-mode-symbolic")
+            .css_classes(["flat"])
+            .tooltip_text("Select several services to act on at once")
+            .build();
+
         controls_row.append(&search_entry);
         controls_row.append(&service_filter_toggle);
+        controls_row.append(&columns_button);
+        controls_row.append(&selection_toggle);
+
+        let chips_row = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(6)
+            .build();
+        let state_chips: Vec<(StateFilter, gtk::ToggleButton)> = [
+            (StateFilter::Running, "Running"),
+            (StateFilter::Down, "Down"),
+            (StateFilter::Failed, "Failed"),
+        ]
+        .into_iter()
+        .map(|(state, label)| {
+            let chip = gtk::ToggleButton::builder()
+                .label(label)
+                .css_classes(["flat", "caption"])
+                .tooltip_text(format!("Only list {state} services"))
+                .build();
+            chips_row.append(&chip);
+            (state, chip)
+        })
+        .collect();
+        let sort_labels: Vec<String> = ServiceSort::ALL
+            .iter()
+            .map(|sort| format!("By {}", sort.label().to_lowercase()))
+            .collect();
+        let sort_labels: Vec<&str> = sort_labels.iter().map(String::as_str).collect();
+        let sort_dropdown = gtk::DropDown::builder()
+            .model(&gtk::StringList::new(&sort_labels))
+            .tooltip_text("Order of the service list")
+            .halign(gtk::Align::End)
+            .hexpand(true)
+            .build();
+        chips_row.append(&sort_dropdown);
 
         let loading_spinner = gtk::Spinner::builder().spinning(false).build();
+        let loading_label = gtk::Label::builder()
+            .xalign(0.0)
+            .hexpand(true)
+            .ellipsize(pango::EllipsizeMode::End)
+            .css_classes(["dim-label"])
+            .visible(false)
+            .build();
+        let loading_cancel = gtk::Button::builder()
+            .label("Cancel")
+            .css_classes(["flat"])
+            .visible(false)
+            .build();
+        let loading_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(6)
+            .halign(gtk::Align::Fill)
+            .build();
+        loading_box.append(&loading_spinner);
+        loading_box.append(&loading_label);
+        loading_box.append(&loading_cancel);
         let loading_revealer = gtk::Revealer::builder()
             .reveal_child(false)
             .transition_type(gtk::RevealerTransitionType::SlideDown)
-            .child(&loading_spinner)
+            .child(&loading_box)
             .build();
 
         let list_box = gtk::ListBox::new();
@@ -457,11 +973,62 @@ impl AppWidgets {
             .margin_end(16)
             .build();
         left_column.set_width_request(340);
+        left_column.append(&scope_row);
         left_column.append(&controls_row);
+        left_column.append(&chips_row);
         left_column.append(&summary_label);
         left_column.append(&loading_revealer);
         left_column.append(&list_scroller);
 
+        let selection_label = gtk::Label::builder()
+            .label("No services selected")
+            .xalign(0.0)
+            .hexpand(true)
+            .css_classes(["dim-label"])
+            .build();
+        let selection_clear = gtk::Button::builder()
+            .label("Clear")
+            .css_classes(["flat"])
+            .build();
+        let selection_header = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(6)
+            .build();
+        selection_header.append(&selection_label);
+        selection_header.append(&selection_clear);
+        let selection_actions = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .homogeneous(true)
+            .css_classes(["linked"])
+            .sensitive(false)
+            .build();
+        let selection_buttons: Vec<(&'static str, gtk::Button)> = [
+            ("start", "Start"),
+            ("stop", "Stop"),
+            ("restart", "Restart"),
+            ("enable", "Enable"),
+            ("disable", "Disable"),
+        ]
+        .into_iter()
+        .map(|(action, label)| {
+            let button = gtk::Button::with_label(label);
+            selection_actions.append(&button);
+            (action, button)
+        })
+        .collect();
+        let selection_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(6)
+            .build();
+        selection_box.append(&selection_header);
+        selection_box.append(&selection_actions);
+        let selection_revealer = gtk::Revealer::builder()
+            .reveal_child(false)
+            .transition_type(gtk::RevealerTransitionType::SlideUp)
+            .child(&selection_box)
+            .build();
+        left_column.append(&selection_revealer);
+
         let action_start = gtk::Button::builder()
             .label("Start")
             .css_classes(["suggested-action"])
@@ -469,9 +1036,49 @@ impl AppWidgets {
         let action_stop = gtk::Button::with_label("Stop");
         let action_restart = gtk::Button::with_label("Restart");
         let action_reload = gtk::Button::with_label("Reload");
+        let action_advanced = gtk::MenuButton::builder()
+            .label("Advanced")
+            .menu_model(&build_control_menu())
+            .tooltip_text("Send the service a signal, pause it, or force it down.")
+            .build();
         let action_enable = gtk::Button::with_label("Enable service");
+        action_enable.set_tooltip_text(Some(
+            "Enable this service and start it now; both are undone if it does not come up.",
+        ));
         let action_disable = gtk::Button::with_label("Disable service");
+        action_disable.set_tooltip_text(Some("Stop this service and disable it."));
         let action_check = gtk::Button::with_label("Run health check");
+        let action_export = gtk::Button::with_label("Export…");
+        action_export.set_tooltip_text(Some("Save this service definition as a .tar.gz archive."));
+        let action_clone = gtk::Button::with_label("Clone…");
+        action_clone.set_tooltip_text(Some(
+            "Create another instance of this service, such as a getty on a further tty.",
+        ));
+        let action_edit_files = gtk::Button::with_label("Edit files…");
+        action_edit_files.set_tooltip_text(Some(
+            "Edit the run, finish, check or conf file. The old version is kept as a backup.",
+        ));
+        let action_environment = gtk::Button::with_label("Environment…");
+        action_environment.set_tooltip_text(Some(
+            "Set variables such as RUST_LOG in the env dir the run script loads.",
+        ));
+        let action_restart_policy = gtk::Button::with_label("Keep down after exit");
+        let action_down_file = gtk::Button::with_label("Don't start at boot");
+        let action_maintenance = gtk::Button::with_label("Start maintenance");
+        let action_log_config = gtk::Button::with_label("Log rotation…");
+        action_log_config.set_tooltip_text(Some(
+            "Change how large svlogd lets this service's log grow and how many old files it keeps.",
+        ));
+
+        let action_metrics = gtk::Button::with_label("Resource usage…");
+        action_metrics.set_tooltip_text(Some(
+            "Watch CPU, memory, open files and threads of this service and its child processes.",
+        ));
+
+        let action_runlevels = gtk::Button::with_label("Runlevels…");
+        action_runlevels.set_tooltip_text(Some(
+            "Choose which runlevels start this service, or switch to another runlevel.",
+        ));
 
         let action_row_one = gtk::Box::builder()
             .orientation(gtk::Orientation::Horizontal)
@@ -481,6 +1088,7 @@ impl AppWidgets {
         action_row_one.append(&action_stop);
         action_row_one.append(&action_restart);
         action_row_one.append(&action_reload);
+        action_row_one.append(&action_advanced);
 
         let action_row_two = gtk::Box::builder()
             .orientation(gtk::Orientation::Horizontal)
@@ -489,6 +1097,16 @@ impl AppWidgets {
         action_row_two.append(&action_enable);
         action_row_two.append(&action_disable);
         action_row_two.append(&action_check);
+        action_row_two.append(&action_restart_policy);
+        action_row_two.append(&action_down_file);
+        action_row_two.append(&action_maintenance);
+        action_row_two.append(&action_log_config);
+        action_row_two.append(&action_metrics);
+        action_row_two.append(&action_runlevels);
+        action_row_two.append(&action_export);
+        action_row_two.append(&action_clone);
+        action_row_two.append(&action_edit_files);
+        action_row_two.append(&action_environment);
 
         let detail_title = gtk::Label::builder()
             .xalign(0.0)
@@ -504,6 +1122,23 @@ impl AppWidgets {
             .wrap_mode(pango::WrapMode::WordChar)
             .build();
 
+        let detail_run_script_label = gtk::Label::builder()
+            .xalign(0.0)
+            .css_classes(["dim-label", "caption"])
+            .wrap(true)
+            .wrap_mode(pango::WrapMode::WordChar)
+            .selectable(true)
+            .visible(false)
+            .build();
+
+        let detail_history_label = gtk::Label::builder()
+            .xalign(0.0)
+            .css_classes(["warning", "caption"])
+            .wrap(true)
+            .wrap_mode(pango::WrapMode::WordChar)
+            .visible(false)
+            .build();
+
         let detail_description_label = gtk::Label::builder()
             .xalign(0.0)
             .wrap(true)
@@ -544,8 +1179,79 @@ impl AppWidgets {
         detail_box.append(&detail_description_label);
         detail_box.append(&tag_row);
         detail_box.append(&detail_state_label);
+        detail_box.append(&detail_run_script_label);
+        detail_box.append(&detail_history_label);
         detail_box.append(&action_row_one);
         detail_box.append(&action_row_two);
+
+        let definition_row = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .css_classes(["linked"])
+            .build();
+        let mut definition_files: Vec<(&'static str, gtk::ToggleButton)> = Vec::new();
+        for file in DEFINITION_FILES {
+            let button = gtk::ToggleButton::with_label(file);
+            match definition_files.first() {
+                Some((_, first)) => button.set_group(Some(first)),
+                None => button.set_active(true),
+            }
+            definition_row.append(&button);
+            definition_files.push((file, button));
+        }
+        let definition_edit = gtk::ToggleButton::builder()
+            .icon_name("document-edit-symbolic")
+            .css_classes(["flat"])
+            .tooltip_text("Edit this file")
+            .build();
+        let definition_save = gtk::Button::builder()
+            .label("Save")
+            .css_classes(["suggested-action"])
+            .visible(false)
+            .build();
+        let definition_toolbar = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(6)
+            .build();
+        definition_toolbar.append(&definition_row);
+        definition_toolbar.append(&gtk::Box::builder().hexpand(true).build());
+        definition_toolbar.append(&definition_edit);
+        definition_toolbar.append(&definition_save);
+        let definition_status = gtk::Label::builder()
+            .xalign(0.0)
+            .wrap(true)
+            .css_classes(["dim-label", "caption"])
+            .visible(false)
+            .build();
+        let definition_buffer = gtk::TextBuffer::new(None);
+        definition_buffer.connect_changed(highlight_shell);
+        let definition_view = gtk::TextView::builder()
+            .buffer(&definition_buffer)
+            .editable(false)
+            .cursor_visible(false)
+            .monospace(true)
+            .top_margin(6)
+            .bottom_margin(6)
+            .left_margin(6)
+            .right_margin(6)
+            .build();
+        let definition_scroller = gtk::ScrolledWindow::builder()
+            .child(&definition_view)
+            .min_content_height(240)
+            .css_classes(["card"])
+            .build();
+        let definition_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(6)
+            .margin_top(6)
+            .build();
+        definition_box.append(&definition_toolbar);
+        definition_box.append(&definition_status);
+        definition_box.append(&definition_scroller);
+        let definition_expander = gtk::Expander::builder()
+            .label("Scripts and configuration")
+            .child(&definition_box)
+            .build();
+        detail_box.append(&definition_expander);
         detail_box.append(&gtk::Separator::new(gtk::Orientation::Horizontal));
 
         let activity_label = gtk::Label::builder()
@@ -555,6 +1261,36 @@ impl AppWidgets {
             .css_classes(["body"])
             .build();
         activity_label.set_text("Select a service to see recent activity.");
+
+        let log_filter_all = gtk::ToggleButton::with_label("All");
+        log_filter_all.set_active(true);
+        let log_filter_warnings = gtk::ToggleButton::with_label("Warnings");
+        log_filter_warnings.set_group(Some(&log_filter_all));
+        log_filter_warnings.set_tooltip_text(Some("Show only warnings and errors."));
+        let log_filter_errors = gtk::ToggleButton::with_label("Errors");
+        log_filter_errors.set_group(Some(&log_filter_all));
+        log_filter_errors.set_tooltip_text(Some("Show only errors."));
+        let log_filter_row = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .halign(gtk::Align::Start)
+            .css_classes(["linked"])
+            .build();
+        log_filter_row.append(&log_filter_all);
+        log_filter_row.append(&log_filter_warnings);
+        log_filter_row.append(&log_filter_errors);
+        let log_viewer_button = gtk::Button::builder()
+            .label("Open Log…")
+            .css_classes(["flat"])
+            .tooltip_text("Follow, search and export the whole log")
+            .build();
+        let log_toolbar = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(6)
+            .build();
+        log_toolbar.append(&log_filter_row);
+        log_toolbar.append(&gtk::Box::builder().hexpand(true).build());
+        log_toolbar.append(&log_viewer_button);
+        detail_box.append(&log_toolbar);
         detail_box.append(&activity_label);
 
         let placeholder = adw::StatusPage::builder()
@@ -569,7 +1305,12 @@ impl AppWidgets {
             .transition_type(gtk::StackTransitionType::Crossfade)
             .build();
         detail_stack.add_named(&placeholder, Some("placeholder"));
-        detail_stack.add_named(&detail_box, Some("details"));
+        // Scrolls once the scripts are expanded below the actions.
+        let detail_scroller = gtk::ScrolledWindow::builder()
+            .child(&detail_box)
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .build();
+        detail_stack.add_named(&detail_scroller, Some("details"));
         detail_stack.set_visible_child_name("placeholder");
 
         let right_column = gtk::Box::builder()
@@ -595,29 +1336,100 @@ impl AppWidgets {
         AppWidgets {
             window: window.clone(),
             search_entry,
+            scope_system,
+            scope_user,
+            user_scope: Cell::new(false),
             service_filter_toggle,
+            state_chips,
+            sort_dropdown,
+            column_checks,
+            group_check,
+            category_filter,
+            category_names,
+            category_ids: RefCell::new(Vec::new()),
             list_box,
+            selection_toggle,
+            selection_revealer,
+            selection_buttons,
+            selection_clear,
+            selection_checks: Rc::new(RefCell::new(Vec::new())),
+            selection_label,
+            selection_actions,
             action_start,
             action_stop,
             action_restart,
             action_reload,
+            action_advanced,
             action_enable,
             action_disable,
             action_check,
+            action_export,
+            action_clone,
+            action_edit_files,
+            action_environment,
+            action_restart_policy,
+            action_down_file,
+            action_maintenance,
+            action_log_config,
+            action_metrics,
+            action_runlevels,
+            action_row_one,
+            action_row_two,
+            import_row,
+            template_row,
+            trash_row,
+            save_profile_row,
+            restore_profile_row,
+            silence_row,
+            stage_logs_row,
+            window_title,
             detail_stack,
             detail_title,
             detail_state_label,
+            detail_run_script_label,
+            detail_history_label,
+            definition_expander,
+            definition_files,
+            definition_edit,
+            definition_save,
+            definition_status,
+            definition_view,
             detail_description_label,
             detail_status_indicator,
             detail_status_text,
+            log_filter_all,
+            log_filter_warnings,
+            log_filter_errors,
+            log_viewer_button,
             activity_label,
             banner,
             summary_label,
             loading_revealer,
             loading_spinner,
+            loading_label,
+            loading_cancel,
+            toast_overlay,
+            health_button,
+            health_list,
+            health_fix_button,
+            timestamp_format: Cell::new(TimestampFormat::default()),
             menu_popover: popover,
             preferences_action,
+            import_action,
+            template_action,
+            trash_action,
+            save_profile_action,
+            restore_profile_action,
+            dashboard_action,
+            silence_action,
+            archive_logs_action,
+            stage_logs_action,
             about_action,
+            control_action,
+            control_force_action,
+            row_control_action,
+            row_control_force_action,
+            row_open_action,
         }
     }
 
@@ -627,30 +1439,118 @@ impl AppWidgets {
             self.loading_spinner.start();
         } else {
             self.loading_spinner.stop();
+            self.loading_label.set_visible(false);
+            self.loading_cancel.set_visible(false);
         }
     }
 
-    pub fn populate_list(&self, services: &[ServiceInfo]) {
+    /// Say what the running job is doing next to the spinner, and offer to
+    /// cancel it.
+    pub fn show_job(&self, message: &str) {
+        self.show_loading(true);
+        self.loading_label.set_text(message);
+        self.loading_label.set_visible(!message.is_empty());
+        self.loading_cancel.set_visible(true);
+    }
+
+    pub fn populate_list(
+        &self,
+        services: &[ServiceInfo],
+        columns: &[ListColumn],
+        column_data: &ColumnData,
+        sections: &[String],
+        favorites: &[String],
+    ) {
         let current = self.current_service();
+        let checked = self.selected_services();
+        let selecting = self.selection_toggle.is_active();
+        let mut checks = Vec::new();
         self.list_box.unselect_all();
         while let Some(row) = self.list_box.row_at_index(0) {
             self.list_box.remove(&row);
         }
+        if !sections.is_empty() {
+            self.list_box.set_header_func(|row, before| {
+                let section = |row: &gtk::ListBoxRow| unsafe {
+                    row.data::<String>("section")
+                        .map(|section| section.as_ref().clone())
+                };
+                let title = section(row).unwrap_or_default();
+                if before.is_some_and(|before| section(before).as_ref() == Some(&title)) {
+                    row.set_header(None::<&gtk::Widget>);
+                    return;
+                }
+                let header = gtk::Label::builder()
+                    .label(title)
+                    .xalign(0.0)
+                    .margin_top(12)
+                    .margin_bottom(6)
+                    .margin_start(12)
+                    .css_classes(["heading", "dim-label"])
+                    .build();
+                row.set_header(Some(&header));
+            });
+        } else {
+            self.list_box.unset_header_func();
+        }
 
-        for service in services {
+        for (index, service) in services.iter().enumerate() {
             let row = adw::ActionRow::builder()
                 .title(&service.name)
-                .subtitle(&list_row_subtitle(service))
+                .subtitle(list_row_subtitle(service))
                 .build();
             row.set_selectable(true);
             row.set_activatable(true);
             unsafe {
                 row.set_data("service-name", service.name.clone());
+                if let Some(section) = sections.get(index) {
+                    row.set_data("section", section.clone());
+                }
+            }
+
+            if selecting {
+                let check = gtk::CheckButton::builder()
+                    .active(checked.contains(&service.name))
+                    .valign(gtk::Align::Center)
+                    .build();
+                let label = self.selection_label.clone();
+                let actions = self.selection_actions.clone();
+                let all = Rc::clone(&self.selection_checks);
+                check.connect_toggled(move |_| {
+                    update_selection_summary(&label, &actions, &all.borrow());
+                });
+                row.add_prefix(&check);
+                checks.push((service.name.clone(), check));
+            }
+
+            for column in columns {
+                let cell = gtk::Label::builder()
+                    .label(column.text(service, column_data).unwrap_or_default())
+                    .width_chars(column.width_chars())
+                    .max_width_chars(column.width_chars())
+                    .ellipsize(pango::EllipsizeMode::End)
+                    .xalign(1.0)
+                    .tooltip_text(column.label())
+                    .css_classes(["dim-label", "numeric", "caption"])
+                    .build();
+                row.add_suffix(&cell);
             }
 
             let indicator = build_status_indicator(status_level(service));
             row.add_suffix(&indicator);
 
+            let quick_actions = gtk::MenuButton::builder()
+                .icon_name("view-more-symbolic")
+                .menu_model(&build_row_menu(
+                    &service.name,
+                    favorites.contains(&service.name),
+                ))
+                .tooltip_text("More actions")
+                .valign(gtk::Align::Center)
+                .css_classes(["flat"])
+                .build();
+            row.add_suffix(&quick_actions);
+
             self.list_box.append(&row);
 
             if current
@@ -665,6 +1565,76 @@ impl AppWidgets {
         if self.list_box.selected_row().is_none() {
             self.show_placeholder();
         }
+        *self.selection_checks.borrow_mut() = checks;
+        update_selection_summary(
+            &self.selection_label,
+            &self.selection_actions,
+            &self.selection_checks.borrow(),
+        );
+        self.selection_revealer.set_reveal_child(selecting);
+    }
+
+    /// Offer `categories` (ids) in the category filter, keeping the one
+    /// chosen if it is still there.
+    pub fn set_categories(&self, categories: &[String]) {
+        let selected = self.selected_category();
+        let labels: Vec<String> = categories
+            .iter()
+            .map(|category| category_label(category))
+            .collect();
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        self.category_ids.replace(categories.to_vec());
+        self.category_names
+            .splice(1, self.category_names.n_items() - 1, &labels);
+        let position = selected
+            .and_then(|selected| categories.iter().position(|id| *id == selected))
+            .map_or(0, |index| index as u32 + 1);
+        self.category_filter.set_selected(position);
+    }
+
+    /// The category the list is limited to, if any.
+    pub fn selected_category(&self) -> Option<String> {
+        let position = self.category_filter.selected();
+        if position == 0 || position == gtk::INVALID_LIST_POSITION {
+            return None;
+        }
+        self.category_ids
+            .borrow()
+            .get(position as usize - 1)
+            .cloned()
+    }
+
+    /// The states of the active chips.
+    pub fn selected_states(&self) -> Vec<StateFilter> {
+        self.state_chips
+            .iter()
+            .filter(|(_, chip)| chip.is_active())
+            .map(|(state, _)| *state)
+            .collect()
+    }
+
+    pub fn selected_sort(&self) -> ServiceSort {
+        ServiceSort::ALL
+            .get(self.sort_dropdown.selected() as usize)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The services checked in selection mode, in list order.
+    pub fn selected_services(&self) -> Vec<String> {
+        self.selection_checks
+            .borrow()
+            .iter()
+            .filter(|(_, check)| check.is_active())
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    pub fn clear_selection(&self) {
+        let checks = self.selection_checks.borrow().clone();
+        for (_, check) in checks {
+            check.set_active(false);
+        }
     }
 
     pub fn set_service_filter_toggle(&self, show_all: bool) {
@@ -692,13 +1662,12 @@ impl AppWidgets {
     pub fn select_service(&self, service: &str) {
         let mut child = self.list_box.first_child();
         while let Some(widget) = child {
-            if let Ok(row) = widget.clone().downcast::<gtk::ListBoxRow>() {
-                if let Some(name) = self.row_service_name(&row) {
-                    if name == service {
-                        self.list_box.select_row(Some(&row));
-                        return;
-                    }
-                }
+            if let Ok(row) = widget.clone().downcast::<gtk::ListBoxRow>()
+                && let Some(name) = self.row_service_name(&row)
+                && name == service
+            {
+                self.list_box.select_row(Some(&row));
+                return;
             }
             child = widget.next_sibling();
         }
@@ -707,8 +1676,7 @@ impl AppWidgets {
     pub fn show_service_details(&self, service: &ServiceInfo) {
         self.detail_stack.set_visible_child_name("details");
         self.detail_title.set_label(&service.name);
-        self.detail_state_label
-            .set_label(&runtime_state_detail(service));
+        self.show_service_state(service);
         self.show_description(service.description.as_deref());
         self.show_activity_loading(&service.name);
 
@@ -717,6 +1685,90 @@ impl AppWidgets {
         configure_indicator(&self.detail_status_indicator, status_level(service));
     }
 
+    /// Refresh only the state line, e.g. to tick the uptime between refreshes.
+    pub fn show_service_state(&self, service: &ServiceInfo) {
+        self.detail_state_label
+            .set_label(&runtime_state_detail(service));
+    }
+
+    /// Summary of the run script; hidden when it could not be worked out.
+    pub fn show_run_script(&self, summary: Option<&str>) {
+        match summary {
+            Some(summary) => {
+                self.detail_run_script_label.set_label(summary);
+                self.detail_run_script_label.set_visible(true);
+            }
+            None => self.detail_run_script_label.set_visible(false),
+        }
+    }
+
+    /// The file of the definition chosen above the script view, e.g. `run`.
+    pub fn selected_definition_file(&self) -> &'static str {
+        self.definition_files
+            .iter()
+            .find(|(_, button)| button.is_active())
+            .map_or(DEFINITION_FILES[0], |(file, _)| file)
+    }
+
+    /// Show what was read of `file`: its text, nothing when it does not exist
+    /// yet, or why it could not be read.
+    pub fn show_definition_file(&self, file: &str, contents: Result<Option<String>, String>) {
+        let buffer = self.definition_view.buffer();
+        let status = match contents {
+            Ok(Some(contents)) => {
+                buffer.set_text(&contents);
+                None
+            }
+            Ok(None) => {
+                buffer.set_text("");
+                Some(format!(
+                    "{file} does not exist yet; saving an edit creates it."
+                ))
+            }
+            Err(err) => {
+                buffer.set_text("");
+                Some(err)
+            }
+        };
+        self.definition_status.set_visible(status.is_some());
+        self.definition_status
+            .set_text(status.as_deref().unwrap_or_default());
+    }
+
+    pub fn definition_text(&self) -> String {
+        let buffer = self.definition_view.buffer();
+        let (start, end) = buffer.bounds();
+        buffer.text(&start, &end, false).to_string()
+    }
+
+    /// Make the script view editable with a Save button, or read-only again.
+    /// The file cannot be switched while editing.
+    pub fn set_definition_editing(&self, editing: bool) {
+        if self.definition_edit.is_active() != editing {
+            self.definition_edit.set_active(editing);
+        }
+        self.definition_view.set_editable(editing);
+        self.definition_view.set_cursor_visible(editing);
+        self.definition_save.set_visible(editing);
+        for (_, button) in &self.definition_files {
+            button.set_sensitive(!editing);
+        }
+        if editing {
+            self.definition_view.grab_focus();
+        }
+    }
+
+    /// Warning about recent crashes; hidden when there were none.
+    pub fn show_crash_history(&self, warning: Option<&str>) {
+        match warning {
+            Some(warning) => {
+                self.detail_history_label.set_label(warning);
+                self.detail_history_label.set_visible(true);
+            }
+            None => self.detail_history_label.set_visible(false),
+        }
+    }
+
     pub fn show_placeholder(&self) {
         self.detail_stack.set_visible_child_name("placeholder");
         self.clear_activity();
@@ -765,15 +1817,77 @@ impl AppWidgets {
             .map(|s| is_auto_start(s.desired_state))
             .unwrap_or(false);
         let service_enabled = service.map(|s| s.enabled).unwrap_or(false);
+        let restart_policy = service.map(|s| s.restart_policy).unwrap_or_default();
+        let has_down_file = service.map(|s| s.has_down_file).unwrap_or(false);
+        let in_maintenance = service.is_some_and(|s| s.maintenance.is_some());
 
         self.action_start
-            .set_sensitive(enabled && service_enabled && !running);
+            .set_sensitive(enabled && service_enabled && !running && !in_maintenance);
         self.action_stop
             .set_sensitive(enabled && service_enabled && running);
         self.action_restart
             .set_sensitive(enabled && service_enabled);
         self.action_reload.set_sensitive(enabled && service_enabled);
+        self.action_advanced
+            .set_sensitive(enabled && service_enabled);
         self.action_check.set_sensitive(enabled && service_enabled);
+        self.action_export.set_sensitive(enabled);
+        self.action_clone
+            .set_sensitive(enabled && service.is_some());
+        self.action_environment
+            .set_sensitive(enabled && service.is_some());
+        self.action_log_config
+            .set_sensitive(enabled && service.is_some());
+        self.action_metrics.set_sensitive(enabled && running);
+        self.action_runlevels
+            .set_sensitive(enabled && service.is_some());
+        self.action_restart_policy
+            .set_sensitive(enabled && service.is_some());
+        // While in maintenance the down file belongs to it.
+        self.action_down_file.set_sensitive(
+            enabled
+                && service.is_some()
+                && restart_policy == RestartPolicy::Always
+                && !in_maintenance,
+        );
+        self.action_maintenance
+            .set_sensitive(enabled && service.is_some());
+        if in_maintenance {
+            self.action_maintenance.set_label("End maintenance");
+            self.action_maintenance.set_tooltip_text(Some(
+                "Bring this service back and start it again if it was running.",
+            ));
+        } else {
+            self.action_maintenance.set_label("Start maintenance");
+            self.action_maintenance.set_tooltip_text(Some(
+                "Stop this service and keep it down without raising alerts.",
+            ));
+        }
+        if has_down_file {
+            self.action_down_file.set_label("Start at boot");
+            self.action_down_file.set_tooltip_text(Some(
+                "Remove the down file so runit starts this service automatically.",
+            ));
+        } else {
+            self.action_down_file.set_label("Don't start at boot");
+            self.action_down_file.set_tooltip_text(Some(
+                "Keep this service enabled but wait for a manual start (creates a down file).",
+            ));
+        }
+        match restart_policy {
+            RestartPolicy::Always => {
+                self.action_restart_policy.set_label("Keep down after exit");
+                self.action_restart_policy.set_tooltip_text(Some(
+                    "Stop runit from restarting this service when it exits or crashes.",
+                ));
+            }
+            RestartPolicy::Never => {
+                self.action_restart_policy
+                    .set_label("Restart automatically");
+                self.action_restart_policy
+                    .set_tooltip_text(Some("Let runit restart this service whenever it exits."));
+            }
+        }
         self.action_enable.set_sensitive(enabled && !autostart);
         self.action_disable.set_sensitive(enabled && autostart);
     }
@@ -794,31 +1908,95 @@ impl AppWidgets {
             .set_text(&format!("Showing {count} matches for “{text}”"));
     }
 
+    /// Show the header warning badge listing broken links and run scripts.
+    pub fn update_health(&self, issues: &[HealthIssue]) {
+        while let Some(child) = self.health_list.first_child() {
+            self.health_list.remove(&child);
+        }
+        self.health_button.set_visible(!issues.is_empty());
+        self.health_fix_button.set_visible(
+            issues
+                .iter()
+                .any(|issue| issue.kind == HealthIssueKind::DuplicateEnable),
+        );
+        if issues.is_empty() {
+            return;
+        }
+
+        self.health_button.set_tooltip_text(Some(&format!(
+            "{} service configuration problem(s)",
+            issues.len()
+        )));
+        let heading = gtk::Label::builder()
+            .label("Service configuration problems")
+            .xalign(0.0)
+            .css_classes(["heading"])
+            .build();
+        self.health_list.append(&heading);
+        for issue in issues {
+            let label = gtk::Label::builder()
+                .label(format!("{}: {}", issue.service, issue.detail))
+                .xalign(0.0)
+                .wrap(true)
+                .max_width_chars(48)
+                .tooltip_text(issue.path.to_string_lossy())
+                .build();
+            self.health_list.append(&label);
+        }
+    }
+
+    pub fn set_timestamp_format(&self, format: TimestampFormat) {
+        self.timestamp_format.set(format);
+    }
+
+    /// Least severe log lines to show, `None` when the filter is on "All".
+    pub fn log_severity_filter(&self) -> Option<LogSeverity> {
+        if self.log_filter_errors.is_active() {
+            Some(LogSeverity::Error)
+        } else if self.log_filter_warnings.is_active() {
+            Some(LogSeverity::Warning)
+        } else {
+            None
+        }
+    }
+
     pub fn show_activity(&self, service: &str, entries: &[LogEntry], notes: &[String]) {
         const MAX_ITEMS: usize = 5;
-
+        // Filtered views exist to find problems, so they skip the action notes
+        // and show more lines.
+        const MAX_FILTERED_ITEMS: usize = 20;
+
+        let severity = self.log_severity_filter();
+        let (max_items, notes) = match severity {
+            Some(_) => (MAX_FILTERED_ITEMS, &[][..]),
+            None => (MAX_ITEMS, notes),
+        };
         let mut bullet_lines = Vec::new();
 
-        for note in notes.iter().take(MAX_ITEMS) {
+        for note in notes.iter().take(max_items) {
             bullet_lines.push(format!("- {note}"));
-            if bullet_lines.len() >= MAX_ITEMS {
+            if bullet_lines.len() >= max_items {
                 break;
             }
         }
 
-        if bullet_lines.len() < MAX_ITEMS {
-            let remaining = MAX_ITEMS - bullet_lines.len();
+        if bullet_lines.len() < max_items {
+            let remaining = max_items - bullet_lines.len();
             let mut logs = entries.iter().rev().take(remaining).collect::<Vec<_>>();
             logs.reverse();
             bullet_lines.extend(logs.into_iter().map(|entry| {
-                let line = format_log_entry(entry);
+                let line = format_log_entry(entry, self.timestamp_format.get());
                 format!("- {line}")
             }));
         }
 
         if bullet_lines.is_empty() {
-            self.activity_label
-                .set_text(&format!("No recent activity recorded for {service} yet."));
+            let text = match severity {
+                Some(LogSeverity::Error) => format!("No recent errors logged by {service}."),
+                Some(_) => format!("No recent warnings or errors logged by {service}."),
+                None => format!("No recent activity recorded for {service} yet."),
+            };
+            self.activity_label.set_text(&text);
         } else {
             self.activity_label.set_text(&bullet_lines.join("\n"));
         }
@@ -835,6 +2013,150 @@ impl AppWidgets {
             .set_text(&format!("Loading recent activity for {service}…"));
     }
 
+    /// Hide every control that would call a polkit-gated method.
+    pub fn set_read_only(&self, read_only: bool) {
+        let system = !self.user_scope.get();
+        for row in [&self.action_row_one, &self.action_row_two] {
+            row.set_visible(!read_only);
+            row.set_sensitive(true);
+            row.set_tooltip_text(None);
+        }
+        if read_only {
+            self.set_definition_editing(false);
+        }
+        self.definition_edit.set_visible(!read_only);
+        self.import_row.set_visible(!read_only && system);
+        self.import_action.set_enabled(!read_only && system);
+        self.template_row.set_visible(!read_only);
+        self.template_action.set_enabled(!read_only);
+        self.trash_row.set_visible(!read_only && system);
+        self.trash_action.set_enabled(!read_only && system);
+        self.save_profile_row.set_visible(system);
+        self.save_profile_action.set_enabled(system);
+        self.restore_profile_row.set_visible(!read_only && system);
+        self.restore_profile_action
+            .set_enabled(!read_only && system);
+        self.silence_row.set_visible(!read_only && system);
+        self.silence_action.set_enabled(!read_only && system);
+        self.stage_logs_row.set_visible(system);
+        self.stage_logs_action.set_enabled(system);
+        self.row_control_action.set_enabled(!read_only);
+        self.selection_toggle.set_visible(!read_only);
+        self.selection_toggle.set_sensitive(true);
+        if read_only {
+            self.selection_toggle.set_active(false);
+        }
+        self.control_force_action.set_enabled(!read_only);
+        self.row_control_force_action.set_enabled(!read_only);
+        self.health_fix_button.set_sensitive(!read_only && system);
+        self.window_title.set_subtitle(match (read_only, system) {
+            (false, true) => "",
+            (true, true) => "Read-only",
+            (false, false) => "Your services",
+            (true, false) => "Your services · Read-only",
+        });
+    }
+
+    /// Hide what runkitd does not offer, going by its capabilities. Call
+    /// after [`AppWidgets::set_read_only`].
+    pub fn hide_unsupported(&self, supports: impl Fn(&str) -> bool) {
+        if !supports("stage-logs") {
+            self.stage_logs_row.set_visible(false);
+            self.stage_logs_action.set_enabled(false);
+        }
+        if !supports("template-bundles") {
+            self.template_row.set_visible(false);
+            self.template_action.set_enabled(false);
+        }
+        if !supports("trash") {
+            self.trash_row.set_visible(false);
+            self.trash_action.set_enabled(false);
+        }
+        if !supports("profiles") {
+            self.save_profile_row.set_visible(false);
+            self.save_profile_action.set_enabled(false);
+            self.restore_profile_row.set_visible(false);
+            self.restore_profile_action.set_enabled(false);
+        }
+        if !supports("alerts") {
+            self.silence_row.set_visible(false);
+            self.silence_action.set_enabled(false);
+        }
+        if !supports("batch-actions") {
+            self.selection_toggle.set_active(false);
+            self.selection_toggle.set_visible(false);
+        }
+    }
+
+    /// Grey out what polkit will refuse this user, so it is not offered only to
+    /// fail after a click. Call after [`AppWidgets::set_read_only`].
+    pub fn set_authorization(&self, control_denied: bool, force_denied: bool) {
+        let tooltip = control_denied.then_some("Your account is not allowed to manage services");
+        for row in [&self.action_row_one, &self.action_row_two] {
+            row.set_sensitive(!control_denied);
+            row.set_tooltip_text(tooltip);
+        }
+        if control_denied {
+            for action in [
+                &self.import_action,
+                &self.template_action,
+                &self.trash_action,
+                &self.restore_profile_action,
+                &self.silence_action,
+                &self.row_control_action,
+            ] {
+                action.set_enabled(false);
+            }
+            self.health_fix_button.set_sensitive(false);
+            self.selection_toggle.set_active(false);
+            self.selection_toggle.set_sensitive(false);
+            self.window_title
+                .set_subtitle("Not authorized to make changes");
+        }
+        if control_denied || force_denied {
+            self.control_force_action.set_enabled(false);
+            self.row_control_force_action.set_enabled(false);
+        }
+    }
+
+    /// Label the app as running where no runsvdir supervises services, e.g.
+    /// in a container, and disable what needs one: the live controls, the
+    /// row menu's verbs and signals. Editing definitions stays available.
+    /// Call after [`AppWidgets::set_read_only`].
+    pub fn set_reduced_mode(&self, confinement: &str) {
+        self.action_row_one.set_sensitive(false);
+        self.action_row_one.set_tooltip_text(Some(&format!(
+            "No runsvdir supervises services in {confinement}, so they cannot be started or stopped"
+        )));
+        for action in [
+            &self.row_control_action,
+            &self.control_force_action,
+            &self.row_control_force_action,
+        ] {
+            action.set_enabled(false);
+        }
+        self.window_title
+            .set_subtitle(&format!("Reduced mode · no live status in {confinement}"));
+    }
+
+    /// Hide the controls that only runkitd provides while the user's own
+    /// services are shown. Call [`AppWidgets::set_read_only`] afterwards.
+    pub fn set_user_scope(&self, user: bool) {
+        self.user_scope.set(user);
+        for button in [
+            &self.action_export,
+            &self.action_log_config,
+            &self.action_metrics,
+            &self.action_runlevels,
+        ] {
+            button.set_visible(!user);
+        }
+    }
+
+    pub fn show_toast(&self, message: &str) {
+        self.toast_overlay.add_toast(adw::Toast::new(message));
+    }
+
     pub fn show_error(&self, message: &str) {
         self.banner.set_title(message);
         self.banner.set_button_label(Some("Dismiss"));
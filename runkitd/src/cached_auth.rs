@@ -0,0 +1,83 @@
+//! The per-user choice to reuse an authorization for a while instead of
+//! typing the password for every change.
+//!
+//! runkitd keeps the choice itself so a client cannot grant it by passing a
+//! flag: a user opts in by authenticating once, and the choice lapses at an
+//! explicit expiry. Each user who opted in has a file named after their uid
+//! holding the Unix time it expires, in a directory only root can read. It is
+//! under `/run`, so the choice never outlives a reboot.
+use std::fs::{self, DirBuilder};
+use std::io::{self, ErrorKind};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::notify::unix_now;
+
+pub const DEFAULT_CACHED_AUTH_DIR: &str = "/run/runkit/cached-authorization";
+
+/// How long the choice lasts when the client does not say.
+pub const DEFAULT_CACHED_AUTH_DURATION: Duration = Duration::from_secs(8 * 60 * 60);
+
+/// Longest a client may ask for; longer requests are cut to this.
+pub const MAX_CACHED_AUTH_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub struct CachedAuthorization {
+    dir: PathBuf,
+}
+
+impl Default for CachedAuthorization {
+    fn default() -> Self {
+        CachedAuthorization::new(DEFAULT_CACHED_AUTH_DIR)
+    }
+}
+
+impl CachedAuthorization {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        CachedAuthorization { dir: dir.into() }
+    }
+
+    /// Unix time `uid`'s choice expires, or `None` when they must
+    /// authenticate for every change.
+    pub fn until(&self, uid: u32) -> Option<u64> {
+        let until: u64 = fs::read_to_string(self.path(uid))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()?;
+        (until > unix_now()).then_some(until)
+    }
+
+    /// Let `uid` reuse authorizations for `duration`, the default when zero
+    /// and at most [`MAX_CACHED_AUTH_DURATION`], replacing an earlier expiry.
+    /// Returns when it ends.
+    pub fn allow_for(&self, uid: u32, duration: Duration) -> io::Result<u64> {
+        let duration = if duration.is_zero() {
+            DEFAULT_CACHED_AUTH_DURATION
+        } else {
+            duration.min(MAX_CACHED_AUTH_DURATION)
+        };
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&self.dir)?;
+        let until = unix_now().saturating_add(duration.as_secs());
+        fs::write(self.path(uid), format!("{until}\n"))?;
+        Ok(until)
+    }
+
+    /// Ask `uid` for a password every time again. Returns whether they had
+    /// been reusing authorizations.
+    pub fn revoke(&self, uid: u32) -> io::Result<bool> {
+        let active = self.until(uid).is_some();
+        match fs::remove_file(self.path(uid)) {
+            Ok(()) => Ok(active),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn path(&self, uid: u32) -> PathBuf {
+        self.dir.join(uid.to_string())
+    }
+}
@@ -1,80 +1,1610 @@
 use std::collections::HashMap;
+use std::fs::File;
+use std::os::fd::{AsRawFd, BorrowedFd};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use zbus::MessageHeader;
-use zbus::blocking::{Connection, ConnectionBuilder};
-use zbus::fdo;
-use zbus_polkit::policykit1::{AuthorityProxyBlocking, CheckAuthorizationFlags, Subject};
+use zbus::blocking::fdo::DBusProxy;
+use zbus::blocking::{Connection, Proxy};
+use zbus::fdo::{self, RequestNameFlags, RequestNameReply};
+use zbus::names::BusName;
+use zbus::zvariant::{Fd, OwnedValue};
+use zbus::{ConnectionBuilder, MessageHeader, SignalContext};
+use zbus_polkit::policykit1::{
+    AuthorityProxyBlocking, AuthorizationResult, CheckAuthorizationFlags, Subject,
+};
 
-use crate::{ActionKind, CommandOutcome, HelperContext, HelperError, HelperResponse};
+use runkit_core::{
+    BOOT_WINDOW, BUS_NAME, BootSchedule, DEFAULT_BOOT_REPORTS, DEFAULT_SESSIONS_DIR, LogFilter,
+    LogPattern, LogSeverity, POLKIT_ACTION_ALERTS, POLKIT_ACTION_ALLOW_CACHE,
+    POLKIT_ACTION_EDIT_FILES, POLKIT_ACTION_ENABLE, POLKIT_ACTION_FORCE, POLKIT_ACTION_PROTECTED,
+    POLKIT_ACTION_REQUIRE_PASSWORD, POLKIT_ACTION_RESTART, POLKIT_ACTION_START, POLKIT_ACTION_STOP,
+    ServiceEvent, ServiceManager, ServiceMonitor, ServiceQuery, ServiceSort, ServiceTemplate,
+    ServiceWatcher, StateChange, StateFilter, SvlogdConfig, TemplateBundle, boot_regressions,
+    format_duration, record_boot_report,
+};
 
-const BUS_NAME: &str = "tech.geektoshi.Runkit1";
-const OBJECT_PATH: &str = "/tech/geektoshi/Runkit1";
-const POLKIT_ACTION_REQUIRE_PASSWORD: &str = "tech.geektoshi.Runkit.require_password";
-const POLKIT_ACTION_ALLOW_CACHE: &str = "tech.geektoshi.Runkit.cached";
+use crate::cached_auth::CachedAuthorization;
+use crate::dbus_typed::TypedController;
+use crate::follow::LogSubscriptions;
+use crate::jobs::{JobProgress, Jobs, Progress};
+use crate::notify::{Alert, AlertTracker, Notifications, Notifier, unix_now};
+use crate::profile::ProfileFormat;
+use crate::{
+    ActionKind, CommandOutcome, HelperContext, HelperError, HelperResponse, controls_supervision,
+    parse_svlogd_filters,
+};
 
-pub fn run_dbus_service() -> Result<(), Box<dyn std::error::Error>> {
+pub(crate) const OBJECT_PATH: &str = "/tech/geektoshi/Runkit1";
+pub(crate) const INTERFACE: &str = "tech.geektoshi.Runkit1.Controller";
+
+/// How often runkitd looks for the end of the last graphical session while
+/// restarts are waiting for it.
+const LOGOUT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often runkitd looks for scheduled checks that are due.
+const CHECK_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How far back `GetServiceHistory` looks.
+const HISTORY_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// How many method calls runkitd works on at once unless `--workers` says
+/// otherwise.
+pub(crate) const DEFAULT_WORKERS: usize = 8;
+
+/// Minutes without calls before runkitd exits, unless `--idle-timeout` says
+/// otherwise.
+pub(crate) const DEFAULT_IDLE_TIMEOUT: u64 = 10;
+
+pub fn run_dbus_service(
+    manager: ServiceManager,
+    context: HelperContext,
+    notify_config: &Path,
+    replace: bool,
+    workers: usize,
+    idle_timeout: Option<Duration>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let watcher = manager.watch();
+    let context = Arc::new(context);
     let service = RunkitService {
-        context: HelperContext::default(),
+        context: Arc::clone(&context),
+        subscriptions: LogSubscriptions::default(),
+        jobs: Jobs::default(),
+    };
+
+    let connection: Connection = async_io::block_on(
+        ConnectionBuilder::system()?
+            .internal_executor(false)
+            .serve_at(OBJECT_PATH, service)?
+            .serve_at(OBJECT_PATH, TypedController { context })?
+            .build(),
+    )?
+    .into();
+    start_workers(connection.inner(), workers)?;
+    acquire_bus_name(&connection, replace)?;
+
+    let desktop = SignalContext::new(connection.inner(), OBJECT_PATH)
+        .map(|ctxt| Arc::new(DesktopNotifier { ctxt }) as Arc<dyn Notifier>)
+        .ok();
+    let notifications = Notifications::load(notify_config, desktop).unwrap_or_else(|err| {
+        eprintln!("runkitd: alerts disabled: {err}");
+        Notifications::default()
+    });
+
+    let notifications = Arc::new(notifications);
+    // Cleared once this boot's report is recorded, or found not to be needed.
+    let boot_report_pending = Arc::new(AtomicBool::new(true));
+
+    if let Some(timeout) = idle_timeout {
+        let busy_connection = connection.clone();
+        let busy_manager = manager.clone();
+        let busy_notifications = Arc::clone(&notifications);
+        let busy_boot_report = Arc::clone(&boot_report_pending);
+        let busy = move || {
+            let service = busy_connection
+                .object_server()
+                .interface::<_, RunkitService>(OBJECT_PATH);
+            service.is_ok_and(|service| service.get().is_busy())
+                || busy_manager
+                    .logout_restarts()
+                    .is_ok_and(|pending| !pending.is_empty())
+                || !busy_manager.scheduled_checks().is_empty()
+                || busy_notifications.reaches_off_bus()
+                || busy_boot_report.load(Ordering::Relaxed)
+        };
+        if let Err(err) = crate::idle::exit_when_idle(&connection, timeout, busy) {
+            eprintln!("runkitd: idle exit disabled: {err}");
+        }
+    }
+
+    let logout_manager = manager.clone();
+    let spawned = thread::Builder::new()
+        .name("runkitd-logout".into())
+        .spawn(move || restart_after_logout(&logout_manager));
+    if let Err(err) = spawned {
+        eprintln!("runkitd: restarts at logout disabled: {err}");
+    }
+
+    let check_manager = manager.clone();
+    let check_connection = connection.clone();
+    let spawned = thread::Builder::new()
+        .name("runkitd-checks".into())
+        .spawn(move || run_scheduled_checks(&check_connection, &check_manager));
+    if let Err(err) = spawned {
+        eprintln!("runkitd: scheduled checks disabled: {err}");
+    }
+
+    let report_manager = manager.clone();
+    let report_pending = Arc::clone(&boot_report_pending);
+    let spawned = thread::Builder::new()
+        .name("runkitd-boot-report".into())
+        .spawn(move || {
+            record_boot_report_once_settled(&report_manager);
+            report_pending.store(false, Ordering::Relaxed);
+        });
+    if let Err(err) = spawned {
+        boot_report_pending.store(false, Ordering::Relaxed);
+        eprintln!("runkitd: boot report disabled: {err}");
+    }
+
+    // Broadcast filesystem changes so clients do not have to poll ListServices.
+    match watcher {
+        Ok(watcher) => emit_service_changes(&connection, watcher, &manager, &notifications),
+        Err(err) => eprintln!("runkitd: change notifications disabled: {err}"),
+    }
+
+    // Keep the process alive while the workers service requests.
+    loop {
+        thread::park_timeout(Duration::from_secs(60));
+    }
+
+    #[allow(unreachable_code)]
+    Ok(())
+}
+
+/// Run the connection's executor on `workers` threads. zbus spawns a task for
+/// every method call, so a call stuck in `sv -w` or a polkit prompt holds up
+/// one worker while the others keep answering, and no more than `workers`
+/// calls run `sv` at once.
+fn start_workers(connection: &zbus::Connection, workers: usize) -> std::io::Result<()> {
+    for index in 0..workers.max(1) {
+        let executor = connection.executor().clone();
+        thread::Builder::new()
+            .name(format!("runkitd-worker-{index}"))
+            .spawn(move || {
+                async_io::block_on(async {
+                    loop {
+                        executor.tick().await;
+                    }
+                })
+            })?;
+    }
+    Ok(())
+}
+
+/// Take the bus name, or explain who holds it. Every instance lets a later
+/// `--replace` take over and exits once it has lost the name.
+fn acquire_bus_name(connection: &Connection, replace: bool) -> Result<(), String> {
+    let flags = if replace {
+        RequestNameFlags::AllowReplacement | RequestNameFlags::ReplaceExisting
+    } else {
+        RequestNameFlags::AllowReplacement | RequestNameFlags::DoNotQueue
     };
+    let dbus = DBusProxy::new(connection).map_err(|err| err.to_string())?;
+    // Subscribe before requesting so a NameAcquired after queueing is not missed.
+    let mut acquired = dbus
+        .receive_name_acquired()
+        .map_err(|err| err.to_string())?;
+    let lost = dbus.receive_name_lost().map_err(|err| err.to_string())?;
+
+    // zbus reports an `Exists` reply as `NameTaken`.
+    match connection.request_name_with_flags(BUS_NAME, flags) {
+        Ok(RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner) => {}
+        Ok(RequestNameReply::Exists) | Err(zbus::Error::NameTaken) => {
+            return Err(format!(
+                "{BUS_NAME} is already owned by {}; stop it or pass --replace",
+                describe_owner(connection)
+            ));
+        }
+        Err(err) => return Err(format!("failed to request {BUS_NAME}: {err}")),
+        Ok(RequestNameReply::InQueue) => {
+            eprintln!(
+                "runkitd: {BUS_NAME} is owned by {}, which does not allow replacement; \
+                 waiting for it to exit",
+                describe_owner(connection)
+            );
+            let taken = acquired.any(|signal| {
+                signal
+                    .args()
+                    .is_ok_and(|args| args.name().as_str() == BUS_NAME)
+            });
+            if !taken {
+                return Err(format!("gave up waiting for {BUS_NAME}"));
+            }
+        }
+    }
+
+    let spawned = thread::Builder::new()
+        .name("runkitd-name".into())
+        .spawn(move || {
+            for signal in lost {
+                if signal
+                    .args()
+                    .is_ok_and(|args| args.name().as_str() == BUS_NAME)
+                    && !crate::idle::is_exiting()
+                {
+                    eprintln!("runkitd: replaced by another instance; exiting");
+                    std::process::exit(0);
+                }
+            }
+        });
+    if let Err(err) = spawned {
+        eprintln!("runkitd: cannot watch for replacement: {err}");
+    }
+    Ok(())
+}
+
+/// `runkitd 0.4.0 (pid 812, /usr/libexec/runkitd)` for the current owner of the
+/// bus name, with whatever could be found out.
+fn describe_owner(connection: &Connection) -> String {
+    let Ok(dbus) = DBusProxy::new(connection) else {
+        return "another process".to_string();
+    };
+    let Ok(owner) = dbus.get_name_owner(BUS_NAME.try_into().expect("valid bus name")) else {
+        return "another process".to_string();
+    };
+    // Instances from before the Version property was added do not report one.
+    let version = Proxy::new(connection, owner.as_str(), OBJECT_PATH, INTERFACE)
+        .and_then(|proxy| proxy.get_property::<String>("Version"))
+        .map(|version| format!("runkitd {version}"))
+        .unwrap_or_else(|_| "an older runkitd".to_string());
+    let Ok(pid) = dbus.get_connection_unix_process_id(owner.as_ref().into()) else {
+        return format!("{version} ({owner})");
+    };
+    match std::fs::read_link(format!("/proc/{pid}/exe")) {
+        Ok(exe) => format!("{version} (pid {pid}, {})", exe.display()),
+        Err(_) => format!("{version} (pid {pid})"),
+    }
+}
+
+/// Restart the services put off until logout once no graphical user session
+/// is left.
+fn restart_after_logout(manager: &ServiceManager) {
+    loop {
+        thread::sleep(LOGOUT_POLL_INTERVAL);
+        match manager.restart_after_logout(Path::new(DEFAULT_SESSIONS_DIR)) {
+            Ok(Some(report)) => {
+                for service in report.restarted {
+                    eprintln!("runkitd: restarted {service} after logout");
+                }
+                for (service, err) in report.failed {
+                    eprintln!("runkitd: failed to restart {service} after logout: {err}");
+                }
+            }
+            Ok(None) => {}
+            Err(err) => eprintln!("runkitd: cannot restart services after logout: {err}"),
+        }
+    }
+}
+
+/// Run each opted-in service's `./check` at its interval while the service
+/// runs and is not in maintenance, and emit `ServiceHealthChanged` whenever
+/// a check passes after failing, or the other way round.
+fn run_scheduled_checks(connection: &Connection, manager: &ServiceManager) {
+    let ctxt = match SignalContext::new(connection.inner(), OBJECT_PATH) {
+        Ok(ctxt) => ctxt,
+        Err(err) => {
+            eprintln!("runkitd: scheduled checks disabled: {err}");
+            return;
+        }
+    };
+    let mut last_run: HashMap<String, Instant> = HashMap::new();
+    let mut passing: HashMap<String, bool> = HashMap::new();
+    loop {
+        thread::sleep(CHECK_POLL_INTERVAL);
+        let scheduled = manager.scheduled_checks();
+        last_run.retain(|service, _| scheduled.iter().any(|(name, _)| name == service));
+        passing.retain(|service, _| scheduled.iter().any(|(name, _)| name == service));
+        for (service, interval) in scheduled {
+            if last_run
+                .get(&service)
+                .is_some_and(|ran| ran.elapsed() < interval)
+            {
+                continue;
+            }
+            last_run.insert(service.clone(), Instant::now());
+            let running = manager
+                .status(&service)
+                .is_ok_and(|state| state.pid().is_some());
+            let in_maintenance = manager
+                .maintenance(&service)
+                .is_ok_and(|maintenance| maintenance.is_some());
+            if !running || in_maintenance {
+                continue;
+            }
+            let result = match manager.run_check(&service) {
+                Ok(result) => result,
+                Err(err) => {
+                    eprintln!("runkitd: cannot check {service}: {err}");
+                    continue;
+                }
+            };
+            if passing.insert(service.clone(), result.passed) == Some(result.passed) {
+                continue;
+            }
+            let emitted = zbus::block_on(RunkitService::service_health_changed(
+                &ctxt,
+                &service,
+                result.passed,
+                &result.detail,
+            ));
+            if let Err(err) = emitted {
+                eprintln!("runkitd: failed to emit ServiceHealthChanged: {err}");
+            }
+        }
+    }
+}
+
+/// Add this boot to the boot report history once [`BOOT_WINDOW`] has passed
+/// since boot, so services started late in boot are included.
+fn record_boot_report_once_settled(manager: &ServiceManager) {
+    let Ok(Some(report)) = manager.boot_report() else {
+        return;
+    };
+    let settled_at = report
+        .booted_at
+        .saturating_add(BOOT_WINDOW.as_secs() as i64);
+    let wait = settled_at.saturating_sub(unix_now() as i64);
+    if wait > 0 {
+        thread::sleep(Duration::from_secs(wait as u64));
+    }
+    let recorded = manager.boot_report().and_then(|report| match report {
+        Some(report) => record_boot_report(Path::new(DEFAULT_BOOT_REPORTS), report).map(Some),
+        None => Ok(None),
+    });
+    match recorded {
+        Ok(Some(history)) => {
+            for regression in boot_regressions(&history) {
+                eprintln!(
+                    "runkitd: {} came up {:.1}s after boot, usually {:.1}s",
+                    regression.service, regression.seconds, regression.baseline
+                );
+            }
+        }
+        Ok(None) => {}
+        Err(err) => eprintln!("runkitd: failed to record the boot report: {err}"),
+    }
+}
+
+/// Emit a `ServiceChanged(kind, service)` signal for every watcher event, and
+/// raise an alert when a supervisor reports that its service failed.
+fn emit_service_changes(
+    connection: &Connection,
+    mut watcher: ServiceWatcher,
+    manager: &ServiceManager,
+    notifications: &Arc<Notifications>,
+) {
+    let mut tracker = AlertTracker::default();
+    let ctxt = match SignalContext::new(connection.inner(), OBJECT_PATH) {
+        Ok(ctxt) => ctxt,
+        Err(err) => {
+            eprintln!("runkitd: change notifications disabled: {err}");
+            return;
+        }
+    };
+    loop {
+        let events = match watcher.read_events() {
+            Ok(events) => events,
+            Err(err) => {
+                eprintln!("runkitd: change notifications stopped: {err}");
+                return;
+            }
+        };
+        for event in events {
+            let emitted = zbus::block_on(RunkitService::service_changed(
+                &ctxt,
+                event.kind(),
+                event.service(),
+            ));
+            if let Err(err) = emitted {
+                eprintln!("runkitd: failed to emit ServiceChanged: {err}");
+            }
+
+            let ServiceEvent::StateChanged(service) = &event else {
+                continue;
+            };
+            let (Ok(state), Ok(policy)) =
+                (manager.status(service), manager.restart_policy(service))
+            else {
+                continue;
+            };
+            if let Err(err) = manager.record_state(service, &state, policy) {
+                eprintln!("runkitd: cannot record the history of {service}: {err}");
+            }
+            if manager
+                .maintenance(service)
+                .is_ok_and(|maintenance| maintenance.is_some())
+            {
+                tracker.forget(service);
+            } else if let Some(alert) = tracker.observe(service, &state, policy) {
+                notifications.dispatch(alert);
+            }
+        }
+    }
+}
+
+/// Delivers alerts as a `ServiceAlert` signal; the GUI shows them as desktop
+/// notifications.
+struct DesktopNotifier {
+    ctxt: SignalContext<'static>,
+}
+
+impl Notifier for DesktopNotifier {
+    fn describe(&self) -> String {
+        "desktop".to_string()
+    }
+
+    fn bus_only(&self) -> bool {
+        true
+    }
+
+    fn send(&self, alert: &Alert) -> Result<(), String> {
+        zbus::block_on(RunkitService::service_alert(
+            &self.ctxt,
+            alert.severity.as_str(),
+            &alert.service,
+            &alert.message,
+        ))
+        .map_err(|err| err.to_string())
+    }
+}
+
+struct RunkitService {
+    context: Arc<HelperContext>,
+    subscriptions: LogSubscriptions,
+    jobs: Jobs,
+}
+
+#[zbus::dbus_interface(name = "tech.geektoshi.Runkit1.Controller")]
+impl RunkitService {
+    /// runkitd's package version, so a second instance can say what it found.
+    #[dbus_interface(property)]
+    fn version(&self) -> String {
+        env!("CARGO_PKG_VERSION").to_string()
+    }
+
+    /// runkitd's version, the interface and response versions it speaks and
+    /// the capabilities it offers.
+    fn get_server_info(&self) -> fdo::Result<String> {
+        serialize_response(self.context.server_info())
+    }
+
+    fn perform_action(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        action: &str,
+        service: &str,
+    ) -> fdo::Result<String> {
+        serialize_response(perform_action(&self.context, &header, action, service))
+    }
+
+    /// Start, stop or restart a service, then wait up to `timeout_secs` for it to
+    /// be running or down. The response reports the state it reached, or is an
+    /// error naming the state it was last seen in.
+    fn perform_action_and_wait(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        action: &str,
+        service: &str,
+        timeout_secs: u32,
+    ) -> fdo::Result<String> {
+        serialize_response(perform_action_and_wait(
+            &self.context,
+            &header,
+            action,
+            service,
+            timeout_secs,
+        ))
+    }
+
+    /// Enable `service` and start it under one authorization, waiting up to
+    /// `timeout_secs` for it to be running. If it does not get there it is
+    /// stopped and disabled again, and the response is an error.
+    fn enable_and_start(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+        timeout_secs: u32,
+    ) -> fdo::Result<String> {
+        if let Err(message) = authorize_operation(&header, service, "enable-and-start") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        serialize_response(
+            self.context
+                .enable_and_start(service, Duration::from_secs(timeout_secs.into())),
+        )
+    }
+
+    /// Stop `service` and disable it under one authorization. If it is not down
+    /// within `timeout_secs` it is started again, stays enabled, and the
+    /// response is an error.
+    fn stop_and_disable(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+        timeout_secs: u32,
+    ) -> fdo::Result<String> {
+        if let Err(err) = self.context.guard_protected(service, false) {
+            return serialize_response(Err(err));
+        }
+        if let Err(message) = authorize_operation(&header, service, "stop-and-disable") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        serialize_response(
+            self.context
+                .stop_and_disable(service, Duration::from_secs(timeout_secs.into())),
+        )
+    }
+
+    /// Stop, disable or stop-and-disable a protected service despite the guard.
+    /// Always asks for [`POLKIT_ACTION_PROTECTED`], whatever the
+    /// cached-authorization preference. Stops wait up to `timeout_secs`.
+    fn perform_protected_action(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        action: &str,
+        service: &str,
+        timeout_secs: u32,
+    ) -> fdo::Result<String> {
+        let kind = ActionKind::parse(action).filter(|kind| kind.takes_down());
+        if kind.is_none() && action != "stop-and-disable" {
+            return serialize_response(Err(HelperError::Other(format!(
+                "Unsupported action '{action}' for a protected service"
+            ))));
+        }
+
+        let mut details = HashMap::new();
+        details.insert("service", service);
+        details.insert("operation", action);
+        if let Err(message) = authorize(&header, POLKIT_ACTION_PROTECTED, details) {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        let timeout = Duration::from_secs(timeout_secs.into());
+        serialize_response(match kind {
+            None => self.context.stop_and_disable(service, timeout),
+            Some(kind) if kind.state_change().is_some() => {
+                self.context.perform_action_and_wait(kind, service, timeout)
+            }
+            Some(kind) => self.context.perform_action(kind, service),
+        })
+    }
+
+    /// The services runkitd refuses to stop or disable without
+    /// [`Self::perform_protected_action`].
+    fn list_protected_services(&self) -> fdo::Result<String> {
+        serialize_response(self.context.protected_services())
+    }
+
+    /// Start the services `service` depends on, in order, then `service`,
+    /// waiting up to `timeout_secs` for each. Data is `{changed}`.
+    fn start_with_dependencies(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+        timeout_secs: u32,
+    ) -> fdo::Result<String> {
+        if let Err(message) = authorize_operation(&header, service, "start-with-dependencies") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        serialize_response(self.context.change_with_dependencies(
+            service,
+            StateChange::Start,
+            Duration::from_secs(timeout_secs.into()),
+        ))
+    }
+
+    /// Stop the services that depend on `service`, then `service` itself.
+    fn stop_with_dependents(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+        timeout_secs: u32,
+    ) -> fdo::Result<String> {
+        if let Err(err) = self
+            .context
+            .stopped_with_dependents(service)
+            .and_then(|services| self.context.guard_protected_all(&services, false))
+        {
+            return serialize_response(Err(err));
+        }
+        if let Err(message) = authorize_operation(&header, service, "stop-with-dependents") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        serialize_response(self.context.change_with_dependencies(
+            service,
+            StateChange::Stop,
+            Duration::from_secs(timeout_secs.into()),
+        ))
+    }
+
+    /// The whole dependency graph as `{dependencies, cycles}`, where
+    /// `dependencies` maps each service to the ones it needs.
+    fn get_dependency_graph(&self) -> fdo::Result<String> {
+        serialize_response(self.context.dependencies(None))
+    }
+
+    /// Put `service` into maintenance with an optional `reason`, or with
+    /// `enabled` false end it. An empty reason means none.
+    fn set_maintenance_mode(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+        enabled: bool,
+        reason: &str,
+    ) -> fdo::Result<String> {
+        if enabled && let Err(err) = self.context.guard_protected(service, false) {
+            return serialize_response(Err(err));
+        }
+        if let Err(message) = authorize_operation(&header, service, "maintenance") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        let reason = (!reason.is_empty()).then_some(reason);
+        serialize_response(self.context.set_maintenance(service, enabled, reason))
+    }
+
+    /// Run `service`'s `./check` every `seconds` while it runs, or with 0 stop.
+    /// Outcomes show as `last_check` in `ListServices`.
+    fn set_check_interval(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+        seconds: u64,
+    ) -> fdo::Result<String> {
+        if let Err(message) = authorize_operation(&header, service, "check-interval") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+        serialize_response(self.context.set_check_interval(service, seconds))
+    }
+
+    /// Run `service`'s `./check` now; `{at, passed, detail}`.
+    fn run_check(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+    ) -> fdo::Result<String> {
+        if let Err(message) = authorize_operation(&header, service, "check") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+        serialize_response(self.context.run_check(service))
+    }
+
+    /// Restart `services` one at a time with a pause between them. More than the
+    /// daemon's batch limit fails with a confirmation error unless `confirmed`.
+    fn restart_services(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        services: Vec<String>,
+        confirmed: bool,
+    ) -> fdo::Result<String> {
+        if let Err(message) = authorize_operation(&header, &services.join(","), "restart") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        serialize_response(self.context.restart_services(&services, confirmed, &()))
+    }
+
+    /// Start, stop, restart, enable or disable each of `services` under one
+    /// authorization. Data lists `{service, ok, message}` for each; stop and
+    /// disable skip protected services, and restarts follow the same limit and
+    /// pacing as `RestartServices`.
+    fn perform_batch_action(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        action: &str,
+        services: Vec<String>,
+        confirmed: bool,
+    ) -> fdo::Result<String> {
+        serialize_response(
+            authorize_batch(&header, action, &services).and_then(|kind| {
+                self.context
+                    .run_batch(kind, &services, confirmed, false, &())
+            }),
+        )
+    }
+
+    /// Write a `.tar.gz` of the service definition into the caller-supplied descriptor.
+    fn export_definition(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+        destination: Fd,
+    ) -> fdo::Result<String> {
+        if let Err(message) = authorize_operation(&header, service, "export") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        let mut file = match file_from_fd(&destination) {
+            Ok(file) => file,
+            Err(err) => return serialize_response(Err(err)),
+        };
+        serialize_response(self.context.export_definition(service, &mut file))
+    }
+
+    /// Install a definition from a `.tar.gz` read from the caller-supplied descriptor.
+    fn import_definition(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        source: Fd,
+        replace: bool,
+    ) -> fdo::Result<String> {
+        if let Err(message) = authorize_operation(&header, "", "import") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        let mut file = match file_from_fd(&source) {
+            Ok(file) => file,
+            Err(err) => return serialize_response(Err(err)),
+        };
+        serialize_response(self.context.import_definition(&mut file, replace))
+    }
+
+    /// Scaffold a new definition that execs `command`. Options: `user` (s, as
+    /// `USER` or `USER:GROUP`), `environment` (a{ss}, written to an env dir),
+    /// `logger` (b, add an svlogd log service) and `enable` (b). The template
+    /// is checked before anything is written.
+    fn create_service(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+        command: &str,
+        options: HashMap<String, OwnedValue>,
+    ) -> fdo::Result<String> {
+        if let Err(message) = authorize_operation(&header, service, "create") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        let response =
+            service_template_from_options(service, command, &options).and_then(|template| {
+                let enable = option::<bool>(&options, "enable")?.unwrap_or(false);
+                self.context.create_service(&template, enable)
+            });
+        serialize_response(response)
+    }
+
+    /// A file of the service's definition as `{path, contents, mode}`. Files
+    /// other users cannot read, such as secrets in an env dir, need
+    /// authorization.
+    fn read_service_file(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+        file: &str,
+    ) -> fdo::Result<String> {
+        let response = self.context.read_service_file(service, file);
+        let private = response.as_ref().is_ok_and(|outcome| {
+            outcome
+                .data
+                .as_ref()
+                .and_then(|data| data["mode"].as_u64())
+                .is_none_or(|mode| mode & 0o004 == 0)
+        });
+        if private && let Err(message) = authorize_operation(&header, service, "read-file") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+        serialize_response(response)
+    }
+
+    /// Replace a file of the service's definition, backing up the old one.
+    /// Shell files with a syntax error are refused unless `force` is set.
+    fn write_service_file(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+        file: &str,
+        contents: &str,
+        force: bool,
+    ) -> fdo::Result<String> {
+        if controls_supervision(file)
+            && let Err(err) = self.context.guard_protected(service, false)
+        {
+            return serialize_response(Err(err));
+        }
+        if let Err(message) = authorize_operation(&header, service, "write-file") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+        serialize_response(
+            self.context
+                .write_service_file(service, file, contents, force),
+        )
+    }
+
+    /// Create `service` from a template bundle, given as its JSON text, with
+    /// `values` for the bundle's variables.
+    fn create_from_bundle(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+        bundle: &str,
+        values: Vec<(String, String)>,
+        enable: bool,
+    ) -> fdo::Result<String> {
+        if let Err(message) = authorize_operation(&header, service, "create") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        let response = TemplateBundle::parse(bundle)
+            .map_err(HelperError::from)
+            .and_then(|bundle| {
+                self.context
+                    .create_from_bundle(&bundle, service, &values, enable)
+            });
+        serialize_response(response)
+    }
+
+    /// Copy the definition of `source` to `name`, replacing each `(from, to)`
+    /// of `substitutions` in its scripts and `conf`.
+    fn instantiate_service(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        source: &str,
+        name: &str,
+        substitutions: Vec<(String, String)>,
+        enable: bool,
+    ) -> fdo::Result<String> {
+        if let Err(message) = authorize_operation(&header, name, "instantiate") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        serialize_response(
+            self.context
+                .instantiate_service(source, name, &substitutions, enable),
+        )
+    }
+
+    /// Stop every alert route for `minutes`, or with 0 send alerts again. The
+    /// change and its requester are written to the daemon's log.
+    fn silence_alerts(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        minutes: u32,
+    ) -> fdo::Result<String> {
+        if let Err(message) = authorize_operation(&header, "", "silence-alerts") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        let requester = header
+            .sender()
+            .ok()
+            .flatten()
+            .map(|sender| sender.to_string())
+            .unwrap_or_else(|| "an unknown client".to_string());
+        let response = self.context.silence_alerts(minutes.into());
+        if response.is_ok() {
+            if minutes == 0 {
+                eprintln!("runkitd: alerts resumed by {requester}");
+            } else {
+                eprintln!("runkitd: alerts silenced for {minutes} minute(s) by {requester}");
+            }
+        }
+        serialize_response(response)
+    }
+
+    /// `{silenced_until}`: Unix time the alert silence ends, or null.
+    fn get_alert_silence(&self) -> fdo::Result<String> {
+        serialize_response(self.context.alert_silence())
+    }
+
+    /// `{current, runlevels: [{name, path, current, services}]}` for the
+    /// runsvdir runlevels.
+    fn list_runlevels(&self) -> fdo::Result<String> {
+        serialize_response(self.context.runlevels())
+    }
+
+    /// Make `runlevel` current with `runsvchdir`.
+    fn switch_runlevel(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        runlevel: &str,
+    ) -> fdo::Result<String> {
+        if let Err(err) = self
+            .context
+            .stopped_by_runlevel_switch(runlevel)
+            .and_then(|services| self.context.guard_protected_all(&services, false))
+        {
+            return serialize_response(Err(err));
+        }
+        if let Err(message) = authorize_operation(&header, runlevel, "switch-runlevel") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        serialize_response(self.context.switch_runlevel(runlevel))
+    }
+
+    /// Link `service` into `runlevel`, or remove its links there.
+    fn set_enabled_in_runlevel(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+        runlevel: &str,
+        enabled: bool,
+    ) -> fdo::Result<String> {
+        if !enabled && let Err(err) = self.context.guard_protected(service, false) {
+            return serialize_response(Err(err));
+        }
+        let operation = if enabled { "enable" } else { "disable" };
+        if let Err(message) = authorize_operation(&header, service, operation) {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        serialize_response(
+            self.context
+                .set_enabled_in_runlevel(service, runlevel, enabled),
+        )
+    }
+
+    /// Drop duplicate enable links; an empty `runlevel` means the current one.
+    fn keep_only_in_runlevel(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+        runlevel: &str,
+    ) -> fdo::Result<String> {
+        if let Err(err) = self.context.guard_protected(service, false) {
+            return serialize_response(Err(err));
+        }
+        if let Err(message) = authorize_operation(&header, service, "keep-runlevel") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        let runlevel = (!runlevel.is_empty()).then_some(runlevel);
+        serialize_response(self.context.keep_only_in_runlevel(service, runlevel))
+    }
+
+    /// Disable the service and move its definition to the trash.
+    fn trash_service(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+    ) -> fdo::Result<String> {
+        if let Err(err) = self.context.guard_protected(service, false) {
+            return serialize_response(Err(err));
+        }
+        if let Err(message) = authorize_operation(&header, service, "trash") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        serialize_response(self.context.trash_definition(service))
+    }
+
+    /// Stop the service, unlink it from every runlevel and trash its
+    /// definition, or with `remove` delete it for good.
+    fn delete_service(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+        remove: bool,
+    ) -> fdo::Result<String> {
+        if let Err(err) = self.context.guard_protected(service, false) {
+            return serialize_response(Err(err));
+        }
+        if let Err(message) = authorize_operation(&header, service, "delete") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        serialize_response(self.context.delete_service(service, remove))
+    }
+
+    fn list_trash(&self) -> fdo::Result<String> {
+        serialize_response(self.context.list_trash())
+    }
+
+    /// Restore a trashed definition by its trash entry id.
+    fn restore_service(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        id: &str,
+    ) -> fdo::Result<String> {
+        if let Err(message) = authorize_operation(&header, id, "restore") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        serialize_response(self.context.restore_from_trash(id))
+    }
+
+    /// Purge expired trash entries, or every entry when `everything` is set.
+    fn purge_trash(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        everything: bool,
+    ) -> fdo::Result<String> {
+        if let Err(message) = authorize_operation(&header, "", "purge-trash") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        serialize_response(self.context.purge_trash(everything))
+    }
+
+    /// Schedule `service` to be enabled or disabled relative to the next boot.
+    /// `schedule` is enable-next-boot-only, disable-after-next-boot or
+    /// disable-at-next-boot; an empty string cancels the scheduled change.
+    fn schedule_boot_change(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+        schedule: &str,
+    ) -> fdo::Result<String> {
+        let schedule = match schedule {
+            "" => None,
+            value => match value.parse::<BootSchedule>() {
+                Ok(schedule) => Some(schedule),
+                Err(message) => return serialize_response(Err(HelperError::Other(message))),
+            },
+        };
+        if matches!(
+            schedule,
+            Some(BootSchedule::DisableAfterNextBoot | BootSchedule::DisableAtNextBoot)
+        ) && let Err(err) = self.context.guard_protected(service, false)
+        {
+            return serialize_response(Err(err));
+        }
+        if let Err(message) = authorize_operation(&header, service, "schedule-boot") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        serialize_response(self.context.schedule_boot_change(service, schedule))
+    }
+
+    fn get_boot_schedule(&self) -> fdo::Result<String> {
+        serialize_response(self.context.boot_schedule())
+    }
+
+    /// Compare this boot's service start times with the recorded boots and
+    /// return `{boot_id, services, boots, regressions}`, where each regression
+    /// is `{service, seconds, baseline}` in seconds after boot. Nothing is
+    /// written; runkitd records the boot itself once it has settled.
+    fn get_boot_report(&self) -> fdo::Result<String> {
+        serialize_response(
+            self.context
+                .boot_report(Path::new(DEFAULT_BOOT_REPORTS), false),
+        )
+    }
+
+    /// Restart `service` once the last graphical session has ended, e.g. a
+    /// display manager that would otherwise take the caller's session with it.
+    /// `cancel` drops a restart still waiting.
+    fn restart_at_logout(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+        cancel: bool,
+    ) -> fdo::Result<String> {
+        if let Err(message) = authorize_operation(&header, service, "restart-at-logout") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        serialize_response(self.context.restart_at_logout(service, !cancel))
+    }
+
+    fn get_logout_restarts(&self) -> fdo::Result<String> {
+        serialize_response(self.context.logout_restarts())
+    }
+
+    /// Whether the caller may run `operation` (an action such as `start` or
+    /// `kill`, or an operation such as `delete`) on `service`, without
+    /// prompting. `data.result` is `allowed`, `authentication-required` or
+    /// `denied`, so clients can disable what would only fail.
+    fn check_authorization(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        operation: &str,
+        service: &str,
+    ) -> fdo::Result<String> {
+        let mut details = HashMap::new();
+        details.insert("service", service);
+        details.insert("operation", operation);
+        let result = resolve_action(&header, operation, &details)
+            .map_err(HelperError::Other)
+            .map(|(action_id, result)| {
+                let verdict = if result.is_authorized {
+                    "allowed"
+                } else if result.is_challenge {
+                    "authentication-required"
+                } else {
+                    "denied"
+                };
+                CommandOutcome::with(
+                    None,
+                    Some(serde_json::json!({ "action_id": action_id, "result": verdict })),
+                )
+            });
+        serialize_response(result)
+    }
+
+    /// Let the caller reuse an authorization for `duration_secs` instead of
+    /// typing the password for every change, or with `enabled` false stop.
+    /// Enabling asks for the password once, now. A zero duration means the
+    /// default of eight hours, and no more than a day is granted. Returns
+    /// `{enabled, until}` like [`Self::get_cached_authorization`].
+    fn set_cached_authorization(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        enabled: bool,
+        duration_secs: u64,
+    ) -> fdo::Result<String> {
+        let uid = match caller_uid(&header) {
+            Ok(uid) => uid,
+            Err(message) => return serialize_response(Err(HelperError::Other(message))),
+        };
+        let store = CachedAuthorization::default();
+        if !enabled {
+            let response = store
+                .revoke(uid)
+                .map(|_| {
+                    cached_authorization_response(
+                        None,
+                        "The password is asked for every change".to_string(),
+                    )
+                })
+                .map_err(|err| HelperError::Other(err.to_string()));
+            return serialize_response(response);
+        }
+
+        let mut details = HashMap::new();
+        details.insert("operation", "cached-authorization");
+        if let Err(message) = authorize(&header, POLKIT_ACTION_REQUIRE_PASSWORD, details) {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+        let response = store
+            .allow_for(uid, Duration::from_secs(duration_secs))
+            .map(|until| {
+                let granted = Duration::from_secs(until.saturating_sub(unix_now()));
+                cached_authorization_response(
+                    Some(until),
+                    format!("Authorization is reused for {}", format_duration(granted)),
+                )
+            })
+            .map_err(|err| HelperError::Other(err.to_string()));
+        serialize_response(response)
+    }
 
-    let _connection = ConnectionBuilder::system()?
-        .name(BUS_NAME)?
-        .serve_at(OBJECT_PATH, service)?
-        .build()?;
+    /// `{enabled, until}` for the caller: whether their authorizations are
+    /// reused, and the Unix time that ends, or null.
+    fn get_cached_authorization(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+    ) -> fdo::Result<String> {
+        let response = caller_uid(&header)
+            .map(|uid| {
+                let until = CachedAuthorization::default().until(uid);
+                let message = match until {
+                    Some(until) => format!(
+                        "Authorization is reused for another {}",
+                        format_duration(Duration::from_secs(until.saturating_sub(unix_now())))
+                    ),
+                    None => "The password is asked for every change".to_string(),
+                };
+                cached_authorization_response(until, message)
+            })
+            .map_err(HelperError::Other);
+        serialize_response(response)
+    }
 
-    // Keep the process alive while zbus' internal executor services requests.
-    loop {
-        thread::park_timeout(Duration::from_secs(60));
+    /// Which services are enabled and which have a `down` file, rendered as
+    /// `format` (json or toml) in `data.contents`.
+    fn get_profile(&self, format: &str) -> fdo::Result<String> {
+        match format.parse::<ProfileFormat>() {
+            Ok(format) => serialize_response(self.context.profile(format)),
+            Err(message) => serialize_response(Err(HelperError::Other(message))),
+        }
     }
 
-    #[allow(unreachable_code)]
-    Ok(())
-}
+    /// Apply a profile in JSON or TOML with one authorization for the whole
+    /// batch. A `dry_run` only reports the changes and needs none.
+    fn restore_profile(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        contents: &str,
+        dry_run: bool,
+    ) -> fdo::Result<String> {
+        if !dry_run
+            && let Err(err) = self
+                .context
+                .taken_down_by_profile(contents)
+                .and_then(|services| self.context.guard_protected_all(&services, false))
+        {
+            return serialize_response(Err(err));
+        }
+        if !dry_run && let Err(message) = authorize_operation(&header, "", "restore-profile") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
 
-struct RunkitService {
-    context: HelperContext,
-}
+        serialize_response(self.context.restore_profile(contents, dry_run, &()))
+    }
 
-#[zbus::dbus_interface(name = "tech.geektoshi.Runkit1.Controller")]
-impl RunkitService {
-    fn perform_action(
+    /// Resource usage of the service's process tree; `data` is null when it is
+    /// not running. Takes about a quarter of a second to sample CPU time.
+    fn get_service_metrics(&self, service: &str) -> fdo::Result<String> {
+        serialize_response(self.context.metrics(service))
+    }
+
+    /// Starts, crashes and stops of `service` in the last hour:
+    /// `{window_seconds, restarts, crashes, last_exit_codes, flapping, events}`.
+    /// Only transitions seen while runkitd was running are recorded.
+    fn get_service_history(&self, service: &str) -> fdo::Result<String> {
+        serialize_response(self.context.history(service, HISTORY_WINDOW))
+    }
+
+    /// Resident memory in bytes of every running service, keyed by name.
+    fn get_memory_usage(&self) -> fdo::Result<String> {
+        serialize_response(self.context.memory_usage())
+    }
+
+    /// The service's supervised process and its descendants as nested
+    /// `{pid, comm, cmdline, children}` objects; `data` is null when it is not running.
+    fn get_process_tree(&self, service: &str) -> fdo::Result<String> {
+        serialize_response(self.context.process_tree(service))
+    }
+
+    /// What the service's run script executes, parsed without running it.
+    fn inspect_run_script(&self, service: &str) -> fdo::Result<String> {
+        serialize_response(self.context.run_script_info(service))
+    }
+
+    /// `{package}`: the xbps package that installed the definition, or null.
+    fn get_service_package(&self, service: &str) -> fdo::Result<String> {
+        serialize_response(self.context.service_package(service))
+    }
+
+    /// Variables the service's `conf` and env dir set, and for a running
+    /// service the ones its process does not have as expected. The values can
+    /// be secrets, so this needs the same authorization as reading a private
+    /// file.
+    fn get_effective_environment(
         &self,
         #[zbus(header)] header: MessageHeader<'_>,
-        action: &str,
         service: &str,
-        allow_cached_authorization: bool,
     ) -> fdo::Result<String> {
-        let Some(kind) = ActionKind::parse(action) else {
-            return serialize_response(Err(HelperError::Other(format!(
-                "Unsupported action '{action}'"
-            ))));
-        };
+        if let Err(message) = authorize_operation(&header, service, "read-file") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+        serialize_response(self.context.effective_environment(service))
+    }
 
-        let action_id = if allow_cached_authorization {
-            POLKIT_ACTION_ALLOW_CACHE
-        } else {
-            POLKIT_ACTION_REQUIRE_PASSWORD
-        };
+    /// The service's env dir as `{path, used_by_run_script, variables}`.
+    /// Authorized like [`Self::get_effective_environment`].
+    fn get_env_dir(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+    ) -> fdo::Result<String> {
+        if let Err(message) = authorize_operation(&header, service, "read-file") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+        serialize_response(self.context.env_dir(service))
+    }
 
-        let mut details = HashMap::new();
-        details.insert("service", service);
-        details.insert("operation", kind.as_str());
+    /// Set a variable in the service's env dir; an empty value makes chpst
+    /// remove it from the environment.
+    fn set_env_variable(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+        name: &str,
+        value: &str,
+    ) -> fdo::Result<String> {
+        if let Err(message) = authorize_operation(&header, service, "set-env") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+        serialize_response(self.context.set_env_variable(service, name, Some(value)))
+    }
+
+    fn unset_env_variable(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+        name: &str,
+    ) -> fdo::Result<String> {
+        if let Err(message) = authorize_operation(&header, service, "set-env") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+        serialize_response(self.context.set_env_variable(service, name, None))
+    }
+
+    fn get_log_config(&self, service: &str) -> fdo::Result<String> {
+        serialize_response(self.context.log_config(service))
+    }
 
-        if let Err(message) = authorize(&header, action_id, details) {
+    /// Replace the service's svlogd settings. Recognised options: `size` (t),
+    /// `num` (u), `min` (u), `timeout` (t), `processor` (s), `prefix` (s) and
+    /// `filters` (as, svlogd lines such as `-*debug*`); absent options are unset.
+    fn set_log_config(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        service: &str,
+        options: HashMap<String, OwnedValue>,
+    ) -> fdo::Result<String> {
+        if let Err(message) = authorize_operation(&header, service, "set-log-config") {
             return serialize_response(Err(HelperError::Other(message)));
         }
 
-        serialize_response(self.context.perform_action(kind, service))
+        let response = self
+            .context
+            .backend
+            .log_config(service)
+            .map_err(HelperError::from)
+            .and_then(|current| log_config_from_options(&options, current))
+            .and_then(|config| self.context.set_log_config(service, &config));
+        serialize_response(response)
     }
 
     fn list_services(&self) -> fdo::Result<String> {
-        serialize_response(self.context.list())
+        serialize_response(self.context.list(&ServiceQuery::default()))
+    }
+
+    /// `ListServices` narrowed and ordered by `options`: `states` (as: any of
+    /// running, down and failed), `enabled` (b), `search` (s, in the name or
+    /// description) and `sort` (s: name, uptime or state).
+    fn list_services_filtered(&self, options: HashMap<String, OwnedValue>) -> fdo::Result<String> {
+        let response =
+            service_query_from_options(&options).and_then(|query| self.context.list(&query));
+        serialize_response(response)
+    }
+
+    fn check_health(&self) -> fdo::Result<String> {
+        serialize_response(self.context.health())
     }
 
     fn fetch_logs(&self, service: &str, lines: u32) -> fdo::Result<String> {
-        serialize_response(self.context.logs(service, lines as usize))
+        serialize_response(
+            self.context
+                .logs(service, lines as usize, &LogFilter::default(), None),
+        )
+    }
+
+    /// Names of runit's stage logs, which `FetchLogs` and `FollowLogs` take in
+    /// place of a service.
+    fn list_stage_logs(&self) -> fdo::Result<String> {
+        serialize_response(self.context.stage_logs())
+    }
+
+    /// The last `lines` log entries of every failed service, for the client
+    /// to archive under the user's own home.
+    fn fetch_failed_logs(&self, lines: u32) -> fdo::Result<String> {
+        serialize_response(self.context.failed_logs(lines as usize))
+    }
+
+    /// `FetchLogs` with optional filters: `pattern` (s), `regex` (b), `since`
+    /// (x, Unix seconds) and `severity` (s: info, warning or error).
+    fn fetch_logs_filtered(
+        &self,
+        service: &str,
+        lines: u32,
+        options: HashMap<String, OwnedValue>,
+    ) -> fdo::Result<String> {
+        let response = log_filter_from_options(&options)
+            .and_then(|filter| self.context.logs(service, lines as usize, &filter, None));
+        serialize_response(response)
+    }
+
+    /// Search the logs of `services` (every service when empty) for `pattern`,
+    /// returning at most `limit` matches, newest first.
+    fn search_logs(
+        &self,
+        pattern: &str,
+        regex: bool,
+        services: Vec<String>,
+        limit: u32,
+    ) -> fdo::Result<String> {
+        let response = LogPattern::new(pattern, regex)
+            .map_err(HelperError::from)
+            .and_then(|pattern| {
+                self.context
+                    .search_logs(&pattern, &services, limit as usize, None)
+            });
+        serialize_response(response)
+    }
+
+    /// Stream entries written to `service`'s log from now on as
+    /// `LogLine(subscription, service, entry)` signals sent to the caller
+    /// only, `entry` being JSON like a `FetchLogs` item. Returns
+    /// `{subscription}`. Following stops with `StopFollowing`, when the caller
+    /// leaves the bus, or with a `LogFollowEnded(subscription, reason)` signal
+    /// when the log can no longer be read.
+    fn follow_logs(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        #[zbus(connection)] connection: &zbus::Connection,
+        service: &str,
+    ) -> fdo::Result<String> {
+        let response = caller_name(&header)
+            .map_err(HelperError::Other)
+            .and_then(|client| {
+                let follower = self.context.backend.follow_logs(service)?;
+                let connection = Connection::from(connection.clone());
+                self.subscriptions
+                    .start(connection, &client, service, follower)
+            })
+            .map(|subscription| {
+                CommandOutcome::with(
+                    None,
+                    Some(serde_json::json!({ "subscription": subscription })),
+                )
+            });
+        serialize_response(response)
+    }
+
+    /// End one of the caller's `FollowLogs` subscriptions.
+    fn stop_following(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        subscription: u64,
+    ) -> fdo::Result<String> {
+        let response = caller_name(&header)
+            .map_err(HelperError::Other)
+            .and_then(|client| {
+                if self.subscriptions.stop(&client, subscription) {
+                    Ok(CommandOutcome::message("stopped following"))
+                } else {
+                    Err(HelperError::Other(format!(
+                        "no log subscription {subscription}"
+                    )))
+                }
+            });
+        serialize_response(response)
+    }
+
+    /// `PerformActionAndWait` as a job: once authorized, returns `{job}` and
+    /// reports through `JobProgress(job, done, total, message)` and
+    /// `JobCompleted(job, response)` signals sent to the caller only.
+    fn start_action_job(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        #[zbus(connection)] connection: &zbus::Connection,
+        action: &str,
+        service: &str,
+        timeout_secs: u32,
+    ) -> fdo::Result<String> {
+        let response = authorize_wait(&self.context, &header, action, service).and_then(|kind| {
+            let context = Arc::clone(&self.context);
+            let service = service.to_string();
+            let timeout = Duration::from_secs(timeout_secs.into());
+            self.start_job(&header, connection, action, move |progress| {
+                progress.report(0, 1, &format!("Waiting for {service}"));
+                context.perform_action_and_wait(kind, &service, timeout)
+            })
+        });
+        serialize_response(response)
+    }
+
+    /// `RestartServices` as a job, reporting each service as it is restarted.
+    /// Cancelling skips the services not restarted yet.
+    fn start_restart_job(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        #[zbus(connection)] connection: &zbus::Connection,
+        services: Vec<String>,
+        confirmed: bool,
+    ) -> fdo::Result<String> {
+        if let Err(message) = authorize_operation(&header, &services.join(","), "restart") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        let context = Arc::clone(&self.context);
+        serialize_response(
+            self.start_job(&header, connection, "restart", move |progress| {
+                context.restart_services(&services, confirmed, progress)
+            }),
+        )
+    }
+
+    /// `PerformBatchAction` as a job, reporting each service as it is changed.
+    /// Cancelling skips the services not changed yet.
+    fn start_batch_job(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        #[zbus(connection)] connection: &zbus::Connection,
+        action: &str,
+        services: Vec<String>,
+        confirmed: bool,
+    ) -> fdo::Result<String> {
+        let response = authorize_batch(&header, action, &services).and_then(|kind| {
+            let context = Arc::clone(&self.context);
+            self.start_job(&header, connection, action, move |progress| {
+                context.run_batch(kind, &services, confirmed, false, progress)
+            })
+        });
+        serialize_response(response)
+    }
+
+    /// `RestoreProfile` as a job, reporting each change as it is made.
+    /// Cancelling leaves the changes not made yet.
+    fn start_profile_restore_job(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        #[zbus(connection)] connection: &zbus::Connection,
+        contents: String,
+    ) -> fdo::Result<String> {
+        if let Err(err) = self
+            .context
+            .taken_down_by_profile(&contents)
+            .and_then(|services| self.context.guard_protected_all(&services, false))
+        {
+            return serialize_response(Err(err));
+        }
+        if let Err(message) = authorize_operation(&header, "", "restore-profile") {
+            return serialize_response(Err(HelperError::Other(message)));
+        }
+
+        let context = Arc::clone(&self.context);
+        serialize_response(self.start_job(
+            &header,
+            connection,
+            "profile restore",
+            move |progress| context.restore_profile(&contents, false, progress),
+        ))
+    }
+
+    /// Cancel one of the caller's jobs; it completes with a "cancelled" error.
+    fn cancel_job(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        job: u64,
+    ) -> fdo::Result<String> {
+        let response = caller_name(&header)
+            .map_err(HelperError::Other)
+            .and_then(|client| {
+                if self.jobs.cancel(&client, job) {
+                    Ok(CommandOutcome::message("cancelled"))
+                } else {
+                    Err(HelperError::Other(format!("no running job {job}")))
+                }
+            });
+        serialize_response(response)
+    }
+
+    /// Emitted when a definition, enable link, or supervisor status changes.
+    #[dbus_interface(signal)]
+    async fn service_changed(
+        ctxt: &SignalContext<'_>,
+        kind: &str,
+        service: &str,
+    ) -> zbus::Result<()>;
+
+    /// Emitted when a service fails, exits unexpectedly or recovers, if the
+    /// notification config routes that severity to the desktop.
+    #[dbus_interface(signal)]
+    async fn service_alert(
+        ctxt: &SignalContext<'_>,
+        severity: &str,
+        service: &str,
+        message: &str,
+    ) -> zbus::Result<()>;
+
+    /// Emitted when a scheduled `./check` of `service` passes after failing or
+    /// fails after passing, and for its first outcome after runkitd starts.
+    #[dbus_interface(signal)]
+    async fn service_health_changed(
+        ctxt: &SignalContext<'_>,
+        service: &str,
+        passed: bool,
+        detail: &str,
+    ) -> zbus::Result<()>;
+
+    /// `{reduced, confinement}`: whether runkitd runs in a container or
+    /// chroot with no runsvdir, where services have no live status and
+    /// control verbs fail. `confinement` is `{kind: "container", runtime}`,
+    /// `{kind: "chroot"}` or null.
+    fn get_supervision(&self) -> fdo::Result<String> {
+        serialize_response(self.context.supervision())
     }
 
     fn fetch_description(&self, service: &str) -> fdo::Result<String> {
@@ -82,21 +1612,329 @@ impl RunkitService {
     }
 }
 
+impl RunkitService {
+    /// Whether a job or log subscription is running.
+    fn is_busy(&self) -> bool {
+        !self.jobs.is_empty() || !self.subscriptions.is_empty()
+    }
+
+    /// Run `work` as one of the caller's jobs; the outcome is `{job}`.
+    fn start_job<F>(
+        &self,
+        header: &MessageHeader<'_>,
+        connection: &zbus::Connection,
+        what: &str,
+        work: F,
+    ) -> Result<CommandOutcome, HelperError>
+    where
+        F: FnOnce(&JobProgress) -> Result<CommandOutcome, HelperError> + Send + 'static,
+    {
+        let client = caller_name(header).map_err(HelperError::Other)?;
+        let connection = Connection::from(connection.clone());
+        let job = self.jobs.start(connection, &client, what, work)?;
+        Ok(CommandOutcome::with(
+            None,
+            Some(serde_json::json!({ "job": job })),
+        ))
+    }
+}
+
+/// `PerformAction` for both interfaces: check the action, the protected-service
+/// guard and polkit, then run it.
+pub(crate) fn perform_action(
+    context: &HelperContext,
+    header: &MessageHeader<'_>,
+    action: &str,
+    service: &str,
+) -> Result<CommandOutcome, HelperError> {
+    let kind = ActionKind::parse(action)
+        .ok_or_else(|| HelperError::Other(format!("Unsupported action '{action}'")))?;
+    if kind.takes_down() {
+        context.guard_protected(service, false)?;
+    }
+    authorize_action(header, service, kind).map_err(HelperError::Other)?;
+    context.perform_action(kind, service)
+}
+
+/// `PerformActionAndWait` for both interfaces.
+pub(crate) fn perform_action_and_wait(
+    context: &HelperContext,
+    header: &MessageHeader<'_>,
+    action: &str,
+    service: &str,
+    timeout_secs: u32,
+) -> Result<CommandOutcome, HelperError> {
+    let kind = authorize_wait(context, header, action, service)?;
+    context.perform_action_and_wait(kind, service, Duration::from_secs(timeout_secs.into()))
+}
+
+/// Check that `action` can be waited for and that the caller may run it.
+fn authorize_wait(
+    context: &HelperContext,
+    header: &MessageHeader<'_>,
+    action: &str,
+    service: &str,
+) -> Result<ActionKind, HelperError> {
+    let kind = ActionKind::parse(action)
+        .filter(|kind| kind.state_change().is_some())
+        .ok_or_else(|| HelperError::Other(format!("Cannot wait for action '{action}'")))?;
+    if kind.takes_down() {
+        context.guard_protected(service, false)?;
+    }
+    authorize_operation(header, service, kind.as_str()).map_err(HelperError::Other)?;
+    Ok(kind)
+}
+
+/// Check that `action` can be run on several services and that the caller
+/// may run it on all of `services`, with a single prompt.
+fn authorize_batch(
+    header: &MessageHeader<'_>,
+    action: &str,
+    services: &[String],
+) -> Result<ActionKind, HelperError> {
+    let kind = ActionKind::parse(action)
+        .filter(|kind| {
+            matches!(
+                kind,
+                ActionKind::Start
+                    | ActionKind::Stop
+                    | ActionKind::Restart
+                    | ActionKind::Enable
+                    | ActionKind::Disable
+            )
+        })
+        .ok_or_else(|| HelperError::Other(format!("Cannot run '{action}' on several services")))?;
+    authorize_action(header, &services.join(","), kind).map_err(HelperError::Other)?;
+    Ok(kind)
+}
+
+/// Authorize `operation` on `service` with its own polkit action, passing
+/// both as details. Where that would ask for a password and the caller's
+/// [`CachedAuthorization`] choice is active, [`POLKIT_ACTION_ALLOW_CACHE`] is
+/// asked instead. Forceful actions always ask.
+fn authorize_operation(
+    header: &MessageHeader<'_>,
+    service: &str,
+    operation: &str,
+) -> Result<(), String> {
+    let mut details = HashMap::new();
+    details.insert("service", service);
+    details.insert("operation", operation);
+
+    let (action_id, result) = resolve_action(header, operation, &details)?;
+    if result.is_authorized {
+        Ok(())
+    } else if !result.is_challenge {
+        Err("Authorization denied".to_string())
+    } else {
+        authorize(header, action_id, details)
+    }
+}
+
+/// Like [`authorize_operation`], for one of the [`ActionKind`]s.
+fn authorize_action(
+    header: &MessageHeader<'_>,
+    service: &str,
+    action: ActionKind,
+) -> Result<(), String> {
+    authorize_operation(header, service, action.as_str())
+}
+
+/// The polkit action [`authorize_operation`] would ask for and polkit's
+/// answer without prompting, so admin rules for the operation's own action
+/// apply whether or not the caller reuses authorizations.
+fn resolve_action(
+    header: &MessageHeader<'_>,
+    operation: &str,
+    details: &HashMap<&str, &str>,
+) -> Result<(&'static str, AuthorizationResult), String> {
+    let action_id = polkit_action_for(operation)
+        .ok_or_else(|| format!("No polkit action guards operation '{operation}'"))?;
+    let result = check_polkit(header, action_id, details, false)?;
+    if result.is_challenge && action_id != POLKIT_ACTION_FORCE && reuses_authorization(header) {
+        let cached = check_polkit(header, POLKIT_ACTION_ALLOW_CACHE, details, false)?;
+        return Ok((POLKIT_ACTION_ALLOW_CACHE, cached));
+    }
+    Ok((action_id, result))
+}
+
+/// Which polkit action guards `operation`, an [`ActionKind`] name or one of
+/// the D-Bus methods' operation names. `None` for anything else, which is
+/// refused rather than authorized with a broader action.
+fn polkit_action_for(operation: &str) -> Option<&'static str> {
+    if let Some(action) = ActionKind::parse(operation) {
+        return Some(action.polkit_action());
+    }
+    let action = match operation {
+        "start-with-dependencies" => POLKIT_ACTION_START,
+        "stop-with-dependents" | "maintenance" => POLKIT_ACTION_STOP,
+        "restart-at-logout" => POLKIT_ACTION_RESTART,
+        "enable-and-start" | "stop-and-disable" | "keep-runlevel" | "switch-runlevel"
+        | "schedule-boot" | "restore-profile" | "check-interval" => POLKIT_ACTION_ENABLE,
+        "create" | "instantiate" | "read-file" | "write-file" | "set-env" | "set-log-config"
+        | "import" | "export" | "trash" | "delete" | "restore" | "purge-trash" => {
+            POLKIT_ACTION_EDIT_FILES
+        }
+        "silence-alerts" => POLKIT_ACTION_ALERTS,
+        _ => return None,
+    };
+    Some(action)
+}
+
+/// Whether the caller chose to reuse authorizations and that has not expired.
+fn reuses_authorization(header: &MessageHeader<'_>) -> bool {
+    caller_uid(header).is_ok_and(|uid| CachedAuthorization::default().until(uid).is_some())
+}
+
+/// The caller's unique bus name.
+fn caller_name(header: &MessageHeader<'_>) -> Result<String, String> {
+    header
+        .sender()
+        .ok()
+        .flatten()
+        .map(ToString::to_string)
+        .ok_or_else(|| "the request has no sender".to_string())
+}
+
+/// Unix user of the connection that sent `header`, as the bus reports it.
+fn caller_uid(header: &MessageHeader<'_>) -> Result<u32, String> {
+    let sender = header
+        .sender()
+        .ok()
+        .flatten()
+        .ok_or_else(|| "the request has no sender".to_string())?;
+    let connection = Connection::system().map_err(|err| format!("bus connection error: {err}"))?;
+    let dbus = DBusProxy::new(&connection).map_err(|err| err.to_string())?;
+    dbus.get_connection_unix_user(BusName::Unique(sender.to_owned()))
+        .map_err(|err| format!("cannot identify the caller: {err}"))
+}
+
+fn cached_authorization_response(until: Option<u64>, message: String) -> CommandOutcome {
+    CommandOutcome::with(
+        Some(message),
+        Some(serde_json::json!({ "enabled": until.is_some(), "until": until })),
+    )
+}
+
+fn option<T: TryFrom<OwnedValue>>(
+    options: &HashMap<String, OwnedValue>,
+    key: &str,
+) -> Result<Option<T>, HelperError> {
+    options
+        .get(key)
+        .map(|value| {
+            T::try_from(value.clone())
+                .map_err(|_| HelperError::Other(format!("invalid type for option '{key}'")))
+        })
+        .transpose()
+}
+
+/// Build the new config from `options`, keeping the lines runkit does not model.
+fn log_config_from_options(
+    options: &HashMap<String, OwnedValue>,
+    current: SvlogdConfig,
+) -> Result<SvlogdConfig, HelperError> {
+    let filters = match options.get("filters") {
+        Some(value) => {
+            let lines = Vec::<String>::try_from(value.clone()).map_err(|_| {
+                HelperError::Other("invalid type for log option 'filters'".to_string())
+            })?;
+            parse_svlogd_filters(&lines)?
+        }
+        None => Vec::new(),
+    };
+    Ok(SvlogdConfig {
+        size: option::<u64>(options, "size")?,
+        num: option::<u32>(options, "num")?,
+        min: option::<u32>(options, "min")?,
+        timeout: option::<u64>(options, "timeout")?,
+        processor: option::<String>(options, "processor")?,
+        prefix: option::<String>(options, "prefix")?,
+        filters,
+        other: current.other,
+    })
+}
+
+fn service_template_from_options(
+    service: &str,
+    command: &str,
+    options: &HashMap<String, OwnedValue>,
+) -> Result<ServiceTemplate, HelperError> {
+    let environment = match options.get("environment") {
+        Some(value) => {
+            let variables = HashMap::<String, String>::try_from(value.clone()).map_err(|_| {
+                HelperError::Other("invalid type for option 'environment'".to_string())
+            })?;
+            // Sorted so the env dir is written in a predictable order.
+            let mut variables: Vec<(String, String)> = variables.into_iter().collect();
+            variables.sort();
+            variables
+        }
+        None => Vec::new(),
+    };
+    Ok(ServiceTemplate {
+        name: service.to_string(),
+        command: command.to_string(),
+        user: option::<String>(options, "user")?.filter(|user| !user.is_empty()),
+        environment,
+        logger: option::<bool>(options, "logger")?.unwrap_or(false),
+    })
+}
+
+fn service_query_from_options(
+    options: &HashMap<String, OwnedValue>,
+) -> Result<ServiceQuery, HelperError> {
+    let states = option::<Vec<String>>(options, "states")?
+        .unwrap_or_default()
+        .iter()
+        .map(|state| state.parse::<StateFilter>().map_err(HelperError::Other))
+        .collect::<Result<_, _>>()?;
+    let sort = option::<String>(options, "sort")?
+        .map(|sort| sort.parse::<ServiceSort>().map_err(HelperError::Other))
+        .transpose()?
+        .unwrap_or_default();
+    Ok(ServiceQuery {
+        states,
+        enabled: option::<bool>(options, "enabled")?,
+        text: option::<String>(options, "search")?.filter(|text| !text.is_empty()),
+        sort,
+    })
+}
+
+fn log_filter_from_options(
+    options: &HashMap<String, OwnedValue>,
+) -> Result<LogFilter, HelperError> {
+    let regex = option::<bool>(options, "regex")?.unwrap_or(false);
+    let pattern = option::<String>(options, "pattern")?
+        .filter(|pattern| !pattern.is_empty())
+        .map(|pattern| LogPattern::new(&pattern, regex))
+        .transpose()?;
+    let min_severity = option::<String>(options, "severity")?
+        .map(|severity| severity.parse::<LogSeverity>().map_err(HelperError::Other))
+        .transpose()?;
+    Ok(LogFilter {
+        pattern,
+        since: option::<i64>(options, "since")?,
+        min_severity,
+    })
+}
+
+fn file_from_fd(fd: &Fd) -> Result<File, HelperError> {
+    // SAFETY: zbus keeps the received descriptor open for the duration of the call;
+    // we duplicate it so the returned File owns an independent descriptor.
+    let borrowed = unsafe { BorrowedFd::borrow_raw(fd.as_raw_fd()) };
+    borrowed
+        .try_clone_to_owned()
+        .map(File::from)
+        .map_err(|err| HelperError::Other(format!("invalid file descriptor: {err}")))
+}
+
 fn authorize(
     header: &MessageHeader<'_>,
     action_id: &str,
     details: HashMap<&str, &str>,
 ) -> Result<(), String> {
-    let connection =
-        Connection::system().map_err(|err| format!("polkit connection error: {err}"))?;
-    let proxy = AuthorityProxyBlocking::new(&connection)
-        .map_err(|err| format!("polkit proxy error: {err}"))?;
-    let subject = Subject::new_for_message_header(header)
-        .map_err(|err| format!("polkit subject error: {err}"))?;
-    let flags = CheckAuthorizationFlags::AllowUserInteraction.into();
-    let result = proxy
-        .check_authorization(&subject, action_id, &details, flags, "")
-        .map_err(|err| format!("polkit check failed: {err}"))?;
+    let result = check_polkit(header, action_id, &details, true)?;
 
     if result.is_authorized {
         Ok(())
@@ -107,6 +1945,30 @@ fn authorize(
     }
 }
 
+/// Ask polkit about `action_id`; with `interactive` it may prompt for a
+/// password and blocks until the user answers.
+fn check_polkit(
+    header: &MessageHeader<'_>,
+    action_id: &str,
+    details: &HashMap<&str, &str>,
+    interactive: bool,
+) -> Result<AuthorizationResult, String> {
+    let flags = if interactive {
+        CheckAuthorizationFlags::AllowUserInteraction.into()
+    } else {
+        Default::default()
+    };
+    let connection =
+        Connection::system().map_err(|err| format!("polkit connection error: {err}"))?;
+    let proxy = AuthorityProxyBlocking::new(&connection)
+        .map_err(|err| format!("polkit proxy error: {err}"))?;
+    let subject = Subject::new_for_message_header(header)
+        .map_err(|err| format!("polkit subject error: {err}"))?;
+    proxy
+        .check_authorization(&subject, action_id, details, flags, "")
+        .map_err(|err| format!("polkit check failed: {err}"))
+}
+
 fn serialize_response(result: Result<CommandOutcome, HelperError>) -> fdo::Result<String> {
     let response = match result {
         Ok(outcome) => HelperResponse::ok_with(outcome),
@@ -114,3 +1976,188 @@ fn serialize_response(result: Result<CommandOutcome, HelperError>) -> fdo::Resul
     };
     serde_json::to_string(&response).map_err(|err| fdo::Error::Failed(err.to_string()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        log_config_from_options, log_filter_from_options, perform_action, polkit_action_for,
+        service_query_from_options, service_template_from_options,
+    };
+    use crate::{HelperContext, HelperError};
+    use runkit_core::testing::SandboxFixture;
+    use runkit_core::{
+        LogSeverity, POLKIT_ACTION_ALERTS, POLKIT_ACTION_EDIT_FILES, POLKIT_ACTION_ENABLE,
+        POLKIT_ACTION_FORCE, POLKIT_ACTION_RESTART, POLKIT_ACTION_START, POLKIT_ACTION_STOP,
+        ServiceSort, StateFilter, SvlogdConfig,
+    };
+    use std::collections::HashMap;
+    use zbus::zvariant::{OwnedValue, Value};
+    use zbus::{MessageFields, MessageHeader, MessagePrimaryHeader, MessageType};
+
+    fn options<'a>(
+        entries: impl IntoIterator<Item = (&'a str, Value<'a>)>,
+    ) -> HashMap<String, OwnedValue> {
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), OwnedValue::from(value)))
+            .collect()
+    }
+
+    #[test]
+    fn every_operation_maps_to_its_own_polkit_action() {
+        let expected = [
+            ("start", POLKIT_ACTION_START),
+            ("once", POLKIT_ACTION_START),
+            ("start-with-dependencies", POLKIT_ACTION_START),
+            ("stop", POLKIT_ACTION_STOP),
+            ("stop-with-dependents", POLKIT_ACTION_STOP),
+            ("maintenance", POLKIT_ACTION_STOP),
+            ("restart", POLKIT_ACTION_RESTART),
+            ("reload", POLKIT_ACTION_RESTART),
+            ("check", POLKIT_ACTION_RESTART),
+            ("hup", POLKIT_ACTION_RESTART),
+            ("pause", POLKIT_ACTION_RESTART),
+            ("restart-at-logout", POLKIT_ACTION_RESTART),
+            ("enable", POLKIT_ACTION_ENABLE),
+            ("disable", POLKIT_ACTION_ENABLE),
+            ("no-restart", POLKIT_ACTION_ENABLE),
+            ("set-down-file", POLKIT_ACTION_ENABLE),
+            ("enable-and-start", POLKIT_ACTION_ENABLE),
+            ("stop-and-disable", POLKIT_ACTION_ENABLE),
+            ("keep-runlevel", POLKIT_ACTION_ENABLE),
+            ("switch-runlevel", POLKIT_ACTION_ENABLE),
+            ("schedule-boot", POLKIT_ACTION_ENABLE),
+            ("restore-profile", POLKIT_ACTION_ENABLE),
+            ("check-interval", POLKIT_ACTION_ENABLE),
+            ("create", POLKIT_ACTION_EDIT_FILES),
+            ("instantiate", POLKIT_ACTION_EDIT_FILES),
+            ("read-file", POLKIT_ACTION_EDIT_FILES),
+            ("write-file", POLKIT_ACTION_EDIT_FILES),
+            ("set-env", POLKIT_ACTION_EDIT_FILES),
+            ("set-log-config", POLKIT_ACTION_EDIT_FILES),
+            ("import", POLKIT_ACTION_EDIT_FILES),
+            ("export", POLKIT_ACTION_EDIT_FILES),
+            ("trash", POLKIT_ACTION_EDIT_FILES),
+            ("delete", POLKIT_ACTION_EDIT_FILES),
+            ("restore", POLKIT_ACTION_EDIT_FILES),
+            ("purge-trash", POLKIT_ACTION_EDIT_FILES),
+            ("silence-alerts", POLKIT_ACTION_ALERTS),
+            ("kill", POLKIT_ACTION_FORCE),
+            ("term", POLKIT_ACTION_FORCE),
+            ("force-stop", POLKIT_ACTION_FORCE),
+            ("force-restart", POLKIT_ACTION_FORCE),
+        ];
+        for (operation, action) in expected {
+            assert_eq!(polkit_action_for(operation), Some(action), "{operation}");
+        }
+    }
+
+    #[test]
+    fn unknown_operations_are_refused() {
+        for operation in ["", "frobnicate", "Start", "read_file", "cached"] {
+            assert_eq!(polkit_action_for(operation), None, "{operation}");
+        }
+    }
+
+    #[test]
+    fn query_and_log_filter_options_are_parsed() {
+        let query = service_query_from_options(&options([
+            ("states", Value::from(vec!["running".to_string()])),
+            ("sort", Value::from("name")),
+            ("enabled", Value::from(true)),
+            ("search", Value::from("")),
+        ]))
+        .unwrap();
+        assert_eq!(query.states, [StateFilter::Running]);
+        assert_eq!(query.sort, ServiceSort::Name);
+        assert_eq!(query.enabled, Some(true));
+        assert_eq!(query.text, None);
+        assert!(service_query_from_options(&options([("sort", Value::from("size"))])).is_err());
+
+        let filter = log_filter_from_options(&options([
+            ("pattern", Value::from("fail")),
+            ("severity", Value::from("error")),
+            ("since", Value::from(60_i64)),
+        ]))
+        .unwrap();
+        assert!(filter.pattern.is_some());
+        assert_eq!(filter.min_severity, Some(LogSeverity::Error));
+        assert_eq!(filter.since, Some(60));
+        assert!(log_filter_from_options(&options([("since", Value::from("an hour"))])).is_err());
+    }
+
+    #[test]
+    fn template_and_log_config_options_are_parsed() {
+        let environment = HashMap::from([
+            ("LANG".to_string(), "C".to_string()),
+            ("HOME".to_string(), "/var/empty".to_string()),
+        ]);
+        let template = service_template_from_options(
+            "web",
+            "/usr/bin/web",
+            &options([
+                ("user", Value::from("")),
+                ("environment", Value::from(environment)),
+                ("logger", Value::from(true)),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(template.user, None);
+        assert_eq!(
+            template.environment,
+            [
+                ("HOME".to_string(), "/var/empty".to_string()),
+                ("LANG".to_string(), "C".to_string()),
+            ]
+        );
+        assert!(template.logger);
+
+        let current = SvlogdConfig {
+            other: vec!["!gzip".to_string()],
+            ..SvlogdConfig::default()
+        };
+        let config = log_config_from_options(
+            &options([
+                ("size", Value::from(4096_u64)),
+                ("filters", Value::from(vec!["-*debug*".to_string()])),
+            ]),
+            current,
+        )
+        .unwrap();
+        assert_eq!(config.size, Some(4096));
+        assert_eq!(config.filters.len(), 1);
+        assert_eq!(config.other, ["!gzip"]);
+        assert!(matches!(
+            log_config_from_options(
+                &options([("filters", Value::from(vec!["debug".to_string()]))]),
+                SvlogdConfig::default(),
+            ),
+            Err(HelperError::InvalidLogConfig(_))
+        ));
+    }
+
+    #[test]
+    fn perform_action_guards_protected_services_before_polkit() {
+        let sandbox = SandboxFixture::new().unwrap();
+        sandbox
+            .add_service("dbus", "#!/bin/sh\nexec dbus-daemon --nofork\n")
+            .unwrap();
+        let context = HelperContext::with_backend(sandbox.manager().clone());
+        // No sender: anything that reaches polkit fails with a different error.
+        let header = MessageHeader::new(
+            MessagePrimaryHeader::new(MessageType::MethodCall, 0),
+            MessageFields::new(),
+        );
+
+        for action in ["no-restart", "interrupt", "quit", "term", "kill"] {
+            assert!(
+                matches!(
+                    perform_action(&context, &header, action, "dbus"),
+                    Err(HelperError::Protected(service)) if service == "dbus"
+                ),
+                "{action}"
+            );
+        }
+        assert!(sandbox.sv_calls().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,170 @@
+//! `tech.geektoshi.Runkit1.Controller2`: the most used calls again, answering
+//! with D-Bus types instead of JSON strings, for busctl, Python or shell
+//! clients without a JSON parser.
+//!
+//! Each JSON object of the string interface becomes an `a{sv}` with the same
+//! keys; `null` members are left out, numbers become `x`, `t` or `d`, and
+//! arrays inside an object become `av`. Failures are D-Bus errors rather
+//! than an `"error"` status.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::Serialize;
+use serde_json::Value as Json;
+use zbus::MessageHeader;
+use zbus::fdo;
+use zbus::zvariant::{Array, Dict, OwnedValue, Signature, Type, Value};
+
+use runkit_core::{LogFilter, ServerInfo, ServiceQuery};
+
+use crate::dbus::{perform_action, perform_action_and_wait};
+use crate::{CommandOutcome, HelperContext, HelperError};
+
+pub(crate) struct TypedController {
+    pub(crate) context: Arc<HelperContext>,
+}
+
+/// What an action answers: runkitd's message and the data the string
+/// interface would return, as `(sa{sv})`.
+#[derive(Debug, Serialize, Type)]
+pub struct ActionResult {
+    message: String,
+    data: HashMap<String, OwnedValue>,
+}
+
+#[zbus::dbus_interface(name = "tech.geektoshi.Runkit1.Controller2")]
+impl TypedController {
+    #[dbus_interface(property)]
+    fn version(&self) -> String {
+        env!("CARGO_PKG_VERSION").to_string()
+    }
+
+    #[dbus_interface(property)]
+    fn api_version(&self) -> u32 {
+        self.server_info().api_version
+    }
+
+    #[dbus_interface(property)]
+    fn schema_version(&self) -> u32 {
+        self.server_info().schema_version
+    }
+
+    #[dbus_interface(property)]
+    fn capabilities(&self) -> Vec<String> {
+        self.server_info().capabilities
+    }
+
+    /// Every service, as `ListServices` of the string interface describes it.
+    fn list_services(&self) -> fdo::Result<Vec<HashMap<String, OwnedValue>>> {
+        records(self.context.list(&ServiceQuery::default()))
+    }
+
+    /// The last `lines` log entries of `service`, oldest first.
+    fn fetch_logs(
+        &self,
+        service: &str,
+        lines: u32,
+    ) -> fdo::Result<Vec<HashMap<String, OwnedValue>>> {
+        records(
+            self.context
+                .logs(service, lines as usize, &LogFilter::default(), None),
+        )
+    }
+
+    fn perform_action(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        action: &str,
+        service: &str,
+    ) -> fdo::Result<ActionResult> {
+        action_result(perform_action(&self.context, &header, action, service))
+    }
+
+    /// Start, stop or restart a service and wait up to `timeout_secs` for it
+    /// to get there; not getting there is an error.
+    fn perform_action_and_wait(
+        &self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        action: &str,
+        service: &str,
+        timeout_secs: u32,
+    ) -> fdo::Result<ActionResult> {
+        action_result(perform_action_and_wait(
+            &self.context,
+            &header,
+            action,
+            service,
+            timeout_secs,
+        ))
+    }
+}
+
+impl TypedController {
+    fn server_info(&self) -> ServerInfo {
+        ServerInfo::current(env!("CARGO_PKG_VERSION"))
+    }
+}
+
+/// The array of objects a listing returns, as `aa{sv}`.
+fn records(
+    result: Result<CommandOutcome, HelperError>,
+) -> fdo::Result<Vec<HashMap<String, OwnedValue>>> {
+    let outcome = result.map_err(|err| fdo::Error::Failed(err.to_string()))?;
+    let Some(Json::Array(items)) = outcome.data else {
+        return Ok(Vec::new());
+    };
+    Ok(items.iter().map(members).collect())
+}
+
+fn action_result(result: Result<CommandOutcome, HelperError>) -> fdo::Result<ActionResult> {
+    let outcome = result.map_err(|err| fdo::Error::Failed(err.to_string()))?;
+    Ok(ActionResult {
+        message: outcome.message.unwrap_or_default(),
+        data: outcome.data.as_ref().map(members).unwrap_or_default(),
+    })
+}
+
+/// The members of a JSON object, or nothing for anything else.
+fn members(json: &Json) -> HashMap<String, OwnedValue> {
+    let Json::Object(object) = json else {
+        return HashMap::new();
+    };
+    object
+        .iter()
+        .filter_map(|(key, value)| Some((key.clone(), OwnedValue::from(variant(value)?))))
+        .collect()
+}
+
+/// `json` as a D-Bus value; `None` for `null`.
+fn variant(json: &Json) -> Option<Value<'static>> {
+    Some(match json {
+        Json::Null => return None,
+        Json::Bool(value) => Value::from(*value),
+        Json::Number(number) => match (number.as_u64(), number.as_i64()) {
+            (Some(value), _) => Value::from(value),
+            (None, Some(value)) => Value::from(value),
+            _ => Value::from(number.as_f64().unwrap_or_default()),
+        },
+        Json::String(value) => Value::from(value.clone()),
+        Json::Array(items) => {
+            let mut array = Array::new(Signature::from_static_str_unchecked("v"));
+            for item in items.iter().filter_map(variant) {
+                array.append(Value::Value(Box::new(item))).ok()?;
+            }
+            Value::Array(array)
+        }
+        Json::Object(object) => {
+            let mut dict = Dict::new(
+                Signature::from_static_str_unchecked("s"),
+                Signature::from_static_str_unchecked("v"),
+            );
+            for (key, value) in object {
+                if let Some(value) = variant(value) {
+                    dict.append(Value::from(key.clone()), Value::Value(Box::new(value)))
+                        .ok()?;
+                }
+            }
+            Value::Dict(dict)
+        }
+    })
+}
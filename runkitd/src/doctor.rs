@@ -0,0 +1,92 @@
+//! `runkitd doctor`: checks for the installation problems behind most support requests.
+use std::fs;
+
+use runkit_core::{BUS_NAME, CheckStatus, DiagnosticCheck, ServiceManager};
+use serde::Serialize;
+use serde_json::json;
+use zbus::blocking::Connection;
+use zbus::blocking::fdo::DBusProxy;
+use zbus::names::BusName;
+
+use crate::{CommandOutcome, HelperError};
+
+#[derive(Debug, Serialize)]
+struct CheckSnapshot {
+    check: &'static str,
+    status: &'static str,
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fix: Option<String>,
+}
+
+impl From<DiagnosticCheck> for CheckSnapshot {
+    fn from(check: DiagnosticCheck) -> Self {
+        CheckSnapshot {
+            check: check.name,
+            status: check.status.as_str(),
+            detail: check.detail,
+            fix: check.fix,
+        }
+    }
+}
+
+/// Run every check and report each with its status and, for problems, a fix.
+pub fn run(manager: &ServiceManager) -> Result<CommandOutcome, HelperError> {
+    let mut checks = manager.installation_checks();
+    checks.push(check_bus_name());
+
+    let problems = checks.iter().filter(|check| check.is_problem()).count();
+    let message = if problems == 0 {
+        "No problems found".to_string()
+    } else {
+        format!("{problems} problem(s) found; see the `fix` field of each failed check")
+    };
+    let checks: Vec<CheckSnapshot> = checks.into_iter().map(CheckSnapshot::from).collect();
+    Ok(CommandOutcome::with(
+        Some(message),
+        Some(json!({ "problems": problems, "checks": checks })),
+    ))
+}
+
+/// A bus name held by a runkitd whose binary has since been replaced keeps
+/// serving the old code until that process exits.
+fn check_bus_name() -> DiagnosticCheck {
+    const NAME: &str = "bus_name";
+    let connection = match Connection::system() {
+        Ok(connection) => connection,
+        Err(err) => {
+            return DiagnosticCheck::problem(
+                NAME,
+                CheckStatus::Error,
+                format!("cannot reach the system bus: {err}"),
+                "enable the dbus service: sudo ln -s /etc/sv/dbus /var/service/",
+            );
+        }
+    };
+    let owner_pid = DBusProxy::new(&connection).ok().and_then(|proxy| {
+        let name = BusName::try_from(BUS_NAME).ok()?;
+        proxy.get_connection_unix_process_id(name).ok()
+    });
+    let Some(pid) = owner_pid else {
+        return DiagnosticCheck::ok(
+            NAME,
+            format!("{BUS_NAME} is not owned; the bus will start runkitd on first use"),
+        );
+    };
+
+    let exe = fs::read_link(format!("/proc/{pid}/exe"))
+        .map(|exe| exe.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if exe.ends_with(" (deleted)") {
+        DiagnosticCheck::problem(
+            NAME,
+            CheckStatus::Warning,
+            format!(
+                "{BUS_NAME} is owned by pid {pid}, running a runkitd binary that has since been replaced"
+            ),
+            format!("sudo kill {pid}; the bus starts the new runkitd on the next request"),
+        )
+    } else {
+        DiagnosticCheck::ok(NAME, format!("{BUS_NAME} is owned by pid {pid}"))
+    }
+}
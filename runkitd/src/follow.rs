@@ -0,0 +1,176 @@
+//! `FollowLogs` subscriptions.
+//!
+//! Each followed log gets a thread that polls it for new entries and sends
+//! them as `LogLine` signals addressed to the subscriber alone, so other
+//! clients never see them. A subscription ends with `StopFollowing`, when the
+//! subscriber leaves the bus, or when the log can no longer be read; only the
+//! last case is announced, with `LogFollowEnded`.
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use runkit_core::LogFollower;
+use zbus::blocking::Connection;
+use zbus::blocking::fdo::DBusProxy;
+use zbus::names::BusName;
+
+use crate::{HelperError, LogEntrySnapshot};
+
+/// Logs one client may follow at once.
+pub const MAX_SUBSCRIPTIONS_PER_CLIENT: usize = 8;
+
+/// How long a follower thread sleeps when nothing new has been written.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Idle polls between checks that the subscriber is still on the bus.
+const CLIENT_CHECK_POLLS: u32 = 20;
+
+struct Subscription {
+    client: String,
+    stop: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+pub struct LogSubscriptions {
+    next_id: AtomicU64,
+    active: Arc<Mutex<HashMap<u64, Subscription>>>,
+}
+
+impl LogSubscriptions {
+    /// Send what `follower` reads from `service`'s log to `client`, the
+    /// subscriber's unique bus name. Returns the subscription id.
+    pub fn start(
+        &self,
+        connection: Connection,
+        client: &str,
+        service: &str,
+        mut follower: LogFollower,
+    ) -> Result<u64, HelperError> {
+        let mut active = self.active.lock().unwrap_or_else(|err| err.into_inner());
+        let held = active
+            .values()
+            .filter(|subscription| subscription.client == client)
+            .count();
+        if held >= MAX_SUBSCRIPTIONS_PER_CLIENT {
+            return Err(HelperError::Other(format!(
+                "already following {held} logs; stop one first"
+            )));
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let stop = Arc::new(AtomicBool::new(false));
+        let subscriptions = Arc::clone(&self.active);
+        let thread_stop = Arc::clone(&stop);
+        let thread_client = client.to_string();
+        let thread_service = service.to_string();
+        thread::Builder::new()
+            .name(format!("runkitd-follow-{id}"))
+            .spawn(move || {
+                let ended = follow(
+                    &connection,
+                    &thread_client,
+                    id,
+                    &thread_service,
+                    &mut follower,
+                    &thread_stop,
+                );
+                subscriptions
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .remove(&id);
+                if let Some(reason) = ended {
+                    let _ = connection.emit_signal(
+                        Some(thread_client.as_str()),
+                        crate::dbus::OBJECT_PATH,
+                        crate::dbus::INTERFACE,
+                        "LogFollowEnded",
+                        &(id, reason.as_str()),
+                    );
+                }
+            })
+            .map_err(|err| HelperError::Other(format!("cannot follow {service}: {err}")))?;
+        active.insert(
+            id,
+            Subscription {
+                client: client.to_string(),
+                stop,
+            },
+        );
+        Ok(id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.active
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .is_empty()
+    }
+
+    /// End `client`'s subscription `id`. Returns whether it had one.
+    pub fn stop(&self, client: &str, id: u64) -> bool {
+        let mut active = self.active.lock().unwrap_or_else(|err| err.into_inner());
+        match active.get(&id) {
+            Some(subscription) if subscription.client == client => {
+                subscription.stop.store(true, Ordering::Relaxed);
+                active.remove(&id);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Relay entries until stopped or the client is gone, which return `None`,
+/// or until reading or signalling fails, which returns why.
+fn follow(
+    connection: &Connection,
+    client: &str,
+    id: u64,
+    service: &str,
+    follower: &mut LogFollower,
+    stop: &AtomicBool,
+) -> Option<String> {
+    let mut idle_polls = 0u32;
+    while !stop.load(Ordering::Relaxed) {
+        match follower.try_next() {
+            Ok(Some(entry)) => {
+                idle_polls = 0;
+                let entry = serde_json::to_string(&LogEntrySnapshot::from(entry))
+                    .unwrap_or_else(|err| format!("{{\"error\":\"{err}\"}}"));
+                let sent = connection.emit_signal(
+                    Some(client),
+                    crate::dbus::OBJECT_PATH,
+                    crate::dbus::INTERFACE,
+                    "LogLine",
+                    &(id, service, entry.as_str()),
+                );
+                if let Err(err) = sent {
+                    return Some(err.to_string());
+                }
+            }
+            Ok(None) => {
+                idle_polls += 1;
+                if idle_polls.is_multiple_of(CLIENT_CHECK_POLLS)
+                    && !is_connected(connection, client)
+                {
+                    return None;
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+            Err(err) => return Some(err.to_string()),
+        }
+    }
+    None
+}
+
+fn is_connected(connection: &Connection, client: &str) -> bool {
+    let Ok(name) = BusName::try_from(client) else {
+        return false;
+    };
+    DBusProxy::new(connection)
+        .and_then(|dbus| Ok(dbus.name_has_owner(name)?))
+        // Keep following when the bus cannot tell.
+        .unwrap_or(true)
+}
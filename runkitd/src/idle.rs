@@ -0,0 +1,109 @@
+//! `--idle-timeout`: exit once nobody has used runkitd for a while, leaving
+//! D-Bus activation to start it again on the next call.
+//!
+//! runkitd stays while a client that called it is still on the bus, so an
+//! open Runkit window keeps it, and while `busy` says there is work left such
+//! as a job, a log subscription, a restart waiting for logout, a scheduled
+//! check, an alert route beyond the bus or an unrecorded boot report. Desktop
+//! alerts and `ServiceChanged` signals only reach clients on the bus, which
+//! keep it running while they are connected.
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use runkit_core::BUS_NAME;
+use zbus::MessageType;
+use zbus::blocking::fdo::DBusProxy;
+use zbus::blocking::{Connection, MessageIterator};
+use zbus::names::BusName;
+
+/// Longest pause between checks whether runkitd is idle.
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time between giving up the bus name and exiting, for replies in flight.
+const EXIT_GRACE: Duration = Duration::from_secs(2);
+
+/// Set once runkitd gives up its bus name to exit, so losing the name is
+/// not mistaken for being replaced.
+static EXITING: AtomicBool = AtomicBool::new(false);
+
+pub fn is_exiting() -> bool {
+    EXITING.load(Ordering::Relaxed)
+}
+
+struct Activity {
+    last: Instant,
+    /// Unique names of the clients that called runkitd.
+    clients: HashSet<String>,
+}
+
+/// Exit the process after `timeout` without method calls, unless a caller
+/// is still connected or `busy` returns true.
+pub fn exit_when_idle<F>(connection: &Connection, timeout: Duration, busy: F) -> std::io::Result<()>
+where
+    F: Fn() -> bool + Send + 'static,
+{
+    let activity = Arc::new(Mutex::new(Activity {
+        last: Instant::now(),
+        clients: HashSet::new(),
+    }));
+
+    let calls = MessageIterator::from(connection.clone());
+    let recorder = Arc::clone(&activity);
+    thread::Builder::new()
+        .name("runkitd-activity".into())
+        .spawn(move || {
+            for message in calls.flatten() {
+                if message.message_type() != MessageType::MethodCall {
+                    continue;
+                }
+                let sender = message
+                    .header()
+                    .ok()
+                    .and_then(|header| header.sender().ok().flatten().map(ToString::to_string));
+                let mut activity = recorder.lock().unwrap_or_else(|err| err.into_inner());
+                activity.last = Instant::now();
+                activity.clients.extend(sender);
+            }
+        })?;
+
+    let connection = connection.clone();
+    thread::Builder::new()
+        .name("runkitd-idle".into())
+        .spawn(move || {
+            loop {
+                thread::sleep(CHECK_INTERVAL.min(timeout));
+                let mut activity = activity.lock().unwrap_or_else(|err| err.into_inner());
+                activity
+                    .clients
+                    .retain(|client| is_connected(&connection, client));
+                if !activity.clients.is_empty() || busy() {
+                    activity.last = Instant::now();
+                    continue;
+                }
+                if activity.last.elapsed() < timeout {
+                    continue;
+                }
+                eprintln!(
+                    "runkitd: idle for {} minute(s); exiting",
+                    timeout.as_secs() / 60
+                );
+                EXITING.store(true, Ordering::Relaxed);
+                let _ = connection.release_name(BUS_NAME);
+                thread::sleep(EXIT_GRACE);
+                std::process::exit(0);
+            }
+        })?;
+    Ok(())
+}
+
+fn is_connected(connection: &Connection, client: &str) -> bool {
+    let Ok(name) = BusName::try_from(client) else {
+        return false;
+    };
+    DBusProxy::new(connection)
+        .and_then(|dbus| Ok(dbus.name_has_owner(name)?))
+        .unwrap_or(false)
+}
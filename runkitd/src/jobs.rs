@@ -0,0 +1,187 @@
+//! Jobs: long operations (waiting restarts, batches, profile restores) that
+//! answer the D-Bus call with a job id straight away and run on their own
+//! thread.
+//!
+//! While a job runs it sends `JobProgress(job, done, total, message)` to the
+//! caller that started it, and when it ends `JobCompleted(job, response)`,
+//! `response` being the JSON the blocking method would have returned. The
+//! caller may end it early with `CancelJob`, which completes it at once with
+//! an error; what was already done to services is not undone.
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use zbus::blocking::Connection;
+
+use crate::{CommandOutcome, HelperError, HelperResponse};
+
+/// Jobs one client may run at once.
+pub const MAX_JOBS_PER_CLIENT: usize = 4;
+
+/// Where a long operation says how far it got, and learns whether to stop.
+/// `()` reports nowhere and never stops.
+pub trait Progress {
+    fn report(&self, done: usize, total: usize, message: &str);
+
+    fn cancelled(&self) -> bool;
+}
+
+impl Progress for () {
+    fn report(&self, _done: usize, _total: usize, _message: &str) {}
+
+    fn cancelled(&self) -> bool {
+        false
+    }
+}
+
+struct Job {
+    client: String,
+    connection: Connection,
+    cancelled: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+pub struct Jobs {
+    next_id: AtomicU64,
+    running: Arc<Mutex<HashMap<u64, Job>>>,
+}
+
+impl Jobs {
+    /// Run `work` for `client`, the caller's unique bus name, and return the
+    /// job id. `what` names the job in errors.
+    pub fn start<F>(
+        &self,
+        connection: Connection,
+        client: &str,
+        what: &str,
+        work: F,
+    ) -> Result<u64, HelperError>
+    where
+        F: FnOnce(&JobProgress) -> Result<CommandOutcome, HelperError> + Send + 'static,
+    {
+        let mut running = self.running.lock().unwrap_or_else(|err| err.into_inner());
+        let held = running.values().filter(|job| job.client == client).count();
+        if held >= MAX_JOBS_PER_CLIENT {
+            return Err(HelperError::Other(format!(
+                "already running {held} jobs; wait for one to finish"
+            )));
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let progress = JobProgress {
+            connection: connection.clone(),
+            client: client.to_string(),
+            id,
+            cancelled: Arc::clone(&cancelled),
+        };
+        let jobs = Arc::clone(&self.running);
+        thread::Builder::new()
+            .name(format!("runkitd-job-{id}"))
+            .spawn(move || {
+                let result = work(&progress);
+                // A cancelled job was already completed by `cancel`.
+                let finished = jobs
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .remove(&id);
+                if let Some(job) = finished {
+                    job.complete(id, result);
+                }
+            })
+            .map_err(|err| HelperError::Other(format!("cannot start {what}: {err}")))?;
+        running.insert(
+            id,
+            Job {
+                client: client.to_string(),
+                connection,
+                cancelled,
+            },
+        );
+        Ok(id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.running
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .is_empty()
+    }
+
+    /// Cancel `client`'s job `id`. Returns whether it had one running.
+    pub fn cancel(&self, client: &str, id: u64) -> bool {
+        let mut running = self.running.lock().unwrap_or_else(|err| err.into_inner());
+        let job = match running.get(&id) {
+            Some(job) if job.client == client => running.remove(&id),
+            _ => None,
+        };
+        drop(running);
+        let Some(job) = job else {
+            return false;
+        };
+        job.cancelled.store(true, Ordering::Relaxed);
+        job.complete(id, Err(HelperError::Other("cancelled".to_string())));
+        true
+    }
+}
+
+impl Job {
+    fn complete(&self, id: u64, result: Result<CommandOutcome, HelperError>) {
+        let response = match result {
+            Ok(outcome) => HelperResponse::ok_with(outcome),
+            Err(err) => HelperResponse::error(err.to_string()),
+        };
+        let response = serde_json::to_string(&response)
+            .unwrap_or_else(|err| format!("{{\"status\":\"error\",\"message\":\"{err}\"}}"));
+        let _ = self.connection.emit_signal(
+            Some(self.client.as_str()),
+            crate::dbus::OBJECT_PATH,
+            crate::dbus::INTERFACE,
+            "JobCompleted",
+            &(id, response.as_str()),
+        );
+    }
+}
+
+/// What a running job reports through.
+pub struct JobProgress {
+    connection: Connection,
+    client: String,
+    id: u64,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Progress for JobProgress {
+    fn report(&self, done: usize, total: usize, message: &str) {
+        if self.cancelled() {
+            return;
+        }
+        let _ = self.connection.emit_signal(
+            Some(self.client.as_str()),
+            crate::dbus::OBJECT_PATH,
+            crate::dbus::INTERFACE,
+            "JobProgress",
+            &(self.id, done as u32, total as u32, message),
+        );
+    }
+
+    fn cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Jobs, Progress};
+
+    #[test]
+    fn only_running_jobs_can_be_cancelled() {
+        let jobs = Jobs::default();
+        assert!(jobs.is_empty());
+        assert!(!jobs.cancel(":1.42", 1));
+        // The blocking calls report nowhere and never stop.
+        ().report(1, 2, "ignored");
+        assert!(!().cancelled());
+    }
+}
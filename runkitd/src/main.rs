@@ -1,15 +1,43 @@
+mod cached_auth;
 mod dbus;
+mod dbus_typed;
+mod doctor;
+mod follow;
+mod idle;
+mod jobs;
+mod notify;
+mod profile;
+mod refresh_profile;
 
 use clap::{Parser, Subcommand};
 use runkit_core::{
-    DesiredState, ServiceError, ServiceInfo, ServiceLogEntry, ServiceManager, ServiceRuntimeState,
+    BootSchedule, CheckResult, DEFAULT_BOOT_REPORTS, DEFAULT_PROTECTED_CONFIG,
+    DEFAULT_RUNKITD_PATH, DEFAULT_SESSIONS_DIR, DeleteMode, DesiredState, EffectiveEnvironment,
+    EnvDir, EnvSource, EnvVariable, HealthIssue, HistoryEvent, Inventory, IoSource, IoUsage,
+    LogFilter, LogPattern, LogSeverity, Maintenance, NamePolicy, POLKIT_ACTION_ENABLE,
+    POLKIT_ACTION_FORCE, POLKIT_ACTION_RESTART, POLKIT_ACTION_START, POLKIT_ACTION_STOP,
+    ProcessNode, ProfileChanges, ProtectedServices, RestartPolicy, RunScriptInfo, RunitBackend,
+    SCHEMA_VERSION, ScheduledBootChange, ServerInfo, ServiceError, ServiceHistory, ServiceInfo,
+    ServiceLogEntry, ServiceManager, ServiceMetrics, ServiceQuery, ServiceRuntimeState,
+    ServiceSort, ServiceTemplate, StateChange, StateFilter, SupervisorDiagnostic, SvlogdConfig,
+    SvlogdFilter, TemplateBundle, TimestampFormat, TimestampStyle, TrashEntry, WaitOutcome,
+    boot_regressions, default_log_archive_dir, format_duration, graphical_user_sessions,
+    merge_boot_report, polkit_policy, read_boot_reports, record_boot_report,
+    session_critical_reason, span, write_log_archive,
 };
 use serde::Serialize;
 use serde_json::{Value, json};
-use std::path::PathBuf;
-use std::process::Command;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
+use crate::jobs::Progress;
+use crate::notify::AlertSilence;
+use crate::profile::ProfileFormat;
+
 /// Command-line entry point.
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Privileged daemon for the Runkit GUI", long_about = None)]
@@ -18,6 +46,82 @@ struct Cli {
     #[arg(long = "dbus-service")]
     dbus_service: bool,
 
+    /// With --dbus-service, take the bus name over from a running instance
+    /// instead of exiting; waits for it if it cannot be replaced.
+    #[arg(long, requires = "dbus_service")]
+    replace: bool,
+
+    /// With --dbus-service, how many method calls to work on at once; a slow
+    /// restart or password prompt only holds up one of them.
+    #[arg(long, value_name = "N", default_value_t = dbus::DEFAULT_WORKERS, requires = "dbus_service")]
+    workers: usize,
+
+    /// With --dbus-service, exit after this many minutes without calls, once
+    /// no caller is connected and no job, log subscription, logout restart,
+    /// scheduled check, off-bus alert route or boot report is pending. D-Bus
+    /// activation starts runkitd again when it is needed; 0 never exits.
+    #[arg(
+        long,
+        value_name = "MINUTES",
+        default_value_t = dbus::DEFAULT_IDLE_TIMEOUT,
+        requires = "dbus_service"
+    )]
+    idle_timeout: u64,
+
+    /// Repeat a GUI refresh (list, encode, tail logs) N times, 5 by default,
+    /// and report the time spent starting processes, reading files and
+    /// encoding responses.
+    #[arg(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        default_missing_value = "5",
+        conflicts_with = "dbus_service"
+    )]
+    profile_refresh: Option<u32>,
+
+    /// Which service names to accept: strict, portable or permissive (allows
+    /// `@`, `+` and other characters; path traversal is always refused).
+    #[arg(long, global = true, value_name = "POLICY", default_value_t = NamePolicy::default())]
+    name_policy: NamePolicy,
+
+    /// Days a trashed definition is kept before it is purged.
+    #[arg(long, global = true, value_name = "DAYS", default_value_t = 30)]
+    trash_retention_days: u64,
+
+    /// Also manage services defined in DEFINITIONS and enabled in ENABLED, for
+    /// setups with a second runsvdir. Repeat for more pairs.
+    #[arg(
+        long = "service-dir",
+        global = true,
+        value_name = "DEFINITIONS:ENABLED",
+        value_parser = parse_service_dirs
+    )]
+    service_dirs: Vec<(PathBuf, PathBuf)>,
+
+    /// Most services one `restart-many` call may restart without `--confirm`.
+    #[arg(long, global = true, value_name = "COUNT", default_value_t = RestartLimits::default().max_unconfirmed)]
+    restart_batch_limit: usize,
+
+    /// Pause between consecutive restarts of a batch, so networking, the
+    /// display manager and the session do not all go down together.
+    #[arg(long, global = true, value_name = "MS", default_value_t = 2000)]
+    restart_stagger_ms: u64,
+
+    /// Where the D-Bus service reads its alert routes (desktop, webhook, mqtt,
+    /// command) from.
+    #[arg(long, value_name = "PATH", default_value = notify::DEFAULT_NOTIFY_CONFIG)]
+    notify_config: PathBuf,
+
+    /// Services that are not stopped or disabled without
+    /// `--override-protection`: the defaults adjusted by this file.
+    #[arg(long, global = true, value_name = "PATH", default_value = DEFAULT_PROTECTED_CONFIG)]
+    protected_config: PathBuf,
+
+    /// Stop, disable or delete a protected service anyway.
+    #[arg(long, global = true)]
+    override_protection: bool,
+
     #[command(subcommand)]
     command: Option<HelperCommand>,
 }
@@ -26,11 +130,99 @@ struct Cli {
 #[derive(Subcommand, Debug)]
 enum HelperCommand {
     /// Start a service and ensure it keeps running.
-    Start { service: String },
+    Start {
+        service: String,
+        /// Wait up to SECS for the service to be running and report its state.
+        #[arg(long, value_name = "SECS")]
+        wait: Option<u64>,
+    },
     /// Stop a service and keep it down.
-    Stop { service: String },
+    Stop {
+        service: String,
+        /// Wait up to SECS for the service to be down and report its state.
+        #[arg(long, value_name = "SECS")]
+        wait: Option<u64>,
+    },
     /// Restart a service.
-    Restart { service: String },
+    Restart {
+        service: String,
+        /// Wait up to SECS for a new process to be running and report its state.
+        #[arg(long, value_name = "SECS")]
+        wait: Option<u64>,
+    },
+    /// Enable a service and start it now, in one step. If it is not running
+    /// within the wait, it is stopped and disabled again.
+    EnableAndStart {
+        service: String,
+        /// How long to wait for the service to be running.
+        #[arg(long, value_name = "SECS", default_value_t = COMBINED_ACTION_WAIT.as_secs())]
+        wait: u64,
+    },
+    /// Stop a service and disable it, in one step. If it does not go down within
+    /// the wait, it is started again and stays enabled.
+    StopAndDisable {
+        service: String,
+        /// How long to wait for the service to be down.
+        #[arg(long, value_name = "SECS", default_value_t = COMBINED_ACTION_WAIT.as_secs())]
+        wait: u64,
+    },
+    /// Start the services a service depends on, in order, then the service.
+    /// Dependencies come from its `depends` file, `# Requires:` comments and
+    /// `sv check` lines in its run script.
+    StartWithDependencies {
+        service: String,
+        /// How long to wait for each service to be running.
+        #[arg(long, value_name = "SECS", default_value_t = COMBINED_ACTION_WAIT.as_secs())]
+        wait: u64,
+    },
+    /// Stop the services that depend on a service, then the service itself.
+    StopWithDependents {
+        service: String,
+        /// How long to wait for each service to be down.
+        #[arg(long, value_name = "SECS", default_value_t = COMBINED_ACTION_WAIT.as_secs())]
+        wait: u64,
+    },
+    /// Show declared dependencies: the whole graph and any cycles, or for one
+    /// service what it needs, what needs it and the order it starts in.
+    Dependencies { service: Option<String> },
+    /// Whether a runsvdir supervises the services, or the container or
+    /// chroot that leaves only browsing and editing definitions.
+    Supervision,
+    /// Stop a service and keep it down for planned work. No alerts are raised
+    /// for it until maintenance ends.
+    Maintenance {
+        service: String,
+        /// Shown next to the service while it is in maintenance.
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// End maintenance and start the service again if it was running before.
+    EndMaintenance { service: String },
+    /// Have runkitd run the service's `./check` every SECONDS while it runs
+    /// and signal when the outcome changes; 0 stops.
+    SetCheckInterval { service: String, seconds: u64 },
+    /// Run the service's `./check` now and record the outcome.
+    RunCheck { service: String },
+    /// Restart several services one at a time, pausing between them.
+    RestartMany {
+        #[arg(required = true)]
+        services: Vec<String>,
+        /// Allow restarting more services than `--restart-batch-limit`.
+        #[arg(long)]
+        confirm: bool,
+    },
+    /// Start, stop, restart, enable or disable several services, one after
+    /// another. Protected services are skipped by stop and disable unless
+    /// `--override-protection` is given.
+    Batch {
+        /// `start`, `stop`, `restart`, `enable` or `disable`.
+        action: String,
+        #[arg(required = true)]
+        services: Vec<String>,
+        /// Allow restarting more services than `--restart-batch-limit`.
+        #[arg(long)]
+        confirm: bool,
+    },
     /// Reload a service's configuration.
     Reload { service: String },
     /// Run the service's check script.
@@ -41,16 +233,374 @@ enum HelperCommand {
     Enable { service: String },
     /// Disable a service (stop auto-start).
     Disable { service: String },
+    /// Keep a service down after it exits instead of restarting it.
+    NoRestart { service: String },
+    /// Let runsv restart a service whenever it exits (the default).
+    AutoRestart { service: String },
+    /// Create the service's `down` file so it is not started at boot.
+    SetDownFile { service: String },
+    /// Remove the service's `down` file so it starts at boot again.
+    ClearDownFile { service: String },
+    /// Send the service SIGHUP, which many daemons take as "reread your config".
+    Hup { service: String },
+    /// Send the service SIGALRM.
+    Alarm { service: String },
+    /// Send the service SIGINT.
+    Interrupt { service: String },
+    /// Send the service SIGQUIT.
+    Quit { service: String },
+    /// Send the service SIGTERM; runsv restarts it unless it is meant to be down.
+    Term { service: String },
+    /// Send the service SIGKILL; runsv restarts it unless it is meant to be down.
+    Kill { service: String },
+    /// Suspend the service with SIGSTOP.
+    Pause { service: String },
+    /// Resume a paused service with SIGCONT.
+    Cont { service: String },
+    /// Stop the service, killing it if it has not exited within sv's timeout.
+    ForceStop { service: String },
+    /// Send SIGHUP and kill the service if it has not restarted within sv's timeout.
+    ForceReload { service: String },
+    /// Restart the service, killing it if it has not exited within sv's timeout.
+    ForceRestart { service: String },
     /// Fetch service description without loading logs or status.
     Describe { service: String },
     /// List all available services with their current status.
-    List,
+    List {
+        /// Only services in this state: running, down or failed. Repeat to
+        /// keep several.
+        #[arg(long, value_name = "STATE")]
+        state: Vec<StateFilter>,
+        /// Only enabled services.
+        #[arg(long, conflicts_with = "disabled")]
+        enabled: bool,
+        /// Only disabled services.
+        #[arg(long)]
+        disabled: bool,
+        /// Only services whose name or description contains this text
+        /// (case-insensitive).
+        #[arg(long, value_name = "TEXT")]
+        search: Option<String>,
+        /// Order by name, uptime (most recently started first) or state
+        /// (failed first).
+        #[arg(long, value_name = "ORDER", default_value_t = ServiceSort::Name)]
+        sort: ServiceSort,
+    },
+    /// Report dangling or foreign enable links, definitions without a usable run
+    /// script, and services enabled in more than one runlevel.
+    Health,
+    /// List the runlevel directories, the services linked into each, and which
+    /// one is current.
+    Runlevels,
+    /// Make RUNLEVEL current with `runsvchdir`.
+    SwitchRunlevel { runlevel: String },
+    /// Link a service into a runlevel other than (or as well as) the current one.
+    EnableIn { runlevel: String, service: String },
+    /// Remove a service's links from a runlevel.
+    DisableIn { runlevel: String, service: String },
+    /// Remove duplicate enable links so the service stays enabled in one runlevel only.
+    KeepRunlevel {
+        service: String,
+        /// Runlevel to keep (defaults to the current one).
+        #[arg(long)]
+        runlevel: Option<String>,
+    },
+    /// Sample CPU, memory, file descriptor and thread usage of a running service,
+    /// including its child processes.
+    Metrics { service: String },
+    /// Show the starts, crashes and stops runkitd recorded for a service this
+    /// boot, with the last exit codes and whether it is flapping.
+    History {
+        service: String,
+        /// How far back to look.
+        #[arg(long, value_name = "MINUTES", default_value_t = 60)]
+        minutes: u64,
+    },
+    /// Show the resident memory of every running service, in bytes.
+    Memory,
+    /// Show every process descended from the service's supervised pid.
+    Processes { service: String },
+    /// Report what the service's run script executes: the binary, the user it
+    /// drops to, a sourced config file and an env dir.
+    Inspect { service: String },
+    /// Show the environment the service starts with, from its `conf` and env
+    /// dir, and flag variables its running process does not have as expected.
+    Environment { service: String },
+    /// Show the xbps package, with its version, that installed a definition.
+    Package { service: String },
+    /// List the variables in the service's env dir, the one its run script
+    /// loads with chpst -e or envdir.
+    EnvDir { service: String },
+    /// Set a variable in the service's env dir. An empty value makes chpst
+    /// remove it from the environment. Takes effect at the next start.
+    SetEnv {
+        service: String,
+        #[arg(value_name = "NAME=VALUE", value_parser = parse_env_pair)]
+        variable: (String, String),
+    },
+    /// Remove a variable from the service's env dir.
+    UnsetEnv { service: String, name: String },
     /// Tail logs for a service.
     Logs {
         service: String,
         #[arg(long, default_value_t = 200)]
         lines: usize,
+        /// Add a rendered `timestamp` to each entry (local, utc, tai64n or relative).
+        #[arg(long, value_name = "STYLE")]
+        timestamps: Option<TimestampStyle>,
+        /// Include fractional seconds in rendered timestamps.
+        #[arg(long, requires = "timestamps")]
+        subsecond: bool,
+        /// Keep running and print new entries as they are written, one JSON
+        /// object per line.
+        #[arg(long, short = 'f')]
+        follow: bool,
+        /// Only entries containing this text (case-insensitive).
+        #[arg(long, value_name = "PATTERN")]
+        grep: Option<String>,
+        /// Treat `--grep` as a regular expression.
+        #[arg(long, requires = "grep")]
+        regex: bool,
+        /// Only entries written at or after this Unix timestamp.
+        #[arg(long, value_name = "UNIX_SECONDS")]
+        since: Option<i64>,
+        /// Only entries at least this severe: info, warning or error.
+        #[arg(long, value_name = "LEVEL")]
+        severity: Option<LogSeverity>,
+    },
+    /// Search the logs of several services at once, newest matches first.
+    Search {
+        pattern: String,
+        /// Treat the pattern as a regular expression.
+        #[arg(long)]
+        regex: bool,
+        /// Restrict the search to this service; may be repeated. Searches every
+        /// service when omitted.
+        #[arg(long = "service", value_name = "SERVICE")]
+        services: Vec<String>,
+        #[arg(long, default_value_t = 100)]
+        limit: usize,
+        /// Add a rendered `timestamp` to each match (local, utc, tai64n or relative).
+        #[arg(long, value_name = "STYLE")]
+        timestamps: Option<TimestampStyle>,
+    },
+    /// Pack the last lines of every failed service's log into
+    /// `runkit-failed-logs-<time>.tar.gz`. A service counts as failed when
+    /// runsv reports it failing or it is enabled and should be up but is down.
+    ArchiveFailedLogs {
+        #[arg(long, default_value_t = 200)]
+        lines: usize,
+        /// Directory for the archive; `~/runkit-logs` by default.
+        #[arg(long, value_name = "DIR")]
+        output: Option<PathBuf>,
+    },
+    /// List runit's stage logs. `logs`, `follow` and `search` read them under
+    /// the names listed, such as `runit/2`.
+    StageLogs,
+    /// Print runkitd's version, the D-Bus API and response schema versions
+    /// it speaks, and the capabilities it offers.
+    ServerInfo,
+    /// List the services that are not stopped or disabled without
+    /// `--override-protection`.
+    ProtectedServices,
+    /// Show the service's svlogd rotation and filter settings.
+    LogConfig { service: String },
+    /// Change the service's svlogd settings and make its logger reread them.
+    /// Options not given keep their current value.
+    SetLogConfig {
+        service: String,
+        /// Rotate once the current log reaches this many bytes.
+        #[arg(long)]
+        size: Option<u64>,
+        /// Keep at most this many rotated files.
+        #[arg(long)]
+        num: Option<u32>,
+        /// Keep at least this many rotated files when the disk is full.
+        #[arg(long)]
+        min: Option<u32>,
+        /// Rotate after this many seconds.
+        #[arg(long, value_name = "SECONDS")]
+        timeout: Option<u64>,
+        /// Replace the filters with these svlogd lines, e.g. `-*debug*`; may be repeated.
+        #[arg(long = "filter", value_name = "LINE", allow_hyphen_values = true)]
+        filters: Vec<String>,
+        /// Remove every filter.
+        #[arg(long, conflicts_with = "filters")]
+        clear_filters: bool,
+    },
+    /// Write the polkit policy that defines every action runkitd checks.
+    PolkitPolicy {
+        #[arg(long, short = 'o')]
+        output: PathBuf,
+        /// Where runkitd is installed, for pkexec.
+        #[arg(long, value_name = "PATH", default_value = DEFAULT_RUNKITD_PATH)]
+        exec_path: String,
+    },
+    /// Package a service definition directory as a .tar.gz archive.
+    Export {
+        service: String,
+        #[arg(long, short = 'o')]
+        output: PathBuf,
+    },
+    /// Install a service definition from an archive produced by `export`.
+    Import {
+        archive: PathBuf,
+        /// Overwrite an existing definition with the same name; the old one is
+        /// moved to the trash.
+        #[arg(long)]
+        replace: bool,
+    },
+    /// Create a definition whose run script execs COMMAND, checking first that
+    /// the program exists and the settings are valid.
+    Create {
+        service: String,
+        /// Program and arguments to run in the foreground.
+        #[arg(long)]
+        command: String,
+        /// Run as this user, or USER:GROUP, via chpst.
+        #[arg(long)]
+        user: Option<String>,
+        /// Set a variable through an env dir. Repeat for more.
+        #[arg(long = "env", value_name = "NAME=VALUE", value_parser = parse_env_pair)]
+        environment: Vec<(String, String)>,
+        /// Add a log service that keeps output with svlogd in log/main.
+        #[arg(long)]
+        logger: bool,
+        /// Enable the service once it is created.
+        #[arg(long)]
+        enable: bool,
+    },
+    /// Create a service from a template bundle, a shared definition whose
+    /// variables are filled in here.
+    CreateFromBundle {
+        service: String,
+        /// The bundle's JSON file.
+        #[arg(long, value_name = "FILE")]
+        bundle: PathBuf,
+        /// Give a variable of the bundle a value. Repeat for more.
+        #[arg(long = "set", value_name = "NAME=VALUE", value_parser = parse_env_pair)]
+        values: Vec<(String, String)>,
+        /// Enable the service once it is created.
+        #[arg(long)]
+        enable: bool,
+    },
+    /// Print a file of the definition, e.g. run, finish or conf.
+    ReadFile { service: String, file: String },
+    /// Replace a file of the definition with the contents of --from, or of
+    /// standard input. The old file is backed up under .runkit-backups.
+    WriteFile {
+        service: String,
+        file: String,
+        /// Read the new contents from this file instead of standard input.
+        #[arg(long)]
+        from: Option<PathBuf>,
+        /// Save even if sh -n reports a syntax error.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Copy a definition under a new name, e.g. agetty-tty7 from agetty-tty1.
+    /// Shared run scripts stay symlinked unless a substitution changes them.
+    Instantiate {
+        source: String,
+        name: String,
+        /// Replace FROM with TO in run, finish, check, conf and log/run. Repeat for more.
+        #[arg(long = "set", value_name = "FROM=TO", value_parser = parse_substitution)]
+        substitutions: Vec<(String, String)>,
+        /// Enable the new service once it is created.
+        #[arg(long)]
+        enable: bool,
+    },
+    /// Disable a service and move its definition to the trash.
+    Trash { service: String },
+    /// Stop a service, unlink it from every runlevel and move its definition
+    /// to the trash. Definitions installed by a package are refused.
+    Delete {
+        service: String,
+        /// Remove the definition for good instead of trashing it.
+        #[arg(long)]
+        remove: bool,
     },
+    /// List trashed definitions, newest first.
+    ListTrash,
+    /// Move a trashed definition back, re-enabling it if it was enabled.
+    Restore {
+        /// Trash entry id as shown by `list-trash`.
+        id: String,
+    },
+    /// Print every definition with its enabled state and run script, for
+    /// comparing machines with `diff`.
+    Inventory,
+    /// Save which services are enabled and which have a `down` file, as TOML
+    /// for a `.toml` path and JSON otherwise.
+    SaveProfile {
+        path: PathBuf,
+        /// json or toml, overriding the extension.
+        #[arg(long)]
+        format: Option<ProfileFormat>,
+    },
+    /// Enable, disable and add or remove `down` files until this machine
+    /// matches a saved profile. Services the profile does not mention are left
+    /// alone.
+    RestoreProfile {
+        path: PathBuf,
+        /// Only list what would change.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Compare two inventories: services defined on one side only, enabled on
+    /// one side only, or with different run scripts.
+    Diff {
+        /// An inventory file written by `runkitd inventory`, or `local` for
+        /// this machine.
+        left: String,
+        /// Same as LEFT.
+        right: String,
+    },
+    /// Check the installation (polkit policy, D-Bus files, service directories,
+    /// `sv`, the bus name) and suggest fixes for anything wrong.
+    Doctor,
+    /// Delete trashed definitions past the retention period for good.
+    PurgeTrash {
+        /// Empty the whole trash regardless of age.
+        #[arg(long)]
+        all: bool,
+    },
+    /// Enable or disable a service relative to the next boot:
+    /// enable-next-boot-only, disable-after-next-boot or disable-at-next-boot.
+    ScheduleBoot {
+        service: String,
+        schedule: BootSchedule,
+    },
+    /// Cancel the change scheduled for a service at the next boot.
+    CancelBootSchedule { service: String },
+    /// List the enable and disable changes waiting for a boot.
+    BootSchedule,
+    /// Record how long after this boot each service came up, and list those
+    /// that came up significantly later than in the boots before.
+    BootReport,
+    /// Restart a service once the last graphical session has ended, for a
+    /// display manager or seat service that would end the session with it.
+    RestartAtLogout {
+        service: String,
+        /// Drop the restart instead.
+        #[arg(long)]
+        cancel: bool,
+    },
+    /// List the services waiting for a logout to restart.
+    LogoutRestarts,
+    /// Stop sending alerts through every route for MINUTES, e.g. during a
+    /// maintenance window. Replaces an earlier silence.
+    SilenceAlerts {
+        #[arg(value_name = "MINUTES", value_parser = clap::value_parser!(u64).range(1..))]
+        minutes: u64,
+    },
+    /// Send alerts again before the silence ends.
+    ResumeAlerts,
+    /// Show whether alerts are silenced and until when.
+    AlertSilence,
+    /// Apply the changes scheduled for this boot. Run once per boot, before
+    /// runsvdir starts, e.g. from `/etc/runit/core-services`.
+    BootTasks,
 }
 
 /// Internal enumeration of privileged actions, reused by the D-Bus service.
@@ -64,6 +614,21 @@ pub enum ActionKind {
     Once,
     Enable,
     Disable,
+    NoRestart,
+    AutoRestart,
+    SetDownFile,
+    ClearDownFile,
+    Hup,
+    Alarm,
+    Interrupt,
+    Quit,
+    Term,
+    Kill,
+    Pause,
+    Cont,
+    ForceStop,
+    ForceReload,
+    ForceRestart,
 }
 
 impl ActionKind {
@@ -77,6 +642,21 @@ impl ActionKind {
             "once" => Some(ActionKind::Once),
             "enable" => Some(ActionKind::Enable),
             "disable" => Some(ActionKind::Disable),
+            "no-restart" => Some(ActionKind::NoRestart),
+            "auto-restart" => Some(ActionKind::AutoRestart),
+            "set-down-file" => Some(ActionKind::SetDownFile),
+            "clear-down-file" => Some(ActionKind::ClearDownFile),
+            "hup" => Some(ActionKind::Hup),
+            "alarm" => Some(ActionKind::Alarm),
+            "interrupt" => Some(ActionKind::Interrupt),
+            "quit" => Some(ActionKind::Quit),
+            "term" => Some(ActionKind::Term),
+            "kill" => Some(ActionKind::Kill),
+            "pause" => Some(ActionKind::Pause),
+            "cont" => Some(ActionKind::Cont),
+            "force-stop" => Some(ActionKind::ForceStop),
+            "force-reload" => Some(ActionKind::ForceReload),
+            "force-restart" => Some(ActionKind::ForceRestart),
             _ => None,
         }
     }
@@ -91,27 +671,222 @@ impl ActionKind {
             ActionKind::Once => "once",
             ActionKind::Enable => "enable",
             ActionKind::Disable => "disable",
+            ActionKind::NoRestart => "no-restart",
+            ActionKind::AutoRestart => "auto-restart",
+            ActionKind::SetDownFile => "set-down-file",
+            ActionKind::ClearDownFile => "clear-down-file",
+            ActionKind::Hup => "hup",
+            ActionKind::Alarm => "alarm",
+            ActionKind::Interrupt => "interrupt",
+            ActionKind::Quit => "quit",
+            ActionKind::Term => "term",
+            ActionKind::Kill => "kill",
+            ActionKind::Pause => "pause",
+            ActionKind::Cont => "cont",
+            ActionKind::ForceStop => "force-stop",
+            ActionKind::ForceReload => "force-reload",
+            ActionKind::ForceRestart => "force-restart",
+        }
+    }
+
+    /// Actions that can take a service down abruptly. The D-Bus service
+    /// authorizes these with their own polkit action, which always asks for a
+    /// password.
+    pub fn is_destructive(self) -> bool {
+        matches!(
+            self,
+            ActionKind::Interrupt
+                | ActionKind::Quit
+                | ActionKind::Term
+                | ActionKind::Kill
+                | ActionKind::ForceStop
+                | ActionKind::ForceReload
+                | ActionKind::ForceRestart
+        )
+    }
+
+    /// Whether the action takes the service down or keeps it from coming
+    /// back, which protected services are guarded against. That includes the
+    /// signals that end most processes, which leave a service down once it is
+    /// not restarted.
+    pub fn takes_down(self) -> bool {
+        matches!(
+            self,
+            ActionKind::Stop
+                | ActionKind::ForceStop
+                | ActionKind::Pause
+                | ActionKind::Disable
+                | ActionKind::SetDownFile
+                | ActionKind::NoRestart
+                | ActionKind::Interrupt
+                | ActionKind::Quit
+                | ActionKind::Term
+                | ActionKind::Kill
+        )
+    }
+
+    /// The polkit action that guards this action.
+    pub fn polkit_action(self) -> &'static str {
+        match self {
+            _ if self.is_destructive() => POLKIT_ACTION_FORCE,
+            ActionKind::Start | ActionKind::Once => POLKIT_ACTION_START,
+            ActionKind::Stop => POLKIT_ACTION_STOP,
+            ActionKind::Enable
+            | ActionKind::Disable
+            | ActionKind::NoRestart
+            | ActionKind::AutoRestart
+            | ActionKind::SetDownFile
+            | ActionKind::ClearDownFile => POLKIT_ACTION_ENABLE,
+            _ => POLKIT_ACTION_RESTART,
+        }
+    }
+
+    /// The state change a caller can wait for after this action, if any.
+    pub fn state_change(self) -> Option<StateChange> {
+        match self {
+            ActionKind::Start => Some(StateChange::Start),
+            ActionKind::Stop => Some(StateChange::Stop),
+            ActionKind::Restart => Some(StateChange::Restart),
+            _ => None,
+        }
+    }
+}
+
+impl HelperCommand {
+    /// The service a command would stop, disable or delete, for the
+    /// protected-service guard. Commands that can take several services down
+    /// are guarded where they run.
+    fn protected_target(&self) -> Option<&str> {
+        match self {
+            HelperCommand::Stop { service, .. }
+            | HelperCommand::ForceStop { service }
+            | HelperCommand::Pause { service }
+            | HelperCommand::Disable { service }
+            | HelperCommand::SetDownFile { service }
+            | HelperCommand::NoRestart { service }
+            | HelperCommand::Interrupt { service }
+            | HelperCommand::Quit { service }
+            | HelperCommand::Term { service }
+            | HelperCommand::Kill { service }
+            | HelperCommand::StopAndDisable { service, .. }
+            | HelperCommand::Maintenance { service, .. }
+            | HelperCommand::DisableIn { service, .. }
+            | HelperCommand::KeepRunlevel { service, .. }
+            | HelperCommand::Trash { service }
+            | HelperCommand::Delete { service, .. } => Some(service),
+            HelperCommand::ScheduleBoot {
+                service,
+                schedule: BootSchedule::DisableAfterNextBoot | BootSchedule::DisableAtNextBoot,
+            } => Some(service),
+            HelperCommand::WriteFile { service, file, .. } if controls_supervision(file) => {
+                Some(service)
+            }
+            _ => None,
         }
     }
 }
 
+/// Files of a definition that decide whether runsv keeps the service up.
+const SUPERVISION_FILES: [&str; 4] = ["down", "run", "finish", "log/run"];
+
+/// Whether writing `file` of a definition could keep the service down, so
+/// protected services refuse it like a stop.
+fn controls_supervision(file: &str) -> bool {
+    let normalized: Vec<_> = Path::new(file)
+        .components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    SUPERVISION_FILES.contains(&normalized.join("/").as_str())
+}
+
+fn parse_service_dirs(value: &str) -> Result<(PathBuf, PathBuf), String> {
+    match value.split_once(':') {
+        Some((definitions, enabled)) if !definitions.is_empty() && !enabled.is_empty() => {
+            Ok((PathBuf::from(definitions), PathBuf::from(enabled)))
+        }
+        _ => Err("expected DEFINITIONS:ENABLED, e.g. /etc/sv-extra:/var/service-extra".into()),
+    }
+}
+
+fn parse_env_pair(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err("expected NAME=VALUE, e.g. LANG=C.UTF-8".into()),
+    }
+}
+
+fn parse_substitution(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((from, to)) if !from.is_empty() => Ok((from.to_string(), to.to_string())),
+        _ => Err("expected FROM=TO, e.g. tty1=tty7".into()),
+    }
+}
+
 fn main() {
     let cli = Cli::parse();
+    let restart_limits = RestartLimits {
+        max_unconfirmed: cli.restart_batch_limit,
+        stagger: Duration::from_millis(cli.restart_stagger_ms),
+    };
+    let manager = cli
+        .service_dirs
+        .iter()
+        .fold(
+            ServiceManager::default()
+                .with_name_policy(cli.name_policy)
+                .with_trash_retention(Duration::from_secs(
+                    cli.trash_retention_days.saturating_mul(24 * 60 * 60),
+                )),
+            |manager, (definitions, enabled)| manager.with_additional_dirs(definitions, enabled),
+        )
+        .detect_reduced_mode();
+    let protected = ProtectedServices::load(&cli.protected_config).unwrap_or_else(|err| {
+        eprintln!("runkitd: using the default protected services: {err}");
+        ProtectedServices::defaults()
+    });
 
     if cli.dbus_service {
-        if let Err(err) = dbus::run_dbus_service() {
+        let context = HelperContext::with_backend(manager.clone())
+            .with_restart_limits(restart_limits)
+            .with_protected_services(protected);
+        if let Err(err) = dbus::run_dbus_service(
+            manager,
+            context,
+            &cli.notify_config,
+            cli.replace,
+            cli.workers,
+            (cli.idle_timeout > 0).then(|| Duration::from_secs(cli.idle_timeout * 60)),
+        ) {
             eprintln!("runkitd: {err}");
             std::process::exit(1);
         }
         return;
     }
 
+    if let Some(iterations) = cli.profile_refresh {
+        let context = HelperContext::with_backend(manager);
+        match refresh_profile::profile_refresh(&context, iterations) {
+            Ok(outcome) => emit_and_exit(HelperResponse::ok_with(outcome), 0),
+            Err(err) => emit_and_exit(HelperResponse::error(err.to_string()), err.exit_code()),
+        }
+    }
+
     let Some(command) = cli.command else {
         eprintln!("runkitd: no command provided. Use --dbus-service to run as a D-Bus service.");
         std::process::exit(2);
     };
 
-    let result = execute_command(command);
+    let result = match command {
+        HelperCommand::Doctor => doctor::run(&manager),
+        command => execute_command(
+            command,
+            HelperContext::with_backend(manager)
+                .with_restart_limits(restart_limits)
+                .with_protected_services(protected),
+            cli.override_protection,
+        ),
+    };
     match result {
         Ok(outcome) => emit_and_exit(HelperResponse::ok_with(outcome), 0),
         Err(err) => {
@@ -120,157 +895,1873 @@ fn main() {
     }
 }
 
-fn execute_command(command: HelperCommand) -> Result<CommandOutcome, HelperError> {
-    let context = HelperContext::default();
+fn execute_command(
+    command: HelperCommand,
+    context: HelperContext,
+    override_protection: bool,
+) -> Result<CommandOutcome, HelperError> {
+    if let Some(service) = command.protected_target() {
+        context.guard_protected(service, override_protection)?;
+    }
     match command {
-        HelperCommand::Start { service } => context.perform_action(ActionKind::Start, &service),
-        HelperCommand::Stop { service } => context.perform_action(ActionKind::Stop, &service),
-        HelperCommand::Restart { service } => context.perform_action(ActionKind::Restart, &service),
+        HelperCommand::Start { service, wait } => {
+            context.perform_action_waiting(ActionKind::Start, &service, wait)
+        }
+        HelperCommand::Stop { service, wait } => {
+            context.perform_action_waiting(ActionKind::Stop, &service, wait)
+        }
+        HelperCommand::Restart { service, wait } => {
+            context.perform_action_waiting(ActionKind::Restart, &service, wait)
+        }
         HelperCommand::Reload { service } => context.perform_action(ActionKind::Reload, &service),
         HelperCommand::Check { service } => context.perform_action(ActionKind::Check, &service),
         HelperCommand::Once { service } => context.perform_action(ActionKind::Once, &service),
         HelperCommand::Enable { service } => context.perform_action(ActionKind::Enable, &service),
         HelperCommand::Disable { service } => context.perform_action(ActionKind::Disable, &service),
+        HelperCommand::NoRestart { service } => {
+            context.perform_action(ActionKind::NoRestart, &service)
+        }
+        HelperCommand::AutoRestart { service } => {
+            context.perform_action(ActionKind::AutoRestart, &service)
+        }
+        HelperCommand::SetDownFile { service } => {
+            context.perform_action(ActionKind::SetDownFile, &service)
+        }
+        HelperCommand::ClearDownFile { service } => {
+            context.perform_action(ActionKind::ClearDownFile, &service)
+        }
+        HelperCommand::EnableAndStart { service, wait } => {
+            context.enable_and_start(&service, Duration::from_secs(wait))
+        }
+        HelperCommand::StartWithDependencies { service, wait } => context.change_with_dependencies(
+            &service,
+            StateChange::Start,
+            Duration::from_secs(wait),
+        ),
+        HelperCommand::StopWithDependents { service, wait } => {
+            context.guard_protected_all(
+                &context.stopped_with_dependents(&service)?,
+                override_protection,
+            )?;
+            context.change_with_dependencies(&service, StateChange::Stop, Duration::from_secs(wait))
+        }
+        HelperCommand::Dependencies { service } => context.dependencies(service.as_deref()),
+        HelperCommand::Supervision => context.supervision(),
+        HelperCommand::StopAndDisable { service, wait } => {
+            context.stop_and_disable(&service, Duration::from_secs(wait))
+        }
+        HelperCommand::Maintenance { service, reason } => {
+            context.set_maintenance(&service, true, reason.as_deref())
+        }
+        HelperCommand::EndMaintenance { service } => context.set_maintenance(&service, false, None),
+        HelperCommand::SetCheckInterval { service, seconds } => {
+            context.set_check_interval(&service, seconds)
+        }
+        HelperCommand::RunCheck { service } => context.run_check(&service),
+        HelperCommand::Hup { service } => context.perform_action(ActionKind::Hup, &service),
+        HelperCommand::Alarm { service } => context.perform_action(ActionKind::Alarm, &service),
+        HelperCommand::Interrupt { service } => {
+            context.perform_action(ActionKind::Interrupt, &service)
+        }
+        HelperCommand::Quit { service } => context.perform_action(ActionKind::Quit, &service),
+        HelperCommand::Term { service } => context.perform_action(ActionKind::Term, &service),
+        HelperCommand::Kill { service } => context.perform_action(ActionKind::Kill, &service),
+        HelperCommand::Pause { service } => context.perform_action(ActionKind::Pause, &service),
+        HelperCommand::Cont { service } => context.perform_action(ActionKind::Cont, &service),
+        HelperCommand::ForceStop { service } => {
+            context.perform_action(ActionKind::ForceStop, &service)
+        }
+        HelperCommand::ForceReload { service } => {
+            context.perform_action(ActionKind::ForceReload, &service)
+        }
+        HelperCommand::ForceRestart { service } => {
+            context.perform_action(ActionKind::ForceRestart, &service)
+        }
+        HelperCommand::RestartMany { services, confirm } => {
+            context.restart_services(&services, confirm, &())
+        }
+        HelperCommand::Batch {
+            action,
+            services,
+            confirm,
+        } => match ActionKind::parse(&action) {
+            Some(kind) => context.run_batch(kind, &services, confirm, override_protection, &()),
+            None => Err(HelperError::Other(format!("Unsupported action '{action}'"))),
+        },
         HelperCommand::Describe { service } => context.describe(&service),
-        HelperCommand::List => context.list(),
-        HelperCommand::Logs { service, lines } => context.logs(&service, lines),
+        HelperCommand::List {
+            state,
+            enabled,
+            disabled,
+            search,
+            sort,
+        } => context.list(&ServiceQuery {
+            states: state,
+            enabled: (enabled || disabled).then_some(enabled),
+            text: search,
+            sort,
+        }),
+        HelperCommand::Health => context.health(),
+        HelperCommand::Runlevels => context.runlevels(),
+        HelperCommand::SwitchRunlevel { runlevel } => {
+            context.guard_protected_all(
+                &context.stopped_by_runlevel_switch(&runlevel)?,
+                override_protection,
+            )?;
+            context.switch_runlevel(&runlevel)
+        }
+        HelperCommand::EnableIn { runlevel, service } => {
+            context.set_enabled_in_runlevel(&service, &runlevel, true)
+        }
+        HelperCommand::DisableIn { runlevel, service } => {
+            context.set_enabled_in_runlevel(&service, &runlevel, false)
+        }
+        HelperCommand::KeepRunlevel { service, runlevel } => {
+            context.keep_only_in_runlevel(&service, runlevel.as_deref())
+        }
+        HelperCommand::Metrics { service } => context.metrics(&service),
+        HelperCommand::History { service, minutes } => {
+            context.history(&service, Duration::from_secs(minutes.saturating_mul(60)))
+        }
+        HelperCommand::Memory => context.memory_usage(),
+        HelperCommand::Processes { service } => context.process_tree(&service),
+        HelperCommand::Inspect { service } => context.run_script_info(&service),
+        HelperCommand::Environment { service } => context.effective_environment(&service),
+        HelperCommand::Package { service } => context.service_package(&service),
+        HelperCommand::EnvDir { service } => context.env_dir(&service),
+        HelperCommand::SetEnv {
+            service,
+            variable: (name, value),
+        } => context.set_env_variable(&service, &name, Some(&value)),
+        HelperCommand::UnsetEnv { service, name } => {
+            context.set_env_variable(&service, &name, None)
+        }
+        HelperCommand::Logs {
+            service,
+            lines,
+            timestamps,
+            subsecond,
+            follow,
+            grep,
+            regex,
+            since,
+            severity,
+        } => {
+            let format = timestamps.map(|style| TimestampFormat::new(style, subsecond));
+            let filter = LogFilter {
+                pattern: grep
+                    .map(|pattern| LogPattern::new(&pattern, regex))
+                    .transpose()?,
+                since,
+                min_severity: severity,
+            };
+            if follow {
+                context.follow_logs(&service, lines, &filter, format)
+            } else {
+                context.logs(&service, lines, &filter, format)
+            }
+        }
+        HelperCommand::Search {
+            pattern,
+            regex,
+            services,
+            limit,
+            timestamps,
+        } => {
+            let pattern = LogPattern::new(&pattern, regex)?;
+            let format = timestamps.map(|style| TimestampFormat::new(style, false));
+            context.search_logs(&pattern, &services, limit, format)
+        }
+        HelperCommand::ArchiveFailedLogs { lines, output } => {
+            let output = output
+                .or_else(default_log_archive_dir)
+                .ok_or_else(|| HelperError::Other("HOME is not set; pass --output".into()))?;
+            context.archive_failed_logs(lines, &output)
+        }
+        HelperCommand::StageLogs => context.stage_logs(),
+        HelperCommand::ServerInfo => context.server_info(),
+        HelperCommand::ProtectedServices => context.protected_services(),
+        HelperCommand::LogConfig { service } => context.log_config(&service),
+        HelperCommand::SetLogConfig {
+            service,
+            size,
+            num,
+            min,
+            timeout,
+            filters,
+            clear_filters,
+        } => {
+            let mut config = context.backend.log_config(&service)?;
+            config.size = size.or(config.size);
+            config.num = num.or(config.num);
+            config.min = min.or(config.min);
+            config.timeout = timeout.or(config.timeout);
+            if clear_filters {
+                config.filters.clear();
+            } else if !filters.is_empty() {
+                config.filters = parse_svlogd_filters(&filters)?;
+            }
+            context.set_log_config(&service, &config)
+        }
+        HelperCommand::PolkitPolicy { output, exec_path } => {
+            std::fs::write(&output, polkit_policy(&exec_path)).map_err(|err| HelperError::Io {
+                path: output.clone(),
+                source: err,
+            })?;
+            Ok(CommandOutcome::message(format!(
+                "Wrote the polkit policy to {}",
+                output.display()
+            )))
+        }
+        HelperCommand::Export { service, output } => {
+            let mut file = File::create(&output).map_err(|err| HelperError::Io {
+                path: output.clone(),
+                source: err,
+            })?;
+            context.export_definition(&service, &mut file)
+        }
+        HelperCommand::Import { archive, replace } => {
+            let mut file = File::open(&archive).map_err(|err| HelperError::Io {
+                path: archive.clone(),
+                source: err,
+            })?;
+            context.import_definition(&mut file, replace)
+        }
+        HelperCommand::ReadFile { service, file } => context.read_service_file(&service, &file),
+        HelperCommand::WriteFile {
+            service,
+            file,
+            from,
+            force,
+        } => {
+            let contents = match &from {
+                Some(path) => std::fs::read_to_string(path).map_err(|err| HelperError::Io {
+                    path: path.clone(),
+                    source: err,
+                })?,
+                None => {
+                    let mut contents = String::new();
+                    std::io::stdin()
+                        .read_to_string(&mut contents)
+                        .map_err(|err| HelperError::Io {
+                            path: PathBuf::from("<stdin>"),
+                            source: err,
+                        })?;
+                    contents
+                }
+            };
+            context.write_service_file(&service, &file, &contents, force)
+        }
+        HelperCommand::Create {
+            service,
+            command,
+            user,
+            environment,
+            logger,
+            enable,
+        } => context.create_service(
+            &ServiceTemplate {
+                name: service,
+                command,
+                user,
+                environment,
+                logger,
+            },
+            enable,
+        ),
+        HelperCommand::CreateFromBundle {
+            service,
+            bundle,
+            values,
+            enable,
+        } => {
+            let bundle = TemplateBundle::read(&bundle)?;
+            context.create_from_bundle(&bundle, &service, &values, enable)
+        }
+        HelperCommand::Instantiate {
+            source,
+            name,
+            substitutions,
+            enable,
+        } => context.instantiate_service(&source, &name, &substitutions, enable),
+        HelperCommand::Inventory => context.inventory(),
+        HelperCommand::Diff { left, right } => context.diff_inventories(&left, &right),
+        HelperCommand::SaveProfile { path, format } => {
+            context.save_profile(&path, format.unwrap_or(ProfileFormat::for_path(&path)))
+        }
+        HelperCommand::RestoreProfile { path, dry_run } => {
+            let contents = std::fs::read_to_string(&path).map_err(|err| HelperError::Io {
+                path: path.clone(),
+                source: err,
+            })?;
+            if !dry_run {
+                context.guard_protected_all(
+                    &context.taken_down_by_profile(&contents)?,
+                    override_protection,
+                )?;
+            }
+            context.restore_profile(&contents, dry_run, &())
+        }
+        HelperCommand::Trash { service } => context.trash_definition(&service),
+        HelperCommand::Delete { service, remove } => context.delete_service(&service, remove),
+        HelperCommand::ListTrash => context.list_trash(),
+        HelperCommand::Restore { id } => context.restore_from_trash(&id),
+        HelperCommand::PurgeTrash { all } => context.purge_trash(all),
+        HelperCommand::ScheduleBoot { service, schedule } => {
+            context.schedule_boot_change(&service, Some(schedule))
+        }
+        HelperCommand::CancelBootSchedule { service } => {
+            context.schedule_boot_change(&service, None)
+        }
+        HelperCommand::BootSchedule => context.boot_schedule(),
+        HelperCommand::BootReport => context.boot_report(Path::new(DEFAULT_BOOT_REPORTS), true),
+        HelperCommand::RestartAtLogout { service, cancel } => {
+            context.restart_at_logout(&service, !cancel)
+        }
+        HelperCommand::LogoutRestarts => context.logout_restarts(),
+        HelperCommand::BootTasks => context.run_boot_tasks(),
+        HelperCommand::SilenceAlerts { minutes } => context.silence_alerts(minutes),
+        HelperCommand::ResumeAlerts => context.silence_alerts(0),
+        HelperCommand::AlertSilence => context.alert_silence(),
+        // `main` runs doctor against the manager itself, before any backend.
+        HelperCommand::Doctor => Err(HelperError::Other(
+            "doctor needs the local service manager, not a backend".to_string(),
+        )),
+    }
+}
+
+/// Longest a start, stop or restart may wait for its outcome, so one call
+/// cannot hold the daemon indefinitely.
+pub const MAX_ACTION_WAIT: Duration = Duration::from_secs(300);
+
+/// Default wait for enable-and-start and stop-and-disable. runsvdir scans for
+/// new links every five seconds, so this leaves room for one scan and the
+/// start after it.
+pub const COMBINED_ACTION_WAIT: Duration = Duration::from_secs(15);
+
+/// Guard against restarting many services in one go.
+#[derive(Debug, Clone, Copy)]
+pub struct RestartLimits {
+    /// Most services a batch may restart without explicit confirmation.
+    pub max_unconfirmed: usize,
+    /// Pause between consecutive restarts of a batch.
+    pub stagger: Duration,
+}
+
+impl Default for RestartLimits {
+    fn default() -> Self {
+        RestartLimits {
+            max_unconfirmed: 5,
+            stagger: Duration::from_secs(2),
+        }
     }
 }
 
 /// Shared helper context for both CLI mode and the D-Bus service.
-#[derive(Debug)]
 pub struct HelperContext {
-    manager: ServiceManager,
+    backend: Box<dyn RunitBackend>,
+    restart_limits: RestartLimits,
+    alert_silence: AlertSilence,
+    protected: ProtectedServices,
 }
 
 impl Default for HelperContext {
     fn default() -> Self {
-        HelperContext {
-            manager: ServiceManager::default(),
-        }
+        HelperContext::with_backend(ServiceManager::default())
     }
 }
 
 impl HelperContext {
+    pub fn with_backend(backend: impl RunitBackend + 'static) -> Self {
+        HelperContext {
+            backend: Box::new(backend),
+            restart_limits: RestartLimits::default(),
+            alert_silence: AlertSilence::default(),
+            protected: ProtectedServices::defaults(),
+        }
+    }
+
+    pub fn with_restart_limits(mut self, limits: RestartLimits) -> Self {
+        self.restart_limits = limits;
+        self
+    }
+
+    pub fn with_protected_services(mut self, protected: ProtectedServices) -> Self {
+        self.protected = protected;
+        self
+    }
+
+    /// Refuse to take a protected service down unless `overridden`.
+    pub fn guard_protected(&self, service: &str, overridden: bool) -> Result<(), HelperError> {
+        if self.protected.contains(service) && !overridden {
+            return Err(HelperError::Protected(service.to_string()));
+        }
+        Ok(())
+    }
+
+    /// [`HelperContext::guard_protected`] for each of `services`.
+    pub fn guard_protected_all(
+        &self,
+        services: &[String],
+        overridden: bool,
+    ) -> Result<(), HelperError> {
+        services
+            .iter()
+            .try_for_each(|service| self.guard_protected(service, overridden))
+    }
+
+    /// `service` and every service stopping it with its dependents stops.
+    pub fn stopped_with_dependents(&self, service: &str) -> Result<Vec<String>, HelperError> {
+        Ok(self.backend.dependency_graph()?.stop_order(service)?)
+    }
+
+    /// Services linked into the current runlevel but not into `runlevel`,
+    /// which runsvdir stops on switching to it.
+    pub fn stopped_by_runlevel_switch(&self, runlevel: &str) -> Result<Vec<String>, HelperError> {
+        let current = self.backend.current_runlevel();
+        Ok(self
+            .backend
+            .enabled_runlevels()?
+            .into_iter()
+            .filter(|(_, runlevels)| {
+                runlevels.contains(&current) && !runlevels.iter().any(|name| name == runlevel)
+            })
+            .map(|(service, _)| service)
+            .collect())
+    }
+
+    /// Services restoring the profile in `contents` would disable or give a
+    /// `down` file.
+    pub fn taken_down_by_profile(&self, contents: &str) -> Result<Vec<String>, HelperError> {
+        let changes = self.backend.profile_changes(&profile::parse(contents)?)?;
+        Ok(changes
+            .disable
+            .into_iter()
+            .chain(changes.add_down_file)
+            .collect())
+    }
+
+    /// runkitd's version, interface and response versions and capabilities.
+    pub fn server_info(&self) -> Result<CommandOutcome, HelperError> {
+        let info = ServerInfo::current(env!("CARGO_PKG_VERSION"));
+        Ok(CommandOutcome::with(None, Some(json!(info))))
+    }
+
+    pub fn protected_services(&self) -> Result<CommandOutcome, HelperError> {
+        Ok(CommandOutcome::with(
+            None,
+            Some(json!(self.protected.names())),
+        ))
+    }
+
+    /// Restart `services` one after another, pausing between them. Batches over
+    /// the limit are refused unless `confirmed`; a failed restart does not stop
+    /// the rest, cancelling through `progress` does.
+    pub fn restart_services(
+        &self,
+        services: &[String],
+        confirmed: bool,
+        progress: &dyn Progress,
+    ) -> Result<CommandOutcome, HelperError> {
+        let batch = unique_services(services);
+        if batch.is_empty() {
+            return Err(HelperError::Other("no services to restart".to_string()));
+        }
+        let limit = self.restart_limits.max_unconfirmed;
+        if batch.len() > limit && !confirmed {
+            return Err(HelperError::ConfirmationRequired {
+                count: batch.len(),
+                limit,
+            });
+        }
+
+        let mut results = Vec::new();
+        let mut failed = 0;
+        for (index, service) in batch.iter().enumerate() {
+            if index > 0 {
+                thread::sleep(self.restart_limits.stagger);
+            }
+            if progress.cancelled() {
+                break;
+            }
+            progress.report(index, batch.len(), &format!("Restarting {service}"));
+            let (ok, message) = match self.backend.restart(service) {
+                Ok(output) => (true, sv_outcome("restart", service, output).message),
+                Err(err) => {
+                    failed += 1;
+                    (false, Some(HelperError::from(err).to_string()))
+                }
+            };
+            results.push(json!({ "service": service, "ok": ok, "message": message }));
+        }
+        Ok(batch_outcome("Restarted", batch.len(), failed, results))
+    }
+
+    /// Run a start, stop, enable or disable on each of `services` in turn, or
+    /// restart them as [`HelperContext::restart_services`] does. Stop and
+    /// disable skip protected services unless `override_protection` is set;
+    /// like any other failure, that does not stop the rest, while cancelling
+    /// through `progress` does.
+    pub fn run_batch(
+        &self,
+        action: ActionKind,
+        services: &[String],
+        confirmed: bool,
+        override_protection: bool,
+        progress: &dyn Progress,
+    ) -> Result<CommandOutcome, HelperError> {
+        let (doing, done) = match action {
+            ActionKind::Restart => {
+                return self.restart_services(services, confirmed, progress);
+            }
+            ActionKind::Start => ("Starting", "Started"),
+            ActionKind::Stop => ("Stopping", "Stopped"),
+            ActionKind::Enable => ("Enabling", "Enabled"),
+            ActionKind::Disable => ("Disabling", "Disabled"),
+            other => {
+                return Err(HelperError::Other(format!(
+                    "Cannot {} several services at once",
+                    other.as_str()
+                )));
+            }
+        };
+        let batch = unique_services(services);
+        if batch.is_empty() {
+            return Err(HelperError::Other(format!(
+                "no services to {}",
+                action.as_str()
+            )));
+        }
+
+        let mut results = Vec::new();
+        let mut failed = 0;
+        for (index, service) in batch.iter().enumerate() {
+            if progress.cancelled() {
+                break;
+            }
+            progress.report(index, batch.len(), &format!("{doing} {service}"));
+            let outcome = if action.takes_down() {
+                self.guard_protected(service, override_protection)
+                    .and_then(|()| self.perform_action(action, service))
+            } else {
+                self.perform_action(action, service)
+            };
+            let (ok, message) = match outcome {
+                Ok(outcome) => (true, outcome.message),
+                Err(err) => {
+                    failed += 1;
+                    (false, Some(err.to_string()))
+                }
+            };
+            results.push(json!({ "service": service, "ok": ok, "message": message }));
+        }
+        Ok(batch_outcome(done, batch.len(), failed, results))
+    }
+
     pub fn perform_action(
         &self,
         action: ActionKind,
         service: &str,
     ) -> Result<CommandOutcome, HelperError> {
         match action {
-            ActionKind::Start => self.call_sv("up", service),
+            ActionKind::Start => {
+                let output = self.backend.start(service)?;
+                Ok(sv_outcome("start", service, output))
+            }
             ActionKind::Stop => self.call_sv("down", service),
-            ActionKind::Restart => self.call_sv("restart", service),
+            ActionKind::Restart => {
+                let output = self.backend.restart(service)?;
+                Ok(sv_outcome("restart", service, output))
+            }
             ActionKind::Reload => self.call_sv("reload", service),
             ActionKind::Check => self.call_sv("check", service),
             ActionKind::Once => self.call_sv("once", service),
-            ActionKind::Enable => self.enable(service),
-            ActionKind::Disable => self.disable(service),
+            ActionKind::Enable => {
+                self.backend.enable(service)?;
+                Ok(CommandOutcome::message(format!(
+                    "Enabled service {service}"
+                )))
+            }
+            ActionKind::Disable => {
+                self.backend.disable(service)?;
+                Ok(CommandOutcome::message(format!(
+                    "Disabled service {service}"
+                )))
+            }
+            ActionKind::NoRestart => self.set_restart_policy(service, RestartPolicy::Never),
+            ActionKind::AutoRestart => self.set_restart_policy(service, RestartPolicy::Always),
+            ActionKind::SetDownFile => {
+                self.backend.set_down_file(service)?;
+                Ok(CommandOutcome::message(format!(
+                    "{service} will not be started at boot"
+                )))
+            }
+            ActionKind::ClearDownFile => {
+                self.backend.clear_down_file(service)?;
+                Ok(CommandOutcome::message(format!(
+                    "{service} will be started at boot"
+                )))
+            }
+            ActionKind::Hup
+            | ActionKind::Alarm
+            | ActionKind::Interrupt
+            | ActionKind::Quit
+            | ActionKind::Term
+            | ActionKind::Kill
+            | ActionKind::Pause
+            | ActionKind::Cont
+            | ActionKind::ForceStop
+            | ActionKind::ForceReload
+            | ActionKind::ForceRestart => self.call_sv(action.as_str(), service),
+        }
+    }
+
+    /// [`HelperContext::perform_action`], then with `wait_secs` wait for the
+    /// service to get there.
+    fn perform_action_waiting(
+        &self,
+        action: ActionKind,
+        service: &str,
+        wait_secs: Option<u64>,
+    ) -> Result<CommandOutcome, HelperError> {
+        match wait_secs {
+            Some(secs) => self.perform_action_and_wait(action, service, Duration::from_secs(secs)),
+            None => self.perform_action(action, service),
+        }
+    }
+
+    /// Start, stop or restart a service and wait up to `timeout` (at most
+    /// [`MAX_ACTION_WAIT`]) for it to be running or down. A service that does
+    /// not get there, e.g. because it crashes right after starting, is an error
+    /// naming the state it was last seen in.
+    pub fn perform_action_and_wait(
+        &self,
+        action: ActionKind,
+        service: &str,
+        timeout: Duration,
+    ) -> Result<CommandOutcome, HelperError> {
+        let Some(change) = action.state_change() else {
+            return Err(HelperError::Other(format!(
+                "cannot wait for the result of {}",
+                action.as_str()
+            )));
+        };
+        let (_, outcome) =
+            self.backend
+                .change_and_wait(service, change, timeout.min(MAX_ACTION_WAIT))?;
+        let state = describe_state(&outcome.state);
+        if !outcome.reached {
+            return Err(HelperError::StateNotReached {
+                service: service.to_string(),
+                target: change.target_name(),
+                waited: format_duration(outcome.waited),
+                state,
+            });
         }
+        Ok(CommandOutcome::with(
+            Some(format!("{service} is {state}")),
+            Some(json!({
+                "service": service,
+                "state": outcome.state.state_name(),
+                "pid": outcome.state.pid(),
+                "waited_ms": outcome.waited.as_millis() as u64,
+            })),
+        ))
     }
 
-    pub fn list(&self) -> Result<CommandOutcome, HelperError> {
-        let services = self.manager.list_services()?;
+    /// Enable `service` and start it now, undoing the enable if it does not
+    /// come up within `timeout`.
+    pub fn enable_and_start(
+        &self,
+        service: &str,
+        timeout: Duration,
+    ) -> Result<CommandOutcome, HelperError> {
+        let outcome = self
+            .backend
+            .enable_and_start(service, timeout.min(MAX_ACTION_WAIT))?;
+        combined_outcome(service, StateChange::Start, outcome)
+    }
+
+    /// Stop `service` and disable it, starting it again if it does not go down
+    /// within `timeout`.
+    pub fn stop_and_disable(
+        &self,
+        service: &str,
+        timeout: Duration,
+    ) -> Result<CommandOutcome, HelperError> {
+        let outcome = self
+            .backend
+            .stop_and_disable(service, timeout.min(MAX_ACTION_WAIT))?;
+        combined_outcome(service, StateChange::Stop, outcome)
+    }
+
+    /// Start `service` after its dependencies, or stop it after its
+    /// dependents, waiting up to `timeout` for each.
+    pub fn change_with_dependencies(
+        &self,
+        service: &str,
+        change: StateChange,
+        timeout: Duration,
+    ) -> Result<CommandOutcome, HelperError> {
+        let timeout = timeout.min(MAX_ACTION_WAIT);
+        let outcomes = match change {
+            StateChange::Stop => self.backend.stop_with_dependents(service, timeout)?,
+            _ => self.backend.start_with_dependencies(service, timeout)?,
+        };
+        if let Some((name, outcome)) = outcomes.last()
+            && !outcome.reached
+        {
+            return Err(HelperError::StateNotReached {
+                service: name.clone(),
+                target: change.target_name(),
+                waited: format_duration(outcome.waited),
+                state: describe_state(&outcome.state),
+            });
+        }
+        let changed: Vec<&str> = outcomes.iter().map(|(name, _)| name.as_str()).collect();
+        let message = match (changed.is_empty(), change) {
+            (true, _) => format!(
+                "Nothing to do; every service was already {}",
+                change.target_name()
+            ),
+            (false, StateChange::Stop) => format!("Stopped {}", changed.join(", ")),
+            (false, _) => format!("Started {}", changed.join(", ")),
+        };
+        Ok(CommandOutcome::with(
+            Some(message),
+            Some(json!({ "changed": changed })),
+        ))
+    }
+
+    /// Whether runsvdir supervises the services, or why runkitd is in
+    /// reduced mode.
+    pub fn supervision(&self) -> Result<CommandOutcome, HelperError> {
+        let confinement = self.backend.reduced_mode();
+        let message = match &confinement {
+            Some(confinement) => format!(
+                "Reduced mode: no runsvdir supervises services in {confinement}. Definitions \
+                 can be browsed and edited, but there is no live status or control."
+            ),
+            None => "Services are supervised by runsvdir".to_string(),
+        };
+        Ok(CommandOutcome::with(
+            Some(message),
+            Some(json!({
+                "reduced": confinement.is_some(),
+                "confinement": confinement,
+            })),
+        ))
+    }
+
+    /// The dependency graph, or one service's place in it.
+    pub fn dependencies(&self, service: Option<&str>) -> Result<CommandOutcome, HelperError> {
+        let graph = self.backend.dependency_graph()?;
+        let cycles = graph.cycles();
+        let Some(service) = service else {
+            let message = (!cycles.is_empty()).then(|| {
+                let cycles: Vec<String> = cycles.iter().map(|cycle| cycle.join(" -> ")).collect();
+                format!("Dependency cycles: {}", cycles.join("; "))
+            });
+            return Ok(CommandOutcome::with(
+                message,
+                Some(json!({
+                    "dependencies": graph.dependencies,
+                    "cycles": cycles,
+                })),
+            ));
+        };
+        let start_order = graph.start_order(service)?;
+        Ok(CommandOutcome::with(
+            None,
+            Some(json!({
+                "service": service,
+                "dependencies": graph.dependencies_of(service),
+                "dependents": graph.dependents_of(service),
+                "start_order": start_order,
+            })),
+        ))
+    }
+
+    /// Put `service` into maintenance, or with `enabled` false take it out.
+    pub fn set_maintenance(
+        &self,
+        service: &str,
+        enabled: bool,
+        reason: Option<&str>,
+    ) -> Result<CommandOutcome, HelperError> {
+        if enabled {
+            let maintenance = self.backend.begin_maintenance(service, reason)?;
+            return Ok(CommandOutcome::with(
+                Some(format!("{service} is in maintenance")),
+                Some(json!(MaintenanceSnapshot::from(&maintenance))),
+            ));
+        }
+        let ended = self.backend.end_maintenance(service)?;
+        let message = match &ended {
+            Some(maintenance) if maintenance.was_running => {
+                format!("{service} is out of maintenance and was started again")
+            }
+            Some(_) => format!("{service} is out of maintenance"),
+            None => format!("{service} was not in maintenance"),
+        };
+        Ok(CommandOutcome::message(message))
+    }
+
+    /// Run `service`'s `./check` every `seconds` while it runs, or with 0 stop.
+    pub fn set_check_interval(
+        &self,
+        service: &str,
+        seconds: u64,
+    ) -> Result<CommandOutcome, HelperError> {
+        let interval = (seconds > 0).then(|| Duration::from_secs(seconds));
+        self.backend.set_check_interval(service, interval)?;
+        Ok(CommandOutcome::message(match interval {
+            Some(interval) => format!("{service} is checked every {}", format_duration(interval)),
+            None => format!("{service} is no longer checked"),
+        }))
+    }
+
+    pub fn run_check(&self, service: &str) -> Result<CommandOutcome, HelperError> {
+        let result = self.backend.run_check(service)?;
+        let verdict = if result.passed { "passed" } else { "failed" };
+        Ok(CommandOutcome::with(
+            Some(format!("{service} check {verdict}: {}", result.detail)),
+            Some(json!(CheckSnapshot::from(&result))),
+        ))
+    }
+
+    /// Silence every alert route for `minutes`, or with 0 lift the silence.
+    pub fn silence_alerts(&self, minutes: u64) -> Result<CommandOutcome, HelperError> {
+        let io_error = |source| HelperError::Io {
+            path: self.alert_silence.path().to_path_buf(),
+            source,
+        };
+        if minutes == 0 {
+            let lifted = self.alert_silence.lift().map_err(io_error)?;
+            return Ok(CommandOutcome::with(
+                Some(if lifted {
+                    "Alerts are sent again".to_string()
+                } else {
+                    "Alerts were not silenced".to_string()
+                }),
+                Some(json!({ "silenced_until": null })),
+            ));
+        }
+        let duration = Duration::from_secs(minutes.saturating_mul(60));
+        let until = self.alert_silence.silence_for(duration).map_err(io_error)?;
+        Ok(CommandOutcome::with(
+            Some(format!(
+                "Alerts are silenced for {}",
+                format_duration(duration)
+            )),
+            Some(json!({ "silenced_until": until })),
+        ))
+    }
+
+    pub fn alert_silence(&self) -> Result<CommandOutcome, HelperError> {
+        let until = self.alert_silence.until();
+        let message = match until {
+            Some(until) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_secs())
+                    .unwrap_or_default();
+                format!(
+                    "Alerts are silenced for another {}",
+                    format_duration(Duration::from_secs(until.saturating_sub(now)))
+                )
+            }
+            None => "Alerts are being sent".to_string(),
+        };
+        Ok(CommandOutcome::with(
+            Some(message),
+            Some(json!({ "silenced_until": until })),
+        ))
+    }
+
+    pub fn list(&self, query: &ServiceQuery) -> Result<CommandOutcome, HelperError> {
+        let services = self.backend.list_services_matching(query)?;
         let snapshots: Vec<ServiceSnapshot> = services.iter().map(ServiceSnapshot::from).collect();
+        let _span = span("serialize:snapshots");
+        let data =
+            serde_json::to_value(snapshots).map_err(|err| HelperError::Other(err.to_string()))?;
+        Ok(CommandOutcome::with(None, Some(data)))
+    }
+
+    pub fn logs(
+        &self,
+        service: &str,
+        lines: usize,
+        filter: &LogFilter,
+        timestamps: Option<TimestampFormat>,
+    ) -> Result<CommandOutcome, HelperError> {
+        let entries = self.backend.tail_logs_filtered(service, lines, filter)?;
+        let snapshots: Vec<LogEntrySnapshot> = entries
+            .into_iter()
+            .map(|entry| LogEntrySnapshot::rendered(entry, timestamps))
+            .collect();
+        let data =
+            serde_json::to_value(snapshots).map_err(|err| HelperError::Other(err.to_string()))?;
+        Ok(CommandOutcome::with(None, Some(data)))
+    }
+
+    pub fn search_logs(
+        &self,
+        pattern: &LogPattern,
+        services: &[String],
+        limit: usize,
+        timestamps: Option<TimestampFormat>,
+    ) -> Result<CommandOutcome, HelperError> {
+        let matches = self.backend.search_logs(pattern, services, limit)?;
+        let snapshots: Vec<LogMatchSnapshot> = matches
+            .into_iter()
+            .map(|found| LogMatchSnapshot {
+                service: found.service,
+                entry: LogEntrySnapshot::rendered(found.entry, timestamps),
+            })
+            .collect();
         let data =
             serde_json::to_value(snapshots).map_err(|err| HelperError::Other(err.to_string()))?;
         Ok(CommandOutcome::with(None, Some(data)))
     }
 
-    pub fn logs(&self, service: &str, lines: usize) -> Result<CommandOutcome, HelperError> {
-        let entries = self.manager.tail_logs(service, lines)?;
-        let snapshots: Vec<LogEntrySnapshot> =
-            entries.into_iter().map(LogEntrySnapshot::from).collect();
+    pub fn stage_logs(&self) -> Result<CommandOutcome, HelperError> {
+        let logs = self.backend.stage_logs()?;
+        Ok(CommandOutcome::with(None, Some(json!(logs))))
+    }
+
+    /// The last `lines` entries of each failed service's log.
+    pub fn failed_logs(&self, lines: usize) -> Result<CommandOutcome, HelperError> {
+        let logs = self.backend.failed_service_logs(lines)?;
+        let message = format!("{} failed service(s)", logs.len());
+        let data: Vec<_> = logs
+            .into_iter()
+            .map(|logs| {
+                let entries: Vec<LogEntrySnapshot> = logs
+                    .entries
+                    .into_iter()
+                    .map(LogEntrySnapshot::from)
+                    .collect();
+                json!({ "service": logs.service, "entries": entries })
+            })
+            .collect();
+        Ok(CommandOutcome::with(Some(message), Some(json!(data))))
+    }
+
+    pub fn archive_failed_logs(
+        &self,
+        lines: usize,
+        dir: &Path,
+    ) -> Result<CommandOutcome, HelperError> {
+        let logs = self.backend.failed_service_logs(lines)?;
+        if logs.is_empty() {
+            return Ok(CommandOutcome::with(
+                Some("No service has failed".to_string()),
+                Some(json!({ "archive": null, "services": [] })),
+            ));
+        }
+        let archive = write_log_archive(dir, &logs)?;
+        let services: Vec<&str> = logs.iter().map(|logs| logs.service.as_str()).collect();
+        Ok(CommandOutcome::with(
+            Some(format!(
+                "Archived the logs of {} failed service(s) to {}",
+                services.len(),
+                archive.display()
+            )),
+            Some(json!({ "archive": archive, "services": services })),
+        ))
+    }
+
+    /// Print the last `lines` entries and then every new one as it is written,
+    /// as newline-delimited JSON. Only returns when following fails.
+    pub fn follow_logs(
+        &self,
+        service: &str,
+        lines: usize,
+        filter: &LogFilter,
+        timestamps: Option<TimestampFormat>,
+    ) -> Result<CommandOutcome, HelperError> {
+        let follower = self.backend.follow_logs(service)?;
+        let history = self.backend.tail_logs_filtered(service, lines, filter)?;
+        let mut stdout = std::io::stdout().lock();
+        for entry in history.into_iter().map(Ok).chain(follower) {
+            let entry = entry?;
+            if !filter.matches(&entry) {
+                continue;
+            }
+            let snapshot = LogEntrySnapshot::rendered(entry, timestamps);
+            let line = serde_json::to_string(&snapshot)
+                .map_err(|err| HelperError::Other(err.to_string()))?;
+            writeln!(stdout, "{line}")
+                .and_then(|_| stdout.flush())
+                .map_err(|err| HelperError::Other(err.to_string()))?;
+        }
+        Ok(CommandOutcome::message("log stream ended"))
+    }
+
+    pub fn health(&self) -> Result<CommandOutcome, HelperError> {
+        let health = self.backend.check_health()?;
+        let issues: Vec<HealthIssueSnapshot> = health
+            .issues
+            .iter()
+            .map(HealthIssueSnapshot::from)
+            .collect();
+        let message = (!health.is_healthy())
+            .then(|| format!("{} service problem(s) found", health.issues.len()));
+        let data =
+            serde_json::to_value(issues).map_err(|err| HelperError::Other(err.to_string()))?;
+        Ok(CommandOutcome::with(message, Some(data)))
+    }
+
+    pub fn keep_only_in_runlevel(
+        &self,
+        service: &str,
+        runlevel: Option<&str>,
+    ) -> Result<CommandOutcome, HelperError> {
+        let removed = self.backend.keep_only_in_runlevel(service, runlevel)?;
+        let message = if removed.is_empty() {
+            format!("{service} has no duplicate enable links")
+        } else {
+            format!("Removed {} duplicate link(s) for {service}", removed.len())
+        };
+        Ok(CommandOutcome::with(
+            Some(message),
+            Some(json!({ "service": service, "removed": removed })),
+        ))
+    }
+
+    pub fn runlevels(&self) -> Result<CommandOutcome, HelperError> {
+        let current = self.backend.current_runlevel();
+        let enabled = self.backend.enabled_runlevels()?;
+        let runlevels: Vec<_> = self
+            .backend
+            .runlevels()
+            .into_iter()
+            .map(|(name, path)| {
+                let services: Vec<&String> = enabled
+                    .iter()
+                    .filter(|(_, runlevels)| runlevels.contains(&name))
+                    .map(|(service, _)| service)
+                    .collect();
+                json!({
+                    "current": name == current,
+                    "name": name,
+                    "path": path,
+                    "services": services,
+                })
+            })
+            .collect();
+        Ok(CommandOutcome::with(
+            Some(format!("Current runlevel: {current}")),
+            Some(json!({ "current": current, "runlevels": runlevels })),
+        ))
+    }
+
+    pub fn switch_runlevel(&self, runlevel: &str) -> Result<CommandOutcome, HelperError> {
+        self.backend.switch_runlevel(runlevel)?;
+        Ok(CommandOutcome::message(format!(
+            "Switched to runlevel {runlevel}"
+        )))
+    }
+
+    pub fn set_enabled_in_runlevel(
+        &self,
+        service: &str,
+        runlevel: &str,
+        enabled: bool,
+    ) -> Result<CommandOutcome, HelperError> {
+        self.backend
+            .set_enabled_in_runlevel(service, runlevel, enabled)?;
+        Ok(CommandOutcome::message(if enabled {
+            format!("Enabled {service} in runlevel {runlevel}")
+        } else {
+            format!("Disabled {service} in runlevel {runlevel}")
+        }))
+    }
+
+    pub fn describe(&self, service: &str) -> Result<CommandOutcome, HelperError> {
+        let description = self.backend.describe_service(service)?;
+        let data = json!({
+            "service": service,
+            "description": description.as_ref().map(|description| &description.text),
+            "source": description.as_ref().map(|description| description.source),
+            "package": description.and_then(|description| description.package),
+        });
+        Ok(CommandOutcome::with(None, Some(data)))
+    }
+
+    pub fn export_definition(
+        &self,
+        service: &str,
+        writer: &mut dyn Write,
+    ) -> Result<CommandOutcome, HelperError> {
+        let bytes = self.backend.export_definition(service, writer)?;
+        Ok(CommandOutcome::with(
+            Some(format!("Exported service {service}")),
+            Some(json!({ "service": service, "bytes": bytes })),
+        ))
+    }
+
+    pub fn import_definition(
+        &self,
+        reader: &mut dyn Read,
+        replace: bool,
+    ) -> Result<CommandOutcome, HelperError> {
+        let service = self.backend.import_definition(reader, replace)?;
+        Ok(CommandOutcome::with(
+            Some(format!("Imported service {service}")),
+            Some(json!({ "service": service })),
+        ))
+    }
+
+    pub fn create_from_bundle(
+        &self,
+        bundle: &TemplateBundle,
+        service: &str,
+        values: &[(String, String)],
+        enable: bool,
+    ) -> Result<CommandOutcome, HelperError> {
+        let path = self
+            .backend
+            .create_from_bundle(bundle, service, values, enable)?;
+        let message = if enable {
+            format!("Created and enabled service {service} from {}", bundle.name)
+        } else {
+            format!("Created service {service} from {}", bundle.name)
+        };
+        Ok(CommandOutcome::with(
+            Some(message),
+            Some(json!({
+                "service": service,
+                "definition_path": path.to_string_lossy(),
+                "enabled": enable,
+            })),
+        ))
+    }
+
+    pub fn create_service(
+        &self,
+        template: &ServiceTemplate,
+        enable: bool,
+    ) -> Result<CommandOutcome, HelperError> {
+        let path = self.backend.create_service(template, enable)?;
+        let service = &template.name;
+        let message = if enable {
+            format!("Created and enabled service {service}")
+        } else {
+            format!("Created service {service}")
+        };
+        Ok(CommandOutcome::with(
+            Some(message),
+            Some(json!({
+                "service": service,
+                "definition_path": path.to_string_lossy(),
+                "enabled": enable,
+            })),
+        ))
+    }
+
+    pub fn read_service_file(
+        &self,
+        service: &str,
+        file: &str,
+    ) -> Result<CommandOutcome, HelperError> {
+        let read = self.backend.read_service_file(service, file)?;
+        Ok(CommandOutcome::with(
+            None,
+            Some(json!({
+                "path": read.path.to_string_lossy(),
+                "contents": read.contents,
+                "mode": read.mode,
+            })),
+        ))
+    }
+
+    pub fn write_service_file(
+        &self,
+        service: &str,
+        file: &str,
+        contents: &str,
+        force: bool,
+    ) -> Result<CommandOutcome, HelperError> {
+        let written = self
+            .backend
+            .write_service_file(service, file, contents, force)?;
+        let mut message = format!("Saved {file} of {service}");
+        for warning in &written.warnings {
+            message.push_str(&format!("; warning: {warning}"));
+        }
+        Ok(CommandOutcome::with(
+            Some(message),
+            Some(json!({
+                "path": written.path.to_string_lossy(),
+                "backup": written.backup.map(|backup| backup.to_string_lossy().to_string()),
+                "warnings": written.warnings,
+            })),
+        ))
+    }
+
+    pub fn instantiate_service(
+        &self,
+        source: &str,
+        name: &str,
+        substitutions: &[(String, String)],
+        enable: bool,
+    ) -> Result<CommandOutcome, HelperError> {
+        let path = self
+            .backend
+            .instantiate_service(source, name, substitutions, enable)?;
+        let message = if enable {
+            format!("Created and enabled {name} from {source}")
+        } else {
+            format!("Created {name} from {source}")
+        };
+        Ok(CommandOutcome::with(
+            Some(message),
+            Some(json!({
+                "service": name,
+                "source": source,
+                "definition_path": path.to_string_lossy(),
+                "enabled": enable,
+            })),
+        ))
+    }
+
+    pub fn metrics(&self, service: &str) -> Result<CommandOutcome, HelperError> {
+        match self.backend.service_metrics(service)? {
+            Some(metrics) => Ok(CommandOutcome::with(
+                None,
+                Some(json!(MetricsSnapshot::from(&metrics))),
+            )),
+            None => Ok(CommandOutcome::message(format!(
+                "Service {service} is not running"
+            ))),
+        }
+    }
+
+    /// What happened to `service` within the last `window`, with a summary
+    /// such as "sshd crashed 3 times in the last 1h".
+    pub fn history(&self, service: &str, window: Duration) -> Result<CommandOutcome, HelperError> {
+        let history = self.backend.service_history(service, window)?;
+        let mut message = format!(
+            "{service} crashed {} time(s) and restarted {} time(s) in the last {}",
+            history.crashes,
+            history.restarts,
+            format_duration(window)
+        );
+        if history.flapping {
+            message.push_str("; it is flapping");
+        }
+        Ok(CommandOutcome::with(
+            Some(message),
+            Some(json!(HistorySnapshot::new(&history, window))),
+        ))
+    }
+
+    pub fn memory_usage(&self) -> Result<CommandOutcome, HelperError> {
+        let usage = self.backend.memory_usage()?;
+        Ok(CommandOutcome::with(None, Some(json!(usage))))
+    }
+
+    pub fn process_tree(&self, service: &str) -> Result<CommandOutcome, HelperError> {
+        match self.backend.process_tree(service)? {
+            Some(tree) => Ok(CommandOutcome::with(
+                None,
+                Some(json!(ProcessSnapshot::from(&tree))),
+            )),
+            None => Ok(CommandOutcome::message(format!(
+                "Service {service} is not running"
+            ))),
+        }
+    }
+
+    pub fn run_script_info(&self, service: &str) -> Result<CommandOutcome, HelperError> {
+        let info = self.backend.run_script_info(service)?;
+        Ok(CommandOutcome::with(
+            None,
+            Some(json!(RunScriptSnapshot::from(&info))),
+        ))
+    }
+
+    pub fn service_package(&self, service: &str) -> Result<CommandOutcome, HelperError> {
+        let package = self.backend.service_package(service)?;
+        let message = match &package {
+            Some(package) => format!("{service} is installed by {package}"),
+            None => format!("{service} does not belong to a package"),
+        };
+        Ok(CommandOutcome::with(
+            Some(message),
+            Some(json!({ "package": package })),
+        ))
+    }
+
+    pub fn effective_environment(&self, service: &str) -> Result<CommandOutcome, HelperError> {
+        let environment = self.backend.effective_environment(service)?;
+        let snapshot = EnvironmentSnapshot::from(&environment);
+        let message = match (&environment.running, environment.discrepancies.len()) {
+            (None, _) => None,
+            (Some(_), 0) => Some(format!("{service} runs with the expected environment")),
+            (Some(_), count) => Some(format!(
+                "{count} variable(s) differ from what {service} should start with"
+            )),
+        };
+        Ok(CommandOutcome::with(message, Some(json!(snapshot))))
+    }
+
+    pub fn env_dir(&self, service: &str) -> Result<CommandOutcome, HelperError> {
+        let dir = self.backend.env_dir(service)?;
+        let message = (!dir.used_by_run_script).then(|| {
+            format!(
+                "The run script of {service} does not load {}",
+                dir.path.display()
+            )
+        });
+        Ok(CommandOutcome::with(
+            message,
+            Some(json!(EnvDirSnapshot::from(&dir))),
+        ))
+    }
+
+    /// Set `name` in the service's env dir to `value`, or with `None` remove it.
+    pub fn set_env_variable(
+        &self,
+        service: &str,
+        name: &str,
+        value: Option<&str>,
+    ) -> Result<CommandOutcome, HelperError> {
+        let dir = match value {
+            Some(value) => self.backend.set_env_variable(service, name, value)?,
+            None => self.backend.unset_env_variable(service, name)?,
+        };
+        let mut message = match value {
+            Some(_) => format!("Set {name} for {service}"),
+            None => format!("Removed {name} from {service}'s env dir"),
+        };
+        if dir.used_by_run_script {
+            message.push_str("; restart the service to apply it");
+        } else {
+            message.push_str(&format!(
+                "; note that the run script does not load {}",
+                dir.path.display()
+            ));
+        }
+        Ok(CommandOutcome::with(
+            Some(message),
+            Some(json!(EnvDirSnapshot::from(&dir))),
+        ))
+    }
+
+    pub fn inventory(&self) -> Result<CommandOutcome, HelperError> {
+        let inventory = self.backend.inventory()?;
+        Ok(CommandOutcome::with(None, Some(json!(inventory))))
+    }
+
+    pub fn diff_inventories(&self, left: &str, right: &str) -> Result<CommandOutcome, HelperError> {
+        let left_inventory = self.load_inventory(left)?;
+        let right_inventory = self.load_inventory(right)?;
+        let diff = left_inventory.diff(&right_inventory);
+        let label = |source: &str, inventory: &Inventory| {
+            inventory
+                .hostname
+                .as_ref()
+                .map_or_else(|| source.to_string(), |host| format!("{source} ({host})"))
+        };
+        let differences = diff.only_left.len()
+            + diff.only_right.len()
+            + diff.enabled_differs.len()
+            + diff.run_script_differs.len();
+        let message = if diff.is_empty() {
+            "No differences".to_string()
+        } else {
+            format!("{differences} difference(s)")
+        };
+        let enabled_differs: Vec<Value> = diff
+            .enabled_differs
+            .iter()
+            .map(|difference| {
+                json!({
+                    "service": difference.service,
+                    "left": difference.left,
+                    "right": difference.right,
+                })
+            })
+            .collect();
+        Ok(CommandOutcome::with(
+            Some(message),
+            Some(json!({
+                "identical": diff.is_empty(),
+                "left": label(left, &left_inventory),
+                "right": label(right, &right_inventory),
+                "only_left": diff.only_left,
+                "only_right": diff.only_right,
+                "enabled_differs": enabled_differs,
+                "run_script_differs": diff.run_script_differs,
+            })),
+        ))
+    }
+
+    /// The current profile, rendered as `format` in `contents`.
+    pub fn profile(&self, format: ProfileFormat) -> Result<CommandOutcome, HelperError> {
+        let profile = self.backend.profile()?;
+        let contents = profile::render(&profile, format)?;
+        Ok(CommandOutcome::with(
+            None,
+            Some(json!({
+                "format": format.as_str(),
+                "contents": contents,
+                "profile": profile,
+            })),
+        ))
+    }
+
+    pub fn save_profile(
+        &self,
+        path: &Path,
+        format: ProfileFormat,
+    ) -> Result<CommandOutcome, HelperError> {
+        let profile = self.backend.profile()?;
+        let contents = profile::render(&profile, format)?;
+        std::fs::write(path, contents).map_err(|err| HelperError::Io {
+            path: path.to_path_buf(),
+            source: err,
+        })?;
+        Ok(CommandOutcome::with(
+            Some(format!(
+                "Saved the profile of {} service(s) to {}",
+                profile.services.len(),
+                path.display()
+            )),
+            Some(json!({
+                "path": path,
+                "format": format.as_str(),
+                "profile": profile,
+            })),
+        ))
+    }
+
+    /// Apply a profile given as JSON or TOML, or with `dry_run` only report
+    /// what applying it would change.
+    pub fn restore_profile(
+        &self,
+        contents: &str,
+        dry_run: bool,
+        progress: &dyn Progress,
+    ) -> Result<CommandOutcome, HelperError> {
+        let profile = profile::parse(contents)?;
+        if dry_run {
+            let changes = self.backend.profile_changes(&profile)?;
+            return Ok(CommandOutcome::with(
+                Some(describe_profile_changes(&changes, true)),
+                Some(profile_changes_json(&changes)),
+            ));
+        }
+        let restore = self.backend.restore_profile(&profile, &mut |done, total| {
+            progress.report(done, total, "Restoring profile");
+            !progress.cancelled()
+        })?;
+        let message = describe_profile_changes(&restore.applied, false);
+        if !restore.failed.is_empty() {
+            let failed: Vec<String> = restore
+                .failed
+                .iter()
+                .map(|(service, error)| format!("{service}: {error}"))
+                .collect();
+            return Err(HelperError::Other(format!(
+                "{message}; could not change {}",
+                failed.join("; ")
+            )));
+        }
+        Ok(CommandOutcome::with(
+            Some(message),
+            Some(profile_changes_json(&restore.applied)),
+        ))
+    }
+
+    /// `local` for this machine, otherwise a file holding either the bare
+    /// inventory or the full `runkitd inventory` response.
+    fn load_inventory(&self, source: &str) -> Result<Inventory, HelperError> {
+        if source == "local" {
+            return Ok(self.backend.inventory()?);
+        }
+        let path = PathBuf::from(source);
+        let contents = std::fs::read_to_string(&path).map_err(|err| HelperError::Io {
+            path: path.clone(),
+            source: err,
+        })?;
+        let mut value: Value = serde_json::from_str(&contents)
+            .map_err(|err| HelperError::Other(format!("{source}: {err}")))?;
+        if let Some(data) = value.get_mut("data") {
+            value = data.take();
+        }
+        serde_json::from_value(value)
+            .map_err(|err| HelperError::Other(format!("{source} is not an inventory: {err}")))
+    }
+
+    pub fn log_config(&self, service: &str) -> Result<CommandOutcome, HelperError> {
+        let config = self.backend.log_config(service)?;
+        Ok(CommandOutcome::with(
+            None,
+            Some(json!(LogConfigSnapshot::from(&config))),
+        ))
+    }
+
+    pub fn set_log_config(
+        &self,
+        service: &str,
+        config: &SvlogdConfig,
+    ) -> Result<CommandOutcome, HelperError> {
+        self.backend.set_log_config(service, config)?;
+        Ok(CommandOutcome::with(
+            Some(format!("Updated log settings for {service}")),
+            Some(json!(LogConfigSnapshot::from(config))),
+        ))
+    }
+
+    pub fn trash_definition(&self, service: &str) -> Result<CommandOutcome, HelperError> {
+        let entry = self.backend.trash_definition(service)?;
+        Ok(CommandOutcome::with(
+            Some(format!("Moved {service} to the trash")),
+            Some(json!(TrashEntrySnapshot::from(&entry))),
+        ))
+    }
+
+    /// Delete `service`, into the trash unless `remove` is set.
+    pub fn delete_service(
+        &self,
+        service: &str,
+        remove: bool,
+    ) -> Result<CommandOutcome, HelperError> {
+        let mode = if remove {
+            DeleteMode::Remove
+        } else {
+            DeleteMode::Trash
+        };
+        let deleted = self.backend.delete_service(service, mode)?;
+        let message = match &deleted.trash_entry {
+            Some(_) => format!("Deleted {service}; it can be restored from the trash"),
+            None => format!("Deleted {service} for good"),
+        };
+        Ok(CommandOutcome::with(
+            Some(message),
+            Some(json!({
+                "service": deleted.service,
+                "unlinked": deleted
+                    .unlinked
+                    .iter()
+                    .map(|link| link.to_string_lossy().to_string())
+                    .collect::<Vec<_>>(),
+                "trash_entry": deleted.trash_entry.as_ref().map(TrashEntrySnapshot::from),
+            })),
+        ))
+    }
+
+    pub fn list_trash(&self) -> Result<CommandOutcome, HelperError> {
+        let entries = self.backend.list_trash()?;
+        let snapshots: Vec<TrashEntrySnapshot> =
+            entries.iter().map(TrashEntrySnapshot::from).collect();
+        let data =
+            serde_json::to_value(snapshots).map_err(|err| HelperError::Other(err.to_string()))?;
+        Ok(CommandOutcome::with(None, Some(data)))
+    }
+
+    pub fn restore_from_trash(&self, id: &str) -> Result<CommandOutcome, HelperError> {
+        let service = self.backend.restore_from_trash(id)?;
+        Ok(CommandOutcome::with(
+            Some(format!("Restored service {service}")),
+            Some(json!({ "service": service })),
+        ))
+    }
+
+    pub fn purge_trash(&self, everything: bool) -> Result<CommandOutcome, HelperError> {
+        let purged = self.backend.purge_trash(everything)?;
+        let snapshots: Vec<TrashEntrySnapshot> =
+            purged.iter().map(TrashEntrySnapshot::from).collect();
+        let data =
+            serde_json::to_value(snapshots).map_err(|err| HelperError::Other(err.to_string()))?;
+        Ok(CommandOutcome::with(
+            Some(format!("Purged {} trashed definition(s)", purged.len())),
+            Some(data),
+        ))
+    }
+
+    pub fn schedule_boot_change(
+        &self,
+        service: &str,
+        schedule: Option<BootSchedule>,
+    ) -> Result<CommandOutcome, HelperError> {
+        let change = self.backend.schedule_boot_change(service, schedule)?;
+        let message = match schedule {
+            Some(BootSchedule::EnableNextBootOnly) => {
+                format!("{service} will be enabled for the next boot only")
+            }
+            Some(BootSchedule::DisableAfterNextBoot) => {
+                format!("{service} will be disabled after the next boot")
+            }
+            Some(BootSchedule::DisableAtNextBoot) => {
+                format!("{service} will be disabled at the next boot")
+            }
+            None => format!("Cancelled the boot schedule for {service}"),
+        };
+        Ok(CommandOutcome::with(
+            Some(message),
+            Some(json!(change.as_ref().map(BootChangeSnapshot::from))),
+        ))
+    }
+
+    pub fn boot_schedule(&self) -> Result<CommandOutcome, HelperError> {
+        let changes = self.backend.boot_schedule()?;
+        let snapshots: Vec<BootChangeSnapshot> =
+            changes.iter().map(BootChangeSnapshot::from).collect();
         let data =
             serde_json::to_value(snapshots).map_err(|err| HelperError::Other(err.to_string()))?;
         Ok(CommandOutcome::with(None, Some(data)))
     }
 
-    pub fn describe(&self, service: &str) -> Result<CommandOutcome, HelperError> {
-        let description = self.manager.service_description(service)?;
-        let data = json!({
-            "service": service,
-            "description": description,
-        });
-        Ok(CommandOutcome::with(None, Some(data)))
+    /// Add this boot to the history at `history` and compare it with the
+    /// boots before.
+    /// Compare this boot's service start times with the history at `history`,
+    /// adding this boot to the file only when `record` is set.
+    pub fn boot_report(&self, history: &Path, record: bool) -> Result<CommandOutcome, HelperError> {
+        let report = self.backend.boot_report()?.ok_or_else(|| {
+            HelperError::Other("/proc does not say when the system booted".to_string())
+        })?;
+        let boot_id = report.boot_id.clone();
+        let services = report.services.len();
+        let history = if record {
+            record_boot_report(history, report)?
+        } else {
+            merge_boot_report(read_boot_reports(history)?, report)
+        };
+        let regressions = boot_regressions(&history);
+        let message = match (history.len(), regressions.len()) {
+            (1, _) => {
+                "This is the first recorded boot; there is nothing to compare with yet".to_string()
+            }
+            (boots, 0) => format!(
+                "No service came up markedly later than in the last {} boot(s)",
+                boots - 1
+            ),
+            (_, slower) => format!("{slower} service(s) came up markedly later than usual"),
+        };
+        Ok(CommandOutcome::with(
+            Some(message),
+            Some(json!({
+                "boot_id": boot_id,
+                "services": services,
+                "boots": history.len(),
+                "regressions": regressions,
+            })),
+        ))
     }
 
-    fn call_sv(&self, subcommand: &str, service: &str) -> Result<CommandOutcome, HelperError> {
-        self.manager.validate_service_name(service)?;
-        let mut command = Command::new(self.manager.sv_command_path());
-        command.arg(subcommand).arg(service);
+    pub fn restart_at_logout(
+        &self,
+        service: &str,
+        scheduled: bool,
+    ) -> Result<CommandOutcome, HelperError> {
+        if !scheduled {
+            self.backend.schedule_restart_at_logout(service, false)?;
+            return Ok(CommandOutcome::message(format!(
+                "{service} will no longer restart at logout"
+            )));
+        }
+        if graphical_user_sessions(Path::new(DEFAULT_SESSIONS_DIR))?.is_none() {
+            return Err(HelperError::Other(format!(
+                "{DEFAULT_SESSIONS_DIR} does not exist, so the logout cannot be noticed; \
+                 is elogind running?"
+            )));
+        }
+        self.backend.schedule_restart_at_logout(service, true)?;
+        let mut message = format!("{service} will restart once the last graphical session ends");
+        if let Some(reason) = session_critical_reason(service) {
+            message = format!("{message} ({reason})");
+        }
+        Ok(CommandOutcome::message(message))
+    }
 
-        let output = command.output().map_err(|err| HelperError::Io {
-            path: self.manager.sv_command_path().to_path_buf(),
-            source: err,
-        })?;
+    pub fn logout_restarts(&self) -> Result<CommandOutcome, HelperError> {
+        let services = self.backend.logout_restarts()?;
+        let message =
+            (!services.is_empty()).then(|| format!("Waiting for logout: {}", services.join(", ")));
+        Ok(CommandOutcome::with(message, Some(json!(services))))
+    }
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-            return Err(HelperError::SvFailure {
-                command: subcommand.to_string(),
-                service: service.to_string(),
-                message: if stderr.is_empty() {
-                    format!("exit status {}", output.status)
-                } else {
-                    stderr
-                },
-            });
+    pub fn run_boot_tasks(&self) -> Result<CommandOutcome, HelperError> {
+        let report = self.backend.run_boot_tasks()?;
+        let mut message = format!(
+            "Enabled {} and disabled {} service(s) for this boot",
+            report.enabled.len(),
+            report.disabled.len()
+        );
+        if !report.failed.is_empty() {
+            message.push_str(&format!(
+                "; {} change(s) failed and will be retried",
+                report.failed.len()
+            ));
         }
+        let failed: Vec<Value> = report
+            .failed
+            .iter()
+            .map(|(service, error)| json!({ "service": service, "error": error }))
+            .collect();
+        Ok(CommandOutcome::with(
+            Some(message),
+            Some(json!({
+                "enabled": report.enabled,
+                "disabled": report.disabled,
+                "failed": failed,
+            })),
+        ))
+    }
 
-        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        Ok(CommandOutcome::message(if stdout.is_empty() {
-            format!("{subcommand} command executed for {service}")
-        } else {
-            stdout
+    fn set_restart_policy(
+        &self,
+        service: &str,
+        policy: RestartPolicy,
+    ) -> Result<CommandOutcome, HelperError> {
+        self.backend.apply_restart_policy(service, policy)?;
+        Ok(CommandOutcome::message(match policy {
+            RestartPolicy::Never => format!("{service} will stay down after it exits"),
+            RestartPolicy::Always => format!("{service} will be restarted when it exits"),
         }))
     }
 
-    fn enable(&self, service: &str) -> Result<CommandOutcome, HelperError> {
-        self.manager.validate_service_name(service)?;
-        let src = self.manager.definitions_dir().join(service);
-        if !src.exists() {
-            return Err(HelperError::DefinitionMissing {
-                service: service.to_string(),
-                path: src,
-            });
-        }
-
-        let dest = self.manager.enabled_dir().join(service);
-        if dest.exists() {
-            return Err(HelperError::AlreadyEnabled(service.to_string()));
-        }
+    fn call_sv(&self, subcommand: &str, service: &str) -> Result<CommandOutcome, HelperError> {
+        let output = self.backend.control(service, subcommand)?;
+        Ok(sv_outcome(subcommand, service, output))
+    }
+}
 
-        std::os::unix::fs::symlink(&src, &dest).map_err(|err| HelperError::Io {
-            path: dest.clone(),
-            source: err,
-        })?;
+/// Response for [`HelperContext::enable_and_start`] and
+/// [`HelperContext::stop_and_disable`].
+fn combined_outcome(
+    service: &str,
+    change: StateChange,
+    outcome: WaitOutcome,
+) -> Result<CommandOutcome, HelperError> {
+    let state = describe_state(&outcome.state);
+    if !outcome.reached {
+        return Err(HelperError::ChangeRolledBack {
+            service: service.to_string(),
+            target: change.target_name(),
+            waited: format_duration(outcome.waited),
+            state,
+        });
+    }
+    let message = match change {
+        StateChange::Stop => format!("{service} is disabled and {state}"),
+        _ => format!("{service} is enabled and {state}"),
+    };
+    Ok(CommandOutcome::with(
+        Some(message),
+        Some(json!({
+            "service": service,
+            "enabled": change != StateChange::Stop,
+            "state": outcome.state.state_name(),
+            "pid": outcome.state.pid(),
+            "waited_ms": outcome.waited.as_millis() as u64,
+        })),
+    ))
+}
 
-        Ok(CommandOutcome::message(format!(
-            "Enabled service {service}"
-        )))
+/// `Enabled sshd; disabled cron` or, for a dry run, `Would enable sshd; ...`.
+fn describe_profile_changes(changes: &ProfileChanges, dry_run: bool) -> String {
+    let groups = [
+        (&changes.enable, "enable", "enabled"),
+        (&changes.disable, "disable", "disabled"),
+        (
+            &changes.add_down_file,
+            "add a down file to",
+            "added a down file to",
+        ),
+        (
+            &changes.remove_down_file,
+            "remove the down file from",
+            "removed the down file from",
+        ),
+    ];
+    let mut parts: Vec<String> = groups
+        .iter()
+        .filter(|(services, _, _)| !services.is_empty())
+        .map(|(services, future, past)| {
+            let verb = if dry_run { future } else { past };
+            format!("{verb} {}", services.join(", "))
+        })
+        .collect();
+    if parts.is_empty() {
+        parts.push("nothing to change".to_string());
+    } else if dry_run {
+        parts[0] = format!("would {}", parts[0]);
     }
+    if !changes.missing.is_empty() {
+        parts.push(format!(
+            "skipped {} (not defined here)",
+            changes.missing.join(", ")
+        ));
+    }
+    let mut message = parts.join("; ");
+    message[..1].make_ascii_uppercase();
+    message
+}
+
+fn profile_changes_json(changes: &ProfileChanges) -> Value {
+    json!({
+        "enable": changes.enable,
+        "disable": changes.disable,
+        "add_down_file": changes.add_down_file,
+        "remove_down_file": changes.remove_down_file,
+        "missing": changes.missing,
+    })
+}
 
-    fn disable(&self, service: &str) -> Result<CommandOutcome, HelperError> {
-        self.manager.validate_service_name(service)?;
-        let dest = self.manager.enabled_dir().join(service);
-        if !dest.exists() {
-            return Err(HelperError::NotEnabled(service.to_string()));
+/// E.g. "running (pid 42) for 3s" or "failed with exit code 1".
+fn describe_state(state: &ServiceRuntimeState) -> String {
+    match state {
+        ServiceRuntimeState::Running { pid, uptime } => {
+            format!("running (pid {pid}) for {}", format_duration(*uptime))
         }
+        ServiceRuntimeState::Down { since, .. } => format!("down for {}", format_duration(*since)),
+        ServiceRuntimeState::Failed { exit_code, .. } => {
+            format!("failed with exit code {exit_code}")
+        }
+        ServiceRuntimeState::Unknown { raw } => format!("in an unknown state ({raw})"),
+    }
+}
 
-        std::fs::remove_file(&dest).map_err(|err| HelperError::Io {
-            path: dest.clone(),
-            source: err,
-        })?;
+fn sv_outcome(subcommand: &str, service: &str, output: String) -> CommandOutcome {
+    CommandOutcome::message(if output.is_empty() {
+        format!("{subcommand} command executed for {service}")
+    } else {
+        output
+    })
+}
 
-        Ok(CommandOutcome::message(format!(
-            "Disabled service {service}"
-        )))
+/// `services` without repeats, in the order given.
+fn unique_services(services: &[String]) -> Vec<&str> {
+    let mut unique: Vec<&str> = Vec::new();
+    for service in services {
+        if !unique.contains(&service.as_str()) {
+            unique.push(service);
+        }
     }
+    unique
+}
+
+/// A batch's summary, with one `{service, ok, message}` result per service.
+fn batch_outcome(done: &str, total: usize, failed: usize, results: Vec<Value>) -> CommandOutcome {
+    let message = match failed {
+        0 => format!("{done} {total} service(s)"),
+        failed => format!(
+            "{done} {} of {total} services; {failed} failed",
+            total - failed
+        ),
+    };
+    CommandOutcome::with(Some(message), Some(json!(results)))
 }
 
 #[derive(Debug, Serialize)]
 pub struct HelperResponse {
+    schema_version: u32,
     status: ResponseStatus,
     message: Option<String>,
     data: Option<Value>,
@@ -279,6 +2770,7 @@ pub struct HelperResponse {
 impl HelperResponse {
     pub fn ok_with(outcome: CommandOutcome) -> Self {
         Self {
+            schema_version: SCHEMA_VERSION,
             status: ResponseStatus::Ok,
             message: outcome.message,
             data: outcome.data,
@@ -287,6 +2779,7 @@ impl HelperResponse {
 
     pub fn error(message: impl Into<String>) -> Self {
         Self {
+            schema_version: SCHEMA_VERSION,
             status: ResponseStatus::Error,
             message: Some(message.into()),
             data: None,
@@ -330,6 +2823,51 @@ pub enum HelperError {
     AlreadyEnabled(String),
     #[error("service is not enabled: {0}")]
     NotEnabled(String),
+    #[error("service definition already exists: {0}")]
+    DefinitionExists(String),
+    #[error("invalid service archive: {0}")]
+    InvalidArchive(String),
+    #[error("unknown runlevel: {0}")]
+    UnknownRunlevel(String),
+    #[error("invalid log pattern: {0}")]
+    InvalidPattern(String),
+    #[error("no trashed definition with id {0}")]
+    NotInTrash(String),
+    #[error("invalid svlogd config: {0}")]
+    InvalidLogConfig(String),
+    #[error("invalid service template: {0}")]
+    InvalidTemplate(String),
+    #[error("cannot edit service file {0}")]
+    InvalidServiceFile(String),
+    #[error("shell syntax error in {file}: {message}")]
+    ScriptSyntax { file: String, message: String },
+    #[error("{service} belongs to the {package} package; remove it with xbps-remove")]
+    PackageOwned { service: String, package: String },
+    #[error("dependency cycle: {}", .0.join(" -> "))]
+    DependencyCycle(Vec<String>),
+    #[error("no runsvdir supervises services in {0}; live status and control are unavailable")]
+    Unsupervised(String),
+    #[error("restarting {count} services at once needs confirmation (limit {limit})")]
+    ConfirmationRequired { count: usize, limit: usize },
+    #[error(
+        "{0} is protected: without it you may be unable to log in or manage the system; \
+         override the protection to stop or disable it anyway"
+    )]
+    Protected(String),
+    #[error("{service} was not {target} after {waited}; it is {state}")]
+    StateNotReached {
+        service: String,
+        target: &'static str,
+        waited: String,
+        state: String,
+    },
+    #[error("{service} was not {target} after {waited}, so the change was undone; it is {state}")]
+    ChangeRolledBack {
+        service: String,
+        target: &'static str,
+        waited: String,
+        state: String,
+    },
     #[error("command `{command}` failed for {service}: {message}")]
     SvFailure {
         command: String,
@@ -355,6 +2893,22 @@ impl HelperError {
             HelperError::NotEnabled(_) => 5,
             HelperError::SvFailure { .. } => 6,
             HelperError::Io { .. } => 7,
+            HelperError::DefinitionExists(_) => 8,
+            HelperError::InvalidArchive(_) => 9,
+            HelperError::UnknownRunlevel(_) => 10,
+            HelperError::InvalidPattern(_) => 11,
+            HelperError::NotInTrash(_) => 12,
+            HelperError::InvalidLogConfig(_) => 13,
+            HelperError::ConfirmationRequired { .. } => 14,
+            HelperError::StateNotReached { .. } => 15,
+            HelperError::ChangeRolledBack { .. } => 16,
+            HelperError::InvalidTemplate(_) => 17,
+            HelperError::InvalidServiceFile(_) => 18,
+            HelperError::ScriptSyntax { .. } => 19,
+            HelperError::PackageOwned { .. } => 20,
+            HelperError::DependencyCycle(_) => 21,
+            HelperError::Unsupervised(_) => 22,
+            HelperError::Protected(_) => 23,
             HelperError::Other(_) => 1,
         }
     }
@@ -365,14 +2919,39 @@ impl From<ServiceError> for HelperError {
         match value {
             ServiceError::InvalidServiceName(name) => HelperError::InvalidService(name),
             ServiceError::Io { path, source } => HelperError::Io { path, source },
-            ServiceError::SvCommand { service, message } => HelperError::SvFailure {
-                command: "status".to_string(),
+            ServiceError::SvCommand {
+                command,
+                service,
+                message,
+            } => HelperError::SvFailure {
+                command,
                 service,
                 message,
             },
             ServiceError::LogUnavailable(service) => {
                 HelperError::Other(format!("log stream unavailable for {service}"))
             }
+            ServiceError::DefinitionMissing { service, path } => {
+                HelperError::DefinitionMissing { service, path }
+            }
+            ServiceError::DefinitionExists(service) => HelperError::DefinitionExists(service),
+            ServiceError::AlreadyEnabled(service) => HelperError::AlreadyEnabled(service),
+            ServiceError::NotEnabled(service) => HelperError::NotEnabled(service),
+            ServiceError::InvalidArchive(message) => HelperError::InvalidArchive(message),
+            ServiceError::UnknownRunlevel(runlevel) => HelperError::UnknownRunlevel(runlevel),
+            ServiceError::InvalidPattern(message) => HelperError::InvalidPattern(message),
+            ServiceError::NotInTrash(id) => HelperError::NotInTrash(id),
+            ServiceError::InvalidLogConfig(message) => HelperError::InvalidLogConfig(message),
+            ServiceError::InvalidTemplate(message) => HelperError::InvalidTemplate(message),
+            ServiceError::InvalidServiceFile(message) => HelperError::InvalidServiceFile(message),
+            ServiceError::ScriptSyntax { file, message } => {
+                HelperError::ScriptSyntax { file, message }
+            }
+            ServiceError::PackageOwned { service, package } => {
+                HelperError::PackageOwned { service, package }
+            }
+            ServiceError::DependencyCycle(cycle) => HelperError::DependencyCycle(cycle),
+            ServiceError::Unsupervised(confinement) => HelperError::Unsupervised(confinement),
             ServiceError::Other(err) => HelperError::Other(err.to_string()),
         }
     }
@@ -385,7 +2964,17 @@ struct ServiceSnapshot {
     enabled: bool,
     desired_state: SnapshotDesiredState,
     runtime_state: SnapshotRuntimeState,
+    restart_policy: SnapshotRestartPolicy,
+    has_down_file: bool,
+    defunct_pids: Vec<u32>,
+    waiting_for: Vec<String>,
+    io: Option<SnapshotIoUsage>,
     description: Option<String>,
+    runlevels: Vec<String>,
+    maintenance: Option<MaintenanceSnapshot>,
+    supervisor_diagnostic: Option<SupervisorDiagnosticSnapshot>,
+    check_interval_seconds: Option<u64>,
+    last_check: Option<CheckSnapshot>,
 }
 
 impl From<&ServiceInfo> for ServiceSnapshot {
@@ -396,7 +2985,71 @@ impl From<&ServiceInfo> for ServiceSnapshot {
             enabled: info.enabled,
             desired_state: SnapshotDesiredState::from(info.desired_state),
             runtime_state: SnapshotRuntimeState::from(&info.runtime_state),
+            restart_policy: SnapshotRestartPolicy::from(info.restart_policy),
+            has_down_file: info.has_down_file,
+            defunct_pids: info.defunct_pids.clone(),
+            waiting_for: info.waiting_for.clone(),
+            io: info.io.map(SnapshotIoUsage::from),
             description: info.description.clone(),
+            runlevels: info.runlevels.clone(),
+            maintenance: info.maintenance.as_ref().map(MaintenanceSnapshot::from),
+            supervisor_diagnostic: info
+                .supervisor_diagnostic
+                .as_ref()
+                .map(SupervisorDiagnosticSnapshot::from),
+            check_interval_seconds: info.check_interval.map(|interval| interval.as_secs()),
+            last_check: info.last_check.as_ref().map(CheckSnapshot::from),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CheckSnapshot {
+    at: i64,
+    passed: bool,
+    detail: String,
+}
+
+impl From<&CheckResult> for CheckSnapshot {
+    fn from(result: &CheckResult) -> Self {
+        CheckSnapshot {
+            at: result.at,
+            passed: result.passed,
+            detail: result.detail.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct MaintenanceSnapshot {
+    since: i64,
+    reason: Option<String>,
+    was_running: bool,
+    added_down_file: bool,
+}
+
+impl From<&Maintenance> for MaintenanceSnapshot {
+    fn from(maintenance: &Maintenance) -> Self {
+        MaintenanceSnapshot {
+            since: maintenance.since,
+            reason: maintenance.reason.clone(),
+            was_running: maintenance.was_running,
+            added_down_file: maintenance.added_down_file,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SupervisorDiagnosticSnapshot {
+    message: String,
+    fatal: bool,
+}
+
+impl From<&SupervisorDiagnostic> for SupervisorDiagnosticSnapshot {
+    fn from(diagnostic: &SupervisorDiagnostic) -> Self {
+        SupervisorDiagnosticSnapshot {
+            message: diagnostic.message.clone(),
+            fatal: diagnostic.fatal,
         }
     }
 }
@@ -417,6 +3070,49 @@ impl From<DesiredState> for SnapshotDesiredState {
     }
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum SnapshotRestartPolicy {
+    Always,
+    Never,
+}
+
+impl From<RestartPolicy> for SnapshotRestartPolicy {
+    fn from(value: RestartPolicy) -> Self {
+        match value {
+            RestartPolicy::Always => SnapshotRestartPolicy::Always,
+            RestartPolicy::Never => SnapshotRestartPolicy::Never,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SnapshotIoUsage {
+    read_bytes: u64,
+    write_bytes: u64,
+    source: SnapshotIoSource,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum SnapshotIoSource {
+    Processes,
+    Cgroup,
+}
+
+impl From<IoUsage> for SnapshotIoUsage {
+    fn from(value: IoUsage) -> Self {
+        SnapshotIoUsage {
+            read_bytes: value.read_bytes,
+            write_bytes: value.write_bytes,
+            source: match value.source {
+                IoSource::Processes => SnapshotIoSource::Processes,
+                IoSource::Cgroup => SnapshotIoSource::Cgroup,
+            },
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 #[serde(tag = "state", rename_all = "snake_case")]
 enum SnapshotRuntimeState {
@@ -465,20 +3161,344 @@ impl From<&ServiceRuntimeState> for SnapshotRuntimeState {
     }
 }
 
+#[derive(Debug, Serialize)]
+struct HealthIssueSnapshot {
+    service: String,
+    kind: &'static str,
+    path: String,
+    detail: String,
+}
+
+impl From<&HealthIssue> for HealthIssueSnapshot {
+    fn from(issue: &HealthIssue) -> Self {
+        HealthIssueSnapshot {
+            service: issue.service.clone(),
+            kind: issue.kind.as_str(),
+            path: issue.path.to_string_lossy().to_string(),
+            detail: issue.detail.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct MetricsSnapshot {
+    pid: u32,
+    cpu_percent: f64,
+    rss_bytes: u64,
+    open_fds: Option<u64>,
+    threads: u64,
+    processes: u32,
+}
+
+impl From<&ServiceMetrics> for MetricsSnapshot {
+    fn from(metrics: &ServiceMetrics) -> Self {
+        MetricsSnapshot {
+            pid: metrics.pid,
+            // Two decimals are plenty and keep the JSON readable.
+            cpu_percent: (metrics.cpu_percent * 100.0).round() / 100.0,
+            rss_bytes: metrics.rss_bytes,
+            open_fds: metrics.open_fds,
+            threads: metrics.threads,
+            processes: metrics.processes,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct HistorySnapshot {
+    window_seconds: u64,
+    restarts: usize,
+    crashes: usize,
+    last_exit_codes: Vec<i32>,
+    flapping: bool,
+    events: Vec<HistoryEventSnapshot>,
+}
+
+impl HistorySnapshot {
+    fn new(history: &ServiceHistory, window: Duration) -> Self {
+        HistorySnapshot {
+            window_seconds: window.as_secs(),
+            restarts: history.restarts,
+            crashes: history.crashes,
+            last_exit_codes: history.last_exit_codes.clone(),
+            flapping: history.flapping,
+            events: history
+                .events
+                .iter()
+                .map(HistoryEventSnapshot::from)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct HistoryEventSnapshot {
+    at: i64,
+    kind: &'static str,
+    pid: Option<u32>,
+    exit_code: Option<i32>,
+}
+
+impl From<&HistoryEvent> for HistoryEventSnapshot {
+    fn from(event: &HistoryEvent) -> Self {
+        HistoryEventSnapshot {
+            at: event.at,
+            kind: event.kind.as_str(),
+            pid: event.pid,
+            exit_code: event.exit_code,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ProcessSnapshot {
+    pid: u32,
+    comm: String,
+    cmdline: Vec<String>,
+    children: Vec<ProcessSnapshot>,
+}
+
+impl From<&ProcessNode> for ProcessSnapshot {
+    fn from(node: &ProcessNode) -> Self {
+        ProcessSnapshot {
+            pid: node.pid,
+            comm: node.comm.clone(),
+            cmdline: node.cmdline.clone(),
+            children: node.children.iter().map(ProcessSnapshot::from).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct RunScriptSnapshot {
+    binary: Option<String>,
+    args: Vec<String>,
+    user: Option<String>,
+    group: Option<String>,
+    config_file: Option<String>,
+    env_dir: Option<String>,
+}
+
+impl From<&RunScriptInfo> for RunScriptSnapshot {
+    fn from(info: &RunScriptInfo) -> Self {
+        let display =
+            |path: &Option<PathBuf>| path.as_ref().map(|path| path.to_string_lossy().to_string());
+        RunScriptSnapshot {
+            binary: info.binary.clone(),
+            args: info.args.clone(),
+            user: info.user.clone(),
+            group: info.group.clone(),
+            config_file: display(&info.config_file),
+            env_dir: display(&info.env_dir),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct EnvironmentSnapshot {
+    variables: Vec<EnvVariableSnapshot>,
+    /// Whether the running process's environment could be read; false when the
+    /// service is down.
+    compared: bool,
+    discrepancies: Vec<EnvDiscrepancySnapshot>,
+}
+
+#[derive(Debug, Serialize)]
+struct EnvVariableSnapshot {
+    name: String,
+    /// Null when an empty env dir file unsets the variable.
+    value: Option<String>,
+    /// `env_dir` or `conf`.
+    source: &'static str,
+    path: String,
+    /// The value uses shell expansion and is not compared.
+    dynamic: bool,
+}
+
+impl From<&EnvVariable> for EnvVariableSnapshot {
+    fn from(variable: &EnvVariable) -> Self {
+        let (source, path) = match &variable.source {
+            EnvSource::EnvDir(path) => ("env_dir", path),
+            EnvSource::ConfFile(path) => ("conf", path),
+        };
+        EnvVariableSnapshot {
+            name: variable.name.clone(),
+            value: variable.value.clone(),
+            source,
+            path: path.to_string_lossy().to_string(),
+            dynamic: variable.dynamic,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct EnvDirSnapshot {
+    path: String,
+    used_by_run_script: bool,
+    variables: Vec<EnvVariableSnapshot>,
+}
+
+impl From<&EnvDir> for EnvDirSnapshot {
+    fn from(dir: &EnvDir) -> Self {
+        EnvDirSnapshot {
+            path: dir.path.to_string_lossy().to_string(),
+            used_by_run_script: dir.used_by_run_script,
+            variables: dir
+                .variables
+                .iter()
+                .map(EnvVariableSnapshot::from)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct EnvDiscrepancySnapshot {
+    name: String,
+    expected: Option<String>,
+    actual: Option<String>,
+}
+
+impl From<&EffectiveEnvironment> for EnvironmentSnapshot {
+    fn from(environment: &EffectiveEnvironment) -> Self {
+        EnvironmentSnapshot {
+            variables: environment
+                .variables
+                .iter()
+                .map(EnvVariableSnapshot::from)
+                .collect(),
+            compared: environment.running.is_some(),
+            discrepancies: environment
+                .discrepancies
+                .iter()
+                .map(|discrepancy| EnvDiscrepancySnapshot {
+                    name: discrepancy.name.clone(),
+                    expected: discrepancy.expected.clone(),
+                    actual: discrepancy.actual.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct LogConfigSnapshot {
+    size: Option<u64>,
+    num: Option<u32>,
+    min: Option<u32>,
+    timeout: Option<u64>,
+    processor: Option<String>,
+    prefix: Option<String>,
+    /// svlogd filter lines in order, e.g. `-*debug*`.
+    filters: Vec<String>,
+}
+
+impl From<&SvlogdConfig> for LogConfigSnapshot {
+    fn from(config: &SvlogdConfig) -> Self {
+        LogConfigSnapshot {
+            size: config.size,
+            num: config.num,
+            min: config.min,
+            timeout: config.timeout,
+            processor: config.processor.clone(),
+            prefix: config.prefix.clone(),
+            filters: config.filters.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+/// Parse svlogd filter lines, refusing anything that is not `+`, `-`, `e` or `E`.
+fn parse_svlogd_filters(lines: &[String]) -> Result<Vec<SvlogdFilter>, HelperError> {
+    lines
+        .iter()
+        .map(|line| {
+            SvlogdFilter::parse(line).ok_or_else(|| {
+                HelperError::InvalidLogConfig(format!(
+                    "{line:?} is not a filter (expected +, -, e or E followed by a pattern)"
+                ))
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+struct TrashEntrySnapshot {
+    id: String,
+    service: String,
+    original_path: String,
+    deleted_at: i64,
+    was_enabled: bool,
+}
+
+impl From<&TrashEntry> for TrashEntrySnapshot {
+    fn from(entry: &TrashEntry) -> Self {
+        TrashEntrySnapshot {
+            id: entry.id.clone(),
+            service: entry.service.clone(),
+            original_path: entry.original_path.to_string_lossy().to_string(),
+            deleted_at: entry.deleted_at,
+            was_enabled: entry.was_enabled,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct BootChangeSnapshot {
+    service: String,
+    schedule: &'static str,
+    enabled_dir: String,
+}
+
+impl From<&ScheduledBootChange> for BootChangeSnapshot {
+    fn from(change: &ScheduledBootChange) -> Self {
+        BootChangeSnapshot {
+            service: change.service.clone(),
+            schedule: change.schedule.as_str(),
+            enabled_dir: change.enabled_dir.to_string_lossy().to_string(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 struct LogEntrySnapshot {
     unix_seconds: Option<i64>,
     nanos: Option<u32>,
     raw: Option<String>,
+    /// RFC 3339 rendering of the decoded label, always in UTC.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    utc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<String>,
+    severity: &'static str,
     message: String,
 }
 
+#[derive(Debug, Serialize)]
+struct LogMatchSnapshot {
+    service: String,
+    #[serde(flatten)]
+    entry: LogEntrySnapshot,
+}
+
+impl LogEntrySnapshot {
+    fn rendered(entry: ServiceLogEntry, timestamps: Option<TimestampFormat>) -> Self {
+        let timestamp = timestamps.and_then(|format| entry.format_timestamp(format));
+        LogEntrySnapshot {
+            timestamp,
+            ..LogEntrySnapshot::from(entry)
+        }
+    }
+}
+
 impl From<ServiceLogEntry> for LogEntrySnapshot {
     fn from(entry: ServiceLogEntry) -> Self {
         LogEntrySnapshot {
+            utc: entry.utc_datetime().map(|datetime| datetime.to_rfc3339()),
+            severity: entry.severity().as_str(),
             unix_seconds: entry.timestamp_unix,
             nanos: entry.timestamp_nanos,
             raw: entry.timestamp_raw,
+            timestamp: None,
             message: entry.message,
         }
     }
@@ -491,3 +3511,257 @@ fn emit_and_exit(response: HelperResponse, exit_code: i32) -> ! {
     println!("{}", output);
     std::process::exit(exit_code);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{HelperCommand, HelperContext, HelperError, RestartLimits, execute_command};
+    use crate::jobs::Progress;
+    use runkit_core::testing::SandboxFixture;
+    use std::fs;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// Records each report and asks to stop once `limit` have been made.
+    struct CancelAfter {
+        limit: usize,
+        reports: Mutex<Vec<(usize, usize, String)>>,
+    }
+
+    impl CancelAfter {
+        fn new(limit: usize) -> Self {
+            CancelAfter {
+                limit,
+                reports: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn reports(&self) -> Vec<(usize, usize, String)> {
+            self.reports.lock().unwrap().clone()
+        }
+    }
+
+    impl Progress for CancelAfter {
+        fn report(&self, done: usize, total: usize, message: &str) {
+            self.reports
+                .lock()
+                .unwrap()
+                .push((done, total, message.to_string()));
+        }
+
+        fn cancelled(&self) -> bool {
+            self.reports.lock().unwrap().len() >= self.limit
+        }
+    }
+
+    #[test]
+    fn protected_services_are_not_disabled_through_runlevels_or_profiles() {
+        let sandbox = SandboxFixture::new().unwrap();
+        sandbox
+            .add_service("dbus", "#!/bin/sh\nexec dbus-daemon --nofork\n")
+            .unwrap();
+        sandbox.enable("dbus").unwrap();
+        let context = || HelperContext::with_backend(sandbox.manager().clone());
+        let link = sandbox.manager().enabled_dir().join("dbus");
+
+        let disable_in = HelperCommand::DisableIn {
+            runlevel: "default".to_string(),
+            service: "dbus".to_string(),
+        };
+        assert!(matches!(
+            execute_command(disable_in, context(), false),
+            Err(HelperError::Protected(service)) if service == "dbus"
+        ));
+
+        let profile = sandbox.root().join("profile.json");
+        fs::write(&profile, r#"{"services": {"dbus": {"enabled": false}}}"#).unwrap();
+        let restore = |dry_run| HelperCommand::RestoreProfile {
+            path: profile.clone(),
+            dry_run,
+        };
+        assert!(matches!(
+            execute_command(restore(false), context(), false),
+            Err(HelperError::Protected(service)) if service == "dbus"
+        ));
+        assert!(link.exists());
+        // A dry run changes nothing, and an override lets the restore through.
+        assert!(execute_command(restore(true), context(), false).is_ok());
+        assert!(execute_command(restore(false), context(), true).is_ok());
+        assert!(!link.exists());
+    }
+
+    #[test]
+    fn doctor_is_refused_through_a_backend() {
+        let sandbox = SandboxFixture::new().unwrap();
+        let context = HelperContext::with_backend(sandbox.manager().clone());
+        assert!(matches!(
+            execute_command(HelperCommand::Doctor, context, false),
+            Err(HelperError::Other(_))
+        ));
+    }
+
+    #[test]
+    fn cancelled_restart_batches_skip_the_remaining_services() {
+        let sandbox = SandboxFixture::new().unwrap();
+        let services: Vec<String> = ["sshd", "cronie", "nanoklogd"]
+            .iter()
+            .map(|name| {
+                sandbox
+                    .add_service(name, "#!/bin/sh\nexec sleep 1000\n")
+                    .unwrap();
+                sandbox.enable(name).unwrap();
+                name.to_string()
+            })
+            .collect();
+        let context = HelperContext::with_backend(sandbox.manager().clone()).with_restart_limits(
+            RestartLimits {
+                max_unconfirmed: 10,
+                stagger: Duration::ZERO,
+            },
+        );
+
+        let progress = CancelAfter::new(1);
+        context
+            .restart_services(&services, false, &progress)
+            .unwrap();
+        assert_eq!(progress.reports(), [(0, 3, "Restarting sshd".to_string())]);
+        let restarted: Vec<String> = sandbox
+            .sv_calls()
+            .unwrap()
+            .into_iter()
+            .filter(|call| call.starts_with("restart "))
+            .collect();
+        assert_eq!(restarted, ["restart sshd"]);
+
+        let progress = CancelAfter::new(usize::MAX);
+        context
+            .restart_services(&services, false, &progress)
+            .unwrap();
+        assert_eq!(progress.reports().len(), 3);
+    }
+
+    #[test]
+    fn cancelled_profile_restores_leave_the_remaining_changes() {
+        let sandbox = SandboxFixture::new().unwrap();
+        for name in ["cronie", "sshd"] {
+            sandbox
+                .add_service(name, "#!/bin/sh\nexec sleep 1000\n")
+                .unwrap();
+        }
+        let context = HelperContext::with_backend(sandbox.manager().clone());
+        let profile = r#"{"services": {"cronie": {"enabled": true}, "sshd": {"enabled": true}}}"#;
+
+        let progress = CancelAfter::new(2);
+        context.restore_profile(profile, false, &progress).unwrap();
+        assert_eq!(
+            progress.reports(),
+            [
+                (0, 2, "Restoring profile".to_string()),
+                (1, 2, "Restoring profile".to_string()),
+            ]
+        );
+        let enabled_dir = sandbox.manager().enabled_dir();
+        assert!(enabled_dir.join("cronie").exists());
+        assert!(!enabled_dir.join("sshd").exists());
+    }
+
+    #[test]
+    fn protected_services_are_not_signalled_down_or_left_unrestarted() {
+        let sandbox = SandboxFixture::new().unwrap();
+        sandbox
+            .add_service("dbus", "#!/bin/sh\nexec dbus-daemon --nofork\n")
+            .unwrap();
+        sandbox.enable("dbus").unwrap();
+        let context = || HelperContext::with_backend(sandbox.manager().clone());
+        let service = || "dbus".to_string();
+
+        for command in [
+            HelperCommand::NoRestart { service: service() },
+            HelperCommand::Interrupt { service: service() },
+            HelperCommand::Quit { service: service() },
+            HelperCommand::Term { service: service() },
+            HelperCommand::Kill { service: service() },
+        ] {
+            assert!(matches!(
+                execute_command(command, context(), false),
+                Err(HelperError::Protected(service)) if service == "dbus"
+            ));
+        }
+        assert!(sandbox.sv_calls().unwrap().is_empty());
+        assert!(
+            execute_command(HelperCommand::Term { service: service() }, context(), true).is_ok()
+        );
+    }
+
+    #[test]
+    fn protected_services_refuse_writes_to_their_supervision_files() {
+        let sandbox = SandboxFixture::new().unwrap();
+        let definition = sandbox
+            .add_service("dbus", "#!/bin/sh\nexec dbus-daemon --nofork\n")
+            .unwrap();
+        let context = || HelperContext::with_backend(sandbox.manager().clone());
+        let contents = sandbox.root().join("contents");
+        fs::write(&contents, "#!/bin/sh\nexit 0\n").unwrap();
+        let write = |file: &str| HelperCommand::WriteFile {
+            service: "dbus".to_string(),
+            file: file.to_string(),
+            from: Some(contents.clone()),
+            force: false,
+        };
+
+        for file in ["run", "./run", "down", "finish", "log/run", "log//run"] {
+            assert!(
+                matches!(
+                    execute_command(write(file), context(), false),
+                    Err(HelperError::Protected(service)) if service == "dbus"
+                ),
+                "{file}"
+            );
+        }
+        assert!(!definition.join("down").exists());
+        assert!(execute_command(write("conf"), context(), false).is_ok());
+        assert!(execute_command(write("down"), context(), true).is_ok());
+        assert!(definition.join("down").exists());
+    }
+
+    #[test]
+    fn batch_stops_skip_protected_services_unless_overridden() {
+        let sandbox = SandboxFixture::new().unwrap();
+        for name in ["dbus", "cronie"] {
+            sandbox
+                .add_service(name, "#!/bin/sh\nexec sleep 1000\n")
+                .unwrap();
+        }
+        let context = || HelperContext::with_backend(sandbox.manager().clone());
+        let batch = || HelperCommand::Batch {
+            action: "stop".to_string(),
+            services: vec!["dbus".to_string(), "cronie".to_string()],
+            confirm: false,
+        };
+        let stops = || -> Vec<String> {
+            sandbox
+                .sv_calls()
+                .unwrap()
+                .into_iter()
+                .filter(|call| call.starts_with("down "))
+                .collect()
+        };
+
+        execute_command(batch(), context(), false).unwrap();
+        assert_eq!(stops(), ["down cronie"]);
+        execute_command(batch(), context(), true).unwrap();
+        assert_eq!(stops(), ["down cronie", "down dbus", "down cronie"]);
+    }
+
+    #[test]
+    fn boot_reports_only_write_history_when_recording() {
+        let sandbox = SandboxFixture::new().unwrap();
+        let context = HelperContext::with_backend(sandbox.manager().clone());
+        let history = sandbox.root().join("boot-reports.json");
+
+        let report = context.boot_report(&history, false).unwrap();
+        assert_eq!(report.data.unwrap()["boots"], 1);
+        assert!(!history.exists());
+        context.boot_report(&history, true).unwrap();
+        assert!(history.exists());
+    }
+}
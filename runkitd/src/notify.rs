@@ -0,0 +1,460 @@
+//! Alerts about failing services and the channels they are delivered through.
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use runkit_core::{RestartPolicy, ServiceRuntimeState, format_duration};
+use serde_json::json;
+
+/// Where `runkitd --dbus-service` reads its notification routes from.
+pub const DEFAULT_NOTIFY_CONFIG: &str = "/etc/runkit/notify.conf";
+
+/// Holds the Unix time until which alerts are silenced. Under `/run` so a
+/// silence never outlives the boot it was set in.
+pub const DEFAULT_SILENCE_FILE: &str = "/run/runkit/alerts-silenced-until";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AlertSeverity {
+    /// A service that had failed is running again.
+    Info,
+    /// The service exited although runit was asked to keep it up.
+    Warning,
+    /// runit reports the service as failed.
+    Critical,
+}
+
+impl AlertSeverity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AlertSeverity::Info => "info",
+            AlertSeverity::Warning => "warning",
+            AlertSeverity::Critical => "critical",
+        }
+    }
+}
+
+impl FromStr for AlertSeverity {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "info" => Ok(AlertSeverity::Info),
+            "warning" => Ok(AlertSeverity::Warning),
+            "critical" => Ok(AlertSeverity::Critical),
+            other => Err(format!(
+                "unknown severity '{other}' (expected info, warning or critical)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AlertSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub service: String,
+    pub severity: AlertSeverity,
+    pub message: String,
+    pub timestamp: u64,
+}
+
+impl Alert {
+    fn new(service: &str, severity: AlertSeverity, message: String) -> Self {
+        Alert {
+            service: service.to_string(),
+            severity,
+            message,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        json!({
+            "service": self.service,
+            "severity": self.severity.as_str(),
+            "message": self.message,
+            "timestamp": self.timestamp,
+        })
+        .to_string()
+    }
+}
+
+/// A channel alerts can be delivered through.
+pub trait Notifier: Send + Sync {
+    /// Short label used in error messages, e.g. `webhook https://…`.
+    fn describe(&self) -> String;
+
+    fn send(&self, alert: &Alert) -> Result<(), String>;
+
+    /// Whether alerts only reach clients on the bus, which keep runkitd
+    /// running while they are connected anyway.
+    fn bus_only(&self) -> bool {
+        false
+    }
+}
+
+/// POSTs the alert as JSON with `curl`.
+struct WebhookNotifier {
+    url: String,
+}
+
+impl Notifier for WebhookNotifier {
+    fn describe(&self) -> String {
+        format!("webhook {}", self.url)
+    }
+
+    fn send(&self, alert: &Alert) -> Result<(), String> {
+        let mut command = Command::new("curl");
+        command.args([
+            "--silent",
+            "--show-error",
+            "--fail",
+            "--max-time",
+            "10",
+            "--header",
+            "Content-Type: application/json",
+            "--data-binary",
+            "@-",
+            &self.url,
+        ]);
+        run_with_input(command, &alert.to_json())
+    }
+}
+
+/// Publishes the alert as JSON with `mosquitto_pub`.
+struct MqttNotifier {
+    host: String,
+    port: u16,
+    topic: String,
+}
+
+impl Notifier for MqttNotifier {
+    fn describe(&self) -> String {
+        format!("mqtt {}:{}/{}", self.host, self.port, self.topic)
+    }
+
+    fn send(&self, alert: &Alert) -> Result<(), String> {
+        let mut command = Command::new("mosquitto_pub");
+        command
+            .args(["-h", &self.host, "-p", &self.port.to_string()])
+            .args(["-t", &self.topic, "-s"]);
+        run_with_input(command, &alert.to_json())
+    }
+}
+
+/// Runs an administrator-supplied program with the alert in its environment
+/// and as JSON on stdin.
+struct CommandNotifier {
+    program: String,
+}
+
+impl Notifier for CommandNotifier {
+    fn describe(&self) -> String {
+        format!("command {}", self.program)
+    }
+
+    fn send(&self, alert: &Alert) -> Result<(), String> {
+        let mut command = Command::new(&self.program);
+        command
+            .env("RUNKIT_SERVICE", &alert.service)
+            .env("RUNKIT_SEVERITY", alert.severity.as_str())
+            .env("RUNKIT_MESSAGE", &alert.message);
+        run_with_input(command, &alert.to_json())
+    }
+}
+
+fn run_with_input(mut command: Command, input: &str) -> Result<(), String> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| err.to_string())?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A program that ignores stdin closes it early; that is not a failure.
+        let _ = stdin.write_all(input.as_bytes());
+    }
+    let output = child.wait_with_output().map_err(|err| err.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+struct Route {
+    min_severity: AlertSeverity,
+    notifier: Arc<dyn Notifier>,
+}
+
+/// A temporary pause of every alert route, for maintenance windows. It lives
+/// in a file so that `runkitd silence-alerts` and the running daemon agree.
+#[derive(Debug, Clone)]
+pub struct AlertSilence {
+    path: PathBuf,
+}
+
+impl Default for AlertSilence {
+    fn default() -> Self {
+        AlertSilence::new(DEFAULT_SILENCE_FILE)
+    }
+}
+
+impl AlertSilence {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        AlertSilence { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Unix time the silence ends, or `None` when alerts are being delivered.
+    pub fn until(&self) -> Option<u64> {
+        let until: u64 = fs::read_to_string(&self.path).ok()?.trim().parse().ok()?;
+        (until > unix_now()).then_some(until)
+    }
+
+    /// Silence alerts for `duration` from now, replacing any earlier silence.
+    /// Returns when it ends.
+    pub fn silence_for(&self, duration: Duration) -> io::Result<u64> {
+        let until = unix_now().saturating_add(duration.as_secs());
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, format!("{until}\n"))?;
+        Ok(until)
+    }
+
+    /// End the silence early. Returns whether one was in effect.
+    pub fn lift(&self) -> io::Result<bool> {
+        let active = self.until().is_some();
+        match fs::remove_file(&self.path) {
+            Ok(()) => Ok(active),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// Notification routes, each delivering alerts at or above its own severity.
+#[derive(Default)]
+pub struct Notifications {
+    routes: Vec<Route>,
+    silence: AlertSilence,
+}
+
+impl Notifications {
+    /// Read the routes from `path`. Without a config file, warnings and
+    /// critical alerts go to the desktop.
+    ///
+    /// `desktop` broadcasts alerts to desktop clients; `[desktop]` sections are
+    /// skipped when it is `None`.
+    ///
+    /// The file holds one section per route, repeated as needed:
+    ///
+    /// ```text
+    /// [webhook]
+    /// url = https://hooks.example.com/runkit
+    /// severity = critical
+    /// ```
+    ///
+    /// Sections are `desktop`, `webhook` (`url`), `mqtt` (`host`, `port`, `topic`)
+    /// and `command` (`exec`); `severity` is the least severe alert a route
+    /// receives and defaults to `warning`.
+    pub fn load(path: &Path, desktop: Option<Arc<dyn Notifier>>) -> Result<Self, String> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => "[desktop]\n".to_string(),
+            Err(err) => return Err(format!("{}: {err}", path.display())),
+        };
+        Self::parse(&contents, desktop).map_err(|err| format!("{}: {err}", path.display()))
+    }
+
+    fn parse(contents: &str, desktop: Option<Arc<dyn Notifier>>) -> Result<Self, String> {
+        let mut sections: Vec<(usize, String, HashMap<String, String>)> = Vec::new();
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(kind) = line
+                .strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+            {
+                sections.push((index + 1, kind.trim().to_string(), HashMap::new()));
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("line {}: expected `key = value`", index + 1));
+            };
+            let Some((_, _, settings)) = sections.last_mut() else {
+                return Err(format!("line {}: setting outside a section", index + 1));
+            };
+            settings.insert(key.trim().to_string(), value.trim().to_string());
+        }
+
+        let mut notifications = Notifications::default();
+        for (line, kind, settings) in sections {
+            let required = |key: &str| {
+                settings
+                    .get(key)
+                    .cloned()
+                    .ok_or_else(|| format!("line {line}: [{kind}] needs `{key}`"))
+            };
+            let min_severity = match settings.get("severity") {
+                Some(value) => value.parse().map_err(|err| format!("line {line}: {err}"))?,
+                None => AlertSeverity::Warning,
+            };
+            let notifier: Arc<dyn Notifier> = match kind.as_str() {
+                "desktop" => match &desktop {
+                    Some(notifier) => Arc::clone(notifier),
+                    None => continue,
+                },
+                "webhook" => Arc::new(WebhookNotifier {
+                    url: required("url")?,
+                }),
+                "mqtt" => Arc::new(MqttNotifier {
+                    host: required("host")?,
+                    port: match settings.get("port") {
+                        Some(port) => port
+                            .parse()
+                            .map_err(|_| format!("line {line}: invalid port '{port}'"))?,
+                        None => 1883,
+                    },
+                    topic: required("topic")?,
+                }),
+                "command" => Arc::new(CommandNotifier {
+                    program: required("exec")?,
+                }),
+                other => return Err(format!("line {line}: unknown notifier [{other}]")),
+            };
+            notifications.routes.push(Route {
+                min_severity,
+                notifier,
+            });
+        }
+        Ok(notifications)
+    }
+
+    /// Whether a route delivers alerts beyond the bus, e.g. to a webhook, so
+    /// runkitd has to stay running to send them.
+    pub fn reaches_off_bus(&self) -> bool {
+        self.routes.iter().any(|route| !route.notifier.bus_only())
+    }
+
+    /// Deliver `alert` to every route that wants it. Slow channels do not hold
+    /// up the caller; failures are logged.
+    pub fn dispatch(self: &Arc<Self>, alert: Alert) {
+        if let Some(until) = self.silence.until() {
+            // Logged so what happened during a maintenance window can be traced.
+            eprintln!(
+                "runkitd: alerts are silenced for another {}; not sending: {}",
+                format_duration(Duration::from_secs(until.saturating_sub(unix_now()))),
+                alert.message
+            );
+            return;
+        }
+        let notifications = Arc::clone(self);
+        let spawned = thread::Builder::new()
+            .name("runkitd-notify".into())
+            .spawn(move || {
+                for route in &notifications.routes {
+                    if alert.severity < route.min_severity {
+                        continue;
+                    }
+                    if let Err(err) = route.notifier.send(&alert) {
+                        eprintln!(
+                            "runkitd: failed to notify {} about {}: {err}",
+                            route.notifier.describe(),
+                            alert.service
+                        );
+                    }
+                }
+            });
+        if let Err(err) = spawned {
+            eprintln!("runkitd: failed to start notification thread: {err}");
+        }
+    }
+}
+
+/// Turns successive states of each service into alerts, so each failure is
+/// reported once rather than on every status rewrite.
+#[derive(Debug, Default)]
+pub struct AlertTracker {
+    alerted: HashMap<String, AlertSeverity>,
+}
+
+impl AlertTracker {
+    /// Drop what is known about `service`, e.g. while it is in maintenance, so
+    /// neither its downtime nor its return raises an alert.
+    pub fn forget(&mut self, service: &str) {
+        self.alerted.remove(service);
+    }
+
+    pub fn observe(
+        &mut self,
+        service: &str,
+        state: &ServiceRuntimeState,
+        restart_policy: RestartPolicy,
+    ) -> Option<Alert> {
+        let current = match state {
+            ServiceRuntimeState::Failed { exit_code, .. } => Some((
+                AlertSeverity::Critical,
+                format!("{service} failed with exit code {exit_code}"),
+            )),
+            // `want down` or a no-restart marker mean the stop was requested.
+            ServiceRuntimeState::Down { .. } if restart_policy == RestartPolicy::Always => Some((
+                AlertSeverity::Warning,
+                format!("{service} exited unexpectedly; runit is restarting it"),
+            )),
+            _ => None,
+        };
+
+        match current {
+            Some((severity, message)) => {
+                let previous = self.alerted.insert(service.to_string(), severity);
+                (previous.is_none_or(|previous| previous < severity))
+                    .then(|| Alert::new(service, severity, message))
+            }
+            None => {
+                let previous = self.alerted.remove(service);
+                match (previous, state) {
+                    (Some(AlertSeverity::Critical), ServiceRuntimeState::Running { .. }) => {
+                        Some(Alert::new(
+                            service,
+                            AlertSeverity::Info,
+                            format!("{service} is running again"),
+                        ))
+                    }
+                    _ => None,
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! Reading and writing profiles as JSON or TOML files.
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use runkit_core::Profile;
+
+use crate::HelperError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileFormat {
+    Json,
+    Toml,
+}
+
+impl ProfileFormat {
+    /// TOML for a `.toml` file, JSON for anything else.
+    pub fn for_path(path: &Path) -> Self {
+        if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("toml"))
+        {
+            ProfileFormat::Toml
+        } else {
+            ProfileFormat::Json
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProfileFormat::Json => "json",
+            ProfileFormat::Toml => "toml",
+        }
+    }
+}
+
+impl fmt::Display for ProfileFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ProfileFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "json" => Ok(ProfileFormat::Json),
+            "toml" => Ok(ProfileFormat::Toml),
+            _ => Err(format!(
+                "unknown profile format {value:?} (expected json or toml)"
+            )),
+        }
+    }
+}
+
+pub fn render(profile: &Profile, format: ProfileFormat) -> Result<String, HelperError> {
+    match format {
+        ProfileFormat::Json => serde_json::to_string_pretty(profile)
+            .map(|json| json + "\n")
+            .map_err(|err| HelperError::Other(err.to_string())),
+        ProfileFormat::Toml => Ok(render_toml(profile)),
+    }
+}
+
+/// One `[services.NAME]` table per service. Written by hand because the
+/// `toml` crate is only built with its parser here.
+fn render_toml(profile: &Profile) -> String {
+    let mut toml = String::new();
+    if let Some(hostname) = &profile.hostname {
+        toml.push_str(&format!("hostname = {}\n", quote(hostname)));
+    }
+    for (name, entry) in &profile.services {
+        let key = if !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            name.clone()
+        } else {
+            quote(name)
+        };
+        toml.push_str(&format!(
+            "\n[services.{key}]\nenabled = {}\ndown_file = {}\n",
+            entry.enabled, entry.down_file
+        ));
+    }
+    toml
+}
+
+/// A TOML basic string. JSON's escapes are a subset of TOML's.
+fn quote(value: &str) -> String {
+    serde_json::Value::from(value).to_string()
+}
+
+/// Parse a profile in either format, telling them apart by the opening brace
+/// of a JSON object. Also accepts the full `runkitd save-profile` response.
+pub fn parse(contents: &str) -> Result<Profile, HelperError> {
+    let invalid = |err: String| HelperError::Other(format!("not a profile: {err}"));
+    if !contents.trim_start().starts_with('{') {
+        return toml::from_str(contents).map_err(|err| invalid(err.to_string()));
+    }
+    let mut value: serde_json::Value =
+        serde_json::from_str(contents).map_err(|err| invalid(err.to_string()))?;
+    if let Some(data) = value
+        .get_mut("data")
+        .and_then(|data| data.get_mut("profile"))
+    {
+        value = data.take();
+    }
+    serde_json::from_value(value).map_err(|err| invalid(err.to_string()))
+}
@@ -0,0 +1,81 @@
+//! `runkitd --profile-refresh`: repeat what a GUI refresh asks runkitd for
+//! with timing spans on, and report where the time went.
+use crate::{CommandOutcome, HelperContext, HelperError, HelperResponse};
+use runkit_core::{LogFilter, ServiceQuery, set_profiling, span, take_profile};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+/// Log lines tailed per service, as the GUI's activity view does.
+const PROFILE_LOG_LINES: usize = 50;
+
+/// List every service and tail each one's log, encoding the replies as the
+/// D-Bus service would, `iterations` times. Data is `{iterations, services,
+/// wall_ms, kinds, spans}`, where `kinds` sums the spans by kind (`spawn`,
+/// `io`, `serialize`) and `spans` has each span's count and time.
+pub fn profile_refresh(
+    context: &HelperContext,
+    iterations: u32,
+) -> Result<CommandOutcome, HelperError> {
+    let iterations = iterations.max(1);
+    take_profile();
+    set_profiling(true);
+    let started = Instant::now();
+    let mut count = 0;
+    for _ in 0..iterations {
+        let _span = span("refresh:total");
+        let listing = context.list(&ServiceQuery::default())?;
+        let names: Vec<String> = listing
+            .data
+            .as_ref()
+            .and_then(|data| data.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|service| service["name"].as_str().map(str::to_string))
+            .collect();
+        encode("serialize:services", Ok(listing));
+        for name in &names {
+            let logs = context.logs(name, PROFILE_LOG_LINES, &LogFilter::default(), None);
+            encode("serialize:logs", logs);
+        }
+        count = names.len();
+    }
+    let wall_ms = started.elapsed().as_secs_f64() * 1000.0;
+    set_profiling(false);
+
+    let spans = take_profile();
+    let mut kinds: BTreeMap<&str, f64> = BTreeMap::new();
+    for span in &spans {
+        if matches!(span.kind.as_str(), "spawn" | "io" | "serialize") {
+            *kinds.entry(span.kind.as_str()).or_default() += span.total_ms;
+        }
+    }
+    let slowest = spans
+        .iter()
+        .find(|span| span.kind != "refresh" && span.kind != "list")
+        .map(|span| format!("; most time in {} ({:.1} ms)", span.name, span.total_ms))
+        .unwrap_or_default();
+    Ok(CommandOutcome::with(
+        Some(format!(
+            "{iterations} refreshes of {count} services took {wall_ms:.1} ms{slowest}"
+        )),
+        Some(json!({
+            "iterations": iterations,
+            "services": count,
+            "wall_ms": wall_ms,
+            "kinds": kinds,
+            "spans": spans,
+        })),
+    ))
+}
+
+/// Encode a reply the way the D-Bus service does, timed under `name`. A
+/// service without a logger fails to tail, which is part of the picture.
+fn encode(name: &'static str, result: Result<CommandOutcome, HelperError>) {
+    let _span = span(name);
+    let response = match result {
+        Ok(outcome) => HelperResponse::ok_with(outcome),
+        Err(err) => HelperResponse::error(err.to_string()),
+    };
+    let _ = serde_json::to_string(&response);
+}
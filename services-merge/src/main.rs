@@ -5,7 +5,11 @@ use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
 
-type DescriptionMap = BTreeMap<String, Option<String>>;
+use serde_json::Value;
+
+/// Service name to description: a string, a map from locale to string, or
+/// null.
+type DescriptionMap = BTreeMap<String, Value>;
 
 fn main() {
     if let Err(err) = run() {
@@ -95,9 +99,16 @@ fn load_map(path: &Path) -> Result<DescriptionMap, Box<dyn Error>> {
     Ok(map)
 }
 
+/// Template entries replace the target's, except that two locale maps are
+/// merged locale by locale, keeping translations only the target has.
 fn overlay(target: &mut DescriptionMap, template: DescriptionMap) {
     for (key, value) in template {
-        target.insert(key, value);
+        match (target.get_mut(&key), value) {
+            (Some(Value::Object(existing)), Value::Object(texts)) => existing.extend(texts),
+            (_, value) => {
+                target.insert(key, value);
+            }
+        }
     }
 }
 
@@ -26,6 +26,13 @@ DBUS_SYSTEM_CONFIG_TARGET="/etc/dbus-1/system.d/tech.geektoshi.Runkit1.conf"
 POLKIT_POLICY_SOURCE="assets/polkit-1/actions/tech.geektoshi.Runkit.policy"
 POLKIT_POLICY_TARGET="/usr/share/polkit-1/actions/tech.geektoshi.Runkit.policy"
 SERVICE_DESCRIPTIONS_TEMPLATE="assets/config/services.json"
+SERVICE_DESCRIPTIONS_TARGET="/usr/share/runkit/services.json"
+SERVICE_CATEGORIES_SOURCE="assets/config/categories.json"
+SERVICE_CATEGORIES_TARGET="/usr/share/runkit/categories.json"
+NOTIFY_CONFIG_SOURCE="assets/config/notify.conf"
+NOTIFY_CONFIG_TARGET="/etc/runkit/notify.conf"
+BOOT_HOOK_SOURCE="assets/runit/core-services/90-runkit.sh"
+BOOT_HOOK_TARGET="/etc/runit/core-services/90-runkit.sh"
 
 require_sudo() {
     sudo -v
@@ -116,6 +123,19 @@ install_binaries() {
     done
 }
 
+install_system_descriptions() {
+    if [[ -f "$SERVICE_DESCRIPTIONS_TEMPLATE" ]]; then
+        echo "Installing service descriptions '$SERVICE_DESCRIPTIONS_TEMPLATE' -> '$SERVICE_DESCRIPTIONS_TARGET'..."
+        sudo install -D -m644 "$SERVICE_DESCRIPTIONS_TEMPLATE" "$SERVICE_DESCRIPTIONS_TARGET"
+    else
+        echo "Note: service description template not found at ${SERVICE_DESCRIPTIONS_TEMPLATE}; skipping."
+    fi
+    if [[ -f "$SERVICE_CATEGORIES_SOURCE" ]]; then
+        echo "Installing service categories '$SERVICE_CATEGORIES_SOURCE' -> '$SERVICE_CATEGORIES_TARGET'..."
+        sudo install -D -m644 "$SERVICE_CATEGORIES_SOURCE" "$SERVICE_CATEGORIES_TARGET"
+    fi
+}
+
 install_service_descriptions() {
     local template="$SERVICE_DESCRIPTIONS_TEMPLATE"
     if [[ ! -f "$template" ]]; then
@@ -238,6 +258,26 @@ install_polkit_policy() {
     fi
 }
 
+install_notify_config() {
+    if [[ -f "$NOTIFY_CONFIG_TARGET" ]]; then
+        echo "Keeping existing alert config '$NOTIFY_CONFIG_TARGET'."
+    elif [[ -f "$NOTIFY_CONFIG_SOURCE" ]]; then
+        echo "Installing alert config '$NOTIFY_CONFIG_SOURCE' -> '$NOTIFY_CONFIG_TARGET'..."
+        sudo install -D -m644 "$NOTIFY_CONFIG_SOURCE" "$NOTIFY_CONFIG_TARGET"
+    else
+        echo "Note: alert config not found at ${NOTIFY_CONFIG_SOURCE}; skipping."
+    fi
+}
+
+install_boot_hook() {
+    if [[ -f "$BOOT_HOOK_SOURCE" ]]; then
+        echo "Installing boot hook '$BOOT_HOOK_SOURCE' -> '$BOOT_HOOK_TARGET'..."
+        sudo install -D -m644 "$BOOT_HOOK_SOURCE" "$BOOT_HOOK_TARGET"
+    else
+        echo "Note: boot hook not found at ${BOOT_HOOK_SOURCE}; skipping."
+    fi
+}
+
 uninstall_icons() {
     local removed_any=false
 
@@ -299,6 +339,24 @@ uninstall_polkit_policy() {
     fi
 }
 
+uninstall_system_descriptions() {
+    if [[ -f "$SERVICE_DESCRIPTIONS_TARGET" ]]; then
+        echo "Removing service descriptions '$SERVICE_DESCRIPTIONS_TARGET'..."
+        sudo rm -f "$SERVICE_DESCRIPTIONS_TARGET"
+    fi
+    if [[ -f "$SERVICE_CATEGORIES_TARGET" ]]; then
+        echo "Removing service categories '$SERVICE_CATEGORIES_TARGET'..."
+        sudo rm -f "$SERVICE_CATEGORIES_TARGET"
+    fi
+}
+
+uninstall_boot_hook() {
+    if [[ -f "$BOOT_HOOK_TARGET" ]]; then
+        echo "Removing boot hook '$BOOT_HOOK_TARGET'..."
+        sudo rm -f "$BOOT_HOOK_TARGET"
+    fi
+}
+
 refresh_desktop_database() {
     if command -v update-desktop-database >/dev/null 2>&1; then
         local dir
@@ -334,6 +392,9 @@ case "$ACTION" in
     install_desktop_entry
     install_dbus_service
     install_polkit_policy
+    install_notify_config
+    install_boot_hook
+    install_system_descriptions
     install_service_descriptions
         ;;
     uninstall)
@@ -343,6 +404,8 @@ case "$ACTION" in
         uninstall_desktop_entry
         uninstall_dbus_service
         uninstall_polkit_policy
+        uninstall_boot_hook
+        uninstall_system_descriptions
         ;;
     *)
         echo "Usage: $SCRIPT_NAME [install|uninstall]" >&2